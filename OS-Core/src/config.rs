@@ -15,3 +15,7 @@ pub const SV39_VPN_WIDTH: usize = SV39_VA_WIDTH - PAGE_SIZE_BITS;
 
 pub const TRAMPOLINE: usize = usize::MAX - PAGE_SIZE + 1;
 pub const TRAP_CONTEXT_BASE: usize = TRAMPOLINE - PAGE_SIZE;
+
+/// Shared memory segments are attached between SHM_BASE and SHM_TOP, far above the user stacks.
+pub const SHM_BASE: usize = 0x10_0000_0000;
+pub const SHM_TOP: usize = 0x20_0000_0000;
@@ -1,6 +1,12 @@
-use crate::sbi_services::{console_getchar, console_putchar};
+use crate::{
+    sbi_services::{console_getchar, console_putchar},
+    sync::SpinNoIrqLock,
+};
 use core::fmt::{self, Write};
 
+/// Keep the output of different harts from interleaving.
+static PRINT_LOCK: SpinNoIrqLock<()> = SpinNoIrqLock::new(());
+
 struct Stdout;
 
 impl Write for Stdout {
@@ -13,6 +19,7 @@ impl Write for Stdout {
 }
 
 pub fn print(args: fmt::Arguments) {
+    let _guard = PRINT_LOCK.lock();
     Stdout.write_fmt(args).unwrap();
 }
 
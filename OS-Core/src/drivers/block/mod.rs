@@ -1,5 +1,5 @@
 mod virtio_blk;
-pub use virtio_blk::VitrIOBlock;
+pub use virtio_blk::{VirtIOHal, VitrIOBlock};
 
 use alloc::sync::Arc;
 use easy_fs::block_dev::BlockDevice;
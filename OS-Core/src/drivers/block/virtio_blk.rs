@@ -10,11 +10,11 @@ use crate::{
         memory_set::kernel_token,
         page_table::PageTable,
     },
-    sync::UPSafeCell,
+    sync::SpinNoIrqLock,
 };
 
 lazy_static! {
-    static ref QUEUE_FRAMES: UPSafeCell<Vec<FrameTracker>> = unsafe { UPSafeCell::new(Vec::new()) };
+    static ref QUEUE_FRAMES: SpinNoIrqLock<Vec<FrameTracker>> = SpinNoIrqLock::new(Vec::new());
 }
 pub struct VirtIOHal;
 impl Hal for VirtIOHal {
@@ -25,7 +25,7 @@ impl Hal for VirtIOHal {
         let base_page = frame_alloc().unwrap();
         let base_ppn = base_page.ppn.0;
         let base_addr = PhysAddr::from(base_page.ppn).0;
-        QUEUE_FRAMES.exclusive_access().push(base_page);
+        QUEUE_FRAMES.lock().push(base_page);
         for i in 1..pages {
             let page = frame_alloc().unwrap();
             assert_eq!(
@@ -33,7 +33,7 @@ impl Hal for VirtIOHal {
                 base_ppn + i,
                 "VirtQueue requires contiguous physical memory."
             );
-            QUEUE_FRAMES.exclusive_access().push(page);
+            QUEUE_FRAMES.lock().push(page);
         }
         base_addr
     }
@@ -61,11 +61,11 @@ impl Hal for VirtIOHal {
 }
 
 const VIRTIO_0: usize = 0x10001000;
-pub struct VitrIOBlock(UPSafeCell<VirtIOBlk<'static, VirtIOHal>>);
+pub struct VitrIOBlock(SpinNoIrqLock<VirtIOBlk<'static, VirtIOHal>>);
 impl VitrIOBlock {
     pub fn new() -> Self {
         unsafe {
-            Self(UPSafeCell::new(
+            Self(SpinNoIrqLock::new(
                 VirtIOBlk::<VirtIOHal>::new(&mut *(VIRTIO_0 as *mut VirtIOHeader)).unwrap(),
             ))
         }
@@ -75,14 +75,14 @@ impl VitrIOBlock {
 impl BlockDevice for VitrIOBlock {
     fn read_block(&self, block_id: usize, buf: &mut [u8]) {
         self.0
-            .exclusive_access()
+            .lock()
             .read_block(block_id, buf)
             .expect("Error when reading VirtIOBlk.");
     }
 
     fn write_block(&self, block_id: usize, buf: &[u8]) {
         self.0
-            .exclusive_access()
+            .lock()
             .write_block(block_id, buf)
             .expect("Error when writing VirtIOBlk.");
     }
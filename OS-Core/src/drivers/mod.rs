@@ -1 +1,2 @@
-pub mod block;
\ No newline at end of file
+pub mod block;
+pub mod net;
@@ -0,0 +1,30 @@
+mod virtio_net;
+pub use virtio_net::VirtIONetDevice;
+
+use alloc::sync::Arc;
+use lazy_static::lazy_static;
+
+use crate::platfrom::NetDeviceImpl;
+
+/// The max length of an ethernet frame without the frame check sequence.
+pub const MAX_FRAME_LEN: usize = 1514;
+/// The mac address QEMU gives to the first network card, used if there is no card.
+pub const DEFAULT_MAC: [u8; 6] = [0x52, 0x54, 0x00, 0x12, 0x34, 0x56];
+
+/// A network card sending and receiving ethernet frames, it never waits.
+pub trait NetDevice: Send + Sync {
+    fn mac(&self) -> [u8; 6];
+    /// Send a frame, it is dropped if the device can not send now.
+    fn send(&self, frame: &[u8]);
+    /// Take a received frame.
+    /// # Return
+    /// * The length of the frame.
+    /// * None - no frame is received.
+    fn recv(&self, buf: &mut [u8]) -> Option<usize>;
+}
+
+lazy_static! {
+    /// None if the machine has no network card, only the loopback network works then.
+    pub static ref NET_DEVICE: Option<Arc<dyn NetDevice>> =
+        NetDeviceImpl::probe().map(|device| Arc::new(device) as Arc<dyn NetDevice>);
+}
@@ -0,0 +1,50 @@
+use virtio_drivers::{VirtIOHeader, VirtIONet};
+
+use crate::{drivers::block::VirtIOHal, sync::SpinNoIrqLock};
+
+use super::NetDevice;
+
+const VIRTIO_1: usize = 0x10002000;
+/// The device id register of virtio-mmio, it is 0 if no device is attached.
+const DEVICE_ID: usize = 0x008;
+const DEVICE_NET: u32 = 1;
+
+pub struct VirtIONetDevice(SpinNoIrqLock<VirtIONet<'static, VirtIOHal>>);
+
+impl VirtIONetDevice {
+    /// Initialize the network card.
+    /// # Return
+    /// * None - there is no network card.
+    pub fn probe() -> Option<Self> {
+        let device_id = unsafe { ((VIRTIO_1 + DEVICE_ID) as *const u32).read_volatile() };
+        if device_id != DEVICE_NET {
+            return None;
+        }
+        let net = unsafe { VirtIONet::<VirtIOHal>::new(&mut *(VIRTIO_1 as *mut VirtIOHeader)) };
+        net.ok().map(|net| Self(SpinNoIrqLock::new(net)))
+    }
+}
+
+impl NetDevice for VirtIONetDevice {
+    fn mac(&self) -> [u8; 6] {
+        self.0.lock().mac()
+    }
+
+    fn send(&self, frame: &[u8]) {
+        let mut net = self.0.lock();
+        if net.can_send() {
+            // A frame which can not be sent is dropped.
+            let _ = net.send(frame);
+        }
+    }
+
+    fn recv(&self, buf: &mut [u8]) -> Option<usize> {
+        let mut net = self.0.lock();
+        // Interrupts are not enabled, the device is polled.
+        net.ack_interrupt();
+        if !net.can_recv() {
+            return None;
+        }
+        net.recv(buf).ok()
+    }
+}
@@ -0,0 +1,314 @@
+//! Device file system, mounted on /dev.
+//! Every device is registered with a name and an open function, opening /dev/<name>
+//! calls the function to get a File of the device instead of a regular file.
+//! * /dev/null - reads nothing, writes are discarded.
+//! * /dev/zero - reads zero bytes, writes are discarded.
+//! * /dev/random, /dev/urandom - reads pseudo random bytes.
+//! * /dev/console, /dev/tty - the terminal.
+//! * /dev/vda - the raw block device which easy-fs is on.
+use alloc::{string::String, sync::Arc, vec::Vec};
+use easy_fs::{block_cache::get_block_cache, layout::SuperBlock, BLOCK_SIZE};
+use lazy_static::lazy_static;
+
+use crate::{
+    drivers::block::BLOCK_DEVICE, mm::page_table::UserBuffer, sync::SpinNoIrqLock, timer::get_time,
+};
+
+use super::{
+    stdio::Console,
+    vfs::{NodeType, Stat, VfsNode},
+    File,
+};
+
+/// Device id of devfs in struct stat.
+const DEVFS_DEV: u64 = 4;
+
+/// A device which can be opened by path.
+struct Device {
+    name: &'static str,
+    /// CharDevice or BlockDevice.
+    node_type: NodeType,
+    /// Device number in struct stat, major << 8 | minor.
+    rdev: u64,
+    open: fn() -> Arc<dyn File + Send + Sync>,
+}
+
+lazy_static! {
+    static ref DEVICES: SpinNoIrqLock<Vec<Device>> = SpinNoIrqLock::new(Vec::new());
+}
+
+/// Add a device to /dev.
+/// # Parameter
+/// * 'node_type' - NodeType::CharDevice or NodeType::BlockDevice.
+/// * 'open' - create a File of the device, it is called every time the device is opened.
+pub fn register_device(
+    name: &'static str,
+    node_type: NodeType,
+    major: u64,
+    minor: u64,
+    open: fn() -> Arc<dyn File + Send + Sync>,
+) {
+    let mut devices = DEVICES.lock();
+    assert!(
+        devices.iter().all(|device| device.name != name),
+        "Device {} has been registered.",
+        name
+    );
+    devices.push(Device {
+        name,
+        node_type,
+        rdev: major << 8 | minor,
+        open,
+    });
+}
+
+/// Register the built-in devices.
+pub fn init() {
+    register_device("null", NodeType::CharDevice, 1, 3, || Arc::new(Null));
+    register_device("zero", NodeType::CharDevice, 1, 5, || Arc::new(Zero));
+    register_device("random", NodeType::CharDevice, 1, 8, || Arc::new(Random));
+    register_device("urandom", NodeType::CharDevice, 1, 9, || Arc::new(Random));
+    register_device("tty", NodeType::CharDevice, 5, 0, || Arc::new(Console));
+    register_device("console", NodeType::CharDevice, 5, 1, || Arc::new(Console));
+    register_device("vda", NodeType::BlockDevice, 254, 0, || {
+        Arc::new(RawBlock::new())
+    });
+}
+
+/// The root directory of devfs.
+pub fn root() -> Arc<dyn VfsNode> {
+    Arc::new(DevNode { index: None })
+}
+
+/// The root directory, or a device by its index in DEVICES.
+struct DevNode {
+    index: Option<usize>,
+}
+
+impl VfsNode for DevNode {
+    fn node_type(&self) -> NodeType {
+        match self.index {
+            Some(index) => DEVICES.lock()[index].node_type,
+            None => NodeType::Dir,
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Option<Arc<dyn VfsNode>> {
+        if self.index.is_some() {
+            return None;
+        }
+        let index = DEVICES
+            .lock()
+            .iter()
+            .position(|device| device.name == name)?;
+        Some(Arc::new(DevNode { index: Some(index) }))
+    }
+
+    fn readdir(&self) -> Vec<(String, NodeType)> {
+        if self.index.is_some() {
+            return Vec::new();
+        }
+        DEVICES
+            .lock()
+            .iter()
+            .map(|device| (String::from(device.name), device.node_type))
+            .collect()
+    }
+
+    fn open(&self) -> Option<Arc<dyn File + Send + Sync>> {
+        let open = DEVICES.lock()[self.index?].open;
+        Some(open())
+    }
+
+    fn stat(&self) -> Stat {
+        match self.index {
+            Some(index) => {
+                let devices = DEVICES.lock();
+                let mut stat = Stat::new(DEVFS_DEV, index as u64 + 2, devices[index].node_type, 0);
+                stat.rdev = devices[index].rdev;
+                stat
+            }
+            None => Stat::new(DEVFS_DEV, 1, NodeType::Dir, 0),
+        }
+    }
+}
+
+/// Fill the user buffer with bytes from f.
+fn fill(mut buf: UserBuffer, mut f: impl FnMut() -> u8) -> isize {
+    for slice in buf.buffers.iter_mut() {
+        slice.iter_mut().for_each(|byte| *byte = f());
+    }
+    buf.len() as isize
+}
+
+struct Null;
+
+impl File for Null {
+    fn readable(&self) -> bool {
+        true
+    }
+
+    fn writable(&self) -> bool {
+        true
+    }
+
+    #[allow(unused)]
+    fn read(&self, buf: UserBuffer) -> isize {
+        0
+    }
+
+    fn write(&self, buf: UserBuffer) -> isize {
+        buf.len() as isize
+    }
+
+    fn name(&self) -> String {
+        String::from("/dev/null")
+    }
+}
+
+struct Zero;
+
+impl File for Zero {
+    fn readable(&self) -> bool {
+        true
+    }
+
+    fn writable(&self) -> bool {
+        true
+    }
+
+    fn read(&self, buf: UserBuffer) -> isize {
+        fill(buf, || 0)
+    }
+
+    fn write(&self, buf: UserBuffer) -> isize {
+        buf.len() as isize
+    }
+
+    fn name(&self) -> String {
+        String::from("/dev/zero")
+    }
+}
+
+lazy_static! {
+    /// State of the xorshift64* generator, seeded by the boot time.
+    static ref RANDOM_STATE: SpinNoIrqLock<u64> = SpinNoIrqLock::new(get_time() as u64 | 1);
+}
+
+/// Pseudo random bytes, not suitable for cryptography.
+struct Random;
+
+impl File for Random {
+    fn readable(&self) -> bool {
+        true
+    }
+
+    fn writable(&self) -> bool {
+        true
+    }
+
+    fn read(&self, buf: UserBuffer) -> isize {
+        let mut state = RANDOM_STATE.lock();
+        fill(buf, || {
+            *state ^= *state >> 12;
+            *state ^= *state << 25;
+            *state ^= *state >> 27;
+            (state.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 56) as u8
+        })
+    }
+
+    /// Written bytes are mixed into the state.
+    fn write(&self, buf: UserBuffer) -> isize {
+        let mut state = RANDOM_STATE.lock();
+        for slice in buf.buffers.iter() {
+            for byte in slice.iter() {
+                *state = (state.rotate_left(8) ^ *byte as u64) | 1;
+            }
+        }
+        buf.len() as isize
+    }
+
+    fn name(&self) -> String {
+        String::from("/dev/random")
+    }
+}
+
+/// The block device as a file, it goes through the block cache of easy-fs,
+/// so it always sees the same data as the file system.
+struct RawBlock {
+    /// Size of the device in bytes, read from the super block of easy-fs.
+    size: usize,
+    offset: SpinNoIrqLock<usize>,
+}
+
+impl RawBlock {
+    fn new() -> Self {
+        let total_blocks = get_block_cache(0, BLOCK_DEVICE.clone())
+            .lock()
+            .read(0, |super_block: &SuperBlock| super_block.total_blocks);
+        Self {
+            size: total_blocks as usize * BLOCK_SIZE,
+            offset: SpinNoIrqLock::new(0),
+        }
+    }
+}
+
+impl File for RawBlock {
+    fn readable(&self) -> bool {
+        true
+    }
+
+    fn writable(&self) -> bool {
+        true
+    }
+
+    fn read(&self, mut buf: UserBuffer) -> isize {
+        let mut offset = self.offset.lock();
+        let start = *offset;
+        for slice in buf.buffers.iter_mut() {
+            let mut done = 0;
+            while done < slice.len() && *offset < self.size {
+                let block_offset = *offset % BLOCK_SIZE;
+                let len = (BLOCK_SIZE - block_offset)
+                    .min(slice.len() - done)
+                    .min(self.size - *offset);
+                get_block_cache(*offset / BLOCK_SIZE, BLOCK_DEVICE.clone())
+                    .lock()
+                    .read(0, |block: &[u8; BLOCK_SIZE]| {
+                        slice[done..done + len]
+                            .copy_from_slice(&block[block_offset..block_offset + len])
+                    });
+                done += len;
+                *offset += len;
+            }
+        }
+        (*offset - start) as isize
+    }
+
+    fn write(&self, buf: UserBuffer) -> isize {
+        let mut offset = self.offset.lock();
+        let start = *offset;
+        for slice in buf.buffers.iter() {
+            let mut done = 0;
+            while done < slice.len() && *offset < self.size {
+                let block_offset = *offset % BLOCK_SIZE;
+                let len = (BLOCK_SIZE - block_offset)
+                    .min(slice.len() - done)
+                    .min(self.size - *offset);
+                get_block_cache(*offset / BLOCK_SIZE, BLOCK_DEVICE.clone())
+                    .lock()
+                    .modify(0, |block: &mut [u8; BLOCK_SIZE]| {
+                        block[block_offset..block_offset + len]
+                            .copy_from_slice(&slice[done..done + len])
+                    });
+                done += len;
+                *offset += len;
+            }
+        }
+        (*offset - start) as isize
+    }
+
+    fn name(&self) -> String {
+        String::from("/dev/vda")
+    }
+}
@@ -0,0 +1,328 @@
+//! Epoll, scalable event notification.
+//! Every file added to an epoll instance gets a watcher, which is notified by the wait queues
+//! of the file when its readiness may change. The watcher puts the file to the ready list,
+//! so epoll_wait only checks the files in the ready list instead of all files.
+//! * Level triggered - a ready file stays in the ready list after it is reported,
+//!   and it is removed when it is found not ready.
+//! * Edge triggered - a file is reported once for every notification.
+//!
+//! The readiness of files is checked without the lock of the epoll instance,
+//! because the watchers lock it while the files are locked.
+use alloc::{
+    collections::{BTreeMap, VecDeque},
+    string::String,
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use core::any::Any;
+
+use crate::{
+    mm::page_table::UserBuffer,
+    sync::{
+        wait_queue::{wait_event, WaitQueue, Watcher},
+        SpinNoIrqLock,
+    },
+    syscall::errno::{EEXIST, ENOENT, EPERM},
+    timer::get_time_ms,
+};
+
+use super::{poll::PollEvents, File};
+
+bitflags! {
+    /// Events and flags of epoll, the same values as linux.
+    #[derive(Clone, Copy, PartialEq, Eq)]
+    pub struct EpollEvents: u32 {
+        const EPOLLIN = 0x1;
+        const EPOLLPRI = 0x2;
+        const EPOLLOUT = 0x4;
+        const EPOLLERR = 0x8;
+        const EPOLLHUP = 0x10;
+        /// Disable the file after it is reported once, until it is modified by EPOLL_CTL_MOD.
+        const EPOLLONESHOT = 1 << 30;
+        /// Edge triggered.
+        const EPOLLET = 1 << 31;
+    }
+}
+
+/// An event of epoll_ctl and epoll_wait, the same layout as linux struct epoll_event on riscv64.
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct EpollEvent {
+    pub events: u32,
+    /// Returned to the user as it is.
+    pub data: u64,
+}
+
+/// A file in the interest list.
+struct Interest {
+    /// The epoll instance does not keep the file open, the interest goes away with the file.
+    file: Weak<dyn File + Send + Sync>,
+    events: EpollEvents,
+    data: u64,
+    /// Registered to the file, it is dropped with the interest.
+    watcher: Arc<dyn Watcher>,
+    /// False after a one shot event is reported.
+    enabled: bool,
+    /// The fd is in the ready list.
+    queued: bool,
+}
+
+impl Interest {
+    /// The file is still open.
+    fn alive(&self) -> bool {
+        self.file.strong_count() > 0
+    }
+}
+
+struct EpollInner {
+    /// Interests by fd.
+    interests: BTreeMap<usize, Interest>,
+    /// Fds which may be ready.
+    ready: VecDeque<usize>,
+    /// Threads in epoll_wait.
+    waiters: WaitQueue,
+}
+
+impl EpollInner {
+    /// Put the fd to the ready list if it is not there.
+    fn enqueue(&mut self, fd: usize) {
+        if let Some(interest) = self.interests.get_mut(&fd) {
+            if interest.enabled && !interest.queued {
+                interest.queued = true;
+                self.ready.push_back(fd);
+            }
+        }
+    }
+}
+
+/// Notified by the file of the fd.
+struct EpollWatcher {
+    epoll: Weak<SpinNoIrqLock<EpollInner>>,
+    fd: usize,
+}
+
+impl Watcher for EpollWatcher {
+    fn notify(&self) {
+        if let Some(epoll) = self.epoll.upgrade() {
+            let mut inner = epoll.lock();
+            inner.enqueue(self.fd);
+            inner.waiters.wake_all();
+        }
+    }
+}
+
+/// An epoll instance, opened as a fd.
+pub struct Epoll {
+    inner: Arc<SpinNoIrqLock<EpollInner>>,
+}
+
+impl Epoll {
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(SpinNoIrqLock::new(EpollInner {
+                interests: BTreeMap::new(),
+                ready: VecDeque::new(),
+                waiters: WaitQueue::new(),
+            })),
+        }
+    }
+
+    /// Add the file opened on fd to the interest list, EPOLL_CTL_ADD.
+    /// # Return
+    /// * 0 - success
+    /// * -EEXIST - the fd is already added
+    /// * -EPERM - the file does not support epoll
+    pub fn add(&self, fd: usize, file: &Arc<dyn File + Send + Sync>, event: EpollEvent) -> isize {
+        let watcher: Arc<dyn Watcher> = Arc::new(EpollWatcher {
+            epoll: Arc::downgrade(&self.inner),
+            fd,
+        });
+        let mut inner = self.inner.lock();
+        // The interest of a closed file is replaced.
+        if inner.interests.get(&fd).is_some_and(Interest::alive) {
+            return -EEXIST;
+        }
+        inner.interests.insert(
+            fd,
+            Interest {
+                file: Arc::downgrade(file),
+                events: EpollEvents::from_bits_truncate(event.events),
+                data: event.data,
+                watcher: watcher.clone(),
+                enabled: true,
+                queued: false,
+            },
+        );
+        drop(inner);
+        // The file may notify the watcher at once, the lock must be released.
+        if !file.add_watcher(Arc::downgrade(&watcher)) {
+            self.inner.lock().interests.remove(&fd);
+            return -EPERM;
+        }
+        // Check the current readiness in the next wait.
+        let mut inner = self.inner.lock();
+        inner.enqueue(fd);
+        inner.waiters.wake_all();
+        0
+    }
+
+    /// Change the events of the fd, EPOLL_CTL_MOD. A disabled one shot fd is enabled again.
+    /// # Return
+    /// * -ENOENT - the fd is not added
+    pub fn modify(&self, fd: usize, event: EpollEvent) -> isize {
+        let mut inner = self.inner.lock();
+        let Some(interest) = inner
+            .interests
+            .get_mut(&fd)
+            .filter(|interest| interest.alive())
+        else {
+            return -ENOENT;
+        };
+        interest.events = EpollEvents::from_bits_truncate(event.events);
+        interest.data = event.data;
+        interest.enabled = true;
+        inner.enqueue(fd);
+        inner.waiters.wake_all();
+        0
+    }
+
+    /// Remove the fd from the interest list, EPOLL_CTL_DEL.
+    /// # Return
+    /// * -ENOENT - the fd is not added
+    pub fn delete(&self, fd: usize) -> isize {
+        let Some(interest) = self.inner.lock().interests.remove(&fd) else {
+            return -ENOENT;
+        };
+        match interest.file.upgrade() {
+            Some(file) => {
+                file.remove_watcher(&Arc::downgrade(&interest.watcher));
+                0
+            }
+            None => -ENOENT,
+        }
+    }
+
+    /// Wait until some files are ready, or the timeout expires.
+    /// # Parameter
+    /// * 'max_events' - the max number of events returned, it is not 0
+    /// * 'timeout_ms' - None waits forever, Some(0) only checks the ready list
+    /// # Return
+    /// * Ok - the events, empty if the timeout expired
+    /// * Err(-EINTR) - interrupted by a signal
+    pub fn wait(
+        &self,
+        max_events: usize,
+        timeout_ms: Option<usize>,
+    ) -> Result<Vec<EpollEvent>, isize> {
+        let deadline = timeout_ms.map(|ms| get_time_ms() + ms);
+        let leave = || self.inner.lock().waiters.remove_current();
+        let check = |expired| {
+            let events = self.collect(max_events);
+            if expired || !events.is_empty() {
+                leave();
+                Some(events)
+            } else {
+                None
+            }
+        };
+        wait_event(deadline, true, check, &leave)
+    }
+
+    /// Take the ready list and report the files which are really ready.
+    /// The current thread is added to the waiters, so a notification after it is not lost.
+    fn collect(&self, max_events: usize) -> Vec<EpollEvent> {
+        let mut inner = self.inner.lock();
+        inner.waiters.push_current();
+        let candidates: Vec<(usize, Option<Arc<dyn File + Send + Sync>>, EpollEvents, u64)> =
+            core::mem::take(&mut inner.ready)
+                .into_iter()
+                .filter_map(|fd| {
+                    let interest = inner.interests.get_mut(&fd)?;
+                    interest.queued = false;
+                    let file = interest.file.upgrade();
+                    Some((fd, file, interest.events, interest.data))
+                })
+                .collect();
+        drop(inner);
+
+        let mut events = Vec::new();
+        // (fd, reported) of the files which are still open.
+        let mut checked = Vec::new();
+        for (fd, file, interest_events, data) in candidates {
+            let Some(file) = file else {
+                checked.push((fd, None));
+                continue;
+            };
+            if events.len() == max_events {
+                // Not checked, keep it for the next wait.
+                checked.push((fd, Some(false)));
+                continue;
+            }
+            let ready = poll_to_epoll(file.poll_ready())
+                & (interest_events | EpollEvents::EPOLLERR | EpollEvents::EPOLLHUP);
+            if ready.is_empty() {
+                continue;
+            }
+            events.push(EpollEvent {
+                events: ready.bits(),
+                data,
+            });
+            checked.push((fd, Some(true)));
+        }
+
+        let mut inner = self.inner.lock();
+        for (fd, reported) in checked {
+            match reported {
+                // The file is closed.
+                None => {
+                    inner.interests.remove(&fd);
+                }
+                Some(true) => {
+                    let Some(interest) = inner.interests.get_mut(&fd) else {
+                        continue;
+                    };
+                    if interest.events.contains(EpollEvents::EPOLLONESHOT) {
+                        interest.enabled = false;
+                    } else if !interest.events.contains(EpollEvents::EPOLLET) {
+                        inner.enqueue(fd);
+                    }
+                }
+                Some(false) => inner.enqueue(fd),
+            }
+        }
+        events
+    }
+}
+
+/// Convert the events of poll to the events of epoll, they have the same values.
+fn poll_to_epoll(events: PollEvents) -> EpollEvents {
+    EpollEvents::from_bits_truncate(events.bits() as u32)
+}
+
+impl File for Epoll {
+    fn readable(&self) -> bool {
+        false
+    }
+
+    fn writable(&self) -> bool {
+        false
+    }
+
+    #[allow(unused)]
+    fn read(&self, buf: UserBuffer) -> isize {
+        -1
+    }
+
+    #[allow(unused)]
+    fn write(&self, buf: UserBuffer) -> isize {
+        -1
+    }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+
+    fn name(&self) -> String {
+        String::from("anon_inode:[eventpoll]")
+    }
+}
@@ -0,0 +1,195 @@
+//! Eventfd, a 64-bit counter shared by the threads and processes which have the fd.
+//! A write adds to the counter and a read takes the whole counter, or only 1 in semaphore mode.
+//! A reader blocks while the counter is 0, and a writer blocks while the counter would exceed
+//! u64::MAX - 1.
+use alloc::{string::String, sync::Weak};
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use crate::{
+    mm::page_table::UserBuffer,
+    sync::{
+        wait_queue::{WaitQueue, Watcher},
+        SpinNoIrqLock,
+    },
+    syscall::errno::{EAGAIN, EINTR, EINVAL},
+    task::{block_current_and_run_next, check_current_signals_error, prepare_interruptible_block},
+};
+
+use super::{poll::PollEvents, File};
+
+bitflags! {
+    /// Flags of eventfd2, the same values as linux.
+    #[derive(Clone, Copy, PartialEq, Eq)]
+    pub struct EventfdFlags: u32 {
+        /// A read decreases the counter by 1 instead of resetting it.
+        const EFD_SEMAPHORE = 1;
+        const EFD_NONBLOCK = 1 << 11;
+        const EFD_CLOEXEC = 1 << 19;
+    }
+}
+
+/// The max value of the counter.
+const COUNTER_MAX: u64 = u64::MAX - 1;
+
+struct EventfdInner {
+    counter: u64,
+    /// Readers waiting for a non-zero counter.
+    readers: WaitQueue,
+    /// Writers waiting for the counter to be read.
+    writers: WaitQueue,
+}
+
+pub struct Eventfd {
+    semaphore: bool,
+    /// Return EAGAIN instead of blocking.
+    nonblock: AtomicBool,
+    inner: SpinNoIrqLock<EventfdInner>,
+}
+
+impl Eventfd {
+    pub fn new(initval: u32, flags: EventfdFlags) -> Self {
+        Self {
+            semaphore: flags.contains(EventfdFlags::EFD_SEMAPHORE),
+            nonblock: AtomicBool::new(flags.contains(EventfdFlags::EFD_NONBLOCK)),
+            inner: SpinNoIrqLock::new(EventfdInner {
+                counter: initval as u64,
+                readers: WaitQueue::new(),
+                writers: WaitQueue::new(),
+            }),
+        }
+    }
+}
+
+impl File for Eventfd {
+    fn readable(&self) -> bool {
+        true
+    }
+
+    fn writable(&self) -> bool {
+        true
+    }
+
+    /// Read the counter as 8 bytes, wait until it is not 0.
+    /// # Return
+    /// * -EINVAL - buf is shorter than 8 bytes
+    fn read(&self, buf: UserBuffer) -> isize {
+        if buf.len() < 8 {
+            return -EINVAL;
+        }
+        loop {
+            prepare_interruptible_block();
+            let mut inner = self.inner.lock();
+            if inner.counter > 0 {
+                let value = if self.semaphore { 1 } else { inner.counter };
+                inner.counter -= value;
+                inner.writers.wake_all();
+                drop(inner);
+                for (dst, byte) in buf.into_iter().zip(value.to_ne_bytes()) {
+                    unsafe { *dst = byte };
+                }
+                return 8;
+            }
+            if self.nonblock() {
+                return -EAGAIN;
+            }
+            if check_current_signals_error().is_some() {
+                return -EINTR;
+            }
+            inner.readers.push_current();
+            drop(inner);
+            block_current_and_run_next();
+            // Woken up by a signal, the thread is still in the wait queue.
+            self.inner.lock().readers.remove_current();
+        }
+    }
+
+    /// Add the 8 bytes value to the counter, wait until it fits.
+    /// # Return
+    /// * -EINVAL - buf is shorter than 8 bytes, or the value is u64::MAX
+    fn write(&self, buf: UserBuffer) -> isize {
+        if buf.len() < 8 {
+            return -EINVAL;
+        }
+        let mut bytes = [0u8; 8];
+        for (byte, src) in bytes.iter_mut().zip(buf) {
+            *byte = unsafe { *src };
+        }
+        let value = u64::from_ne_bytes(bytes);
+        if value > COUNTER_MAX {
+            return -EINVAL;
+        }
+        loop {
+            prepare_interruptible_block();
+            let mut inner = self.inner.lock();
+            if COUNTER_MAX - inner.counter >= value {
+                inner.counter += value;
+                if value > 0 {
+                    inner.readers.wake_all();
+                }
+                return 8;
+            }
+            if self.nonblock() {
+                return -EAGAIN;
+            }
+            if check_current_signals_error().is_some() {
+                return -EINTR;
+            }
+            inner.writers.push_current();
+            drop(inner);
+            block_current_and_run_next();
+            // Woken up by a signal, the thread is still in the wait queue.
+            self.inner.lock().writers.remove_current();
+        }
+    }
+
+    /// Readable when the counter is not 0, writable when it can be increased by 1.
+    fn poll_ready(&self) -> PollEvents {
+        let inner = self.inner.lock();
+        let mut events = PollEvents::empty();
+        if inner.counter > 0 {
+            events |= PollEvents::POLLIN;
+        }
+        if inner.counter < COUNTER_MAX {
+            events |= PollEvents::POLLOUT;
+        }
+        events
+    }
+
+    fn register_waker(&self) {
+        let mut inner = self.inner.lock();
+        inner.readers.push_current();
+        inner.writers.push_current();
+    }
+
+    fn unregister_waker(&self) {
+        let mut inner = self.inner.lock();
+        inner.readers.remove_current();
+        inner.writers.remove_current();
+    }
+
+    fn add_watcher(&self, watcher: Weak<dyn Watcher>) -> bool {
+        let mut inner = self.inner.lock();
+        inner.readers.add_watcher(watcher.clone());
+        inner.writers.add_watcher(watcher);
+        true
+    }
+
+    fn remove_watcher(&self, watcher: &Weak<dyn Watcher>) {
+        let mut inner = self.inner.lock();
+        inner.readers.remove_watcher(watcher);
+        inner.writers.remove_watcher(watcher);
+    }
+
+    fn nonblock(&self) -> bool {
+        self.nonblock.load(Ordering::Relaxed)
+    }
+
+    fn set_nonblock(&self, nonblock: bool) -> bool {
+        self.nonblock.store(nonblock, Ordering::Relaxed);
+        true
+    }
+
+    fn name(&self) -> String {
+        String::from("anon_inode:[eventfd]")
+    }
+}
@@ -1,24 +1,31 @@
-use alloc::{sync::Arc, vec, vec::Vec};
-use easy_fs::{efs::EasyFileSystem, vfs::Inode};
+use alloc::{string::String, sync::Arc, vec::Vec};
+use easy_fs::{efs::EasyFileSystem, vfs::Inode, NAME_LENGTH_LIMIT};
 use lazy_static::lazy_static;
 
-use crate::{drivers::block::BLOCK_DEVICE, println, sync::UPSafeCell};
+use crate::{drivers::block::BLOCK_DEVICE, println};
 
-use super::File;
+use super::vfs::{NodeType, Stat, VfsNode};
 
 bitflags! {
     pub struct OpenFlags: u32{
         const READ_ONLY = 0;
         const WRITE_ONLY = 1 << 0;
         const READ_WRITE = 1 << 1;
+        /// With CREATE, fail if it exists.
+        const EXCL = 1 << 7;
+        /// Create the file if it does not exist, an existing file is truncated.
         const CREATE = 1 << 9;
         const TRUNC = 1 << 10;
+        const NONBLOCK = 1 << 11;
+        const APPEND = 1 << 12;
+        const CLOEXEC = 1 << 19;
     }
 }
 
 impl OpenFlags {
     pub fn read_write(&self) -> (bool, bool) {
-        if self.is_empty() {
+        // Flags of the fd and non-blocking mode do not change the access mode.
+        if self.bits() & !(Self::CLOEXEC.bits() | Self::NONBLOCK.bits()) == 0 {
             (true, false)
         } else if self.contains(Self::WRITE_ONLY) {
             (false, true)
@@ -28,68 +35,96 @@ impl OpenFlags {
     }
 }
 
-pub struct OSInodeInner {
-    offset: usize,
+/// Device id of easy-fs in struct stat.
+const EASY_FS_DEV: u64 = 1;
+
+/// A file or directory of easy-fs.
+pub struct EasyFsNode {
     inode: Arc<Inode>,
 }
-pub struct OSInode {
-    readable: bool,
-    writable: bool,
-    inner: UPSafeCell<OSInodeInner>,
+
+impl EasyFsNode {
+    pub fn new(inode: Arc<Inode>) -> Arc<dyn VfsNode> {
+        Arc::new(Self { inode })
+    }
 }
-impl OSInode {
-    /// Create a new OSInode from Inode.
-    pub fn new(readable: bool, writable: bool, inode: Arc<Inode>) -> Self {
-        Self {
-            readable,
-            writable,
-            inner: unsafe { UPSafeCell::new(OSInodeInner { offset: 0, inode }) },
+
+impl VfsNode for EasyFsNode {
+    fn node_type(&self) -> NodeType {
+        if self.inode.is_dir() {
+            NodeType::Dir
+        } else if self.inode.is_fifo() {
+            NodeType::Fifo
+        } else if self.inode.is_socket() {
+            NodeType::Socket
+        } else {
+            NodeType::File
         }
     }
 
-    pub fn read_all(&self) -> Vec<u8> {
-        let mut inner = self.inner.exclusive_access();
-        let len = inner.inode.len() as usize - inner.offset;
-        let mut data = vec![0; len];
-        let mut slice = data.as_mut_slice();
-        inner.inode.read_at(inner.offset, &mut slice);
-        inner.offset += len;
-        slice.to_vec()
+    fn lookup(&self, name: &str) -> Option<Arc<dyn VfsNode>> {
+        if !self.inode.is_dir() {
+            return None;
+        }
+        self.inode.find(name).map(EasyFsNode::new)
     }
-}
-impl File for OSInode {
-    fn readable(&self) -> bool {
-        self.readable
+
+    fn create(&self, name: &str, node_type: NodeType) -> Option<Arc<dyn VfsNode>> {
+        if !self.inode.is_dir() || name.len() > NAME_LENGTH_LIMIT {
+            return None;
+        }
+        match node_type {
+            NodeType::File => self.inode.create_file(name),
+            NodeType::Dir => self.inode.create_directory(name),
+            NodeType::Fifo => self.inode.create_fifo(name),
+            NodeType::Socket => self.inode.create_socket(name),
+            _ => None,
+        }
+        .map(EasyFsNode::new)
     }
 
-    fn writable(&self) -> bool {
-        self.writable
+    fn unlink(&self, name: &str) -> bool {
+        self.inode.is_dir() && self.inode.unlink(name)
     }
-    /// Read data to user buffer from file.
-    fn read(&self, mut buf: crate::mm::page_table::UserBuffer) -> usize {
-        let mut inner = self.inner.exclusive_access();
-        let mut read_size = 0;
-        for slice in buf.buffers.iter_mut() {
-            let curr_read_size = inner.inode.read_at(inner.offset, *slice);
-            if curr_read_size == 0 {
-                break;
-            }
-            inner.offset += curr_read_size;
-            read_size += curr_read_size;
+
+    fn readdir(&self) -> Vec<(String, NodeType)> {
+        if !self.inode.is_dir() {
+            return Vec::new();
         }
-        read_size
+        self.inode
+            .list()
+            .into_iter()
+            .filter_map(|name| {
+                let node_type = EasyFsNode::new(self.inode.find(&name)?).node_type();
+                Some((name, node_type))
+            })
+            .collect()
     }
-    /// Write data to file from user buffer.
-    fn write(&self, buf: crate::mm::page_table::UserBuffer) -> usize {
-        let mut inner = self.inner.exclusive_access();
-        let mut write_size = 0;
-        for slice in buf.buffers.iter() {
-            let curr_write_size = inner.inode.write_at(inner.offset, *slice);
-            assert_eq!(curr_write_size, slice.len(), "Error when writing.");
-            inner.offset += curr_write_size;
-            write_size += curr_write_size;
+
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        self.inode.read_at(offset, buf)
+    }
+
+    fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
+        self.inode.write_at(offset, buf)
+    }
+
+    /// easy-fs can only truncate a file to empty.
+    fn truncate(&self, size: usize) -> bool {
+        if size != 0 || self.inode.is_dir() {
+            return false;
         }
-        write_size
+        self.inode.clear();
+        true
+    }
+
+    fn stat(&self) -> Stat {
+        Stat::new(
+            EASY_FS_DEV,
+            self.inode.inode_id() as u64,
+            self.node_type(),
+            self.inode.len() as usize,
+        )
     }
 }
 
@@ -101,27 +136,6 @@ lazy_static! {
     };
 }
 
-pub fn open_file(name: &str, flags: OpenFlags) -> Option<Arc<OSInode>> {
-    let (readable, writable) = flags.read_write();
-    if flags.contains(OpenFlags::CREATE) {
-        if let Some(inode) = ROOT_INODE.find(name) {
-            inode.clear();
-            Some(Arc::new(OSInode::new(readable, writable, inode)))
-        } else {
-            ROOT_INODE
-                .create_file(name)
-                .map(|inode| Arc::new(OSInode::new(readable, writable, inode)))
-        }
-    } else {
-        ROOT_INODE.find(name).map(|inode| {
-            if flags.contains(OpenFlags::TRUNC) {
-                inode.clear()
-            }
-            Arc::new(OSInode::new(readable, writable, inode))
-        })
-    }
-}
-
 pub fn list_app() {
     println!("************ APPS ************");
     for name in ROOT_INODE.list() {
@@ -1,11 +1,111 @@
+pub mod devfs;
+pub mod epoll;
+pub mod eventfd;
 pub mod inode;
+pub mod mqueue;
 mod stdio;
 pub mod pipe;
-use crate::mm::page_table::UserBuffer;
+pub mod poll;
+pub mod procfs;
+pub mod signalfd;
+pub mod timerfd;
+mod tmpfs;
+pub mod tty;
+pub mod vfs;
+use crate::{mm::page_table::UserBuffer, net::Socket, sync::wait_queue::Watcher};
+use alloc::{string::String, sync::Weak};
+use core::any::Any;
+use poll::PollEvents;
+use vfs::Stat;
 pub use stdio::{Stdin, Stdout};
 pub trait File: Send + Sync {
     fn readable(&self) -> bool;
     fn writable(&self) -> bool;
-    fn read(&self, buf: UserBuffer) -> usize;
-    fn write(&self, buf: UserBuffer) -> usize;
+    /// # Return
+    /// * The number of bytes read, 0 at the end of file.
+    /// * -errno - such as -EAGAIN when the file is non-blocking and has no data.
+    fn read(&self, buf: UserBuffer) -> isize;
+    /// # Return
+    /// * The number of bytes written.
+    /// * -errno - such as -EPIPE when all read ends of a pipe are closed.
+    fn write(&self, buf: UserBuffer) -> isize;
+    /// Device specific control, the default implementation supports nothing.
+    /// # Return
+    /// * -1 - the request is not supported by this file.
+    #[allow(unused)]
+    fn ioctl(&self, cmd: usize, arg: usize) -> isize {
+        -1
+    }
+    /// Read directory entries in the layout of linux struct dirent64,
+    /// the default implementation is for files which are not directory.
+    /// # Return
+    /// * -1 - the file is not a directory, or buf is too small for an entry.
+    /// * 0 - no more entries.
+    /// * The number of bytes read.
+    #[allow(unused)]
+    fn getdents(&self, buf: UserBuffer) -> isize {
+        -1
+    }
+    /// Change the size of the file.
+    /// # Return
+    /// * -1 - the file is not writable, or it can not be truncated to the size.
+    #[allow(unused)]
+    fn truncate(&self, size: usize) -> isize {
+        -1
+    }
+    /// O_NONBLOCK of the open file.
+    fn nonblock(&self) -> bool {
+        false
+    }
+    /// Change O_NONBLOCK of the open file by fcntl.
+    /// # Return
+    /// * false - the file does not support non-blocking mode.
+    #[allow(unused)]
+    fn set_nonblock(&self, nonblock: bool) -> bool {
+        false
+    }
+    /// Events the file is ready for, used by poll.
+    /// The default is for files which never block, such as regular files.
+    fn poll_ready(&self) -> PollEvents {
+        let mut events = PollEvents::empty();
+        if self.readable() {
+            events |= PollEvents::POLLIN;
+        }
+        if self.writable() {
+            events |= PollEvents::POLLOUT;
+        }
+        events
+    }
+    /// Wake up the current thread when the readiness of the file may change,
+    /// until unregister_waker is called. Files which never block need not implement it.
+    fn register_waker(&self) {}
+    /// Stop waking up the current thread.
+    fn unregister_waker(&self) {}
+    /// Notify the watcher whenever the readiness of the file may change, until it is removed.
+    /// # Return
+    /// * false - the file does not support it, such as regular files which are always ready.
+    #[allow(unused)]
+    fn add_watcher(&self, watcher: Weak<dyn Watcher>) -> bool {
+        false
+    }
+    #[allow(unused)]
+    fn remove_watcher(&self, watcher: &Weak<dyn Watcher>) {}
+    /// The concrete file, to get the epoll instance of an epoll fd and so on.
+    fn as_any(&self) -> Option<&dyn Any> {
+        None
+    }
+    /// The socket of a socket fd, for the socket syscalls.
+    fn as_socket(&self) -> Option<&dyn Socket> {
+        None
+    }
+    /// Get the file status.
+    /// # Return
+    /// * None - the file is not in any file system.
+    fn stat(&self) -> Option<Stat> {
+        None
+    }
+    /// What the file is, shown in /proc/<pid>/fd.
+    fn name(&self) -> String {
+        String::from("anon_inode")
+    }
 }
@@ -0,0 +1,393 @@
+//! POSIX message queues, named globally by a name such as "/queue".
+//! A queue keeps at most maxmsg messages, the message of the highest priority is received first,
+//! and messages of the same priority are received in the order they were sent.
+//! An unlinked queue can not be opened again, it is destroyed when the last descriptor is closed.
+use alloc::{
+    collections::{BTreeMap, VecDeque},
+    string::String,
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use core::{
+    any::Any,
+    sync::atomic::{AtomicBool, Ordering},
+};
+use lazy_static::lazy_static;
+
+use crate::{
+    mm::page_table::UserBuffer,
+    sync::{
+        wait_queue::{wait_event, WaitQueue, Watcher},
+        SpinNoIrqLock,
+    },
+    syscall::errno::{EACCES, EAGAIN, EEXIST, EINVAL, EMSGSIZE, ENAMETOOLONG, ENOENT, ENOSPC},
+};
+
+use super::{poll::PollEvents, File};
+
+/// Priorities are less than MQ_PRIO_MAX.
+pub const MQ_PRIO_MAX: u32 = 32768;
+/// Attributes of a queue created without attributes.
+const MQ_DEFAULT_MAXMSG: usize = 10;
+const MQ_DEFAULT_MSGSIZE: usize = 1024;
+/// Limits of the attributes, messages are in the kernel heap.
+const MQ_MAX_MAXMSG: usize = 64;
+const MQ_MAX_MSGSIZE: usize = 8192;
+/// The max number of queues.
+const MQ_MAX_QUEUES: usize = 32;
+/// The max length of a name without the leading '/'.
+const MQ_NAME_MAX: usize = 255;
+/// Permission bits of the mode, there are no users so only the owner bits are checked.
+const MQ_MODE_MASK: u32 = 0o777;
+const MQ_MODE_READ: u32 = 0o400;
+const MQ_MODE_WRITE: u32 = 0o200;
+/// The only flag in MqAttr, the same value as O_NONBLOCK.
+pub const MQ_NONBLOCK: usize = 1 << 11;
+
+/// Attributes of a queue, the same layout as linux struct mq_attr.
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct MqAttr {
+    /// MQ_NONBLOCK of the descriptor.
+    pub flags: usize,
+    pub maxmsg: usize,
+    pub msgsize: usize,
+    /// The number of messages in the queue.
+    pub curmsgs: usize,
+    reserved: [usize; 4],
+}
+
+struct MqueueInner {
+    /// Messages by priority.
+    messages: BTreeMap<u32, VecDeque<Vec<u8>>>,
+    count: usize,
+    /// Receivers waiting for a message.
+    readers: WaitQueue,
+    /// Senders waiting for a free slot.
+    writers: WaitQueue,
+}
+
+pub struct MessageQueue {
+    maxmsg: usize,
+    msgsize: usize,
+    mode: u32,
+    inner: SpinNoIrqLock<MqueueInner>,
+}
+
+impl MessageQueue {
+    fn new(maxmsg: usize, msgsize: usize, mode: u32) -> Self {
+        Self {
+            maxmsg,
+            msgsize,
+            mode: mode & MQ_MODE_MASK,
+            inner: SpinNoIrqLock::new(MqueueInner {
+                messages: BTreeMap::new(),
+                count: 0,
+                readers: WaitQueue::new(),
+                writers: WaitQueue::new(),
+            }),
+        }
+    }
+
+    /// Add the message, wait while the queue is full.
+    /// # Parameter
+    /// * 'deadline' - the time since boot in ms to give up waiting, None waits forever
+    /// # Return
+    /// * -EMSGSIZE - the message is longer than msgsize
+    /// * -EINVAL - prio is not less than MQ_PRIO_MAX
+    /// * -EAGAIN - the queue is full and nonblock is set
+    /// * -ETIMEDOUT - the queue is still full at the deadline
+    /// * -EINTR - interrupted by a signal
+    pub fn send(
+        &self,
+        msg: Vec<u8>,
+        prio: u32,
+        nonblock: bool,
+        deadline: Option<usize>,
+    ) -> Result<(), isize> {
+        if msg.len() > self.msgsize {
+            return Err(-EMSGSIZE);
+        }
+        if prio >= MQ_PRIO_MAX {
+            return Err(-EINVAL);
+        }
+        let mut msg = Some(msg);
+        self.wait(
+            nonblock,
+            deadline,
+            |inner| &mut inner.writers,
+            |inner| {
+                if inner.count == self.maxmsg {
+                    return None;
+                }
+                let msg = msg.take().unwrap();
+                inner.messages.entry(prio).or_default().push_back(msg);
+                inner.count += 1;
+                inner.readers.wake_all();
+                Some(())
+            },
+        )
+    }
+
+    /// Take the oldest message of the highest priority, wait while the queue is empty.
+    /// # Return
+    /// * Ok - the message and its priority
+    /// * -EAGAIN - the queue is empty and nonblock is set
+    /// * -ETIMEDOUT - the queue is still empty at the deadline
+    /// * -EINTR - interrupted by a signal
+    pub fn receive(
+        &self,
+        nonblock: bool,
+        deadline: Option<usize>,
+    ) -> Result<(Vec<u8>, u32), isize> {
+        self.wait(
+            nonblock,
+            deadline,
+            |inner| &mut inner.readers,
+            |inner| {
+                let mut entry = inner.messages.last_entry()?;
+                let prio = *entry.key();
+                let msg = entry.get_mut().pop_front().unwrap();
+                if entry.get().is_empty() {
+                    entry.remove();
+                }
+                inner.count -= 1;
+                inner.writers.wake_all();
+                Some((msg, prio))
+            },
+        )
+    }
+
+    /// Try f under the lock until it succeeds, blocking in the wait queue given by waiters.
+    fn wait<T>(
+        &self,
+        nonblock: bool,
+        deadline: Option<usize>,
+        waiters: fn(&mut MqueueInner) -> &mut WaitQueue,
+        mut f: impl FnMut(&mut MqueueInner) -> Option<T>,
+    ) -> Result<T, isize> {
+        let check = |_| {
+            let mut inner = self.inner.lock();
+            if let Some(value) = f(&mut inner) {
+                return Some(Ok(value));
+            }
+            if nonblock {
+                return Some(Err(-EAGAIN));
+            }
+            waiters(&mut inner).push_current();
+            None
+        };
+        let leave = || waiters(&mut self.inner.lock()).remove_current();
+        wait_event(deadline.filter(|_| !nonblock), true, check, leave)?
+    }
+
+    pub fn attr(&self) -> MqAttr {
+        MqAttr {
+            maxmsg: self.maxmsg,
+            msgsize: self.msgsize,
+            curmsgs: self.inner.lock().count,
+            ..Default::default()
+        }
+    }
+
+    pub fn msgsize(&self) -> usize {
+        self.msgsize
+    }
+}
+
+lazy_static! {
+    /// Queues by name, a queue is removed by mq_unlink.
+    static ref MQUEUES: SpinNoIrqLock<BTreeMap<String, Arc<MessageQueue>>> =
+        SpinNoIrqLock::new(BTreeMap::new());
+}
+
+/// Check the name of a queue, which is '/' followed by a name without '/'.
+/// # Return
+/// * -EINVAL - the name does not start with '/' or has another '/'
+/// * -ENAMETOOLONG - the name is longer than MQ_NAME_MAX
+fn check_name(name: &str) -> Result<(), isize> {
+    let Some(rest) = name.strip_prefix('/') else {
+        return Err(-EINVAL);
+    };
+    if rest.is_empty() || rest.contains('/') {
+        return Err(-EINVAL);
+    }
+    if rest.len() > MQ_NAME_MAX {
+        return Err(-ENAMETOOLONG);
+    }
+    Ok(())
+}
+
+/// Open the queue of the name, or create it.
+/// # Parameter
+/// * 'create', 'excl' - O_CREAT and O_EXCL of mq_open
+/// * 'attr' - maxmsg and msgsize of a new queue, None for the default
+/// # Return
+/// * -EINVAL - bad name, or maxmsg or msgsize is 0 or over the limits
+/// * -ENAMETOOLONG - the name is too long
+/// * -ENOENT - no queue has the name and create is not given
+/// * -EEXIST - a queue has the name and create and excl are given
+/// * -EACCES - the mode of the queue does not allow the access
+/// * -ENOSPC - there are MQ_MAX_QUEUES queues
+pub fn open(
+    name: &str,
+    readable: bool,
+    writable: bool,
+    create: bool,
+    excl: bool,
+    mode: u32,
+    attr: Option<MqAttr>,
+) -> Result<Arc<MessageQueue>, isize> {
+    check_name(name)?;
+    let mut queues = MQUEUES.lock();
+    let queue = match queues.get(name) {
+        Some(_) if create && excl => return Err(-EEXIST),
+        Some(queue) => queue.clone(),
+        None if !create => return Err(-ENOENT),
+        None => {
+            let (maxmsg, msgsize) = match attr {
+                Some(attr) => (attr.maxmsg, attr.msgsize),
+                None => (MQ_DEFAULT_MAXMSG, MQ_DEFAULT_MSGSIZE),
+            };
+            if !(1..=MQ_MAX_MAXMSG).contains(&maxmsg) || !(1..=MQ_MAX_MSGSIZE).contains(&msgsize) {
+                return Err(-EINVAL);
+            }
+            if queues.len() >= MQ_MAX_QUEUES {
+                return Err(-ENOSPC);
+            }
+            let queue = Arc::new(MessageQueue::new(maxmsg, msgsize, mode));
+            queues.insert(String::from(name), queue.clone());
+            // The creator is not checked against the mode of the new queue.
+            return Ok(queue);
+        }
+    };
+    let mut need = 0;
+    if readable {
+        need |= MQ_MODE_READ;
+    }
+    if writable {
+        need |= MQ_MODE_WRITE;
+    }
+    match queue.mode & need == need {
+        true => Ok(queue),
+        false => Err(-EACCES),
+    }
+}
+
+/// Remove the name of the queue, the queue stays until its descriptors are closed.
+/// # Return
+/// * -ENOENT - no queue has the name
+pub fn unlink(name: &str) -> Result<(), isize> {
+    check_name(name)?;
+    match MQUEUES.lock().remove(name) {
+        Some(_) => Ok(()),
+        None => Err(-ENOENT),
+    }
+}
+
+/// An open queue in the fd table, it is not read or written as a file.
+pub struct MqDescriptor {
+    queue: Arc<MessageQueue>,
+    readable: bool,
+    writable: bool,
+    /// Return EAGAIN instead of blocking.
+    nonblock: AtomicBool,
+}
+
+impl MqDescriptor {
+    pub fn new(queue: Arc<MessageQueue>, readable: bool, writable: bool, nonblock: bool) -> Self {
+        Self {
+            queue,
+            readable,
+            writable,
+            nonblock: AtomicBool::new(nonblock),
+        }
+    }
+
+    pub fn queue(&self) -> &Arc<MessageQueue> {
+        &self.queue
+    }
+
+    /// Opened for receiving.
+    pub fn can_receive(&self) -> bool {
+        self.readable
+    }
+
+    /// Opened for sending.
+    pub fn can_send(&self) -> bool {
+        self.writable
+    }
+}
+
+impl File for MqDescriptor {
+    fn readable(&self) -> bool {
+        false
+    }
+
+    fn writable(&self) -> bool {
+        false
+    }
+
+    fn read(&self, _buf: UserBuffer) -> isize {
+        -EINVAL
+    }
+
+    fn write(&self, _buf: UserBuffer) -> isize {
+        -EINVAL
+    }
+
+    /// Readable when the queue has a message, writable when it is not full.
+    fn poll_ready(&self) -> PollEvents {
+        let inner = self.queue.inner.lock();
+        let mut events = PollEvents::empty();
+        if inner.count > 0 {
+            events |= PollEvents::POLLIN;
+        }
+        if inner.count < self.queue.maxmsg {
+            events |= PollEvents::POLLOUT;
+        }
+        events
+    }
+
+    fn register_waker(&self) {
+        let mut inner = self.queue.inner.lock();
+        inner.readers.push_current();
+        inner.writers.push_current();
+    }
+
+    fn unregister_waker(&self) {
+        let mut inner = self.queue.inner.lock();
+        inner.readers.remove_current();
+        inner.writers.remove_current();
+    }
+
+    fn add_watcher(&self, watcher: Weak<dyn Watcher>) -> bool {
+        let mut inner = self.queue.inner.lock();
+        inner.readers.add_watcher(watcher.clone());
+        inner.writers.add_watcher(watcher);
+        true
+    }
+
+    fn remove_watcher(&self, watcher: &Weak<dyn Watcher>) {
+        let mut inner = self.queue.inner.lock();
+        inner.readers.remove_watcher(watcher);
+        inner.writers.remove_watcher(watcher);
+    }
+
+    fn nonblock(&self) -> bool {
+        self.nonblock.load(Ordering::Relaxed)
+    }
+
+    fn set_nonblock(&self, nonblock: bool) -> bool {
+        self.nonblock.store(nonblock, Ordering::Relaxed);
+        true
+    }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+
+    fn name(&self) -> String {
+        String::from("mqueue")
+    }
+}
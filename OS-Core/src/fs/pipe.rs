@@ -1,103 +1,125 @@
-use alloc::sync::{Arc, Weak};
+//! Pipes, a byte stream from the write ends to the read ends.
+//! The buffer grows as data is written, up to PIPE_CAPACITY bytes.
+//! A reader blocks while the pipe is empty and a writer blocks while it is full,
+//! they are woken up when the other side reads, writes or closes its last end.
+//!
+//! A named pipe (FIFO) is a file whose opened ends share a pipe buffer,
+//! the buffer lives while any end is open and it is found by the inode of the FIFO.
+use alloc::{
+    collections::{BTreeMap, VecDeque},
+    string::String,
+    sync::{Arc, Weak},
+};
+use core::sync::atomic::{AtomicBool, Ordering};
+use lazy_static::lazy_static;
 
-use crate::{sync::UPSafeCell, task::suspended_current_and_run_next};
+use crate::{
+    config::PAGE_SIZE,
+    mm::page_table::UserBuffer,
+    sync::{
+        wait_queue::{WaitQueue, Watcher},
+        SpinNoIrqLock,
+    },
+    syscall::errno::{EAGAIN, EINTR, ENXIO, EPIPE},
+    task::{
+        block_current_and_run_next, check_current_signals_error, prepare_interruptible_block,
+        processor::current_process, send_signal, signal::SignalFlags,
+    },
+};
 
-use super::File;
+use super::{poll::PollEvents, File};
 
-const RING_BUFFER_SIZE: usize = 32;
-#[derive(PartialEq, Eq)]
-enum RingBufferStatus {
-    Full,
-    Empty,
-    Normal,
-}
+/// The max number of bytes buffered in a pipe.
+pub const PIPE_CAPACITY: usize = 16 * PAGE_SIZE;
+/// Writes of no more than PIPE_BUF bytes are atomic, they are never interleaved with other writes.
+pub const PIPE_BUF: usize = PAGE_SIZE;
 
-pub struct PipeRingBuffer {
-    arr: [u8; RING_BUFFER_SIZE],
-    head: usize,
-    tail: usize,
-    status: RingBufferStatus,
-    write_end: Option<Weak<Pipe>>,
+pub struct PipeBuffer {
+    data: VecDeque<u8>,
+    /// The number of opened read ends.
+    read_ends: usize,
+    /// The number of opened write ends.
+    write_ends: usize,
+    /// The number of read ends ever opened, an opener of a FIFO waits until it changes.
+    read_opened: usize,
+    /// The number of write ends ever opened.
+    write_opened: usize,
+    /// Readers waiting for data.
+    readers: WaitQueue,
+    /// Writers waiting for space.
+    writers: WaitQueue,
 }
 
-impl PipeRingBuffer {
+impl PipeBuffer {
     pub fn new() -> Self {
         Self {
-            arr: [0; RING_BUFFER_SIZE],
-            head: 0,
-            tail: 0,
-            status: RingBufferStatus::Empty,
-            write_end: None,
-        }
-    }
-
-    pub fn set_write_end(&mut self, write_end: &Arc<Pipe>) {
-        self.write_end = Some(Arc::downgrade(write_end));
-    }
-
-    pub fn read_byte(&mut self) -> u8 {
-        self.status = RingBufferStatus::Normal;
-        let byte = self.arr[self.head];
-        self.head = (self.head + 1) % RING_BUFFER_SIZE;
-        if self.head == self.tail {
-            self.status = RingBufferStatus::Empty;
+            data: VecDeque::new(),
+            read_ends: 0,
+            write_ends: 0,
+            read_opened: 0,
+            write_opened: 0,
+            readers: WaitQueue::new(),
+            writers: WaitQueue::new(),
         }
-        byte
     }
 
-    pub fn write_byte(&mut self, byte: u8) {
-        self.status = RingBufferStatus::Normal;
-        self.arr[self.tail] = byte;
-        self.tail = (self.tail + 1) % RING_BUFFER_SIZE;
-        if self.tail == self.head {
-            self.status = RingBufferStatus::Full;
-        }
-    }
-
-    pub fn available_read(&self) -> usize {
-        if self.status == RingBufferStatus::Empty {
-            0
+    /// The queue an opener of a FIFO waits in, for the other end to be opened.
+    fn open_waiters(&mut self, readable: bool) -> &mut WaitQueue {
+        if readable {
+            &mut self.readers
         } else {
-            match self.head >= self.tail {
-                true => self.tail + RING_BUFFER_SIZE - self.head,
-                false => self.tail - self.head,
-            }
+            &mut self.writers
         }
     }
-
-    pub fn available_write(&self) -> usize {
-        if self.status == RingBufferStatus::Full {
-            0
-        } else {
-            RING_BUFFER_SIZE - self.available_read()
-        }
-    }
-
-    pub fn all_write_ends_closed(&self) -> bool {
-        self.write_end.as_ref().unwrap().upgrade().is_none()
-    }
 }
 
 pub struct Pipe {
     readable: bool,
     writable: bool,
-    buffer: Arc<UPSafeCell<PipeRingBuffer>>,
+    /// Return EAGAIN instead of blocking.
+    nonblock: AtomicBool,
+    buffer: Arc<SpinNoIrqLock<PipeBuffer>>,
 }
 
 impl Pipe {
-    pub fn read_end_with_buffer(buffer: Arc<UPSafeCell<PipeRingBuffer>>) -> Self {
+    /// Open a new end of the pipe buffer.
+    pub fn new(buffer: Arc<SpinNoIrqLock<PipeBuffer>>, readable: bool, writable: bool) -> Self {
+        let mut inner = buffer.lock();
+        // Openers of the other side of a FIFO wait in the queues.
+        if readable {
+            inner.read_ends += 1;
+            inner.read_opened += 1;
+            inner.writers.wake_all();
+        }
+        if writable {
+            inner.write_ends += 1;
+            inner.write_opened += 1;
+            inner.readers.wake_all();
+        }
+        drop(inner);
         Self {
-            readable: true,
-            writable: false,
+            readable,
+            writable,
+            nonblock: AtomicBool::new(false),
             buffer,
         }
     }
+}
 
-    pub fn write_end_with_buffer(buffer: Arc<UPSafeCell<PipeRingBuffer>>) -> Self {
-        Self {
-            readable: false,
-            writable: true,
-            buffer,
+impl Drop for Pipe {
+    fn drop(&mut self) {
+        let mut inner = self.buffer.lock();
+        if self.readable {
+            inner.read_ends -= 1;
+            if inner.read_ends == 0 {
+                inner.writers.wake_all();
+            }
+        }
+        if self.writable {
+            inner.write_ends -= 1;
+            if inner.write_ends == 0 {
+                inner.readers.wake_all();
+            }
         }
     }
 }
@@ -111,68 +133,231 @@ impl File for Pipe {
         self.writable
     }
 
-    fn read(&self, buf: crate::mm::page_table::UserBuffer) -> usize {
-        assert!(self.readable, "File is not readable.");
-        let need_read = buf.len();
-        let mut buf_iter = buf.into_iter();
-        let mut already_read = 0;
+    /// Read the buffered data, wait until there is some data or all write ends are closed.
+    fn read(&self, buf: UserBuffer) -> isize {
+        if buf.len() == 0 {
+            return 0;
+        }
         loop {
-            let mut ring_buffer = self.buffer.exclusive_access();
-            let available_read = ring_buffer.available_read();
-            if available_read == 0 {
-                if ring_buffer.all_write_ends_closed() {
-                    return already_read;
+            prepare_interruptible_block();
+            let mut inner = self.buffer.lock();
+            if !inner.data.is_empty() {
+                let len = inner.data.len().min(buf.len());
+                for (dst, byte) in buf.into_iter().zip(inner.data.drain(..len)) {
+                    unsafe { *dst = byte };
                 }
-                drop(ring_buffer);
-                suspended_current_and_run_next();
-                continue;
+                inner.writers.wake_all();
+                return len as isize;
             }
-            for _ in 0..available_read {
-                if let Some(p) = buf_iter.next() {
-                    unsafe { *p = ring_buffer.read_byte() };
-                    already_read += 1;
-                    if already_read == need_read {
-                        return need_read;
-                    }
-                } else {
-                    return already_read;
-                }
+            if inner.write_ends == 0 {
+                return 0;
+            }
+            if self.nonblock() {
+                return -EAGAIN;
             }
+            if check_current_signals_error().is_some() {
+                return -EINTR;
+            }
+            inner.readers.push_current();
+            drop(inner);
+            block_current_and_run_next();
+            // Woken up by a signal, the thread is still in the wait queue.
+            self.buffer.lock().readers.remove_current();
         }
     }
 
-    fn write(&self, buf: crate::mm::page_table::UserBuffer) -> usize {
-        assert!(self.writable, "File is not writable.");
-        let need_write = buf.len();
+    /// Write all data, wait while the pipe is full.
+    /// SIGPIPE is sent to the current process if all read ends are closed.
+    fn write(&self, buf: UserBuffer) -> isize {
+        let total = buf.len();
         let mut buf_iter = buf.into_iter();
-        let mut already_write = 0;
+        let mut written = 0;
         loop {
-            let mut ring_buffer = self.buffer.exclusive_access();
-            let available_write = ring_buffer.available_write();
-            if available_write == 0 {
-                drop(ring_buffer);
-                suspended_current_and_run_next();
-                continue;
+            prepare_interruptible_block();
+            let mut inner = self.buffer.lock();
+            if inner.read_ends == 0 {
+                drop(inner);
+                send_signal(&current_process(), SignalFlags::SIGPIPE);
+                return written_or(written, EPIPE);
             }
-            for _ in 0..available_write {
-                if let Some(p) = buf_iter.next() {
-                    ring_buffer.write_byte(unsafe { *p });
-                    already_write += 1;
-                    if already_write == need_write {
-                        return need_write;
-                    }
-                } else {
-                    return already_write;
+            let space = PIPE_CAPACITY - inner.data.len();
+            // An atomic write waits until all of it fits.
+            if space >= total - written || (space > 0 && total > PIPE_BUF) {
+                let len = space.min(total - written);
+                inner
+                    .data
+                    .extend(buf_iter.by_ref().take(len).map(|src| unsafe { *src }));
+                written += len;
+                inner.readers.wake_all();
+                if written == total {
+                    return total as isize;
                 }
+                continue;
+            }
+            if self.nonblock() {
+                return written_or(written, EAGAIN);
+            }
+            if check_current_signals_error().is_some() {
+                return written_or(written, EINTR);
+            }
+            inner.writers.push_current();
+            drop(inner);
+            block_current_and_run_next();
+            // Woken up by a signal, the thread is still in the wait queue.
+            self.buffer.lock().writers.remove_current();
+        }
+    }
+
+    /// The read end is readable when there is data, and hung up when all write ends are closed.
+    /// The write end is writable when an atomic write fits, and gets an error without readers.
+    fn poll_ready(&self) -> PollEvents {
+        let inner = self.buffer.lock();
+        let mut events = PollEvents::empty();
+        if self.readable {
+            if !inner.data.is_empty() {
+                events |= PollEvents::POLLIN;
             }
+            if inner.write_ends == 0 {
+                events |= PollEvents::POLLHUP;
+            }
+        }
+        if self.writable {
+            if inner.read_ends == 0 {
+                events |= PollEvents::POLLERR;
+            } else if PIPE_CAPACITY - inner.data.len() >= PIPE_BUF {
+                events |= PollEvents::POLLOUT;
+            }
+        }
+        events
+    }
+
+    fn register_waker(&self) {
+        let mut inner = self.buffer.lock();
+        if self.readable {
+            inner.readers.push_current();
+        }
+        if self.writable {
+            inner.writers.push_current();
         }
     }
+
+    fn unregister_waker(&self) {
+        let mut inner = self.buffer.lock();
+        inner.readers.remove_current();
+        inner.writers.remove_current();
+    }
+
+    fn add_watcher(&self, watcher: Weak<dyn Watcher>) -> bool {
+        let mut inner = self.buffer.lock();
+        if self.readable {
+            inner.readers.add_watcher(watcher.clone());
+        }
+        if self.writable {
+            inner.writers.add_watcher(watcher);
+        }
+        true
+    }
+
+    fn remove_watcher(&self, watcher: &Weak<dyn Watcher>) {
+        let mut inner = self.buffer.lock();
+        inner.readers.remove_watcher(watcher);
+        inner.writers.remove_watcher(watcher);
+    }
+
+    fn nonblock(&self) -> bool {
+        self.nonblock.load(Ordering::Relaxed)
+    }
+
+    fn set_nonblock(&self, nonblock: bool) -> bool {
+        self.nonblock.store(nonblock, Ordering::Relaxed);
+        true
+    }
+
+    fn name(&self) -> String {
+        String::from(if self.readable {
+            "pipe:[read]"
+        } else {
+            "pipe:[write]"
+        })
+    }
+}
+
+/// A write which has written some data returns the length instead of an error.
+pub fn written_or(written: usize, errno: isize) -> isize {
+    if written > 0 {
+        written as isize
+    } else {
+        -errno
+    }
 }
 
+/// Create a pipe.
+/// # Return
+/// * (read end, write end)
 pub fn create_pipe() -> (Arc<Pipe>, Arc<Pipe>) {
-    let buffer = Arc::new(unsafe { UPSafeCell::new(PipeRingBuffer::new()) });
-    let read_end = Arc::new(Pipe::read_end_with_buffer(buffer.clone()));
-    let write_end = Arc::new(Pipe::write_end_with_buffer(buffer.clone()));
-    buffer.exclusive_access().set_write_end(&write_end);
+    let buffer = Arc::new(SpinNoIrqLock::new(PipeBuffer::new()));
+    let read_end = Arc::new(Pipe::new(buffer.clone(), true, false));
+    let write_end = Arc::new(Pipe::new(buffer, false, true));
     (read_end, write_end)
 }
+
+lazy_static! {
+    /// Pipe buffers of the opened FIFOs by (device id, inode number).
+    static ref FIFOS: SpinNoIrqLock<BTreeMap<(u64, u64), Weak<SpinNoIrqLock<PipeBuffer>>>> =
+        SpinNoIrqLock::new(BTreeMap::new());
+}
+
+/// Open an end of a FIFO. Without O_NONBLOCK, opening for reading waits until a writer opens,
+/// and opening for writing waits until a reader opens. Opening for both never waits.
+/// # Parameter
+/// * 'id' - (device id, inode number) of the FIFO
+/// # Return
+/// * -ENXIO - opening for writing with O_NONBLOCK, and there is no reader
+/// * -EINTR - interrupted by a signal while waiting
+pub fn open_fifo(
+    id: (u64, u64),
+    readable: bool,
+    writable: bool,
+    nonblock: bool,
+) -> Result<Arc<Pipe>, isize> {
+    let mut fifos = FIFOS.lock();
+    fifos.retain(|_, buffer| buffer.strong_count() > 0);
+    let buffer = match fifos.get(&id).and_then(Weak::upgrade) {
+        Some(buffer) => buffer,
+        None => {
+            let buffer = Arc::new(SpinNoIrqLock::new(PipeBuffer::new()));
+            fifos.insert(id, Arc::downgrade(&buffer));
+            buffer
+        }
+    };
+    drop(fifos);
+    let inner = buffer.lock();
+    let (read_opened, write_opened) = (inner.read_opened, inner.write_opened);
+    if writable && !readable && nonblock && inner.read_ends == 0 {
+        return Err(-ENXIO);
+    }
+    drop(inner);
+    let pipe = Arc::new(Pipe::new(buffer.clone(), readable, writable));
+    pipe.set_nonblock(nonblock);
+    if (readable && writable) || nonblock {
+        return Ok(pipe);
+    }
+    loop {
+        prepare_interruptible_block();
+        let mut inner = buffer.lock();
+        if readable && (inner.write_ends > 0 || inner.write_opened != write_opened) {
+            return Ok(pipe);
+        }
+        if writable && (inner.read_ends > 0 || inner.read_opened != read_opened) {
+            return Ok(pipe);
+        }
+        if check_current_signals_error().is_some() {
+            return Err(-EINTR);
+        }
+        inner.open_waiters(readable).push_current();
+        drop(inner);
+        block_current_and_run_next();
+        // Woken up by a signal, the thread is still in the wait queue.
+        buffer.lock().open_waiters(readable).remove_current();
+    }
+}
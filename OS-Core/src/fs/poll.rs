@@ -0,0 +1,68 @@
+//! Waiting for any of several files to be ready, the base of ppoll and pselect.
+//! The waiting thread registers itself as a waker of every file before checking them,
+//! so a file which becomes ready after the check still wakes it up.
+use alloc::{sync::Arc, vec::Vec};
+
+use crate::{sync::wait_queue::wait_event, timer::get_time_ms};
+
+use super::File;
+
+bitflags! {
+    /// Events of poll, the same values as linux.
+    #[derive(Clone, Copy, PartialEq, Eq)]
+    pub struct PollEvents: u16 {
+        /// There is data to read.
+        const POLLIN = 0x1;
+        /// There is urgent data to read.
+        const POLLPRI = 0x2;
+        /// Writing will not block.
+        const POLLOUT = 0x4;
+        /// Error condition, such as a pipe without readers, always reported.
+        const POLLERR = 0x8;
+        /// Hang up, such as a pipe without writers, always reported.
+        const POLLHUP = 0x10;
+        /// The fd is not open, always reported.
+        const POLLNVAL = 0x20;
+    }
+}
+
+/// Wait until any of the files is ready for the events, or the timeout expires.
+/// # Parameter
+/// * 'files' - the files and the events waited for, None is a bad fd which gets POLLNVAL
+/// * 'timeout_ms' - None waits forever, Some(0) only checks the files
+/// # Return
+/// * Ok - the ready events of every file, all empty if the timeout expired
+/// * Err(-EINTR) - interrupted by a signal
+pub fn wait(
+    files: &[(Option<Arc<dyn File + Send + Sync>>, PollEvents)],
+    timeout_ms: Option<usize>,
+) -> Result<Vec<PollEvents>, isize> {
+    let deadline = timeout_ms.map(|ms| get_time_ms() + ms);
+    let unregister = || {
+        for file in files.iter().filter_map(|(file, _)| file.as_ref()) {
+            file.unregister_waker();
+        }
+    };
+    let check = |expired| {
+        for file in files.iter().filter_map(|(file, _)| file.as_ref()) {
+            file.register_waker();
+        }
+        let revents: Vec<PollEvents> = files
+            .iter()
+            .map(|(file, events)| match file {
+                Some(file) => {
+                    let always = PollEvents::POLLERR | PollEvents::POLLHUP;
+                    file.poll_ready() & (*events | always)
+                }
+                None => PollEvents::POLLNVAL,
+            })
+            .collect();
+        if expired || revents.iter().any(|events| !events.is_empty()) {
+            unregister();
+            Some(revents)
+        } else {
+            None
+        }
+    };
+    wait_event(deadline, true, check, &unregister)
+}
@@ -0,0 +1,265 @@
+//! Process file system, mounted on /proc.
+//! Nodes are created when they are looked up, and the content of a file is generated
+//! when it is read first time, so a reader always sees a consistent snapshot.
+//! * /proc/meminfo - total and free physical memory.
+//! * /proc/mounts - mounted file systems.
+//! * /proc/uptime - seconds since boot.
+//! * /proc/self - the directory of the current process.
+//! * /proc/<pid>/status - name, state, parent, threads, signals and memory of the process.
+//! * /proc/<pid>/maps - memory areas of the process.
+//! * /proc/<pid>/fd/<fd> - what the open file is.
+use alloc::{format, string::String, sync::Arc, vec, vec::Vec};
+
+use crate::{
+    config::PAGE_SIZE,
+    mm::{frame_allocator::FRAME_ALLOCATOR, memory_set::MapPermission},
+    sync::SpinNoIrqLock,
+    task::{
+        action::SIG_IGN,
+        manager::{all_procs, proc_from_pid},
+        processor::current_process,
+        signal::MAX_SIG,
+        thread::ThreadStatus,
+        usage::cycles_to_ms,
+    },
+    timer::get_time_ms,
+};
+
+use super::vfs::{mounts, NodeType, Stat, VfsNode};
+
+#[derive(Clone, Copy)]
+enum ProcEntry {
+    Root,
+    Meminfo,
+    Mounts,
+    Uptime,
+    ProcessDir(usize),
+    Status(usize),
+    Maps(usize),
+    FdDir(usize),
+    Fd(usize, usize),
+}
+
+impl ProcEntry {
+    /// Inode number, the low 8 bits are the entry kind.
+    fn ino(&self) -> u64 {
+        let (id, kind) = match *self {
+            ProcEntry::Root => (0, 1),
+            ProcEntry::Meminfo => (0, 2),
+            ProcEntry::Mounts => (0, 3),
+            ProcEntry::Uptime => (0, 4),
+            ProcEntry::ProcessDir(pid) => (pid, 5),
+            ProcEntry::Status(pid) => (pid, 6),
+            ProcEntry::Maps(pid) => (pid, 7),
+            ProcEntry::FdDir(pid) => (pid, 8),
+            ProcEntry::Fd(pid, fd) => (pid << 16 | fd, 9),
+        };
+        (id as u64) << 8 | kind
+    }
+}
+
+/// Device id of procfs in struct stat.
+const PROC_DEV: u64 = 2;
+
+struct ProcNode {
+    entry: ProcEntry,
+    /// Generated when the file is read first time.
+    content: SpinNoIrqLock<Option<Vec<u8>>>,
+}
+
+/// The root directory of procfs.
+pub fn root() -> Arc<dyn VfsNode> {
+    ProcNode::new(ProcEntry::Root)
+}
+
+impl ProcNode {
+    fn new(entry: ProcEntry) -> Arc<dyn VfsNode> {
+        Arc::new(Self {
+            entry,
+            content: SpinNoIrqLock::new(None),
+        })
+    }
+
+    fn generate(&self) -> String {
+        match self.entry {
+            ProcEntry::Meminfo => {
+                let allocator = FRAME_ALLOCATOR.lock();
+                format!(
+                    "MemTotal:\t{} kB\nMemFree:\t{} kB\n",
+                    allocator.total_pages() * PAGE_SIZE / 1024,
+                    allocator.free_pages() * PAGE_SIZE / 1024
+                )
+            }
+            ProcEntry::Mounts => mounts()
+                .iter()
+                .map(|(fstype, path)| format!("{} {} {} rw 0 0\n", fstype, path, fstype))
+                .collect(),
+            ProcEntry::Uptime => {
+                let ms = get_time_ms();
+                format!("{}.{:02}\n", ms / 1000, ms % 1000 / 10)
+            }
+            ProcEntry::Status(pid) => process_status(pid).unwrap_or_default(),
+            ProcEntry::Maps(pid) => process_maps(pid).unwrap_or_default(),
+            ProcEntry::Fd(pid, fd) => proc_from_pid(pid)
+                .and_then(|proc| proc.inner_exclusive_access().get_file(fd))
+                .map(|file| format!("{}\n", file.name()))
+                .unwrap_or_default(),
+            _ => String::new(),
+        }
+    }
+}
+
+impl VfsNode for ProcNode {
+    fn node_type(&self) -> NodeType {
+        match self.entry {
+            ProcEntry::Root | ProcEntry::ProcessDir(_) | ProcEntry::FdDir(_) => NodeType::Dir,
+            _ => NodeType::File,
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Option<Arc<dyn VfsNode>> {
+        let entry = match (self.entry, name) {
+            (ProcEntry::Root, "meminfo") => ProcEntry::Meminfo,
+            (ProcEntry::Root, "mounts") => ProcEntry::Mounts,
+            (ProcEntry::Root, "uptime") => ProcEntry::Uptime,
+            (ProcEntry::Root, "self") => ProcEntry::ProcessDir(current_process().pid()),
+            (ProcEntry::Root, pid) => {
+                let pid = pid.parse().ok()?;
+                proc_from_pid(pid)?;
+                ProcEntry::ProcessDir(pid)
+            }
+            (ProcEntry::ProcessDir(pid), "status") => ProcEntry::Status(pid),
+            (ProcEntry::ProcessDir(pid), "maps") => ProcEntry::Maps(pid),
+            (ProcEntry::ProcessDir(pid), "fd") => ProcEntry::FdDir(pid),
+            (ProcEntry::FdDir(pid), fd) => {
+                let fd = fd.parse::<usize>().ok()?;
+                proc_from_pid(pid)?.inner_exclusive_access().get_file(fd)?;
+                ProcEntry::Fd(pid, fd)
+            }
+            _ => return None,
+        };
+        Some(ProcNode::new(entry))
+    }
+
+    fn readdir(&self) -> Vec<(String, NodeType)> {
+        match self.entry {
+            ProcEntry::Root => {
+                let mut entries = vec![
+                    (String::from("meminfo"), NodeType::File),
+                    (String::from("mounts"), NodeType::File),
+                    (String::from("uptime"), NodeType::File),
+                    (String::from("self"), NodeType::Dir),
+                ];
+                for proc in all_procs() {
+                    entries.push((format!("{}", proc.pid()), NodeType::Dir));
+                }
+                entries
+            }
+            ProcEntry::ProcessDir(_) => vec![
+                (String::from("status"), NodeType::File),
+                (String::from("maps"), NodeType::File),
+                (String::from("fd"), NodeType::Dir),
+            ],
+            ProcEntry::FdDir(pid) => match proc_from_pid(pid) {
+                Some(proc) => proc
+                    .inner_exclusive_access()
+                    .fd_table
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, file)| file.is_some())
+                    .map(|(fd, _)| (format!("{}", fd), NodeType::File))
+                    .collect(),
+                None => Vec::new(),
+            },
+            _ => Vec::new(),
+        }
+    }
+
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        let mut content = self.content.lock();
+        let content = content.get_or_insert_with(|| self.generate().into_bytes());
+        if offset >= content.len() {
+            return 0;
+        }
+        let len = buf.len().min(content.len() - offset);
+        buf[..len].copy_from_slice(&content[offset..offset + len]);
+        len
+    }
+
+    /// Files are generated when read, so their size is 0.
+    fn stat(&self) -> Stat {
+        Stat::new(PROC_DEV, self.entry.ino(), self.node_type(), 0)
+    }
+}
+
+/// Content of /proc/<pid>/status.
+fn process_status(pid: usize) -> Option<String> {
+    let proc = proc_from_pid(pid)?;
+    let inner = proc.inner_exclusive_access();
+    let statuses: Vec<ThreadStatus> = inner
+        .threads
+        .iter()
+        .flatten()
+        .map(|thread| thread.inner_exclusive_access().status)
+        .collect();
+    let state = if inner.is_zombie {
+        "Z (zombie)"
+    } else if inner.stopped {
+        "T (stopped)"
+    } else if statuses
+        .iter()
+        .any(|status| matches!(status, ThreadStatus::Running | ThreadStatus::Ready))
+    {
+        "R (running)"
+    } else {
+        "S (sleeping)"
+    };
+    let ppid = inner
+        .parent
+        .as_ref()
+        .and_then(|parent| parent.upgrade())
+        .map_or(0, |parent| parent.pid());
+    let ignored = (1..=MAX_SIG)
+        .filter(|signum| inner.signal_actions.table[*signum].handler == SIG_IGN)
+        .fold(0u32, |mask, signum| mask | 1 << signum);
+    let time = inner.cpu_time();
+    Some(format!(
+        "Name:\t{}\nState:\t{}\nPid:\t{}\nPPid:\t{}\nPgid:\t{}\nSid:\t{}\nThreads:\t{}\n\
+         SigPnd:\t{:08x}\nSigIgn:\t{:08x}\nVmRSS:\t{} kB\nVmHWM:\t{} kB\nUtime:\t{} ms\nStime:\t{} ms\n",
+        inner.name,
+        state,
+        pid,
+        ppid,
+        inner.pgid,
+        inner.sid,
+        statuses.len(),
+        inner.signals.bits(),
+        ignored,
+        inner.memory_set.frame_count() * PAGE_SIZE / 1024,
+        inner.memory_set.peak_frames() * PAGE_SIZE / 1024,
+        cycles_to_ms(time.user),
+        cycles_to_ms(time.kernel),
+    ))
+}
+
+/// Content of /proc/<pid>/maps, every line is "start-end permission resident_size".
+fn process_maps(pid: usize) -> Option<String> {
+    let proc = proc_from_pid(pid)?;
+    let inner = proc.inner_exclusive_access();
+    let mut maps = String::new();
+    for area in inner.memory_set.areas() {
+        let perm = area.permission();
+        let flag = |bit: MapPermission, c: char| if perm.contains(bit) { c } else { '-' };
+        maps += &format!(
+            "{:08x}-{:08x} {}{}{}{} {} kB\n",
+            usize::from(area.start_va()),
+            usize::from(area.end_va()),
+            flag(MapPermission::R, 'r'),
+            flag(MapPermission::W, 'w'),
+            flag(MapPermission::X, 'x'),
+            flag(MapPermission::U, 'u'),
+            area.frame_count() * PAGE_SIZE / 1024,
+        );
+    }
+    Some(maps)
+}
@@ -0,0 +1,179 @@
+//! Signalfd, pending signals of the process read from a fd.
+//! A read takes the pending signals in the mask out of the process of the reader,
+//! so they are handled by the reader instead of their default actions.
+//! The kernel can not block signals, a signal which terminates the process
+//! still terminates it before it can be read.
+use alloc::{string::String, sync::Weak, vec::Vec};
+use core::{
+    any::Any,
+    sync::atomic::{AtomicBool, AtomicU32, Ordering},
+};
+
+use crate::{
+    mm::page_table::UserBuffer,
+    sync::wait_queue::Watcher,
+    syscall::errno::{EAGAIN, EINTR, EINVAL},
+    task::{
+        block_current_and_run_next, check_current_signals_error, prepare_interruptible_block,
+        processor::current_process, signal::SignalFlags,
+    },
+};
+
+use super::{poll::PollEvents, File};
+
+bitflags! {
+    /// Flags of signalfd4, the same values as linux.
+    #[derive(Clone, Copy, PartialEq, Eq)]
+    pub struct SignalfdFlags: u32 {
+        const SFD_NONBLOCK = 1 << 11;
+        const SFD_CLOEXEC = 1 << 19;
+    }
+}
+
+/// The size of linux struct signalfd_siginfo, only ssi_signo (the first u32) is filled.
+pub const SIGINFO_SIZE: usize = 128;
+
+pub struct Signalfd {
+    /// Bits of SignalFlags, SIGKILL and SIGSTOP are never read.
+    mask: AtomicU32,
+    /// Return EAGAIN instead of blocking.
+    nonblock: AtomicBool,
+}
+
+impl Signalfd {
+    pub fn new(mask: SignalFlags, flags: SignalfdFlags) -> Self {
+        let signalfd = Self {
+            mask: AtomicU32::new(0),
+            nonblock: AtomicBool::new(flags.contains(SignalfdFlags::SFD_NONBLOCK)),
+        };
+        signalfd.set_mask(mask);
+        signalfd
+    }
+
+    /// Replace the signals to be read.
+    pub fn set_mask(&self, mask: SignalFlags) {
+        let mask = mask.difference(SignalFlags::SIGKILL | SignalFlags::SIGSTOP);
+        self.mask.store(mask.bits(), Ordering::Relaxed);
+    }
+
+    fn mask(&self) -> SignalFlags {
+        SignalFlags::from_bits_truncate(self.mask.load(Ordering::Relaxed))
+    }
+}
+
+impl File for Signalfd {
+    fn readable(&self) -> bool {
+        true
+    }
+
+    fn writable(&self) -> bool {
+        false
+    }
+
+    /// Read a signalfd_siginfo for every pending signal in the mask, as many as buf can hold.
+    /// Wait until a signal in the mask is pending.
+    /// # Return
+    /// * -EINVAL - buf can not hold a signalfd_siginfo
+    fn read(&self, buf: UserBuffer) -> isize {
+        let max = buf.len() / SIGINFO_SIZE;
+        if max == 0 {
+            return -EINVAL;
+        }
+        let proc = current_process();
+        let signums = loop {
+            prepare_interruptible_block();
+            // Lock the waiters before checking, so a signal sent after the check wakes us up.
+            let mut waiters = proc.signal_waiters.lock();
+            let mut inner = proc.inner_exclusive_access();
+            let mut signums = Vec::new();
+            while signums.len() < max {
+                let pending = inner.signals & self.mask();
+                if pending.is_empty() {
+                    break;
+                }
+                let signum = pending.signum();
+                inner
+                    .signals
+                    .remove(SignalFlags::from_bits_truncate(1 << signum));
+                signums.push(signum);
+            }
+            drop(inner);
+            if !signums.is_empty() {
+                break signums;
+            }
+            if self.nonblock() {
+                return -EAGAIN;
+            }
+            if check_current_signals_error().is_some() {
+                return -EINTR;
+            }
+            waiters.push_current();
+            drop(waiters);
+            block_current_and_run_next();
+            // Woken up by a signal, the thread is still in the wait queue.
+            proc.signal_waiters.lock().remove_current();
+        };
+        let mut siginfo = [0u8; SIGINFO_SIZE];
+        let bytes = signums.iter().flat_map(|signum| {
+            siginfo[..4].copy_from_slice(&signum.to_ne_bytes());
+            siginfo
+        });
+        for (dst, byte) in buf.into_iter().zip(bytes) {
+            unsafe { *dst = byte };
+        }
+        (signums.len() * SIGINFO_SIZE) as isize
+    }
+
+    #[allow(unused)]
+    fn write(&self, buf: UserBuffer) -> isize {
+        -EINVAL
+    }
+
+    /// Readable when a signal in the mask is pending in the current process.
+    fn poll_ready(&self) -> PollEvents {
+        let signals = current_process().inner_exclusive_access().signals;
+        if signals.intersects(self.mask()) {
+            PollEvents::POLLIN
+        } else {
+            PollEvents::empty()
+        }
+    }
+
+    fn register_waker(&self) {
+        current_process().signal_waiters.lock().push_current();
+    }
+
+    fn unregister_waker(&self) {
+        current_process().signal_waiters.lock().remove_current();
+    }
+
+    /// The watcher is notified by signals of the current process.
+    fn add_watcher(&self, watcher: Weak<dyn Watcher>) -> bool {
+        current_process().signal_waiters.lock().add_watcher(watcher);
+        true
+    }
+
+    fn remove_watcher(&self, watcher: &Weak<dyn Watcher>) {
+        current_process()
+            .signal_waiters
+            .lock()
+            .remove_watcher(watcher);
+    }
+
+    fn nonblock(&self) -> bool {
+        self.nonblock.load(Ordering::Relaxed)
+    }
+
+    fn set_nonblock(&self, nonblock: bool) -> bool {
+        self.nonblock.store(nonblock, Ordering::Relaxed);
+        true
+    }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+
+    fn name(&self) -> String {
+        String::from("anon_inode:[signalfd]")
+    }
+}
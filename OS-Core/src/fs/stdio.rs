@@ -1,9 +1,20 @@
-use crate::{console::get_char, print, task::suspended_current_and_run_next};
+use alloc::{string::String, sync::Weak, vec};
 
-use super::File;
+use crate::{print, sync::wait_queue::Watcher};
+
+use super::{
+    poll::PollEvents,
+    tty::{
+        tty_add_watcher, tty_ioctl, tty_poll_ready, tty_read, tty_register_waker,
+        tty_remove_watcher, tty_unregister_waker,
+    },
+    File,
+};
 
 pub struct Stdin;
 pub struct Stdout;
+/// The terminal opened from /dev/console or /dev/tty, it can be read and written.
+pub struct Console;
 
 impl File for Stdin {
     fn readable(&self) -> bool {
@@ -14,25 +25,56 @@ impl File for Stdin {
         false
     }
 
-    fn read(&self, mut buf: crate::mm::page_table::UserBuffer) -> usize {
-        assert_eq!(buf.len(), 1);
-        let mut c;
-        loop {
-            c = get_char();
-            if c != 0 {
-                break;
-            }
-            suspended_current_and_run_next();
+    fn read(&self, buf: crate::mm::page_table::UserBuffer) -> isize {
+        if buf.len() == 0 {
+            return 0;
         }
-        unsafe {
-            buf.buffers[0].as_mut_ptr().write_volatile(c);
+        let mut data = vec![0u8; buf.len()];
+        let len = tty_read(&mut data);
+        if len < 0 {
+            return len;
+        }
+        let len = len as usize;
+        for (dst, src) in buf.into_iter().zip(data[..len].iter()) {
+            unsafe {
+                *dst = *src;
+            }
         }
-        1
+        len as isize
     }
     #[allow(unused)]
-    fn write(&self, buf: crate::mm::page_table::UserBuffer) -> usize {
+    fn write(&self, buf: crate::mm::page_table::UserBuffer) -> isize {
         panic!("Can not write to Stdin.")
     }
+
+    fn ioctl(&self, cmd: usize, arg: usize) -> isize {
+        tty_ioctl(cmd, arg)
+    }
+
+    fn poll_ready(&self) -> PollEvents {
+        tty_poll_ready()
+    }
+
+    fn register_waker(&self) {
+        tty_register_waker();
+    }
+
+    fn unregister_waker(&self) {
+        tty_unregister_waker();
+    }
+
+    fn add_watcher(&self, watcher: Weak<dyn Watcher>) -> bool {
+        tty_add_watcher(watcher);
+        true
+    }
+
+    fn remove_watcher(&self, watcher: &Weak<dyn Watcher>) {
+        tty_remove_watcher(watcher);
+    }
+
+    fn name(&self) -> String {
+        String::from("tty")
+    }
 }
 
 impl File for Stdout {
@@ -45,14 +87,69 @@ impl File for Stdout {
     }
 
     #[allow(unused)]
-    fn read(&self, buf: crate::mm::page_table::UserBuffer) -> usize {
+    fn read(&self, buf: crate::mm::page_table::UserBuffer) -> isize {
         panic!("Can not read from Stdout.")
     }
 
-    fn write(&self, buf: crate::mm::page_table::UserBuffer) -> usize {
+    fn write(&self, buf: crate::mm::page_table::UserBuffer) -> isize {
         for slice in buf.buffers.iter() {
             print!("{}", core::str::from_utf8(*slice).unwrap());
         }
-        buf.len()
+        buf.len() as isize
+    }
+
+    fn ioctl(&self, cmd: usize, arg: usize) -> isize {
+        tty_ioctl(cmd, arg)
+    }
+
+    fn name(&self) -> String {
+        String::from("tty")
+    }
+}
+
+impl File for Console {
+    fn readable(&self) -> bool {
+        true
+    }
+
+    fn writable(&self) -> bool {
+        true
+    }
+
+    fn read(&self, buf: crate::mm::page_table::UserBuffer) -> isize {
+        Stdin.read(buf)
+    }
+
+    fn write(&self, buf: crate::mm::page_table::UserBuffer) -> isize {
+        Stdout.write(buf)
+    }
+
+    fn ioctl(&self, cmd: usize, arg: usize) -> isize {
+        tty_ioctl(cmd, arg)
+    }
+
+    fn poll_ready(&self) -> PollEvents {
+        tty_poll_ready() | PollEvents::POLLOUT
+    }
+
+    fn register_waker(&self) {
+        tty_register_waker();
+    }
+
+    fn unregister_waker(&self) {
+        tty_unregister_waker();
+    }
+
+    fn add_watcher(&self, watcher: Weak<dyn Watcher>) -> bool {
+        tty_add_watcher(watcher);
+        true
+    }
+
+    fn remove_watcher(&self, watcher: &Weak<dyn Watcher>) {
+        tty_remove_watcher(watcher);
+    }
+
+    fn name(&self) -> String {
+        String::from("/dev/console")
     }
 }
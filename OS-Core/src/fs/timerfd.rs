@@ -0,0 +1,266 @@
+//! Timerfd, a timer whose expirations are read from a fd.
+//! An armed timerfd puts a handler to the timer heap, the handler counts the expirations,
+//! wakes up the readers and puts the next handler for an interval timer.
+//! Every timerfd_settime starts a new generation, the handlers of old generations do nothing.
+use alloc::{
+    string::String,
+    sync::{Arc, Weak},
+};
+use core::{
+    any::Any,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use crate::{
+    mm::page_table::UserBuffer,
+    sync::{
+        wait_queue::{WaitQueue, Watcher},
+        SpinNoIrqLock,
+    },
+    syscall::errno::{EAGAIN, EINTR, EINVAL},
+    task::{block_current_and_run_next, check_current_signals_error, prepare_interruptible_block},
+    timer::{add_timer_handler, get_time_ms, TimeSpec, TimerHandler},
+};
+
+use super::{poll::PollEvents, File};
+
+bitflags! {
+    /// Flags of timerfd_create, the same values as linux.
+    #[derive(Clone, Copy, PartialEq, Eq)]
+    pub struct TimerfdFlags: u32 {
+        const TFD_NONBLOCK = 1 << 11;
+        const TFD_CLOEXEC = 1 << 19;
+    }
+}
+
+/// Flag of timerfd_settime, the value of the new setting is an absolute time.
+pub const TFD_TIMER_ABSTIME: u32 = 1;
+/// Both clocks count from the boot, the kernel has no real time clock.
+pub const CLOCK_REALTIME: usize = 0;
+pub const CLOCK_MONOTONIC: usize = 1;
+
+/// The setting of a timer, the same layout as linux struct itimerspec.
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct ITimerSpec {
+    /// The period of the timer, zero for a one shot timer.
+    pub interval: TimeSpec,
+    /// The time until the next expiration, zero if the timer is disarmed.
+    pub value: TimeSpec,
+}
+
+struct TimerfdInner {
+    /// The next expiration in milliseconds, None if the timer is disarmed.
+    next_ms: Option<usize>,
+    interval_ms: usize,
+    /// Expirations which have not been read.
+    expirations: u64,
+    generation: usize,
+    /// Readers waiting for an expiration.
+    readers: WaitQueue,
+}
+
+impl TimerfdInner {
+    /// The setting of the timer now.
+    fn setting(&self, now: usize) -> ITimerSpec {
+        ITimerSpec {
+            interval: TimeSpec::from_ms(self.interval_ms),
+            value: TimeSpec::from_ms(self.next_ms.map_or(0, |next| next.saturating_sub(now))),
+        }
+    }
+}
+
+/// Put to the timer heap for the next expiration.
+struct TimerfdHandler {
+    timerfd: Weak<SpinNoIrqLock<TimerfdInner>>,
+    generation: usize,
+}
+
+impl TimerfdHandler {
+    fn arm(timerfd: &Arc<SpinNoIrqLock<TimerfdInner>>, generation: usize, expire_ms: usize) {
+        add_timer_handler(
+            expire_ms,
+            Arc::new(Self {
+                timerfd: Arc::downgrade(timerfd),
+                generation,
+            }),
+        );
+    }
+}
+
+impl TimerHandler for TimerfdHandler {
+    fn expire(&self) {
+        let Some(timerfd) = self.timerfd.upgrade() else {
+            return;
+        };
+        let mut inner = timerfd.lock();
+        if inner.generation != self.generation {
+            return;
+        }
+        let now = get_time_ms();
+        let Some(next) = inner.next_ms.filter(|next| *next <= now) else {
+            return;
+        };
+        // Expirations missed by a late timer interrupt are counted too.
+        let count = if inner.interval_ms == 0 {
+            inner.next_ms = None;
+            1
+        } else {
+            let count = (now - next) / inner.interval_ms + 1;
+            inner.next_ms = Some(next + count * inner.interval_ms);
+            count
+        };
+        inner.expirations += count as u64;
+        inner.readers.wake_all();
+        let next = inner.next_ms;
+        drop(inner);
+        if let Some(next) = next {
+            Self::arm(&timerfd, self.generation, next);
+        }
+    }
+}
+
+pub struct Timerfd {
+    /// Return EAGAIN instead of blocking.
+    nonblock: AtomicBool,
+    inner: Arc<SpinNoIrqLock<TimerfdInner>>,
+}
+
+impl Timerfd {
+    /// Create a disarmed timer.
+    pub fn new(flags: TimerfdFlags) -> Self {
+        Self {
+            nonblock: AtomicBool::new(flags.contains(TimerfdFlags::TFD_NONBLOCK)),
+            inner: Arc::new(SpinNoIrqLock::new(TimerfdInner {
+                next_ms: None,
+                interval_ms: 0,
+                expirations: 0,
+                generation: 0,
+                readers: WaitQueue::new(),
+            })),
+        }
+    }
+
+    /// Arm or disarm the timer, the expirations which have not been read are dropped.
+    /// # Parameter
+    /// * 'absolute' - the value of the setting is the time since the boot
+    /// * 'setting' - a zero value disarms the timer
+    /// # Return
+    /// * The old setting.
+    pub fn set(&self, absolute: bool, setting: &ITimerSpec) -> ITimerSpec {
+        let mut inner = self.inner.lock();
+        let now = get_time_ms();
+        let old = inner.setting(now);
+        inner.generation += 1;
+        inner.expirations = 0;
+        inner.interval_ms = setting.interval.to_ms();
+        inner.next_ms = if setting.value.is_zero() {
+            None
+        } else if absolute {
+            Some(setting.value.to_ms())
+        } else {
+            Some(now + setting.value.to_ms())
+        };
+        let (generation, next) = (inner.generation, inner.next_ms);
+        drop(inner);
+        if let Some(next) = next {
+            TimerfdHandler::arm(&self.inner, generation, next);
+        }
+        old
+    }
+
+    /// Get the time until the next expiration and the interval.
+    pub fn get(&self) -> ITimerSpec {
+        self.inner.lock().setting(get_time_ms())
+    }
+}
+
+impl File for Timerfd {
+    fn readable(&self) -> bool {
+        true
+    }
+
+    fn writable(&self) -> bool {
+        false
+    }
+
+    /// Read the number of expirations as 8 bytes, wait until the timer expires.
+    /// # Return
+    /// * -EINVAL - buf is shorter than 8 bytes
+    fn read(&self, buf: UserBuffer) -> isize {
+        if buf.len() < 8 {
+            return -EINVAL;
+        }
+        loop {
+            prepare_interruptible_block();
+            let mut inner = self.inner.lock();
+            if inner.expirations > 0 {
+                let expirations = core::mem::take(&mut inner.expirations);
+                drop(inner);
+                for (dst, byte) in buf.into_iter().zip(expirations.to_ne_bytes()) {
+                    unsafe { *dst = byte };
+                }
+                return 8;
+            }
+            if self.nonblock() {
+                return -EAGAIN;
+            }
+            if check_current_signals_error().is_some() {
+                return -EINTR;
+            }
+            inner.readers.push_current();
+            drop(inner);
+            block_current_and_run_next();
+            // Woken up by a signal, the thread is still in the wait queue.
+            self.inner.lock().readers.remove_current();
+        }
+    }
+
+    #[allow(unused)]
+    fn write(&self, buf: UserBuffer) -> isize {
+        -EINVAL
+    }
+
+    /// Readable when the timer has expired since the last read.
+    fn poll_ready(&self) -> PollEvents {
+        if self.inner.lock().expirations > 0 {
+            PollEvents::POLLIN
+        } else {
+            PollEvents::empty()
+        }
+    }
+
+    fn register_waker(&self) {
+        self.inner.lock().readers.push_current();
+    }
+
+    fn unregister_waker(&self) {
+        self.inner.lock().readers.remove_current();
+    }
+
+    fn add_watcher(&self, watcher: Weak<dyn Watcher>) -> bool {
+        self.inner.lock().readers.add_watcher(watcher);
+        true
+    }
+
+    fn remove_watcher(&self, watcher: &Weak<dyn Watcher>) {
+        self.inner.lock().readers.remove_watcher(watcher);
+    }
+
+    fn nonblock(&self) -> bool {
+        self.nonblock.load(Ordering::Relaxed)
+    }
+
+    fn set_nonblock(&self, nonblock: bool) -> bool {
+        self.nonblock.store(nonblock, Ordering::Relaxed);
+        true
+    }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+
+    fn name(&self) -> String {
+        String::from("anon_inode:[timerfd]")
+    }
+}
@@ -0,0 +1,262 @@
+//! Temporary file system in memory, mounted on /tmp.
+//! File data is kept in physical frames from FRAME_ALLOCATOR, pages are allocated when
+//! they are written first time, so holes of a file take no memory and read as zero.
+//! Every tmpfs has a limit of pages, a write stops when the limit is reached or no frame is free.
+use alloc::{collections::BTreeMap, string::String, sync::Arc, vec::Vec};
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+use crate::{
+    config::PAGE_SIZE,
+    mm::frame_allocator::{frame_alloc, FrameTracker, FRAME_ALLOCATOR},
+    sync::SpinNoIrqLock,
+};
+
+use super::vfs::{NodeType, Stat, VfsNode};
+
+/// Device id of tmpfs in struct stat.
+const TMPFS_DEV: u64 = 3;
+
+/// Shared by all nodes of a tmpfs.
+struct TmpFs {
+    /// The max number of pages of file data.
+    max_pages: usize,
+    used_pages: AtomicUsize,
+    next_ino: AtomicU64,
+}
+
+impl TmpFs {
+    /// Allocate a page for file data.
+    /// # Return
+    /// * None - the limit is reached or no frame is free.
+    fn alloc_page(&self) -> Option<FrameTracker> {
+        self.used_pages
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| {
+                if used < self.max_pages {
+                    Some(used + 1)
+                } else {
+                    None
+                }
+            })
+            .ok()?;
+        let frame = frame_alloc();
+        if frame.is_none() {
+            self.used_pages.fetch_sub(1, Ordering::Relaxed);
+        }
+        frame
+    }
+
+    /// Pages are freed by dropping their FrameTracker, this only counts them.
+    fn release_pages(&self, count: usize) {
+        self.used_pages.fetch_sub(count, Ordering::Relaxed);
+    }
+}
+
+enum TmpContent {
+    Dir(BTreeMap<String, Arc<TmpNode>>),
+    File {
+        size: usize,
+        /// Page index in the file -> the page, bytes after size are always zero.
+        pages: BTreeMap<usize, FrameTracker>,
+    },
+}
+
+struct TmpNode {
+    fs: Arc<TmpFs>,
+    ino: u64,
+    content: SpinNoIrqLock<TmpContent>,
+}
+
+/// Create a tmpfs.
+/// # Parameter
+/// * 'data' - mount options separated by ',', "size=<bytes>" with an optional k/m/g suffix
+/// sets the limit, the default limit is half of physical memory.
+/// # Return
+/// * None - bad options.
+pub fn new(data: &str) -> Option<Arc<dyn VfsNode>> {
+    let mut max_pages = FRAME_ALLOCATOR.lock().total_pages() / 2;
+    for option in data.split(',').filter(|option| !option.is_empty()) {
+        let size = option.strip_prefix("size=")?;
+        let (digits, unit) = match size.as_bytes().last()? {
+            b'k' | b'K' => (&size[..size.len() - 1], 1 << 10),
+            b'm' | b'M' => (&size[..size.len() - 1], 1 << 20),
+            b'g' | b'G' => (&size[..size.len() - 1], 1 << 30),
+            _ => (size, 1),
+        };
+        max_pages = digits
+            .parse::<usize>()
+            .ok()?
+            .checked_mul(unit)?
+            .div_ceil(PAGE_SIZE);
+    }
+    let fs = Arc::new(TmpFs {
+        max_pages,
+        used_pages: AtomicUsize::new(0),
+        next_ino: AtomicU64::new(1),
+    });
+    Some(TmpNode::new(fs, NodeType::Dir))
+}
+
+impl TmpNode {
+    fn new(fs: Arc<TmpFs>, node_type: NodeType) -> Arc<Self> {
+        let content = match node_type {
+            NodeType::Dir => TmpContent::Dir(BTreeMap::new()),
+            _ => TmpContent::File {
+                size: 0,
+                pages: BTreeMap::new(),
+            },
+        };
+        Arc::new(Self {
+            ino: fs.next_ino.fetch_add(1, Ordering::Relaxed),
+            fs,
+            content: SpinNoIrqLock::new(content),
+        })
+    }
+}
+
+impl Drop for TmpNode {
+    fn drop(&mut self) {
+        if let TmpContent::File { pages, .. } = &*self.content.lock() {
+            self.fs.release_pages(pages.len());
+        }
+    }
+}
+
+impl VfsNode for TmpNode {
+    fn node_type(&self) -> NodeType {
+        match &*self.content.lock() {
+            TmpContent::Dir(_) => NodeType::Dir,
+            TmpContent::File { .. } => NodeType::File,
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Option<Arc<dyn VfsNode>> {
+        match &*self.content.lock() {
+            TmpContent::Dir(children) => children
+                .get(name)
+                .map(|child| child.clone() as Arc<dyn VfsNode>),
+            TmpContent::File { .. } => None,
+        }
+    }
+
+    fn create(&self, name: &str, node_type: NodeType) -> Option<Arc<dyn VfsNode>> {
+        let mut content = self.content.lock();
+        let TmpContent::Dir(children) = &mut *content else {
+            return None;
+        };
+        if name.is_empty()
+            || children.contains_key(name)
+            || !matches!(node_type, NodeType::File | NodeType::Dir)
+        {
+            return None;
+        }
+        let child = TmpNode::new(self.fs.clone(), node_type);
+        children.insert(String::from(name), child.clone());
+        Some(child)
+    }
+
+    fn unlink(&self, name: &str) -> bool {
+        let mut content = self.content.lock();
+        let TmpContent::Dir(children) = &mut *content else {
+            return false;
+        };
+        match children.get(name) {
+            Some(child) => match &*child.content.lock() {
+                TmpContent::Dir(grandchildren) if !grandchildren.is_empty() => return false,
+                _ => {}
+            },
+            None => return false,
+        }
+        // The data is freed when the last opened file of it is closed.
+        children.remove(name);
+        true
+    }
+
+    fn readdir(&self) -> Vec<(String, NodeType)> {
+        match &*self.content.lock() {
+            TmpContent::Dir(children) => children
+                .iter()
+                .map(|(name, child)| (name.clone(), child.node_type()))
+                .collect(),
+            TmpContent::File { .. } => Vec::new(),
+        }
+    }
+
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        let content = self.content.lock();
+        let TmpContent::File { size, pages } = &*content else {
+            return 0;
+        };
+        if offset >= *size {
+            return 0;
+        }
+        let end = (offset + buf.len()).min(*size);
+        let mut pos = offset;
+        while pos < end {
+            let page_offset = pos % PAGE_SIZE;
+            let len = (PAGE_SIZE - page_offset).min(end - pos);
+            let dst = &mut buf[pos - offset..pos - offset + len];
+            match pages.get(&(pos / PAGE_SIZE)) {
+                Some(frame) => dst.copy_from_slice(
+                    &frame.ppn.get_physical_page_bytes_array()[page_offset..page_offset + len],
+                ),
+                None => dst.fill(0),
+            }
+            pos += len;
+        }
+        end - offset
+    }
+
+    fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
+        let mut content = self.content.lock();
+        let TmpContent::File { size, pages } = &mut *content else {
+            return 0;
+        };
+        let end = offset + buf.len();
+        let mut pos = offset;
+        while pos < end {
+            let index = pos / PAGE_SIZE;
+            if !pages.contains_key(&index) {
+                match self.fs.alloc_page() {
+                    Some(frame) => pages.insert(index, frame),
+                    None => break,
+                };
+            }
+            let page_offset = pos % PAGE_SIZE;
+            let len = (PAGE_SIZE - page_offset).min(end - pos);
+            pages[&index].ppn.get_physical_page_bytes_array()[page_offset..page_offset + len]
+                .copy_from_slice(&buf[pos - offset..pos - offset + len]);
+            pos += len;
+        }
+        if pos > *size {
+            *size = pos;
+        }
+        pos - offset
+    }
+
+    fn truncate(&self, new_size: usize) -> bool {
+        let mut content = self.content.lock();
+        let TmpContent::File { size, pages } = &mut *content else {
+            return false;
+        };
+        if new_size < *size {
+            let removed = pages.split_off(&new_size.div_ceil(PAGE_SIZE));
+            self.fs.release_pages(removed.len());
+            // Keep the bytes after size zero, they are read if the file grows again.
+            if new_size % PAGE_SIZE != 0 {
+                if let Some(frame) = pages.get(&(new_size / PAGE_SIZE)) {
+                    frame.ppn.get_physical_page_bytes_array()[new_size % PAGE_SIZE..].fill(0);
+                }
+            }
+        }
+        *size = new_size;
+        true
+    }
+
+    fn stat(&self) -> Stat {
+        let (node_type, size) = match &*self.content.lock() {
+            TmpContent::Dir(_) => (NodeType::Dir, 0),
+            TmpContent::File { size, .. } => (NodeType::File, *size),
+        };
+        Stat::new(TMPFS_DEV, self.ino, node_type, size)
+    }
+}
@@ -0,0 +1,440 @@
+//! Line discipline between the SBI console and user programs.
+//! Characters are pulled from the console on every timer tick (and while someone is reading),
+//! edited in canonical mode, echoed, and special characters are turned into signals
+//! for the foreground process group.
+//! The terminal is the controlling terminal of at most one session,
+//! processes of that session outside the foreground group are stopped by SIGTTIN when reading.
+use alloc::{collections::VecDeque, sync::Weak, vec::Vec};
+use lazy_static::lazy_static;
+
+use crate::{
+    console::get_char,
+    mm::page_table::PageTable,
+    print,
+    sbi_services::console_putchar,
+    sync::{
+        wait_queue::{WaitQueue, Watcher},
+        SpinNoIrqLock,
+    },
+    syscall::errno::{EINTR, EIO},
+    task::{
+        check_current_signals_error, handle_stop_signals, manager::procs_in_group,
+        processor::current_process, processor::current_user_token, send_signal_to_group,
+        signal::SignalFlags, suspended_current_and_run_next,
+    },
+};
+
+use super::poll::PollEvents;
+
+/// ioctl: get the current termios.
+pub const TCGETS: usize = 0x5401;
+/// ioctl: set the current termios.
+pub const TCSETS: usize = 0x5402;
+/// ioctl: get the foreground process group.
+pub const TIOCGPGRP: usize = 0x540F;
+/// ioctl: set the foreground process group.
+pub const TIOCSPGRP: usize = 0x5410;
+/// ioctl: make the terminal the controlling terminal of the session.
+pub const TIOCSCTTY: usize = 0x540E;
+/// ioctl: give up the controlling terminal.
+pub const TIOCNOTTY: usize = 0x5422;
+
+pub const NCCS: usize = 19;
+pub const VINTR: usize = 0;
+pub const VQUIT: usize = 1;
+pub const VERASE: usize = 2;
+pub const VKILL: usize = 3;
+pub const VEOF: usize = 4;
+pub const VMIN: usize = 6;
+pub const VSUSP: usize = 10;
+
+bitflags! {
+    /// Input mode flags.
+    #[derive(Clone, Copy)]
+    pub struct InputFlags: u32 {
+        /// Translate carriage return to newline on input.
+        const ICRNL = 0o400;
+    }
+}
+
+bitflags! {
+    /// Local mode flags.
+    #[derive(Clone, Copy)]
+    pub struct LocalFlags: u32 {
+        /// Generate signals for INTR, QUIT and SUSP characters.
+        const ISIG = 0o1;
+        /// Canonical mode, input is made available line by line.
+        const ICANON = 0o2;
+        /// Echo input characters.
+        const ECHO = 0o10;
+        /// ERASE character erases the preceding character on screen.
+        const ECHOE = 0o20;
+        /// KILL character erases the current line on screen.
+        const ECHOK = 0o40;
+    }
+}
+
+/// Terminal attributes, same layout as the linux kernel termios.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct Termios {
+    pub iflag: u32,
+    pub oflag: u32,
+    pub cflag: u32,
+    pub lflag: u32,
+    pub line: u8,
+    pub cc: [u8; NCCS],
+}
+
+impl Default for Termios {
+    fn default() -> Self {
+        let mut cc = [0u8; NCCS];
+        cc[VINTR] = 0x03; // ^C
+        cc[VQUIT] = 0x1c; // ^\
+        cc[VERASE] = 0x7f; // DEL
+        cc[VKILL] = 0x15; // ^U
+        cc[VEOF] = 0x04; // ^D
+        cc[VMIN] = 1;
+        cc[VSUSP] = 0x1a; // ^Z
+        Self {
+            iflag: InputFlags::ICRNL.bits(),
+            oflag: 0,
+            cflag: 0,
+            lflag: (LocalFlags::ISIG
+                | LocalFlags::ICANON
+                | LocalFlags::ECHO
+                | LocalFlags::ECHOE
+                | LocalFlags::ECHOK)
+                .bits(),
+            line: 0,
+            cc,
+        }
+    }
+}
+
+impl Termios {
+    fn lflag(&self) -> LocalFlags {
+        LocalFlags::from_bits_truncate(self.lflag)
+    }
+    fn iflag(&self) -> InputFlags {
+        InputFlags::from_bits_truncate(self.iflag)
+    }
+}
+
+pub struct Tty {
+    termios: Termios,
+    /// The line being edited in canonical mode.
+    line: Vec<u8>,
+    /// Input ready to be read. In canonical mode every chunk is one line,
+    /// an empty chunk means end of file.
+    ready: VecDeque<Vec<u8>>,
+    /// Foreground process group.
+    foreground: Option<usize>,
+    /// The session this terminal is the controlling terminal of.
+    session: Option<usize>,
+    /// Threads polling for input.
+    pollers: WaitQueue,
+}
+
+impl Tty {
+    pub fn new() -> Self {
+        Self {
+            termios: Termios::default(),
+            line: Vec::new(),
+            ready: VecDeque::new(),
+            foreground: None,
+            session: None,
+            pollers: WaitQueue::new(),
+        }
+    }
+
+    fn echo(&self, c: u8) {
+        if !self.termios.lflag().contains(LocalFlags::ECHO) {
+            return;
+        }
+        match c {
+            b'\n' | b'\t' | 0x20..=0x7e => console_putchar(c as usize),
+            // Control characters are echoed as ^X.
+            0..=0x1f => {
+                print!("^{}", (c + b'@') as char);
+            }
+            _ => {}
+        }
+    }
+
+    fn erase_char(&mut self) {
+        if self.line.pop().is_some()
+            && self
+                .termios
+                .lflag()
+                .contains(LocalFlags::ECHO | LocalFlags::ECHOE)
+        {
+            print!("\x08 \x08");
+        }
+    }
+
+    /// Handle one input character.
+    /// # Return
+    /// * The signal that should be sent to the foreground process, if any.
+    fn receive(&mut self, mut c: u8) -> Option<SignalFlags> {
+        let lflag = self.termios.lflag();
+        let cc = self.termios.cc;
+        if c == b'\r' && self.termios.iflag().contains(InputFlags::ICRNL) {
+            c = b'\n';
+        }
+        if lflag.contains(LocalFlags::ISIG) {
+            let signal = if c == cc[VINTR] {
+                Some(SignalFlags::SIGINT)
+            } else if c == cc[VQUIT] {
+                Some(SignalFlags::SIGQUIT)
+            } else if c == cc[VSUSP] {
+                Some(SignalFlags::SIGTSTP)
+            } else {
+                None
+            };
+            if signal.is_some() {
+                self.echo(c);
+                self.echo(b'\n');
+                self.line.clear();
+                return signal;
+            }
+        }
+        if !lflag.contains(LocalFlags::ICANON) {
+            self.echo(c);
+            self.ready.push_back(alloc::vec![c]);
+            return None;
+        }
+        if c == cc[VERASE] || c == 0x08 {
+            self.erase_char();
+        } else if c == cc[VKILL] {
+            if lflag.contains(LocalFlags::ECHOK) {
+                while !self.line.is_empty() {
+                    self.erase_char();
+                }
+            }
+            self.line.clear();
+        } else if c == cc[VEOF] {
+            self.ready.push_back(core::mem::take(&mut self.line));
+        } else if c == b'\n' {
+            self.echo(c);
+            self.line.push(c);
+            self.ready.push_back(core::mem::take(&mut self.line));
+        } else {
+            self.echo(c);
+            self.line.push(c);
+        }
+        None
+    }
+
+    /// Copy ready input to buf.
+    /// # Return
+    /// * None - no input is ready.
+    /// * Some(n) - n bytes were copied, 0 means end of file.
+    fn read(&mut self, buf: &mut [u8]) -> Option<usize> {
+        let canonical = self.termios.lflag().contains(LocalFlags::ICANON);
+        let mut len = 0;
+        while let Some(chunk) = self.ready.front_mut() {
+            let n = chunk.len().min(buf.len() - len);
+            buf[len..len + n].copy_from_slice(&chunk[..n]);
+            len += n;
+            if n == chunk.len() {
+                let eof = chunk.is_empty();
+                self.ready.pop_front();
+                if canonical || eof {
+                    return Some(len);
+                }
+            } else {
+                chunk.drain(..n);
+            }
+            if len == buf.len() {
+                return Some(len);
+            }
+        }
+        if len > 0 {
+            Some(len)
+        } else {
+            None
+        }
+    }
+}
+
+lazy_static! {
+    pub static ref TTY: SpinNoIrqLock<Tty> = SpinNoIrqLock::new(Tty::new());
+}
+
+/// Pull all pending characters from the console into the line discipline,
+/// and send the generated signals to the foreground process group.
+pub fn poll_console() {
+    let mut signals = Vec::new();
+    let mut tty = TTY.lock();
+    let ready_chunks = tty.ready.len();
+    loop {
+        let c = get_char();
+        if c == 0 {
+            break;
+        }
+        if let Some(signal) = tty.receive(c) {
+            if let Some(pgid) = tty.foreground {
+                signals.push((pgid, signal));
+            }
+        }
+    }
+    // Only new input wakes up the pollers, so edge triggered epoll sees every new line once.
+    if tty.ready.len() > ready_chunks {
+        tty.pollers.wake_all();
+    }
+    drop(tty);
+    for (pgid, signal) in signals {
+        send_signal_to_group(pgid, signal);
+    }
+}
+
+/// Whether the current process is in the session of the terminal but not in the foreground.
+fn current_in_background() -> bool {
+    let proc = current_process();
+    let inner = proc.inner_exclusive_access();
+    let tty = TTY.lock();
+    tty.session == Some(inner.sid) && tty.foreground != Some(inner.pgid)
+}
+
+/// Read from the terminal, wait until there is input.
+/// A background process is stopped by SIGTTIN until it is moved to the foreground.
+/// # Return
+/// * -EIO - the process is in the background and ignores SIGTTIN.
+/// * -EINTR - a signal which ends the process is sent while it is stopped.
+pub fn tty_read(buf: &mut [u8]) -> isize {
+    loop {
+        poll_console();
+        if current_in_background() {
+            let proc = current_process();
+            let inner = proc.inner_exclusive_access();
+            // Signals are never blocked, only ignoring SIGTTIN keeps it from stopping.
+            if inner.signal_actions.is_ignored(SignalFlags::SIGTTIN) {
+                return -EIO;
+            }
+            let pgid = inner.pgid;
+            drop(inner);
+            drop(proc);
+            send_signal_to_group(pgid, SignalFlags::SIGTTIN);
+            handle_stop_signals();
+            if check_current_signals_error().is_some() {
+                return -EINTR;
+            }
+            continue;
+        }
+        if let Some(len) = TTY.lock().read(buf) {
+            return len as isize;
+        }
+        suspended_current_and_run_next();
+    }
+}
+
+/// Whether the terminal has input to read, for poll.
+pub fn tty_poll_ready() -> PollEvents {
+    poll_console();
+    if TTY.lock().ready.is_empty() {
+        PollEvents::empty()
+    } else {
+        PollEvents::POLLIN
+    }
+}
+
+/// Wake up the current thread when input is ready, input is pulled on every timer tick.
+pub fn tty_register_waker() {
+    TTY.lock().pollers.push_current();
+}
+
+pub fn tty_unregister_waker() {
+    TTY.lock().pollers.remove_current();
+}
+
+pub fn tty_add_watcher(watcher: Weak<dyn Watcher>) {
+    TTY.lock().pollers.add_watcher(watcher);
+}
+
+pub fn tty_remove_watcher(watcher: &Weak<dyn Watcher>) {
+    TTY.lock().pollers.remove_watcher(watcher);
+}
+
+/// Release the controlling terminal when the session leader exits.
+pub fn release_session(sid: usize) {
+    let mut tty = TTY.lock();
+    if tty.session == Some(sid) {
+        tty.session = None;
+        tty.foreground = None;
+    }
+}
+
+/// Handle terminal ioctl request.
+/// # Parameter
+/// * 'cmd' - TCGETS, TCSETS, TIOCGPGRP, TIOCSPGRP, TIOCSCTTY or TIOCNOTTY
+/// * 'arg' - user pointer to a Termios or a i32 process group id
+/// # Return
+/// * 0 - success
+/// * -1 - unsupported request or the request is not permitted
+pub fn tty_ioctl(cmd: usize, arg: usize) -> isize {
+    let page_table = PageTable::from_token(current_user_token());
+    let proc = current_process();
+    let (pid, pgid, sid) = {
+        let inner = proc.inner_exclusive_access();
+        (proc.pid(), inner.pgid, inner.sid)
+    };
+    let mut tty = TTY.lock();
+    match cmd {
+        TCGETS => {
+            *page_table.translated_refmut(arg as *mut Termios) = tty.termios;
+        }
+        TCSETS => {
+            tty.termios = *page_table.translated_ref(arg as *const Termios);
+            // Leaving canonical mode makes the half edited line readable.
+            if !tty.termios.lflag().contains(LocalFlags::ICANON) && !tty.line.is_empty() {
+                let line = core::mem::take(&mut tty.line);
+                tty.ready.push_back(line);
+            }
+        }
+        TIOCGPGRP => {
+            *page_table.translated_refmut(arg as *mut i32) =
+                tty.foreground.map_or(-1, |pgid| pgid as i32);
+        }
+        TIOCSPGRP => {
+            let new_pgid = *page_table.translated_ref(arg as *const i32);
+            if new_pgid < 0 {
+                return -1;
+            }
+            let new_pgid = new_pgid as usize;
+            // Only the processes of the session can change the foreground,
+            // and the new foreground group must be in the same session.
+            if let Some(session) = tty.session {
+                drop(tty);
+                if session != sid
+                    || !procs_in_group(new_pgid)
+                        .iter()
+                        .any(|proc| proc.inner_exclusive_access().sid == sid)
+                {
+                    return -1;
+                }
+                tty = TTY.lock();
+            }
+            tty.foreground = Some(new_pgid);
+        }
+        TIOCSCTTY => {
+            // Only a session leader without controlling terminal can acquire the terminal.
+            if pid != sid || tty.session.is_some_and(|session| session != sid) {
+                return -1;
+            }
+            tty.session = Some(sid);
+            tty.foreground = Some(pgid);
+        }
+        TIOCNOTTY => {
+            if tty.session != Some(sid) {
+                return -1;
+            }
+            if pid == sid {
+                tty.session = None;
+                tty.foreground = None;
+            }
+        }
+        _ => return -1,
+    }
+    0
+}
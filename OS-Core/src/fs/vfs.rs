@@ -0,0 +1,612 @@
+//! Virtual file system layer.
+//! Every file system provides a tree of VfsNode and is mounted on a directory,
+//! easy-fs on the block device is mounted on "/" at boot.
+//! A path is looked up from the root of the file system mounted on its longest matching prefix,
+//! so the lookup crosses mount points naturally.
+//! There is no current directory, relative paths are relative to "/".
+use alloc::{string::String, sync::Arc, vec::Vec};
+use lazy_static::lazy_static;
+
+use crate::{
+    mm::page_table::UserBuffer,
+    sync::{
+        mutex::{Mutex, MutexBlocking, MutexType},
+        SpinNoIrqLock,
+    },
+    syscall::errno::{EADDRINUSE, ECONNREFUSED, EEXIST, ENOENT, ENXIO, EPERM},
+};
+
+use super::{
+    devfs,
+    inode::{EasyFsNode, OpenFlags, ROOT_INODE},
+    pipe::open_fifo,
+    procfs, tmpfs, File,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum NodeType {
+    File,
+    Dir,
+    CharDevice,
+    BlockDevice,
+    /// Named pipe.
+    Fifo,
+    /// Address of a unix domain socket.
+    Socket,
+}
+
+impl NodeType {
+    /// d_type of linux struct dirent64.
+    fn dirent_type(&self) -> u8 {
+        match self {
+            NodeType::File => 8,
+            NodeType::Dir => 4,
+            NodeType::CharDevice => 2,
+            NodeType::BlockDevice => 6,
+            NodeType::Fifo => 1,
+            NodeType::Socket => 12,
+        }
+    }
+
+    /// File type and permission bits of st_mode.
+    fn mode(&self) -> u32 {
+        match self {
+            NodeType::File => S_IFREG | 0o644,
+            NodeType::Dir => S_IFDIR | 0o755,
+            NodeType::CharDevice => S_IFCHR | 0o666,
+            NodeType::BlockDevice => S_IFBLK | 0o660,
+            NodeType::Fifo => S_IFIFO | 0o644,
+            NodeType::Socket => S_IFSOCK | 0o755,
+        }
+    }
+}
+
+/// Mask of the file type bits in st_mode.
+pub const S_IFMT: u32 = 0o170000;
+/// Character device.
+pub const S_IFCHR: u32 = 0o020000;
+/// Directory.
+pub const S_IFDIR: u32 = 0o040000;
+/// Regular file.
+pub const S_IFREG: u32 = 0o100000;
+/// Block device.
+pub const S_IFBLK: u32 = 0o060000;
+/// Named pipe.
+pub const S_IFIFO: u32 = 0o010000;
+/// Socket.
+pub const S_IFSOCK: u32 = 0o140000;
+
+/// File status, the same layout as linux struct stat on riscv64.
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct Stat {
+    /// Id of the file system.
+    pub dev: u64,
+    /// Inode number, unique in the file system.
+    pub ino: u64,
+    /// File type and permission.
+    pub mode: u32,
+    pub nlink: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub rdev: u64,
+    __pad: u64,
+    /// File size in bytes.
+    pub size: i64,
+    pub blksize: u32,
+    __pad2: i32,
+    /// The number of 512 bytes blocks.
+    pub blocks: u64,
+    pub atime_sec: i64,
+    pub atime_nsec: i64,
+    pub mtime_sec: i64,
+    pub mtime_nsec: i64,
+    pub ctime_sec: i64,
+    pub ctime_nsec: i64,
+    __unused: [u32; 2],
+}
+
+impl Stat {
+    pub fn new(dev: u64, ino: u64, node_type: NodeType, size: usize) -> Self {
+        Self {
+            dev,
+            ino,
+            mode: node_type.mode(),
+            nlink: 1,
+            size: size as i64,
+            blksize: 512,
+            blocks: size.div_ceil(512) as u64,
+            ..Default::default()
+        }
+    }
+}
+
+/// A file or directory of a file system.
+pub trait VfsNode: Send + Sync {
+    fn node_type(&self) -> NodeType;
+    /// Find a child of the directory by name.
+    #[allow(unused)]
+    fn lookup(&self, name: &str) -> Option<Arc<dyn VfsNode>> {
+        None
+    }
+    /// Create a child of the directory.
+    /// # Return
+    /// * None - the name exists or the file system is read only.
+    #[allow(unused)]
+    fn create(&self, name: &str, node_type: NodeType) -> Option<Arc<dyn VfsNode>> {
+        None
+    }
+    /// Remove a file or an empty directory from the directory.
+    /// # Return
+    /// * false - the name does not exist, or it is a directory which is not empty.
+    #[allow(unused)]
+    fn unlink(&self, name: &str) -> bool {
+        false
+    }
+    /// Names and types of all children of the directory.
+    fn readdir(&self) -> Vec<(String, NodeType)> {
+        Vec::new()
+    }
+    /// Read data from offset of the file.
+    /// # Return
+    /// * The number of bytes read, 0 at the end of file.
+    #[allow(unused)]
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        0
+    }
+    /// Write data to offset of the file.
+    /// # Return
+    /// * The number of bytes written.
+    #[allow(unused)]
+    fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
+        0
+    }
+    /// Change the size of the file.
+    /// # Return
+    /// * false - the file system does not support the size.
+    #[allow(unused)]
+    fn truncate(&self, size: usize) -> bool {
+        false
+    }
+    /// Open the node as a special file, such as a device.
+    /// # Return
+    /// * None - it is opened as a regular file or directory.
+    fn open(&self) -> Option<Arc<dyn File + Send + Sync>> {
+        None
+    }
+    fn stat(&self) -> Stat;
+}
+
+/// A file system mounted on a directory.
+struct Mount {
+    /// Names of the mount point path, empty for "/".
+    names: Vec<String>,
+    fstype: String,
+    root: Arc<dyn VfsNode>,
+}
+
+lazy_static! {
+    static ref MOUNTS: SpinNoIrqLock<Vec<Mount>> = SpinNoIrqLock::new(Vec::new());
+}
+
+/// Create a file system by type.
+/// # Parameter
+/// * 'data' - file system specific options, such as "size=1m" of tmpfs.
+fn new_fs(fstype: &str, data: &str) -> Option<Arc<dyn VfsNode>> {
+    match fstype {
+        "easy-fs" => Some(EasyFsNode::new(ROOT_INODE.clone())),
+        "proc" => Some(procfs::root()),
+        "devfs" => Some(devfs::root()),
+        "tmpfs" => tmpfs::new(data),
+        _ => None,
+    }
+}
+
+/// Split the path into names, "." is skipped and ".." removes the previous name.
+fn path_names(path: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for name in path.split('/') {
+        match name {
+            "" | "." => {}
+            ".." => {
+                names.pop();
+            }
+            _ => names.push(String::from(name)),
+        }
+    }
+    names
+}
+
+/// Join the names to an absolute path.
+fn names_path(names: &[String]) -> String {
+    if names.is_empty() {
+        return String::from("/");
+    }
+    names
+        .iter()
+        .fold(String::new(), |path, name| path + "/" + name)
+}
+
+fn lookup_names(names: &[String]) -> Option<Arc<dyn VfsNode>> {
+    let (depth, root) = MOUNTS
+        .lock()
+        .iter()
+        .filter(|mount| names.starts_with(&mount.names))
+        .max_by_key(|mount| mount.names.len())
+        .map(|mount| (mount.names.len(), mount.root.clone()))?;
+    let mut node = root;
+    for name in &names[depth..] {
+        node = node.lookup(name)?;
+    }
+    Some(node)
+}
+
+/// Find the node of the path.
+pub fn lookup(path: &str) -> Option<Arc<dyn VfsNode>> {
+    lookup_names(&path_names(path))
+}
+
+/// Find the directory containing the path.
+/// # Return
+/// * (the directory, the last name of the path)
+fn lookup_parent(path: &str) -> Option<(Arc<dyn VfsNode>, String)> {
+    let mut names = path_names(path);
+    let name = names.pop()?;
+    let parent = lookup_names(&names)?;
+    if parent.node_type() != NodeType::Dir {
+        return None;
+    }
+    Some((parent, name))
+}
+
+fn is_mount_point(names: &[String]) -> bool {
+    MOUNTS.lock().iter().any(|mount| mount.names == names)
+}
+
+/// Open a file by path, a named pipe is opened as an end of its pipe.
+/// A file is created with CREATE if it does not exist, an existing one is truncated
+/// with CREATE or TRUNC.
+/// # Return
+/// * -1 - the file does not exist and can not be created, or a directory is opened for writing.
+/// * -EEXIST - the file exists and CREATE and EXCL are given.
+/// * -ENXIO - the file is a socket, it is connected to instead of opened.
+/// * -errno - errors of opening a named pipe, see open_fifo.
+pub fn open(path: &str, flags: OpenFlags) -> Result<Arc<dyn File + Send + Sync>, isize> {
+    let (readable, writable) = flags.read_write();
+    let node = match lookup(path) {
+        Some(_) if flags.contains(OpenFlags::CREATE | OpenFlags::EXCL) => return Err(-EEXIST),
+        Some(node) => node,
+        None if flags.contains(OpenFlags::CREATE) => {
+            let (parent, name) = lookup_parent(path).ok_or(-1isize)?;
+            parent.create(&name, NodeType::File).ok_or(-1isize)?
+        }
+        None => return Err(-1),
+    };
+    match node.node_type() {
+        NodeType::Dir if writable => return Err(-1),
+        NodeType::File if flags.intersects(OpenFlags::CREATE | OpenFlags::TRUNC) => {
+            node.truncate(0);
+        }
+        NodeType::Fifo => {
+            let stat = node.stat();
+            let nonblock = flags.contains(OpenFlags::NONBLOCK);
+            let pipe = open_fifo((stat.dev, stat.ino), readable, writable, nonblock)?;
+            return Ok(pipe);
+        }
+        NodeType::Socket => return Err(-ENXIO),
+        _ => {}
+    }
+    if let Some(file) = node.open() {
+        return Ok(file);
+    }
+    let path = names_path(&path_names(path));
+    let append = flags.contains(OpenFlags::APPEND);
+    let file = VfsFile::new(readable, writable, append, path, node);
+    Ok(Arc::new(file))
+}
+
+/// Read the whole file.
+pub fn read_file(path: &str) -> Option<Vec<u8>> {
+    let node = lookup(path)?;
+    if node.node_type() != NodeType::File {
+        return None;
+    }
+    let mut data = Vec::new();
+    let mut buf = [0u8; 512];
+    loop {
+        let len = node.read_at(data.len(), &mut buf);
+        if len == 0 {
+            return Some(data);
+        }
+        data.extend_from_slice(&buf[..len]);
+    }
+}
+
+/// Change the size of a file by path.
+/// # Return
+/// * -1 - the file does not exist, it is a directory, or its file system does not support the size.
+pub fn truncate(path: &str, size: usize) -> isize {
+    match lookup(path) {
+        Some(node) if node.node_type() == NodeType::File && node.truncate(size) => 0,
+        _ => -1,
+    }
+}
+
+/// Create a directory.
+/// # Return
+/// * -1 - the parent directory does not exist or the name exists.
+pub fn mkdir(path: &str) -> isize {
+    match lookup_parent(path) {
+        Some((parent, name)) if parent.create(&name, NodeType::Dir).is_some() => 0,
+        _ => -1,
+    }
+}
+
+/// Create a named pipe.
+/// # Return
+/// * -1 - the parent directory does not exist, the name exists,
+///   or the file system does not support named pipes.
+pub fn mkfifo(path: &str) -> isize {
+    match lookup_parent(path) {
+        Some((parent, name)) if parent.create(&name, NodeType::Fifo).is_some() => 0,
+        _ => -1,
+    }
+}
+
+/// Create the socket file of a unix domain socket bound to the path.
+/// # Return
+/// * (device id, inode number) of the file, the socket is registered by it
+/// * -EADDRINUSE - the path exists
+/// * -ENOENT - the parent directory does not exist
+/// * -EPERM - the file system does not support socket files
+pub fn mksock(path: &str) -> Result<(u64, u64), isize> {
+    if lookup(path).is_some() {
+        return Err(-EADDRINUSE);
+    }
+    let (parent, name) = lookup_parent(path).ok_or(-ENOENT)?;
+    let stat = parent.create(&name, NodeType::Socket).ok_or(-EPERM)?.stat();
+    Ok((stat.dev, stat.ino))
+}
+
+/// Find the socket file of the path.
+/// # Return
+/// * (device id, inode number) of the file
+/// * -ENOENT - the path does not exist
+/// * -ECONNREFUSED - the file is not a socket
+pub fn socket_id(path: &str) -> Result<(u64, u64), isize> {
+    let node = lookup(path).ok_or(-ENOENT)?;
+    if node.node_type() != NodeType::Socket {
+        return Err(-ECONNREFUSED);
+    }
+    let stat = node.stat();
+    Ok((stat.dev, stat.ino))
+}
+
+/// Remove a file, or an empty directory if dir is true.
+/// # Return
+/// * -1 - the file does not exist, the type does not match, or it is a mount point.
+pub fn unlink(path: &str, dir: bool) -> isize {
+    let Some(node) = lookup(path) else {
+        return -1;
+    };
+    if (node.node_type() == NodeType::Dir) != dir || is_mount_point(&path_names(path)) {
+        return -1;
+    }
+    match lookup_parent(path) {
+        Some((parent, name)) if parent.unlink(&name) => 0,
+        _ => -1,
+    }
+}
+
+/// Mount a new file system on a directory.
+/// # Parameter
+/// * 'fstype' - "easy-fs", "proc", "devfs" or "tmpfs".
+/// * 'path' - an existing directory which is not a mount point.
+/// * 'data' - file system specific options.
+/// # Return
+/// * -1 - unknown file system type, bad options, or the path can not be mounted on.
+pub fn mount(fstype: &str, path: &str, data: &str) -> isize {
+    let names = path_names(path);
+    match lookup_names(&names) {
+        Some(node) if node.node_type() == NodeType::Dir && !is_mount_point(&names) => {}
+        _ => return -1,
+    }
+    let Some(root) = new_fs(fstype, data) else {
+        return -1;
+    };
+    MOUNTS.lock().push(Mount {
+        names,
+        fstype: String::from(fstype),
+        root,
+    });
+    0
+}
+
+/// Unmount the file system mounted on the path.
+/// Files opened in it are still usable.
+/// # Return
+/// * -1 - the path is not a mount point, it is "/", or other file systems are mounted under it.
+pub fn umount(path: &str) -> isize {
+    let names = path_names(path);
+    let mut mounts = MOUNTS.lock();
+    if names.is_empty()
+        || mounts
+            .iter()
+            .any(|mount| mount.names.len() > names.len() && mount.names.starts_with(&names))
+    {
+        return -1;
+    }
+    match mounts.iter().position(|mount| mount.names == names) {
+        Some(index) => {
+            mounts.remove(index);
+            0
+        }
+        None => -1,
+    }
+}
+
+/// All mounted file systems.
+/// # Return
+/// * (file system type, mount point path)
+pub fn mounts() -> Vec<(String, String)> {
+    MOUNTS
+        .lock()
+        .iter()
+        .map(|mount| (mount.fstype.clone(), names_path(&mount.names)))
+        .collect()
+}
+
+/// Mount easy-fs on "/", procfs on "/proc", devfs on "/dev" and tmpfs on "/tmp".
+pub fn init() {
+    devfs::init();
+    MOUNTS.lock().push(Mount {
+        names: Vec::new(),
+        fstype: String::from("easy-fs"),
+        root: new_fs("easy-fs", "").unwrap(),
+    });
+    for (fstype, path) in [("proc", "/proc"), ("devfs", "/dev"), ("tmpfs", "/tmp")] {
+        if lookup(path).is_none() {
+            mkdir(path);
+        }
+        assert_eq!(
+            mount(fstype, path, ""),
+            0,
+            "Failed to mount {} on {}.",
+            fstype,
+            path
+        );
+    }
+}
+
+/// An opened VfsNode.
+pub struct VfsFile {
+    readable: bool,
+    writable: bool,
+    /// Every write starts at the end of file.
+    append: bool,
+    path: String,
+    node: Arc<dyn VfsNode>,
+    /// Byte offset for files, entry index for directories.
+    /// It is copied out and stored back, not held during the I/O of the node.
+    offset: SpinNoIrqLock<usize>,
+    /// Held by an appending write from getting the size to writing, so appends do not overlap.
+    append_lock: MutexBlocking,
+}
+
+impl VfsFile {
+    pub fn new(
+        readable: bool,
+        writable: bool,
+        append: bool,
+        path: String,
+        node: Arc<dyn VfsNode>,
+    ) -> Self {
+        Self {
+            readable,
+            writable,
+            append,
+            path,
+            node,
+            offset: SpinNoIrqLock::new(0),
+            append_lock: MutexBlocking::new(MutexType::Normal),
+        }
+    }
+}
+
+impl File for VfsFile {
+    fn readable(&self) -> bool {
+        self.readable
+    }
+
+    fn writable(&self) -> bool {
+        self.writable
+    }
+
+    fn read(&self, mut buf: UserBuffer) -> isize {
+        let mut offset = *self.offset.lock();
+        let mut read_size = 0;
+        for slice in buf.buffers.iter_mut() {
+            let curr_read_size = self.node.read_at(offset, slice);
+            offset += curr_read_size;
+            read_size += curr_read_size;
+            if curr_read_size < slice.len() {
+                break;
+            }
+        }
+        *self.offset.lock() = offset;
+        read_size as isize
+    }
+
+    fn write(&self, buf: UserBuffer) -> isize {
+        let mut offset = if self.append {
+            self.append_lock.lock();
+            self.node.stat().size as usize
+        } else {
+            *self.offset.lock()
+        };
+        let mut write_size = 0;
+        for slice in buf.buffers.iter() {
+            let curr_write_size = self.node.write_at(offset, slice);
+            offset += curr_write_size;
+            write_size += curr_write_size;
+            if curr_write_size < slice.len() {
+                break;
+            }
+        }
+        *self.offset.lock() = offset;
+        if self.append {
+            self.append_lock.unlock();
+        }
+        write_size as isize
+    }
+
+    fn getdents(&self, buf: UserBuffer) -> isize {
+        if self.node.node_type() != NodeType::Dir {
+            return -1;
+        }
+        let entries = self.node.readdir();
+        let mut offset = self.offset.lock();
+        if *offset >= entries.len() {
+            return 0;
+        }
+        let mut data = Vec::new();
+        for (name, node_type) in entries.iter().skip(*offset) {
+            // d_ino, d_off, d_reclen, d_type, d_name with '\0', aligned to 8 bytes.
+            let reclen = (8 + 8 + 2 + 1 + name.len() + 1 + 7) & !7;
+            if data.len() + reclen > buf.len() {
+                break;
+            }
+            *offset += 1;
+            data.extend_from_slice(&(*offset as u64).to_ne_bytes());
+            data.extend_from_slice(&(*offset as i64).to_ne_bytes());
+            data.extend_from_slice(&(reclen as u16).to_ne_bytes());
+            data.push(node_type.dirent_type());
+            data.extend_from_slice(name.as_bytes());
+            data.resize(data.len() + reclen - (8 + 8 + 2 + 1 + name.len()), 0);
+        }
+        drop(offset);
+        if data.is_empty() {
+            return -1;
+        }
+        for (dst, src) in buf.into_iter().zip(data.iter()) {
+            unsafe { *dst = *src };
+        }
+        data.len() as isize
+    }
+
+    fn truncate(&self, size: usize) -> isize {
+        if self.writable && self.node.node_type() == NodeType::File && self.node.truncate(size) {
+            0
+        } else {
+            -1
+        }
+    }
+
+    fn stat(&self) -> Option<Stat> {
+        Some(self.node.stat())
+    }
+
+    fn name(&self) -> String {
+        self.path.clone()
+    }
+}
@@ -17,7 +17,9 @@ mod console;
 mod drivers;
 pub mod fs;
 pub mod mm;
+pub mod net;
 mod sbi_services;
+pub mod smp;
 pub mod sync;
 pub mod syscall;
 pub mod task;
@@ -38,12 +40,29 @@ pub fn rust_main() -> ! {
     trap::init();
     trap::enable_timer_interrupt();
     timer::set_next_trigger();
+    fs::vfs::init();
+    net::iface::init();
     task::add_initproc();
     list_app();
+    smp::set_online();
+    smp::start_secondary_harts();
     task::processor::run_tasks();
     panic!("Unreachable in rust_main!");
 }
 
+/// Entry of the harts started by the boot hart, the kernel has been initialized.
+#[no_mangle]
+pub fn secondary_main() -> ! {
+    mm::activate_kernel_space();
+    trap::init();
+    trap::enable_timer_interrupt();
+    timer::set_next_trigger();
+    smp::set_online();
+    info!("Hart {} started.", smp::hart_id());
+    task::processor::run_tasks();
+    panic!("Unreachable in secondary_main!");
+}
+
 fn clean_bss() {
     extern "C" {
         fn sbss();
@@ -1,7 +1,7 @@
 use alloc::vec::Vec;
 use lazy_static::lazy_static;
 
-use crate::{info, mm::address::PhysAddr, platfrom::MEMORY_END, sync::UPSafeCell};
+use crate::{info, mm::address::PhysAddr, platfrom::MEMORY_END, sync::SpinNoIrqLock};
 
 use super::address::PhysPageNum;
 
@@ -12,6 +12,7 @@ trait FrameAllocator {
 }
 
 pub struct StackFrameAllocator {
+    start: usize,   // first usable physics page number
     current: usize, // current free physics page number
     end: usize,     // last free physics page number
     recycled: Vec<PhysPageNum>,
@@ -19,6 +20,7 @@ pub struct StackFrameAllocator {
 impl StackFrameAllocator {
     /// Init a StackFrameAllocator from start physical page num and end physical page num
     pub fn init(&mut self, s: PhysPageNum, e: PhysPageNum) {
+        self.start = s.0;
         self.current = s.0;
         self.end = e.0;
         info!(
@@ -28,12 +30,21 @@ impl StackFrameAllocator {
             e.0 - s.0
         );
     }
+    /// The number of pages managed by the allocator.
+    pub fn total_pages(&self) -> usize {
+        self.end - self.start
+    }
+    /// The number of pages not allocated.
+    pub fn free_pages(&self) -> usize {
+        self.end - self.current + self.recycled.len()
+    }
 }
 
 impl FrameAllocator for StackFrameAllocator {
     /// Create a StackFrameAllocator object, call init function before use
     fn new() -> Self {
         StackFrameAllocator {
+            start: 0,
             current: 0,
             end: 0,
             recycled: Vec::new(),
@@ -85,15 +96,15 @@ impl Drop for FrameTracker {
 
 // Global physical page allocator.
 lazy_static! {
-    pub static ref FRAME_ALLOCATOR: UPSafeCell<StackFrameAllocator> =
-        unsafe { UPSafeCell::new(StackFrameAllocator::new()) };
+    pub static ref FRAME_ALLOCATOR: SpinNoIrqLock<StackFrameAllocator> =
+        SpinNoIrqLock::new(StackFrameAllocator::new());
 }
 
 pub fn frame_allocator_init() {
     extern "C" {
         fn ekernel();
     }
-    FRAME_ALLOCATOR.exclusive_access().init(
+    FRAME_ALLOCATOR.lock().init(
         PhysAddr::from(ekernel as usize).ceil(),
         PhysAddr::from(MEMORY_END - 1).floor(),
     );
@@ -101,11 +112,11 @@ pub fn frame_allocator_init() {
 
 pub fn frame_alloc() -> Option<FrameTracker> {
     FRAME_ALLOCATOR
-        .exclusive_access()
+        .lock()
         .alloc()
         .map(|ppn| FrameTracker::new(ppn))
 }
 
 pub fn frame_dealloc(ppn: PhysPageNum) {
-    FRAME_ALLOCATOR.exclusive_access().dealloc(ppn);
+    FRAME_ALLOCATOR.lock().dealloc(ppn);
 }
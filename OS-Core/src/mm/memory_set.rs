@@ -2,13 +2,15 @@ use super::{
     address::{PhysPageNum, VPNRange, VirtAddr, VirtPageNum},
     frame_allocator::{frame_alloc, FrameTracker},
     page_table::{PTEFlags, PageTable, PageTableEntry},
+    shm::{ShmAttachment, ShmSegment},
 };
 use crate::{
     config::{PAGE_SIZE, TRAMPOLINE},
     mm::address::{PhysAddr, StepByOne},
     platfrom::{MEMORY_END, MMIO},
     println,
-    sync::UPSafeCell,
+    smp::flush_tlb_all,
+    sync::SpinNoIrqLock,
 };
 use alloc::{collections::BTreeMap, sync::Arc, vec::Vec};
 use core::arch::asm;
@@ -28,10 +30,12 @@ bitflags! {
 /// How physical pages and virtual pages are mapped
 /// * 'Identical' - Identity mapping, ppn==vpn, use for kernel
 /// * 'Framed' - Randomly assign physical page frames
+/// * 'Shared' - The frames of a shared memory segment, shared with other memory sets
 #[derive(PartialEq, Debug, Clone, Copy)]
 pub enum MapType {
     Identical,
     Framed,
+    Shared,
 }
 
 /// A continuous virtual page that has been mapped,
@@ -40,11 +44,13 @@ pub enum MapType {
 /// * 'data_frames' - A mapping of virtual pages to physical pages.
 /// * 'map_type' - How virtual pages and physical pages are mapped.
 /// * 'map_permission' - Permissions for all virtual pages in this area.
+/// * 'shm' - The attached segment of a shared area.
 pub struct MapArea {
     vpn_range: VPNRange,
     data_frames: BTreeMap<VirtPageNum, FrameTracker>,
     map_type: MapType,
     map_permission: MapPermission,
+    shm: Option<ShmAttachment>,
 }
 
 impl MapArea {
@@ -64,6 +70,23 @@ impl MapArea {
             data_frames: BTreeMap::new(),
             map_type,
             map_permission,
+            shm: None,
+        }
+    }
+
+    /// Create a shared area mapping the whole segment from start_va.
+    pub fn new_shared(
+        start_va: VirtAddr,
+        attachment: ShmAttachment,
+        map_permission: MapPermission,
+    ) -> Self {
+        let end_va = VirtAddr::from(start_va.0 + attachment.segment().pages() * PAGE_SIZE);
+        Self {
+            vpn_range: VPNRange::new(start_va.floor(), end_va.ceil()),
+            data_frames: BTreeMap::new(),
+            map_type: MapType::Shared,
+            map_permission,
+            shm: Some(attachment),
         }
     }
 
@@ -81,6 +104,10 @@ impl MapArea {
                 self.data_frames.insert(vpn, fame);
                 temp
             }
+            MapType::Shared => {
+                let index = vpn.0 - self.vpn_range.get_start().0;
+                self.shm.as_ref().unwrap().ppn(index)
+            }
         };
         page_table.map(vpn, ppn, flags);
     }
@@ -115,6 +142,25 @@ impl MapArea {
         }
     }
 
+    /// The first virtual address of the area.
+    pub fn start_va(&self) -> VirtAddr {
+        self.vpn_range.get_start().into()
+    }
+
+    /// The end virtual address of the area (exclusive).
+    pub fn end_va(&self) -> VirtAddr {
+        self.vpn_range.get_end().into()
+    }
+
+    pub fn permission(&self) -> MapPermission {
+        self.map_permission
+    }
+
+    /// The number of physical pages allocated for the area.
+    pub fn frame_count(&self) -> usize {
+        self.data_frames.len()
+    }
+
     /// Copy a piece of data to this memory area.
     /// Note that copying starts from the first page of the memory area.
     pub fn copy_data(&mut self, page_table: &mut PageTable, data: &[u8]) {
@@ -140,12 +186,14 @@ impl MapArea {
 }
 impl Clone for MapArea {
     /// When forking a new process, copy the MapArea.
+    /// A shared area is attached to the same segment.
     fn clone(&self) -> Self {
         Self {
             vpn_range: VPNRange::new(self.vpn_range.get_start(), self.vpn_range.get_end()),
             data_frames: BTreeMap::new(),
             map_type: self.map_type,
             map_permission: self.map_permission,
+            shm: self.shm.clone(),
         }
     }
 }
@@ -156,6 +204,8 @@ impl Clone for MapArea {
 pub struct MemorySet {
     page_table: PageTable,
     areas: Vec<MapArea>,
+    /// The max number of data pages ever mapped.
+    peak_frames: usize,
 }
 
 impl MemorySet {
@@ -164,6 +214,7 @@ impl MemorySet {
         Self {
             page_table: PageTable::new(),
             areas: Vec::new(),
+            peak_frames: 0,
         }
     }
     /// The highest-addressed virtual page of all MemorySets
@@ -198,6 +249,20 @@ impl MemorySet {
             map_area.copy_data(&mut self.page_table, data);
         }
         self.areas.push(map_area);
+        self.peak_frames = self.peak_frames.max(self.frame_count());
+    }
+    /// The number of data pages allocated for the memory set (resident set size),
+    /// directory pages and identical mapped pages are not counted.
+    pub fn frame_count(&self) -> usize {
+        self.areas.iter().map(|area| area.frame_count()).sum()
+    }
+    /// All mapped areas, in the order they are mapped.
+    pub fn areas(&self) -> &[MapArea] {
+        &self.areas
+    }
+    /// The max resident set size in pages.
+    pub fn peak_frames(&self) -> usize {
+        self.peak_frames
     }
     /// Physical pages are allocated by start_va and end_va and inserted into MemorySet
     pub fn insert_framed_area(
@@ -211,6 +276,62 @@ impl MemorySet {
             None,
         );
     }
+    /// Whether no area has any of the virtual pages from start to end (exclusive).
+    fn is_free(&self, start: VirtPageNum, end: VirtPageNum) -> bool {
+        self.areas
+            .iter()
+            .all(|area| area.vpn_range.get_end() <= start || end <= area.vpn_range.get_start())
+    }
+    /// Find the lowest free virtual pages of len bytes between base and top.
+    /// # Return
+    /// * The start address of the pages.
+    /// * None - there are not enough free pages.
+    pub fn find_free_area(&self, base: VirtAddr, top: VirtAddr, len: usize) -> Option<VirtAddr> {
+        let pages = len.div_ceil(PAGE_SIZE);
+        let mut start = base.ceil().0;
+        while start + pages <= top.floor().0 {
+            let end = start + pages;
+            match self.areas.iter().find(|area| {
+                start < area.vpn_range.get_end().0 && area.vpn_range.get_start().0 < end
+            }) {
+                Some(area) => start = area.vpn_range.get_end().0,
+                None => return Some(VirtPageNum(start).into()),
+            }
+        }
+        None
+    }
+    /// Attach the segment and map it from start_va, which is page aligned.
+    /// # Return
+    /// * false - some of the pages are mapped, the segment is not attached.
+    pub fn insert_shared_area(
+        &mut self,
+        start_va: VirtAddr,
+        segment: &Arc<ShmSegment>,
+        permission: MapPermission,
+    ) -> bool {
+        let end_va = VirtAddr::from(start_va.0 + segment.pages() * PAGE_SIZE);
+        if !self.is_free(start_va.floor(), end_va.ceil()) {
+            return false;
+        }
+        let area = MapArea::new_shared(start_va, segment.attach(), permission);
+        self.push(area, None);
+        true
+    }
+    /// Unmap the shared area starting from start_va.
+    /// # Return
+    /// * The attachment of the area.
+    /// * None - no shared area starts from start_va.
+    pub fn remove_shared_area(&mut self, start_va: VirtAddr) -> Option<ShmAttachment> {
+        let index = self
+            .areas
+            .iter()
+            .position(|area| area.map_type == MapType::Shared && area.start_va().0 == start_va.0)?;
+        let mut area = self.areas.remove(index);
+        area.unmap(&mut self.page_table);
+        // Other harts may have cached the mapping.
+        flush_tlb_all();
+        area.shm.take()
+    }
     /// Create a MemorySet for the application through elf,
     /// return the MemorySet, user_sp, and application entry point
     pub fn new_app_from_elf(elf_data: &[u8]) -> (Self, usize, usize) {
@@ -420,6 +541,8 @@ impl MemorySet {
             if area.vpn_range.get_start() == start_vpn {
                 area.unmap(&mut self.page_table);
                 self.areas.remove(i);
+                // Other harts may have cached the mapping.
+                flush_tlb_all();
                 break;
             }
         }
@@ -427,7 +550,11 @@ impl MemorySet {
     /// When a process becomes a zombie process,
     /// all memory areas (excluding directory pages) are reclaimed.
     pub fn recycle_data_pages(&mut self) {
+        for area in self.areas.iter_mut() {
+            area.unmap(&mut self.page_table);
+        }
         self.areas.clear();
+        flush_tlb_all();
     }
 }
 impl Clone for MemorySet {
@@ -439,6 +566,9 @@ impl Clone for MemorySet {
         for area in self.areas.iter() {
             let new_area = area.clone();
             new_memory_set.push(new_area, None);
+            if area.map_type == MapType::Shared {
+                continue;
+            }
             for vpn in area.vpn_range {
                 let src = self
                     .translate(vpn)
@@ -458,10 +588,10 @@ impl Clone for MemorySet {
 }
 
 lazy_static! {
-    pub static ref KERNEL_SPACE: Arc<UPSafeCell<MemorySet>> =
-        Arc::new(unsafe { UPSafeCell::new(MemorySet::new_kernel()) });
+    pub static ref KERNEL_SPACE: Arc<SpinNoIrqLock<MemorySet>> =
+        Arc::new(SpinNoIrqLock::new(MemorySet::new_kernel()));
 }
 
 pub fn kernel_token() -> usize {
-    KERNEL_SPACE.exclusive_access().token()
+    KERNEL_SPACE.lock().token()
 }
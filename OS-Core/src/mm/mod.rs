@@ -5,9 +5,15 @@ pub mod frame_allocator;
 pub mod heap_allocator;
 pub mod memory_set;
 pub mod page_table;
+pub mod shm;
 
 pub fn init() {
     heap_allocator::init_heap();
     frame_allocator::frame_allocator_init();
-    KERNEL_SPACE.exclusive_access().activate();
+    activate_kernel_space();
+}
+
+/// Switch the current hart to the kernel page table.
+pub fn activate_kernel_space() {
+    KERNEL_SPACE.lock().activate();
 }
@@ -240,6 +240,41 @@ pub fn translate_byte_buffer(
     ret
 }
 
+/// Copy data to user memory set, the destination may cross pages.
+/// # Parameter
+/// * 'token' - satp of user memory set
+/// * 'dst' - virtual address of user memory set
+/// * 'src' - data in kernel address space
+pub fn copy_bytes_to_user(token: usize, dst: *mut u8, src: &[u8]) {
+    let mut copied = 0;
+    for buffer in translate_byte_buffer(token, dst, src.len()) {
+        buffer.copy_from_slice(&src[copied..copied + buffer.len()]);
+        copied += buffer.len();
+    }
+}
+
+/// Copy a value to user memory set, the destination may cross pages.
+pub fn copy_to_user<T: Copy>(token: usize, dst: *mut T, value: &T) {
+    let src = unsafe {
+        core::slice::from_raw_parts(value as *const T as *const u8, core::mem::size_of::<T>())
+    };
+    copy_bytes_to_user(token, dst as *mut u8, src);
+}
+
+/// Copy a value from user memory set, the source may cross pages.
+pub fn copy_from_user<T: Copy + Default>(token: usize, src: *const T) -> T {
+    let mut value = T::default();
+    let dst = unsafe {
+        core::slice::from_raw_parts_mut(&mut value as *mut T as *mut u8, core::mem::size_of::<T>())
+    };
+    let mut copied = 0;
+    for buffer in translate_byte_buffer(token, src as *const u8, dst.len()) {
+        dst[copied..copied + buffer.len()].copy_from_slice(buffer);
+        copied += buffer.len();
+    }
+    value
+}
+
 /// Save the physical address of the user space address area.
 pub struct UserBuffer {
     pub buffers: Vec<&'static mut [u8]>,
@@ -0,0 +1,311 @@
+//! System V shared memory segments.
+//! A segment owns its frames, and the shared areas of memory sets map them by attachments.
+//! A removed segment can not be found by its key, it is destroyed when the last attachment
+//! is detached, including the attachments inherited by fork.
+use alloc::{collections::BTreeMap, sync::Arc, vec::Vec};
+use lazy_static::lazy_static;
+
+use crate::{
+    config::PAGE_SIZE,
+    sync::SpinNoIrqLock,
+    syscall::errno::{EACCES, EEXIST, EINVAL, ENOENT, ENOMEM, ENOSPC},
+    timer::get_time_ms,
+};
+
+use super::{
+    address::PhysPageNum,
+    frame_allocator::{frame_alloc, FrameTracker},
+};
+
+/// The key of a segment which can only be found by its id.
+pub const IPC_PRIVATE: i32 = 0;
+/// Flags of shmget.
+pub const IPC_CREAT: usize = 0o1000;
+pub const IPC_EXCL: usize = 0o2000;
+/// Commands of shmctl.
+pub const IPC_RMID: usize = 0;
+pub const IPC_SET: usize = 1;
+pub const IPC_STAT: usize = 2;
+/// Flags of shmat.
+pub const SHM_RDONLY: usize = 0o10000;
+pub const SHM_RND: usize = 0o20000;
+/// Permission bits of the mode, there are no users so only the owner bits are checked.
+pub const SHM_MODE_MASK: u32 = 0o777;
+const SHM_MODE_READ: u32 = 0o400;
+const SHM_MODE_WRITE: u32 = 0o200;
+
+/// The max size of a segment.
+pub const SHM_MAX_SIZE: usize = 4 * 1024 * 1024;
+/// The max number of segments.
+pub const SHM_MAX_SEGMENTS: usize = 64;
+
+/// Owner and mode of an IPC object, the same layout as linux struct ipc64_perm.
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct IpcPerm {
+    pub key: i32,
+    pub uid: u32,
+    pub gid: u32,
+    pub cuid: u32,
+    pub cgid: u32,
+    pub mode: u32,
+    pub seq: u16,
+    pad: u16,
+    unused: [usize; 2],
+}
+
+/// State of a segment, the same layout as linux struct shmid64_ds.
+/// Times are in seconds since boot.
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct ShmidDs {
+    pub perm: IpcPerm,
+    pub segsz: usize,
+    pub atime: isize,
+    pub dtime: isize,
+    pub ctime: isize,
+    pub cpid: i32,
+    pub lpid: i32,
+    pub nattch: usize,
+    unused: [usize; 2],
+}
+
+struct ShmInner {
+    mode: u32,
+    nattch: usize,
+    /// Removed by IPC_RMID, it can not be found by its key.
+    removed: bool,
+    /// The pid of the last shmat or shmdt.
+    lpid: usize,
+    atime: usize,
+    dtime: usize,
+    ctime: usize,
+}
+
+pub struct ShmSegment {
+    id: usize,
+    key: i32,
+    /// The size given to shmget, the segment has whole pages.
+    size: usize,
+    cpid: usize,
+    frames: Vec<FrameTracker>,
+    inner: SpinNoIrqLock<ShmInner>,
+}
+
+fn now() -> usize {
+    get_time_ms() / 1000
+}
+
+impl ShmSegment {
+    /// The number of pages of the segment.
+    pub fn pages(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Check the mode for an attachment.
+    /// # Return
+    /// * -EACCES - the mode does not allow reading, or writing if it is not read only
+    pub fn check_access(&self, readonly: bool) -> Result<(), isize> {
+        let mut need = SHM_MODE_READ;
+        if !readonly {
+            need |= SHM_MODE_WRITE;
+        }
+        match self.inner.lock().mode & need == need {
+            true => Ok(()),
+            false => Err(-EACCES),
+        }
+    }
+
+    /// Attach the segment, the attachment is counted until it is dropped.
+    pub fn attach(self: &Arc<Self>) -> ShmAttachment {
+        ShmAttachment::new(self.clone())
+    }
+
+    /// Record the attachment by the process, after the segment is mapped.
+    pub fn attached(&self, pid: usize) {
+        let mut inner = self.inner.lock();
+        inner.lpid = pid;
+        inner.atime = now();
+    }
+
+    pub fn stat(&self) -> ShmidDs {
+        let inner = self.inner.lock();
+        ShmidDs {
+            perm: IpcPerm {
+                key: if inner.removed { IPC_PRIVATE } else { self.key },
+                mode: inner.mode,
+                seq: self.id as u16,
+                ..Default::default()
+            },
+            segsz: self.size,
+            atime: inner.atime as isize,
+            dtime: inner.dtime as isize,
+            ctime: inner.ctime as isize,
+            cpid: self.cpid as i32,
+            lpid: inner.lpid as i32,
+            nattch: inner.nattch,
+            ..Default::default()
+        }
+    }
+
+    /// Set the permission bits of the mode, the others are ignored.
+    pub fn set_mode(&self, mode: u32) {
+        let mut inner = self.inner.lock();
+        inner.mode = mode & SHM_MODE_MASK;
+        inner.ctime = now();
+    }
+}
+
+/// An attachment of a segment, held by the shared area mapping it.
+/// The number of attachments of the segment is the number of ShmAttachment.
+pub struct ShmAttachment(Arc<ShmSegment>);
+
+impl ShmAttachment {
+    fn new(segment: Arc<ShmSegment>) -> Self {
+        segment.inner.lock().nattch += 1;
+        Self(segment)
+    }
+
+    pub fn segment(&self) -> &Arc<ShmSegment> {
+        &self.0
+    }
+
+    /// The frame of the page at the index of the segment.
+    pub fn ppn(&self, index: usize) -> PhysPageNum {
+        self.0.frames[index].ppn
+    }
+
+    /// Detach the segment by the process.
+    pub fn detach(self, pid: usize) {
+        self.0.inner.lock().lpid = pid;
+    }
+}
+
+impl Clone for ShmAttachment {
+    /// A forked child inherits the attachments.
+    fn clone(&self) -> Self {
+        Self::new(self.0.clone())
+    }
+}
+
+impl Drop for ShmAttachment {
+    fn drop(&mut self) {
+        let mut inner = self.0.inner.lock();
+        inner.nattch -= 1;
+        inner.dtime = now();
+        let destroy = inner.removed && inner.nattch == 0;
+        drop(inner);
+        if destroy {
+            SHM_MANAGER.lock().segments.remove(&self.0.id);
+        }
+    }
+}
+
+pub struct ShmManager {
+    /// Segments by id, a removed segment stays until it is destroyed.
+    segments: BTreeMap<usize, Arc<ShmSegment>>,
+    next_id: usize,
+}
+
+impl ShmManager {
+    fn new() -> Self {
+        Self {
+            segments: BTreeMap::new(),
+            next_id: 0,
+        }
+    }
+
+    /// Get the segment of the key, or create a new segment.
+    /// # Parameter
+    /// * 'key' - IPC_PRIVATE always creates a new segment
+    /// * 'size' - the size of the new segment, or at most the size of the existing segment
+    /// * 'flags' - IPC_CREAT, IPC_EXCL and the permission bits of the mode
+    /// * 'pid' - the creator
+    /// # Return
+    /// * The id of the segment.
+    /// * -ENOENT - no segment has the key and IPC_CREAT is not given
+    /// * -EEXIST - a segment has the key and IPC_CREAT and IPC_EXCL are given
+    /// * -EINVAL - size is 0 or larger than SHM_MAX_SIZE, or larger than the existing segment
+    /// * -EACCES - the mode of the existing segment does not have the permission bits
+    /// * -ENOSPC - there are SHM_MAX_SEGMENTS segments
+    /// * -ENOMEM - no enough frames
+    pub fn get(&mut self, key: i32, size: usize, flags: usize, pid: usize) -> Result<usize, isize> {
+        let mode = flags as u32 & SHM_MODE_MASK;
+        if key != IPC_PRIVATE {
+            let existing = self
+                .segments
+                .values()
+                .find(|segment| segment.key == key && !segment.inner.lock().removed);
+            if let Some(segment) = existing {
+                if flags & IPC_CREAT != 0 && flags & IPC_EXCL != 0 {
+                    return Err(-EEXIST);
+                }
+                if size > segment.size {
+                    return Err(-EINVAL);
+                }
+                if mode & !segment.inner.lock().mode != 0 {
+                    return Err(-EACCES);
+                }
+                return Ok(segment.id);
+            }
+            if flags & IPC_CREAT == 0 {
+                return Err(-ENOENT);
+            }
+        }
+        if size == 0 || size > SHM_MAX_SIZE {
+            return Err(-EINVAL);
+        }
+        if self.segments.len() >= SHM_MAX_SEGMENTS {
+            return Err(-ENOSPC);
+        }
+        let frames = (0..size.div_ceil(PAGE_SIZE))
+            .map(|_| frame_alloc())
+            .collect::<Option<Vec<_>>>()
+            .ok_or(-ENOMEM)?;
+        let id = self.next_id;
+        self.next_id += 1;
+        let segment = ShmSegment {
+            id,
+            key,
+            size,
+            cpid: pid,
+            frames,
+            inner: SpinNoIrqLock::new(ShmInner {
+                mode,
+                nattch: 0,
+                removed: false,
+                lpid: 0,
+                atime: 0,
+                dtime: 0,
+                ctime: now(),
+            }),
+        };
+        self.segments.insert(id, Arc::new(segment));
+        Ok(id)
+    }
+
+    /// Find a segment by id, a removed segment is found until it is destroyed.
+    /// # Return
+    /// * -EINVAL - no segment has the id
+    pub fn find(&self, id: usize) -> Result<Arc<ShmSegment>, isize> {
+        self.segments.get(&id).cloned().ok_or(-EINVAL)
+    }
+
+    /// Remove the segment, it is destroyed now if it is not attached.
+    /// # Return
+    /// * -EINVAL - no segment has the id
+    pub fn remove(&mut self, id: usize) -> Result<(), isize> {
+        let segment = self.find(id)?;
+        let mut inner = segment.inner.lock();
+        inner.removed = true;
+        inner.ctime = now();
+        if inner.nattch == 0 {
+            self.segments.remove(&id);
+        }
+        Ok(())
+    }
+}
+
+lazy_static! {
+    pub static ref SHM_MANAGER: SpinNoIrqLock<ShmManager> = SpinNoIrqLock::new(ShmManager::new());
+}
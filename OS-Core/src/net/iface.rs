@@ -0,0 +1,487 @@
+//! The IPv4 stack of TCP and UDP sockets, built on smoltcp.
+//! One ethernet interface has the loopback address 127.0.0.1/8 and the address of QEMU user-mode
+//! networking 10.0.2.15/24, whose gateway 10.0.2.2 is the host. The device of the interface loops
+//! back the frames sent to the interface itself, so the loopback network works without a network
+//! card, and the interface resolves its own addresses with ARP like any other host.
+//!
+//! The stack is polled by every socket operation, and by the scheduling loop of a hart after the
+//! poll timer expires, so polling never runs in interrupt context. Every socket has its own wait
+//! queue, which is woken up when a poll changes the readiness of the smoltcp sockets it owns.
+use alloc::{
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    sync::Arc,
+    vec,
+    vec::Vec,
+};
+use core::sync::atomic::{AtomicBool, Ordering};
+use lazy_static::lazy_static;
+use smoltcp::{
+    iface::{Config, Interface, SocketHandle, SocketSet},
+    phy::{Device, DeviceCapabilities, Medium, RxToken, TxToken},
+    socket::{tcp, udp, Socket},
+    time::Instant,
+    wire::{
+        EthernetAddress, HardwareAddress, IpAddress, IpCidr, IpEndpoint, IpListenEndpoint,
+        Ipv4Address,
+    },
+};
+
+use crate::{
+    config::PAGE_SIZE,
+    drivers::net::{NetDevice, DEFAULT_MAC, MAX_FRAME_LEN, NET_DEVICE},
+    sync::{wait_queue::WaitQueue, SpinNoIrqLock},
+    syscall::errno::{EADDRINUSE, EAGAIN, EINTR},
+    task::{block_current_and_run_next, check_current_signals_error, prepare_interruptible_block},
+    timer::{add_timer_handler, get_time_ms, TimerHandler},
+};
+
+use super::InetAddr;
+
+pub const LOOPBACK_ADDR: [u8; 4] = [127, 0, 0, 1];
+/// The address QEMU user-mode networking gives to the guest.
+pub const GUEST_ADDR: [u8; 4] = [10, 0, 2, 15];
+/// The host in QEMU user-mode networking.
+const GATEWAY_ADDR: [u8; 4] = [10, 0, 2, 2];
+
+/// How often the stack is polled without socket operations,
+/// for retransmissions and frames from the network card.
+const POLL_INTERVAL_MS: usize = 10;
+/// The range of ephemeral ports, the same as linux.
+const EPHEMERAL_PORT_MIN: u16 = 32768;
+const EPHEMERAL_PORT_MAX: u16 = 60999;
+
+/// The size of the send buffer and the receive buffer of a TCP socket.
+const TCP_BUFFER_SIZE: usize = 2 * PAGE_SIZE;
+/// The size of the send buffer and the receive buffer of a UDP socket.
+const UDP_BUFFER_SIZE: usize = 2 * PAGE_SIZE;
+/// The max number of datagrams in a buffer of a UDP socket.
+const UDP_PACKETS: usize = 16;
+
+impl From<InetAddr> for IpEndpoint {
+    fn from(addr: InetAddr) -> Self {
+        IpEndpoint::new(IpAddress::Ipv4(Ipv4Address(addr.addr)), addr.port)
+    }
+}
+
+impl From<InetAddr> for IpListenEndpoint {
+    /// 0.0.0.0 listens on all addresses.
+    fn from(addr: InetAddr) -> Self {
+        IpListenEndpoint {
+            addr: (!addr.is_unspecified()).then_some(IpAddress::Ipv4(Ipv4Address(addr.addr))),
+            port: addr.port,
+        }
+    }
+}
+
+impl From<IpEndpoint> for InetAddr {
+    fn from(endpoint: IpEndpoint) -> Self {
+        let IpAddress::Ipv4(addr) = endpoint.addr;
+        InetAddr {
+            addr: addr.0,
+            port: endpoint.port,
+        }
+    }
+}
+
+/// Whether the address is an address of the interface, or 0.0.0.0.
+pub fn is_local_addr(addr: [u8; 4]) -> bool {
+    addr == [0; 4] || addr == LOOPBACK_ADDR || addr == GUEST_ADDR
+}
+
+/// The source address of the packets to dst.
+pub fn source_addr(dst: [u8; 4]) -> [u8; 4] {
+    if dst[0] == LOOPBACK_ADDR[0] {
+        LOOPBACK_ADDR
+    } else {
+        GUEST_ADDR
+    }
+}
+
+/// The device of the interface.
+struct LoopbackDevice {
+    mac: [u8; 6],
+    /// Frames sent to the interface itself.
+    queue: VecDeque<Vec<u8>>,
+    card: Option<Arc<dyn NetDevice>>,
+    /// The frame being received, its buffer is reused for the frames of the network card.
+    rx_frame: Vec<u8>,
+}
+
+struct LoopbackRxToken<'a>(&'a mut [u8]);
+
+impl RxToken for LoopbackRxToken<'_> {
+    fn consume<R, F>(self, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        f(self.0)
+    }
+}
+
+struct LoopbackTxToken<'a> {
+    mac: [u8; 6],
+    queue: &'a mut VecDeque<Vec<u8>>,
+    card: Option<&'a Arc<dyn NetDevice>>,
+}
+
+impl TxToken for LoopbackTxToken<'_> {
+    /// Frames to the interface itself are looped back, broadcast frames are also looped back
+    /// for ARP requests of its own addresses, and the others go to the network card.
+    fn consume<R, F>(self, len: usize, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        let mut frame = vec![0; len];
+        let ret = f(&mut frame);
+        // The destination is the first field of an ethernet frame.
+        let to_self = frame[..6] == self.mac;
+        if !to_self {
+            if let Some(card) = self.card {
+                card.send(&frame);
+            }
+        }
+        if to_self || frame[..6] == [0xff; 6] {
+            self.queue.push_back(frame);
+        }
+        ret
+    }
+}
+
+impl Device for LoopbackDevice {
+    type RxToken<'a>
+        = LoopbackRxToken<'a>
+    where
+        Self: 'a;
+    type TxToken<'a>
+        = LoopbackTxToken<'a>
+    where
+        Self: 'a;
+
+    fn receive(&mut self, _timestamp: Instant) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+        match self.queue.pop_front() {
+            Some(frame) => self.rx_frame = frame,
+            None => {
+                let card = self.card.as_ref()?;
+                self.rx_frame.resize(MAX_FRAME_LEN, 0);
+                let len = card.recv(&mut self.rx_frame)?;
+                self.rx_frame.truncate(len);
+            }
+        }
+        Some((
+            LoopbackRxToken(&mut self.rx_frame),
+            LoopbackTxToken {
+                mac: self.mac,
+                queue: &mut self.queue,
+                card: self.card.as_ref(),
+            },
+        ))
+    }
+
+    /// Frames are dropped if the network card is busy, TCP sends them again.
+    fn transmit(&mut self, _timestamp: Instant) -> Option<Self::TxToken<'_>> {
+        Some(LoopbackTxToken {
+            mac: self.mac,
+            queue: &mut self.queue,
+            card: self.card.as_ref(),
+        })
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        let mut caps = DeviceCapabilities::default();
+        caps.medium = Medium::Ethernet;
+        caps.max_transmission_unit = MAX_FRAME_LEN;
+        caps
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Protocol {
+    Tcp,
+    Udp,
+}
+
+/// What the waiters of a smoltcp socket wait for, they are woken up when it changes.
+fn readiness(socket: &Socket) -> u8 {
+    match socket {
+        Socket::Tcp(socket) => {
+            (socket.state() as u8) << 2 | (socket.can_recv() as u8) << 1 | socket.can_send() as u8
+        }
+        Socket::Udp(socket) => (socket.can_recv() as u8) << 1 | socket.can_send() as u8,
+    }
+}
+
+pub struct NetStack {
+    iface: Interface,
+    device: LoopbackDevice,
+    sockets: SocketSet<'static>,
+    /// Threads waiting for a TCP or UDP socket and watchers of it, by the id of the socket.
+    waiters: BTreeMap<usize, WaitQueue>,
+    next_id: usize,
+    /// The id of the socket owning a smoltcp socket, and the readiness of the smoltcp socket.
+    owners: BTreeMap<SocketHandle, (usize, u8)>,
+    /// Bound ports, a port is bound by one socket for all addresses.
+    ports: BTreeSet<(Protocol, u16)>,
+    next_port: u16,
+    /// TCP sockets which are closed by the user but still sending,
+    /// removed when they are closed or waiting in TIME-WAIT.
+    closing: Vec<SocketHandle>,
+}
+
+impl NetStack {
+    fn new() -> Self {
+        let card = NET_DEVICE.clone();
+        let mac = card.as_ref().map_or(DEFAULT_MAC, |card| card.mac());
+        let mut device = LoopbackDevice {
+            mac,
+            queue: VecDeque::new(),
+            card,
+            rx_frame: Vec::new(),
+        };
+        let config = Config::new(HardwareAddress::Ethernet(EthernetAddress(mac)));
+        let now = Instant::from_millis(get_time_ms() as i64);
+        let mut iface = Interface::new(config, &mut device, now);
+        // The first address is the source address of UDP sockets bound to 0.0.0.0,
+        // the host of user-mode networking drops packets from 127.0.0.1.
+        iface.update_ip_addrs(|addrs| {
+            addrs
+                .push(IpCidr::new(IpAddress::Ipv4(Ipv4Address(GUEST_ADDR)), 24))
+                .unwrap();
+            addrs
+                .push(IpCidr::new(IpAddress::Ipv4(Ipv4Address(LOOPBACK_ADDR)), 8))
+                .unwrap();
+        });
+        iface
+            .routes_mut()
+            .add_default_ipv4_route(Ipv4Address(GATEWAY_ADDR))
+            .unwrap();
+        Self {
+            iface,
+            device,
+            sockets: SocketSet::new(Vec::new()),
+            waiters: BTreeMap::new(),
+            next_id: 0,
+            owners: BTreeMap::new(),
+            ports: BTreeSet::new(),
+            next_port: EPHEMERAL_PORT_MIN,
+            closing: Vec::new(),
+        }
+    }
+
+    /// Send and receive frames, handle the timers of the sockets, remove the closed sockets,
+    /// and wake up the waiters of the sockets whose readiness has changed.
+    pub fn poll(&mut self) {
+        let now = Instant::from_millis(get_time_ms() as i64);
+        if self.iface.poll(now, &mut self.device, &mut self.sockets) {
+            for (handle, socket) in self.sockets.iter() {
+                let Some((owner, last)) = self.owners.get_mut(&handle) else {
+                    continue;
+                };
+                let readiness = readiness(socket);
+                if *last != readiness {
+                    *last = readiness;
+                    if let Some(waiters) = self.waiters.get_mut(owner) {
+                        waiters.wake_all();
+                    }
+                }
+            }
+        }
+        for handle in core::mem::take(&mut self.closing) {
+            let state = self.sockets.get::<tcp::Socket>(handle).state();
+            if state == tcp::State::Closed || state == tcp::State::TimeWait {
+                self.remove(handle);
+            } else {
+                self.closing.push(handle);
+            }
+        }
+    }
+
+    /// Add the wait queue of a new socket.
+    /// # Return
+    /// * The id of the socket, which owns the smoltcp sockets added with it.
+    pub fn add_waiters(&mut self) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.waiters.insert(id, WaitQueue::new());
+        id
+    }
+
+    /// Remove the wait queue of a dropped socket.
+    pub fn remove_waiters(&mut self, id: usize) {
+        self.waiters.remove(&id);
+    }
+
+    pub fn waiters(&mut self, id: usize) -> &mut WaitQueue {
+        self.waiters.get_mut(&id).unwrap()
+    }
+
+    /// Give the smoltcp socket to the socket of the id, such as an accepted connection.
+    /// The readiness of a new smoltcp socket starts at 0, which wakes up its waiters
+    /// at most once more than needed.
+    pub fn set_owner(&mut self, handle: SocketHandle, owner: usize) {
+        let readiness = self
+            .owners
+            .get(&handle)
+            .map_or(0, |(_, readiness)| *readiness);
+        self.owners.insert(handle, (owner, readiness));
+    }
+
+    /// Remove the smoltcp socket.
+    pub fn remove(&mut self, handle: SocketHandle) {
+        self.sockets.remove(handle);
+        self.owners.remove(&handle);
+    }
+
+    /// Start connecting the TCP socket.
+    pub fn connect_tcp(
+        &mut self,
+        handle: SocketHandle,
+        remote: InetAddr,
+        local: InetAddr,
+    ) -> Result<(), tcp::ConnectError> {
+        let socket = self.sockets.get_mut::<tcp::Socket>(handle);
+        socket.connect(
+            self.iface.context(),
+            IpEndpoint::from(remote),
+            IpEndpoint::from(local),
+        )
+    }
+
+    /// Take the port for a socket.
+    /// # Parameter
+    /// * 'port' - 0 for an ephemeral port
+    /// # Return
+    /// * The port.
+    /// * -EADDRINUSE - the port is bound, or all ephemeral ports are bound
+    pub fn bind_port(&mut self, protocol: Protocol, port: u16) -> Result<u16, isize> {
+        if port != 0 {
+            return match self.ports.insert((protocol, port)) {
+                true => Ok(port),
+                false => Err(-EADDRINUSE),
+            };
+        }
+        for _ in EPHEMERAL_PORT_MIN..=EPHEMERAL_PORT_MAX {
+            let port = self.next_port;
+            self.next_port = if port == EPHEMERAL_PORT_MAX {
+                EPHEMERAL_PORT_MIN
+            } else {
+                port + 1
+            };
+            if self.ports.insert((protocol, port)) {
+                return Ok(port);
+            }
+        }
+        Err(-EADDRINUSE)
+    }
+
+    pub fn unbind_port(&mut self, protocol: Protocol, port: u16) {
+        self.ports.remove(&(protocol, port));
+    }
+
+    /// Add a smoltcp socket owned by the socket of the id.
+    pub fn add_tcp(&mut self, owner: usize) -> SocketHandle {
+        let socket = tcp::Socket::new(
+            tcp::SocketBuffer::new(vec![0; TCP_BUFFER_SIZE]),
+            tcp::SocketBuffer::new(vec![0; TCP_BUFFER_SIZE]),
+        );
+        let handle = self.sockets.add(socket);
+        self.set_owner(handle, owner);
+        handle
+    }
+
+    pub fn tcp(&mut self, handle: SocketHandle) -> &mut tcp::Socket<'static> {
+        self.sockets.get_mut(handle)
+    }
+
+    /// Close the TCP socket, it is removed after the connection is closed.
+    pub fn close_tcp(&mut self, handle: SocketHandle) {
+        self.tcp(handle).close();
+        self.closing.push(handle);
+    }
+
+    /// Add a smoltcp socket owned by the socket of the id.
+    pub fn add_udp(&mut self, owner: usize) -> SocketHandle {
+        let socket = udp::Socket::new(
+            udp::PacketBuffer::new(
+                vec![udp::PacketMetadata::EMPTY; UDP_PACKETS],
+                vec![0; UDP_BUFFER_SIZE],
+            ),
+            udp::PacketBuffer::new(
+                vec![udp::PacketMetadata::EMPTY; UDP_PACKETS],
+                vec![0; UDP_BUFFER_SIZE],
+            ),
+        );
+        let handle = self.sockets.add(socket);
+        self.set_owner(handle, owner);
+        handle
+    }
+
+    pub fn udp(&mut self, handle: SocketHandle) -> &mut udp::Socket<'static> {
+        self.sockets.get_mut(handle)
+    }
+}
+
+lazy_static! {
+    pub static ref NET: SpinNoIrqLock<NetStack> = SpinNoIrqLock::new(NetStack::new());
+}
+
+/// The poll timer has expired since the last poll by poll_if_due.
+static POLL_DUE: AtomicBool = AtomicBool::new(false);
+
+/// Ask for polling the stack periodically, the timer only marks it due.
+struct NetPoller;
+
+impl TimerHandler for NetPoller {
+    fn expire(&self) {
+        POLL_DUE.store(true, Ordering::Release);
+        add_timer_handler(get_time_ms() + POLL_INTERVAL_MS, Arc::new(NetPoller));
+    }
+}
+
+/// Poll the stack if the poll timer has expired, called by the scheduling loop of a hart.
+pub fn poll_if_due() {
+    if POLL_DUE.swap(false, Ordering::AcqRel) {
+        NET.lock().poll();
+    }
+}
+
+/// Set up the stack and start polling it.
+pub fn init() {
+    NET.lock().poll();
+    add_timer_handler(get_time_ms() + POLL_INTERVAL_MS, Arc::new(NetPoller));
+}
+
+/// Try f with the polled stack until it returns Some, the stack is polled again after f
+/// to send what f has queued. The lock of a socket is taken inside f, after the lock of the stack.
+/// # Parameter
+/// * 'id' - the socket, the thread waits in its queue
+/// * 'nonblock' - do not wait if f returns None
+/// # Return
+/// * The result of f.
+/// * -EAGAIN - f returns None and nonblock is true
+/// * -EINTR - interrupted by a signal while waiting
+pub fn wait_net<T>(
+    id: usize,
+    nonblock: bool,
+    mut f: impl FnMut(&mut NetStack) -> Option<Result<T, isize>>,
+) -> Result<T, isize> {
+    loop {
+        prepare_interruptible_block();
+        let mut net = NET.lock();
+        net.poll();
+        if let Some(ret) = f(&mut net) {
+            net.poll();
+            return ret;
+        }
+        if nonblock {
+            return Err(-EAGAIN);
+        }
+        if check_current_signals_error().is_some() {
+            return Err(-EINTR);
+        }
+        net.waiters(id).push_current();
+        drop(net);
+        block_current_and_run_next();
+        // Woken up by a signal, the thread is still in the wait queue.
+        NET.lock().waiters(id).remove_current();
+    }
+}
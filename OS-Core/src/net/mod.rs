@@ -0,0 +1,191 @@
+//! Sockets, the constants are the same values as linux.
+//! Every kind of socket is a file implementing Socket, the socket syscalls work on the trait.
+pub mod iface;
+pub mod tcp;
+pub mod udp;
+pub mod unix;
+
+use alloc::{string::String, sync::Arc, vec::Vec};
+
+use crate::{
+    fs::File,
+    mm::page_table::UserBuffer,
+    task::{processor::current_process, send_signal, signal::SignalFlags},
+};
+
+/// Unix domain sockets, addressed by paths in the file system.
+pub const AF_UNIX: usize = 1;
+/// IPv4 sockets.
+pub const AF_INET: usize = 2;
+
+/// Protocols of AF_INET sockets, 0 selects the protocol of the socket type.
+pub const IPPROTO_TCP: usize = 6;
+pub const IPPROTO_UDP: usize = 17;
+
+/// Reliable byte stream.
+pub const SOCK_STREAM: usize = 1;
+/// Messages with boundaries.
+pub const SOCK_DGRAM: usize = 2;
+/// The low bits of the type argument of socket are the type, the other bits are flags.
+pub const SOCK_TYPE_MASK: usize = 0xf;
+pub const SOCK_NONBLOCK: usize = 1 << 11;
+pub const SOCK_CLOEXEC: usize = 1 << 19;
+
+/// The max backlog of listen.
+pub const SOMAXCONN: usize = 128;
+
+/// Flags of send and recv.
+pub const MSG_CTRUNC: u32 = 0x8;
+pub const MSG_TRUNC: u32 = 0x20;
+pub const MSG_DONTWAIT: u32 = 0x40;
+/// Do not send SIGPIPE when the peer is closed.
+pub const MSG_NOSIGNAL: u32 = 0x4000;
+/// Received fds are opened with FD_CLOEXEC.
+pub const MSG_CMSG_CLOEXEC: u32 = 0x4000_0000;
+
+/// How of shutdown.
+pub const SHUT_RD: usize = 0;
+pub const SHUT_WR: usize = 1;
+pub const SHUT_RDWR: usize = 2;
+
+/// Level and type of the control message passing fds.
+pub const SOL_SOCKET: i32 = 1;
+pub const SCM_RIGHTS: i32 = 1;
+
+/// The max length of the path of a unix socket address.
+pub const UNIX_PATH_MAX: usize = 108;
+/// The max number of iovecs of sendmsg and recvmsg.
+pub const UIO_MAXIOV: usize = 1024;
+
+/// Address of a unix domain socket, the same layout as linux struct sockaddr_un.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct SockaddrUn {
+    pub family: u16,
+    /// The path ending with '\0'.
+    pub path: [u8; UNIX_PATH_MAX],
+}
+
+impl Default for SockaddrUn {
+    fn default() -> Self {
+        Self {
+            family: AF_UNIX as u16,
+            path: [0; UNIX_PATH_MAX],
+        }
+    }
+}
+
+/// Address of an IPv4 socket, the same layout as linux struct sockaddr_in.
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct SockaddrIn {
+    pub family: u16,
+    /// In network byte order.
+    pub port: u16,
+    /// In network byte order.
+    pub addr: [u8; 4],
+    pub zero: [u8; 8],
+}
+
+/// An IPv4 address and a port, 0.0.0.0 is any address and port 0 is any port.
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub struct InetAddr {
+    pub addr: [u8; 4],
+    pub port: u16,
+}
+
+impl InetAddr {
+    pub fn is_unspecified(&self) -> bool {
+        self.addr == [0; 4]
+    }
+}
+
+/// Address of a socket, read from or written to the user sockaddr of its family.
+#[derive(Clone)]
+pub enum SockAddr {
+    /// The path of a unix domain socket, None for an unbound socket.
+    Unix(Option<String>),
+    Inet(InetAddr),
+}
+
+/// Open files sent with SCM_RIGHTS.
+pub type PassedFiles = Vec<Arc<dyn File + Send + Sync>>;
+
+/// Received data of recv.
+#[derive(Default)]
+pub struct RecvMsg {
+    /// The number of bytes received,
+    /// or the whole length of a truncated datagram if MSG_TRUNC is passed to recv.
+    pub len: usize,
+    pub files: PassedFiles,
+    /// The address of the sender, None if the socket does not report it.
+    pub from: Option<SockAddr>,
+    /// MSG_TRUNC if a datagram is truncated.
+    pub flags: u32,
+}
+
+/// The operations of the socket syscalls, an address of another family is -EINVAL.
+pub trait Socket: File {
+    fn bind(&self, addr: SockAddr) -> isize;
+    fn listen(&self, backlog: usize) -> isize;
+    /// Take a connection of a listening socket.
+    /// # Return
+    /// * (the connected socket, the address of the client)
+    fn accept(&self) -> Result<(Arc<dyn File + Send + Sync>, SockAddr), isize>;
+    fn connect(&self, addr: SockAddr) -> isize;
+    /// # Parameter
+    /// * 'files' - files of SCM_RIGHTS, ignored by sockets which can not pass files
+    /// * 'to' - the receiver of a datagram, None for the connected peer
+    fn send(&self, buf: UserBuffer, files: PassedFiles, to: Option<SockAddr>, flags: u32) -> isize;
+    fn recv(&self, buf: UserBuffer, flags: u32) -> Result<RecvMsg, isize>;
+    fn shutdown(&self, how: usize) -> isize;
+    /// The bound address.
+    fn local_addr(&self) -> SockAddr;
+    /// The address of the connected peer.
+    /// # Return
+    /// * -ENOTCONN - the socket is not connected
+    fn peer_addr(&self) -> Result<SockAddr, isize>;
+}
+
+/// Send SIGPIPE to the current process for writing to a closed stream, unless MSG_NOSIGNAL.
+pub fn broken_pipe(flags: u32) {
+    if flags & MSG_NOSIGNAL == 0 {
+        send_signal(&current_process(), SignalFlags::SIGPIPE);
+    }
+}
+
+/// A user buffer, the same layout as linux struct iovec.
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct IoVec {
+    pub base: usize,
+    pub len: usize,
+}
+
+/// Message of sendmsg and recvmsg, the same layout as linux struct msghdr.
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct MsgHdr {
+    /// Address of the receiver or the sender, a SockaddrUn.
+    pub name: usize,
+    pub namelen: u32,
+    /// An array of IoVec.
+    pub iov: usize,
+    pub iovlen: usize,
+    /// Control messages, each starts with a CmsgHdr.
+    pub control: usize,
+    pub controllen: usize,
+    /// Flags of the received message, such as MSG_TRUNC.
+    pub flags: u32,
+}
+
+/// Header of a control message, the same layout as linux struct cmsghdr.
+/// The data follows the header, and the next message starts at the 8 bytes aligned end of the data.
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct CmsgHdr {
+    /// The length of the header and the data.
+    pub len: usize,
+    pub level: i32,
+    pub type_: i32,
+}
@@ -0,0 +1,545 @@
+//! TCP sockets. A smoltcp socket carries one connection, so a listening socket keeps a backlog
+//! of smoltcp sockets listening on its port, and accept takes an established one out
+//! and puts a new listening one in its place.
+//! The lock of the stack is always taken before the lock of a socket.
+use alloc::{
+    string::String,
+    sync::{Arc, Weak},
+    vec,
+    vec::Vec,
+};
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use smoltcp::{iface::SocketHandle, socket::tcp::State, wire::IpListenEndpoint};
+
+use crate::{
+    fs::{pipe::written_or, poll::PollEvents, File},
+    mm::page_table::UserBuffer,
+    sync::{wait_queue::Watcher, SpinNoIrqLock},
+    syscall::errno::{
+        EADDRNOTAVAIL, EALREADY, ECONNREFUSED, EINPROGRESS, EINVAL, EISCONN, ENOTCONN, EPIPE,
+    },
+};
+
+use super::{
+    broken_pipe,
+    iface::{is_local_addr, source_addr, wait_net, NetStack, Protocol, NET},
+    InetAddr, PassedFiles, RecvMsg, SockAddr, Socket, MSG_DONTWAIT, SHUT_RD, SHUT_RDWR, SHUT_WR,
+};
+
+/// The max backlog of a listening socket, every pending connection has its own buffers.
+const TCP_MAX_BACKLOG: usize = 4;
+
+enum TcpState {
+    /// Created, or bound.
+    Unconnected,
+    Listening {
+        /// Smoltcp sockets listening on the port, or connected and waiting to be accepted.
+        backlog: Vec<SocketHandle>,
+    },
+    /// Connecting or connected.
+    Connected {
+        handle: SocketHandle,
+        remote: InetAddr,
+    },
+}
+
+struct TcpInner {
+    state: TcpState,
+    /// The bound address, or the address of the connection.
+    local: InetAddr,
+    /// The port is bound by this socket, accepted sockets use the port of the listening socket.
+    owns_port: bool,
+    read_shutdown: bool,
+}
+
+pub struct TcpSocket {
+    /// The id of the wait queue in the stack.
+    id: usize,
+    /// Return EAGAIN instead of blocking.
+    nonblock: AtomicBool,
+    inner: SpinNoIrqLock<TcpInner>,
+}
+
+/// The address of a socket syscall.
+/// # Return
+/// * -EINVAL - the address is not an IPv4 address
+fn inet_addr(addr: SockAddr) -> Result<InetAddr, isize> {
+    match addr {
+        SockAddr::Inet(addr) => Ok(addr),
+        _ => Err(-EINVAL),
+    }
+}
+
+/// The smoltcp socket is connected and waiting to be accepted.
+fn is_established(net: &mut NetStack, handle: SocketHandle) -> bool {
+    !matches!(net.tcp(handle).state(), State::Listen | State::SynReceived)
+}
+
+impl TcpSocket {
+    /// Create an unbound and unconnected socket.
+    pub fn new() -> Self {
+        let id = NET.lock().add_waiters();
+        Self::with_state(id, TcpState::Unconnected, InetAddr::default(), false)
+    }
+
+    fn with_state(id: usize, state: TcpState, local: InetAddr, owns_port: bool) -> Self {
+        Self {
+            id,
+            nonblock: AtomicBool::new(false),
+            inner: SpinNoIrqLock::new(TcpInner {
+                state,
+                local,
+                owns_port,
+                read_shutdown: false,
+            }),
+        }
+    }
+
+    /// Bind an ephemeral port if the socket is not bound.
+    /// # Return
+    /// * -EADDRINUSE - no ephemeral port is free
+    fn autobind(net: &mut NetStack, inner: &mut TcpInner) -> Result<(), isize> {
+        if inner.local.port == 0 {
+            inner.local.port = net.bind_port(Protocol::Tcp, 0)?;
+            inner.owns_port = true;
+        }
+        Ok(())
+    }
+
+    /// Start connecting, the socket is unconnected.
+    fn start_connect(&self, net: &mut NetStack, remote: InetAddr) -> isize {
+        let mut inner = self.inner.lock();
+        if let Err(errno) = Self::autobind(net, &mut inner) {
+            return errno;
+        }
+        if inner.local.is_unspecified() {
+            inner.local.addr = source_addr(remote.addr);
+        }
+        let handle = net.add_tcp(self.id);
+        if net.connect_tcp(handle, remote, inner.local).is_err() {
+            net.remove(handle);
+            return -EINVAL;
+        }
+        inner.state = TcpState::Connected { handle, remote };
+        0
+    }
+
+    /// Wait until the connection is established or refused.
+    /// A refused socket becomes unconnected, and it can connect again.
+    fn wait_connected(&self) -> isize {
+        let ret = wait_net(self.id, false, |net| {
+            let mut inner = self.inner.lock();
+            let TcpState::Connected { handle, .. } = inner.state else {
+                return Some(Err(-ECONNREFUSED));
+            };
+            match net.tcp(handle).state() {
+                State::SynSent => None,
+                State::Closed => {
+                    net.remove(handle);
+                    inner.state = TcpState::Unconnected;
+                    Some(Err(-ECONNREFUSED))
+                }
+                _ => Some(Ok(0)),
+            }
+        });
+        ret.unwrap_or_else(|errno| errno)
+    }
+}
+
+impl Socket for TcpSocket {
+    /// Bind the socket to the address, port 0 is an ephemeral port.
+    /// # Return
+    /// * -EINVAL - the socket is already bound, or addr is not an IPv4 address
+    /// * -EADDRNOTAVAIL - the address is not an address of the machine
+    /// * -EADDRINUSE - the port is bound by another socket
+    fn bind(&self, addr: SockAddr) -> isize {
+        let addr = match inet_addr(addr) {
+            Ok(addr) => addr,
+            Err(errno) => return errno,
+        };
+        if !is_local_addr(addr.addr) {
+            return -EADDRNOTAVAIL;
+        }
+        let mut net = NET.lock();
+        let mut inner = self.inner.lock();
+        if inner.local.port != 0 {
+            return -EINVAL;
+        }
+        match net.bind_port(Protocol::Tcp, addr.port) {
+            Ok(port) => {
+                inner.local = InetAddr {
+                    addr: addr.addr,
+                    port,
+                };
+                inner.owns_port = true;
+                0
+            }
+            Err(errno) => errno,
+        }
+    }
+
+    /// Accept connections, an unbound socket is bound to an ephemeral port.
+    /// # Parameter
+    /// * 'backlog' - the max number of connections waiting to be accepted, at most TCP_MAX_BACKLOG
+    /// # Return
+    /// * -EINVAL - the socket is connected
+    /// * -EADDRINUSE - no ephemeral port is free
+    fn listen(&self, backlog: usize) -> isize {
+        let mut net = NET.lock();
+        let mut inner = self.inner.lock();
+        match inner.state {
+            TcpState::Unconnected => {}
+            TcpState::Listening { .. } => return 0,
+            TcpState::Connected { .. } => return -EINVAL,
+        }
+        if let Err(errno) = Self::autobind(&mut net, &mut inner) {
+            return errno;
+        }
+        let endpoint = IpListenEndpoint::from(inner.local);
+        let backlog = (0..backlog.clamp(1, TCP_MAX_BACKLOG))
+            .map(|_| {
+                let handle = net.add_tcp(self.id);
+                net.tcp(handle).listen(endpoint).unwrap();
+                handle
+            })
+            .collect();
+        inner.state = TcpState::Listening { backlog };
+        0
+    }
+
+    /// Take an established connection, wait until a client connects.
+    /// # Return
+    /// * -EINVAL - the socket is not listening
+    /// * -EAGAIN - no connection is established and the socket is non-blocking
+    /// * -EINTR - interrupted by a signal while waiting
+    fn accept(&self) -> Result<(Arc<dyn File + Send + Sync>, SockAddr), isize> {
+        wait_net(self.id, self.nonblock(), |net| {
+            let mut inner = self.inner.lock();
+            let local = inner.local;
+            let TcpState::Listening { backlog } = &mut inner.state else {
+                return Some(Err(-EINVAL));
+            };
+            let index = backlog
+                .iter()
+                .position(|handle| is_established(net, *handle))?;
+            let handle = backlog.remove(index);
+            let listener = net.add_tcp(self.id);
+            net.tcp(listener)
+                .listen(IpListenEndpoint::from(local))
+                .unwrap();
+            backlog.push(listener);
+            let socket = net.tcp(handle);
+            let remote = socket.remote_endpoint().map(InetAddr::from);
+            let remote = remote.unwrap_or_default();
+            let local = socket.local_endpoint().map_or(local, InetAddr::from);
+            let id = net.add_waiters();
+            net.set_owner(handle, id);
+            let connection =
+                Self::with_state(id, TcpState::Connected { handle, remote }, local, false);
+            Some(Ok((
+                Arc::new(connection) as Arc<dyn File + Send + Sync>,
+                SockAddr::Inet(remote),
+            )))
+        })
+    }
+
+    /// Connect to the address, an unbound socket is bound to an ephemeral port,
+    /// wait until the connection is established.
+    /// # Return
+    /// * -EINVAL - the socket is listening, or addr is not an address to connect to
+    /// * -EISCONN - the socket is already connected
+    /// * -EALREADY - a non-blocking connect is in progress
+    /// * -EINPROGRESS - the connection is started and the socket is non-blocking
+    /// * -ECONNREFUSED - the connection is refused
+    /// * -EINTR - interrupted by a signal, the connection goes on
+    fn connect(&self, addr: SockAddr) -> isize {
+        let remote = match inet_addr(addr) {
+            Ok(addr) => addr,
+            Err(errno) => return errno,
+        };
+        let mut net = NET.lock();
+        let state = match &self.inner.lock().state {
+            TcpState::Unconnected => None,
+            TcpState::Listening { .. } => Some(-EINVAL),
+            TcpState::Connected { handle, .. } => match net.tcp(*handle).state() {
+                State::SynSent => Some(-EALREADY),
+                _ => Some(-EISCONN),
+            },
+        };
+        if let Some(errno) = state {
+            return errno;
+        }
+        let ret = self.start_connect(&mut net, remote);
+        net.poll();
+        drop(net);
+        if ret < 0 {
+            return ret;
+        }
+        if self.nonblock() {
+            return -EINPROGRESS;
+        }
+        self.wait_connected()
+    }
+
+    /// Send the data, wait until all data is in the send buffer.
+    /// # Parameter
+    /// * 'files', 'to' - ignored
+    /// * 'flags' - MSG_DONTWAIT and MSG_NOSIGNAL
+    /// # Return
+    /// * The number of bytes sent.
+    /// * -ENOTCONN - the socket is not connected
+    /// * -EPIPE - the connection is closed, or the socket is shut down for writing
+    /// * -EAGAIN - the send buffer is full and the socket is non-blocking
+    /// * -EINTR - interrupted by a signal while waiting
+    #[allow(unused)]
+    fn send(&self, buf: UserBuffer, files: PassedFiles, to: Option<SockAddr>, flags: u32) -> isize {
+        let nonblock = self.nonblock() || flags & MSG_DONTWAIT != 0;
+        let total = buf.len();
+        if total == 0 {
+            return match self.inner.lock().state {
+                TcpState::Connected { .. } => 0,
+                _ => -ENOTCONN,
+            };
+        }
+        let mut buf_iter = buf.into_iter();
+        let mut sent = 0;
+        loop {
+            let ret = wait_net(self.id, nonblock, |net| {
+                let TcpState::Connected { handle, .. } = self.inner.lock().state else {
+                    return Some(Err(-ENOTCONN));
+                };
+                let socket = net.tcp(handle);
+                if socket.state() == State::SynSent {
+                    return None;
+                }
+                if !socket.may_send() {
+                    return Some(Err(-EPIPE));
+                }
+                let len = (socket.send_capacity() - socket.send_queue()).min(total - sent);
+                if len == 0 {
+                    return None;
+                }
+                let data: Vec<u8> = buf_iter
+                    .by_ref()
+                    .take(len)
+                    .map(|src| unsafe { *src })
+                    .collect();
+                Some(socket.send_slice(&data).map_err(|_| -EPIPE))
+            });
+            match ret {
+                Ok(len) => {
+                    sent += len;
+                    if sent == total {
+                        return total as isize;
+                    }
+                }
+                Err(errno) => {
+                    if errno == -EPIPE {
+                        broken_pipe(flags);
+                    }
+                    return written_or(sent, -errno);
+                }
+            }
+        }
+    }
+
+    /// Receive data, wait until there is some data.
+    /// # Parameter
+    /// * 'flags' - MSG_DONTWAIT
+    /// # Return
+    /// * The received data, its length is 0 at the end of the stream.
+    /// * -ENOTCONN - the socket is not connected
+    /// * -EAGAIN - there is no data and the socket is non-blocking
+    /// * -EINTR - interrupted by a signal while waiting
+    fn recv(&self, buf: UserBuffer, flags: u32) -> Result<RecvMsg, isize> {
+        let nonblock = self.nonblock() || flags & MSG_DONTWAIT != 0;
+        let len = buf.len();
+        if len == 0 {
+            return match self.inner.lock().state {
+                TcpState::Connected { .. } => Ok(RecvMsg::default()),
+                _ => Err(-ENOTCONN),
+            };
+        }
+        let mut buf_iter = buf.into_iter();
+        wait_net(self.id, nonblock, |net| {
+            let inner = self.inner.lock();
+            let TcpState::Connected { handle, .. } = inner.state else {
+                return Some(Err(-ENOTCONN));
+            };
+            if inner.read_shutdown {
+                return Some(Ok(RecvMsg::default()));
+            }
+            let socket = net.tcp(handle);
+            if socket.can_recv() {
+                let mut data = vec![0; socket.recv_queue().min(len)];
+                let len = socket.recv_slice(&mut data).unwrap_or(0);
+                for (dst, byte) in buf_iter.by_ref().zip(&data[..len]) {
+                    unsafe { *dst = *byte };
+                }
+                return Some(Ok(RecvMsg {
+                    len,
+                    ..Default::default()
+                }));
+            }
+            match socket.state() {
+                State::SynSent => None,
+                _ if !socket.may_recv() => Some(Ok(RecvMsg::default())),
+                _ => None,
+            }
+        })
+    }
+
+    /// Shut down reading, writing or both. Shutting down writing sends FIN,
+    /// and the peer gets end of file after it has received the data.
+    /// # Return
+    /// * -ENOTCONN - the socket is not connected
+    fn shutdown(&self, how: usize) -> isize {
+        let mut net = NET.lock();
+        let mut inner = self.inner.lock();
+        let TcpState::Connected { handle, .. } = inner.state else {
+            return -ENOTCONN;
+        };
+        if how == SHUT_WR || how == SHUT_RDWR {
+            net.tcp(handle).close();
+        }
+        if how == SHUT_RD || how == SHUT_RDWR {
+            inner.read_shutdown = true;
+        }
+        drop(inner);
+        net.poll();
+        // Readers of this socket get end of file.
+        net.waiters(self.id).wake_all();
+        0
+    }
+
+    /// The bound address, or the local address of the connection.
+    fn local_addr(&self) -> SockAddr {
+        SockAddr::Inet(self.inner.lock().local)
+    }
+
+    fn peer_addr(&self) -> Result<SockAddr, isize> {
+        match self.inner.lock().state {
+            TcpState::Connected { remote, .. } => Ok(SockAddr::Inet(remote)),
+            _ => Err(-ENOTCONN),
+        }
+    }
+}
+
+impl Drop for TcpSocket {
+    /// The connection is closed gracefully, its smoltcp socket is removed after the close.
+    fn drop(&mut self) {
+        let mut net = NET.lock();
+        let mut inner = self.inner.lock();
+        match core::mem::replace(&mut inner.state, TcpState::Unconnected) {
+            TcpState::Unconnected => {}
+            TcpState::Listening { backlog } => {
+                for handle in backlog {
+                    net.close_tcp(handle);
+                }
+            }
+            TcpState::Connected { handle, .. } => net.close_tcp(handle),
+        }
+        if inner.owns_port {
+            net.unbind_port(Protocol::Tcp, inner.local.port);
+        }
+        net.remove_waiters(self.id);
+        net.poll();
+    }
+}
+
+impl File for TcpSocket {
+    fn readable(&self) -> bool {
+        true
+    }
+
+    fn writable(&self) -> bool {
+        true
+    }
+
+    fn read(&self, buf: UserBuffer) -> isize {
+        match self.recv(buf, 0) {
+            Ok(msg) => msg.len as isize,
+            Err(errno) => errno,
+        }
+    }
+
+    fn write(&self, buf: UserBuffer) -> isize {
+        self.send(buf, Vec::new(), None, 0)
+    }
+
+    /// A listening socket is readable when a connection is established.
+    /// A connecting socket is not ready, a refused or reset connection is POLLERR.
+    fn poll_ready(&self) -> PollEvents {
+        let mut net = NET.lock();
+        net.poll();
+        let inner = self.inner.lock();
+        let mut events = PollEvents::empty();
+        match &inner.state {
+            TcpState::Unconnected => events |= PollEvents::POLLOUT | PollEvents::POLLHUP,
+            TcpState::Listening { backlog } => {
+                if backlog
+                    .iter()
+                    .any(|handle| is_established(&mut net, *handle))
+                {
+                    events |= PollEvents::POLLIN;
+                }
+            }
+            TcpState::Connected { handle, .. } => {
+                let socket = net.tcp(*handle);
+                match socket.state() {
+                    State::SynSent => {}
+                    State::Closed if !socket.can_recv() => {
+                        events |= PollEvents::POLLIN | PollEvents::POLLERR | PollEvents::POLLHUP
+                    }
+                    _ => {
+                        if socket.can_recv() || !socket.may_recv() || inner.read_shutdown {
+                            events |= PollEvents::POLLIN;
+                        }
+                        if socket.can_send() {
+                            events |= PollEvents::POLLOUT;
+                        }
+                        if !socket.is_open() {
+                            events |= PollEvents::POLLHUP;
+                        }
+                    }
+                }
+            }
+        }
+        events
+    }
+
+    fn register_waker(&self) {
+        NET.lock().waiters(self.id).push_current();
+    }
+
+    fn unregister_waker(&self) {
+        NET.lock().waiters(self.id).remove_current();
+    }
+
+    fn add_watcher(&self, watcher: Weak<dyn Watcher>) -> bool {
+        NET.lock().waiters(self.id).add_watcher(watcher);
+        true
+    }
+
+    fn remove_watcher(&self, watcher: &Weak<dyn Watcher>) {
+        NET.lock().waiters(self.id).remove_watcher(watcher);
+    }
+
+    fn nonblock(&self) -> bool {
+        self.nonblock.load(Ordering::Relaxed)
+    }
+
+    fn set_nonblock(&self, nonblock: bool) -> bool {
+        self.nonblock.store(nonblock, Ordering::Relaxed);
+        true
+    }
+
+    fn as_socket(&self) -> Option<&dyn Socket> {
+        Some(self)
+    }
+
+    fn name(&self) -> String {
+        String::from("socket:[tcp]")
+    }
+}
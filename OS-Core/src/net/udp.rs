@@ -0,0 +1,354 @@
+//! UDP sockets. The smoltcp socket is created when the socket is bound,
+//! a socket used before it is bound is bound to an ephemeral port.
+//! The lock of the stack is always taken before the lock of a socket.
+use alloc::{
+    string::String,
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use smoltcp::{
+    iface::SocketHandle,
+    socket::udp::SendError,
+    wire::{IpEndpoint, IpListenEndpoint},
+};
+
+use crate::{
+    drivers::net::MAX_FRAME_LEN,
+    fs::{poll::PollEvents, File},
+    mm::page_table::UserBuffer,
+    sync::{wait_queue::Watcher, SpinNoIrqLock},
+    syscall::errno::{EADDRNOTAVAIL, EDESTADDRREQ, EINVAL, EMSGSIZE, ENOTCONN, EOPNOTSUPP},
+};
+
+use super::{
+    iface::{is_local_addr, wait_net, NetStack, Protocol, NET},
+    InetAddr, PassedFiles, RecvMsg, SockAddr, Socket, MSG_DONTWAIT, MSG_TRUNC,
+};
+
+/// The max size of a datagram, it must fit in an ethernet frame
+/// after the ethernet header, the IPv4 header and the UDP header.
+pub const UDP_MAX_PAYLOAD: usize = MAX_FRAME_LEN - 14 - 20 - 8;
+
+struct UdpInner {
+    /// The smoltcp socket of the bound socket.
+    handle: Option<SocketHandle>,
+    local: InetAddr,
+    /// The default destination, datagrams from other addresses are dropped.
+    peer: Option<InetAddr>,
+}
+
+pub struct UdpSocket {
+    /// The id of the wait queue in the stack.
+    id: usize,
+    /// Return EAGAIN instead of blocking.
+    nonblock: AtomicBool,
+    inner: SpinNoIrqLock<UdpInner>,
+}
+
+/// The address of a socket syscall.
+/// # Return
+/// * -EINVAL - the address is not an IPv4 address
+fn inet_addr(addr: SockAddr) -> Result<InetAddr, isize> {
+    match addr {
+        SockAddr::Inet(addr) => Ok(addr),
+        _ => Err(-EINVAL),
+    }
+}
+
+impl UdpSocket {
+    /// Create an unbound socket.
+    pub fn new() -> Self {
+        Self {
+            id: NET.lock().add_waiters(),
+            nonblock: AtomicBool::new(false),
+            inner: SpinNoIrqLock::new(UdpInner {
+                handle: None,
+                local: InetAddr::default(),
+                peer: None,
+            }),
+        }
+    }
+
+    /// Bind the socket to the address, port 0 is an ephemeral port.
+    /// # Return
+    /// * The smoltcp socket.
+    /// * -EADDRINUSE - the port is bound by another socket
+    fn bind_to(
+        &self,
+        net: &mut NetStack,
+        inner: &mut UdpInner,
+        addr: InetAddr,
+    ) -> Result<SocketHandle, isize> {
+        let port = net.bind_port(Protocol::Udp, addr.port)?;
+        let local = InetAddr {
+            addr: addr.addr,
+            port,
+        };
+        let handle = net.add_udp(self.id);
+        net.udp(handle).bind(IpListenEndpoint::from(local)).unwrap();
+        inner.handle = Some(handle);
+        inner.local = local;
+        Ok(handle)
+    }
+
+    /// The smoltcp socket, an unbound socket is bound to an ephemeral port.
+    fn handle(&self, net: &mut NetStack, inner: &mut UdpInner) -> Result<SocketHandle, isize> {
+        match inner.handle {
+            Some(handle) => Ok(handle),
+            None => self.bind_to(net, inner, InetAddr::default()),
+        }
+    }
+}
+
+impl Socket for UdpSocket {
+    /// Bind the socket to the address, port 0 is an ephemeral port.
+    /// # Return
+    /// * -EINVAL - the socket is already bound, or addr is not an IPv4 address
+    /// * -EADDRNOTAVAIL - the address is not an address of the machine
+    /// * -EADDRINUSE - the port is bound by another socket
+    fn bind(&self, addr: SockAddr) -> isize {
+        let addr = match inet_addr(addr) {
+            Ok(addr) => addr,
+            Err(errno) => return errno,
+        };
+        if !is_local_addr(addr.addr) {
+            return -EADDRNOTAVAIL;
+        }
+        let mut net = NET.lock();
+        let mut inner = self.inner.lock();
+        if inner.handle.is_some() {
+            return -EINVAL;
+        }
+        match self.bind_to(&mut net, &mut inner, addr) {
+            Ok(_) => 0,
+            Err(errno) => errno,
+        }
+    }
+
+    /// # Return
+    /// * -EOPNOTSUPP - a datagram socket does not accept connections
+    #[allow(unused)]
+    fn listen(&self, backlog: usize) -> isize {
+        -EOPNOTSUPP
+    }
+
+    /// # Return
+    /// * -EOPNOTSUPP - a datagram socket does not accept connections
+    fn accept(&self) -> Result<(Arc<dyn File + Send + Sync>, SockAddr), isize> {
+        Err(-EOPNOTSUPP)
+    }
+
+    /// Set the default destination, and only receive datagrams from it.
+    /// # Return
+    /// * -EINVAL - addr is not an IPv4 address
+    /// * -EADDRINUSE - the socket is not bound and no ephemeral port is free
+    fn connect(&self, addr: SockAddr) -> isize {
+        let addr = match inet_addr(addr) {
+            Ok(addr) => addr,
+            Err(errno) => return errno,
+        };
+        let mut net = NET.lock();
+        let mut inner = self.inner.lock();
+        if let Err(errno) = self.handle(&mut net, &mut inner) {
+            return errno;
+        }
+        inner.peer = Some(addr);
+        0
+    }
+
+    /// Send a datagram, wait until it fits in the send buffer.
+    /// # Parameter
+    /// * 'files' - ignored
+    /// * 'to' - the receiver, None for the default destination
+    /// * 'flags' - MSG_DONTWAIT
+    /// # Return
+    /// * The number of bytes sent.
+    /// * -EINVAL - to is not an IPv4 address, or an address which can not be sent to
+    /// * -EDESTADDRREQ - no address is given and the socket is not connected
+    /// * -EMSGSIZE - the datagram is larger than UDP_MAX_PAYLOAD
+    /// * -EAGAIN - the send buffer is full and the socket is non-blocking
+    /// * -EINTR - interrupted by a signal while waiting
+    #[allow(unused)]
+    fn send(&self, buf: UserBuffer, files: PassedFiles, to: Option<SockAddr>, flags: u32) -> isize {
+        let nonblock = self.nonblock() || flags & MSG_DONTWAIT != 0;
+        let to = match to.map(inet_addr).transpose() {
+            Ok(to) => to,
+            Err(errno) => return errno,
+        };
+        let Some(to) = to.or(self.inner.lock().peer) else {
+            return -EDESTADDRREQ;
+        };
+        if buf.len() > UDP_MAX_PAYLOAD {
+            return -EMSGSIZE;
+        }
+        let data: Vec<u8> = if buf.len() > 0 {
+            buf.into_iter().map(|src| unsafe { *src }).collect()
+        } else {
+            Vec::new()
+        };
+        let ret = wait_net(self.id, nonblock, |net| {
+            let handle = match self.handle(net, &mut self.inner.lock()) {
+                Ok(handle) => handle,
+                Err(errno) => return Some(Err(errno)),
+            };
+            match net.udp(handle).send_slice(&data, IpEndpoint::from(to)) {
+                Ok(()) => Some(Ok(data.len() as isize)),
+                Err(SendError::BufferFull) => None,
+                Err(SendError::Unaddressable) => Some(Err(-EINVAL)),
+            }
+        });
+        ret.unwrap_or_else(|errno| errno)
+    }
+
+    /// Receive a datagram, wait until there is one.
+    /// The bytes which do not fit in buf are dropped.
+    /// # Parameter
+    /// * 'flags' - MSG_DONTWAIT, and MSG_TRUNC to get the whole length of a truncated datagram
+    /// # Return
+    /// * The received datagram and its sender.
+    /// * -EAGAIN - there is no datagram and the socket is non-blocking
+    /// * -EINTR - interrupted by a signal while waiting
+    fn recv(&self, buf: UserBuffer, flags: u32) -> Result<RecvMsg, isize> {
+        let nonblock = self.nonblock() || flags & MSG_DONTWAIT != 0;
+        let mut buf = Some(buf);
+        wait_net(self.id, nonblock, |net| {
+            let mut inner = self.inner.lock();
+            let handle = match self.handle(net, &mut inner) {
+                Ok(handle) => handle,
+                Err(errno) => return Some(Err(errno)),
+            };
+            let socket = net.udp(handle);
+            loop {
+                let (data, meta) = socket.recv().ok()?;
+                let from = InetAddr::from(meta.endpoint);
+                if inner.peer.is_some_and(|peer| peer != from) {
+                    continue;
+                }
+                let buf = buf.take().unwrap();
+                let len = data.len().min(buf.len());
+                if len > 0 {
+                    for (dst, byte) in buf.into_iter().zip(&data[..len]) {
+                        unsafe { *dst = *byte };
+                    }
+                }
+                let truncated = len < data.len();
+                return Some(Ok(RecvMsg {
+                    len: if truncated && flags & MSG_TRUNC != 0 {
+                        data.len()
+                    } else {
+                        len
+                    },
+                    from: Some(SockAddr::Inet(from)),
+                    flags: if truncated { MSG_TRUNC } else { 0 },
+                    ..Default::default()
+                }));
+            }
+        })
+    }
+
+    /// Shutting down a datagram socket has no effect.
+    /// # Return
+    /// * -ENOTCONN - the socket is not connected
+    #[allow(unused)]
+    fn shutdown(&self, how: usize) -> isize {
+        match self.inner.lock().peer {
+            Some(_) => 0,
+            None => -ENOTCONN,
+        }
+    }
+
+    fn local_addr(&self) -> SockAddr {
+        SockAddr::Inet(self.inner.lock().local)
+    }
+
+    fn peer_addr(&self) -> Result<SockAddr, isize> {
+        self.inner.lock().peer.map(SockAddr::Inet).ok_or(-ENOTCONN)
+    }
+}
+
+impl Drop for UdpSocket {
+    fn drop(&mut self) {
+        let mut net = NET.lock();
+        let inner = self.inner.lock();
+        if let Some(handle) = inner.handle {
+            net.remove(handle);
+            net.unbind_port(Protocol::Udp, inner.local.port);
+        }
+        net.remove_waiters(self.id);
+    }
+}
+
+impl File for UdpSocket {
+    fn readable(&self) -> bool {
+        true
+    }
+
+    fn writable(&self) -> bool {
+        true
+    }
+
+    fn read(&self, buf: UserBuffer) -> isize {
+        match self.recv(buf, 0) {
+            Ok(msg) => msg.len as isize,
+            Err(errno) => errno,
+        }
+    }
+
+    fn write(&self, buf: UserBuffer) -> isize {
+        self.send(buf, Vec::new(), None, 0)
+    }
+
+    /// Readable when a datagram is received, writable when the send buffer has space.
+    fn poll_ready(&self) -> PollEvents {
+        let mut net = NET.lock();
+        net.poll();
+        let Some(handle) = self.inner.lock().handle else {
+            return PollEvents::POLLOUT;
+        };
+        let socket = net.udp(handle);
+        let mut events = PollEvents::empty();
+        if socket.can_recv() {
+            events |= PollEvents::POLLIN;
+        }
+        if socket.can_send() {
+            events |= PollEvents::POLLOUT;
+        }
+        events
+    }
+
+    fn register_waker(&self) {
+        NET.lock().waiters(self.id).push_current();
+    }
+
+    fn unregister_waker(&self) {
+        NET.lock().waiters(self.id).remove_current();
+    }
+
+    fn add_watcher(&self, watcher: Weak<dyn Watcher>) -> bool {
+        NET.lock().waiters(self.id).add_watcher(watcher);
+        true
+    }
+
+    fn remove_watcher(&self, watcher: &Weak<dyn Watcher>) {
+        NET.lock().waiters(self.id).remove_watcher(watcher);
+    }
+
+    fn nonblock(&self) -> bool {
+        self.nonblock.load(Ordering::Relaxed)
+    }
+
+    fn set_nonblock(&self, nonblock: bool) -> bool {
+        self.nonblock.store(nonblock, Ordering::Relaxed);
+        true
+    }
+
+    fn as_socket(&self) -> Option<&dyn Socket> {
+        Some(self)
+    }
+
+    fn name(&self) -> String {
+        String::from("socket:[udp]")
+    }
+}
@@ -0,0 +1,819 @@
+//! Unix domain sockets, stream and datagram sockets for local IPC.
+//! Every socket receives into its own channel, a connected socket sends to the channel of its peer.
+//! A stream channel is read as a byte stream, a datagram channel keeps the message boundaries.
+//! Fds sent with SCM_RIGHTS are queued with the data as open files,
+//! the receiver opens them in its own fd table.
+//!
+//! Binding a socket creates a socket file, connect and sendto find the bound socket
+//! by the inode of the file. A stream connect queues a new socket connected to the client
+//! in the listening socket, and accept takes it out.
+use alloc::{
+    collections::{BTreeMap, VecDeque},
+    string::String,
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use core::{
+    any::Any,
+    sync::atomic::{AtomicBool, Ordering},
+};
+use lazy_static::lazy_static;
+
+use crate::{
+    config::PAGE_SIZE,
+    fs::{pipe::written_or, poll::PollEvents, vfs, File},
+    mm::page_table::UserBuffer,
+    sync::{
+        wait_queue::{WaitQueue, Watcher},
+        SpinNoIrqLock,
+    },
+    syscall::errno::{
+        EAGAIN, ECONNREFUSED, EINTR, EINVAL, EISCONN, EMSGSIZE, ENOTCONN, EOPNOTSUPP, EPIPE,
+        EPROTOTYPE,
+    },
+    task::{block_current_and_run_next, check_current_signals_error, prepare_interruptible_block},
+};
+
+use super::{
+    broken_pipe, PassedFiles, RecvMsg, SockAddr, Socket, MSG_DONTWAIT, MSG_TRUNC, SHUT_RD,
+    SHUT_RDWR, SHUT_WR, SOMAXCONN,
+};
+
+/// The max number of bytes buffered in the channel of a socket, and the max size of a datagram.
+pub const SOCKET_CAPACITY: usize = 16 * PAGE_SIZE;
+/// The max number of fds sent in a message.
+pub const SCM_MAX_FD: usize = 253;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SocketType {
+    Stream,
+    Dgram,
+}
+
+struct Packet {
+    data: Vec<u8>,
+    files: PassedFiles,
+    /// The bound address of the sender of a datagram.
+    from: Option<String>,
+}
+
+struct Channel {
+    packets: VecDeque<Packet>,
+    /// The number of bytes buffered.
+    len: usize,
+    /// The receiver is closed or shut down for reading, sending to it fails.
+    reader_closed: bool,
+    /// The peer of a stream is closed or shut down for writing, the receiver gets end of file.
+    writer_closed: bool,
+    /// Receivers waiting for data, and a listening socket waiting for connections.
+    readers: WaitQueue,
+    /// Senders waiting for space, and clients waiting for the backlog of a listening socket.
+    writers: WaitQueue,
+}
+
+type ChannelRef = Arc<SpinNoIrqLock<Channel>>;
+
+impl Channel {
+    fn new() -> ChannelRef {
+        Arc::new(SpinNoIrqLock::new(Self {
+            packets: VecDeque::new(),
+            len: 0,
+            reader_closed: false,
+            writer_closed: false,
+            readers: WaitQueue::new(),
+            writers: WaitQueue::new(),
+        }))
+    }
+
+    fn push(&mut self, packet: Packet) {
+        self.len += packet.data.len();
+        self.packets.push_back(packet);
+        self.readers.wake_all();
+    }
+
+    fn close_reader(&mut self) {
+        self.reader_closed = true;
+        self.readers.wake_all();
+        self.writers.wake_all();
+    }
+
+    fn close_writer(&mut self) {
+        self.writer_closed = true;
+        self.readers.wake_all();
+    }
+
+    /// Read the bytes of the stream. It stops before the data carrying files,
+    /// so the files are received with the first byte of their data.
+    /// buf must not be empty.
+    fn recv_stream(&mut self, buf: UserBuffer) -> RecvMsg {
+        let mut msg = RecvMsg::default();
+        let mut buf_iter = buf.into_iter();
+        while let Some(packet) = self.packets.front_mut() {
+            if msg.len > 0 && !packet.files.is_empty() {
+                break;
+            }
+            msg.files.append(&mut packet.files);
+            let len = packet
+                .data
+                .iter()
+                .zip(buf_iter.by_ref())
+                .map(|(byte, dst)| unsafe { *dst = *byte })
+                .count();
+            packet.data.drain(..len);
+            self.len -= len;
+            msg.len += len;
+            if !packet.data.is_empty() {
+                break;
+            }
+            self.packets.pop_front();
+        }
+        msg
+    }
+
+    /// Read a datagram, the bytes which do not fit in buf are dropped.
+    fn recv_dgram(&mut self, buf: UserBuffer, flags: u32) -> RecvMsg {
+        let packet = self.packets.pop_front().unwrap();
+        self.len -= packet.data.len();
+        let len = packet.data.len().min(buf.len());
+        if len > 0 {
+            for (dst, byte) in buf.into_iter().zip(&packet.data[..len]) {
+                unsafe { *dst = *byte };
+            }
+        }
+        let truncated = len < packet.data.len();
+        RecvMsg {
+            len: if truncated && flags & MSG_TRUNC != 0 {
+                packet.data.len()
+            } else {
+                len
+            },
+            files: packet.files,
+            from: Some(SockAddr::Unix(packet.from)),
+            flags: if truncated { MSG_TRUNC } else { 0 },
+        }
+    }
+}
+
+enum SocketState {
+    Unconnected,
+    /// A stream socket accepting connections.
+    Listening {
+        backlog: usize,
+        /// Connected sockets to be accepted.
+        pending: VecDeque<Arc<UnixSocket>>,
+    },
+    /// A connected stream, or a datagram socket with a default destination.
+    Connected {
+        /// The channel of the peer.
+        tx: ChannelRef,
+        /// The bound address of the peer.
+        peer_path: Option<String>,
+    },
+}
+
+struct SocketInner {
+    /// The bound address.
+    path: Option<String>,
+    /// (device id, inode number) of the socket file, the key in SOCKETS.
+    id: Option<(u64, u64)>,
+    state: SocketState,
+    /// Shut down for writing.
+    write_shutdown: bool,
+}
+
+/// The part of a socket found by connect and sendto.
+struct SocketShared {
+    socket_type: SocketType,
+    rx: ChannelRef,
+    inner: SpinNoIrqLock<SocketInner>,
+}
+
+lazy_static! {
+    /// Bound sockets by (device id, inode number) of their socket files.
+    static ref SOCKETS: SpinNoIrqLock<BTreeMap<(u64, u64), Weak<SocketShared>>> =
+        SpinNoIrqLock::new(BTreeMap::new());
+}
+
+/// Find the socket bound to the path.
+/// # Return
+/// * -ENOENT - the path does not exist
+/// * -ECONNREFUSED - the file is not a socket file, or its socket is closed
+fn lookup_socket(path: &str) -> Result<Arc<SocketShared>, isize> {
+    let id = vfs::socket_id(path)?;
+    SOCKETS
+        .lock()
+        .get(&id)
+        .and_then(Weak::upgrade)
+        .ok_or(-ECONNREFUSED)
+}
+
+/// The path of a unix domain socket address.
+/// # Return
+/// * -EINVAL - the address is not the address of a bound unix domain socket
+fn unix_path(addr: SockAddr) -> Result<String, isize> {
+    match addr {
+        SockAddr::Unix(Some(path)) => Ok(path),
+        _ => Err(-EINVAL),
+    }
+}
+
+pub struct UnixSocket {
+    /// Return EAGAIN instead of blocking.
+    nonblock: AtomicBool,
+    shared: Arc<SocketShared>,
+}
+
+impl UnixSocket {
+    /// Create an unbound and unconnected socket.
+    pub fn new(socket_type: SocketType) -> Self {
+        Self {
+            nonblock: AtomicBool::new(false),
+            shared: Arc::new(SocketShared {
+                socket_type,
+                rx: Channel::new(),
+                inner: SpinNoIrqLock::new(SocketInner {
+                    path: None,
+                    id: None,
+                    state: SocketState::Unconnected,
+                    write_shutdown: false,
+                }),
+            }),
+        }
+    }
+
+    /// Create a pair of unbound sockets connected to each other.
+    pub fn pair(socket_type: SocketType) -> (Self, Self) {
+        let (a, b) = (Self::new(socket_type), Self::new(socket_type));
+        a.shared.inner.lock().state = SocketState::Connected {
+            tx: b.shared.rx.clone(),
+            peer_path: None,
+        };
+        b.shared.inner.lock().state = SocketState::Connected {
+            tx: a.shared.rx.clone(),
+            peer_path: None,
+        };
+        (a, b)
+    }
+
+    pub fn socket_type(&self) -> SocketType {
+        self.shared.socket_type
+    }
+
+    /// Queue a socket connected to this socket in the listening socket.
+    /// # Return
+    /// * The channel of the queued socket.
+    fn connect_stream(
+        &self,
+        target: &Arc<SocketShared>,
+        local_path: Option<String>,
+    ) -> Result<ChannelRef, isize> {
+        loop {
+            prepare_interruptible_block();
+            let mut guard = target.inner.lock();
+            let target_inner = &mut *guard;
+            let SocketState::Listening { backlog, pending } = &mut target_inner.state else {
+                return Err(-ECONNREFUSED);
+            };
+            if pending.len() < *backlog {
+                let server = Self::new(SocketType::Stream);
+                let tx = server.shared.rx.clone();
+                let mut server_inner = server.shared.inner.lock();
+                server_inner.path = target_inner.path.clone();
+                server_inner.state = SocketState::Connected {
+                    tx: self.shared.rx.clone(),
+                    peer_path: local_path,
+                };
+                drop(server_inner);
+                pending.push_back(Arc::new(server));
+                target.rx.lock().readers.wake_all();
+                return Ok(tx);
+            }
+            if self.nonblock() {
+                return Err(-EAGAIN);
+            }
+            if check_current_signals_error().is_some() {
+                return Err(-EINTR);
+            }
+            target.rx.lock().writers.push_current();
+            drop(guard);
+            block_current_and_run_next();
+            // Woken up by a signal, the thread is still in the wait queue.
+            target.rx.lock().writers.remove_current();
+        }
+    }
+
+    fn send_stream(
+        tx: &ChannelRef,
+        buf: UserBuffer,
+        files: PassedFiles,
+        nonblock: bool,
+        flags: u32,
+    ) -> isize {
+        let total = buf.len();
+        if total == 0 {
+            return 0;
+        }
+        let mut buf_iter = buf.into_iter();
+        // The files are sent with the first segment.
+        let mut files = Some(files);
+        let mut sent = 0;
+        loop {
+            prepare_interruptible_block();
+            let mut channel = tx.lock();
+            if channel.reader_closed {
+                drop(channel);
+                broken_pipe(flags);
+                return written_or(sent, EPIPE);
+            }
+            let space = SOCKET_CAPACITY - channel.len;
+            if space > 0 {
+                let len = space.min(total - sent);
+                channel.push(Packet {
+                    data: buf_iter
+                        .by_ref()
+                        .take(len)
+                        .map(|src| unsafe { *src })
+                        .collect(),
+                    files: files.take().unwrap_or_default(),
+                    from: None,
+                });
+                sent += len;
+                if sent == total {
+                    return total as isize;
+                }
+                continue;
+            }
+            if nonblock {
+                return written_or(sent, EAGAIN);
+            }
+            if check_current_signals_error().is_some() {
+                return written_or(sent, EINTR);
+            }
+            channel.writers.push_current();
+            drop(channel);
+            block_current_and_run_next();
+            // Woken up by a signal, the thread is still in the wait queue.
+            tx.lock().writers.remove_current();
+        }
+    }
+
+    fn send_dgram(
+        tx: &ChannelRef,
+        buf: UserBuffer,
+        files: PassedFiles,
+        from: Option<String>,
+        nonblock: bool,
+    ) -> isize {
+        if buf.len() > SOCKET_CAPACITY {
+            return -EMSGSIZE;
+        }
+        let data = if buf.len() > 0 {
+            buf.into_iter().map(|src| unsafe { *src }).collect()
+        } else {
+            Vec::new()
+        };
+        let packet = Packet { data, files, from };
+        loop {
+            prepare_interruptible_block();
+            let mut channel = tx.lock();
+            if channel.reader_closed {
+                return -ECONNREFUSED;
+            }
+            let len = packet.data.len();
+            if channel.len + len <= SOCKET_CAPACITY {
+                channel.push(packet);
+                return len as isize;
+            }
+            if nonblock {
+                return -EAGAIN;
+            }
+            if check_current_signals_error().is_some() {
+                return -EINTR;
+            }
+            channel.writers.push_current();
+            drop(channel);
+            block_current_and_run_next();
+            // Woken up by a signal, the thread is still in the wait queue.
+            tx.lock().writers.remove_current();
+        }
+    }
+}
+
+impl Socket for UnixSocket {
+    /// Bind the socket to the path, the socket file is created.
+    /// # Return
+    /// * -EINVAL - the socket is already bound, or addr is not a path
+    /// * -errno - the socket file can not be created, see vfs::mksock
+    fn bind(&self, addr: SockAddr) -> isize {
+        let path = match unix_path(addr) {
+            Ok(path) => path,
+            Err(errno) => return errno,
+        };
+        let mut inner = self.shared.inner.lock();
+        if inner.path.is_some() {
+            return -EINVAL;
+        }
+        let id = match vfs::mksock(&path) {
+            Ok(id) => id,
+            Err(errno) => return errno,
+        };
+        SOCKETS.lock().insert(id, Arc::downgrade(&self.shared));
+        inner.path = Some(path);
+        inner.id = Some(id);
+        0
+    }
+
+    /// Accept connections, or change the backlog of a listening socket.
+    /// # Parameter
+    /// * 'backlog' - the max number of connections waiting to be accepted, at most SOMAXCONN
+    /// # Return
+    /// * -EOPNOTSUPP - the socket is not a stream socket
+    /// * -EINVAL - the socket is not bound, or it is connected
+    fn listen(&self, backlog: usize) -> isize {
+        if self.shared.socket_type != SocketType::Stream {
+            return -EOPNOTSUPP;
+        }
+        let mut inner = self.shared.inner.lock();
+        if inner.path.is_none() {
+            return -EINVAL;
+        }
+        let backlog = backlog.clamp(1, SOMAXCONN);
+        match &mut inner.state {
+            SocketState::Listening { backlog: old, .. } => *old = backlog,
+            SocketState::Connected { .. } => return -EINVAL,
+            state => {
+                *state = SocketState::Listening {
+                    backlog,
+                    pending: VecDeque::new(),
+                }
+            }
+        }
+        0
+    }
+
+    /// Take a connection of a listening socket, wait until a client connects.
+    /// # Return
+    /// * The socket connected to the client, and the bound address of the client.
+    /// * -EINVAL - the socket is not listening
+    /// * -EAGAIN - no connection is waiting and the socket is non-blocking
+    /// * -EINTR - interrupted by a signal while waiting
+    fn accept(&self) -> Result<(Arc<dyn File + Send + Sync>, SockAddr), isize> {
+        loop {
+            prepare_interruptible_block();
+            let mut inner = self.shared.inner.lock();
+            let SocketState::Listening { pending, .. } = &mut inner.state else {
+                return Err(-EINVAL);
+            };
+            if let Some(socket) = pending.pop_front() {
+                // Clients waiting for the backlog.
+                self.shared.rx.lock().writers.wake_all();
+                let peer = socket.peer_addr().unwrap_or(SockAddr::Unix(None));
+                return Ok((socket as Arc<dyn File + Send + Sync>, peer));
+            }
+            if self.nonblock() {
+                return Err(-EAGAIN);
+            }
+            if check_current_signals_error().is_some() {
+                return Err(-EINTR);
+            }
+            self.shared.rx.lock().readers.push_current();
+            drop(inner);
+            block_current_and_run_next();
+            // Woken up by a signal, the thread is still in the wait queue.
+            self.shared.rx.lock().readers.remove_current();
+        }
+    }
+
+    /// Connect a stream socket to the listening socket bound to the path,
+    /// wait while the backlog of the listening socket is full.
+    /// Connecting a datagram socket sets the default destination of send.
+    /// # Return
+    /// * -EISCONN - the stream socket is already connected
+    /// * -EINVAL - the stream socket is listening, or addr is not a path
+    /// * -EPROTOTYPE - the socket bound to the path is not of the same type
+    /// * -ECONNREFUSED - no socket is bound to the path, or the stream socket is not listening
+    /// * -ENOENT - the path does not exist
+    /// * -EAGAIN - the backlog is full and the socket is non-blocking
+    /// * -EINTR - interrupted by a signal while waiting
+    fn connect(&self, addr: SockAddr) -> isize {
+        let path = match unix_path(addr) {
+            Ok(path) => path,
+            Err(errno) => return errno,
+        };
+        let socket_type = self.shared.socket_type;
+        let local_path = {
+            let inner = self.shared.inner.lock();
+            match inner.state {
+                SocketState::Connected { .. } if socket_type == SocketType::Stream => {
+                    return -EISCONN
+                }
+                SocketState::Listening { .. } => return -EINVAL,
+                _ => {}
+            }
+            inner.path.clone()
+        };
+        let target = match lookup_socket(&path) {
+            Ok(target) => target,
+            Err(errno) => return errno,
+        };
+        if target.socket_type != socket_type {
+            return -EPROTOTYPE;
+        }
+        let tx = match socket_type {
+            SocketType::Stream => match self.connect_stream(&target, local_path) {
+                Ok(tx) => tx,
+                Err(errno) => return errno,
+            },
+            SocketType::Dgram => target.rx.clone(),
+        };
+        self.shared.inner.lock().state = SocketState::Connected {
+            tx,
+            peer_path: Some(path),
+        };
+        0
+    }
+
+    /// Send the data and the files. A stream waits until all data is sent,
+    /// a datagram waits until it fits in the channel of the receiver.
+    /// # Parameter
+    /// * 'to' - the address of the receiver of a datagram, None for the connected peer
+    /// * 'flags' - MSG_DONTWAIT and MSG_NOSIGNAL
+    /// # Return
+    /// * The number of bytes sent.
+    /// * -EINVAL - to is not a path
+    /// * -ENOTCONN - no address is given and the socket is not connected
+    /// * -EISCONN - an address is given to a connected stream socket
+    /// * -EPIPE - the peer of the stream is closed, or the socket is shut down for writing
+    /// * -EMSGSIZE - the datagram is larger than SOCKET_CAPACITY
+    /// * -ECONNREFUSED - the receiver of the datagram is closed
+    /// * -EAGAIN - the data does not fit and the socket is non-blocking
+    /// * -EINTR - interrupted by a signal while waiting
+    fn send(&self, buf: UserBuffer, files: PassedFiles, to: Option<SockAddr>, flags: u32) -> isize {
+        let to = match to.map(unix_path).transpose() {
+            Ok(to) => to,
+            Err(errno) => return errno,
+        };
+        let nonblock = self.nonblock() || flags & MSG_DONTWAIT != 0;
+        let inner = self.shared.inner.lock();
+        let from = inner.path.clone();
+        let connected = match &inner.state {
+            SocketState::Connected { tx, .. } => Some(tx.clone()),
+            _ => None,
+        };
+        let write_shutdown = inner.write_shutdown;
+        drop(inner);
+        if write_shutdown {
+            broken_pipe(flags);
+            return -EPIPE;
+        }
+        match (self.shared.socket_type, to.as_deref(), connected) {
+            (SocketType::Stream, Some(_), Some(_)) => -EISCONN,
+            (SocketType::Stream, None, Some(tx)) => {
+                Self::send_stream(&tx, buf, files, nonblock, flags)
+            }
+            (SocketType::Dgram, Some(path), _) => match lookup_socket(path) {
+                Ok(target) if target.socket_type == SocketType::Dgram => {
+                    Self::send_dgram(&target.rx, buf, files, from, nonblock)
+                }
+                Ok(_) => -EPROTOTYPE,
+                Err(errno) => errno,
+            },
+            (SocketType::Dgram, None, Some(tx)) => {
+                Self::send_dgram(&tx, buf, files, from, nonblock)
+            }
+            (_, _, None) => -ENOTCONN,
+        }
+    }
+
+    /// Receive data and files, wait until there is some data.
+    /// # Parameter
+    /// * 'flags' - MSG_DONTWAIT, and MSG_TRUNC to get the whole length of a truncated datagram
+    /// # Return
+    /// * The received data, its length is 0 at the end of the stream.
+    /// * -ENOTCONN - the stream socket is not connected
+    /// * -EAGAIN - there is no data and the socket is non-blocking
+    /// * -EINTR - interrupted by a signal while waiting
+    fn recv(&self, buf: UserBuffer, flags: u32) -> Result<RecvMsg, isize> {
+        let socket_type = self.shared.socket_type;
+        if socket_type == SocketType::Stream {
+            let connected = matches!(
+                self.shared.inner.lock().state,
+                SocketState::Connected { .. }
+            );
+            if !connected {
+                return Err(-ENOTCONN);
+            }
+            if buf.len() == 0 {
+                return Ok(RecvMsg::default());
+            }
+        }
+        let nonblock = self.nonblock() || flags & MSG_DONTWAIT != 0;
+        loop {
+            prepare_interruptible_block();
+            let mut channel = self.shared.rx.lock();
+            if !channel.packets.is_empty() {
+                let msg = match socket_type {
+                    SocketType::Stream => channel.recv_stream(buf),
+                    SocketType::Dgram => channel.recv_dgram(buf, flags),
+                };
+                channel.writers.wake_all();
+                return Ok(msg);
+            }
+            if channel.reader_closed || channel.writer_closed {
+                return Ok(RecvMsg::default());
+            }
+            if nonblock {
+                return Err(-EAGAIN);
+            }
+            if check_current_signals_error().is_some() {
+                return Err(-EINTR);
+            }
+            channel.readers.push_current();
+            drop(channel);
+            block_current_and_run_next();
+            // Woken up by a signal, the thread is still in the wait queue.
+            self.shared.rx.lock().readers.remove_current();
+        }
+    }
+
+    /// Shut down reading, writing or both of a connected socket.
+    /// The peer of a stream gets end of file after the socket is shut down for writing.
+    /// # Parameter
+    /// * 'how' - SHUT_RD, SHUT_WR or SHUT_RDWR
+    /// # Return
+    /// * -ENOTCONN - the socket is not connected
+    fn shutdown(&self, how: usize) -> isize {
+        let mut inner = self.shared.inner.lock();
+        let tx = match &inner.state {
+            SocketState::Connected { tx, .. } => tx.clone(),
+            _ => return -ENOTCONN,
+        };
+        if how == SHUT_WR || how == SHUT_RDWR {
+            inner.write_shutdown = true;
+            if self.shared.socket_type == SocketType::Stream {
+                tx.lock().close_writer();
+            }
+        }
+        if how == SHUT_RD || how == SHUT_RDWR {
+            self.shared.rx.lock().close_reader();
+        }
+        0
+    }
+
+    /// The bound path, None if the socket is not bound.
+    fn local_addr(&self) -> SockAddr {
+        SockAddr::Unix(self.shared.inner.lock().path.clone())
+    }
+
+    /// The bound path of the peer, None if the peer is not bound.
+    /// # Return
+    /// * -ENOTCONN - the socket is not connected
+    fn peer_addr(&self) -> Result<SockAddr, isize> {
+        match &self.shared.inner.lock().state {
+            SocketState::Connected { peer_path, .. } => Ok(SockAddr::Unix(peer_path.clone())),
+            _ => Err(-ENOTCONN),
+        }
+    }
+}
+
+impl Drop for UnixSocket {
+    fn drop(&mut self) {
+        let mut inner = self.shared.inner.lock();
+        if let Some(id) = inner.id.take() {
+            let mut sockets = SOCKETS.lock();
+            let bound = sockets.get(&id);
+            if bound.is_some_and(|bound| Weak::as_ptr(bound) == Arc::as_ptr(&self.shared)) {
+                sockets.remove(&id);
+            }
+        }
+        let state = core::mem::replace(&mut inner.state, SocketState::Unconnected);
+        drop(inner);
+        if let SocketState::Connected { tx, .. } = &state {
+            if self.shared.socket_type == SocketType::Stream {
+                tx.lock().close_writer();
+            }
+        }
+        let mut rx = self.shared.rx.lock();
+        rx.close_reader();
+        rx.len = 0;
+        let packets = core::mem::take(&mut rx.packets);
+        drop(rx);
+        // Queued connections and files are closed without the locks.
+        drop(packets);
+        drop(state);
+    }
+}
+
+impl File for UnixSocket {
+    fn readable(&self) -> bool {
+        true
+    }
+
+    fn writable(&self) -> bool {
+        true
+    }
+
+    /// recv without flags, received files are closed.
+    fn read(&self, buf: UserBuffer) -> isize {
+        match self.recv(buf, 0) {
+            Ok(msg) => msg.len as isize,
+            Err(errno) => errno,
+        }
+    }
+
+    /// send to the connected peer without flags.
+    fn write(&self, buf: UserBuffer) -> isize {
+        self.send(buf, Vec::new(), None, 0)
+    }
+
+    /// A listening socket is readable when a connection is waiting.
+    /// A connected socket is readable when there is data or at the end of the stream,
+    /// and writable when the channel of the peer has space.
+    fn poll_ready(&self) -> PollEvents {
+        let inner = self.shared.inner.lock();
+        let mut events = PollEvents::empty();
+        match &inner.state {
+            SocketState::Listening { pending, .. } => {
+                if !pending.is_empty() {
+                    events |= PollEvents::POLLIN;
+                }
+                return events;
+            }
+            SocketState::Connected { tx, .. } => {
+                let tx = tx.lock();
+                if tx.reader_closed {
+                    events |= PollEvents::POLLERR;
+                } else if tx.len < SOCKET_CAPACITY {
+                    events |= PollEvents::POLLOUT;
+                }
+            }
+            SocketState::Unconnected if self.shared.socket_type == SocketType::Stream => {
+                return PollEvents::POLLHUP;
+            }
+            SocketState::Unconnected => events |= PollEvents::POLLOUT,
+        }
+        let rx = self.shared.rx.lock();
+        if !rx.packets.is_empty() || rx.reader_closed || rx.writer_closed {
+            events |= PollEvents::POLLIN;
+        }
+        if rx.writer_closed {
+            events |= PollEvents::POLLHUP;
+        }
+        events
+    }
+
+    fn register_waker(&self) {
+        let inner = self.shared.inner.lock();
+        self.shared.rx.lock().readers.push_current();
+        if let SocketState::Connected { tx, .. } = &inner.state {
+            tx.lock().writers.push_current();
+        }
+    }
+
+    fn unregister_waker(&self) {
+        let inner = self.shared.inner.lock();
+        self.shared.rx.lock().readers.remove_current();
+        if let SocketState::Connected { tx, .. } = &inner.state {
+            tx.lock().writers.remove_current();
+        }
+    }
+
+    /// The watcher is added to the channel of the peer only if the socket is connected.
+    fn add_watcher(&self, watcher: Weak<dyn Watcher>) -> bool {
+        let inner = self.shared.inner.lock();
+        self.shared.rx.lock().readers.add_watcher(watcher.clone());
+        if let SocketState::Connected { tx, .. } = &inner.state {
+            tx.lock().writers.add_watcher(watcher);
+        }
+        true
+    }
+
+    fn remove_watcher(&self, watcher: &Weak<dyn Watcher>) {
+        let inner = self.shared.inner.lock();
+        self.shared.rx.lock().readers.remove_watcher(watcher);
+        if let SocketState::Connected { tx, .. } = &inner.state {
+            tx.lock().writers.remove_watcher(watcher);
+        }
+    }
+
+    fn nonblock(&self) -> bool {
+        self.nonblock.load(Ordering::Relaxed)
+    }
+
+    fn set_nonblock(&self, nonblock: bool) -> bool {
+        self.nonblock.store(nonblock, Ordering::Relaxed);
+        true
+    }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+
+    fn as_socket(&self) -> Option<&dyn Socket> {
+        Some(self)
+    }
+
+    fn name(&self) -> String {
+        String::from("socket:[unix]")
+    }
+}
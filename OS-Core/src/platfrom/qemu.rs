@@ -1,9 +1,13 @@
 pub const CLOCK_FREQ: usize = 12500000;
 pub const MEMORY_END: usize = 0x8800_0000; // Total 126MB
+/// Harts supported by the kernel, hart id must be less than it.
+pub const MAX_HARTS: usize = 4;
 
 pub const MMIO: &[(usize, usize)] = &[
     (0x0010_0000, 0x00_2000), // VIRT_TEST/RTC  in virt machine
     (0x1000_1000, 0x00_1000), // Virtio Block in virt machine
+    (0x1000_2000, 0x00_1000), // Virtio Net in virt machine
 ];
 
 pub type BlockDeviceImpl = crate::drivers::block::VitrIOBlock;
+pub type NetDeviceImpl = crate::drivers::net::VirtIONetDevice;
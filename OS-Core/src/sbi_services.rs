@@ -13,6 +13,12 @@ const FID_SHUTDOWN: usize = 0;
 const EID_TIMER_EXTENSION: usize = 0x54494D45;
 const FID_SET_TIMER: usize = 0;
 
+const EID_IPI_EXTENSION: usize = 0x735049;
+const FID_SEND_IPI: usize = 0;
+
+const EID_HSM_EXTENSION: usize = 0x48534D;
+const FID_HART_START: usize = 0;
+
 use core::{arch::asm, result};
 
 use crate::println;
@@ -58,3 +64,31 @@ pub fn shutdown() -> ! {
 pub fn set_timer(timer: usize) {
     sbi_call(EID_TIMER_EXTENSION, FID_SET_TIMER, [timer, 0, 0, 0, 0, 0]);
 }
+
+/// Send supervisor software interrupt to harts.
+/// # Parameter
+/// * 'hart_mask' - bit i is hart 'hart_mask_base + i'
+/// * 'hart_mask_base' - the hart id of bit 0
+pub fn send_ipi(hart_mask: usize, hart_mask_base: usize) {
+    sbi_call(
+        EID_IPI_EXTENSION,
+        FID_SEND_IPI,
+        [hart_mask, hart_mask_base, 0, 0, 0, 0],
+    );
+}
+
+/// Start a stopped hart in supervisor mode.
+/// # Parameter
+/// * 'hartid' - the hart to start
+/// * 'start_addr' - physical address the hart starts from, with satp = 0
+/// * 'opaque' - passed to the hart in a1
+/// # Return
+/// * 0 - success
+/// * negative sbi error code, e.g. the hart is already started or does not exist
+pub fn hart_start(hartid: usize, start_addr: usize, opaque: usize) -> isize {
+    sbi_call(
+        EID_HSM_EXTENSION,
+        FID_HART_START,
+        [hartid, start_addr, opaque, 0, 0, 0],
+    )
+}
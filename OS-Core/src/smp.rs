@@ -0,0 +1,104 @@
+//! Multi-hart support.
+//! The boot hart initializes the kernel and then starts other harts by SBI HSM extension,
+//! every hart runs its own scheduling loop and takes threads from the global task manager.
+//! Harts notify each other by supervisor software interrupt (IPI),
+//! to wake up an idle hart or to flush the TLB after a page is unmapped.
+use core::{
+    arch::asm,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::{
+    platfrom::MAX_HARTS,
+    sbi_services::{hart_start, send_ipi},
+};
+
+/// Harts which have finished initialization, bit i is hart i.
+static ONLINE_HARTS: AtomicUsize = AtomicUsize::new(0);
+/// Harts waiting for interrupt in the idle loop, bit i is hart i.
+static IDLE_HARTS: AtomicUsize = AtomicUsize::new(0);
+/// Harts asked to flush the TLB which have not done it yet, bit i is hart i.
+static FLUSH_PENDING: AtomicUsize = AtomicUsize::new(0);
+
+/// Get the id of the current hart.
+/// The hart id is kept in tp register while running in kernel.
+#[inline(always)]
+pub fn hart_id() -> usize {
+    let id: usize;
+    unsafe { asm!("mv {}, tp", out(reg) id) };
+    id
+}
+
+/// Mark the current hart ready to run threads.
+pub fn set_online() {
+    ONLINE_HARTS.fetch_or(1 << hart_id(), Ordering::SeqCst);
+}
+
+/// Start all other harts from _secondary_start.
+pub fn start_secondary_harts() {
+    extern "C" {
+        fn _secondary_start();
+    }
+    for hart in (0..MAX_HARTS).filter(|hart| *hart != hart_id()) {
+        // Fails if the hart does not exist, just ignore it.
+        hart_start(hart, _secondary_start as usize, 0);
+    }
+}
+
+/// Mark the current hart idle or busy.
+pub fn set_idle(idle: bool) {
+    let bit = 1 << hart_id();
+    if idle {
+        IDLE_HARTS.fetch_or(bit, Ordering::SeqCst);
+    } else {
+        IDLE_HARTS.fetch_and(!bit, Ordering::SeqCst);
+    }
+}
+
+/// Wake up one idle hart to run the thread just become ready.
+pub fn wakeup_idle_hart() {
+    let idle = IDLE_HARTS.load(Ordering::SeqCst) & !(1 << hart_id());
+    if idle != 0 {
+        // Only the lowest idle hart.
+        send_ipi(idle & idle.wrapping_neg(), 0);
+    }
+}
+
+/// Flush the TLB of all harts after a page is unmapped,
+/// and wait until the other harts have flushed, so the page is not used after it is freed.
+pub fn flush_tlb_all() {
+    unsafe { asm!("sfence.vma") };
+    let others = ONLINE_HARTS.load(Ordering::SeqCst) & !(1 << hart_id());
+    if others == 0 {
+        return;
+    }
+    FLUSH_PENDING.fetch_or(others, Ordering::SeqCst);
+    send_ipi(others, 0);
+    while FLUSH_PENDING.load(Ordering::SeqCst) & others != 0 {
+        // Another hart may be waiting for this one to flush.
+        ack_tlb_flush();
+        core::hint::spin_loop();
+    }
+}
+
+/// Flush the TLB if another hart has asked to.
+/// The IPI is not taken while interrupt is disabled in kernel,
+/// so a hart spinning there calls it, or the hart asking would wait for it forever.
+pub fn ack_tlb_flush() {
+    let bit = 1 << hart_id();
+    if FLUSH_PENDING.load(Ordering::SeqCst) & bit != 0 {
+        unsafe { asm!("sfence.vma") };
+        FLUSH_PENDING.fetch_and(!bit, Ordering::SeqCst);
+    }
+}
+
+/// Handle the supervisor software interrupt.
+/// Waking up from the idle loop needs nothing else, and flushing the TLB is cheap,
+/// so every IPI flushes the TLB and acknowledges flush_tlb_all.
+pub fn handle_ipi() {
+    unsafe {
+        riscv::register::sip::clear_ssoft();
+        asm!("sfence.vma");
+    }
+    FLUSH_PENDING.fetch_and(!(1 << hart_id()), Ordering::SeqCst);
+}
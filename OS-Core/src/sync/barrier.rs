@@ -0,0 +1,58 @@
+//! Barrier, threads wait until a number of threads have arrived, then all of them go on.
+//! It is reused for the next round at once, so threads can meet at it once every phase.
+use crate::task::{block_current_and_run_next, prepare_block};
+
+use super::{wait_queue::WaitQueue, SpinNoIrqLock};
+
+pub struct Barrier {
+    count: usize,
+    inner: SpinNoIrqLock<BarrierInner>,
+}
+pub struct BarrierInner {
+    arrived: usize,
+    /// Changed every round, a waiter goes on when it changes.
+    round: usize,
+    waiters: WaitQueue,
+}
+
+impl Barrier {
+    /// # Parameter
+    /// * 'count' - the number of threads of a round, not 0
+    pub fn new(count: usize) -> Self {
+        Self {
+            count,
+            inner: SpinNoIrqLock::new(BarrierInner {
+                arrived: 0,
+                round: 0,
+                waiters: WaitQueue::new(),
+            }),
+        }
+    }
+
+    /// Wait until count threads have arrived.
+    /// # Return
+    /// * true - for the last thread of the round, which wakes up the others
+    pub fn wait(&self) -> bool {
+        let mut inner = self.inner.lock();
+        inner.arrived += 1;
+        if inner.arrived == self.count {
+            inner.arrived = 0;
+            inner.round += 1;
+            inner.waiters.wake_all();
+            return true;
+        }
+        let round = inner.round;
+        loop {
+            prepare_block();
+            inner.waiters.push_current();
+            drop(inner);
+            block_current_and_run_next();
+            inner = self.inner.lock();
+            // Woken up spuriously, the thread is still in the queue.
+            inner.waiters.remove_current();
+            if inner.round != round {
+                return false;
+            }
+        }
+    }
+}
@@ -3,44 +3,62 @@ use alloc::{
     sync::{Arc, Weak},
 };
 
-use crate::task::{
-    block_current_and_run_next, manager::wakeup_thread, processor::current_task,
-    thread::ThreadControlBlock,
-};
+use crate::task::{manager::wakeup_thread, processor::current_task, thread::ThreadControlBlock};
 
-use super::{mutex::Mutex, UPSafeCell};
+use super::{mutex::Mutex, wait_queue::block_current_until, SpinNoIrqLock};
 
 pub struct Condvar {
-    inner: UPSafeCell<CondvarInner>,
+    inner: SpinNoIrqLock<CondvarInner>,
 }
 pub struct CondvarInner {
     wait_queue: VecDeque<Weak<ThreadControlBlock>>,
 }
+impl CondvarInner {
+    fn wait_queue(&mut self) -> &mut VecDeque<Weak<ThreadControlBlock>> {
+        &mut self.wait_queue
+    }
+}
 
 impl Condvar {
     pub fn new() -> Self {
         Self {
-            inner: unsafe {
-                UPSafeCell::new(CondvarInner {
-                    wait_queue: VecDeque::new(),
-                })
-            },
+            inner: SpinNoIrqLock::new(CondvarInner {
+                wait_queue: VecDeque::new(),
+            }),
         }
     }
 
     pub fn wait(&self, mutex: Arc<dyn Mutex>) {
-        mutex.unlock();
+        self.wait_until(mutex, None);
+    }
+
+    /// Unlock the mutex and wait for a signal or the deadline, then lock the mutex again.
+    /// # Parameter
+    /// * 'deadline' - the time since boot in ms to give up waiting, None waits forever
+    /// # Return
+    /// * false - not signalled before the deadline
+    pub fn wait_until(&self, mutex: Arc<dyn Mutex>, deadline: Option<usize>) -> bool {
+        // Wait before unlocking, so a signal after the unlock is not lost.
         self.inner
-            .exclusive_access()
+            .lock()
             .wait_queue
             .push_back(Arc::downgrade(&current_task().unwrap()));
-        block_current_and_run_next();
+        mutex.unlock();
+        let signalled =
+            block_current_until(&self.inner, CondvarInner::wait_queue, deadline, |_| {});
         mutex.lock();
+        signalled
     }
 
     pub fn signal(&self) {
-        if let Some(tcb) = self.inner.exclusive_access().wait_queue.pop_front() {
+        if let Some(tcb) = self.inner.lock().wait_queue.pop_front() {
             wakeup_thread(tcb);
         }
     }
+
+    /// Wake up all waiting threads.
+    pub fn broadcast(&self) {
+        let waiters = core::mem::take(&mut self.inner.lock().wait_queue);
+        waiters.into_iter().for_each(wakeup_thread);
+    }
 }
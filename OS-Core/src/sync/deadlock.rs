@@ -0,0 +1,162 @@
+//! Deadlock detection of the kernel mutexes and semaphores of a process, by the banker's algorithm.
+//! Every mutex has 1 unit and every semaphore has a unit for each of its count.
+//! Before a thread waits for a unit, the detector checks that all threads could still finish,
+//! that is every thread could get what it needs after others release what they hold.
+//! A request which breaks it fails with EDEADLK instead of blocking.
+//! A semaphore upped by threads which did not down it, such as one used for signalling,
+//! is not held by anyone, so waiting on it may be taken as a deadlock.
+//! The free units are counted from the held ones, so a thread handed a unit may acquire it
+//! before the thread giving it up releases it.
+//! Only the kernel objects are seen, the futex based objects of the user library are not.
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    vec::Vec,
+};
+
+use crate::syscall::errno::EDEADLK;
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Resource {
+    Mutex(usize),
+    Semaphore(usize),
+}
+
+/// Units of resources, a resource without units is removed.
+type Units = BTreeMap<Resource, usize>;
+
+fn add_unit(units: &mut Units, res: Resource) {
+    *units.entry(res).or_default() += 1;
+}
+
+/// Take a unit if there is one.
+fn sub_unit(units: &mut Units, res: Resource) {
+    if let Some(count) = units.get_mut(&res) {
+        *count -= 1;
+        if *count == 0 {
+            units.remove(&res);
+        }
+    }
+}
+
+/// The units of resources are tracked even when detection is disabled,
+/// so it can be enabled at any time.
+#[derive(Default)]
+pub struct DeadlockDetector {
+    enabled: bool,
+    /// Units of the resources, free or held.
+    total: Units,
+    /// Units held by threads, by tid.
+    allocation: BTreeMap<usize, Units>,
+    /// Units requested and not acquired yet by threads, by tid.
+    need: BTreeMap<usize, Units>,
+}
+
+impl DeadlockDetector {
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    /// A new resource with the free units.
+    pub fn add_resource(&mut self, res: Resource, units: usize) {
+        match units {
+            0 => self.total.remove(&res),
+            units => self.total.insert(res, units),
+        };
+    }
+
+    /// The thread is going to wait for a unit of the resource.
+    /// # Return
+    /// * -EDEADLK - detection is enabled, and threads could not all finish if it waits
+    pub fn request(&mut self, tid: usize, res: Resource) -> Result<(), isize> {
+        add_unit(self.need.entry(tid).or_default(), res);
+        if self.enabled && !self.is_safe() {
+            self.remove_need(tid, res);
+            return Err(-EDEADLK);
+        }
+        Ok(())
+    }
+
+    /// The thread gets a unit of the resource, which it has requested or not,
+    /// such as the mutex locked again by a condvar wait.
+    pub fn acquire(&mut self, tid: usize, res: Resource) {
+        self.remove_need(tid, res);
+        add_unit(self.allocation.entry(tid).or_default(), res);
+    }
+
+    /// The thread stops waiting for a unit of the resource without getting it.
+    pub fn cancel(&mut self, tid: usize, res: Resource) {
+        self.remove_need(tid, res);
+    }
+
+    /// The thread gives back a unit of the resource,
+    /// a semaphore upped by a thread which does not hold it gets a new unit.
+    pub fn release(&mut self, tid: usize, res: Resource) {
+        let held = self
+            .allocation
+            .get(&tid)
+            .is_some_and(|units| units.contains_key(&res));
+        if !held {
+            add_unit(&mut self.total, res);
+            return;
+        }
+        let units = self.allocation.get_mut(&tid).unwrap();
+        sub_unit(units, res);
+        if units.is_empty() {
+            self.allocation.remove(&tid);
+        }
+    }
+
+    /// Forget the exited thread, its tid may be reused. The units it holds are never released
+    /// by it, so they are taken out of the total, until another thread releases them.
+    pub fn remove_thread(&mut self, tid: usize) {
+        self.need.remove(&tid);
+        for (res, count) in self.allocation.remove(&tid).into_iter().flatten() {
+            for _ in 0..count {
+                sub_unit(&mut self.total, res);
+            }
+        }
+    }
+
+    fn remove_need(&mut self, tid: usize, res: Resource) {
+        if let Some(units) = self.need.get_mut(&tid) {
+            sub_unit(units, res);
+            if units.is_empty() {
+                self.need.remove(&tid);
+            }
+        }
+    }
+
+    /// Whether the threads can all finish in some order, every thread getting what it needs
+    /// from the available units and the units released by the threads finished before it.
+    fn is_safe(&self) -> bool {
+        let mut work = self.total.clone();
+        for (res, count) in self.allocation.values().flatten() {
+            if let Some(free) = work.get_mut(res) {
+                *free = free.saturating_sub(*count);
+            }
+        }
+        let tids: BTreeSet<usize> = self
+            .allocation
+            .keys()
+            .chain(self.need.keys())
+            .copied()
+            .collect();
+        let mut unfinished: Vec<usize> = tids.into_iter().collect();
+        loop {
+            let finishing = unfinished.iter().position(|tid| {
+                self.need
+                    .get(tid)
+                    .into_iter()
+                    .flatten()
+                    .all(|(res, count)| work.get(res).is_some_and(|free| free >= count))
+            });
+            let Some(index) = finishing else {
+                return unfinished.is_empty();
+            };
+            let tid = unfinished.swap_remove(index);
+            for (res, count) in self.allocation.get(&tid).into_iter().flatten() {
+                *work.entry(*res).or_default() += count;
+            }
+        }
+    }
+}
@@ -0,0 +1,168 @@
+//! Futex, threads waiting on a user u32 word until another thread wakes them up.
+//! A futex is keyed by the physical address of the word, so threads of a process and processes
+//! sharing a memory segment wait on the same futex. The word is only read by the kernel,
+//! user space changes it with atomics and calls the futex syscall when it has to wait or wake.
+use alloc::{
+    collections::{BTreeMap, VecDeque},
+    sync::{Arc, Weak},
+};
+use core::sync::atomic::{AtomicU32, Ordering};
+use lazy_static::lazy_static;
+
+use crate::{
+    syscall::errno::{EAGAIN, EINTR, ETIMEDOUT},
+    task::{
+        block_current_and_run_next, check_current_signals_error, manager::wakeup_thread,
+        prepare_interruptible_block, processor::current_task, thread::ThreadControlBlock,
+    },
+    timer::{add_timer, get_time_ms, remove_timer},
+};
+
+use super::SpinNoIrqLock;
+
+/// Operations of the futex syscall, the same values as linux.
+pub const FUTEX_WAIT: usize = 0;
+pub const FUTEX_WAKE: usize = 1;
+pub const FUTEX_REQUEUE: usize = 3;
+pub const FUTEX_CMP_REQUEUE: usize = 4;
+/// The futex is only used by the threads of a process, it is keyed the same way.
+pub const FUTEX_PRIVATE_FLAG: usize = 128;
+
+lazy_static! {
+    /// Waiting threads by the physical address of the word, a key without waiters is removed.
+    static ref FUTEXES: SpinNoIrqLock<BTreeMap<usize, VecDeque<Weak<ThreadControlBlock>>>> =
+        SpinNoIrqLock::new(BTreeMap::new());
+}
+
+/// Read the word at the physical address, it is aligned and mapped by the caller.
+fn load(key: usize) -> u32 {
+    unsafe { &*(key as *const AtomicU32) }.load(Ordering::SeqCst)
+}
+
+/// Wait until the futex is woken up, if the word is still val.
+/// The word is checked with the lock of the futexes held, so a wakeup after it is not lost.
+/// # Parameter
+/// * 'key' - the physical address of the word
+/// * 'deadline' - the time since boot in ms to give up waiting, None waits forever
+/// # Return
+/// * 0 - woken up, maybe spuriously
+/// * -EAGAIN - the word is not val
+/// * -ETIMEDOUT - not woken up before the deadline
+/// * -EINTR - interrupted by a signal
+pub fn wait(key: usize, val: u32, deadline: Option<usize>) -> isize {
+    let thread = current_task().unwrap();
+    prepare_interruptible_block();
+    let mut futexes = FUTEXES.lock();
+    if load(key) != val {
+        return -EAGAIN;
+    }
+    if check_current_signals_error().is_some() {
+        return -EINTR;
+    }
+    futexes
+        .entry(key)
+        .or_default()
+        .push_back(Arc::downgrade(&thread));
+    drop(futexes);
+    if let Some(deadline) = deadline {
+        add_timer(deadline, thread.clone());
+    }
+    block_current_and_run_next();
+    if deadline.is_some() {
+        remove_timer(&thread);
+    }
+    // A woken thread has been removed by the waker.
+    if !remove_waiter(&thread) {
+        return 0;
+    }
+    if check_current_signals_error().is_some() {
+        -EINTR
+    } else if deadline.is_some_and(|deadline| get_time_ms() >= deadline) {
+        -ETIMEDOUT
+    } else {
+        0
+    }
+}
+
+/// Remove the thread which stops waiting by itself, it may have been requeued to another key.
+/// # Return
+/// * false - the thread is not waiting
+fn remove_waiter(thread: &Arc<ThreadControlBlock>) -> bool {
+    let thread = Arc::downgrade(thread);
+    let mut futexes = FUTEXES.lock();
+    let Some((&key, waiters)) = futexes
+        .iter_mut()
+        .find(|(_, waiters)| waiters.iter().any(|waiter| waiter.ptr_eq(&thread)))
+    else {
+        return false;
+    };
+    waiters.retain(|waiter| !waiter.ptr_eq(&thread));
+    if waiters.is_empty() {
+        futexes.remove(&key);
+    }
+    true
+}
+
+/// Pop at most n threads waiting longest on the key.
+fn take_waiters(
+    futexes: &mut BTreeMap<usize, VecDeque<Weak<ThreadControlBlock>>>,
+    key: usize,
+    n: usize,
+) -> VecDeque<Weak<ThreadControlBlock>> {
+    let Some(waiters) = futexes.get_mut(&key) else {
+        return VecDeque::new();
+    };
+    let taken = waiters.drain(..n.min(waiters.len())).collect();
+    if waiters.is_empty() {
+        futexes.remove(&key);
+    }
+    taken
+}
+
+/// Wake up at most n threads waiting on the key.
+/// # Return
+/// * The number of threads woken up.
+pub fn wake(key: usize, n: usize) -> usize {
+    let waiters = take_waiters(&mut FUTEXES.lock(), key, n);
+    let count = waiters.len();
+    waiters.into_iter().for_each(wakeup_thread);
+    count
+}
+
+/// Wake up at most n_wake threads waiting on the key,
+/// and move at most n_requeue of the others to wait on key2 without waking them up.
+/// # Parameter
+/// * 'cmp' - the value the word must have, None does not check the word
+/// # Return
+/// * The number of threads woken up and moved.
+/// * -EAGAIN - the word is not the value of cmp
+pub fn requeue(
+    key: usize,
+    n_wake: usize,
+    key2: usize,
+    n_requeue: usize,
+    cmp: Option<u32>,
+) -> isize {
+    let mut futexes = FUTEXES.lock();
+    if cmp.is_some_and(|val| load(key) != val) {
+        return -EAGAIN;
+    }
+    let woken = take_waiters(&mut futexes, key, n_wake);
+    let count = woken.len();
+    woken.into_iter().for_each(wakeup_thread);
+    let moved = match key == key2 {
+        // Moving the waiters to the same key only changes their order.
+        true => futexes
+            .get(&key)
+            .map_or(0, |waiters| waiters.len().min(n_requeue)),
+        false => {
+            let moved = take_waiters(&mut futexes, key, n_requeue);
+            let len = moved.len();
+            if len > 0 {
+                futexes.entry(key2).or_default().extend(moved);
+            }
+            len
+        }
+    };
+    (count + moved) as isize
+}
@@ -1,5 +1,10 @@
-mod up;
-pub use up::UPSafeCell;
+mod spin;
+pub use spin::{SpinNoIrqGuard, SpinNoIrqLock};
 pub mod mutex;
 pub mod semaphore;
-pub mod condvar;
\ No newline at end of file
+pub mod condvar;
+pub mod rwlock;
+pub mod barrier;
+pub mod deadlock;
+pub mod futex;
+pub mod wait_queue;
\ No newline at end of file
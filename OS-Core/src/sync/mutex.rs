@@ -3,89 +3,377 @@ use alloc::{
     sync::{Arc, Weak},
 };
 
-use crate::task::{
-    block_current_and_run_next, manager::wakeup_thread, processor::current_task,
-    suspended_current_and_run_next, thread::ThreadControlBlock,
+use crate::{
+    syscall::errno::{EBUSY, EDEADLK, EPERM, ETIMEDOUT},
+    task::{
+        manager::wakeup_thread, processor::current_task, suspended_current_and_run_next,
+        thread::ThreadControlBlock,
+    },
+    timer::get_time_ms,
 };
 
-use super::UPSafeCell;
+use super::{wait_queue::block_current_until, SpinNoIrqLock};
+
+/// Flags of sys_mutex_create.
+/// Waiting threads block instead of yielding.
+pub const MUTEX_BLOCKING: usize = 1;
+/// The owner can lock it again.
+pub const MUTEX_RECURSIVE: usize = 1 << 1;
+/// Locking it again by the owner and unlocking it by others fail.
+pub const MUTEX_ERRORCHECK: usize = 1 << 2;
+/// The owner inherits the priority of the waiting threads, only for a blocking mutex.
+pub const MUTEX_PRIO_INHERIT: usize = 1 << 3;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum MutexType {
+    /// Locking it again by the owner deadlocks, and any thread can unlock it.
+    Normal,
+    /// The owner can lock it again, it is unlocked after as many unlocks.
+    Recursive,
+    /// Locking it again by the owner fails with EDEADLK.
+    ErrorCheck,
+}
 
 pub trait Mutex: Send + Sync {
-    fn lock(&self);
-    fn unlock(&self);
+    /// Lock the mutex, wait until it is unlocked.
+    /// # Return
+    /// * -EDEADLK - an error checking mutex is held by the current thread
+    fn lock(&self) -> isize {
+        self.lock_until(None)
+    }
+    /// Lock the mutex, wait until it is unlocked or the deadline is reached.
+    /// # Parameter
+    /// * 'deadline' - the time since boot in ms to give up waiting, None waits forever
+    /// # Return
+    /// * -EDEADLK - an error checking mutex is held by the current thread
+    /// * -ETIMEDOUT - not locked before the deadline
+    fn lock_until(&self, deadline: Option<usize>) -> isize;
+    /// Lock the mutex without waiting.
+    /// # Return
+    /// * -EBUSY - the mutex is locked, and it is not a recursive mutex held by the current thread
+    fn try_lock(&self) -> isize;
+    /// # Return
+    /// * -EPERM - the mutex is not locked,
+    ///   or a recursive or error checking mutex is held by another thread
+    fn unlock(&self) -> isize;
+    /// The thread holding the mutex.
+    fn owner(&self) -> Option<Arc<ThreadControlBlock>>;
+    fn mutex_type(&self) -> MutexType;
+}
+
+/// The thread holding a mutex, and the times a recursive mutex is locked by it.
+struct Owner {
+    mutex_type: MutexType,
+    thread: Option<Weak<ThreadControlBlock>>,
+    count: usize,
+}
+
+impl Owner {
+    fn new(mutex_type: MutexType) -> Self {
+        Self {
+            mutex_type,
+            thread: None,
+            count: 0,
+        }
+    }
+
+    fn is_locked(&self) -> bool {
+        self.thread.is_some()
+    }
+
+    fn get(&self) -> Option<Arc<ThreadControlBlock>> {
+        self.thread.as_ref().and_then(Weak::upgrade)
+    }
+
+    fn is_current(&self) -> bool {
+        self.get()
+            .is_some_and(|thread| Arc::ptr_eq(&thread, &current_task().unwrap()))
+    }
+
+    fn set(&mut self, thread: Weak<ThreadControlBlock>) {
+        self.thread = Some(thread);
+        self.count = 1;
+    }
+
+    /// Lock the mutex again by the owner without waiting.
+    /// # Return
+    /// * None - the current thread does not hold it, or it is a normal mutex
+    fn relock(&mut self) -> Option<isize> {
+        if !self.is_current() {
+            return None;
+        }
+        match self.mutex_type {
+            MutexType::Normal => None,
+            MutexType::Recursive => {
+                self.count += 1;
+                Some(0)
+            }
+            MutexType::ErrorCheck => Some(-EDEADLK),
+        }
+    }
+
+    /// Take the mutex for the current thread without waiting.
+    /// # Return
+    /// * -EBUSY - the mutex is locked, and it is not a recursive mutex held by the current thread
+    fn try_lock(&mut self) -> isize {
+        if self.mutex_type == MutexType::Recursive && self.is_current() {
+            self.count += 1;
+            return 0;
+        }
+        if self.is_locked() {
+            return -EBUSY;
+        }
+        self.set(Arc::downgrade(&current_task().unwrap()));
+        0
+    }
+
+    /// Check the unlock and give up the ownership.
+    /// # Return
+    /// * Ok(true) - the mutex is unlocked, and can be handed to a waiting thread
+    /// * Ok(false) - the owner still holds a recursive mutex
+    /// * -EPERM - the mutex is not locked, or is held by another thread and the type checks it
+    fn release(&mut self) -> Result<bool, isize> {
+        if !self.is_locked() || (self.mutex_type != MutexType::Normal && !self.is_current()) {
+            return Err(-EPERM);
+        }
+        if self.mutex_type == MutexType::Recursive && self.count > 1 {
+            self.count -= 1;
+            return Ok(false);
+        }
+        self.thread = None;
+        self.count = 0;
+        Ok(true)
+    }
 }
 
 pub struct MutexBlocking {
-    inner: UPSafeCell<MutexBlockingInner>,
+    inner: SpinNoIrqLock<MutexBlockingInner>,
 }
 pub struct MutexBlockingInner {
-    locked: bool,
+    owner: Owner,
     blocked_threads: VecDeque<Weak<ThreadControlBlock>>,
 }
+impl MutexBlockingInner {
+    fn blocked_threads(&mut self) -> &mut VecDeque<Weak<ThreadControlBlock>> {
+        &mut self.blocked_threads
+    }
+}
 impl MutexBlocking {
-    pub fn new() -> Self {
+    pub fn new(mutex_type: MutexType) -> Self {
         Self {
-            inner: unsafe {
-                UPSafeCell::new(MutexBlockingInner {
-                    locked: false,
-                    blocked_threads: VecDeque::new(),
-                })
-            },
+            inner: SpinNoIrqLock::new(MutexBlockingInner {
+                owner: Owner::new(mutex_type),
+                blocked_threads: VecDeque::new(),
+            }),
         }
     }
 }
 impl Mutex for MutexBlocking {
-    fn lock(&self) {
-        let mut inner = self.inner.exclusive_access();
-        if inner.locked {
-            inner
-                .blocked_threads
-                .push_back(Arc::downgrade(&current_task().unwrap()));
-            drop(inner);
-            block_current_and_run_next();
-        } else {
-            inner.locked = true;
+    fn lock_until(&self, deadline: Option<usize>) -> isize {
+        let mut inner = self.inner.lock();
+        if let Some(ret) = inner.owner.relock() {
+            return ret;
+        }
+        let thread = Arc::downgrade(&current_task().unwrap());
+        if !inner.owner.is_locked() {
+            inner.owner.set(thread);
+            return 0;
         }
+        // The unlocking thread hands the mutex to this one.
+        inner.blocked_threads.push_back(thread);
+        drop(inner);
+        let waiters = MutexBlockingInner::blocked_threads;
+        match block_current_until(&self.inner, waiters, deadline, |_| {}) {
+            true => 0,
+            false => -ETIMEDOUT,
+        }
+    }
+
+    fn try_lock(&self) -> isize {
+        self.inner.lock().owner.try_lock()
     }
 
-    fn unlock(&self) {
-        let mut inner = self.inner.exclusive_access();
-        assert!(inner.locked);
-        if inner.blocked_threads.is_empty() {
-            inner.locked = false;
-        } else {
-            wakeup_thread(inner.blocked_threads.pop_front().unwrap());
+    fn unlock(&self) -> isize {
+        let mut inner = self.inner.lock();
+        match inner.owner.release() {
+            Ok(true) => {}
+            Ok(false) => return 0,
+            Err(errno) => return errno,
         }
+        if let Some(thread) = inner.blocked_threads.pop_front() {
+            inner.owner.set(thread.clone());
+            wakeup_thread(thread);
+        }
+        0
+    }
+
+    fn owner(&self) -> Option<Arc<ThreadControlBlock>> {
+        self.inner.lock().owner.get()
+    }
+
+    fn mutex_type(&self) -> MutexType {
+        self.inner.lock().owner.mutex_type
     }
 }
 
 pub struct MutexSpin {
-    locked: UPSafeCell<bool>,
+    owner: SpinNoIrqLock<Owner>,
 }
 impl MutexSpin {
-    pub fn new() -> Self {
+    pub fn new(mutex_type: MutexType) -> Self {
         Self {
-            locked: unsafe { UPSafeCell::new(false) },
+            owner: SpinNoIrqLock::new(Owner::new(mutex_type)),
         }
     }
 }
 impl Mutex for MutexSpin {
-    fn lock(&self) {
+    fn lock_until(&self, deadline: Option<usize>) -> isize {
+        if let Some(ret) = self.owner.lock().relock() {
+            return ret;
+        }
         loop {
-            let mut locked = self.locked.exclusive_access();
-            if *locked {
-                drop(locked);
-                suspended_current_and_run_next();
-            } else {
-                *locked = true;
-                break;
+            let mut owner = self.owner.lock();
+            if !owner.is_locked() {
+                owner.set(Arc::downgrade(&current_task().unwrap()));
+                return 0;
+            }
+            drop(owner);
+            if deadline.is_some_and(|deadline| get_time_ms() >= deadline) {
+                return -ETIMEDOUT;
             }
+            suspended_current_and_run_next();
+        }
+    }
+
+    fn try_lock(&self) -> isize {
+        self.owner.lock().try_lock()
+    }
+
+    fn unlock(&self) -> isize {
+        match self.owner.lock().release() {
+            Ok(_) => 0,
+            Err(errno) => errno,
+        }
+    }
+
+    fn owner(&self) -> Option<Arc<ThreadControlBlock>> {
+        self.owner.lock().get()
+    }
+
+    fn mutex_type(&self) -> MutexType {
+        self.owner.lock().mutex_type
+    }
+}
+
+/// A blocking mutex whose owner runs at the highest priority of the threads waiting on it,
+/// so a low priority owner is not kept from running and unlocking by middle priority threads.
+/// The highest priority waiting thread gets the mutex first.
+/// The priority is not passed on when the owner itself waits on another mutex.
+pub struct MutexPriorityInherit {
+    inner: SpinNoIrqLock<MutexBlockingInner>,
+}
+impl MutexPriorityInherit {
+    pub fn new(mutex_type: MutexType) -> Self {
+        Self {
+            inner: SpinNoIrqLock::new(MutexBlockingInner {
+                owner: Owner::new(mutex_type),
+                blocked_threads: VecDeque::new(),
+            }),
         }
     }
 
-    fn unlock(&self) {
-        let mut locked = self.locked.exclusive_access();
-        assert!(*locked);
-        *locked = false;
+    /// The key of the priority inherited from this mutex.
+    fn key(&self) -> usize {
+        self as *const Self as usize
+    }
+
+    /// Give the owner the highest priority of the waiting threads.
+    fn update_owner_priority(&self, inner: &MutexBlockingInner) {
+        let Some(owner) = inner.owner.get() else {
+            return;
+        };
+        let nice = inner.blocked_threads.iter().filter_map(nice_of).min();
+        let mut owner_inner = owner.inner_exclusive_access();
+        match nice {
+            Some(nice) => owner_inner.inherited_nice.insert(self.key(), nice),
+            None => owner_inner.inherited_nice.remove(&self.key()),
+        };
+    }
+}
+
+/// The priority a thread gives to the owner of the mutex it waits on.
+fn nice_of(thread: &Weak<ThreadControlBlock>) -> Option<isize> {
+    thread
+        .upgrade()
+        .map(|thread| thread.inner_exclusive_access().effective_nice())
+}
+
+impl Mutex for MutexPriorityInherit {
+    fn lock_until(&self, deadline: Option<usize>) -> isize {
+        let mut inner = self.inner.lock();
+        if let Some(ret) = inner.owner.relock() {
+            return ret;
+        }
+        let thread = Arc::downgrade(&current_task().unwrap());
+        if !inner.owner.is_locked() {
+            inner.owner.set(thread);
+            return 0;
+        }
+        inner.blocked_threads.push_back(thread);
+        self.update_owner_priority(&inner);
+        drop(inner);
+        let waiters = MutexBlockingInner::blocked_threads;
+        // The owner does not inherit the priority of a thread which stops waiting.
+        let on_timeout = |inner: &mut MutexBlockingInner| self.update_owner_priority(inner);
+        match block_current_until(&self.inner, waiters, deadline, on_timeout) {
+            true => 0,
+            false => -ETIMEDOUT,
+        }
+    }
+
+    fn try_lock(&self) -> isize {
+        self.inner.lock().owner.try_lock()
+    }
+
+    fn unlock(&self) -> isize {
+        let mut inner = self.inner.lock();
+        let owner = inner.owner.get();
+        match inner.owner.release() {
+            Ok(true) => {}
+            Ok(false) => return 0,
+            Err(errno) => return errno,
+        }
+        if let Some(owner) = owner {
+            owner
+                .inner_exclusive_access()
+                .inherited_nice
+                .remove(&self.key());
+        }
+        // Threads which have exited are dropped.
+        inner
+            .blocked_threads
+            .retain(|thread| thread.strong_count() > 0);
+        let next = inner
+            .blocked_threads
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, thread)| nice_of(thread).unwrap_or(isize::MAX))
+            .map(|(index, _)| index);
+        let Some(thread) = next.and_then(|index| inner.blocked_threads.remove(index)) else {
+            return 0;
+        };
+        // The new owner inherits the priority of the rest.
+        inner.owner.set(thread.clone());
+        self.update_owner_priority(&inner);
+        wakeup_thread(thread);
+        0
+    }
+
+    fn owner(&self) -> Option<Arc<ThreadControlBlock>> {
+        self.inner.lock().owner.get()
+    }
+
+    fn mutex_type(&self) -> MutexType {
+        self.inner.lock().owner.mutex_type
     }
 }
@@ -0,0 +1,203 @@
+//! Reader-writer lock, held by many readers or one writer.
+//! Writers are preferred, once a writer waits new readers wait behind it,
+//! so writers are not kept waiting by readers which keep coming.
+//! A reader can upgrade to the writer, it waits until the other readers unlock,
+//! and new readers and writers wait behind it.
+//! The holders are kept by tid, only a holder can unlock or upgrade it.
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    sync::Weak,
+};
+
+use crate::{
+    syscall::errno::{EDEADLK, EPERM},
+    task::{
+        block_current_and_run_next, prepare_block, processor::current_task,
+        thread::ThreadControlBlock,
+    },
+};
+
+use super::{wait_queue::WaitQueue, SpinNoIrqLock};
+
+pub struct RwLock {
+    inner: SpinNoIrqLock<RwLockInner>,
+}
+pub struct RwLockInner {
+    /// Threads holding it for reading and how many times,
+    /// including the one waiting to upgrade.
+    readers: BTreeMap<usize, usize>,
+    writer: Option<usize>,
+    /// Writers waiting or going to wait.
+    waiting_writers: BTreeSet<usize>,
+    /// The reader waiting to upgrade.
+    upgrading: Option<usize>,
+    read_waiters: WaitQueue,
+    write_waiters: WaitQueue,
+    upgrade_waiters: WaitQueue,
+}
+
+impl RwLockInner {
+    fn read_waiters(&mut self) -> &mut WaitQueue {
+        &mut self.read_waiters
+    }
+
+    fn write_waiters(&mut self) -> &mut WaitQueue {
+        &mut self.write_waiters
+    }
+
+    fn upgrade_waiters(&mut self) -> &mut WaitQueue {
+        &mut self.upgrade_waiters
+    }
+
+    /// Wake up the threads which may take the lock now.
+    fn wake(&mut self) {
+        if self.upgrading.is_some() {
+            if self.readers.len() == 1 {
+                self.upgrade_waiters.wake_one();
+            }
+        } else if self.writer.is_none() {
+            if self.waiting_writers.is_empty() {
+                self.read_waiters.wake_all();
+            } else if self.readers.is_empty() {
+                self.write_waiters.wake_one();
+            }
+        }
+    }
+}
+
+fn current_tid() -> usize {
+    current_task().unwrap().tid()
+}
+
+impl RwLock {
+    pub fn new() -> Self {
+        Self {
+            inner: SpinNoIrqLock::new(RwLockInner {
+                readers: BTreeMap::new(),
+                writer: None,
+                waiting_writers: BTreeSet::new(),
+                upgrading: None,
+                read_waiters: WaitQueue::new(),
+                write_waiters: WaitQueue::new(),
+                upgrade_waiters: WaitQueue::new(),
+            }),
+        }
+    }
+
+    /// Wait in the queue until acquire takes the lock.
+    fn wait(
+        &self,
+        waiters: fn(&mut RwLockInner) -> &mut WaitQueue,
+        acquire: impl Fn(&mut RwLockInner) -> bool,
+    ) {
+        loop {
+            prepare_block();
+            let mut inner = self.inner.lock();
+            if acquire(&mut inner) {
+                return;
+            }
+            waiters(&mut inner).push_current();
+            drop(inner);
+            block_current_and_run_next();
+            // Woken up spuriously, the thread is still in the queue.
+            waiters(&mut self.inner.lock()).remove_current();
+        }
+    }
+
+    /// Lock it for reading, wait while a writer holds it or waits for it.
+    /// A reader locking it again waits behind the waiting writers, so it may deadlock.
+    pub fn read(&self) {
+        let tid = current_tid();
+        self.wait(RwLockInner::read_waiters, |inner| {
+            let free = inner.writer.is_none()
+                && inner.waiting_writers.is_empty()
+                && inner.upgrading.is_none();
+            if free {
+                *inner.readers.entry(tid).or_insert(0) += 1;
+            }
+            free
+        });
+    }
+
+    /// Lock it for writing, wait until all readers and the writer unlock it.
+    pub fn write(&self) {
+        let tid = current_tid();
+        self.inner.lock().waiting_writers.insert(tid);
+        self.wait(RwLockInner::write_waiters, |inner| {
+            let free =
+                inner.writer.is_none() && inner.readers.is_empty() && inner.upgrading.is_none();
+            if free {
+                inner.writer = Some(tid);
+                inner.waiting_writers.remove(&tid);
+            }
+            free
+        });
+    }
+
+    /// Turn the read lock of the current thread into the write lock,
+    /// wait until the other readers unlock it.
+    /// A read lock taken more than once turns into one write lock.
+    /// # Return
+    /// * -EPERM - the current thread does not hold it for reading
+    /// * -EDEADLK - another reader is waiting to upgrade, they would wait for each other
+    pub fn upgrade(&self) -> isize {
+        let tid = current_tid();
+        let mut inner = self.inner.lock();
+        if !inner.readers.contains_key(&tid) {
+            return -EPERM;
+        }
+        if inner.upgrading.is_some() {
+            return -EDEADLK;
+        }
+        inner.upgrading = Some(tid);
+        drop(inner);
+        self.wait(RwLockInner::upgrade_waiters, |inner| {
+            let free = inner.readers.len() == 1;
+            if free {
+                inner.readers.clear();
+                inner.writer = Some(tid);
+                inner.upgrading = None;
+            }
+            free
+        });
+        0
+    }
+
+    /// Unlock the read lock or the write lock of the current thread.
+    /// # Return
+    /// * -EPERM - the current thread does not hold it
+    pub fn unlock(&self) -> isize {
+        let tid = current_tid();
+        let mut inner = self.inner.lock();
+        if inner.writer == Some(tid) {
+            inner.writer = None;
+        } else if let Some(count) = inner.readers.get_mut(&tid) {
+            *count -= 1;
+            if *count == 0 {
+                inner.readers.remove(&tid);
+            }
+        } else {
+            return -EPERM;
+        }
+        inner.wake();
+        0
+    }
+
+    /// Forget the exited thread, its tid may be reused.
+    /// The locks it holds are unlocked, and it stops waiting.
+    pub fn remove_thread(&self, tid: usize, thread: &Weak<ThreadControlBlock>) {
+        let mut inner = self.inner.lock();
+        inner.readers.remove(&tid);
+        inner.waiting_writers.remove(&tid);
+        if inner.writer == Some(tid) {
+            inner.writer = None;
+        }
+        if inner.upgrading == Some(tid) {
+            inner.upgrading = None;
+        }
+        inner.read_waiters.remove(thread);
+        inner.write_waiters.remove(thread);
+        inner.upgrade_waiters.remove(thread);
+        inner.wake();
+    }
+}
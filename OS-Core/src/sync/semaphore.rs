@@ -3,48 +3,75 @@ use alloc::{
     sync::{Arc, Weak},
 };
 
-use crate::task::{
-    block_current_and_run_next, manager::wakeup_thread, processor::current_task,
-    thread::ThreadControlBlock,
-};
+use crate::task::{manager::wakeup_thread, processor::current_task, thread::ThreadControlBlock};
 
-use super::UPSafeCell;
+use super::{wait_queue::block_current_until, SpinNoIrqLock};
 
 pub struct Semaphore {
-    inner: UPSafeCell<SemaphoreInner>,
+    inner: SpinNoIrqLock<SemaphoreInner>,
 }
 pub struct SemaphoreInner {
+    /// The free units, or the number of waiting threads if it is negative.
     count: isize,
     blocked_threads: VecDeque<Weak<ThreadControlBlock>>,
 }
+impl SemaphoreInner {
+    fn blocked_threads(&mut self) -> &mut VecDeque<Weak<ThreadControlBlock>> {
+        &mut self.blocked_threads
+    }
+}
 impl Semaphore {
     pub fn new(res_count: usize) -> Self {
         Self {
-            inner: unsafe {
-                UPSafeCell::new(SemaphoreInner {
-                    count: res_count as isize,
-                    blocked_threads: VecDeque::new(),
-                })
-            },
+            inner: SpinNoIrqLock::new(SemaphoreInner {
+                count: res_count as isize,
+                blocked_threads: VecDeque::new(),
+            }),
         }
     }
     pub fn up(&self) {
-        let mut inner = self.inner.exclusive_access();
+        let mut inner = self.inner.lock();
         inner.count += 1;
         if inner.count <= 0 {
             wakeup_thread(inner.blocked_threads.pop_front().unwrap());
         }
     }
     pub fn down(&self) {
-        let mut inner = self.inner.exclusive_access();
+        self.down_until(None);
+    }
+    /// Take a unit, wait until there is one or the deadline is reached.
+    /// # Parameter
+    /// * 'deadline' - the time since boot in ms to give up waiting, None waits forever
+    /// # Return
+    /// * false - no unit is taken before the deadline
+    pub fn down_until(&self, deadline: Option<usize>) -> bool {
+        let mut inner = self.inner.lock();
         inner.count -= 1;
-        if inner.count < 0 {
-            let curr_thread = current_task().unwrap();
-            inner
-                .blocked_threads
-                .push_back(Arc::downgrade(&curr_thread));
-            drop(inner);
-            block_current_and_run_next();
+        if inner.count >= 0 {
+            return true;
         }
+        let curr_thread = current_task().unwrap();
+        inner
+            .blocked_threads
+            .push_back(Arc::downgrade(&curr_thread));
+        drop(inner);
+        // A thread which stops waiting is not counted any more.
+        block_current_until(
+            &self.inner,
+            SemaphoreInner::blocked_threads,
+            deadline,
+            |inner| inner.count += 1,
+        )
+    }
+    /// Take a unit without waiting.
+    /// # Return
+    /// * false - there is no free unit
+    pub fn try_down(&self) -> bool {
+        let mut inner = self.inner.lock();
+        if inner.count <= 0 {
+            return false;
+        }
+        inner.count -= 1;
+        true
     }
 }
@@ -0,0 +1,105 @@
+use core::{
+    cell::UnsafeCell,
+    ops::{Deref, DerefMut},
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+};
+
+use riscv::register::sstatus;
+
+use crate::{
+    platfrom::MAX_HARTS,
+    smp::{ack_tlb_flush, hart_id},
+};
+
+#[allow(clippy::declare_interior_mutable_const)]
+const DEPTH_INIT: AtomicUsize = AtomicUsize::new(0);
+#[allow(clippy::declare_interior_mutable_const)]
+const ENABLED_INIT: AtomicBool = AtomicBool::new(false);
+/// How many locks the hart is holding now.
+static LOCK_DEPTH: [AtomicUsize; MAX_HARTS] = [DEPTH_INIT; MAX_HARTS];
+/// Whether the interrupt was enabled before the hart took its first lock.
+static INTR_ENABLED: [AtomicBool; MAX_HARTS] = [ENABLED_INIT; MAX_HARTS];
+
+/// Disable interrupt of the current hart, it can be nested.
+fn push_off() {
+    let enabled = sstatus::read().sie();
+    unsafe { sstatus::clear_sie() };
+    let hart = hart_id();
+    if LOCK_DEPTH[hart].fetch_add(1, Ordering::Relaxed) == 0 {
+        INTR_ENABLED[hart].store(enabled, Ordering::Relaxed);
+    }
+}
+
+/// Restore interrupt of the current hart when the outermost lock is released.
+fn pop_off() {
+    let hart = hart_id();
+    if LOCK_DEPTH[hart].fetch_sub(1, Ordering::Relaxed) == 1
+        && INTR_ENABLED[hart].load(Ordering::Relaxed)
+    {
+        unsafe { sstatus::set_sie() };
+    }
+}
+
+/// Spin lock shared by all harts.
+/// Interrupt of the current hart is disabled while holding the lock,
+/// so an interrupt handler never spins on a lock held by the code it interrupts.
+pub struct SpinNoIrqLock<T: ?Sized> {
+    locked: AtomicBool,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: ?Sized + Send> Sync for SpinNoIrqLock<T> {}
+unsafe impl<T: ?Sized + Send> Send for SpinNoIrqLock<T> {}
+
+impl<T> SpinNoIrqLock<T> {
+    pub const fn new(data: T) -> Self {
+        Self {
+            locked: AtomicBool::new(false),
+            data: UnsafeCell::new(data),
+        }
+    }
+}
+
+impl<T: ?Sized> SpinNoIrqLock<T> {
+    /// Spin until the lock is acquired.
+    pub fn lock(&self) -> SpinNoIrqGuard<'_, T> {
+        push_off();
+        while self
+            .locked
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            while self.locked.load(Ordering::Relaxed) {
+                // The holder may be waiting for this hart to flush the TLB.
+                ack_tlb_flush();
+                core::hint::spin_loop();
+            }
+        }
+        SpinNoIrqGuard { lock: self }
+    }
+}
+
+/// RAII guard of SpinNoIrqLock, the lock is released when it is dropped.
+pub struct SpinNoIrqGuard<'a, T: ?Sized> {
+    lock: &'a SpinNoIrqLock<T>,
+}
+
+impl<T: ?Sized> Deref for SpinNoIrqGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for SpinNoIrqGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for SpinNoIrqGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.locked.store(false, Ordering::Release);
+        pop_off();
+    }
+}
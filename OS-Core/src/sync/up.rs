@@ -1,19 +0,0 @@
-use core::cell::{RefCell, RefMut};
-
-pub struct UPSafeCell<T> {
-    inner: RefCell<T>,
-}
-
-// not do anything, but have to implement this impl
-unsafe impl<T> Sync for UPSafeCell<T> {}
-
-impl<T> UPSafeCell<T> {
-    pub unsafe fn new(v: T) -> Self {
-        Self {
-            inner: RefCell::new(v),
-        }
-    }
-    pub fn exclusive_access(&self) -> RefMut<'_, T> {
-        self.inner.borrow_mut()
-    }
-}
@@ -0,0 +1,190 @@
+use alloc::{
+    collections::VecDeque,
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+
+use crate::{
+    syscall::errno::{EINTR, ETIMEDOUT},
+    task::{
+        block_current_and_run_next, cancel_block, check_current_signals_error,
+        manager::wakeup_thread, prepare_block, prepare_interruptible_block,
+        processor::current_task, thread::ThreadControlBlock,
+    },
+    timer::{add_timer, get_time_ms, remove_timer},
+};
+
+use super::SpinNoIrqLock;
+
+/// Notified on every wakeup of the wait queues it is added to, such as an epoll instance.
+/// It is called with the lock of the object being waited for held,
+/// so it must not lock the object again.
+pub trait Watcher: Send + Sync {
+    fn notify(&self);
+}
+
+/// Threads waiting for something, it is kept in the lock of the object being waited for.
+/// A waiter prepares to block, adds itself with the lock held, releases the lock
+/// and calls block_current_and_run_next, so a wakeup between them is not lost.
+pub struct WaitQueue {
+    threads: VecDeque<Weak<ThreadControlBlock>>,
+    /// Watchers stay in the queue until they are removed or dropped.
+    watchers: Vec<Weak<dyn Watcher>>,
+}
+
+impl WaitQueue {
+    pub fn new() -> Self {
+        Self {
+            threads: VecDeque::new(),
+            watchers: Vec::new(),
+        }
+    }
+
+    /// Add the current thread to the queue.
+    pub fn push_current(&mut self) {
+        self.threads
+            .push_back(Arc::downgrade(&current_task().unwrap()));
+    }
+
+    /// Remove the current thread from the queue, when it stops waiting without being woken up.
+    pub fn remove_current(&mut self) {
+        self.remove(&Arc::downgrade(&current_task().unwrap()));
+    }
+
+    /// Remove the thread from the queue, such as when it exits.
+    pub fn remove(&mut self, thread: &Weak<ThreadControlBlock>) {
+        self.threads.retain(|other| !other.ptr_eq(thread));
+    }
+
+    pub fn add_watcher(&mut self, watcher: Weak<dyn Watcher>) {
+        self.watchers.push(watcher);
+    }
+
+    pub fn remove_watcher(&mut self, watcher: &Weak<dyn Watcher>) {
+        self.watchers.retain(|other| !other.ptr_eq(watcher));
+    }
+
+    /// Notify all watchers, and forget the dropped ones.
+    fn notify_watchers(&mut self) {
+        self.watchers.retain(|watcher| match watcher.upgrade() {
+            Some(watcher) => {
+                watcher.notify();
+                true
+            }
+            None => false,
+        });
+    }
+
+    /// Wake up the thread which waits longest, watchers are always notified.
+    /// # Return
+    /// * false - no thread is waiting.
+    pub fn wake_one(&mut self) -> bool {
+        self.notify_watchers();
+        self.wake_thread()
+    }
+
+    /// Wake up all waiting threads.
+    pub fn wake_all(&mut self) {
+        self.notify_watchers();
+        while self.wake_thread() {}
+    }
+
+    fn wake_thread(&mut self) -> bool {
+        match self.threads.pop_front() {
+            Some(thread) => {
+                wakeup_thread(thread);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Block the current thread, which has added itself to a queue of waiting threads of an object
+/// and released the lock of the object, until a waker takes it out of the queue
+/// or the deadline is reached. A thread still in the queue after it is woken up,
+/// such as spuriously, blocks again.
+/// # Parameter
+/// * 'waiters' - the queue in the object
+/// * 'deadline' - the time since boot in ms to give up waiting, None waits forever
+/// * 'on_timeout' - called with the lock held, after the thread is removed from the queue
+/// # Return
+/// * false - the deadline is reached before a waker takes it
+pub fn block_current_until<T>(
+    lock: &SpinNoIrqLock<T>,
+    waiters: fn(&mut T) -> &mut VecDeque<Weak<ThreadControlBlock>>,
+    deadline: Option<usize>,
+    on_timeout: impl FnOnce(&mut T),
+) -> bool {
+    let current = Arc::downgrade(&current_task().unwrap());
+    let mut on_timeout = Some(on_timeout);
+    let check = |expired: bool| {
+        let mut inner = lock.lock();
+        let queue = waiters(&mut inner);
+        let Some(index) = queue.iter().position(|waiter| waiter.ptr_eq(&current)) else {
+            return Some(true);
+        };
+        if !expired {
+            return None;
+        }
+        queue.remove(index);
+        if let Some(on_timeout) = on_timeout.take() {
+            on_timeout(&mut inner);
+        }
+        Some(false)
+    };
+    // The thread stays in the queue until a waker or the timeout takes it.
+    wait_event(deadline, false, check, || {}).unwrap_or(false)
+}
+
+/// Block the current thread until check returns Some, the deadline is reached,
+/// or a signal is sent to the process if the wait is interruptible.
+/// check is called after the thread prepares to block, at first and every time it is woken up,
+/// it returns Some to stop waiting, or adds the thread to the wait queues and returns None.
+/// A wakeup kept after the thread stops waiting, such as by the timer, is dropped.
+/// # Parameter
+/// * 'deadline' - the time since boot in ms to give up waiting, None waits forever
+/// * 'check' - called with true once the deadline is reached
+/// * 'leave' - removes the thread from the wait queues, it is called when the thread
+///   is woken up or stops waiting after check returns None
+/// # Return
+/// * The value returned by check.
+/// * -ETIMEDOUT - check returns None after the deadline
+/// * -EINTR - interrupted by a signal
+pub fn wait_event<T>(
+    deadline: Option<usize>,
+    interruptible: bool,
+    mut check: impl FnMut(bool) -> Option<T>,
+    mut leave: impl FnMut(),
+) -> Result<T, isize> {
+    let thread = current_task().unwrap();
+    if let Some(deadline) = deadline.filter(|deadline| *deadline > get_time_ms()) {
+        add_timer(deadline, thread.clone());
+    }
+    let result = loop {
+        if interruptible {
+            prepare_interruptible_block();
+        } else {
+            prepare_block();
+        }
+        let expired = deadline.is_some_and(|deadline| get_time_ms() >= deadline);
+        if let Some(value) = check(expired) {
+            break Ok(value);
+        }
+        if expired {
+            leave();
+            break Err(-ETIMEDOUT);
+        }
+        if interruptible && check_current_signals_error().is_some() {
+            leave();
+            break Err(-EINTR);
+        }
+        block_current_and_run_next();
+        leave();
+    };
+    if deadline.is_some() {
+        remove_timer(&thread);
+    }
+    cancel_block();
+    result
+}
@@ -0,0 +1,69 @@
+//! Error numbers returned by syscalls as negative values, the same as linux.
+//! Most syscalls still return -1 for all errors.
+
+/// Operation not permitted.
+pub const EPERM: isize = 1;
+/// No such file or directory, or no such entry.
+pub const ENOENT: isize = 2;
+/// Interrupted system call.
+pub const EINTR: isize = 4;
+/// Input/output error, such as reading the terminal from the background ignoring SIGTTIN.
+pub const EIO: isize = 5;
+/// No such device or address, such as a named pipe without readers.
+pub const ENXIO: isize = 6;
+/// Bad file descriptor.
+pub const EBADF: isize = 9;
+/// Try again, the operation would block.
+pub const EAGAIN: isize = 11;
+/// Out of memory.
+pub const ENOMEM: isize = 12;
+/// Permission denied by the mode.
+pub const EACCES: isize = 13;
+/// Bad address, such as an unmapped futex word.
+pub const EFAULT: isize = 14;
+/// Device or resource busy, such as a locked mutex.
+pub const EBUSY: isize = 16;
+/// The entry already exists.
+pub const EEXIST: isize = 17;
+/// Invalid argument.
+pub const EINVAL: isize = 22;
+/// Too many open files.
+pub const EMFILE: isize = 24;
+/// No space left, such as no free id of a shared memory segment.
+pub const ENOSPC: isize = 28;
+/// Broken pipe.
+pub const EPIPE: isize = 32;
+/// The request of a lock would deadlock.
+pub const EDEADLK: isize = 35;
+/// File name too long, such as the name of a message queue.
+pub const ENAMETOOLONG: isize = 36;
+/// Function not implemented, such as an unknown futex operation.
+pub const ENOSYS: isize = 38;
+/// The fd is not a socket.
+pub const ENOTSOCK: isize = 88;
+/// A datagram socket has no destination address.
+pub const EDESTADDRREQ: isize = 89;
+/// Message too long for a datagram socket.
+pub const EMSGSIZE: isize = 90;
+/// Wrong socket type for the operation, such as connecting a stream socket to a datagram socket.
+pub const EPROTOTYPE: isize = 91;
+/// Operation not supported by the socket, such as listen of a datagram socket.
+pub const EOPNOTSUPP: isize = 95;
+/// Address family not supported.
+pub const EAFNOSUPPORT: isize = 97;
+/// Address already in use.
+pub const EADDRINUSE: isize = 98;
+/// The address is not an address of the machine.
+pub const EADDRNOTAVAIL: isize = 99;
+/// The socket is already connected.
+pub const EISCONN: isize = 106;
+/// The socket is not connected.
+pub const ENOTCONN: isize = 107;
+/// A timed wait reached its deadline.
+pub const ETIMEDOUT: isize = 110;
+/// Connection refused, no socket is listening on the address.
+pub const ECONNREFUSED: isize = 111;
+/// A non-blocking connect is in progress.
+pub const EALREADY: isize = 114;
+/// A non-blocking connect has started, poll for writing to wait for it.
+pub const EINPROGRESS: isize = 115;
@@ -1,14 +1,29 @@
 // File stream mod
 
-use alloc::sync::Arc;
+use alloc::{string::String, sync::Arc, vec::Vec};
 
 use crate::{
     fs::{
-        inode::{open_file, OpenFlags},
+        epoll::{Epoll, EpollEvent},
+        eventfd::{Eventfd, EventfdFlags},
+        inode::OpenFlags,
         pipe::create_pipe,
+        poll::{self, PollEvents},
+        signalfd::{Signalfd, SignalfdFlags},
+        timerfd::{
+            ITimerSpec, Timerfd, TimerfdFlags, CLOCK_MONOTONIC, CLOCK_REALTIME, TFD_TIMER_ABSTIME,
+        },
+        vfs::{self, Stat, S_IFIFO, S_IFMT},
+        File,
     },
-    mm::page_table::{translate_byte_buffer, PageTable, UserBuffer},
-    task::processor::{current_process, current_user_token},
+    mm::page_table::{copy_from_user, copy_to_user, translate_byte_buffer, PageTable, UserBuffer},
+    syscall::errno::{EBADF, EINVAL, EMFILE},
+    task::{
+        process::{FdEntry, FD_MAX},
+        processor::{current_process, current_user_token},
+        signal::SignalFlags,
+    },
+    timer::TimeSpec,
 };
 
 /// Wtire buf of lenth "len" to file fd.
@@ -22,12 +37,12 @@ pub fn sys_write(fd: usize, buf: *const u8, len: usize) -> isize {
     let token = current_user_token();
     let proc = current_process();
     let inner = proc.inner_exclusive_access();
-    if fd >= inner.fd_table.len() {
-        return -1;
-    }
-    if let Some(file) = inner.fd_table[fd].clone() {
+    if let Some(file) = inner.get_file(fd) {
         drop(inner);
-        file.write(UserBuffer::new(translate_byte_buffer(token, buf, len))) as isize
+        if !file.writable() {
+            return -EBADF;
+        }
+        file.write(UserBuffer::new(translate_byte_buffer(token, buf, len)))
     } else {
         -1
     }
@@ -44,27 +59,42 @@ pub fn sys_read(fd: usize, buf: *const u8, len: usize) -> isize {
     let token = current_user_token();
     let proc = current_process();
     let inner = proc.inner_exclusive_access();
-    if fd >= inner.fd_table.len() {
-        return -1;
-    }
-    if let Some(file) = inner.fd_table[fd].clone() {
+    if let Some(file) = inner.get_file(fd) {
         drop(inner);
-        file.read(UserBuffer::new(translate_byte_buffer(token, buf, len))) as isize
+        if !file.readable() {
+            return -EBADF;
+        }
+        file.read(UserBuffer::new(translate_byte_buffer(token, buf, len)))
     } else {
         -1
     }
 }
 
+/// Open a file by path.
+/// # Parameter
+/// * 'flags' - OpenFlags, O_CLOEXEC sets FD_CLOEXEC of the new fd
+/// # Return
+/// * -1 - bad flags, or the file can not be opened
+/// * -EMFILE - all fds are in use
+/// * -ENXIO - a named pipe is opened for writing with O_NONBLOCK, and it has no readers
+/// * -EINTR - interrupted by a signal while opening a named pipe
 pub fn sys_open(path: *const u8, flags: u32) -> isize {
     let proc = current_process();
     let token = current_user_token();
     let path = PageTable::from_token(token).translated_str(path);
-    if let Some(inode) = open_file(&path, OpenFlags::from_bits(flags).unwrap()) {
-        let mut inner = proc.inner_exclusive_access();
-        let fd = inner.open_file(inode);
-        fd as isize
-    } else {
-        -1
+    let Some(flags) = OpenFlags::from_bits(flags) else {
+        return -1;
+    };
+    let cloexec = flags.contains(OpenFlags::CLOEXEC);
+    match vfs::open(&path, flags) {
+        Ok(file) => {
+            let mut inner = proc.inner_exclusive_access();
+            match inner.open_file(file, cloexec) {
+                Some(fd) => fd as isize,
+                None => -EMFILE,
+            }
+        }
+        Err(errno) => errno,
     }
 }
 
@@ -72,32 +102,785 @@ pub fn sys_close(fd: usize) -> isize {
     let proc = current_process();
     let mut inner = proc.inner_exclusive_access();
     if fd < inner.fd_table.len() && inner.fd_table[fd].is_some() {
-        inner.fd_table[fd].take();
+        // The closed file may wake up other processes when it is dropped.
+        let file = inner.fd_table[fd].take();
+        drop(inner);
+        drop(file);
         0
     } else {
         -1
     }
 }
 
-pub fn sys_pipe(pipe: *mut usize) -> isize {
+/// Create a pipe.
+/// # Parameter
+/// * 'pipe' - user array of two fds, the read end and the write end
+/// * 'flags' - O_NONBLOCK and O_CLOEXEC are supported
+/// # Return
+/// * -1 - unsupported flags
+/// * -EMFILE - all fds are in use
+pub fn sys_pipe(pipe: *mut usize, flags: u32) -> isize {
     let token = current_user_token();
     let proc = current_process();
+    if flags & !(OpenFlags::NONBLOCK.bits() | OpenFlags::CLOEXEC.bits()) != 0 {
+        return -1;
+    }
+    let flags = OpenFlags::from_bits_truncate(flags);
     let (read_end, write_end) = create_pipe();
+    if flags.contains(OpenFlags::NONBLOCK) {
+        read_end.set_nonblock(true);
+        write_end.set_nonblock(true);
+    }
+    let cloexec = flags.contains(OpenFlags::CLOEXEC);
     let mut task_inner = proc.inner_exclusive_access();
-    let read_end_fd = task_inner.open_file(read_end);
-    let write_end_fd = task_inner.open_file(write_end);
+    let Some(read_end_fd) = task_inner.open_file(read_end, cloexec) else {
+        return -EMFILE;
+    };
+    let Some(write_end_fd) = task_inner.open_file(write_end, cloexec) else {
+        task_inner.fd_table[read_end_fd] = None;
+        return -EMFILE;
+    };
     *PageTable::from_token(token).translated_refmut(pipe) = read_end_fd;
     *PageTable::from_token(token).translated_refmut(unsafe { pipe.add(1) }) = write_end_fd;
     0
 }
 
+/// Duplicate the fd to the lowest free fd, FD_CLOEXEC of the new fd is cleared.
+/// # Return
+/// * -1 - bad fd
+/// * -EMFILE - all fds are in use
 pub fn sys_dup(fd: usize) -> isize {
     let proc = current_process();
     let mut inner = proc.inner_exclusive_access();
-    if fd >= inner.fd_table.len() || inner.fd_table[fd].is_none() {
+    let Some(file) = inner.get_file(fd) else {
         return -1;
+    };
+    match inner.open_file(file, false) {
+        Some(new_fd) => new_fd as isize,
+        None => -EMFILE,
+    }
+}
+
+/// Duplicate old_fd to new_fd, the file opened on new_fd is closed first.
+/// # Parameter
+/// * 'flags' - only O_CLOEXEC is supported, it sets FD_CLOEXEC of new_fd
+/// # Return
+/// * -EBADF - old_fd is not open, or new_fd is out of range
+/// * -EINVAL - old_fd equals new_fd, or unsupported flags
+pub fn sys_dup3(old_fd: usize, new_fd: usize, flags: u32) -> isize {
+    if old_fd == new_fd || flags & !OpenFlags::CLOEXEC.bits() != 0 {
+        return -EINVAL;
     }
-    let fd_clone = Arc::clone(inner.fd_table[fd].as_ref().unwrap());
-    let new_fd = inner.open_file(fd_clone);
+    let proc = current_process();
+    let mut inner = proc.inner_exclusive_access();
+    let Some(file) = inner.get_file(old_fd) else {
+        return -EBADF;
+    };
+    if new_fd >= FD_MAX {
+        return -EBADF;
+    }
+    let cloexec = flags & OpenFlags::CLOEXEC.bits() != 0;
+    let old_entry = inner.fd_table.get_mut(new_fd).and_then(Option::take);
+    inner.set_fd(new_fd, FdEntry::new(file, cloexec));
+    // The replaced file may wake up other processes when it is dropped.
+    drop(inner);
+    drop(old_entry);
     new_fd as isize
 }
+
+/// Duplicate the fd to the lowest free fd not less than arg.
+const F_DUPFD: usize = 0;
+/// Get the fd flags.
+const F_GETFD: usize = 1;
+/// Set the fd flags.
+const F_SETFD: usize = 2;
+/// Get the access mode and status flags of the open file.
+const F_GETFL: usize = 3;
+/// Set the status flags of the open file, only O_NONBLOCK can be changed.
+const F_SETFL: usize = 4;
+/// F_DUPFD with FD_CLOEXEC set on the new fd.
+const F_DUPFD_CLOEXEC: usize = 1030;
+/// The only fd flag, close the fd in exec.
+const FD_CLOEXEC: usize = 1;
+
+/// Control the fd or the open file.
+/// # Parameter
+/// * 'cmd' - F_DUPFD, F_DUPFD_CLOEXEC, F_GETFD, F_SETFD, F_GETFL or F_SETFL
+/// # Return
+/// * -EBADF - bad fd
+/// * -EINVAL - unsupported cmd, or the argument is out of range
+/// * -EMFILE - no free fd for F_DUPFD
+pub fn sys_fcntl(fd: usize, cmd: usize, arg: usize) -> isize {
+    let proc = current_process();
+    let mut inner = proc.inner_exclusive_access();
+    let Some(Some(entry)) = inner.fd_table.get_mut(fd) else {
+        return -EBADF;
+    };
+    match cmd {
+        F_GETFD => {
+            if entry.cloexec {
+                FD_CLOEXEC as isize
+            } else {
+                0
+            }
+        }
+        F_SETFD => {
+            entry.cloexec = arg & FD_CLOEXEC != 0;
+            0
+        }
+        F_GETFL => {
+            let file = &entry.file;
+            let mut flags = match (file.readable(), file.writable()) {
+                (true, true) => OpenFlags::READ_WRITE,
+                (false, true) => OpenFlags::WRITE_ONLY,
+                _ => OpenFlags::READ_ONLY,
+            };
+            if file.nonblock() {
+                flags |= OpenFlags::NONBLOCK;
+            }
+            flags.bits() as isize
+        }
+        F_SETFL => {
+            let nonblock = arg & OpenFlags::NONBLOCK.bits() as usize != 0;
+            // Files which can not be non-blocking keep blocking.
+            entry.file.set_nonblock(nonblock);
+            0
+        }
+        F_DUPFD | F_DUPFD_CLOEXEC => {
+            if arg >= FD_MAX {
+                return -EINVAL;
+            }
+            let file = entry.file.clone();
+            let Some(new_fd) = inner.alloc_fd(arg) else {
+                return -EMFILE;
+            };
+            inner.set_fd(new_fd, FdEntry::new(file, cmd == F_DUPFD_CLOEXEC));
+            new_fd as isize
+        }
+        _ => -EINVAL,
+    }
+}
+
+/// Device specific control of an open file.
+/// # Parameter
+/// * 'fd' - file descriptor
+/// * 'cmd' - request code, such as TCGETS/TCSETS/TIOCGPGRP/TIOCSPGRP for terminal
+/// * 'arg' - request argument, usually a user pointer
+/// # Return
+/// * -1 - bad fd or unsupported request
+pub fn sys_ioctl(fd: usize, cmd: usize, arg: usize) -> isize {
+    let proc = current_process();
+    let inner = proc.inner_exclusive_access();
+    if let Some(file) = inner.get_file(fd) {
+        drop(inner);
+        file.ioctl(cmd, arg)
+    } else {
+        -1
+    }
+}
+
+/// Read entries of an opened directory.
+/// # Parameter
+/// * 'fd' - file descriptor of the directory
+/// * 'buf' - user buffer to be filled with struct dirent64
+/// * 'len' - the length of buf
+/// # Return
+/// * -1 - bad fd, not a directory, or buf is too small
+/// * 0 - the end of directory
+/// * the number of bytes filled
+pub fn sys_getdents64(fd: usize, buf: *const u8, len: usize) -> isize {
+    let token = current_user_token();
+    let proc = current_process();
+    let inner = proc.inner_exclusive_access();
+    if let Some(file) = inner.get_file(fd) {
+        drop(inner);
+        file.getdents(UserBuffer::new(translate_byte_buffer(token, buf, len)))
+    } else {
+        -1
+    }
+}
+
+/// Only remove directories in unlinkat.
+const AT_REMOVEDIR: u32 = 0x200;
+/// The dirfd of the *at syscalls for the current directory, the only one supported.
+const AT_FDCWD: isize = -100;
+
+/// Create a special file, only named pipes are supported.
+/// # Parameter
+/// * 'dirfd' - must be AT_FDCWD, all paths are relative to the root directory
+/// * 'mode' - file type and permission, the type must be S_IFIFO and the permission is ignored
+/// * 'dev' - ignored, it is only used by device files
+/// # Return
+/// * -1 - the parent directory does not exist, the name exists,
+///   or the file system does not support named pipes
+/// * -EINVAL - the file type is not S_IFIFO, or dirfd is not AT_FDCWD
+#[allow(unused)]
+pub fn sys_mknodat(dirfd: isize, path: *const u8, mode: u32, dev: usize) -> isize {
+    if dirfd != AT_FDCWD || mode & S_IFMT != S_IFIFO {
+        return -EINVAL;
+    }
+    let path = PageTable::from_token(current_user_token()).translated_str(path);
+    vfs::mkfifo(&path)
+}
+
+/// Create a directory.
+/// # Parameter
+/// * 'dirfd' - must be AT_FDCWD, all paths are relative to the root directory
+/// * 'path' - the directory path
+/// * 'mode' - ignored
+/// # Return
+/// * -1 - the parent directory does not exist or the name exists
+/// * -EINVAL - dirfd is not AT_FDCWD
+#[allow(unused)]
+pub fn sys_mkdirat(dirfd: isize, path: *const u8, mode: u32) -> isize {
+    if dirfd != AT_FDCWD {
+        return -EINVAL;
+    }
+    let path = PageTable::from_token(current_user_token()).translated_str(path);
+    vfs::mkdir(&path)
+}
+
+/// Remove a file, or an empty directory if flags contains AT_REMOVEDIR.
+/// # Parameter
+/// * 'dirfd' - must be AT_FDCWD, all paths are relative to the root directory
+/// # Return
+/// * -1 - the file does not exist, the type does not match, or it is a mount point
+/// * -EINVAL - dirfd is not AT_FDCWD
+pub fn sys_unlinkat(dirfd: isize, path: *const u8, flags: u32) -> isize {
+    if dirfd != AT_FDCWD {
+        return -EINVAL;
+    }
+    let path = PageTable::from_token(current_user_token()).translated_str(path);
+    vfs::unlink(&path, flags & AT_REMOVEDIR != 0)
+}
+
+/// Mount a file system on a directory.
+/// # Parameter
+/// * 'source' - ignored, the file systems have no backing device to choose
+/// * 'target' - an existing directory which is not a mount point
+/// * 'fstype' - "easy-fs", "proc", "devfs" or "tmpfs"
+/// * 'flags' - ignored
+/// * 'data' - options string, such as "size=1m" of tmpfs, it can be null
+/// # Return
+/// * -1 - unknown file system type, bad options, or the target can not be mounted on
+#[allow(unused)]
+pub fn sys_mount(
+    source: *const u8,
+    target: *const u8,
+    fstype: *const u8,
+    flags: usize,
+    data: *const u8,
+) -> isize {
+    let page_table = PageTable::from_token(current_user_token());
+    let target = page_table.translated_str(target);
+    let fstype = page_table.translated_str(fstype);
+    let data = if data.is_null() {
+        String::new()
+    } else {
+        page_table.translated_str(data)
+    };
+    vfs::mount(&fstype, &target, &data)
+}
+
+/// Unmount the file system mounted on the target.
+/// # Parameter
+/// * 'flags' - ignored
+/// # Return
+/// * -1 - the target is not a mount point, it is "/", or other file systems are mounted under it
+#[allow(unused)]
+pub fn sys_umount2(target: *const u8, flags: u32) -> isize {
+    let target = PageTable::from_token(current_user_token()).translated_str(target);
+    vfs::umount(&target)
+}
+
+/// Change the size of a file by path.
+/// # Return
+/// * -1 - the file does not exist, it is a directory, or the size is not supported
+pub fn sys_truncate(path: *const u8, length: usize) -> isize {
+    let path = PageTable::from_token(current_user_token()).translated_str(path);
+    vfs::truncate(&path, length)
+}
+
+/// Change the size of an open file.
+/// # Return
+/// * -1 - bad fd, the file is not opened for writing, or the size is not supported
+pub fn sys_ftruncate(fd: usize, length: usize) -> isize {
+    let proc = current_process();
+    let inner = proc.inner_exclusive_access();
+    let Some(file) = inner.get_file(fd) else {
+        return -1;
+    };
+    drop(inner);
+    file.truncate(length)
+}
+
+/// Get the status of an open file.
+/// # Return
+/// * -1 - bad fd, or the file is not in any file system
+pub fn sys_fstat(fd: usize, stat: *mut Stat) -> isize {
+    let token = current_user_token();
+    let proc = current_process();
+    let inner = proc.inner_exclusive_access();
+    let Some(file) = inner.get_file(fd) else {
+        return -1;
+    };
+    drop(inner);
+    match file.stat() {
+        Some(file_stat) => {
+            copy_to_user(token, stat, &file_stat);
+            0
+        }
+        None => -1,
+    }
+}
+
+/// Get the status of a file by path.
+/// # Parameter
+/// * 'dirfd' - must be AT_FDCWD, all paths are relative to the root directory
+/// * 'flags' - ignored
+/// # Return
+/// * -1 - the file does not exist
+/// * -EINVAL - dirfd is not AT_FDCWD
+#[allow(unused)]
+pub fn sys_newfstatat(dirfd: isize, path: *const u8, stat: *mut Stat, flags: u32) -> isize {
+    if dirfd != AT_FDCWD {
+        return -EINVAL;
+    }
+    let token = current_user_token();
+    let path = PageTable::from_token(token).translated_str(path);
+    match vfs::lookup(&path) {
+        Some(node) => {
+            copy_to_user(token, stat, &node.stat());
+            0
+        }
+        None => -1,
+    }
+}
+
+/// An fd and the events to wait for, the same layout as linux struct pollfd.
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct PollFd {
+    pub fd: i32,
+    pub events: i16,
+    pub revents: i16,
+}
+
+/// Read the timeout of ppoll and pselect.
+/// # Return
+/// * None - the pointer is null, wait forever.
+fn read_timeout(token: usize, timeout: *const TimeSpec) -> Option<usize> {
+    if timeout.is_null() {
+        None
+    } else {
+        Some(copy_from_user(token, timeout).to_ms())
+    }
+}
+
+/// Wait until any of the fds is ready.
+/// # Parameter
+/// * 'fds' - user array of struct pollfd, revents is filled, negative fds are ignored
+/// * 'timeout' - null waits forever
+/// * 'sigmask' - ignored, signals can not be blocked
+/// # Return
+/// * the number of fds with non-zero revents, 0 if the timeout expired
+/// * -EINVAL - nfds is larger than FD_MAX
+/// * -EINTR - interrupted by a signal
+#[allow(unused)]
+pub fn sys_ppoll(
+    fds: *mut PollFd,
+    nfds: usize,
+    timeout: *const TimeSpec,
+    sigmask: *const u8,
+) -> isize {
+    if nfds > FD_MAX {
+        return -EINVAL;
+    }
+    let token = current_user_token();
+    let timeout_ms = read_timeout(token, timeout);
+    let poll_fds: Vec<PollFd> = (0..nfds)
+        .map(|i| copy_from_user(token, unsafe { fds.add(i) }))
+        .collect();
+    // Indexes in poll_fds of the files waited for.
+    let mut indexes = Vec::new();
+    let mut files = Vec::new();
+    let proc = current_process();
+    let inner = proc.inner_exclusive_access();
+    for (i, poll_fd) in poll_fds.iter().enumerate() {
+        if poll_fd.fd >= 0 {
+            let events = PollEvents::from_bits_truncate(poll_fd.events as u16);
+            indexes.push(i);
+            files.push((inner.get_file(poll_fd.fd as usize), events));
+        }
+    }
+    drop(inner);
+    let revents = match poll::wait(&files, timeout_ms) {
+        Ok(revents) => revents,
+        Err(errno) => return errno,
+    };
+    for (i, mut poll_fd) in poll_fds.into_iter().enumerate() {
+        poll_fd.revents = indexes
+            .iter()
+            .position(|index| *index == i)
+            .map_or(0, |pos| revents[pos].bits() as i16);
+        copy_to_user(token, unsafe { fds.add(i) }, &poll_fd);
+    }
+    revents.iter().filter(|events| !events.is_empty()).count() as isize
+}
+
+/// Bits of an fd set in a word.
+const FD_SET_WORD_BITS: usize = usize::BITS as usize;
+
+/// Read the words of a user fd set holding the first nfds bits, a null set is empty.
+fn read_fd_set(token: usize, set: *mut usize, nfds: usize) -> Vec<usize> {
+    let words = nfds.div_ceil(FD_SET_WORD_BITS);
+    if set.is_null() {
+        return alloc::vec![0; words];
+    }
+    (0..words)
+        .map(|i| copy_from_user(token, unsafe { set.add(i) }))
+        .collect()
+}
+
+fn write_fd_set(token: usize, set: *mut usize, words: &[usize]) {
+    if set.is_null() {
+        return;
+    }
+    for (i, word) in words.iter().enumerate() {
+        copy_to_user(token, unsafe { set.add(i) }, word);
+    }
+}
+
+fn fd_isset(words: &[usize], fd: usize) -> bool {
+    words[fd / FD_SET_WORD_BITS] & (1 << (fd % FD_SET_WORD_BITS)) != 0
+}
+
+/// Wait until any of the fds in the sets is ready, the sets are replaced by the ready fds.
+/// # Parameter
+/// * 'nfds' - the highest fd in the sets plus one
+/// * 'read_fds', 'write_fds', 'except_fds' - user fd sets, can be null
+/// * 'timeout' - null waits forever
+/// * 'sigmask' - ignored, signals can not be blocked
+/// # Return
+/// * the number of ready fds in all sets, 0 if the timeout expired
+/// * -EBADF - a fd in the sets is not open
+/// * -EINVAL - nfds is larger than FD_MAX
+/// * -EINTR - interrupted by a signal
+#[allow(unused)]
+pub fn sys_pselect6(
+    nfds: usize,
+    read_fds: *mut usize,
+    write_fds: *mut usize,
+    except_fds: *mut usize,
+    timeout: *const TimeSpec,
+    sigmask: *const u8,
+) -> isize {
+    if nfds > FD_MAX {
+        return -EINVAL;
+    }
+    let token = current_user_token();
+    let timeout_ms = read_timeout(token, timeout);
+    let user_sets = [read_fds, write_fds, except_fds];
+    let sets = user_sets.map(|set| read_fd_set(token, set, nfds));
+    let set_events = [PollEvents::POLLIN, PollEvents::POLLOUT, PollEvents::POLLPRI];
+    let mut fds = Vec::new();
+    let mut files: Vec<(Option<Arc<dyn File + Send + Sync>>, PollEvents)> = Vec::new();
+    let proc = current_process();
+    let inner = proc.inner_exclusive_access();
+    for fd in 0..nfds {
+        let mut events = PollEvents::empty();
+        for (set, set_event) in sets.iter().zip(set_events.iter()) {
+            if fd_isset(set, fd) {
+                events |= *set_event;
+            }
+        }
+        if events.is_empty() {
+            continue;
+        }
+        let Some(file) = inner.get_file(fd) else {
+            return -EBADF;
+        };
+        fds.push(fd);
+        files.push((Some(file), events));
+    }
+    drop(inner);
+    let revents = match poll::wait(&files, timeout_ms) {
+        Ok(revents) => revents,
+        Err(errno) => return errno,
+    };
+    // Errors and hang up make the fd readable and writable, reading or writing reports them.
+    let ready_events = [
+        PollEvents::POLLIN | PollEvents::POLLHUP | PollEvents::POLLERR,
+        PollEvents::POLLOUT | PollEvents::POLLERR,
+        PollEvents::POLLPRI,
+    ];
+    let mut count = 0;
+    for ((set, user_set), ready) in sets.iter().zip(user_sets).zip(ready_events) {
+        let mut ready_set = alloc::vec![0usize; set.len()];
+        for (fd, events) in fds.iter().zip(revents.iter()) {
+            if fd_isset(set, *fd) && events.intersects(ready) {
+                ready_set[fd / FD_SET_WORD_BITS] |= 1 << (fd % FD_SET_WORD_BITS);
+                count += 1;
+            }
+        }
+        write_fd_set(token, user_set, &ready_set);
+    }
+    count
+}
+
+/// Add a fd to the interest list of epoll.
+const EPOLL_CTL_ADD: usize = 1;
+/// Remove a fd from the interest list of epoll.
+const EPOLL_CTL_DEL: usize = 2;
+/// Change the events of a fd in the interest list of epoll.
+const EPOLL_CTL_MOD: usize = 3;
+
+/// Create an epoll instance.
+/// # Parameter
+/// * 'flags' - only EPOLL_CLOEXEC is supported, the same as O_CLOEXEC
+/// # Return
+/// * the epoll fd
+/// * -EINVAL - unsupported flags
+/// * -EMFILE - all fds are in use
+pub fn sys_epoll_create1(flags: u32) -> isize {
+    if flags & !OpenFlags::CLOEXEC.bits() != 0 {
+        return -EINVAL;
+    }
+    let cloexec = flags & OpenFlags::CLOEXEC.bits() != 0;
+    let proc = current_process();
+    let mut inner = proc.inner_exclusive_access();
+    match inner.open_file(Arc::new(Epoll::new()), cloexec) {
+        Some(fd) => fd as isize,
+        None => -EMFILE,
+    }
+}
+
+/// Add, modify or remove a fd in the interest list of the epoll instance.
+/// # Parameter
+/// * 'op' - EPOLL_CTL_ADD, EPOLL_CTL_MOD or EPOLL_CTL_DEL
+/// * 'event' - the events and user data, ignored by EPOLL_CTL_DEL
+/// # Return
+/// * -EBADF - epfd or fd is not open
+/// * -EINVAL - epfd is not an epoll fd, fd is epfd, or unsupported op
+/// * -EEXIST - adding a fd which is already added
+/// * -ENOENT - modifying or removing a fd which is not added
+/// * -EPERM - the file does not support epoll, such as a regular file
+pub fn sys_epoll_ctl(epfd: usize, op: usize, fd: usize, event: *const EpollEvent) -> isize {
+    let token = current_user_token();
+    let proc = current_process();
+    let inner = proc.inner_exclusive_access();
+    let (Some(epoll_file), Some(file)) = (inner.get_file(epfd), inner.get_file(fd)) else {
+        return -EBADF;
+    };
+    drop(inner);
+    let Some(epoll) = epoll_file
+        .as_any()
+        .and_then(|any| any.downcast_ref::<Epoll>())
+    else {
+        return -EINVAL;
+    };
+    if epfd == fd {
+        return -EINVAL;
+    }
+    match op {
+        EPOLL_CTL_ADD => epoll.add(fd, &file, copy_from_user(token, event)),
+        EPOLL_CTL_MOD => epoll.modify(fd, copy_from_user(token, event)),
+        EPOLL_CTL_DEL => epoll.delete(fd),
+        _ => -EINVAL,
+    }
+}
+
+/// Wait for events of the epoll instance.
+/// # Parameter
+/// * 'events' - user array to be filled with the events
+/// * 'max_events' - the length of the array
+/// * 'timeout' - in milliseconds, negative waits forever
+/// * 'sigmask' - ignored, signals can not be blocked
+/// # Return
+/// * the number of events, 0 if the timeout expired
+/// * -EBADF - epfd is not open
+/// * -EINVAL - epfd is not an epoll fd, or max_events is not positive
+/// * -EINTR - interrupted by a signal
+#[allow(unused)]
+pub fn sys_epoll_pwait(
+    epfd: usize,
+    events: *mut EpollEvent,
+    max_events: isize,
+    timeout: isize,
+    sigmask: *const u8,
+) -> isize {
+    let token = current_user_token();
+    let Some(epoll_file) = current_process().inner_exclusive_access().get_file(epfd) else {
+        return -EBADF;
+    };
+    let Some(epoll) = epoll_file
+        .as_any()
+        .and_then(|any| any.downcast_ref::<Epoll>())
+    else {
+        return -EINVAL;
+    };
+    if max_events <= 0 {
+        return -EINVAL;
+    }
+    let timeout_ms = if timeout < 0 {
+        None
+    } else {
+        Some(timeout as usize)
+    };
+    match epoll.wait(max_events as usize, timeout_ms) {
+        Ok(ready) => {
+            for (i, event) in ready.iter().enumerate() {
+                copy_to_user(token, unsafe { events.add(i) }, event);
+            }
+            ready.len() as isize
+        }
+        Err(errno) => errno,
+    }
+}
+
+/// Open a file which is not in any file system, such as an eventfd.
+/// # Parameter
+/// * 'flags' - the flags of the file type, only CLOEXEC is used here
+/// # Return
+/// * the new fd
+/// * -EMFILE - no fd can be allocated
+pub fn open_anon_file(file: Arc<dyn File + Send + Sync>, flags: u32) -> isize {
+    let cloexec = flags & OpenFlags::CLOEXEC.bits() != 0;
+    let proc = current_process();
+    let mut inner = proc.inner_exclusive_access();
+    match inner.open_file(file, cloexec) {
+        Some(fd) => fd as isize,
+        None => -EMFILE,
+    }
+}
+
+/// Create an eventfd.
+/// # Parameter
+/// * 'initval' - the initial value of the counter
+/// * 'flags' - EFD_SEMAPHORE, EFD_NONBLOCK and EFD_CLOEXEC
+/// # Return
+/// * the new fd
+/// * -EINVAL - unsupported flags
+/// * -EMFILE - no fd can be allocated
+pub fn sys_eventfd2(initval: u32, flags: u32) -> isize {
+    let Some(flags) = EventfdFlags::from_bits(flags) else {
+        return -EINVAL;
+    };
+    open_anon_file(Arc::new(Eventfd::new(initval, flags)), flags.bits())
+}
+
+/// Create a signalfd, or change the mask of a signalfd.
+/// # Parameter
+/// * 'fd' - -1 to create a new signalfd
+/// * 'mask' - linux sigset_t, bit (signum - 1) is the signal signum
+/// * 'sizemask' - the size of the mask, 8 bytes
+/// * 'flags' - SFD_NONBLOCK and SFD_CLOEXEC
+/// # Return
+/// * the fd
+/// * -EBADF - fd is not open
+/// * -EINVAL - fd is not a signalfd, wrong sizemask or unsupported flags
+/// * -EMFILE - no fd can be allocated
+pub fn sys_signalfd4(fd: isize, mask: *const u64, sizemask: usize, flags: u32) -> isize {
+    if sizemask != core::mem::size_of::<u64>() {
+        return -EINVAL;
+    }
+    let Some(flags) = SignalfdFlags::from_bits(flags) else {
+        return -EINVAL;
+    };
+    let mask = copy_from_user(current_user_token(), mask);
+    let mask = SignalFlags::from_bits_truncate((mask << 1) as u32);
+    if fd == -1 {
+        return open_anon_file(Arc::new(Signalfd::new(mask, flags)), flags.bits());
+    }
+    let Some(file) = current_process()
+        .inner_exclusive_access()
+        .get_file(fd as usize)
+    else {
+        return -EBADF;
+    };
+    match file.as_any().and_then(|any| any.downcast_ref::<Signalfd>()) {
+        Some(signalfd) => {
+            signalfd.set_mask(mask);
+            fd
+        }
+        None => -EINVAL,
+    }
+}
+
+/// Create a disarmed timerfd.
+/// # Parameter
+/// * 'clockid' - CLOCK_REALTIME or CLOCK_MONOTONIC, both count from the boot
+/// * 'flags' - TFD_NONBLOCK and TFD_CLOEXEC
+/// # Return
+/// * the new fd
+/// * -EINVAL - unsupported clock or flags
+/// * -EMFILE - no fd can be allocated
+pub fn sys_timerfd_create(clockid: usize, flags: u32) -> isize {
+    if clockid != CLOCK_REALTIME && clockid != CLOCK_MONOTONIC {
+        return -EINVAL;
+    }
+    let Some(flags) = TimerfdFlags::from_bits(flags) else {
+        return -EINVAL;
+    };
+    open_anon_file(Arc::new(Timerfd::new(flags)), flags.bits())
+}
+
+/// Call f with the timerfd of fd.
+/// # Return
+/// * the return value of f
+/// * -EBADF - fd is not open
+/// * -EINVAL - fd is not a timerfd
+fn with_timerfd(fd: usize, f: impl FnOnce(&Timerfd) -> isize) -> isize {
+    let Some(file) = current_process().inner_exclusive_access().get_file(fd) else {
+        return -EBADF;
+    };
+    match file.as_any().and_then(|any| any.downcast_ref::<Timerfd>()) {
+        Some(timerfd) => f(timerfd),
+        None => -EINVAL,
+    }
+}
+
+/// Arm or disarm the timerfd.
+/// # Parameter
+/// * 'flags' - TFD_TIMER_ABSTIME, the value of the new setting is the time since the boot
+/// * 'new_value' - the new setting, a zero value disarms the timer
+/// * 'old_value' - filled with the old setting if it is not null
+/// # Return
+/// * 0 - success
+/// * -EBADF - fd is not open
+/// * -EINVAL - fd is not a timerfd, unsupported flags or nsec out of range
+pub fn sys_timerfd_settime(
+    fd: usize,
+    flags: u32,
+    new_value: *const ITimerSpec,
+    old_value: *mut ITimerSpec,
+) -> isize {
+    let token = current_user_token();
+    with_timerfd(fd, |timerfd| {
+        if flags & !TFD_TIMER_ABSTIME != 0 {
+            return -EINVAL;
+        }
+        let setting = copy_from_user(token, new_value);
+        if setting.interval.nsec >= 1_000_000_000 || setting.value.nsec >= 1_000_000_000 {
+            return -EINVAL;
+        }
+        let old = timerfd.set(flags & TFD_TIMER_ABSTIME != 0, &setting);
+        if !old_value.is_null() {
+            copy_to_user(token, old_value, &old);
+        }
+        0
+    })
+}
+
+/// Get the time until the next expiration and the interval of the timerfd.
+/// # Return
+/// * 0 - success
+/// * -EBADF - fd is not open
+/// * -EINVAL - fd is not a timerfd
+pub fn sys_timerfd_gettime(fd: usize, curr_value: *mut ITimerSpec) -> isize {
+    let token = current_user_token();
+    with_timerfd(fd, |timerfd| {
+        copy_to_user(token, curr_value, &timerfd.get());
+        0
+    })
+}
@@ -0,0 +1,332 @@
+// System V and POSIX IPC mod
+
+use alloc::{sync::Arc, vec::Vec};
+
+use crate::{
+    config::{PAGE_SIZE, SHM_BASE, SHM_TOP},
+    fs::{
+        inode::OpenFlags,
+        mqueue::{self, MqAttr, MqDescriptor, MQ_NONBLOCK},
+        File,
+    },
+    mm::{
+        address::VirtAddr,
+        memory_set::MapPermission,
+        page_table::{
+            copy_bytes_to_user, copy_from_user, copy_to_user, translate_byte_buffer, PageTable,
+        },
+        shm::{ShmidDs, IPC_RMID, IPC_SET, IPC_STAT, SHM_MANAGER, SHM_RDONLY, SHM_RND},
+    },
+    syscall::errno::{EBADF, EINVAL, EMSGSIZE, ENOMEM},
+    task::processor::{current_process, current_user_token},
+    timer::TimeSpec,
+};
+
+use super::fs::open_anon_file;
+
+/// Get the shared memory segment of the key, or create a new segment.
+/// # Parameter
+/// * 'key' - IPC_PRIVATE always creates a new segment
+/// * 'size' - the size of the segment in bytes, it has whole pages
+/// * 'shmflg' - IPC_CREAT, IPC_EXCL, and the permission bits of the mode of a new segment
+/// # Return
+/// * The id of the segment.
+/// * -errno - see ShmManager::get
+pub fn sys_shmget(key: i32, size: usize, shmflg: usize) -> isize {
+    let pid = current_process().pid();
+    match SHM_MANAGER.lock().get(key, size, shmflg, pid) {
+        Ok(id) => id as isize,
+        Err(errno) => errno,
+    }
+}
+
+/// Attach the segment to the address space of the current process,
+/// the attachment is inherited by fork and detached by exec and exit.
+/// # Parameter
+/// * 'shmaddr' - page aligned address, or 0 to attach at a free address from SHM_BASE
+/// * 'shmflg' - SHM_RDONLY, and SHM_RND to round shmaddr down to a page
+/// # Return
+/// * The address of the attachment.
+/// * -EINVAL - no segment has the id, shmaddr is not aligned,
+///   or the segment at shmaddr overlaps mapped memory or is not between SHM_BASE and SHM_TOP
+/// * -EACCES - the mode of the segment does not allow the access
+/// * -ENOMEM - no free address for the segment
+pub fn sys_shmat(shmid: usize, shmaddr: usize, shmflg: usize) -> isize {
+    let segment = match SHM_MANAGER.lock().find(shmid) {
+        Ok(segment) => segment,
+        Err(errno) => return errno,
+    };
+    let readonly = shmflg & SHM_RDONLY != 0;
+    if let Err(errno) = segment.check_access(readonly) {
+        return errno;
+    }
+    let mut permission = MapPermission::R | MapPermission::U;
+    if !readonly {
+        permission |= MapPermission::W;
+    }
+    let shmaddr = match shmflg & SHM_RND != 0 {
+        true => shmaddr & !(PAGE_SIZE - 1),
+        false => shmaddr,
+    };
+    if shmaddr % PAGE_SIZE != 0 {
+        return -EINVAL;
+    }
+    let process = current_process();
+    let pid = process.pid();
+    let mut inner = process.inner_exclusive_access();
+    let len = segment.pages() * PAGE_SIZE;
+    let start = if shmaddr == 0 {
+        let free = inner
+            .memory_set
+            .find_free_area(SHM_BASE.into(), SHM_TOP.into(), len);
+        match free {
+            Some(start) => start,
+            None => return -ENOMEM,
+        }
+    } else if shmaddr >= SHM_BASE && shmaddr.checked_add(len).is_some_and(|end| end <= SHM_TOP) {
+        VirtAddr::from(shmaddr)
+    } else {
+        return -EINVAL;
+    };
+    if !inner
+        .memory_set
+        .insert_shared_area(start, &segment, permission)
+    {
+        return -EINVAL;
+    }
+    segment.attached(pid);
+    start.0 as isize
+}
+
+/// Detach the segment attached at the address.
+/// # Return
+/// * -EINVAL - no segment is attached at shmaddr
+pub fn sys_shmdt(shmaddr: usize) -> isize {
+    let process = current_process();
+    let pid = process.pid();
+    let mut inner = process.inner_exclusive_access();
+    let Some(attachment) = inner.memory_set.remove_shared_area(shmaddr.into()) else {
+        return -EINVAL;
+    };
+    drop(inner);
+    attachment.detach(pid);
+    0
+}
+
+/// Control the segment.
+/// # Parameter
+/// * 'cmd' - IPC_STAT writes the state to buf, IPC_SET sets the permission bits of the mode
+///   from buf, and IPC_RMID removes the segment
+/// # Return
+/// * -EINVAL - no segment has the id, or an unknown cmd
+pub fn sys_shmctl(shmid: usize, cmd: usize, buf: *mut ShmidDs) -> isize {
+    let token = current_user_token();
+    let mut manager = SHM_MANAGER.lock();
+    let segment = match manager.find(shmid) {
+        Ok(segment) => segment,
+        Err(errno) => return errno,
+    };
+    match cmd {
+        IPC_STAT => {
+            let stat = segment.stat();
+            drop(manager);
+            copy_to_user(token, buf, &stat);
+            0
+        }
+        IPC_SET => {
+            drop(manager);
+            let stat = copy_from_user(token, buf as *const ShmidDs);
+            segment.set_mode(stat.perm.mode);
+            0
+        }
+        IPC_RMID => match manager.remove(shmid) {
+            Ok(()) => 0,
+            Err(errno) => errno,
+        },
+        _ => -EINVAL,
+    }
+}
+
+/// Open a message queue, or create it.
+/// # Parameter
+/// * 'name' - '/' followed by a name without '/', queues are named globally
+/// * 'oflag' - the access mode, O_CREAT, O_EXCL, O_NONBLOCK and O_CLOEXEC
+/// * 'mode' - the permission bits of a new queue
+/// * 'attr' - maxmsg and msgsize of a new queue, null for the default
+/// # Return
+/// * The fd of the queue.
+/// * -EMFILE - no fd can be allocated
+/// * -errno - see mqueue::open
+pub fn sys_mq_open(name: *const u8, oflag: u32, mode: u32, attr: *const MqAttr) -> isize {
+    let token = current_user_token();
+    let name = PageTable::from_token(token).translated_str(name);
+    let flags = OpenFlags::from_bits_truncate(oflag);
+    // OpenFlags::read_write takes O_CREAT and O_EXCL as a write access.
+    let (readable, writable) = match oflag & 0b11 {
+        0 => (true, false),
+        1 => (false, true),
+        _ => (true, true),
+    };
+    let attr = match attr.is_null() {
+        true => None,
+        false => Some(copy_from_user(token, attr)),
+    };
+    let create = flags.contains(OpenFlags::CREATE);
+    let excl = flags.contains(OpenFlags::EXCL);
+    let queue = match mqueue::open(&name, readable, writable, create, excl, mode, attr) {
+        Ok(queue) => queue,
+        Err(errno) => return errno,
+    };
+    let nonblock = flags.contains(OpenFlags::NONBLOCK);
+    let descriptor = MqDescriptor::new(queue, readable, writable, nonblock);
+    open_anon_file(Arc::new(descriptor), oflag)
+}
+
+/// Remove the name of a message queue, opened descriptors still use the queue.
+/// # Return
+/// * -ENOENT - no queue has the name
+/// * -EINVAL - bad name
+pub fn sys_mq_unlink(name: *const u8) -> isize {
+    let name = PageTable::from_token(current_user_token()).translated_str(name);
+    match mqueue::unlink(&name) {
+        Ok(()) => 0,
+        Err(errno) => errno,
+    }
+}
+
+fn with_mqueue(mqdes: usize, f: impl FnOnce(&MqDescriptor) -> isize) -> isize {
+    let Some(file) = current_process().inner_exclusive_access().get_file(mqdes) else {
+        return -EBADF;
+    };
+    match file
+        .as_any()
+        .and_then(|any| any.downcast_ref::<MqDescriptor>())
+    {
+        Some(descriptor) => f(descriptor),
+        None => -EBADF,
+    }
+}
+
+/// Read the absolute timeout of a timed wait.
+/// # Return
+/// * Ok - the deadline in ms since boot, None for a null timeout
+/// * -EINVAL - nsec is not less than 1e9
+pub fn read_deadline(token: usize, abs_timeout: *const TimeSpec) -> Result<Option<usize>, isize> {
+    if abs_timeout.is_null() {
+        return Ok(None);
+    }
+    let timeout = copy_from_user(token, abs_timeout);
+    if timeout.nsec >= 1_000_000_000 {
+        return Err(-EINVAL);
+    }
+    Ok(Some(timeout.to_ms()))
+}
+
+/// Send a message, wait while the queue is full.
+/// # Parameter
+/// * 'msg_prio' - less than MQ_PRIO_MAX, a higher priority is received first
+/// * 'abs_timeout' - the time since boot to give up waiting, null waits forever
+/// # Return
+/// * -EBADF - mqdes is not a queue opened for writing
+/// * -EINVAL - abs_timeout is invalid
+/// * -errno - see MessageQueue::send
+pub fn sys_mq_timedsend(
+    mqdes: usize,
+    msg_ptr: *const u8,
+    msg_len: usize,
+    msg_prio: u32,
+    abs_timeout: *const TimeSpec,
+) -> isize {
+    let token = current_user_token();
+    with_mqueue(mqdes, |descriptor| {
+        if !descriptor.can_send() {
+            return -EBADF;
+        }
+        // Checked before reading the message from the user.
+        if msg_len > descriptor.queue().msgsize() {
+            return -EMSGSIZE;
+        }
+        let deadline = match read_deadline(token, abs_timeout) {
+            Ok(deadline) => deadline,
+            Err(errno) => return errno,
+        };
+        let msg: Vec<u8> = translate_byte_buffer(token, msg_ptr, msg_len).concat();
+        let nonblock = descriptor.nonblock();
+        match descriptor.queue().send(msg, msg_prio, nonblock, deadline) {
+            Ok(()) => 0,
+            Err(errno) => errno,
+        }
+    })
+}
+
+/// Receive the oldest message of the highest priority, wait while the queue is empty.
+/// # Parameter
+/// * 'msg_len' - the size of the buffer, at least msgsize of the queue
+/// * 'msg_prio' - filled with the priority of the message if it is not null
+/// * 'abs_timeout' - the time since boot to give up waiting, null waits forever
+/// # Return
+/// * The length of the message.
+/// * -EBADF - mqdes is not a queue opened for reading
+/// * -EMSGSIZE - msg_len is less than msgsize
+/// * -EINVAL - abs_timeout is invalid
+/// * -errno - see MessageQueue::receive
+pub fn sys_mq_timedreceive(
+    mqdes: usize,
+    msg_ptr: *mut u8,
+    msg_len: usize,
+    msg_prio: *mut u32,
+    abs_timeout: *const TimeSpec,
+) -> isize {
+    let token = current_user_token();
+    with_mqueue(mqdes, |descriptor| {
+        if !descriptor.can_receive() {
+            return -EBADF;
+        }
+        if msg_len < descriptor.queue().msgsize() {
+            return -EMSGSIZE;
+        }
+        let deadline = match read_deadline(token, abs_timeout) {
+            Ok(deadline) => deadline,
+            Err(errno) => return errno,
+        };
+        let nonblock = descriptor.nonblock();
+        match descriptor.queue().receive(nonblock, deadline) {
+            Ok((msg, prio)) => {
+                copy_bytes_to_user(token, msg_ptr, &msg);
+                if !msg_prio.is_null() {
+                    copy_to_user(token, msg_prio, &prio);
+                }
+                msg.len() as isize
+            }
+            Err(errno) => errno,
+        }
+    })
+}
+
+/// Get and set the attributes of a message queue descriptor.
+/// # Parameter
+/// * 'newattr' - only MQ_NONBLOCK of flags is set, ignored if it is null
+/// * 'oldattr' - filled with the attributes before the change if it is not null
+/// # Return
+/// * -EBADF - mqdes is not a queue
+/// * -EINVAL - newattr has flags other than MQ_NONBLOCK
+pub fn sys_mq_getsetattr(mqdes: usize, newattr: *const MqAttr, oldattr: *mut MqAttr) -> isize {
+    let token = current_user_token();
+    with_mqueue(mqdes, |descriptor| {
+        let mut attr = descriptor.queue().attr();
+        if descriptor.nonblock() {
+            attr.flags = MQ_NONBLOCK;
+        }
+        if !newattr.is_null() {
+            let new = copy_from_user(token, newattr);
+            if new.flags & !MQ_NONBLOCK != 0 {
+                return -EINVAL;
+            }
+            descriptor.set_nonblock(new.flags & MQ_NONBLOCK != 0);
+        }
+        if !oldattr.is_null() {
+            copy_to_user(token, oldattr, &attr);
+        }
+        0
+    })
+}
@@ -1,77 +1,332 @@
+const SYSCALL_EVENTFD2: usize = 19;
+const SYSCALL_EPOLL_CREATE1: usize = 20;
+const SYSCALL_EPOLL_CTL: usize = 21;
+const SYSCALL_EPOLL_PWAIT: usize = 22;
+// rCore uses 24 of linux dup3 for dup, so dup3 takes 23 of linux dup.
+const SYSCALL_DUP3: usize = 23;
 const SYSCALL_DUP: usize = 24;
+const SYSCALL_FCNTL: usize = 25;
+const SYSCALL_IOCTL: usize = 29;
+const SYSCALL_MKNODAT: usize = 33;
+const SYSCALL_MKDIRAT: usize = 34;
+const SYSCALL_UNLINKAT: usize = 35;
+const SYSCALL_UMOUNT2: usize = 39;
+const SYSCALL_MOUNT: usize = 40;
+const SYSCALL_TRUNCATE: usize = 45;
+const SYSCALL_FTRUNCATE: usize = 46;
 const SYSCALL_OPEN: usize = 56;
 const SYSCALL_CLOSE: usize = 57;
 const SYSCALL_PIPE: usize = 59;
+const SYSCALL_GETDENTS64: usize = 61;
 const SYSCALL_READ: usize = 63;
 const SYSCALL_WRITE: usize = 64;
+const SYSCALL_PSELECT6: usize = 72;
+const SYSCALL_PPOLL: usize = 73;
+const SYSCALL_SIGNALFD4: usize = 74;
+const SYSCALL_NEWFSTATAT: usize = 79;
+const SYSCALL_FSTAT: usize = 80;
+const SYSCALL_TIMERFD_CREATE: usize = 85;
+const SYSCALL_TIMERFD_SETTIME: usize = 86;
+const SYSCALL_TIMERFD_GETTIME: usize = 87;
 const SYSCALL_EXIT: usize = 93;
+const SYSCALL_FUTEX: usize = 98;
 const SYSCALL_SLEEP: usize = 101;
 const SYSCALL_YIELD: usize = 124;
 const SYSCALL_KILL: usize = 129;
+const SYSCALL_SIGACTION: usize = 134;
+const SYSCALL_SETPRIORITY: usize = 140;
+const SYSCALL_GETPRIORITY: usize = 141;
+const SYSCALL_TIMES: usize = 153;
+const SYSCALL_SETPGID: usize = 154;
+const SYSCALL_GETPGID: usize = 155;
+const SYSCALL_GETSID: usize = 156;
+const SYSCALL_SETSID: usize = 157;
+const SYSCALL_GETRUSAGE: usize = 165;
 const SYSCALL_GET_TIME: usize = 169;
 const SYSCALL_GET_PID: usize = 172;
+const SYSCALL_MQ_OPEN: usize = 180;
+const SYSCALL_MQ_UNLINK: usize = 181;
+const SYSCALL_MQ_TIMEDSEND: usize = 182;
+const SYSCALL_MQ_TIMEDRECEIVE: usize = 183;
+const SYSCALL_MQ_GETSETATTR: usize = 185;
+const SYSCALL_SHMGET: usize = 194;
+const SYSCALL_SHMCTL: usize = 195;
+const SYSCALL_SHMAT: usize = 196;
+const SYSCALL_SHMDT: usize = 197;
+const SYSCALL_SOCKET: usize = 198;
+const SYSCALL_SOCKETPAIR: usize = 199;
+const SYSCALL_BIND: usize = 200;
+const SYSCALL_LISTEN: usize = 201;
+const SYSCALL_ACCEPT: usize = 202;
+const SYSCALL_CONNECT: usize = 203;
+const SYSCALL_GETSOCKNAME: usize = 204;
+const SYSCALL_GETPEERNAME: usize = 205;
+const SYSCALL_SENDTO: usize = 206;
+const SYSCALL_RECVFROM: usize = 207;
+const SYSCALL_SHUTDOWN: usize = 210;
+const SYSCALL_SENDMSG: usize = 211;
+const SYSCALL_RECVMSG: usize = 212;
 const SYSCALL_FORK: usize = 220;
 const SYSCALL_EXEC: usize = 221;
+const SYSCALL_ACCEPT4: usize = 242;
 const SYSCALL_WAITPID: usize = 260;
 const SYSCALL_SPAWN: usize = 400;
-//const SYSCALL_TASK_INFO: usize = 410;
+const SYSCALL_TASK_INFO: usize = 410;
+const SYSCALL_ENABLE_DEADLOCK_DETECT: usize = 469;
 const SYSCALL_THREAD_CREATE: usize = 1000;
 const SYSCALL_GET_TID: usize = 1001;
 const SYSCALL_WAITTID: usize = 1002;
 const SYSCALL_MUTEX_CREATE: usize = 1010;
 const SYSCALL_MUTEX_LOCK: usize = 1011;
 const SYSCALL_MUTEX_UNLOCK: usize = 1012;
+const SYSCALL_MUTEX_TRYLOCK: usize = 1013;
+const SYSCALL_MUTEX_TIMEDLOCK: usize = 1014;
 const SYSCALL_SEMAPHORE_CREATE: usize = 1020;
 const SYSCALL_SEMAPHORE_UP: usize = 1021;
 const SYSCALL_SEMAPHORE_DOWN: usize = 1022;
+const SYSCALL_SEMAPHORE_TRYDOWN: usize = 1023;
+const SYSCALL_SEMAPHORE_TIMEDDOWN: usize = 1024;
 const SYSCALL_CONDVAR_CREATE: usize = 1030;
 const SYSCALL_CONDVAR_SIGNAL: usize = 1031;
 const SYSCALL_CONDVAR_WAIT: usize = 1032;
+const SYSCALL_CONDVAR_BROADCAST: usize = 1033;
+const SYSCALL_CONDVAR_TIMEDWAIT: usize = 1034;
+const SYSCALL_RWLOCK_CREATE: usize = 1040;
+const SYSCALL_RWLOCK_RDLOCK: usize = 1041;
+const SYSCALL_RWLOCK_WRLOCK: usize = 1042;
+const SYSCALL_RWLOCK_UPGRADE: usize = 1043;
+const SYSCALL_RWLOCK_UNLOCK: usize = 1044;
+const SYSCALL_BARRIER_CREATE: usize = 1050;
+const SYSCALL_BARRIER_WAIT: usize = 1051;
 
+pub mod errno;
 mod fs;
+mod ipc;
+mod net;
 mod process;
 mod sync;
 mod thread;
 
 use fs::*;
+use ipc::*;
+use net::*;
 use process::*;
 use sync::*;
 use thread::*;
 
+use crate::{
+    fs::{epoll::EpollEvent, mqueue::MqAttr, timerfd::ITimerSpec, vfs::Stat},
+    mm::shm::ShmidDs,
+    net::MsgHdr,
+    timer::TimeSpec,
+    task::{
+        action::SignalAction,
+        processor::current_process,
+        usage::{RUsage, Tms},
+    },
+};
+
 /// Call the real handler function by syscall id.
 /// # Parameter
 /// * 'syscall_id' - the system call id
 /// * 'args' - required parameters for system call functions
-pub fn syscall(syscall_id: usize, args: [usize; 3]) -> isize {
+pub fn syscall(syscall_id: usize, args: [usize; 6]) -> isize {
+    current_process()
+        .inner_exclusive_access()
+        .usage
+        .count_syscall(syscall_id);
     match syscall_id {
+        SYSCALL_EVENTFD2 => sys_eventfd2(args[0] as u32, args[1] as u32),
+        SYSCALL_EPOLL_CREATE1 => sys_epoll_create1(args[0] as u32),
+        SYSCALL_EPOLL_CTL => sys_epoll_ctl(args[0], args[1], args[2], args[3] as *const EpollEvent),
+        SYSCALL_EPOLL_PWAIT => sys_epoll_pwait(
+            args[0],
+            args[1] as *mut EpollEvent,
+            args[2] as isize,
+            args[3] as isize,
+            args[4] as *const u8,
+        ),
+        SYSCALL_DUP3 => sys_dup3(args[0], args[1], args[2] as u32),
         SYSCALL_DUP => sys_dup(args[0]),
+        SYSCALL_FCNTL => sys_fcntl(args[0], args[1], args[2]),
+        SYSCALL_IOCTL => sys_ioctl(args[0], args[1], args[2]),
+        SYSCALL_MKNODAT => sys_mknodat(
+            args[0] as isize,
+            args[1] as *const u8,
+            args[2] as u32,
+            args[3],
+        ),
+        SYSCALL_MKDIRAT => sys_mkdirat(args[0] as isize, args[1] as *const u8, args[2] as u32),
+        SYSCALL_UNLINKAT => sys_unlinkat(args[0] as isize, args[1] as *const u8, args[2] as u32),
+        SYSCALL_UMOUNT2 => sys_umount2(args[0] as *const u8, args[1] as u32),
+        SYSCALL_MOUNT => sys_mount(
+            args[0] as *const u8,
+            args[1] as *const u8,
+            args[2] as *const u8,
+            args[3],
+            args[4] as *const u8,
+        ),
+        SYSCALL_TRUNCATE => sys_truncate(args[0] as *const u8, args[1]),
+        SYSCALL_FTRUNCATE => sys_ftruncate(args[0], args[1]),
         SYSCALL_OPEN => sys_open(args[0] as *const u8, args[1] as u32),
         SYSCALL_CLOSE => sys_close(args[0]),
-        SYSCALL_PIPE => sys_pipe(args[0] as *mut usize),
+        SYSCALL_PIPE => sys_pipe(args[0] as *mut usize, args[1] as u32),
+        SYSCALL_GETDENTS64 => sys_getdents64(args[0], args[1] as *const u8, args[2]),
         SYSCALL_READ => sys_read(args[0], args[1] as *const u8, args[2]),
         SYSCALL_WRITE => sys_write(args[0], args[1] as *const u8, args[2]),
+        SYSCALL_PSELECT6 => sys_pselect6(
+            args[0],
+            args[1] as *mut usize,
+            args[2] as *mut usize,
+            args[3] as *mut usize,
+            args[4] as *const TimeSpec,
+            args[5] as *const u8,
+        ),
+        SYSCALL_PPOLL => sys_ppoll(
+            args[0] as *mut PollFd,
+            args[1],
+            args[2] as *const TimeSpec,
+            args[3] as *const u8,
+        ),
+        SYSCALL_SIGNALFD4 => sys_signalfd4(
+            args[0] as isize,
+            args[1] as *const u64,
+            args[2],
+            args[3] as u32,
+        ),
+        SYSCALL_NEWFSTATAT => sys_newfstatat(
+            args[0] as isize,
+            args[1] as *const u8,
+            args[2] as *mut Stat,
+            args[3] as u32,
+        ),
+        SYSCALL_FSTAT => sys_fstat(args[0], args[1] as *mut Stat),
+        SYSCALL_TIMERFD_CREATE => sys_timerfd_create(args[0], args[1] as u32),
+        SYSCALL_TIMERFD_SETTIME => sys_timerfd_settime(
+            args[0],
+            args[1] as u32,
+            args[2] as *const ITimerSpec,
+            args[3] as *mut ITimerSpec,
+        ),
+        SYSCALL_TIMERFD_GETTIME => sys_timerfd_gettime(args[0], args[1] as *mut ITimerSpec),
         SYSCALL_EXIT => sys_exit(args[0] as i32),
+        SYSCALL_FUTEX => sys_futex(
+            args[0],
+            args[1],
+            args[2] as u32,
+            args[3],
+            args[4],
+            args[5] as u32,
+        ),
         SYSCALL_SLEEP => sys_sleep(args[0]),
         SYSCALL_YIELD => sys_yield(),
-        SYSCALL_KILL => sys_kill(args[0], args[1] as u32),
+        SYSCALL_KILL => sys_kill(args[0] as isize, args[1] as u32),
+        SYSCALL_SIGACTION => sys_sigaction(
+            args[0] as u32,
+            args[1] as *const SignalAction,
+            args[2] as *mut SignalAction,
+        ),
+        SYSCALL_SETPRIORITY => sys_setpriority(args[0], args[1], args[2] as isize),
+        SYSCALL_GETPRIORITY => sys_getpriority(args[0], args[1]),
+        SYSCALL_TIMES => sys_times(args[0] as *mut Tms),
+        SYSCALL_SETPGID => sys_setpgid(args[0], args[1]),
+        SYSCALL_GETPGID => sys_getpgid(args[0]),
+        SYSCALL_GETSID => sys_getsid(args[0]),
+        SYSCALL_SETSID => sys_setsid(),
+        SYSCALL_GETRUSAGE => sys_getrusage(args[0] as isize, args[1] as *mut RUsage),
         SYSCALL_GET_TIME => sys_get_time(),
         SYSCALL_GET_PID => sys_get_pid(),
+        SYSCALL_MQ_OPEN => sys_mq_open(
+            args[0] as *const u8,
+            args[1] as u32,
+            args[2] as u32,
+            args[3] as *const MqAttr,
+        ),
+        SYSCALL_MQ_UNLINK => sys_mq_unlink(args[0] as *const u8),
+        SYSCALL_MQ_TIMEDSEND => sys_mq_timedsend(
+            args[0],
+            args[1] as *const u8,
+            args[2],
+            args[3] as u32,
+            args[4] as *const TimeSpec,
+        ),
+        SYSCALL_MQ_TIMEDRECEIVE => sys_mq_timedreceive(
+            args[0],
+            args[1] as *mut u8,
+            args[2],
+            args[3] as *mut u32,
+            args[4] as *const TimeSpec,
+        ),
+        SYSCALL_MQ_GETSETATTR => {
+            sys_mq_getsetattr(args[0], args[1] as *const MqAttr, args[2] as *mut MqAttr)
+        }
+        SYSCALL_SHMGET => sys_shmget(args[0] as i32, args[1], args[2]),
+        SYSCALL_SHMCTL => sys_shmctl(args[0], args[1], args[2] as *mut ShmidDs),
+        SYSCALL_SHMAT => sys_shmat(args[0], args[1], args[2]),
+        SYSCALL_SHMDT => sys_shmdt(args[0]),
+        SYSCALL_SOCKET => sys_socket(args[0], args[1], args[2]),
+        SYSCALL_SOCKETPAIR => sys_socketpair(args[0], args[1], args[2], args[3] as *mut i32),
+        SYSCALL_BIND => sys_bind(args[0], args[1] as *const u8, args[2] as u32),
+        SYSCALL_LISTEN => sys_listen(args[0], args[1]),
+        SYSCALL_ACCEPT => sys_accept4(args[0], args[1] as *mut u8, args[2] as *mut u32, 0),
+        SYSCALL_CONNECT => sys_connect(args[0], args[1] as *const u8, args[2] as u32),
+        SYSCALL_GETSOCKNAME => sys_getsockname(args[0], args[1] as *mut u8, args[2] as *mut u32),
+        SYSCALL_GETPEERNAME => sys_getpeername(args[0], args[1] as *mut u8, args[2] as *mut u32),
+        SYSCALL_SENDTO => sys_sendto(
+            args[0],
+            args[1] as *const u8,
+            args[2],
+            args[3] as u32,
+            args[4] as *const u8,
+            args[5] as u32,
+        ),
+        SYSCALL_RECVFROM => sys_recvfrom(
+            args[0],
+            args[1] as *mut u8,
+            args[2],
+            args[3] as u32,
+            args[4] as *mut u8,
+            args[5] as *mut u32,
+        ),
+        SYSCALL_SHUTDOWN => sys_shutdown(args[0], args[1]),
+        SYSCALL_SENDMSG => sys_sendmsg(args[0], args[1] as *const MsgHdr, args[2] as u32),
+        SYSCALL_RECVMSG => sys_recvmsg(args[0], args[1] as *mut MsgHdr, args[2] as u32),
         SYSCALL_FORK => sys_fork(),
         SYSCALL_EXEC => sys_exec(args[0] as *const u8, args[1] as *const usize),
+        SYSCALL_ACCEPT4 => sys_accept4(args[0], args[1] as *mut u8, args[2] as *mut u32, args[3]),
         SYSCALL_SPAWN => sys_spawn(args[0] as *const u8, args[1] as *const usize),
-        SYSCALL_WAITPID => sys_waitpid(args[0] as isize, args[1] as *mut i32),
+        SYSCALL_TASK_INFO => sys_task_info(args[0] as *mut u8),
+        SYSCALL_WAITPID => sys_waitpid(args[0] as isize, args[1] as *mut i32, args[2] as u32),
+        SYSCALL_ENABLE_DEADLOCK_DETECT => sys_enable_deadlock_detect(args[0]),
         SYSCALL_THREAD_CREATE => sys_thread_create(args[0], args[1]),
         SYSCALL_GET_TID => sys_get_tid(),
         SYSCALL_WAITTID => sys_waittid(args[0]),
-        SYSCALL_MUTEX_CREATE => sys_mutex_create(args[0] != 0),
+        SYSCALL_MUTEX_CREATE => sys_mutex_create(args[0]),
         SYSCALL_MUTEX_LOCK => sys_mutex_lock(args[0]),
         SYSCALL_MUTEX_UNLOCK => sys_mutex_unlock(args[0]),
+        SYSCALL_MUTEX_TRYLOCK => sys_mutex_trylock(args[0]),
+        SYSCALL_MUTEX_TIMEDLOCK => sys_mutex_timedlock(args[0], args[1] as *const TimeSpec),
         SYSCALL_SEMAPHORE_CREATE => sys_semaphore_create(args[0]),
         SYSCALL_SEMAPHORE_UP => sys_semaphore_up(args[0]),
         SYSCALL_SEMAPHORE_DOWN => sys_semaphore_down(args[0]),
+        SYSCALL_SEMAPHORE_TRYDOWN => sys_semaphore_trydown(args[0]),
+        SYSCALL_SEMAPHORE_TIMEDDOWN => {
+            sys_semaphore_timeddown(args[0], args[1] as *const TimeSpec)
+        }
         SYSCALL_CONDVAR_CREATE => sys_condvar_create(),
         SYSCALL_CONDVAR_SIGNAL => sys_condvar_signal(args[0]),
         SYSCALL_CONDVAR_WAIT => sys_condvar_wait(args[0], args[1]),
+        SYSCALL_CONDVAR_BROADCAST => sys_condvar_broadcast(args[0]),
+        SYSCALL_CONDVAR_TIMEDWAIT => {
+            sys_condvar_timedwait(args[0], args[1], args[2] as *const TimeSpec)
+        }
+        SYSCALL_RWLOCK_CREATE => sys_rwlock_create(),
+        SYSCALL_RWLOCK_RDLOCK => sys_rwlock_rdlock(args[0]),
+        SYSCALL_RWLOCK_WRLOCK => sys_rwlock_wrlock(args[0]),
+        SYSCALL_RWLOCK_UPGRADE => sys_rwlock_upgrade(args[0]),
+        SYSCALL_RWLOCK_UNLOCK => sys_rwlock_unlock(args[0]),
+        SYSCALL_BARRIER_CREATE => sys_barrier_create(args[0]),
+        SYSCALL_BARRIER_WAIT => sys_barrier_wait(args[0]),
         _ => panic!("Unsupported syscall_id: {}", syscall_id),
     }
 }
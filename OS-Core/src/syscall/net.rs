@@ -0,0 +1,538 @@
+// Socket mod
+
+use alloc::{string::String, sync::Arc, vec::Vec};
+use core::mem::size_of;
+
+use crate::{
+    fs::File,
+    mm::page_table::{
+        copy_bytes_to_user, copy_from_user, copy_to_user, translate_byte_buffer, UserBuffer,
+    },
+    net::{
+        tcp::TcpSocket,
+        udp::UdpSocket,
+        unix::{SocketType, UnixSocket, SCM_MAX_FD},
+        CmsgHdr, InetAddr, IoVec, MsgHdr, PassedFiles, SockAddr, SockaddrIn, SockaddrUn, Socket,
+        AF_INET, AF_UNIX, IPPROTO_TCP, IPPROTO_UDP, MSG_CMSG_CLOEXEC, MSG_CTRUNC, SCM_RIGHTS,
+        SHUT_RDWR, SOCK_CLOEXEC, SOCK_DGRAM, SOCK_NONBLOCK, SOCK_STREAM, SOCK_TYPE_MASK,
+        SOL_SOCKET, UIO_MAXIOV, UNIX_PATH_MAX,
+    },
+    syscall::errno::{EAFNOSUPPORT, EBADF, EINVAL, EMFILE, ENOTSOCK, EOPNOTSUPP},
+    task::processor::{current_process, current_user_token},
+};
+
+use super::fs::open_anon_file;
+
+/// Call f with the socket of fd.
+/// # Return
+/// * the return value of f
+/// * -EBADF - fd is not open
+/// * -ENOTSOCK - fd is not a socket
+fn with_socket(fd: usize, f: impl FnOnce(&dyn Socket) -> isize) -> isize {
+    let Some(file) = current_process().inner_exclusive_access().get_file(fd) else {
+        return -EBADF;
+    };
+    match file.as_socket() {
+        Some(socket) => f(socket),
+        None => -ENOTSOCK,
+    }
+}
+
+/// Read a sockaddr_un or a sockaddr_in of addrlen bytes.
+/// # Return
+/// * -EINVAL - wrong addrlen or family, or an empty path (abstract addresses are not supported)
+fn read_addr(token: usize, addr: *const u8, addrlen: u32) -> Result<SockAddr, isize> {
+    let addrlen = addrlen as usize;
+    if addrlen <= size_of::<u16>() || addrlen > size_of::<SockaddrUn>() {
+        return Err(-EINVAL);
+    }
+    let mut bytes = Vec::new();
+    for buffer in translate_byte_buffer(token, addr, addrlen) {
+        bytes.extend_from_slice(buffer);
+    }
+    match u16::from_ne_bytes([bytes[0], bytes[1]]) as usize {
+        AF_UNIX => read_path(&bytes[size_of::<u16>()..]).map(|path| SockAddr::Unix(Some(path))),
+        AF_INET if addrlen >= size_of::<SockaddrIn>() => Ok(SockAddr::Inet(InetAddr {
+            addr: [bytes[4], bytes[5], bytes[6], bytes[7]],
+            port: u16::from_be_bytes([bytes[2], bytes[3]]),
+        })),
+        _ => Err(-EINVAL),
+    }
+}
+
+/// The path of a sockaddr_un, it ends at the first '\0' or the end of the address.
+/// # Return
+/// * -EINVAL - an empty path, or a path which is too long
+fn read_path(path: &[u8]) -> Result<String, isize> {
+    let len = path
+        .iter()
+        .position(|byte| *byte == 0)
+        .unwrap_or(path.len());
+    if len == 0 || len >= UNIX_PATH_MAX {
+        return Err(-EINVAL);
+    }
+    Ok(path[..len].iter().map(|byte| *byte as char).collect())
+}
+
+/// Write the address as a sockaddr_un or a sockaddr_in, truncated to len bytes.
+/// The path of an unbound unix domain socket is empty, only the family is written.
+/// # Return
+/// * The whole length of the address.
+fn write_addr(token: usize, addr: *mut u8, len: usize, sockaddr: &SockAddr) -> u32 {
+    let path = match sockaddr {
+        SockAddr::Unix(path) => path.as_deref(),
+        SockAddr::Inet(inet) => {
+            let sockaddr = SockaddrIn {
+                family: AF_INET as u16,
+                port: inet.port.to_be(),
+                addr: inet.addr,
+                zero: [0; 8],
+            };
+            let bytes = unsafe {
+                core::slice::from_raw_parts(
+                    &sockaddr as *const _ as *const u8,
+                    size_of::<SockaddrIn>(),
+                )
+            };
+            copy_bytes_to_user(token, addr, &bytes[..bytes.len().min(len)]);
+            return bytes.len() as u32;
+        }
+    };
+    let mut sockaddr = SockaddrUn::default();
+    let path = path.unwrap_or_default().as_bytes();
+    sockaddr.path[..path.len()].copy_from_slice(path);
+    let addrlen = if path.is_empty() {
+        size_of::<u16>()
+    } else {
+        size_of::<u16>() + path.len() + 1
+    };
+    let bytes = unsafe {
+        core::slice::from_raw_parts(&sockaddr as *const _ as *const u8, size_of::<SockaddrUn>())
+    };
+    copy_bytes_to_user(token, addr, &bytes[..addrlen.min(len)]);
+    addrlen as u32
+}
+
+/// Write the address to addr if it is not null, *addrlen is the size of addr,
+/// and it is set to the whole length of the address, or 0 if there is no address.
+fn write_addr_len(token: usize, addr: *mut u8, addrlen: *mut u32, sockaddr: Option<&SockAddr>) {
+    if addr.is_null() {
+        return;
+    }
+    let len = match sockaddr {
+        Some(sockaddr) => {
+            let len = copy_from_user(token, addrlen) as usize;
+            write_addr(token, addr, len, sockaddr)
+        }
+        None => 0,
+    };
+    copy_to_user(token, addrlen, &len);
+}
+
+/// The user buffers of an iovec array as one buffer.
+fn iov_buffer(token: usize, iov: *const IoVec, iovlen: usize) -> UserBuffer {
+    let mut buffers = Vec::new();
+    for i in 0..iovlen {
+        let iov = copy_from_user(token, unsafe { iov.add(i) });
+        buffers.extend(translate_byte_buffer(token, iov.base as *const u8, iov.len));
+    }
+    UserBuffer::new(buffers)
+}
+
+/// The end of a control message, aligned to 8 bytes.
+fn cmsg_align(len: usize) -> usize {
+    (len + size_of::<usize>() - 1) & !(size_of::<usize>() - 1)
+}
+
+/// Get the open files of the fds in the SCM_RIGHTS messages, other control messages are ignored.
+/// # Return
+/// * -EINVAL - a broken control message, or more than SCM_MAX_FD fds
+/// * -EBADF - a fd is not open
+fn read_rights(token: usize, control: usize, controllen: usize) -> Result<PassedFiles, isize> {
+    let mut files = Vec::new();
+    let mut offset = 0;
+    while offset + size_of::<CmsgHdr>() <= controllen {
+        let cmsg = copy_from_user(token, (control + offset) as *const CmsgHdr);
+        if cmsg.len < size_of::<CmsgHdr>() || offset + cmsg.len > controllen {
+            return Err(-EINVAL);
+        }
+        if cmsg.level == SOL_SOCKET && cmsg.type_ == SCM_RIGHTS {
+            let fds = (control + offset + size_of::<CmsgHdr>()) as *const i32;
+            let count = (cmsg.len - size_of::<CmsgHdr>()) / size_of::<i32>();
+            if files.len() + count > SCM_MAX_FD {
+                return Err(-EINVAL);
+            }
+            let proc = current_process();
+            let inner = proc.inner_exclusive_access();
+            for i in 0..count {
+                let fd = copy_from_user(token, unsafe { fds.add(i) });
+                files.push(inner.get_file(fd as usize).ok_or(-EBADF)?);
+            }
+        }
+        offset += cmsg_align(cmsg.len);
+    }
+    Ok(files)
+}
+
+/// Open the received files in the fd table, and write their fds as an SCM_RIGHTS message.
+/// The files which do not fit in the control buffer or the fd table are closed.
+/// # Return
+/// * (the length of the control message, MSG_CTRUNC if some files are closed)
+fn write_rights(
+    token: usize,
+    control: usize,
+    controllen: usize,
+    files: PassedFiles,
+    cloexec: bool,
+) -> (usize, u32) {
+    if files.is_empty() {
+        return (0, 0);
+    }
+    let total = files.len();
+    let max = controllen.saturating_sub(size_of::<CmsgHdr>()) / size_of::<i32>();
+    let mut files = files.into_iter();
+    let mut fds = Vec::new();
+    let proc = current_process();
+    let mut inner = proc.inner_exclusive_access();
+    while fds.len() < max {
+        let Some(file) = files.next() else {
+            break;
+        };
+        match inner.open_file(file, cloexec) {
+            Some(fd) => fds.push(fd as i32),
+            None => break,
+        }
+    }
+    drop(inner);
+    let flags = if fds.len() < total { MSG_CTRUNC } else { 0 };
+    if fds.is_empty() {
+        return (0, flags);
+    }
+    let cmsg = CmsgHdr {
+        len: size_of::<CmsgHdr>() + fds.len() * size_of::<i32>(),
+        level: SOL_SOCKET,
+        type_: SCM_RIGHTS,
+    };
+    copy_to_user(token, control as *mut CmsgHdr, &cmsg);
+    let data = (control + size_of::<CmsgHdr>()) as *mut i32;
+    for (i, fd) in fds.iter().enumerate() {
+        copy_to_user(token, unsafe { data.add(i) }, fd);
+    }
+    (cmsg_align(cmsg.len).min(controllen), flags)
+}
+
+/// Check the arguments of socket and socketpair.
+/// # Return
+/// * (socket type, SOCK_NONBLOCK and SOCK_CLOEXEC)
+/// * -EAFNOSUPPORT - the domain is not AF_UNIX or AF_INET
+/// * -EINVAL - unsupported type, flags or protocol
+fn socket_args(domain: usize, type_: usize, protocol: usize) -> Result<(SocketType, usize), isize> {
+    if domain != AF_UNIX && domain != AF_INET {
+        return Err(-EAFNOSUPPORT);
+    }
+    let flags = type_ & !SOCK_TYPE_MASK;
+    if flags & !(SOCK_NONBLOCK | SOCK_CLOEXEC) != 0 {
+        return Err(-EINVAL);
+    }
+    match (type_ & SOCK_TYPE_MASK, domain, protocol) {
+        (SOCK_STREAM, AF_UNIX, 0) | (SOCK_STREAM, AF_INET, 0 | IPPROTO_TCP) => {
+            Ok((SocketType::Stream, flags))
+        }
+        (SOCK_DGRAM, AF_UNIX, 0) | (SOCK_DGRAM, AF_INET, 0 | IPPROTO_UDP) => {
+            Ok((SocketType::Dgram, flags))
+        }
+        _ => Err(-EINVAL),
+    }
+}
+
+/// Create an unbound socket.
+/// # Parameter
+/// * 'domain' - AF_UNIX or AF_INET
+/// * 'type_' - SOCK_STREAM or SOCK_DGRAM, with SOCK_NONBLOCK and SOCK_CLOEXEC
+/// * 'protocol' - 0, or IPPROTO_TCP and IPPROTO_UDP of AF_INET
+/// # Return
+/// * the new fd
+/// * -EAFNOSUPPORT - the domain is not AF_UNIX or AF_INET
+/// * -EINVAL - unsupported type, flags or protocol
+/// * -EMFILE - no fd can be allocated
+pub fn sys_socket(domain: usize, type_: usize, protocol: usize) -> isize {
+    let (socket_type, flags) = match socket_args(domain, type_, protocol) {
+        Ok(args) => args,
+        Err(errno) => return errno,
+    };
+    let socket: Arc<dyn File + Send + Sync> = match (domain, socket_type) {
+        (AF_UNIX, _) => Arc::new(UnixSocket::new(socket_type)),
+        (_, SocketType::Stream) => Arc::new(TcpSocket::new()),
+        (_, SocketType::Dgram) => Arc::new(UdpSocket::new()),
+    };
+    socket.set_nonblock(flags & SOCK_NONBLOCK != 0);
+    open_anon_file(socket, flags as u32)
+}
+
+/// Create a pair of connected unix domain sockets.
+/// # Parameter
+/// * 'sv' - user array of two fds
+/// # Return
+/// * -EOPNOTSUPP - the domain is AF_INET
+/// * -errno - see sys_socket
+pub fn sys_socketpair(domain: usize, type_: usize, protocol: usize, sv: *mut i32) -> isize {
+    let (socket_type, flags) = match socket_args(domain, type_, protocol) {
+        Ok(args) => args,
+        Err(errno) => return errno,
+    };
+    if domain != AF_UNIX {
+        return -EOPNOTSUPP;
+    }
+    let (a, b) = UnixSocket::pair(socket_type);
+    a.set_nonblock(flags & SOCK_NONBLOCK != 0);
+    b.set_nonblock(flags & SOCK_NONBLOCK != 0);
+    let cloexec = flags & SOCK_CLOEXEC != 0;
+    let proc = current_process();
+    let mut inner = proc.inner_exclusive_access();
+    let Some(fd_a) = inner.open_file(Arc::new(a), cloexec) else {
+        return -EMFILE;
+    };
+    let Some(fd_b) = inner.open_file(Arc::new(b), cloexec) else {
+        let a = inner.fd_table[fd_a].take();
+        drop(inner);
+        drop(a);
+        return -EMFILE;
+    };
+    drop(inner);
+    let token = current_user_token();
+    copy_to_user(token, sv, &(fd_a as i32));
+    copy_to_user(token, unsafe { sv.add(1) }, &(fd_b as i32));
+    0
+}
+
+/// Bind the socket to the address. A socket file is created at the path of a unix domain socket,
+/// and port 0 of an IPv4 socket is an ephemeral port.
+/// # Return
+/// * -EBADF, -ENOTSOCK - fd is not a socket
+/// * -EINVAL - the socket is bound, or a bad address
+/// * -EADDRINUSE - the path exists, or the port is bound
+/// * -ENOENT - the parent directory does not exist
+/// * -EADDRNOTAVAIL - the IPv4 address is not an address of the machine
+pub fn sys_bind(fd: usize, addr: *const u8, addrlen: u32) -> isize {
+    let addr = match read_addr(current_user_token(), addr, addrlen) {
+        Ok(addr) => addr,
+        Err(errno) => return errno,
+    };
+    with_socket(fd, |socket| socket.bind(addr))
+}
+
+/// Accept connections on a stream socket.
+/// # Return
+/// * -EOPNOTSUPP - not a stream socket
+/// * -EINVAL - the unix domain socket is not bound, or the socket is connected
+pub fn sys_listen(fd: usize, backlog: usize) -> isize {
+    with_socket(fd, |socket| socket.listen(backlog))
+}
+
+/// Take a connection of a listening socket, wait until a client connects.
+/// # Parameter
+/// * 'addr' - filled with the address of the client if it is not null
+/// * 'addrlen' - the size of addr, set to the length of the address
+/// * 'flags' - SOCK_NONBLOCK and SOCK_CLOEXEC of the new fd
+/// # Return
+/// * the new fd
+/// * -EINVAL - the socket is not listening, or unsupported flags
+/// * -EAGAIN - no connection is waiting and the socket is non-blocking
+/// * -EINTR - interrupted by a signal
+/// * -EMFILE - no fd can be allocated
+pub fn sys_accept4(fd: usize, addr: *mut u8, addrlen: *mut u32, flags: usize) -> isize {
+    if flags & !(SOCK_NONBLOCK | SOCK_CLOEXEC) != 0 {
+        return -EINVAL;
+    }
+    with_socket(fd, |socket| {
+        let (connection, peer) = match socket.accept() {
+            Ok(accepted) => accepted,
+            Err(errno) => return errno,
+        };
+        connection.set_nonblock(flags & SOCK_NONBLOCK != 0);
+        let new_fd = open_anon_file(connection, flags as u32);
+        if new_fd >= 0 {
+            write_addr_len(current_user_token(), addr, addrlen, Some(&peer));
+        }
+        new_fd
+    })
+}
+
+/// Connect a stream socket to the listening socket at the address,
+/// or set the default destination of a datagram socket.
+/// # Return
+/// * -ENOENT - the path does not exist
+/// * -ECONNREFUSED - no socket is listening on the address
+/// * -EPROTOTYPE - the socket bound to the path is not of the same type
+/// * -EISCONN - the stream socket is already connected
+/// * -EINPROGRESS - the TCP connection is started and the socket is non-blocking
+/// * -EAGAIN, -EINTR - see the connect of the socket
+pub fn sys_connect(fd: usize, addr: *const u8, addrlen: u32) -> isize {
+    let addr = match read_addr(current_user_token(), addr, addrlen) {
+        Ok(addr) => addr,
+        Err(errno) => return errno,
+    };
+    with_socket(fd, |socket| socket.connect(addr))
+}
+
+/// Get the bound address of the socket.
+pub fn sys_getsockname(fd: usize, addr: *mut u8, addrlen: *mut u32) -> isize {
+    with_socket(fd, |socket| {
+        let local = socket.local_addr();
+        write_addr_len(current_user_token(), addr, addrlen, Some(&local));
+        0
+    })
+}
+
+/// Get the address of the peer.
+/// # Return
+/// * -ENOTCONN - the socket is not connected
+pub fn sys_getpeername(fd: usize, addr: *mut u8, addrlen: *mut u32) -> isize {
+    with_socket(fd, |socket| match socket.peer_addr() {
+        Ok(peer) => {
+            write_addr_len(current_user_token(), addr, addrlen, Some(&peer));
+            0
+        }
+        Err(errno) => errno,
+    })
+}
+
+/// Send data to the connected peer, or to the datagram socket at addr if it is not null.
+/// # Parameter
+/// * 'flags' - MSG_DONTWAIT and MSG_NOSIGNAL
+/// # Return
+/// * The number of bytes sent.
+/// * -errno - see the send of the socket
+pub fn sys_sendto(
+    fd: usize,
+    buf: *const u8,
+    len: usize,
+    flags: u32,
+    addr: *const u8,
+    addrlen: u32,
+) -> isize {
+    let token = current_user_token();
+    let to = if addr.is_null() {
+        None
+    } else {
+        match read_addr(token, addr, addrlen) {
+            Ok(addr) => Some(addr),
+            Err(errno) => return errno,
+        }
+    };
+    with_socket(fd, |socket| {
+        let buf = UserBuffer::new(translate_byte_buffer(token, buf, len));
+        socket.send(buf, Vec::new(), to, flags)
+    })
+}
+
+/// Receive data, and the address of the sender to addr if it is not null.
+/// # Parameter
+/// * 'flags' - MSG_DONTWAIT, and MSG_TRUNC to get the whole length of a truncated datagram
+/// # Return
+/// * The number of bytes received, 0 at the end of the stream.
+/// * -errno - see the recv of the socket
+pub fn sys_recvfrom(
+    fd: usize,
+    buf: *mut u8,
+    len: usize,
+    flags: u32,
+    addr: *mut u8,
+    addrlen: *mut u32,
+) -> isize {
+    let token = current_user_token();
+    with_socket(fd, |socket| {
+        let buf = UserBuffer::new(translate_byte_buffer(token, buf, len));
+        match socket.recv(buf, flags) {
+            Ok(msg) => {
+                write_addr_len(token, addr, addrlen, msg.from.as_ref());
+                msg.len as isize
+            }
+            Err(errno) => errno,
+        }
+    })
+}
+
+/// Shut down reading, writing or both of a connected socket.
+/// # Return
+/// * -EINVAL - how is not SHUT_RD, SHUT_WR or SHUT_RDWR
+/// * -ENOTCONN - the socket is not connected
+pub fn sys_shutdown(fd: usize, how: usize) -> isize {
+    if how > SHUT_RDWR {
+        return -EINVAL;
+    }
+    with_socket(fd, |socket| socket.shutdown(how))
+}
+
+/// Send the data of an iovec array, and the fds of SCM_RIGHTS control messages.
+/// # Return
+/// * The number of bytes sent.
+/// * -EINVAL - too many iovecs or fds, or a broken control message
+/// * -EBADF - a passed fd is not open
+/// * -errno - see the send of the socket
+pub fn sys_sendmsg(fd: usize, msg: *const MsgHdr, flags: u32) -> isize {
+    let token = current_user_token();
+    let header = copy_from_user(token, msg);
+    if header.iovlen > UIO_MAXIOV {
+        return -EINVAL;
+    }
+    let to = if header.name == 0 {
+        None
+    } else {
+        match read_addr(token, header.name as *const u8, header.namelen) {
+            Ok(addr) => Some(addr),
+            Err(errno) => return errno,
+        }
+    };
+    with_socket(fd, |socket| {
+        let files = match read_rights(token, header.control, header.controllen) {
+            Ok(files) => files,
+            Err(errno) => return errno,
+        };
+        let buf = iov_buffer(token, header.iov as *const IoVec, header.iovlen);
+        socket.send(buf, files, to, flags)
+    })
+}
+
+/// Receive data to an iovec array, received fds are opened and written as an SCM_RIGHTS message.
+/// msg_namelen, msg_controllen and msg_flags are updated.
+/// # Parameter
+/// * 'flags' - MSG_DONTWAIT, MSG_TRUNC and MSG_CMSG_CLOEXEC
+/// # Return
+/// * The number of bytes received, 0 at the end of the stream.
+/// * -EINVAL - too many iovecs
+/// * -errno - see the recv of the socket
+pub fn sys_recvmsg(fd: usize, msg: *mut MsgHdr, flags: u32) -> isize {
+    let token = current_user_token();
+    let mut header = copy_from_user(token, msg);
+    if header.iovlen > UIO_MAXIOV {
+        return -EINVAL;
+    }
+    with_socket(fd, |socket| {
+        let buf = iov_buffer(token, header.iov as *const IoVec, header.iovlen);
+        let received = match socket.recv(buf, flags) {
+            Ok(received) => received,
+            Err(errno) => return errno,
+        };
+        if header.name != 0 {
+            let addr = header.name as *mut u8;
+            let namelen = header.namelen as usize;
+            header.namelen = match &received.from {
+                Some(from) => write_addr(token, addr, namelen, from),
+                None => 0,
+            };
+        }
+        let cloexec = flags & MSG_CMSG_CLOEXEC != 0;
+        let (controllen, ctrunc) = write_rights(
+            token,
+            header.control,
+            header.controllen,
+            received.files,
+            cloexec,
+        );
+        header.controllen = controllen;
+        header.flags = received.flags | ctrunc;
+        copy_to_user(token, msg, &header);
+        received.len as isize
+    })
+}
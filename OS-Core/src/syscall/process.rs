@@ -1,20 +1,31 @@
-use core::usize;
+use core::{mem::size_of, usize};
 
-use alloc::vec::Vec;
+use alloc::{sync::Arc, vec, vec::Vec};
 
 // process manage mod
 use crate::{
-    fs::inode::{open_file, OpenFlags},
-    mm::page_table::PageTable,
+    config::PAGE_SIZE,
+    fs::vfs::read_file,
+    mm::page_table::{copy_bytes_to_user, copy_to_user, PageTable},
     println,
+    syscall::errno::{ENOSYS, EPERM},
     task::{
+        action::{SignalAction, SIG_IGN},
         exit_current_and_run_next, get_pid,
-        manager::{add_proc, proc_from_pid, remove_proc},
-        processor::{current_process, current_user_token},
-        signal::SignalFlags,
+        manager::{add_proc, all_procs, proc_from_pid, procs_in_group, remove_proc},
+        process::{JobEvent, ProcessControlBlock},
+        processor::{current_process, current_task, current_user_token},
+        send_signal,
+        signal::{SignalFlags, MAX_SIG},
         suspended_current_and_run_next,
+        thread::{ThreadControlBlock, NICE_MAX, NICE_MIN},
+        usage::{
+            cycles_to_ms, cycles_to_ticks, RUsage, TaskInfoTail, TimeVal, Tms, MAX_SYSCALL_NUM,
+            RUSAGE_CHILDREN, RUSAGE_SELF, RUSAGE_THREAD,
+        },
+        INITPROC,
     },
-    timer::get_time_ms,
+    timer::{get_time, get_time_ms},
 };
 
 pub fn sys_exit(exit_code: i32) -> ! {
@@ -58,47 +69,87 @@ pub fn sys_exec(path: *const u8, mut args: *const usize) -> isize {
         args_vec.push(PageTable::from_token(user_token).translated_str(arg_str_ptr as *const u8));
         args = unsafe { args.add(1) };
     }
-    if let Some(inode) = open_file(&path, OpenFlags::READ_ONLY) {
+    if let Some(app_data) = read_file(&path) {
         let argc = args_vec.len();
-        let app_data = inode.read_all();
         let proc = current_process();
-        proc.exec(app_data.as_slice(), args_vec);
+        proc.exec(&path, app_data.as_slice(), args_vec);
         argc as isize
     } else {
         -1
     }
 }
 
+/// Report stopped children in waitpid.
+pub const WUNTRACED: u32 = 2;
+/// Report continued children in waitpid.
+pub const WCONTINUED: u32 = 8;
+
 /// Wait for the child process to exit and reclaim resources,
 /// while collecting the exit code.
+/// This function never blocks, the caller polls until the child exits.
 /// # Parameter
-/// * 'pid' - The pid of the child process to be recycled, or -1 for any child process.
+/// * 'pid' - The pid of the child process to be recycled,
+/// -1 for any child process, 0 for any child in the current process group,
+/// less than -1 for any child in the process group -pid.
 /// * 'exit_code_ptr' - Child process exit code address.
+/// * 'options' - WUNTRACED and WCONTINUED also report children stopped or continued by signals,
+/// the status is (signum << 8) | 0x7f for a stopped child, 0xffff for a continued child.
 /// # Return
 /// * -1 - Child process does not exist.
 /// * -2 - The child process has not exited yet.
-/// * pid - The pid of the child process that was successfully recycled.
-pub fn sys_waitpid(pid: isize, exit_code_ptr: *mut i32) -> isize {
+/// * pid - The pid of the child process that was successfully recycled, stopped or continued.
+pub fn sys_waitpid(pid: isize, exit_code_ptr: *mut i32, options: u32) -> isize {
     let proc = current_process();
     let mut inner = proc.inner_exclusive_access();
+    let page_table = PageTable::from_token(inner.memory_set.token());
+    let pgid = inner.pgid;
     let mut ret = -1;
     let mut idx = -1;
     for (index, child) in inner.children.iter().enumerate() {
-        if pid == -1 || pid as usize == child.pid() {
-            ret = -2;
-            if child.inner_exclusive_access().is_zombie {
-                ret = child.pid() as isize;
-                *(PageTable::from_token(inner.memory_set.token())
-                    .translated_refmut::<i32>(exit_code_ptr)) =
-                    child.inner_exclusive_access().exit_code;
-                idx = index as isize;
-                break;
+        let mut child_inner = child.inner_exclusive_access();
+        let matched = match pid {
+            -1 => true,
+            0 => child_inner.pgid == pgid,
+            pid if pid < -1 => child_inner.pgid == (-pid) as usize,
+            pid => pid as usize == child.pid(),
+        };
+        if !matched {
+            continue;
+        }
+        ret = -2;
+        if child_inner.is_zombie {
+            ret = child.pid() as isize;
+            *page_table.translated_refmut::<i32>(exit_code_ptr) = child_inner.exit_code;
+            idx = index as isize;
+            break;
+        }
+        let status = match child_inner.job_event {
+            Some(JobEvent::Stopped(signum)) if options & WUNTRACED != 0 => {
+                Some(((signum << 8) | 0x7f) as i32)
             }
+            Some(JobEvent::Continued) if options & WCONTINUED != 0 => Some(0xffff),
+            _ => None,
+        };
+        if let Some(status) = status {
+            child_inner.job_event = None;
+            *page_table.translated_refmut::<i32>(exit_code_ptr) = status;
+            ret = child.pid() as isize;
+            break;
         }
     }
     if idx != -1 {
-        let child_pid = inner.children.remove(idx as usize).pid();
-        remove_proc(child_pid);
+        let child = inner.children.remove(idx as usize);
+        // The resource usage of the child is added to its parent when it is recycled.
+        let child_inner = child.inner_exclusive_access();
+        inner.usage.children_time += child_inner.usage.exited_time;
+        inner.usage.children_time += child_inner.usage.children_time;
+        inner.usage.children_max_rss = inner
+            .usage
+            .children_max_rss
+            .max(child_inner.memory_set.peak_frames())
+            .max(child_inner.usage.children_max_rss);
+        drop(child_inner);
+        remove_proc(child.pid());
     }
     ret
 }
@@ -115,34 +166,401 @@ pub fn sys_spawn(path: *const u8, mut args: *const usize) -> isize {
         args_vec.push(PageTable::from_token(user_token).translated_str(arg_str_ptr as *const u8));
         args = unsafe { args.add(1) };
     }
-    if let Some(inode) = open_file(&path, OpenFlags::READ_ONLY) {
-        let app_data = inode.read_all();
+    if let Some(app_data) = read_file(&path) {
         let proc = current_process();
-        proc.spawn(app_data.as_slice(), args_vec) as isize
+        proc.spawn(&path, app_data.as_slice(), args_vec) as isize
     } else {
         -1
     }
 }
 
-pub fn sys_kill(pid: usize, signum: u32) -> isize {
-    if let Some(pcb) = proc_from_pid(pid) {
-        if let Some(signal) = SignalFlags::from_bits(1 << signum) {
-            let mut inner = pcb.inner_exclusive_access();
-            if inner.signals.contains(signal) {
+/// Whether the current process may signal the process.
+/// There are no users, so a process may only signal the processes in its own session,
+/// except that the init process may signal all processes.
+fn may_signal(sid: usize, proc: &Arc<ProcessControlBlock>) -> bool {
+    get_pid() as usize == INITPROC.pid() || proc.inner_exclusive_access().sid == sid
+}
+
+/// Send the signal to the processes which the current process may signal.
+/// # Return
+/// * 0 - the signal is sent to at least one process
+/// * -1 - there is no process
+/// * -EPERM - the current process may not signal any of them
+fn signal_procs(procs: Vec<Arc<ProcessControlBlock>>, signal: SignalFlags) -> isize {
+    if procs.is_empty() {
+        return -1;
+    }
+    let sid = current_process().inner_exclusive_access().sid;
+    let mut sent = false;
+    for proc in procs.iter().filter(|proc| may_signal(sid, proc)) {
+        send_signal(proc, signal);
+        sent = true;
+    }
+    if sent {
+        0
+    } else {
+        -EPERM
+    }
+}
+
+/// Send a signal to processes, only the processes in the same session can be signalled.
+/// # Parameter
+/// * 'pid' - the target process if pid > 0, all processes in the current process group if pid == 0,
+/// all processes but init process and the current process if pid == -1,
+/// all processes in the process group -pid if pid < -1.
+/// * 'signum' - signal number
+/// # Return
+/// * 0 - success
+/// * -1 - no such process or invalid signal, or the signal is pending in the target process
+/// * -EPERM - the target processes are all in other sessions
+pub fn sys_kill(pid: isize, signum: u32) -> isize {
+    if signum as usize > MAX_SIG {
+        println!("[kernel] Can not general SignalFlags from {}", signum);
+        return -1;
+    }
+    let signal = SignalFlags::from_bits_truncate(1 << signum);
+    match pid {
+        0 => {
+            let pgid = current_process().inner_exclusive_access().pgid;
+            signal_procs(procs_in_group(pgid), signal)
+        }
+        -1 => {
+            let curr_pid = get_pid() as usize;
+            let procs = all_procs()
+                .into_iter()
+                .filter(|proc| proc.pid() != curr_pid && proc.pid() != INITPROC.pid())
+                .collect();
+            signal_procs(procs, signal)
+        }
+        pid if pid < -1 => signal_procs(procs_in_group((-pid) as usize), signal),
+        pid => {
+            let Some(pcb) = proc_from_pid(pid as usize) else {
+                println!("[kernel] Not found pcb which pid is {}", pid);
+                return -1;
+            };
+            if pcb.inner_exclusive_access().signals.contains(signal) {
                 return -1;
             }
-            inner.signals.insert(signal);
-            0
-        } else {
-            println!("[kernel] Can not general SignalFlags from {}", signum);
-            -1
+            signal_procs(vec![pcb], signal)
+        }
+    }
+}
+
+/// Set the signal action of the current process.
+/// Only default action (SIG_DFL) and ignoring the signal (SIG_IGN) are supported,
+/// user handlers are not, since there is no way to return from them.
+/// # Parameter
+/// * 'signum' - signal number, SIGKILL and SIGSTOP can not be changed
+/// * 'action' - the new action, or null
+/// * 'old_action' - where to save the old action, or null
+/// # Return
+/// * 0 - success, -1 - invalid signal
+/// * -ENOSYS - the handler of action is a function
+pub fn sys_sigaction(
+    signum: u32,
+    action: *const SignalAction,
+    old_action: *mut SignalAction,
+) -> isize {
+    if signum == 0 || signum as usize > MAX_SIG {
+        return -1;
+    }
+    let signal = SignalFlags::from_bits_truncate(1 << signum);
+    let page_table = PageTable::from_token(current_user_token());
+    let proc = current_process();
+    let mut inner = proc.inner_exclusive_access();
+    if !old_action.is_null() {
+        *page_table.translated_refmut(old_action) = inner.signal_actions.table[signum as usize];
+    }
+    if !action.is_null() {
+        let action = *page_table.translated_ref(action);
+        if signal.intersects(SignalFlags::SIGKILL | SignalFlags::SIGSTOP) {
+            return -1;
         }
+        if action.handler > SIG_IGN {
+            return -ENOSYS;
+        }
+        inner.signal_actions.table[signum as usize] = action;
+        if action.handler == SIG_IGN {
+            inner.signals.remove(signal);
+        }
+    }
+    0
+}
+
+/// Set the process group of a process.
+/// # Parameter
+/// * 'pid' - the current process or one of its children, 0 means the current process
+/// * 'pgid' - the process group to join, it must exist in the same session unless it is pid,
+/// 0 means use pid as pgid
+/// # Return
+/// * 0 - success
+/// * -1 - no such process, the process is a session leader, or the group is in another session
+pub fn sys_setpgid(pid: usize, pgid: usize) -> isize {
+    let curr_proc = current_process();
+    let target = if pid == 0 || pid == curr_proc.pid() {
+        curr_proc.clone()
+    } else if let Some(child) = curr_proc
+        .inner_exclusive_access()
+        .children
+        .iter()
+        .find(|child| child.pid() == pid)
+    {
+        child.clone()
     } else {
-        println!("[kernel] Not found pcb which pid is {}", pid);
-        -1
+        return -1;
+    };
+    let pgid = if pgid == 0 { target.pid() } else { pgid };
+    let sid = curr_proc.inner_exclusive_access().sid;
+    let target_sid = target.inner_exclusive_access().sid;
+    if target_sid != sid || target_sid == target.pid() {
+        return -1;
     }
+    if pgid != target.pid()
+        && !procs_in_group(pgid)
+            .iter()
+            .any(|proc| proc.inner_exclusive_access().sid == sid)
+    {
+        return -1;
+    }
+    target.inner_exclusive_access().pgid = pgid;
+    0
+}
+
+/// Get the process group id of a process, 0 means the current process.
+/// # Return
+/// * -1 - no such process
+pub fn sys_getpgid(pid: usize) -> isize {
+    let proc = if pid == 0 {
+        Some(current_process())
+    } else {
+        proc_from_pid(pid)
+    };
+    proc.map_or(-1, |proc| proc.inner_exclusive_access().pgid as isize)
+}
+
+/// Get the session id of a process, 0 means the current process.
+/// # Return
+/// * -1 - no such process
+pub fn sys_getsid(pid: usize) -> isize {
+    let proc = if pid == 0 {
+        Some(current_process())
+    } else {
+        proc_from_pid(pid)
+    };
+    proc.map_or(-1, |proc| proc.inner_exclusive_access().sid as isize)
+}
+
+/// Create a new session and a new process group, the current process is the leader of both.
+/// The new session has no controlling terminal.
+/// # Return
+/// * -1 - the current process is already a process group leader
+/// * the new session id
+pub fn sys_setsid() -> isize {
+    let proc = current_process();
+    let pid = proc.pid();
+    let mut inner = proc.inner_exclusive_access();
+    if inner.pgid == pid {
+        return -1;
+    }
+    inner.pgid = pid;
+    inner.sid = pid;
+    pid as isize
+}
+
+/// Set or get the priority of a process.
+pub const PRIO_PROCESS: usize = 0;
+/// Set or get the priority of a process group.
+pub const PRIO_PGRP: usize = 1;
+/// Set or get the priority of a thread of the current process.
+pub const PRIO_THREAD: usize = 3;
+
+/// Get the threads selected by setpriority and getpriority.
+/// The processes selected by which and who, the current process for PRIO_THREAD.
+fn priority_procs(which: usize, who: usize) -> Vec<Arc<ProcessControlBlock>> {
+    match which {
+        PRIO_PROCESS if who == 0 => vec![current_process()],
+        PRIO_PROCESS => proc_from_pid(who).into_iter().collect(),
+        PRIO_PGRP if who == 0 => procs_in_group(current_process().inner_exclusive_access().pgid),
+        PRIO_PGRP => procs_in_group(who),
+        PRIO_THREAD => vec![current_process()],
+        _ => Vec::new(),
+    }
+}
+
+fn priority_targets(which: usize, who: usize) -> Vec<Arc<ThreadControlBlock>> {
+    if which == PRIO_THREAD {
+        return current_process()
+            .inner_exclusive_access()
+            .threads
+            .get(who)
+            .cloned()
+            .flatten()
+            .into_iter()
+            .collect();
+    }
+    priority_procs(which, who)
+        .iter()
+        .flat_map(|proc| {
+            proc.inner_exclusive_access()
+                .threads
+                .iter()
+                .flatten()
+                .cloned()
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// Whether the process is the current process or one of its descendants,
+/// whose priority the current process may set.
+fn is_own_proc(proc: &Arc<ProcessControlBlock>) -> bool {
+    let current = current_process();
+    let mut proc = proc.clone();
+    loop {
+        if Arc::ptr_eq(&proc, &current) {
+            return true;
+        }
+        let parent = proc.inner_exclusive_access().parent.clone();
+        match parent.and_then(|parent| parent.upgrade()) {
+            Some(parent) => proc = parent,
+            None => return false,
+        }
+    }
+}
+
+/// Set the scheduling priority of all threads of the processes, or of a thread.
+/// # Parameter
+/// * 'which' - PRIO_PROCESS, PRIO_PGRP or PRIO_THREAD
+/// * 'who' - pid or pgid, 0 means the current process or process group, or tid for PRIO_THREAD
+/// * 'nice' - the new nice value, clamped to NICE_MIN..=NICE_MAX, lower nice means higher priority
+/// # Return
+/// * 0 - success, -1 - no process found
+/// * -EPERM - a process is not the current process or one of its descendants
+pub fn sys_setpriority(which: usize, who: usize, nice: isize) -> isize {
+    let targets = priority_targets(which, who);
+    if targets.is_empty() {
+        return -1;
+    }
+    if !priority_procs(which, who).iter().all(is_own_proc) {
+        return -EPERM;
+    }
+    let nice = nice.clamp(NICE_MIN, NICE_MAX);
+    for thread in targets {
+        thread.inner_exclusive_access().nice = nice;
+    }
+    0
+}
+
+/// Get the highest scheduling priority of the processes, or of a thread.
+/// A thread holding a priority inheritance mutex may run at a higher priority than it is set.
+/// # Parameter
+/// * 'which' - PRIO_PROCESS, PRIO_PGRP or PRIO_THREAD
+/// * 'who' - pid or pgid, 0 means the current process or process group, or tid for PRIO_THREAD
+/// # Return
+/// * -1 - no process found
+/// * 20 - nice, in 1..=40, so it is never negative
+pub fn sys_getpriority(which: usize, who: usize) -> isize {
+    priority_targets(which, who)
+        .iter()
+        .map(|thread| thread.inner_exclusive_access().effective_nice())
+        .min()
+        .map_or(-1, |nice| 20 - nice)
 }
 
 pub fn sys_get_pid() -> isize {
     get_pid()
 }
+
+/// Count the kernel time of the current thread until now,
+/// so the cpu time reported includes the running syscall.
+fn update_current_usage() {
+    current_task()
+        .unwrap()
+        .inner_exclusive_access()
+        .usage
+        .leave_kernel();
+}
+
+/// Get resource usage.
+/// # Parameter
+/// * 'who' - RUSAGE_SELF, RUSAGE_CHILDREN or RUSAGE_THREAD
+/// * 'usage' - user pointer to RUsage
+/// # Return
+/// * 0 - success
+/// * -1 - 'who' is invalid
+pub fn sys_getrusage(who: isize, usage: *mut RUsage) -> isize {
+    update_current_usage();
+    let proc = current_process();
+    let inner = proc.inner_exclusive_access();
+    let (time, max_rss) = match who {
+        RUSAGE_SELF => (inner.cpu_time(), inner.memory_set.peak_frames()),
+        RUSAGE_CHILDREN => (inner.usage.children_time, inner.usage.children_max_rss),
+        RUSAGE_THREAD => (
+            current_task().unwrap().inner_exclusive_access().usage.time,
+            inner.memory_set.peak_frames(),
+        ),
+        _ => return -1,
+    };
+    let rusage = RUsage {
+        utime: TimeVal::from_cycles(time.user),
+        stime: TimeVal::from_cycles(time.kernel),
+        maxrss: max_rss * PAGE_SIZE / 1024,
+        ..Default::default()
+    };
+    copy_to_user(inner.user_token(), usage, &rusage);
+    0
+}
+
+/// Get the cpu time of the current process and its waited children.
+/// # Parameter
+/// * 'tms' - user pointer to Tms, ignored if it is null
+/// # Return
+/// * Clock ticks since the system started.
+pub fn sys_times(tms: *mut Tms) -> isize {
+    update_current_usage();
+    let proc = current_process();
+    let inner = proc.inner_exclusive_access();
+    if !tms.is_null() {
+        let time = inner.cpu_time();
+        let children_time = inner.usage.children_time;
+        let value = Tms {
+            utime: cycles_to_ticks(time.user),
+            stime: cycles_to_ticks(time.kernel),
+            cutime: cycles_to_ticks(children_time.user),
+            cstime: cycles_to_ticks(children_time.kernel),
+        };
+        copy_to_user(inner.user_token(), tms, &value);
+    }
+    cycles_to_ticks(get_time()) as isize
+}
+
+/// Get the syscall counts, cpu time and memory usage of the current process.
+/// # Parameter
+/// * 'task_info' - user pointer to struct TaskInfo, which is
+///   u32 syscall_times[MAX_SYSCALL_NUM] followed by the fields of TaskInfoTail
+/// # Return
+/// * 0 - success
+pub fn sys_task_info(task_info: *mut u8) -> isize {
+    update_current_usage();
+    let proc = current_process();
+    let inner = proc.inner_exclusive_access();
+    let token = inner.user_token();
+    let bytes = unsafe {
+        core::slice::from_raw_parts(
+            inner.usage.syscall_times.as_ptr() as *const u8,
+            MAX_SYSCALL_NUM * size_of::<u32>(),
+        )
+    };
+    copy_bytes_to_user(token, task_info, bytes);
+    let time = inner.cpu_time();
+    let tail = TaskInfoTail {
+        time: get_time_ms() - inner.usage.start_ms,
+        utime: cycles_to_ms(time.user),
+        stime: cycles_to_ms(time.kernel),
+        rss: inner.memory_set.frame_count(),
+        max_rss: inner.memory_set.peak_frames(),
+    };
+    let tail_ptr = task_info.wrapping_add(MAX_SYSCALL_NUM * size_of::<u32>()) as *mut TaskInfoTail;
+    copy_to_user(token, tail_ptr, &tail);
+    0
+}
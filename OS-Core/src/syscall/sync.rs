@@ -1,71 +1,245 @@
 use alloc::sync::Arc;
 
 use crate::{
-    sync::{condvar::Condvar, mutex::*, semaphore::Semaphore},
+    mm::{
+        address::{PhysAddr, VirtAddr},
+        page_table::copy_from_user,
+    },
+    sync::{
+        barrier::Barrier,
+        condvar::Condvar,
+        deadlock::Resource,
+        futex::{self, *},
+        mutex::*,
+        rwlock::RwLock,
+        semaphore::Semaphore,
+    },
+    syscall::errno::{EAGAIN, EFAULT, EINVAL, ENOSYS, EPERM, ETIMEDOUT},
     task::{
-        block_current_and_run_next,
-        processor::{current_process, current_task},
+        block_current_and_run_next, prepare_block,
+        processor::{current_process, current_task, current_user_token},
+        thread::ThreadControlBlock,
     },
-    timer::{add_timer, get_time_ms},
+    timer::{add_timer, get_time_ms, TimeSpec},
 };
 
+use super::ipc::read_deadline;
+
 pub fn sys_sleep(ms: usize) -> isize {
     let expire_ms = get_time_ms() + ms;
     let thread = current_task().unwrap();
+    prepare_block();
     add_timer(expire_ms, thread);
     block_current_and_run_next();
     0
 }
 
-pub fn sys_mutex_create(blocking: bool) -> isize {
-    let mutex: Arc<dyn Mutex>;
-    match blocking {
-        true => {
-            mutex = Arc::new(MutexBlocking::new());
+/// Create a mutex of the process.
+/// # Parameter
+/// * 'flags' - MUTEX_BLOCKING and MUTEX_PRIO_INHERIT, with MUTEX_RECURSIVE or MUTEX_ERRORCHECK
+/// # Return
+/// * The mutex id.
+/// * -EINVAL - unknown flags, both types, or priority inheritance without MUTEX_BLOCKING
+pub fn sys_mutex_create(flags: usize) -> isize {
+    let all = MUTEX_BLOCKING | MUTEX_RECURSIVE | MUTEX_ERRORCHECK | MUTEX_PRIO_INHERIT;
+    if flags & !all != 0 {
+        return -EINVAL;
+    }
+    let mutex_type = match flags & (MUTEX_RECURSIVE | MUTEX_ERRORCHECK) {
+        0 => MutexType::Normal,
+        MUTEX_RECURSIVE => MutexType::Recursive,
+        MUTEX_ERRORCHECK => MutexType::ErrorCheck,
+        _ => return -EINVAL,
+    };
+    let mutex: Arc<dyn Mutex> = match flags & (MUTEX_BLOCKING | MUTEX_PRIO_INHERIT) {
+        0 => Arc::new(MutexSpin::new(mutex_type)),
+        MUTEX_BLOCKING => Arc::new(MutexBlocking::new(mutex_type)),
+        MUTEX_PRIO_INHERIT => return -EINVAL,
+        _ => Arc::new(MutexPriorityInherit::new(mutex_type)),
+    };
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    let mutex_id = inner.add_mutex(mutex);
+    inner.deadlock.add_resource(Resource::Mutex(mutex_id), 1);
+    mutex_id as isize
+}
+
+/// Whether the thread holds the mutex.
+fn holds(mutex: &Arc<dyn Mutex>, thread: &Arc<ThreadControlBlock>) -> bool {
+    mutex
+        .owner()
+        .is_some_and(|owner| Arc::ptr_eq(&owner, thread))
+}
+
+/// Lock the mutex, wait until it is unlocked or the deadline is reached.
+fn mutex_lock(mutex_id: usize, deadline: Option<usize>) -> isize {
+    let thread = current_task().unwrap();
+    let tid = thread.tid();
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    let mutex = inner.get_mutex(mutex_id);
+    let res = Resource::Mutex(mutex_id);
+    // A recursive or error checking mutex locked again by the owner does not wait.
+    let relock = holds(&mutex, &thread);
+    if !relock || mutex.mutex_type() == MutexType::Normal {
+        if let Err(errno) = inner.deadlock.request(tid, res) {
+            return errno;
         }
-        false => {
-            mutex = Arc::new(MutexSpin::new());
+    }
+    drop(inner);
+    let ret = mutex.lock_until(deadline);
+    if !relock {
+        let deadlock = &mut process.inner_exclusive_access().deadlock;
+        match ret {
+            0 => deadlock.acquire(tid, res),
+            _ => deadlock.cancel(tid, res),
         }
-    };
-    current_process().inner_exclusive_access().add_mutex(mutex) as isize
+    }
+    ret
 }
 
+/// Lock the mutex, wait until it is unlocked.
+/// # Return
+/// * -EDEADLK - deadlock detection is enabled and waiting for the mutex would deadlock,
+///   or an error checking mutex is held by the current thread
 pub fn sys_mutex_lock(mutex_id: usize) -> isize {
-    let mutex = current_process()
-        .inner_exclusive_access()
-        .get_mutex(mutex_id);
-    mutex.lock();
-    0
+    mutex_lock(mutex_id, None)
+}
+
+/// Lock the mutex, wait until it is unlocked or the absolute timeout is reached.
+/// # Return
+/// * -EDEADLK - deadlock detection is enabled and waiting for the mutex would deadlock,
+///   or an error checking mutex is held by the current thread
+/// * -ETIMEDOUT - not locked before the timeout
+/// * -EINVAL - abs_timeout is invalid
+pub fn sys_mutex_timedlock(mutex_id: usize, abs_timeout: *const TimeSpec) -> isize {
+    match read_deadline(current_user_token(), abs_timeout) {
+        Ok(deadline) => mutex_lock(mutex_id, deadline),
+        Err(errno) => errno,
+    }
 }
 
+/// Lock the mutex without waiting.
+/// # Return
+/// * -EBUSY - the mutex is locked, and it is not a recursive mutex held by the current thread
+pub fn sys_mutex_trylock(mutex_id: usize) -> isize {
+    let thread = current_task().unwrap();
+    let tid = thread.tid();
+    let process = current_process();
+    let mutex = process.inner_exclusive_access().get_mutex(mutex_id);
+    let relock = holds(&mutex, &thread);
+    let ret = mutex.try_lock();
+    if ret == 0 && !relock {
+        process
+            .inner_exclusive_access()
+            .deadlock
+            .acquire(tid, Resource::Mutex(mutex_id));
+    }
+    ret
+}
+
+/// Unlock the mutex, the waiting thread which gets it is woken up.
+/// # Return
+/// * -EPERM - the mutex is not locked,
+///   or a recursive or error checking mutex is held by another thread
 pub fn sys_mutex_unlock(mutex_id: usize) -> isize {
-    let mutex = current_process()
-        .inner_exclusive_access()
-        .get_mutex(mutex_id);
-    mutex.unlock();
-    0
+    let process = current_process();
+    let mutex = process.inner_exclusive_access().get_mutex(mutex_id);
+    let owner = mutex.owner();
+    let ret = mutex.unlock();
+    // The owner still holds a recursive mutex locked more than once.
+    if let Some(owner) = owner.filter(|owner| ret == 0 && !holds(&mutex, owner)) {
+        let tid = owner.tid();
+        process
+            .inner_exclusive_access()
+            .deadlock
+            .release(tid, Resource::Mutex(mutex_id));
+    }
+    ret
 }
 
 pub fn sys_semaphore_create(res_count: usize) -> isize {
     let semaphore = Arc::new(Semaphore::new(res_count));
-    current_process()
-        .inner_exclusive_access()
-        .add_semaphore(semaphore) as isize
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    let semaphore_id = inner.add_semaphore(semaphore);
+    inner
+        .deadlock
+        .add_resource(Resource::Semaphore(semaphore_id), res_count);
+    semaphore_id as isize
 }
 
 pub fn sys_semaphore_up(semaphore_id: usize) -> isize {
-    let semaphore = current_process()
-        .inner_exclusive_access()
-        .get_semaphore(semaphore_id);
+    let tid = current_task().unwrap().tid();
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    let semaphore = inner.get_semaphore(semaphore_id);
+    inner
+        .deadlock
+        .release(tid, Resource::Semaphore(semaphore_id));
+    drop(inner);
     semaphore.up();
     0
 }
 
+/// Take a unit of the semaphore, wait until there is one or the deadline is reached.
+fn semaphore_down(semaphore_id: usize, deadline: Option<usize>) -> isize {
+    let tid = current_task().unwrap().tid();
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    let semaphore = inner.get_semaphore(semaphore_id);
+    let res = Resource::Semaphore(semaphore_id);
+    if let Err(errno) = inner.deadlock.request(tid, res) {
+        return errno;
+    }
+    drop(inner);
+    let taken = semaphore.down_until(deadline);
+    let deadlock = &mut process.inner_exclusive_access().deadlock;
+    match taken {
+        true => {
+            deadlock.acquire(tid, res);
+            0
+        }
+        false => {
+            deadlock.cancel(tid, res);
+            -ETIMEDOUT
+        }
+    }
+}
+
+/// Take a unit of the semaphore, wait until there is one.
+/// # Return
+/// * -EDEADLK - deadlock detection is enabled and waiting for the semaphore would deadlock
 pub fn sys_semaphore_down(semaphore_id: usize) -> isize {
-    let semaphore = current_process()
+    semaphore_down(semaphore_id, None)
+}
+
+/// Take a unit of the semaphore, wait until there is one or the absolute timeout is reached.
+/// # Return
+/// * -EDEADLK - deadlock detection is enabled and waiting for the semaphore would deadlock
+/// * -ETIMEDOUT - no unit is taken before the timeout
+/// * -EINVAL - abs_timeout is invalid
+pub fn sys_semaphore_timeddown(semaphore_id: usize, abs_timeout: *const TimeSpec) -> isize {
+    match read_deadline(current_user_token(), abs_timeout) {
+        Ok(deadline) => semaphore_down(semaphore_id, deadline),
+        Err(errno) => errno,
+    }
+}
+
+/// Take a unit of the semaphore without waiting.
+/// # Return
+/// * -EAGAIN - there is no free unit
+pub fn sys_semaphore_trydown(semaphore_id: usize) -> isize {
+    let tid = current_task().unwrap().tid();
+    let process = current_process();
+    let semaphore = process.inner_exclusive_access().get_semaphore(semaphore_id);
+    if !semaphore.try_down() {
+        return -EAGAIN;
+    }
+    process
         .inner_exclusive_access()
-        .get_semaphore(semaphore_id);
-    semaphore.down();
+        .deadlock
+        .acquire(tid, Resource::Semaphore(semaphore_id));
     0
 }
 
@@ -83,13 +257,239 @@ pub fn sys_condvar_signal(condvar_id: usize) -> isize {
     0
 }
 
-pub fn sys_condvar_wait(condvar_id: usize, mutex_id: usize) -> isize {
+/// Wake up all threads waiting on the condvar.
+pub fn sys_condvar_broadcast(condvar_id: usize) -> isize {
+    current_process()
+        .inner_exclusive_access()
+        .get_condvar(condvar_id)
+        .broadcast();
+    0
+}
+
+/// Unlock the mutex and wait for a signal or the deadline, then lock the mutex again.
+fn condvar_wait(condvar_id: usize, mutex_id: usize, deadline: Option<usize>) -> isize {
+    let thread = current_task().unwrap();
+    let tid = thread.tid();
     let proc = current_process();
-    let proc_inner = proc.inner_exclusive_access();
+    let mut proc_inner = proc.inner_exclusive_access();
     let mutex = proc_inner.get_mutex(mutex_id);
     let condvar = proc_inner.get_condvar(condvar_id);
+    if !holds(&mutex, &thread) {
+        return -EPERM;
+    }
+    // The mutex is unlocked while waiting, and locked again before returning.
+    let res = Resource::Mutex(mutex_id);
+    proc_inner.deadlock.release(tid, res);
     drop(proc_inner);
-    drop(proc);
-    condvar.wait(mutex);
+    let signalled = condvar.wait_until(mutex, deadline);
+    proc.inner_exclusive_access().deadlock.acquire(tid, res);
+    match signalled {
+        true => 0,
+        false => -ETIMEDOUT,
+    }
+}
+
+/// Unlock the mutex and wait for a signal, then lock the mutex again.
+/// # Return
+/// * -EPERM - the mutex is not held by the current thread
+pub fn sys_condvar_wait(condvar_id: usize, mutex_id: usize) -> isize {
+    condvar_wait(condvar_id, mutex_id, None)
+}
+
+/// Unlock the mutex and wait for a signal or the absolute timeout, then lock the mutex again.
+/// The mutex is locked again even if it times out.
+/// # Return
+/// * -EPERM - the mutex is not held by the current thread
+/// * -ETIMEDOUT - not signalled before the timeout
+/// * -EINVAL - abs_timeout is invalid
+pub fn sys_condvar_timedwait(
+    condvar_id: usize,
+    mutex_id: usize,
+    abs_timeout: *const TimeSpec,
+) -> isize {
+    match read_deadline(current_user_token(), abs_timeout) {
+        Ok(deadline) => condvar_wait(condvar_id, mutex_id, deadline),
+        Err(errno) => errno,
+    }
+}
+
+pub fn sys_rwlock_create() -> isize {
+    current_process()
+        .inner_exclusive_access()
+        .add_rwlock(Arc::new(RwLock::new())) as isize
+}
+
+/// Lock the rwlock for reading, wait while a writer holds it or waits for it.
+/// # Return
+/// * -EINVAL - the rwlock does not exist
+pub fn sys_rwlock_rdlock(rwlock_id: usize) -> isize {
+    let Some(rwlock) = current_process()
+        .inner_exclusive_access()
+        .get_rwlock(rwlock_id)
+    else {
+        return -EINVAL;
+    };
+    rwlock.read();
+    0
+}
+
+/// Lock the rwlock for writing, wait until readers and the writer unlock it.
+/// # Return
+/// * -EINVAL - the rwlock does not exist
+pub fn sys_rwlock_wrlock(rwlock_id: usize) -> isize {
+    let Some(rwlock) = current_process()
+        .inner_exclusive_access()
+        .get_rwlock(rwlock_id)
+    else {
+        return -EINVAL;
+    };
+    rwlock.write();
     0
 }
+
+/// Turn the read lock of the current thread into the write lock,
+/// wait until the other readers unlock it.
+/// # Return
+/// * -EPERM - the current thread does not hold the rwlock for reading
+/// * -EDEADLK - another reader is waiting to upgrade
+/// * -EINVAL - the rwlock does not exist
+pub fn sys_rwlock_upgrade(rwlock_id: usize) -> isize {
+    let Some(rwlock) = current_process()
+        .inner_exclusive_access()
+        .get_rwlock(rwlock_id)
+    else {
+        return -EINVAL;
+    };
+    rwlock.upgrade()
+}
+
+/// # Return
+/// * -EPERM - the current thread does not hold the rwlock
+/// * -EINVAL - the rwlock does not exist
+pub fn sys_rwlock_unlock(rwlock_id: usize) -> isize {
+    let Some(rwlock) = current_process()
+        .inner_exclusive_access()
+        .get_rwlock(rwlock_id)
+    else {
+        return -EINVAL;
+    };
+    rwlock.unlock()
+}
+
+/// # Parameter
+/// * 'count' - the number of threads to wait for in every round
+/// # Return
+/// * -EINVAL - count is 0
+pub fn sys_barrier_create(count: usize) -> isize {
+    if count == 0 {
+        return -EINVAL;
+    }
+    current_process()
+        .inner_exclusive_access()
+        .add_barrier(Arc::new(Barrier::new(count))) as isize
+}
+
+/// Wait until count threads have arrived at the barrier.
+/// # Return
+/// * 1 - for the last thread of the round
+/// * 0 - for the other threads
+/// * -EINVAL - the barrier does not exist
+pub fn sys_barrier_wait(barrier_id: usize) -> isize {
+    let Some(barrier) = current_process()
+        .inner_exclusive_access()
+        .get_barrier(barrier_id)
+    else {
+        return -EINVAL;
+    };
+    barrier.wait() as isize
+}
+
+/// Enable or disable deadlock detection of the mutexes and semaphores of the process.
+/// # Parameter
+/// * 'enabled' - 1 to enable and 0 to disable
+/// # Return
+/// * -EINVAL - enabled is not 0 or 1
+pub fn sys_enable_deadlock_detect(enabled: usize) -> isize {
+    match enabled {
+        0 | 1 => {
+            current_process()
+                .inner_exclusive_access()
+                .deadlock
+                .set_enabled(enabled == 1);
+            0
+        }
+        _ => -EINVAL,
+    }
+}
+
+/// The key of the futex word, which is its physical address.
+/// # Return
+/// * -EINVAL - uaddr is not aligned to 4 bytes
+/// * -EFAULT - uaddr is not mapped
+fn futex_key(uaddr: usize) -> Result<usize, isize> {
+    if uaddr % 4 != 0 {
+        return Err(-EINVAL);
+    }
+    let va = VirtAddr::from(uaddr);
+    let process = current_process();
+    let inner = process.inner_exclusive_access();
+    match inner.memory_set.translate(va.floor()) {
+        Some(pte) if pte.is_valid() => Ok(PhysAddr::from(pte.ppn()).0 + va.page_offset()),
+        _ => Err(-EFAULT),
+    }
+}
+
+/// Wait on or wake up threads waiting on a user u32 word.
+/// # Parameter
+/// * 'op' - FUTEX_WAIT, FUTEX_WAKE, FUTEX_REQUEUE or FUTEX_CMP_REQUEUE,
+///   FUTEX_PRIVATE_FLAG is ignored
+/// * 'val' - the expected word of FUTEX_WAIT, or the max number of threads to wake up
+/// * 'timeout' - the relative timeout of FUTEX_WAIT, null waits forever,
+///   or the max number of threads to requeue
+/// * 'uaddr2' - the word to requeue to
+/// * 'val3' - the expected word of FUTEX_CMP_REQUEUE
+/// # Return
+/// * FUTEX_WAIT - 0 when woken up, see futex::wait
+/// * FUTEX_WAKE - the number of threads woken up
+/// * FUTEX_REQUEUE - the number of threads woken up and requeued, see futex::requeue
+/// * -EINVAL - an address is not aligned, or nsec of timeout is out of range
+/// * -EFAULT - an address is not mapped
+/// * -ENOSYS - unknown op
+pub fn sys_futex(
+    uaddr: usize,
+    op: usize,
+    val: u32,
+    timeout: usize,
+    uaddr2: usize,
+    val3: u32,
+) -> isize {
+    let key = match futex_key(uaddr) {
+        Ok(key) => key,
+        Err(errno) => return errno,
+    };
+    match op & !FUTEX_PRIVATE_FLAG {
+        FUTEX_WAIT => {
+            let deadline = match timeout {
+                0 => None,
+                timeout => {
+                    let timeout = copy_from_user(current_user_token(), timeout as *const TimeSpec);
+                    if timeout.nsec >= 1_000_000_000 {
+                        return -EINVAL;
+                    }
+                    Some(get_time_ms() + timeout.to_ms())
+                }
+            };
+            futex::wait(key, val, deadline)
+        }
+        FUTEX_WAKE => futex::wake(key, val as usize) as isize,
+        cmd @ (FUTEX_REQUEUE | FUTEX_CMP_REQUEUE) => {
+            let key2 = match futex_key(uaddr2) {
+                Ok(key2) => key2,
+                Err(errno) => return errno,
+            };
+            let cmp = (cmd == FUTEX_CMP_REQUEUE).then_some(val3);
+            futex::requeue(key, val as usize, key2, timeout, cmp)
+        }
+        _ => -ENOSYS,
+    }
+}
@@ -19,7 +19,7 @@ pub fn sys_thread_create(entry: usize, arg: usize) -> isize {
         true,
     ));
     // Set thread trap context.
-    let tcb_inner = tcb.inner_exclusive_access();
+    let mut tcb_inner = tcb.inner_exclusive_access();
     let user_sp = tcb_inner.res.as_ref().unwrap().user_stack_bottom();
     let trap_context = tcb_inner.trap_context();
     *trap_context = TrapContext::init_app_context(
@@ -31,6 +31,8 @@ pub fn sys_thread_create(entry: usize, arg: usize) -> isize {
     );
     //Set arg
     trap_context.x[10] = arg;
+    // The new thread inherits the scheduling priority.
+    tcb_inner.nice = current_task().unwrap().inner_exclusive_access().nice;
     drop(tcb_inner);
     // Add thread to process.
     let tid = tcb.tid();
@@ -62,7 +64,10 @@ pub fn sys_waittid(tid: usize) -> isize {
         {
             // Move out thread.
             let tcb = inner.threads[tid].take().unwrap();
-            let exit_code = tcb.inner_exclusive_access().exit_code.take().unwrap();
+            let mut tcb_inner = tcb.inner_exclusive_access();
+            let exit_code = tcb_inner.exit_code.take().unwrap();
+            inner.usage.exited_time += tcb_inner.usage.time;
+            drop(tcb_inner);
             exit_code as isize
         } else {
             -2
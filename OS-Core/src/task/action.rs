@@ -1,5 +1,10 @@
 use super::signal::{SignalFlags, MAX_SIG};
 
+/// Default signal handling.
+pub const SIG_DFL: usize = 0;
+/// Ignore the signal.
+pub const SIG_IGN: usize = 1;
+
 #[repr(C, align(16))]
 #[derive(Debug, Clone, Copy)]
 pub struct SignalAction {
@@ -15,6 +20,7 @@ impl SignalAction {
     }
 }
 
+#[derive(Clone)]
 pub struct SignalActions {
     pub table: [SignalAction; MAX_SIG + 1],
 }
@@ -25,4 +31,13 @@ impl SignalActions {
             table: [SignalAction::default(); MAX_SIG + 1],
         }
     }
+
+    /// Whether the signal is ignored by the process.
+    /// SIGKILL and SIGSTOP can never be ignored.
+    pub fn is_ignored(&self, signal: SignalFlags) -> bool {
+        if signal.intersects(SignalFlags::SIGKILL | SignalFlags::SIGSTOP) {
+            return false;
+        }
+        self.table[signal.signum() as usize].handler == SIG_IGN
+    }
 }
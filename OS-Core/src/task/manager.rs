@@ -1,26 +1,151 @@
 use alloc::{
-    collections::{BTreeMap, VecDeque},
+    boxed::Box,
+    collections::{BTreeMap, BinaryHeap, VecDeque},
     sync::{Arc, Weak},
+    vec::Vec,
 };
+use core::cmp::Ordering;
 use lazy_static::lazy_static;
 
-use crate::sync::UPSafeCell;
+use crate::{smp::wakeup_idle_hart, sync::SpinNoIrqLock};
 
 use super::{
     process::ProcessControlBlock,
     thread::{ThreadControlBlock, ThreadStatus},
 };
 
+/// Scheduling policy of the ready threads.
+pub trait Scheduler: Send + Sync {
+    /// Add a ready thread.
+    fn push(&mut self, thread: Arc<ThreadControlBlock>);
+    /// Take out the next thread to run.
+    fn pop(&mut self) -> Option<Arc<ThreadControlBlock>>;
+    /// Remove a thread which is not ready anymore.
+    fn remove(&mut self, thread: &Arc<ThreadControlBlock>);
+    /// No thread is ready.
+    fn is_empty(&self) -> bool;
+}
+
+/// Round robin scheduler, priority is ignored.
+pub struct FifoScheduler {
+    queue: VecDeque<Arc<ThreadControlBlock>>,
+}
+
+impl FifoScheduler {
+    pub fn new() -> Self {
+        Self {
+            queue: VecDeque::new(),
+        }
+    }
+}
+
+impl Scheduler for FifoScheduler {
+    fn push(&mut self, thread: Arc<ThreadControlBlock>) {
+        self.queue.push_back(thread);
+    }
+    fn pop(&mut self) -> Option<Arc<ThreadControlBlock>> {
+        self.queue.pop_front()
+    }
+    fn remove(&mut self, thread: &Arc<ThreadControlBlock>) {
+        self.queue.retain(|t| !Arc::ptr_eq(t, thread));
+    }
+    fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+}
+
+/// The pass value step of a thread whose weight is 1.
+const BIG_STRIDE: u64 = 1 << 32;
+
+struct StrideEntry {
+    pass: u64,
+    /// Threads with the same pass run in the order they are pushed.
+    order: u64,
+    thread: Arc<ThreadControlBlock>,
+}
+
+impl PartialEq for StrideEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+impl Eq for StrideEntry {}
+impl PartialOrd for StrideEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for StrideEntry {
+    /// Reverse order, so BinaryHeap pops the smallest pass.
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .pass
+            .cmp(&self.pass)
+            .then_with(|| other.order.cmp(&self.order))
+    }
+}
+
+/// Stride scheduler, the thread with the smallest pass runs next,
+/// and its pass grows by BIG_STRIDE / weight, so threads get cpu time in proportion to their weight.
+pub struct StrideScheduler {
+    heap: BinaryHeap<StrideEntry>,
+    /// The pass of the last thread taken out.
+    /// A thread which has been blocked for a long time starts from here,
+    /// so it can not take up the cpu to catch up.
+    min_pass: u64,
+    order: u64,
+}
+
+impl StrideScheduler {
+    pub fn new() -> Self {
+        Self {
+            heap: BinaryHeap::new(),
+            min_pass: 0,
+            order: 0,
+        }
+    }
+}
+
+impl Scheduler for StrideScheduler {
+    fn push(&mut self, thread: Arc<ThreadControlBlock>) {
+        let mut inner = thread.inner_exclusive_access();
+        inner.pass = inner.pass.max(self.min_pass);
+        let pass = inner.pass;
+        drop(inner);
+        self.order += 1;
+        self.heap.push(StrideEntry {
+            pass,
+            order: self.order,
+            thread,
+        });
+    }
+    fn pop(&mut self) -> Option<Arc<ThreadControlBlock>> {
+        let entry = self.heap.pop()?;
+        self.min_pass = entry.pass;
+        let mut inner = entry.thread.inner_exclusive_access();
+        inner.pass = entry.pass + BIG_STRIDE / inner.weight();
+        drop(inner);
+        Some(entry.thread)
+    }
+    fn remove(&mut self, thread: &Arc<ThreadControlBlock>) {
+        self.heap
+            .retain(|entry| !Arc::ptr_eq(&entry.thread, thread));
+    }
+    fn is_empty(&self) -> bool {
+        self.heap.is_empty()
+    }
+}
+
 pub struct TaskManager {
-    ready_queue: VecDeque<Arc<ThreadControlBlock>>,
+    ready_queue: Box<dyn Scheduler>,
     blocked_thread: BTreeMap<usize, Arc<ThreadControlBlock>>,
     all_pcb: BTreeMap<usize, Arc<ProcessControlBlock>>,
 }
 
 impl TaskManager {
-    pub fn new() -> Self {
+    pub fn new(ready_queue: Box<dyn Scheduler>) -> Self {
         Self {
-            ready_queue: VecDeque::new(),
+            ready_queue,
             blocked_thread: BTreeMap::new(),
             all_pcb: BTreeMap::new(),
         }
@@ -29,71 +154,119 @@ impl TaskManager {
         self.all_pcb.insert(proc.pid.0, proc.clone());
     }
     pub fn add_ready_thread(&mut self, thread: Arc<ThreadControlBlock>) {
-        self.ready_queue.push_back(thread);
+        self.ready_queue.push(thread);
     }
     pub fn add_block_thread(&mut self, thread: Arc<ThreadControlBlock>) {
+        let mut inner = thread.inner_exclusive_access();
+        inner.blocking = false;
+        if inner.wakeup_pending {
+            // Woken up before it is switched out.
+            inner.wakeup_pending = false;
+            inner.set_status(ThreadStatus::Ready);
+            drop(inner);
+            self.add_ready_thread(thread);
+            return;
+        }
+        drop(inner);
         let ptr = Arc::as_ptr(&thread) as usize;
         self.blocked_thread.insert(ptr, thread);
     }
+    /// Move a blocked thread to the ready queue.
+    /// If the thread has prepared to block but is not blocked yet, it is marked and won't be blocked.
+    /// A thread which is not waiting is left alone.
+    pub fn wakeup(&mut self, thread: Arc<ThreadControlBlock>) {
+        let ptr = Arc::as_ptr(&thread) as usize;
+        if let Some(thread) = self.blocked_thread.remove(&ptr) {
+            thread
+                .inner_exclusive_access()
+                .set_status(ThreadStatus::Ready);
+            self.add_ready_thread(thread);
+        } else {
+            let mut inner = thread.inner_exclusive_access();
+            if inner.blocking {
+                inner.wakeup_pending = true;
+            }
+        }
+    }
     pub fn remove_process(&mut self, pid: usize) {
         self.all_pcb.remove(&pid);
     }
     pub fn remove_thread(&mut self, thread: Arc<ThreadControlBlock>) {
-        self.ready_queue
-            .retain(|ptr| Arc::as_ptr(&ptr) != Arc::as_ptr(&thread));
+        self.ready_queue.remove(&thread);
         self.blocked_thread.remove(&(Arc::as_ptr(&thread) as usize));
     }
-    pub fn move_out_blocked_thread(&mut self, ptr: usize) -> Arc<ThreadControlBlock> {
-        self.blocked_thread.remove(&ptr).unwrap()
-    }
     pub fn fetch(&mut self) -> Option<Arc<ThreadControlBlock>> {
-        self.ready_queue.pop_front()
+        self.ready_queue.pop()
+    }
+    pub fn has_ready_thread(&self) -> bool {
+        !self.ready_queue.is_empty()
+    }
+}
+
+/// The scheduler chosen at build time, the stride scheduler unless the fifo-scheduler feature is on.
+fn default_scheduler() -> Box<dyn Scheduler> {
+    if cfg!(feature = "fifo-scheduler") {
+        Box::new(FifoScheduler::new())
+    } else {
+        Box::new(StrideScheduler::new())
     }
 }
 
 lazy_static! {
-    pub static ref TASK_MANAGER: UPSafeCell<TaskManager> =
-        unsafe { UPSafeCell::new(TaskManager::new()) };
+    pub static ref TASK_MANAGER: SpinNoIrqLock<TaskManager> =
+        SpinNoIrqLock::new(TaskManager::new(default_scheduler()));
 }
 
 pub fn add_proc(proc: Arc<ProcessControlBlock>) {
-    TASK_MANAGER.exclusive_access().add_process(proc);
+    TASK_MANAGER.lock().add_process(proc);
 }
 pub fn add_ready_thread(thread: Arc<ThreadControlBlock>) {
-    TASK_MANAGER.exclusive_access().add_ready_thread(thread);
+    TASK_MANAGER.lock().add_ready_thread(thread);
+    wakeup_idle_hart();
 }
 pub fn add_block_thread(thread: Arc<ThreadControlBlock>) {
-    TASK_MANAGER.exclusive_access().add_block_thread(thread);
+    TASK_MANAGER.lock().add_block_thread(thread);
 }
 pub fn remove_proc(pid: usize) {
-    TASK_MANAGER.exclusive_access().remove_process(pid);
+    TASK_MANAGER.lock().remove_process(pid);
 }
 pub fn remove_thread(thread: Arc<ThreadControlBlock>) {
-    TASK_MANAGER.exclusive_access().remove_thread(thread);
-}
-pub fn move_out_blocked_thread(ptr: usize) -> Arc<ThreadControlBlock> {
-    TASK_MANAGER.exclusive_access().move_out_blocked_thread(ptr)
+    TASK_MANAGER.lock().remove_thread(thread);
 }
 
 pub fn fetch_task() -> Option<Arc<ThreadControlBlock>> {
-    TASK_MANAGER.exclusive_access().fetch()
+    TASK_MANAGER.lock().fetch()
+}
+
+pub fn has_ready_thread() -> bool {
+    TASK_MANAGER.lock().has_ready_thread()
 }
 
 pub fn wakeup_thread(thread: Weak<ThreadControlBlock>) {
-    if thread.strong_count() != 0 {
-        let ptr = thread.as_ptr() as usize;
-        let thread = move_out_blocked_thread(ptr);
-        thread
-            .inner_exclusive_access()
-            .set_status(ThreadStatus::Ready);
-        add_ready_thread(thread);
+    if let Some(thread) = thread.upgrade() {
+        TASK_MANAGER.lock().wakeup(thread);
+        wakeup_idle_hart();
     }
 }
 
 pub fn proc_from_pid(pid: usize) -> Option<Arc<ProcessControlBlock>> {
+    TASK_MANAGER.lock().all_pcb.get(&pid).map(Arc::clone)
+}
+
+/// Get all processes in the process group.
+pub fn procs_in_group(pgid: usize) -> Vec<Arc<ProcessControlBlock>> {
+    all_procs()
+        .into_iter()
+        .filter(|proc| proc.inner_exclusive_access().pgid == pgid)
+        .collect()
+}
+
+/// Get all processes alive.
+pub fn all_procs() -> Vec<Arc<ProcessControlBlock>> {
     TASK_MANAGER
-        .exclusive_access()
+        .lock()
         .all_pcb
-        .get(&pid)
+        .values()
         .map(Arc::clone)
+        .collect()
 }
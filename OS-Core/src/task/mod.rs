@@ -1,13 +1,20 @@
-use alloc::sync::Arc;
+use alloc::{sync::Arc, vec::Vec};
 use lazy_static::lazy_static;
 
-use crate::fs::inode::{open_file, OpenFlags};
+use crate::{
+    fs::{tty::release_session, vfs::read_file},
+    smp::ack_tlb_flush,
+};
 
 use self::{
-    manager::{add_proc, add_ready_thread, remove_proc, remove_thread},
-    process::ProcessControlBlock,
-    processor::{current_process, schedule, schedule_block, take_current_task},
+    manager::{
+        add_proc, add_ready_thread, procs_in_group, remove_proc, remove_thread, wakeup_thread,
+    },
+    process::{JobEvent, ProcessControlBlock},
+    processor::{current_process, current_task, schedule, schedule_block, schedule_exit},
     signal::SignalFlags,
+    thread::ThreadStatus,
+    usage::CpuTime,
 };
 
 pub mod action;
@@ -19,14 +26,11 @@ pub mod res;
 pub mod signal;
 pub mod switch;
 pub mod thread;
+pub mod usage;
 
 lazy_static! {
-    pub static ref INITPROC: Arc<ProcessControlBlock> = ProcessControlBlock::new(
-        open_file("initproc", OpenFlags::READ_ONLY)
-            .unwrap()
-            .read_all()
-            .as_slice()
-    );
+    pub static ref INITPROC: Arc<ProcessControlBlock> =
+        ProcessControlBlock::new(read_file("/initproc").unwrap().as_slice());
 }
 
 pub fn add_initproc() {
@@ -49,10 +53,20 @@ pub fn exit_current_and_run_next(exit_code: i32) {
     //inner.memory_set.recycle_data_pages();
     //drop(inner);
 
-    let task = take_current_task().unwrap();
+    let task = current_task().unwrap();
     let tid = task.tid();
-    // Dealloc user resource.
-    task.inner_exclusive_access().res.take();
+    // Dealloc user resource, it locks the process so don't hold the thread lock.
+    let res = task.inner_exclusive_access().res.take();
+    drop(res);
+    if let Some(proc) = task.process.upgrade() {
+        let mut proc_inner = proc.inner_exclusive_access();
+        proc_inner.deadlock.remove_thread(tid);
+        let rwlocks: Vec<_> = proc_inner.rwlock_list.iter().flatten().cloned().collect();
+        drop(proc_inner);
+        for rwlock in rwlocks {
+            rwlock.remove_thread(tid, &Arc::downgrade(&task));
+        }
+    }
     // Set exit code to current thread.
     task.inner_exclusive_access().exit_code = Some(exit_code);
     // If main thread exit, then add all child process to init process, recycle all thread resource.
@@ -61,34 +75,56 @@ pub fn exit_current_and_run_next(exit_code: i32) {
         let mut proc_inner = proc.inner_exclusive_access();
         // Set current process to zombie process.
         proc_inner.is_zombie = true;
-        proc_inner.exit_code = exit_code;
-        // Add all child process to init process.
-        let mut initproc_inner = INITPROC.inner_exclusive_access();
-        for child in proc_inner.children.iter() {
-            child.inner_exclusive_access().parent = Some(Arc::downgrade(&INITPROC));
-            initproc_inner.children.push(child.clone());
+        // The controlling terminal is released when the session leader exits.
+        if proc_inner.sid == proc.pid() {
+            release_session(proc_inner.sid);
         }
-        drop(initproc_inner);
-        proc_inner.children.clear();
+        proc_inner.exit_code = exit_code;
+        let children = core::mem::take(&mut proc_inner.children);
         // Recycle all thread resource but main thread.
-        let mut temp = proc_inner.threads.clone();
+        let temp = proc_inner.threads.clone();
         proc_inner.threads.clear();
         // Delete fd_table
         proc_inner.fd_table.clear();
         drop(proc_inner);
-        // Recycle data page.
-        for i in 1..temp.len() {
-            if let Some(tcb) = temp[i].take() {
-                remove_thread(tcb);
-            }
+        // Add all child process to init process.
+        // Don't hold the lock of the current process, the init process may lock it in waitpid.
+        for child in children.iter() {
+            child.inner_exclusive_access().parent = Some(Arc::downgrade(&INITPROC));
+        }
+        INITPROC.inner_exclusive_access().children.extend(children);
+        for tcb in temp.iter().skip(1).flatten() {
+            remove_thread(tcb.clone());
+        }
+        // Threads running on other harts exit at their next trap,
+        // wait for them before recycling the memory they are using.
+        while temp
+            .iter()
+            .skip(1)
+            .flatten()
+            .any(|tcb| tcb.inner_exclusive_access().status == ThreadStatus::Running)
+        {
+            ack_tlb_flush();
+            core::hint::spin_loop();
+        }
+        // Cpu time of the threads not waited yet.
+        task.inner_exclusive_access().usage.leave_kernel();
+        let mut time = CpuTime::default();
+        for tcb in temp.iter().flatten() {
+            time += tcb.inner_exclusive_access().usage.time;
         }
+        proc.inner_exclusive_access().usage.exited_time += time;
+        drop(temp);
+        // Recycle data page.
         proc.inner_exclusive_access()
             .memory_set
             .recycle_data_pages();
         remove_proc(proc.pid());
         drop(proc);
     }
-    schedule();
+    // The thread is dropped by run_tasks after it is switched out.
+    drop(task);
+    schedule_exit();
 }
 
 pub fn suspended_current_and_run_next() {
@@ -98,10 +134,43 @@ pub fn suspended_current_and_run_next() {
 /// Block current thread and run next thread.
 /// This function will put the reference of the current thread control block into the global task manager.
 /// The caller only needs to save the weak reference of the thread control block.
+/// Block the current thread until it is woken up, prepare_block is called before.
 pub fn block_current_and_run_next() {
     schedule_block();
 }
 
+/// Prepare the current thread to block, before it checks the condition to wait for
+/// and adds itself to wait queues or timers. A wakeup from then on is kept,
+/// so the thread does not block if it is woken up before it is switched out.
+/// A wakeup kept from an earlier wait is dropped.
+pub fn prepare_block() {
+    let thread = current_task().unwrap();
+    let mut inner = thread.inner_exclusive_access();
+    inner.blocking = true;
+    inner.interruptible = false;
+    inner.wakeup_pending = false;
+}
+
+/// Prepare the current thread to block like prepare_block, and a signal sent to the process
+/// wakes it up. The thread checks check_current_signals_error after it prepares,
+/// and removes itself from the wait queues after it is woken up.
+pub fn prepare_interruptible_block() {
+    prepare_block();
+    current_task()
+        .unwrap()
+        .inner_exclusive_access()
+        .interruptible = true;
+}
+
+/// The current thread stops waiting without blocking after prepare_block, a wakeup kept is dropped.
+pub fn cancel_block() {
+    let thread = current_task().unwrap();
+    let mut inner = thread.inner_exclusive_access();
+    inner.blocking = false;
+    inner.interruptible = false;
+    inner.wakeup_pending = false;
+}
+
 pub fn get_pid() -> isize {
     current_process().pid() as isize
 }
@@ -117,3 +186,82 @@ pub fn check_current_signals_error() -> Option<(i32, &'static str)> {
         .signals
         .check_error()
 }
+
+/// Send a signal to the process.
+/// SIGCONT and SIGKILL continue a stopped process, signals ignored by the process are dropped.
+/// The signal waiters of the process are woken up, such as readers of signalfd,
+/// and so are its threads in interruptible waits if the signal is kept.
+pub fn send_signal(proc: &Arc<ProcessControlBlock>, signal: SignalFlags) {
+    let mut inner = proc.inner_exclusive_access();
+    let mut stopped_threads = Vec::new();
+    if signal.intersects(SignalFlags::SIGCONT | SignalFlags::SIGKILL) {
+        inner.signals.remove(SignalFlags::STOP_SIGNALS);
+        if inner.stopped {
+            inner.stopped = false;
+            inner.job_event = Some(JobEvent::Continued);
+            stopped_threads = core::mem::take(&mut inner.stopped_threads);
+        }
+    } else if signal.intersects(SignalFlags::STOP_SIGNALS) {
+        inner.signals.remove(SignalFlags::SIGCONT);
+    }
+    let mut interrupted = Vec::new();
+    if signal != SignalFlags::SIGCONT && !inner.signal_actions.is_ignored(signal) {
+        inner.signals.insert(signal);
+        interrupted = inner
+            .threads
+            .iter()
+            .flatten()
+            .filter(|thread| thread.inner_exclusive_access().interruptible)
+            .map(Arc::downgrade)
+            .collect();
+    }
+    drop(inner);
+    for thread in stopped_threads.into_iter().chain(interrupted) {
+        wakeup_thread(thread);
+    }
+    proc.signal_waiters.lock().wake_all();
+}
+
+/// Send a signal to all processes in the process group.
+/// # Return
+/// * false - the process group does not exist.
+pub fn send_signal_to_group(pgid: usize, signal: SignalFlags) -> bool {
+    let group = procs_in_group(pgid);
+    for proc in group.iter() {
+        send_signal(proc, signal);
+    }
+    !group.is_empty()
+}
+
+/// Handle pending stop signals of the current process,
+/// the current thread is blocked until the process is continued by SIGCONT or SIGKILL.
+pub fn handle_stop_signals() {
+    loop {
+        let proc = current_process();
+        let mut inner = proc.inner_exclusive_access();
+        if let Some(signum) = inner.signals.take_stop_signal() {
+            if !inner.stopped {
+                inner.stopped = true;
+                inner.job_event = Some(JobEvent::Stopped(signum));
+            }
+        }
+        if !inner.stopped {
+            return;
+        }
+        drop(inner);
+        // Check again after preparing to block, so SIGCONT sent in between is not lost.
+        prepare_block();
+        let mut inner = proc.inner_exclusive_access();
+        if !inner.stopped {
+            drop(inner);
+            cancel_block();
+            return;
+        }
+        inner
+            .stopped_threads
+            .push(Arc::downgrade(&current_task().unwrap()));
+        drop(inner);
+        drop(proc);
+        block_current_and_run_next();
+    }
+}
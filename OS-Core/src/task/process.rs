@@ -1,5 +1,3 @@
-use core::cell::RefMut;
-
 use alloc::{
     string::String,
     sync::{Arc, Weak},
@@ -13,22 +11,30 @@ use crate::{
         memory_set::{MemorySet, KERNEL_SPACE},
         page_table::PageTable,
     },
-    sync::{condvar::Condvar, mutex::Mutex, semaphore::Semaphore, UPSafeCell},
+    sync::{
+        barrier::Barrier, condvar::Condvar, deadlock::DeadlockDetector, mutex::Mutex,
+        rwlock::RwLock, semaphore::Semaphore, wait_queue::WaitQueue, SpinNoIrqGuard, SpinNoIrqLock,
+    },
     task::res::pid_alloc,
     trap::{trap_handler, TrapContext},
 };
 
 use super::{
+    action::SignalActions,
     manager::{add_proc, add_ready_thread},
     res::{IdAlloctor, PidHandle, SequenceAllocator},
     signal::SignalFlags,
     thread::ThreadControlBlock,
+    usage::{CpuTime, ProcessUsage},
 };
 
 pub struct ProcessControlBlock {
     pub pid: PidHandle,
     pub user_stack_base: usize,
-    pub inner: UPSafeCell<ProcessControlBlockInner>,
+    /// Woken up by every signal sent to the process, such as readers of signalfd.
+    /// It is not in the inner lock, so it is woken up without the inner lock held.
+    pub signal_waiters: SpinNoIrqLock<WaitQueue>,
+    pub inner: SpinNoIrqLock<ProcessControlBlockInner>,
 }
 impl ProcessControlBlock {
     /// Create a new process with main thread.
@@ -36,32 +42,43 @@ impl ProcessControlBlock {
         // Alloc pid and memory set for process.
         let (memory_set, user_stack_base, entry_point) = MemorySet::new_app_from_elf(elf_data);
         let pid = pid_alloc();
+        let pgid = pid.0;
         let pcb = Self {
             pid,
             user_stack_base,
-            inner: unsafe {
-                UPSafeCell::new(ProcessControlBlockInner {
-                    is_zombie: false,
-                    memory_set,
-                    parent: None,
-                    children: Vec::new(),
-                    exit_code: 0,
-                    fd_table: vec![
-                        //stdin
-                        Some(Arc::new(Stdin)),
-                        //stdout
-                        Some(Arc::new(Stdout)),
-                        //stderr
-                        Some(Arc::new(Stdout)),
-                    ],
-                    mutex_list: Vec::new(),
-                    semaphore_list: Vec::new(),
-                    condvar_list: Vec::new(),
-                    signals: SignalFlags::empty(),
-                    thread_res_allocator: SequenceAllocator::new(),
-                    threads: Vec::new(),
-                })
-            },
+            signal_waiters: SpinNoIrqLock::new(WaitQueue::new()),
+            inner: SpinNoIrqLock::new(ProcessControlBlockInner {
+                is_zombie: false,
+                memory_set,
+                parent: None,
+                children: Vec::new(),
+                exit_code: 0,
+                fd_table: vec![
+                    //stdin
+                    Some(FdEntry::new(Arc::new(Stdin), false)),
+                    //stdout
+                    Some(FdEntry::new(Arc::new(Stdout), false)),
+                    //stderr
+                    Some(FdEntry::new(Arc::new(Stdout), false)),
+                ],
+                mutex_list: Vec::new(),
+                semaphore_list: Vec::new(),
+                condvar_list: Vec::new(),
+                rwlock_list: Vec::new(),
+                barrier_list: Vec::new(),
+                deadlock: DeadlockDetector::default(),
+                signals: SignalFlags::empty(),
+                thread_res_allocator: SequenceAllocator::new(),
+                threads: Vec::new(),
+                pgid,
+                sid: pgid,
+                signal_actions: SignalActions::default(),
+                stopped: false,
+                job_event: None,
+                stopped_threads: Vec::new(),
+                usage: ProcessUsage::new(),
+                name: String::from("initproc"),
+            }),
         };
         let process = Arc::new(pcb);
         // Create the main thread.
@@ -78,7 +95,7 @@ impl ProcessControlBlock {
         *trap_context = TrapContext::init_app_context(
             entry_point,
             user_stack_bottom,
-            KERNEL_SPACE.exclusive_access().token(),
+            KERNEL_SPACE.lock().token(),
             kernel_stack_bottom,
             trap_handler as usize,
         );
@@ -94,12 +111,24 @@ impl ProcessControlBlock {
     }
     /// Replace the program of the current process with the specified program.
     /// Only support single thread process.
-    pub fn exec(self: &Arc<Self>, elf_data: &[u8], args: Vec<String>) {
+    pub fn exec(self: &Arc<Self>, path: &str, elf_data: &[u8], args: Vec<String>) {
         assert!(self.inner_exclusive_access().thread_count() == 1);
         // New memory set
         let (memory_set, user_stack_base, entry_point) = MemorySet::new_app_from_elf(elf_data);
         let token = memory_set.token();
-        self.inner_exclusive_access().memory_set = memory_set;
+        let mut inner = self.inner_exclusive_access();
+        inner.memory_set = memory_set;
+        inner.name = String::from(path);
+        // Close the fds with FD_CLOEXEC.
+        let closed: Vec<_> = inner
+            .fd_table
+            .iter_mut()
+            .filter(|entry| entry.as_ref().is_some_and(|entry| entry.cloexec))
+            .filter_map(Option::take)
+            .collect();
+        // The closed files may wake up other processes when they are dropped.
+        drop(inner);
+        drop(closed);
         // Alloc new thread user resource in new memory set.
         let main_thread = self.inner_exclusive_access().get_thread(0);
         let mut thread_inner = main_thread.inner_exclusive_access();
@@ -136,7 +165,7 @@ impl ProcessControlBlock {
         let mut trap_context = TrapContext::init_app_context(
             entry_point,
             user_sp,
-            KERNEL_SPACE.exclusive_access().token(),
+            KERNEL_SPACE.lock().token(),
             main_thread.kernel_stack.get_bottom(),
             trap_handler as usize,
         );
@@ -157,22 +186,32 @@ impl ProcessControlBlock {
         let child_proc = Arc::new(Self {
             pid: pid_handle,
             user_stack_base,
-            inner: unsafe {
-                UPSafeCell::new(ProcessControlBlockInner {
-                    is_zombie: false,
-                    memory_set,
-                    parent: Some(Arc::downgrade(self)),
-                    children: Vec::new(),
-                    exit_code: 0,
-                    fd_table,
-                    mutex_list: Vec::new(),
-                    semaphore_list: Vec::new(),
-                    condvar_list: Vec::new(),
-                    signals: SignalFlags::empty(),
-                    thread_res_allocator: SequenceAllocator::new(), // Single thread, don't need clone thread allocator
-                    threads: Vec::new(),
-                })
-            },
+            signal_waiters: SpinNoIrqLock::new(WaitQueue::new()),
+            inner: SpinNoIrqLock::new(ProcessControlBlockInner {
+                is_zombie: false,
+                memory_set,
+                parent: Some(Arc::downgrade(self)),
+                children: Vec::new(),
+                exit_code: 0,
+                fd_table,
+                mutex_list: Vec::new(),
+                semaphore_list: Vec::new(),
+                condvar_list: Vec::new(),
+                rwlock_list: Vec::new(),
+                barrier_list: Vec::new(),
+                deadlock: DeadlockDetector::default(),
+                signals: SignalFlags::empty(),
+                thread_res_allocator: SequenceAllocator::new(), // Single thread, don't need clone thread allocator
+                threads: Vec::new(),
+                pgid: parent_inner.pgid,
+                sid: parent_inner.sid,
+                signal_actions: parent_inner.signal_actions.clone(),
+                stopped: false,
+                job_event: None,
+                stopped_threads: Vec::new(),
+                usage: ProcessUsage::new(),
+                name: parent_inner.name.clone(),
+            }),
         });
         // Create main thread for child process.
         let parent_main_thread = parent_inner.get_thread(0);
@@ -183,10 +222,11 @@ impl ProcessControlBlock {
             false,
         ));
         let kernel_stack_bottom = child_main_thread.kernel_stack.get_bottom();
-        child_main_thread
-            .inner_exclusive_access()
-            .trap_context()
-            .kernel_sp = kernel_stack_bottom;
+        let mut child_thread_inner = child_main_thread.inner_exclusive_access();
+        child_thread_inner.trap_context().kernel_sp = kernel_stack_bottom;
+        // The child inherits the scheduling priority.
+        child_thread_inner.nice = parent_thread_inner.nice;
+        drop(child_thread_inner);
         drop(parent_thread_inner);
         // Push child main thread to child process.
         child_proc
@@ -202,9 +242,19 @@ impl ProcessControlBlock {
     }
     /// Create a child process and run the elf program.
     /// Only support single thread process.
-    pub fn spawn(self: &Arc<ProcessControlBlock>, elf_data: &[u8], args: Vec<String>) -> usize {
+    pub fn spawn(
+        self: &Arc<ProcessControlBlock>,
+        path: &str,
+        elf_data: &[u8],
+        args: Vec<String>,
+    ) -> usize {
         assert!(self.inner_exclusive_access().thread_count() == 1);
-        let fd_table = self.inner_exclusive_access().fd_table.clone();
+        let parent_inner = self.inner_exclusive_access();
+        let fd_table = parent_inner.fd_table.clone();
+        let (pgid, sid) = (parent_inner.pgid, parent_inner.sid);
+        let signal_actions = parent_inner.signal_actions.clone();
+        let nice = parent_inner.get_thread(0).inner_exclusive_access().nice;
+        drop(parent_inner);
         // Alloc pid and memory set for process.
         let (memory_set, user_stack_base, entry_point) = MemorySet::new_app_from_elf(elf_data);
         let pid = pid_alloc();
@@ -213,22 +263,32 @@ impl ProcessControlBlock {
         let child_proc = Arc::new(Self {
             pid,
             user_stack_base,
-            inner: unsafe {
-                UPSafeCell::new(ProcessControlBlockInner {
-                    is_zombie: false,
-                    memory_set,
-                    parent: None,
-                    children: Vec::new(),
-                    exit_code: 0,
-                    fd_table,
-                    mutex_list: Vec::new(),
-                    semaphore_list: Vec::new(),
-                    condvar_list: Vec::new(),
-                    signals: SignalFlags::empty(),
-                    thread_res_allocator: SequenceAllocator::new(),
-                    threads: Vec::new(),
-                })
-            },
+            signal_waiters: SpinNoIrqLock::new(WaitQueue::new()),
+            inner: SpinNoIrqLock::new(ProcessControlBlockInner {
+                is_zombie: false,
+                memory_set,
+                parent: Some(Arc::downgrade(self)),
+                children: Vec::new(),
+                exit_code: 0,
+                fd_table,
+                mutex_list: Vec::new(),
+                semaphore_list: Vec::new(),
+                condvar_list: Vec::new(),
+                rwlock_list: Vec::new(),
+                barrier_list: Vec::new(),
+                deadlock: DeadlockDetector::default(),
+                signals: SignalFlags::empty(),
+                thread_res_allocator: SequenceAllocator::new(),
+                threads: Vec::new(),
+                pgid,
+                sid,
+                signal_actions,
+                stopped: false,
+                job_event: None,
+                stopped_threads: Vec::new(),
+                usage: ProcessUsage::new(),
+                name: String::from(path),
+            }),
         });
         // Create the main thread.
         let main_thread = Arc::new(ThreadControlBlock::new(
@@ -236,7 +296,8 @@ impl ProcessControlBlock {
             user_stack_base,
             true,
         ));
-        let thread_inner = main_thread.inner_exclusive_access();
+        let mut thread_inner = main_thread.inner_exclusive_access();
+        thread_inner.nice = nice;
         let mut user_sp = thread_inner.res.as_ref().unwrap().user_stack_bottom();
 
         // Put arguments to user stack
@@ -271,7 +332,7 @@ impl ProcessControlBlock {
         *trap_context = TrapContext::init_app_context(
             entry_point,
             user_sp,
-            KERNEL_SPACE.exclusive_access().token(),
+            KERNEL_SPACE.lock().token(),
             kernel_stack_bottom,
             trap_handler as usize,
         );
@@ -280,6 +341,10 @@ impl ProcessControlBlock {
             .inner_exclusive_access()
             .threads
             .push(Some(main_thread.clone()));
+        // Add child process
+        self.inner_exclusive_access()
+            .children
+            .push(child_proc.clone());
         // Add process and thread to manager
         add_proc(child_proc.clone());
         add_ready_thread(main_thread);
@@ -291,45 +356,115 @@ impl ProcessControlBlock {
     pub fn pid(&self) -> usize {
         self.pid.0
     }
-    pub fn inner_exclusive_access(&self) -> RefMut<'_, ProcessControlBlockInner> {
-        self.inner.exclusive_access()
+    pub fn inner_exclusive_access(&self) -> SpinNoIrqGuard<'_, ProcessControlBlockInner> {
+        self.inner.lock()
     }
 }
 
+/// The max number of fds a process can open.
+pub const FD_MAX: usize = 1024;
+
+/// An entry of the fd table, the open file and the flags of the descriptor.
+/// Dup shares the file but not the flags.
+#[derive(Clone)]
+pub struct FdEntry {
+    pub file: Arc<dyn File + Send + Sync>,
+    /// FD_CLOEXEC, the fd is closed when the process executes a new program.
+    pub cloexec: bool,
+}
+
+impl FdEntry {
+    pub fn new(file: Arc<dyn File + Send + Sync>, cloexec: bool) -> Self {
+        Self { file, cloexec }
+    }
+}
+
+/// Job control state change of a process, reported to the parent by waitpid.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum JobEvent {
+    /// Stopped by the signal.
+    Stopped(u32),
+    /// Continued by SIGCONT.
+    Continued,
+}
+
 pub struct ProcessControlBlockInner {
     pub is_zombie: bool,
     pub memory_set: MemorySet,
     pub parent: Option<Weak<ProcessControlBlock>>,
     pub children: Vec<Arc<ProcessControlBlock>>,
     pub exit_code: i32,
-    pub fd_table: Vec<Option<Arc<dyn File + Send + Sync>>>,
+    pub fd_table: Vec<Option<FdEntry>>,
     pub mutex_list: Vec<Option<Arc<dyn Mutex>>>,
     pub semaphore_list: Vec<Option<Arc<Semaphore>>>,
     pub condvar_list: Vec<Option<Arc<Condvar>>>,
+    pub rwlock_list: Vec<Option<Arc<RwLock>>>,
+    pub barrier_list: Vec<Option<Arc<Barrier>>>,
+    /// Deadlock detection of mutex_list and semaphore_list.
+    pub deadlock: DeadlockDetector,
     pub signals: SignalFlags,
     pub thread_res_allocator: SequenceAllocator,
     pub threads: Vec<Option<Arc<ThreadControlBlock>>>,
+    /// Process group id.
+    pub pgid: usize,
+    /// Session id.
+    pub sid: usize,
+    pub signal_actions: SignalActions,
+    /// The process is stopped by a job control signal.
+    pub stopped: bool,
+    /// Job control state change not yet reported to the parent.
+    pub job_event: Option<JobEvent>,
+    /// Threads blocked because the process is stopped.
+    pub stopped_threads: Vec<Weak<ThreadControlBlock>>,
+    /// Cpu time and syscall counts.
+    pub usage: ProcessUsage,
+    /// Path of the program running in the process.
+    pub name: String,
 }
 impl ProcessControlBlockInner {
     pub fn user_token(&self) -> usize {
         self.memory_set.token()
     }
-    pub fn alloc_fd(&mut self) -> usize {
-        if let Some(fd) = (0..self.fd_table.len()).find(|fd| self.fd_table[*fd].is_none()) {
-            fd
-        } else {
-            self.fd_table.push(None);
-            self.fd_table.len() - 1
+    /// Cpu time of all threads of the process, children not included.
+    pub fn cpu_time(&self) -> CpuTime {
+        let mut time = self.usage.exited_time;
+        for thread in self.threads.iter().flatten() {
+            time += thread.inner_exclusive_access().usage.time;
         }
+        time
     }
-    pub fn open_file(&mut self, file: Arc<dyn File + Send + Sync>) -> usize {
-        if let Some(fd) = (0..self.fd_table.len()).find(|fd| self.fd_table[*fd].is_none()) {
-            self.fd_table[fd] = Some(file);
-            fd
-        } else {
-            self.fd_table.push(Some(file));
-            self.fd_table.len() - 1
+    /// Find the lowest free fd which is not less than min_fd.
+    /// # Return
+    /// * None - all fds are in use.
+    pub fn alloc_fd(&mut self, min_fd: usize) -> Option<usize> {
+        let fd =
+            (min_fd..FD_MAX).find(|fd| self.fd_table.get(*fd).map_or(true, Option::is_none))?;
+        if fd >= self.fd_table.len() {
+            self.fd_table.resize(fd + 1, None);
+        }
+        Some(fd)
+    }
+    /// Open the file on the lowest free fd.
+    /// # Parameter
+    /// * 'cloexec' - FD_CLOEXEC of the new fd
+    /// # Return
+    /// * None - all fds are in use.
+    pub fn open_file(&mut self, file: Arc<dyn File + Send + Sync>, cloexec: bool) -> Option<usize> {
+        let fd = self.alloc_fd(0)?;
+        self.fd_table[fd] = Some(FdEntry::new(file, cloexec));
+        Some(fd)
+    }
+    /// Put the entry on the fd, the file opened on it is closed.
+    /// The fd must be less than FD_MAX.
+    pub fn set_fd(&mut self, fd: usize, entry: FdEntry) {
+        if fd >= self.fd_table.len() {
+            self.fd_table.resize(fd + 1, None);
         }
+        self.fd_table[fd] = Some(entry);
+    }
+    /// Get the file opened on the fd.
+    pub fn get_file(&self, fd: usize) -> Option<Arc<dyn File + Send + Sync>> {
+        Some(self.fd_table.get(fd)?.as_ref()?.file.clone())
     }
     pub fn add_mutex(&mut self, mutex: Arc<dyn Mutex>) -> usize {
         if let Some(mutex_id) =
@@ -364,6 +499,28 @@ impl ProcessControlBlockInner {
             self.condvar_list.len() - 1
         }
     }
+    pub fn add_rwlock(&mut self, rwlock: Arc<RwLock>) -> usize {
+        if let Some(rwlock_id) =
+            (0..self.rwlock_list.len()).find(|&rwlock_id| self.rwlock_list[rwlock_id].is_none())
+        {
+            self.rwlock_list[rwlock_id] = Some(rwlock);
+            rwlock_id
+        } else {
+            self.rwlock_list.push(Some(rwlock));
+            self.rwlock_list.len() - 1
+        }
+    }
+    pub fn add_barrier(&mut self, barrier: Arc<Barrier>) -> usize {
+        if let Some(barrier_id) =
+            (0..self.barrier_list.len()).find(|&barrier_id| self.barrier_list[barrier_id].is_none())
+        {
+            self.barrier_list[barrier_id] = Some(barrier);
+            barrier_id
+        } else {
+            self.barrier_list.push(Some(barrier));
+            self.barrier_list.len() - 1
+        }
+    }
     pub fn alloc_tid(&mut self) -> usize {
         self.thread_res_allocator.alloc()
     }
@@ -385,4 +542,10 @@ impl ProcessControlBlockInner {
     pub fn get_condvar(&self, condvar_id: usize) -> Arc<Condvar> {
         self.condvar_list[condvar_id].as_ref().unwrap().clone()
     }
+    pub fn get_rwlock(&self, rwlock_id: usize) -> Option<Arc<RwLock>> {
+        self.rwlock_list.get(rwlock_id)?.clone()
+    }
+    pub fn get_barrier(&self, barrier_id: usize) -> Option<Arc<Barrier>> {
+        self.barrier_list.get(barrier_id)?.clone()
+    }
 }
@@ -1,8 +1,14 @@
-use crate::{sync::UPSafeCell, trap::TrapContext};
+use crate::{
+    net::iface::poll_if_due,
+    platfrom::MAX_HARTS,
+    smp::{hart_id, set_idle},
+    sync::{SpinNoIrqGuard, SpinNoIrqLock},
+    trap::{wait_for_interrupt, TrapContext},
+};
 
 use super::{
     context::TaskContext,
-    manager::{add_block_thread, add_ready_thread, fetch_task},
+    manager::{add_block_thread, add_ready_thread, fetch_task, has_ready_thread},
     process::ProcessControlBlock,
     switch::__switch,
     thread::{ThreadControlBlock, ThreadStatus},
@@ -12,6 +18,9 @@ use lazy_static::lazy_static;
 
 pub struct Processor {
     current: Option<Arc<ThreadControlBlock>>,
+    /// The thread just switched out, it is put back to the task manager by run_tasks
+    /// after its task context is saved, so no other hart can run it before that.
+    switched_out: Option<Arc<ThreadControlBlock>>,
     processor_task_context: TaskContext,
 }
 
@@ -19,6 +28,7 @@ impl Processor {
     pub fn new() -> Self {
         Self {
             current: None,
+            switched_out: None,
             processor_task_context: TaskContext::zero_init(),
         }
     }
@@ -41,16 +51,23 @@ impl Processor {
 }
 
 lazy_static! {
-    pub static ref PROCESSOR: UPSafeCell<Processor> = unsafe { UPSafeCell::new(Processor::new()) };
+    /// One processor for every hart.
+    pub static ref PROCESSORS: [SpinNoIrqLock<Processor>; MAX_HARTS] =
+        core::array::from_fn(|_| SpinNoIrqLock::new(Processor::new()));
+}
+
+/// Get the processor of the current hart.
+pub fn current_processor() -> SpinNoIrqGuard<'static, Processor> {
+    PROCESSORS[hart_id()].lock()
 }
 
 /// Take current thread from processor
 pub fn take_current_task() -> Option<Arc<ThreadControlBlock>> {
-    PROCESSOR.exclusive_access().take_current()
+    current_processor().take_current()
 }
 /// Get a current tcb copy
 pub fn current_task() -> Option<Arc<ThreadControlBlock>> {
-    PROCESSOR.exclusive_access().current()
+    current_processor().current()
 }
 /// Get current process.
 pub fn current_process() -> Arc<ProcessControlBlock> {
@@ -78,55 +95,82 @@ pub fn current_trap_context_va() -> usize {
 /// Take out a task from the task scheduling queue,
 /// set the task status to Running,
 /// and then switch to the task running.
+/// When the task is switched out, put it back to the task manager according to its status.
+/// Wait for interrupt if there is no task to run.
 pub fn run_tasks() {
     loop {
+        // The network stack is polled here instead of in the timer interrupt.
+        poll_if_due();
         if let Some(tcb) = fetch_task() {
             let mut next_task_inner = tcb.inner_exclusive_access();
             let next_task = next_task_inner.task_context_ptr_mut();
-            let mut processor = PROCESSOR.exclusive_access();
-            let processor_task = processor.get_processor_task_context();
             next_task_inner.set_status(ThreadStatus::Running);
+            next_task_inner.usage.resume();
             drop(next_task_inner);
+            // The process exited while the thread was taken out of the ready queue.
+            if tcb.process_exited() {
+                tcb.inner_exclusive_access()
+                    .set_status(ThreadStatus::Exited);
+                continue;
+            }
+            let mut processor = current_processor();
+            let processor_task = processor.get_processor_task_context();
             processor.set_current(Some(tcb));
             drop(processor);
             unsafe { __switch(processor_task, next_task) };
+            let switched_out = current_processor().switched_out.take();
+            if let Some(tcb) = switched_out {
+                let status = tcb.inner_exclusive_access().status;
+                let exited = status == ThreadStatus::Exited || tcb.process_exited();
+                match status {
+                    ThreadStatus::Ready if !exited => add_ready_thread(tcb),
+                    ThreadStatus::Blocked if !exited => add_block_thread(tcb),
+                    // The last reference of an exited thread is dropped here,
+                    // its kernel stack is not used any more.
+                    _ => tcb
+                        .inner_exclusive_access()
+                        .set_status(ThreadStatus::Exited),
+                }
+            }
+        } else {
+            set_idle(true);
+            // No IPI is sent for a thread made ready before the hart is marked idle.
+            if !has_ready_thread() {
+                wait_for_interrupt();
+            }
+            set_idle(false);
         }
     }
 }
 
+/// Set the status of the current thread and switch to the processor task (run_tasks function).
+fn switch_out(status: ThreadStatus) {
+    let mut processor = current_processor();
+    let curr_tcb = processor.take_current().unwrap();
+    let mut tcb_inner = curr_tcb.inner_exclusive_access();
+    tcb_inner.set_status(status);
+    tcb_inner.usage.leave_kernel();
+    let curr_task = tcb_inner.task_context_ptr_mut();
+    drop(tcb_inner);
+    processor.switched_out = Some(curr_tcb);
+    let processor_task = processor.get_processor_task_context();
+    drop(processor);
+    unsafe { __switch(curr_task, processor_task) };
+}
+
 /// Switch the currently running task to the Ready status,
 /// put it in the task scheduling queue,
 /// and then switch to the processor task (run_tasks function).
 pub fn schedule() {
-    let curr_task;
-    if let Some(curr_tcb) = take_current_task() {
-        let mut tcb_inner = curr_tcb.inner_exclusive_access();
-        tcb_inner.set_status(ThreadStatus::Ready);
-        curr_task = tcb_inner.task_context_ptr_mut();
-        drop(tcb_inner);
-        add_ready_thread(curr_tcb);
-    } else {
-        curr_task = (&mut TaskContext::zero_init()) as *mut TaskContext;
-    }
-    let mut processor = PROCESSOR.exclusive_access();
-    let processor_task = processor.get_processor_task_context();
-    drop(processor);
-    unsafe { __switch(curr_task, processor_task) };
+    switch_out(ThreadStatus::Ready);
 }
 
+/// Switch the currently running task to the Blocked status.
 pub fn schedule_block() {
-    let curr_task;
-    if let Some(curr_tcb) = take_current_task() {
-        let mut tcb_inner = curr_tcb.inner_exclusive_access();
-        tcb_inner.set_status(ThreadStatus::Blocked);
-        curr_task = tcb_inner.task_context_ptr_mut();
-        drop(tcb_inner);
-        add_block_thread(curr_tcb);
-    } else {
-        curr_task = (&mut TaskContext::zero_init()) as *mut TaskContext;
-    }
-    let mut processor = PROCESSOR.exclusive_access();
-    let processor_task = processor.get_processor_task_context();
-    drop(processor);
-    unsafe { __switch(curr_task, processor_task) };
+    switch_out(ThreadStatus::Blocked);
+}
+
+/// Switch out the current thread which has exited, it will never run again.
+pub fn schedule_exit() {
+    switch_out(ThreadStatus::Exited);
 }
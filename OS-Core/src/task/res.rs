@@ -11,7 +11,7 @@ use crate::{
         address::{PhysPageNum, VirtAddr, VirtPageNum},
         memory_set::{MapPermission, KERNEL_SPACE},
     },
-    sync::UPSafeCell,
+    sync::SpinNoIrqLock,
 };
 
 use super::process::ProcessControlBlock;
@@ -58,22 +58,22 @@ type KernelStackAllocator = SequenceAllocator;
 
 lazy_static! {
     /// Global pid allocator.
-    static ref PID_ALLOCATOR: UPSafeCell<PidAllocator> =
-        unsafe { UPSafeCell::new(PidAllocator::new()) };
+    static ref PID_ALLOCATOR: SpinNoIrqLock<PidAllocator> =
+        SpinNoIrqLock::new(PidAllocator::new());
     /// Global KernelStack allocator.
-    static ref KERNEL_STACK_ALLOCATOR: UPSafeCell<KernelStackAllocator> =
-        unsafe { UPSafeCell::new(KernelStackAllocator::new()) };
+    static ref KERNEL_STACK_ALLOCATOR: SpinNoIrqLock<KernelStackAllocator> =
+        SpinNoIrqLock::new(KernelStackAllocator::new());
 }
 /// RAII style pid encapsulation.
 pub struct PidHandle(pub usize);
 impl Drop for PidHandle {
     fn drop(&mut self) {
-        PID_ALLOCATOR.exclusive_access().dealloc(self.0);
+        PID_ALLOCATOR.lock().dealloc(self.0);
     }
 }
 /// Alloc a new pid.
 pub fn pid_alloc() -> PidHandle {
-    PidHandle(PID_ALLOCATOR.exclusive_access().alloc())
+    PidHandle(PID_ALLOCATOR.lock().alloc())
 }
 
 /// RAII style KernelStack encapsulation.
@@ -94,9 +94,9 @@ impl Drop for KernelStack {
     fn drop(&mut self) {
         let (top, _) = kernel_stack_position(self.0);
         KERNEL_SPACE
-            .exclusive_access()
+            .lock()
             .remove_area_whith_start_vpn(VirtAddr::from(top).into());
-        KERNEL_STACK_ALLOCATOR.exclusive_access().dealloc(self.0)
+        KERNEL_STACK_ALLOCATOR.lock().dealloc(self.0)
     }
 }
 impl KernelStack {
@@ -116,9 +116,9 @@ impl KernelStack {
 }
 /// Alloc a new kernel stack
 pub fn kernel_stack_alloc() -> KernelStack {
-    let stack_id = KERNEL_STACK_ALLOCATOR.exclusive_access().alloc();
+    let stack_id = KERNEL_STACK_ALLOCATOR.lock().alloc();
     let (top, bottom) = kernel_stack_position(stack_id);
-    KERNEL_SPACE.exclusive_access().insert_framed_area(
+    KERNEL_SPACE.lock().insert_framed_area(
         top.into(),
         bottom.into(),
         MapPermission::R | MapPermission::W,
@@ -176,7 +176,10 @@ impl ThreadUserRes {
 
     /// Dealloc tid, unmap user stack and trap page.
     pub fn dealloc_res(&self) {
-        let proc = self.process.upgrade().unwrap();
+        // Nothing to do if the process has been recycled.
+        let Some(proc) = self.process.upgrade() else {
+            return;
+        };
         let mut proc_inner = proc.inner_exclusive_access();
         // dealloc tid
         proc_inner.dealloc_tid(self.tid);
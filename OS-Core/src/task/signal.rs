@@ -38,6 +38,30 @@ bitflags! {
 }
 
 impl SignalFlags {
+    /// Signals whose default action is to stop the process.
+    pub const STOP_SIGNALS: Self = Self::SIGSTOP
+        .union(Self::SIGTSTP)
+        .union(Self::SIGTTIN)
+        .union(Self::SIGTTOU);
+
+    /// Get the signal number of a single signal.
+    pub fn signum(&self) -> u32 {
+        self.bits().trailing_zeros()
+    }
+
+    /// Take out a pending stop signal, all pending stop signals are cleared.
+    /// # Return
+    /// * The signal number of the stop signal, None if no stop signal pending.
+    pub fn take_stop_signal(&mut self) -> Option<u32> {
+        let stop = self.intersection(Self::STOP_SIGNALS);
+        if stop.is_empty() {
+            None
+        } else {
+            self.remove(Self::STOP_SIGNALS);
+            Some(stop.signum())
+        }
+    }
+
     /// Check error signal, return error code and infomation.
     pub fn check_error(&self) -> Option<(i32, &'static str)> {
         if self.contains(Self::SIGINT) {
@@ -52,6 +76,8 @@ impl SignalFlags {
             Some((-9, "Killed, SIGKILL=9"))
         } else if self.contains(Self::SIGSEGV) {
             Some((-11, "Segmentation Fault, SIGSEGV=11"))
+        } else if self.contains(Self::SIGPIPE) {
+            Some((-13, "Broken Pipe, SIGPIPE=13"))
         } else {
             None
         }
@@ -1,25 +1,33 @@
-use core::cell::RefMut;
-
-use alloc::sync::{Arc, Weak};
+use alloc::{
+    collections::BTreeMap,
+    sync::{Arc, Weak},
+};
 
-use crate::{mm::address::PhysPageNum, sync::UPSafeCell, trap::TrapContext};
+use crate::{
+    mm::address::PhysPageNum,
+    sync::{SpinNoIrqGuard, SpinNoIrqLock},
+    trap::TrapContext,
+};
 
 use super::{
     context::TaskContext,
     process::ProcessControlBlock,
     res::{kernel_stack_alloc, KernelStack, ThreadUserRes},
+    usage::ThreadUsage,
 };
 
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub enum ThreadStatus {
     Ready,
     Running,
     Blocked,
+    Exited,
 }
 
 pub struct ThreadControlBlock {
     pub process: Weak<ProcessControlBlock>,
     pub kernel_stack: KernelStack,
-    inner: UPSafeCell<ThreadControlBlockInner>,
+    inner: SpinNoIrqLock<ThreadControlBlockInner>,
 }
 impl ThreadControlBlock {
     /// Create a new thread
@@ -31,15 +39,20 @@ impl ThreadControlBlock {
         Self {
             process: Arc::downgrade(&process),
             kernel_stack,
-            inner: unsafe {
-                UPSafeCell::new(ThreadControlBlockInner {
-                    res: Some(res),
-                    trap_context_ppn,
-                    task_context: TaskContext::goto_trap_return(kernel_stack_buttom),
-                    status: ThreadStatus::Ready,
-                    exit_code: None,
-                })
-            },
+            inner: SpinNoIrqLock::new(ThreadControlBlockInner {
+                res: Some(res),
+                trap_context_ppn,
+                task_context: TaskContext::goto_trap_return(kernel_stack_buttom),
+                status: ThreadStatus::Ready,
+                exit_code: None,
+                nice: 0,
+                inherited_nice: BTreeMap::new(),
+                pass: 0,
+                blocking: false,
+                interruptible: false,
+                wakeup_pending: false,
+                usage: ThreadUsage::new(),
+            }),
         }
     }
 
@@ -51,8 +64,16 @@ impl ThreadControlBlock {
         self.inner_exclusive_access().res.as_ref().unwrap().tid
     }
 
-    pub fn inner_exclusive_access(&self) -> RefMut<'_, ThreadControlBlockInner> {
-        self.inner.exclusive_access()
+    /// Whether the main thread of the process has exited,
+    /// other threads of the process should not run any more.
+    pub fn process_exited(&self) -> bool {
+        self.process
+            .upgrade()
+            .map_or(true, |proc| proc.inner_exclusive_access().is_zombie)
+    }
+
+    pub fn inner_exclusive_access(&self) -> SpinNoIrqGuard<'_, ThreadControlBlockInner> {
+        self.inner.lock()
     }
 }
 
@@ -62,6 +83,22 @@ pub struct ThreadControlBlockInner {
     pub task_context: TaskContext,
     pub status: ThreadStatus,
     pub exit_code: Option<i32>,
+    /// Scheduling priority, from NICE_MIN (highest) to NICE_MAX (lowest).
+    pub nice: isize,
+    /// Priorities inherited from the threads waiting on the priority inheritance mutexes it holds,
+    /// by the address of the mutex.
+    pub inherited_nice: BTreeMap<usize, isize>,
+    /// Stride scheduling pass value, the thread with the smallest pass runs first.
+    pub pass: u64,
+    /// The thread has prepared to block and is not put in the blocked list yet.
+    pub blocking: bool,
+    /// The thread is in a wait which a signal sent to the process stops.
+    pub interruptible: bool,
+    /// The thread is woken up after it prepares to block and before it is put in the blocked list,
+    /// it will be put back to the ready queue instead of blocking.
+    pub wakeup_pending: bool,
+    /// Cpu time of the thread.
+    pub usage: ThreadUsage,
 }
 impl ThreadControlBlockInner {
     pub fn trap_context(&self) -> &'static mut TrapContext {
@@ -87,4 +124,30 @@ impl ThreadControlBlockInner {
     pub fn set_status(&mut self, status: ThreadStatus) {
         self.status = status;
     }
+
+    /// The nice value it is scheduled by, the highest of its own and the inherited ones.
+    pub fn effective_nice(&self) -> isize {
+        self.inherited_nice
+            .values()
+            .copied()
+            .fold(self.nice, isize::min)
+    }
+
+    /// The share of cpu time of the thread, nice 0 is 1024,
+    /// every nice level is about 10% cpu time.
+    pub fn weight(&self) -> u64 {
+        NICE_TO_WEIGHT[(self.effective_nice() - NICE_MIN) as usize]
+    }
 }
+
+/// The highest priority.
+pub const NICE_MIN: isize = -20;
+/// The lowest priority.
+pub const NICE_MAX: isize = 19;
+
+/// Weight of every nice level, the same as the linux CFS scheduler.
+const NICE_TO_WEIGHT: [u64; 40] = [
+    88761, 71755, 56483, 46273, 36291, 29154, 23254, 18705, 14949, 11916, 9548, 7620, 6100, 4904,
+    3906, 3121, 2501, 1991, 1586, 1277, 1024, 820, 655, 526, 423, 335, 272, 215, 172, 137, 110, 87,
+    70, 56, 45, 36, 29, 23, 18, 15,
+];
@@ -0,0 +1,182 @@
+//! Resource accounting of threads and processes.
+//! Cpu time is counted when a thread traps into the kernel, returns to user mode,
+//! or is switched out, so a thread is never charged for the time it is not running.
+use alloc::{boxed::Box, vec};
+use core::ops::AddAssign;
+
+use crate::{
+    config::TICKS_PER_SEC,
+    platfrom::CLOCK_FREQ,
+    timer::{get_time, get_time_ms},
+};
+
+/// Syscalls whose id is less than it are reported by task_info,
+/// it is larger than all syscall ids.
+pub const MAX_SYSCALL_NUM: usize = 1100;
+
+/// getrusage: the calling process.
+pub const RUSAGE_SELF: isize = 0;
+/// getrusage: the children which have been waited.
+pub const RUSAGE_CHILDREN: isize = -1;
+/// getrusage: the calling thread.
+pub const RUSAGE_THREAD: isize = 1;
+
+/// Cpu time in timer cycles.
+#[derive(Clone, Copy, Default)]
+pub struct CpuTime {
+    pub user: usize,
+    pub kernel: usize,
+}
+
+impl AddAssign for CpuTime {
+    fn add_assign(&mut self, rhs: Self) {
+        self.user += rhs.user;
+        self.kernel += rhs.kernel;
+    }
+}
+
+/// Cpu time of a thread.
+pub struct ThreadUsage {
+    pub time: CpuTime,
+    /// When the thread entered the mode it is running in.
+    last: usize,
+}
+
+impl ThreadUsage {
+    pub fn new() -> Self {
+        Self {
+            time: CpuTime::default(),
+            last: get_time(),
+        }
+    }
+
+    /// The thread starts running on a hart, in kernel mode.
+    pub fn resume(&mut self) {
+        self.last = get_time();
+    }
+
+    /// The thread traps from user mode.
+    pub fn enter_kernel(&mut self) {
+        let now = get_time();
+        self.time.user += now - self.last;
+        self.last = now;
+    }
+
+    /// The thread returns to user mode or is switched out.
+    /// It is also called to count the kernel time until now.
+    pub fn leave_kernel(&mut self) {
+        let now = get_time();
+        self.time.kernel += now - self.last;
+        self.last = now;
+    }
+}
+
+/// Resource usage of a process.
+pub struct ProcessUsage {
+    /// When the process was created, in milliseconds.
+    pub start_ms: usize,
+    /// Cpu time of the threads which have exited.
+    pub exited_time: CpuTime,
+    /// Cpu time of the children which have been waited, including their children.
+    pub children_time: CpuTime,
+    /// The max resident pages of the children which have been waited.
+    pub children_max_rss: usize,
+    /// How many times every syscall has been called, indexed by the syscall id.
+    /// It is boxed to keep the large array off the kernel stack.
+    pub syscall_times: Box<[u32]>,
+}
+
+impl ProcessUsage {
+    pub fn new() -> Self {
+        Self {
+            start_ms: get_time_ms(),
+            exited_time: CpuTime::default(),
+            children_time: CpuTime::default(),
+            children_max_rss: 0,
+            syscall_times: vec![0; MAX_SYSCALL_NUM].into_boxed_slice(),
+        }
+    }
+
+    pub fn count_syscall(&mut self, syscall_id: usize) {
+        if let Some(times) = self.syscall_times.get_mut(syscall_id) {
+            *times += 1;
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct TimeVal {
+    pub sec: usize,
+    pub usec: usize,
+}
+
+impl TimeVal {
+    pub fn from_cycles(cycles: usize) -> Self {
+        Self {
+            sec: cycles / CLOCK_FREQ,
+            usec: cycles % CLOCK_FREQ * 1_000_000 / CLOCK_FREQ,
+        }
+    }
+}
+
+/// Resource usage, the same layout as linux struct rusage.
+/// Only cpu time and max resident set size are filled.
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct RUsage {
+    pub utime: TimeVal,
+    pub stime: TimeVal,
+    /// Max resident set size in KB.
+    pub maxrss: usize,
+    pub ixrss: usize,
+    pub idrss: usize,
+    pub isrss: usize,
+    pub minflt: usize,
+    pub majflt: usize,
+    pub nswap: usize,
+    pub inblock: usize,
+    pub oublock: usize,
+    pub msgsnd: usize,
+    pub msgrcv: usize,
+    pub nsignals: usize,
+    pub nvcsw: usize,
+    pub nivcsw: usize,
+}
+
+/// Process times in clock ticks (TICKS_PER_SEC per second), the same layout as linux struct tms.
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct Tms {
+    pub utime: usize,
+    pub stime: usize,
+    pub cutime: usize,
+    pub cstime: usize,
+}
+
+/// Convert timer cycles to clock ticks.
+pub fn cycles_to_ticks(cycles: usize) -> usize {
+    cycles / (CLOCK_FREQ / TICKS_PER_SEC)
+}
+
+/// Convert timer cycles to milliseconds.
+pub fn cycles_to_ms(cycles: usize) -> usize {
+    cycles / (CLOCK_FREQ / 1000)
+}
+
+/// The fields of struct TaskInfo after u32 syscall_times[MAX_SYSCALL_NUM].
+/// TaskInfo is too large for the kernel stack, so it is written to user memory in parts.
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct TaskInfoTail {
+    /// Milliseconds since the process was created.
+    pub time: usize,
+    /// Cpu time in user mode, in milliseconds.
+    pub utime: usize,
+    /// Cpu time in kernel mode, in milliseconds.
+    pub stime: usize,
+    /// Resident pages.
+    pub rss: usize,
+    /// Max resident pages.
+    pub max_rss: usize,
+}
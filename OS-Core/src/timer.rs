@@ -1,11 +1,12 @@
 use crate::config::TICKS_PER_SEC;
 use crate::platfrom::CLOCK_FREQ;
 use crate::sbi_services::set_timer;
-use crate::sync::UPSafeCell;
+use crate::sync::SpinNoIrqLock;
 use crate::task::manager::wakeup_thread;
 use crate::task::thread::ThreadControlBlock;
 use alloc::collections::BinaryHeap;
 use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
 use lazy_static::lazy_static;
 use riscv::register::time;
 
@@ -14,16 +15,55 @@ pub fn get_time() -> usize {
 }
 
 pub fn get_time_ms() -> usize {
-    time::read() / CLOCK_FREQ * 1000
+    time::read() / (CLOCK_FREQ / 1000)
 }
 
 pub fn set_next_trigger() {
     set_timer(get_time() + CLOCK_FREQ / TICKS_PER_SEC)
 }
 
+/// Time in seconds and nanoseconds, the same layout as linux struct timespec.
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct TimeSpec {
+    pub sec: usize,
+    pub nsec: usize,
+}
+
+impl TimeSpec {
+    /// Convert to milliseconds, rounded up so a timeout never expires early.
+    pub fn to_ms(&self) -> usize {
+        self.sec * 1000 + (self.nsec + 999_999) / 1_000_000
+    }
+
+    pub fn from_ms(ms: usize) -> Self {
+        Self {
+            sec: ms / 1000,
+            nsec: ms % 1000 * 1_000_000,
+        }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.sec == 0 && self.nsec == 0
+    }
+}
+
+/// Called when a timer expires, such as the timer of a timerfd.
+pub trait TimerHandler: Send + Sync {
+    /// Called without the lock of the timer heap, so it may add timers.
+    fn expire(&self);
+}
+
+/// What a timer does when it expires.
+pub enum TimerTarget {
+    /// Wake up a sleeping or waiting thread.
+    Thread(Weak<ThreadControlBlock>),
+    Handler(Arc<dyn TimerHandler>),
+}
+
 pub struct TimerCondVar {
     pub expire_ms: usize,
-    pub thread: Weak<ThreadControlBlock>,
+    pub target: TimerTarget,
 }
 
 impl PartialEq for TimerCondVar {
@@ -51,25 +91,50 @@ impl Ord for TimerCondVar {
 }
 
 lazy_static! {
-    static ref TIMER: UPSafeCell<BinaryHeap<TimerCondVar>> =
-        unsafe { UPSafeCell::new(BinaryHeap::new()) };
+    static ref TIMER: SpinNoIrqLock<BinaryHeap<TimerCondVar>> =
+        SpinNoIrqLock::new(BinaryHeap::new());
 }
 
 pub fn add_timer(expire_ms: usize, thread: Arc<ThreadControlBlock>) {
-    TIMER.exclusive_access().push(TimerCondVar {
+    TIMER.lock().push(TimerCondVar {
         expire_ms,
-        thread: Arc::downgrade(&thread),
+        target: TimerTarget::Thread(Arc::downgrade(&thread)),
+    });
+}
+
+/// Call the handler when the time reaches expire_ms.
+pub fn add_timer_handler(expire_ms: usize, handler: Arc<dyn TimerHandler>) {
+    TIMER.lock().push(TimerCondVar {
+        expire_ms,
+        target: TimerTarget::Handler(handler),
+    });
+}
+
+/// Cancel the timers of the thread, when it is woken up by others before the timer expires.
+pub fn remove_timer(thread: &Arc<ThreadControlBlock>) {
+    let thread = Arc::downgrade(thread);
+    TIMER.lock().retain(|timer| match &timer.target {
+        TimerTarget::Thread(other) => !other.ptr_eq(&thread),
+        TimerTarget::Handler(_) => true,
     });
 }
 
 pub fn check_timer() {
     let now = get_time_ms();
-    let mut timers = TIMER.exclusive_access();
+    let mut timers = TIMER.lock();
+    let mut handlers = Vec::new();
     while let Some(timer) = timers.peek() {
         if timer.expire_ms <= now {
-            wakeup_thread(timers.pop().unwrap().thread);
+            match timers.pop().unwrap().target {
+                TimerTarget::Thread(thread) => wakeup_thread(thread),
+                TimerTarget::Handler(handler) => handlers.push(handler),
+            }
         } else {
             break;
         }
     }
+    drop(timers);
+    for handler in handlers {
+        handler.expire();
+    }
 }
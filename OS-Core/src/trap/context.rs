@@ -16,6 +16,8 @@ pub struct TrapContext {
     pub kernel_sp: usize,
     // trap handler
     pub trap_handler: usize,
+    // hart id of the hart the thread runs on, loaded to tp when entering kernel
+    pub kernel_tp: usize,
 }
 
 impl TrapContext {
@@ -41,6 +43,7 @@ impl TrapContext {
             kernel_satp,
             kernel_sp,
             trap_handler,
+            kernel_tp: 0,
         };
         ret.set_sp(sp);
         ret
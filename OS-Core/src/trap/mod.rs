@@ -1,12 +1,17 @@
 mod context;
 use crate::{
     config::TRAMPOLINE,
+    fs::tty::poll_console,
     mm::address::VirtAddr,
     println,
+    smp::{handle_ipi, hart_id},
     syscall::syscall,
     task::{
         check_current_signals_error, current_add_signal, exit_current_and_run_next,
-        processor::{current_trap_context, current_trap_context_va, current_user_token},
+        handle_stop_signals,
+        processor::{
+            current_task, current_trap_context, current_trap_context_va, current_user_token,
+        },
         signal::SignalFlags,
         suspended_current_and_run_next,
     },
@@ -29,10 +34,11 @@ pub fn init() {
     set_user_trap_entry();
 }
 
-/// Enable timer interrupt
+/// Enable timer interrupt and software interrupt (IPI).
 pub fn enable_timer_interrupt() {
     unsafe {
         sie::set_stimer();
+        sie::set_ssoft();
     }
 }
 
@@ -46,6 +52,17 @@ pub fn disable_smode_interrupt() {
     unsafe { sstatus::clear_sie() };
 }
 
+/// Wait for an interrupt when there is no thread to run.
+/// wfi returns on a pending interrupt though S mode interrupt is disabled,
+/// so an interrupt coming before wfi is not missed.
+/// It is handled by trap_from_kernel once S mode interrupt is enabled.
+pub fn wait_for_interrupt() {
+    set_kernel_trap_entry();
+    unsafe { asm!("wfi") };
+    enable_smode_interrupt();
+    disable_smode_interrupt();
+}
+
 /// Set the user trap entry to trampoline page.
 fn set_user_trap_entry() {
     unsafe {
@@ -72,19 +89,26 @@ fn set_kernel_trap_entry() {
 #[no_mangle]
 pub fn trap_handler() {
     set_kernel_trap_entry();
+    current_task()
+        .unwrap()
+        .inner_exclusive_access()
+        .usage
+        .enter_kernel();
     let scause = scause::read();
     let stval = stval::read();
     match scause.cause() {
         Trap::Interrupt(Interrupt::SupervisorTimer) => {
             check_timer();
+            poll_console();
             set_next_trigger();
             suspended_current_and_run_next();
         }
+        Trap::Interrupt(Interrupt::SupervisorSoft) => handle_ipi(),
         Trap::Exception(Exception::UserEnvCall) => {
             let mut context = current_trap_context();
             context.sepc += 4;
-            let ret =
-                syscall(context.x[17], [context.x[10], context.x[11], context.x[12]]) as usize;
+            let args: [usize; 6] = context.x[10..16].try_into().unwrap();
+            let ret = syscall(context.x[17], args) as usize;
             context = current_trap_context();
             context.x[10] = ret;
         }
@@ -115,6 +139,11 @@ pub fn trap_handler() {
             );
         }
     };
+    // The main thread exited on another hart.
+    if current_task().unwrap().process_exited() {
+        exit_current_and_run_next(0);
+    }
+    handle_stop_signals();
     if let Some((err_code, err_info)) = check_current_signals_error() {
         println!("[kernel] {}", err_info);
         exit_current_and_run_next(err_code);
@@ -126,6 +155,13 @@ pub fn trap_handler() {
 #[no_mangle]
 pub fn trap_return() -> ! {
     set_user_trap_entry();
+    // The thread may run on another hart next time.
+    current_trap_context().kernel_tp = hart_id();
+    current_task()
+        .unwrap()
+        .inner_exclusive_access()
+        .usage
+        .leave_kernel();
     let trap_context_ptr = current_trap_context_va();
     let user_satp = current_user_token();
     extern "C" {
@@ -145,7 +181,22 @@ pub fn trap_return() -> ! {
 }
 
 /// Kernel trap entry.
+/// The kernel only enables interrupt in the idle loop, so only interrupts come here.
 #[no_mangle]
 pub fn trap_from_kernel() {
-    todo!("handle external trap and timer trap")
+    let scause = scause::read();
+    match scause.cause() {
+        Trap::Interrupt(Interrupt::SupervisorTimer) => {
+            check_timer();
+            set_next_trigger();
+        }
+        Trap::Interrupt(Interrupt::SupervisorSoft) => handle_ipi(),
+        _ => {
+            panic!(
+                "Unsupported trap from kernel {:?}, stval = {:#x}!",
+                scause.cause(),
+                stval::read()
+            );
+        }
+    }
 }
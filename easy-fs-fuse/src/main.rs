@@ -171,3 +171,58 @@ fn efs_test() -> std::io::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn efs_unlink_test() -> std::io::Result<()> {
+    let block_file = Arc::new(BlockFile(Mutex::new({
+        let f = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open("target/fs_unlink.img")?;
+        f.set_len(8192 * 512).unwrap();
+        f
+    })));
+    EasyFileSystem::create(block_file.clone(), 4096, 1);
+    let efs = EasyFileSystem::open(block_file.clone());
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    let filea = root_inode.create_file("filea").unwrap();
+    filea.write_at(0, &[1u8; 10 * BLOCK_SZ]);
+    let filea_id = filea.inode_id();
+    root_inode.create_file("fileb").unwrap();
+    assert!(!filea.is_dir());
+
+    // A removed file can not be found, but it is kept while it has a handle.
+    assert!(root_inode.unlink("filea"));
+    assert!(!root_inode.unlink("filea"));
+    assert!(root_inode.find("filea").is_none());
+    assert_eq!(root_inode.list(), vec![String::from("fileb")]);
+    let mut buf = [0u8; BLOCK_SZ];
+    assert_eq!(filea.read_at(9 * BLOCK_SZ, &mut buf), BLOCK_SZ);
+    assert_eq!(buf, [1u8; BLOCK_SZ]);
+    let filec = root_inode.create_file("filec").unwrap();
+    assert_ne!(filec.inode_id(), filea_id);
+    assert!(root_inode.unlink("filec"));
+    drop(filec);
+
+    // Its inode and entry are reused after the last handle is dropped.
+    drop(filea);
+    let filec = root_inode.create_file("filec").unwrap();
+    assert_eq!(filec.inode_id(), filea_id);
+    assert_eq!(filec.len(), 0);
+    assert_eq!(
+        root_inode.list(),
+        vec![String::from("filec"), String::from("fileb")]
+    );
+
+    // Only empty directories can be removed.
+    let dir = root_inode.create_directory("dir").unwrap();
+    assert!(dir.is_dir());
+    dir.create_file("filed").unwrap();
+    assert!(!root_inode.unlink("dir"));
+    assert!(dir.unlink("filed"));
+    assert!(root_inode.unlink("dir"));
+    assert!(root_inode.find("dir").is_none());
+    Ok(())
+}
@@ -1,4 +1,7 @@
-use alloc::sync::Arc;
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    sync::Arc,
+};
 use spin::Mutex;
 
 use crate::{
@@ -16,6 +19,11 @@ pub struct EasyFileSystem {
     pub data_bitmap: Bitmap,
     inode_block_start: u32,
     data_block_start: u32,
+    /// How many Inode handles refer to every inode.
+    inode_refs: BTreeMap<u32, usize>,
+    /// Inodes removed from their directory while they have handles,
+    /// they are freed when their last handle is dropped.
+    orphans: BTreeSet<u32>,
 }
 
 type DataBlock = [u8; BLOCK_SIZE];
@@ -54,6 +62,8 @@ impl EasyFileSystem {
             data_bitmap,
             inode_block_start: inode_bitmap_blocks + 1,
             data_block_start: inode_total_blocks + data_bitmap_blocks + 1,
+            inode_refs: BTreeMap::new(),
+            orphans: BTreeSet::new(),
         };
         for i in 0..total_blocks as usize {
             get_block_cache(i, Arc::clone(&block_device)).lock().modify(
@@ -109,6 +119,8 @@ impl EasyFileSystem {
                         + super_block.inode_bitmap_blocks
                         + super_block.inode_area_blocks
                         + super_block.data_bitmap_blocks,
+                    inode_refs: BTreeMap::new(),
+                    orphans: BTreeSet::new(),
                 };
                 Arc::new(Mutex::new(efs))
             })
@@ -121,6 +133,59 @@ impl EasyFileSystem {
         self.inode_bitmap.alloc(&self.block_device).unwrap() as u32
     }
 
+    /// Set the bitmap of the inode to 0.
+    pub fn dealloc_inode(&mut self, inode_id: u32) {
+        self.inode_bitmap
+            .dealloc(&self.block_device, inode_id as usize);
+    }
+
+    /// Free the data blocks and the inode of a removed file or directory.
+    pub fn free_inode(&mut self, inode_id: u32) {
+        let (block_id, offset) = self.inode_position(inode_id);
+        let blocks = get_block_cache(block_id as usize, Arc::clone(&self.block_device))
+            .lock()
+            .modify(offset, |disk_inode: &mut DiskInode| {
+                disk_inode.clear_size(&self.block_device)
+            });
+        for block in blocks {
+            self.dealloc_data(block);
+        }
+        self.dealloc_inode(inode_id);
+    }
+
+    /// A new Inode handle refers to the inode.
+    pub fn open_inode(&mut self, inode_id: u32) {
+        *self.inode_refs.entry(inode_id).or_insert(0) += 1;
+    }
+
+    /// An Inode handle of the inode is dropped.
+    /// # Return
+    /// * true - it is the last handle of a removed inode, which should be freed now.
+    pub fn close_inode(&mut self, inode_id: u32) -> bool {
+        let Some(refs) = self.inode_refs.get_mut(&inode_id) else {
+            return false;
+        };
+        *refs -= 1;
+        if *refs > 0 {
+            return false;
+        }
+        self.inode_refs.remove(&inode_id);
+        self.orphans.remove(&inode_id)
+    }
+
+    /// The inode is removed from its directory.
+    /// # Return
+    /// * true - no handle refers to it, it should be freed now,
+    ///   otherwise it is freed when its last handle is dropped.
+    pub fn unlink_inode(&mut self, inode_id: u32) -> bool {
+        if self.inode_refs.contains_key(&inode_id) {
+            self.orphans.insert(inode_id);
+            false
+        } else {
+            true
+        }
+    }
+
     /// Alloc a block for data.
     /// # Return
     /// * Disk block id.
@@ -155,6 +220,13 @@ impl EasyFileSystem {
         (block_id, offset)
     }
 
+    /// Get the inode id from the block number and offset of the block where the inode is located.
+    pub fn inode_id(&self, block_id: u32, offset: usize) -> u32 {
+        let inode_size = core::mem::size_of::<DiskInode>();
+        (block_id - self.inode_block_start) * (BLOCK_SIZE / inode_size) as u32
+            + (offset / inode_size) as u32
+    }
+
     /// Get disk block id from data block id.
     pub fn data_block_id(&self, data_block_id: u32) -> u32 {
         self.data_block_start + data_block_id
@@ -162,8 +234,6 @@ impl EasyFileSystem {
 
     /// Get root inode.
     pub fn root_inode(efs: &Arc<Mutex<Self>>) -> Inode {
-        let block_device = Arc::clone(&efs.lock().block_device);
-        let (block_id, offset) = efs.lock().inode_position(0);
-        Inode::new(block_id, offset, Arc::clone(efs), block_device)
+        Inode::new(0, &mut efs.lock(), Arc::clone(efs))
     }
 }
@@ -43,6 +43,10 @@ impl SuperBlock {
 pub enum DiskInodeType {
     File,
     Directory,
+    /// Named pipe, it has no data on the disk.
+    Fifo,
+    /// Address of a unix domain socket, it has no data on the disk.
+    Socket,
 }
 
 #[repr(C)]
@@ -73,6 +77,14 @@ impl DiskInode {
     pub fn is_file(&self) -> bool {
         self.type_ == DiskInodeType::File
     }
+    /// Determine whether the current Inode is a named pipe.
+    pub fn is_fifo(&self) -> bool {
+        self.type_ == DiskInodeType::Fifo
+    }
+    /// Determine whether the current Inode is a socket.
+    pub fn is_socket(&self) -> bool {
+        self.type_ == DiskInodeType::Socket
+    }
     /// Get actual block number by the linear data block number inside the Inode.
     /// # Parameter
     /// * 'inner_id' - Linear data block number inside the Inode.
@@ -17,18 +17,18 @@ pub struct Inode {
 }
 
 impl Inode {
-    /// Create a new inode.
-    pub fn new(
-        block_id: u32,
-        block_offset: usize,
-        fs: Arc<Mutex<EasyFileSystem>>,
-        block_device: Arc<dyn BlockDevice>,
-    ) -> Self {
+    /// Create a handle of the inode.
+    /// # Parameter
+    /// * 'efs' - the file system locked by the caller, the handle is counted in it.
+    /// * 'fs' - the file system kept by the handle.
+    pub fn new(inode_id: u32, efs: &mut EasyFileSystem, fs: Arc<Mutex<EasyFileSystem>>) -> Self {
+        let (block_id, block_offset) = efs.inode_position(inode_id);
+        efs.open_inode(inode_id);
         Self {
             block_id: block_id as usize,
             block_offset,
             fs,
-            block_device,
+            block_device: Arc::clone(&efs.block_device),
         }
     }
 
@@ -49,23 +49,38 @@ impl Inode {
     /// * 'name' - File or directory name.
     /// * 'disk_inode' - A Directory inode.
     fn find_inode_id(&self, name: &str, disk_inode: &DiskInode) -> Option<u32> {
+        self.find_entry(name, disk_inode)
+            .map(|(_, inode_id)| inode_id)
+    }
+
+    /// Find a directory entry from directory inode by file or directory name.
+    /// # Return
+    /// * (index of the entry, inode number)
+    fn find_entry(&self, name: &str, disk_inode: &DiskInode) -> Option<(usize, u32)> {
         assert!(disk_inode.is_directory(), "disk_inode must be directory.");
-        let mut dir_entry = DirEntry::empty();
-        for i in 0..disk_inode.size as usize / DIRENTRY_SIZE {
-            assert_eq!(
-                disk_inode.read_at(
-                    DIRENTRY_SIZE * i,
-                    dir_entry.as_bytes_mut(),
-                    &self.block_device
-                ),
-                DIRENTRY_SIZE,
-                "Faild to read directory entry."
-            );
-            if dir_entry.get_name() == name {
-                return Some(dir_entry.get_inode_number());
-            }
+        // The name of a removed entry is empty.
+        if name.is_empty() {
+            return None;
         }
-        None
+        (0..disk_inode.size as usize / DIRENTRY_SIZE)
+            .map(|i| (i, self.dir_entry_at(i, disk_inode)))
+            .find(|(_, dir_entry)| dir_entry.get_name() == name)
+            .map(|(i, dir_entry)| (i, dir_entry.get_inode_number()))
+    }
+
+    /// Read the i-th directory entry of the directory inode.
+    fn dir_entry_at(&self, i: usize, disk_inode: &DiskInode) -> DirEntry {
+        let mut dir_entry = DirEntry::empty();
+        assert_eq!(
+            disk_inode.read_at(
+                DIRENTRY_SIZE * i,
+                dir_entry.as_bytes_mut(),
+                &self.block_device
+            ),
+            DIRENTRY_SIZE,
+            "Faild to read directory entry."
+        );
+        dir_entry
     }
 
     // Just for debug
@@ -80,6 +95,31 @@ impl Inode {
         self.read_disk_inode(|disk_inode| disk_inode.size)
     }
 
+    /// Determine whether the inode is a directory.
+    pub fn is_dir(&self) -> bool {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| disk_inode.is_directory())
+    }
+
+    /// Determine whether the inode is a named pipe.
+    pub fn is_fifo(&self) -> bool {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| disk_inode.is_fifo())
+    }
+
+    /// Determine whether the inode is a socket.
+    pub fn is_socket(&self) -> bool {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| disk_inode.is_socket())
+    }
+
+    /// Get the inode number.
+    pub fn inode_id(&self) -> u32 {
+        self.fs
+            .lock()
+            .inode_id(self.block_id as u32, self.block_offset)
+    }
+
     /// Get inode position in device.
     pub fn device_position(&self) -> (usize, usize) {
         (self.block_id, self.block_offset)
@@ -87,17 +127,10 @@ impl Inode {
 
     /// Get the inode of a file or directory by its name in the current directory inode.
     pub fn find(&self, file_name: &str) -> Option<Arc<Inode>> {
-        let fs = self.fs.lock();
+        let mut fs = self.fs.lock();
         self.read_disk_inode(|disk_inode| {
-            self.find_inode_id(file_name, disk_inode).map(|inode_id| {
-                let (block_id, offset) = fs.inode_position(inode_id);
-                Arc::new(Self::new(
-                    block_id,
-                    offset,
-                    self.fs.clone(),
-                    self.block_device.clone(),
-                ))
-            })
+            self.find_inode_id(file_name, disk_inode)
+                .map(|inode_id| Arc::new(Self::new(inode_id, &mut fs, self.fs.clone())))
         })
     }
 
@@ -125,7 +158,7 @@ impl Inode {
     /// Create a new inode in current directory.
     /// # Parameter
     /// * 'name' - File or directory name.
-    /// * 'type_' - Inode type (file or directory).
+    /// * 'type_' - Inode type (file, directory, named pipe or socket).
     /// # Return
     /// * None if inode exist, or Arc<Inode>
     fn create(&self, name: &str, type_: DiskInodeType) -> Option<Arc<Inode>> {
@@ -144,25 +177,25 @@ impl Inode {
             });
         // Add DirEntry to current inode.
         self.modify_disk_inode(|directory| {
-            // Increase current inode size.
             let inode_count = directory.size as usize / DIRENTRY_SIZE;
-            let new_size = directory.size + DIRENTRY_SIZE as u32;
-            self.increase_size(new_size, directory, &mut fs);
+            // Reuse the entry of a removed file, or increase current inode size.
+            let index = (0..inode_count)
+                .find(|i| self.dir_entry_at(*i, directory).get_name().is_empty())
+                .unwrap_or_else(|| {
+                    let new_size = directory.size + DIRENTRY_SIZE as u32;
+                    self.increase_size(new_size, directory, &mut fs);
+                    inode_count
+                });
             // Add new DirEntry
             let direntry = DirEntry::new(name, new_inode_id);
             directory.write_at(
-                inode_count * DIRENTRY_SIZE,
+                index * DIRENTRY_SIZE,
                 direntry.as_bytes(),
                 &self.block_device,
             );
         });
         sync_all_block();
-        let result = Self::new(
-            new_inode_block_id,
-            new_block_offset,
-            self.fs.clone(),
-            self.block_device.clone(),
-        );
+        let result = Self::new(new_inode_id, &mut fs, self.fs.clone());
         Some(Arc::new(result))
     }
 
@@ -174,6 +207,51 @@ impl Inode {
         self.create(name, DiskInodeType::Directory)
     }
 
+    pub fn create_fifo(&self, name: &str) -> Option<Arc<Inode>> {
+        self.create(name, DiskInodeType::Fifo)
+    }
+
+    pub fn create_socket(&self, name: &str) -> Option<Arc<Inode>> {
+        self.create(name, DiskInodeType::Socket)
+    }
+
+    /// Remove a file or an empty directory from the current directory.
+    /// The directory entry is left empty and reused by the next created file.
+    /// The inode is freed when the last handle of it is dropped, so an open file is still usable.
+    /// # Return
+    /// * false if the name does not exist, or it is a directory which is not empty.
+    pub fn unlink(&self, name: &str) -> bool {
+        let mut fs = self.fs.lock();
+        let Some((index, inode_id)) =
+            self.read_disk_inode(|directory| self.find_entry(name, directory))
+        else {
+            return false;
+        };
+        let (block_id, offset) = fs.inode_position(inode_id);
+        let not_empty_dir = get_block_cache(block_id as usize, Arc::clone(&self.block_device))
+            .lock()
+            .read(offset, |disk_inode: &DiskInode| {
+                disk_inode.is_directory()
+                    && (0..disk_inode.size as usize / DIRENTRY_SIZE)
+                        .any(|i| !self.dir_entry_at(i, disk_inode).get_name().is_empty())
+            });
+        if not_empty_dir {
+            return false;
+        }
+        if fs.unlink_inode(inode_id) {
+            fs.free_inode(inode_id);
+        }
+        self.modify_disk_inode(|directory| {
+            directory.write_at(
+                index * DIRENTRY_SIZE,
+                DirEntry::empty().as_bytes(),
+                &self.block_device,
+            );
+        });
+        sync_all_block();
+        true
+    }
+
     /// Recycle all index blocks and data blocks from inode.
     pub fn clear(&self) {
         let mut fs = self.fs.lock();
@@ -214,19 +292,25 @@ impl Inode {
             let file_quantity = disk_inode.size as usize / DIRENTRY_SIZE;
             let mut result = Vec::with_capacity(file_quantity);
             for i in 0..file_quantity {
-                let mut direntry = DirEntry::empty();
-                assert_eq!(
-                    disk_inode.read_at(
-                        i * DIRENTRY_SIZE,
-                        direntry.as_bytes_mut(),
-                        &self.block_device
-                    ),
-                    DIRENTRY_SIZE,
-                    "Faild to read directory entry."
-                );
-                result.push(String::from(direntry.get_name()));
+                let direntry = self.dir_entry_at(i, disk_inode);
+                // Skip removed entries.
+                if !direntry.get_name().is_empty() {
+                    result.push(String::from(direntry.get_name()));
+                }
             }
             result
         })
     }
 }
+
+impl Drop for Inode {
+    /// Free the inode with its last handle if it has been removed.
+    fn drop(&mut self) {
+        let mut fs = self.fs.lock();
+        let inode_id = fs.inode_id(self.block_id as u32, self.block_offset);
+        if fs.close_inode(inode_id) {
+            fs.free_inode(inode_id);
+            sync_all_block();
+        }
+    }
+}
@@ -0,0 +1,39 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use user_lib::{exit, get_time, thread_create, waittid};
+
+/// Threads may run on different harts at the same time,
+/// the counter is only correct if the atomic instructions work across harts.
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+const PER_THREAD: usize = 100000;
+const THREAD_COUNT: usize = 8;
+
+fn f() -> ! {
+    for _ in 0..PER_THREAD {
+        COUNTER.fetch_add(1, Ordering::Relaxed);
+    }
+    exit(0)
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let start = get_time();
+    let mut v = Vec::new();
+    for _ in 0..THREAD_COUNT {
+        v.push(thread_create(f as usize, 0) as usize);
+    }
+    for tid in v.into_iter() {
+        assert_eq!(waittid(tid), 0);
+    }
+    println!("time cost is {}ms", get_time() - start);
+    assert_eq!(COUNTER.load(Ordering::Relaxed), PER_THREAD * THREAD_COUNT);
+    println!("atomic_adder passed!");
+    0
+}
@@ -0,0 +1,125 @@
+#![no_std]
+#![no_main]
+#![allow(clippy::needless_range_loop)]
+
+#[macro_use]
+extern crate user_lib;
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+use core::ptr::{addr_of, addr_of_mut};
+use core::sync::atomic::{AtomicUsize, Ordering};
+use user_lib::{barrier_create, barrier_wait, exit, thread_create, waittid, yield_, EINVAL};
+
+const THREADS: usize = 4;
+const N: usize = 8;
+const ROUNDS: usize = 20;
+const P: i32 = 10007;
+type Arr = [[i32; N]; N];
+
+static BARRIER: AtomicUsize = AtomicUsize::new(0);
+/// The threads arrived at the barrier in all phases.
+static ARRIVED: AtomicUsize = AtomicUsize::new(0);
+/// The waits which returned 1, one for every phase.
+static SERIAL: AtomicUsize = AtomicUsize::new(0);
+static mut A: Arr = [[0; N]; N];
+static mut B: Arr = [[0; N]; N];
+static mut C: Arr = [[0; N]; N];
+
+fn init(a: &mut Arr, b: &mut Arr) {
+    for i in 0..N {
+        for j in 0..N {
+            a[i][j] = (i * N + j) as i32 % 7 + 1;
+            b[i][j] = (i + 2 * j) as i32 % 5 + 1;
+        }
+    }
+}
+
+/// Rows start..end of a * b.
+fn multiply(a: &Arr, b: &Arr, c: &mut Arr, start: usize, end: usize) {
+    for i in start..end {
+        for j in 0..N {
+            c[i][j] = 0;
+            for k in 0..N {
+                c[i][j] = (c[i][j] + a[i][k] * b[k][j]) % P;
+            }
+        }
+    }
+}
+
+/// Wait until all threads finish the phase.
+/// # Return
+/// * true - for one thread of the phase
+fn end_phase(phase: usize) -> bool {
+    ARRIVED.fetch_add(1, Ordering::SeqCst);
+    let serial = match barrier_wait(BARRIER.load(Ordering::SeqCst)) {
+        0 => false,
+        1 => true,
+        errno => panic!("barrier_wait failed: {}", errno),
+    };
+    assert!(ARRIVED.load(Ordering::SeqCst) >= (phase + 1) * THREADS);
+    if serial {
+        SERIAL.fetch_add(1, Ordering::SeqCst);
+    }
+    serial
+}
+
+/// Every round computes A * B by rows in parallel, then one thread stores it to A.
+fn worker(id: usize) -> ! {
+    let (start, end) = (id * N / THREADS, (id + 1) * N / THREADS);
+    for round in 0..ROUNDS {
+        unsafe {
+            multiply(
+                &*addr_of!(A),
+                &*addr_of!(B),
+                &mut *addr_of_mut!(C),
+                start,
+                end,
+            );
+        }
+        // Let the others run into the barrier early or late.
+        if (id + round) % 3 == 0 {
+            yield_();
+        }
+        if end_phase(round * 2) {
+            unsafe { *addr_of_mut!(A) = *addr_of!(C) };
+        }
+        end_phase(round * 2 + 1);
+    }
+    exit(0)
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(barrier_create(0), -EINVAL);
+    let barrier = barrier_create(THREADS);
+    assert!(barrier >= 0);
+    assert_eq!(barrier_wait(barrier as usize + 1), -EINVAL);
+    BARRIER.store(barrier as usize, Ordering::SeqCst);
+
+    let mut a: Arr = Default::default();
+    let mut b: Arr = Default::default();
+    init(&mut a, &mut b);
+    unsafe {
+        *addr_of_mut!(A) = a;
+        *addr_of_mut!(B) = b;
+    }
+    let tids: Vec<usize> = (0..THREADS)
+        .map(|id| thread_create(worker as usize, id) as usize)
+        .collect();
+    for tid in tids {
+        assert_eq!(waittid(tid), 0);
+    }
+    assert_eq!(ARRIVED.load(Ordering::SeqCst), ROUNDS * 2 * THREADS);
+    assert_eq!(SERIAL.load(Ordering::SeqCst), ROUNDS * 2);
+
+    let mut c: Arr = Default::default();
+    for _ in 0..ROUNDS {
+        multiply(&a, &b, &mut c, 0, N);
+        a = c;
+    }
+    assert_eq!(unsafe { *addr_of!(A) }, a);
+    println!("barrier_test passed!");
+    0
+}
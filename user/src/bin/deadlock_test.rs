@@ -0,0 +1,151 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use user_lib::{
+    enable_deadlock_detect, exit, kernel_mutex_create, kernel_mutex_lock, kernel_mutex_unlock,
+    kernel_semaphore_create, kernel_semaphore_down, kernel_semaphore_up, sleep, thread_create,
+    waittid, yield_, EDEADLK,
+};
+
+const PHILOSOPHERS: usize = 5;
+const MEALS: usize = 20;
+
+static MUTEX_A: AtomicUsize = AtomicUsize::new(0);
+static MUTEX_B: AtomicUsize = AtomicUsize::new(0);
+static SIGNAL: AtomicUsize = AtomicUsize::new(0);
+static FORKS: AtomicUsize = AtomicUsize::new(0);
+static RETRIES: AtomicUsize = AtomicUsize::new(0);
+static MEALS_EATEN: AtomicUsize = AtomicUsize::new(0);
+
+fn self_test() {
+    let mutex = kernel_mutex_create(true);
+    assert_eq!(kernel_mutex_lock(mutex), 0);
+    assert_eq!(kernel_mutex_lock(mutex), -EDEADLK);
+    assert_eq!(kernel_mutex_unlock(mutex), 0);
+
+    let semaphore = kernel_semaphore_create(2);
+    assert_eq!(kernel_semaphore_down(semaphore), 0);
+    assert_eq!(kernel_semaphore_down(semaphore), 0);
+    assert_eq!(kernel_semaphore_down(semaphore), -EDEADLK);
+    assert_eq!(kernel_semaphore_up(semaphore), 0);
+    assert_eq!(kernel_semaphore_down(semaphore), 0);
+    assert_eq!(kernel_semaphore_up(semaphore), 0);
+    assert_eq!(kernel_semaphore_up(semaphore), 0);
+}
+
+fn lock_b_then_a() -> ! {
+    let a = MUTEX_A.load(Ordering::SeqCst);
+    let b = MUTEX_B.load(Ordering::SeqCst);
+    assert_eq!(kernel_mutex_lock(b), 0);
+    // Blocks until the main thread gives up A.
+    assert_eq!(kernel_mutex_lock(a), 0);
+    kernel_mutex_unlock(a);
+    kernel_mutex_unlock(b);
+    exit(0)
+}
+
+/// The main thread and another lock two mutexes in different orders,
+/// the lock which closes the cycle fails instead of blocking both.
+fn abba_test() {
+    let a = kernel_mutex_create(true);
+    let b = kernel_mutex_create(true);
+    MUTEX_A.store(a, Ordering::SeqCst);
+    MUTEX_B.store(b, Ordering::SeqCst);
+    assert_eq!(kernel_mutex_lock(a), 0);
+    let tid = thread_create(lock_b_then_a as usize, 0) as usize;
+    // Let the thread take B and wait for A.
+    sleep(50);
+    assert_eq!(kernel_mutex_lock(b), -EDEADLK);
+    assert_eq!(kernel_mutex_unlock(a), 0);
+    assert_eq!(waittid(tid), 0);
+    // Both are free after the thread exits.
+    assert_eq!(kernel_mutex_lock(b), 0);
+    assert_eq!(kernel_mutex_lock(a), 0);
+    kernel_mutex_unlock(a);
+    kernel_mutex_unlock(b);
+}
+
+fn signal() -> ! {
+    sleep(50);
+    kernel_semaphore_up(SIGNAL.load(Ordering::SeqCst));
+    exit(0)
+}
+
+/// A semaphore upped by another thread is not held by anyone,
+/// waiting on it is taken as a deadlock unless detection is disabled.
+fn signal_test() {
+    let semaphore = kernel_semaphore_create(0);
+    SIGNAL.store(semaphore, Ordering::SeqCst);
+    assert_eq!(kernel_semaphore_down(semaphore), -EDEADLK);
+    assert_eq!(enable_deadlock_detect(false), 0);
+    let tid = thread_create(signal as usize, 0) as usize;
+    assert_eq!(kernel_semaphore_down(semaphore), 0);
+    assert_eq!(waittid(tid), 0);
+    assert_eq!(enable_deadlock_detect(true), 0);
+}
+
+/// Every philosopher takes the left fork first, which could deadlock,
+/// so a philosopher puts the left fork down and retries when taking the right one fails.
+fn philosopher(id: usize) -> ! {
+    let forks = FORKS.load(Ordering::SeqCst);
+    let left = forks + id;
+    let right = forks + (id + 1) % PHILOSOPHERS;
+    for _ in 0..MEALS {
+        loop {
+            assert_eq!(kernel_mutex_lock(left), 0);
+            match kernel_mutex_lock(right) {
+                0 => break,
+                errno => {
+                    assert_eq!(errno, -EDEADLK);
+                    RETRIES.fetch_add(1, Ordering::Relaxed);
+                    kernel_mutex_unlock(left);
+                    yield_();
+                }
+            }
+        }
+        MEALS_EATEN.fetch_add(1, Ordering::Relaxed);
+        // Hold both forks for a while, so the others are likely to take their left one.
+        yield_();
+        kernel_mutex_unlock(right);
+        kernel_mutex_unlock(left);
+    }
+    exit(0)
+}
+
+fn philosopher_test() {
+    let forks: Vec<usize> = (0..PHILOSOPHERS)
+        .map(|_| kernel_mutex_create(true))
+        .collect();
+    // The ids of mutexes created in a row are consecutive.
+    assert!(forks.windows(2).all(|ids| ids[1] == ids[0] + 1));
+    FORKS.store(forks[0], Ordering::SeqCst);
+    let tids: Vec<usize> = (0..PHILOSOPHERS)
+        .map(|id| thread_create(philosopher as usize, id) as usize)
+        .collect();
+    for tid in tids {
+        assert_eq!(waittid(tid), 0);
+    }
+    assert_eq!(MEALS_EATEN.load(Ordering::Relaxed), PHILOSOPHERS * MEALS);
+    println!(
+        "philosophers retried {} times",
+        RETRIES.load(Ordering::Relaxed)
+    );
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(enable_deadlock_detect(true), 0);
+    self_test();
+    abba_test();
+    signal_test();
+    philosopher_test();
+    println!("deadlock_test passed!");
+    0
+}
@@ -0,0 +1,63 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, open, read, read_dir, stat, write, OpenFlags, Stat};
+
+/// Magic number in the super block of easy-fs.
+const EFS_MAGIC: u32 = 7604003;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let devices = read_dir("/dev\0").unwrap();
+    for name in ["null", "zero", "random", "urandom", "tty", "console", "vda"] {
+        assert!(devices.iter().any(|device| device == name));
+    }
+    let mut st = Stat::default();
+    assert_eq!(stat("/dev/null\0", &mut st), 0);
+    assert!(st.is_char_device());
+    assert_eq!(st.rdev, 1 << 8 | 3);
+    assert_eq!(stat("/dev/vda\0", &mut st), 0);
+    assert!(st.is_block_device());
+    // Files can not be created in devfs.
+    assert_eq!(
+        open("/dev/new\0", OpenFlags::CREATE | OpenFlags::WRITE_ONLY),
+        -1
+    );
+
+    // null reads nothing and swallows writes.
+    let fd = open("/dev/null\0", OpenFlags::READ_WRITE) as usize;
+    let mut buf = [0xffu8; 64];
+    assert_eq!(write(fd, b"discarded"), 9);
+    assert_eq!(read(fd, &mut buf), 0);
+    close(fd);
+
+    // zero fills the whole buffer.
+    let fd = open("/dev/zero\0", OpenFlags::READ_ONLY) as usize;
+    assert_eq!(read(fd, &mut buf), 64);
+    assert!(buf.iter().all(|byte| *byte == 0));
+    close(fd);
+
+    // Two reads of random are not the same.
+    let fd = open("/dev/random\0", OpenFlags::READ_ONLY) as usize;
+    let mut other = [0u8; 64];
+    assert_eq!(read(fd, &mut buf), 64);
+    assert_eq!(read(fd, &mut other), 64);
+    assert_ne!(buf, other);
+    close(fd);
+
+    // The console writes to the terminal.
+    let fd = open("/dev/console\0", OpenFlags::WRITE_ONLY) as usize;
+    assert_eq!(write(fd, b"hello from /dev/console\n"), 24);
+    close(fd);
+
+    // The raw block device starts with the super block of easy-fs.
+    let fd = open("/dev/vda\0", OpenFlags::READ_ONLY) as usize;
+    assert_eq!(read(fd, &mut buf), 64);
+    assert_eq!(u32::from_le_bytes(buf[..4].try_into().unwrap()), EFS_MAGIC);
+    close(fd);
+    println!("devfs_test passed!");
+    0
+}
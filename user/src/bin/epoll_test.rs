@@ -0,0 +1,131 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, epoll_create1, epoll_ctl, epoll_wait, exit, fork, open, pipe, read, sleep, waitpid,
+    write, EpollEvent, EpollEvents, OpenFlags, EBADF, EEXIST, EINVAL, ENOENT, EPERM, EPOLL_CTL_ADD,
+    EPOLL_CTL_DEL, EPOLL_CTL_MOD,
+};
+
+/// Add fd to the epoll instance, the data is the fd.
+fn add(epfd: usize, fd: usize, events: EpollEvents) {
+    let event = EpollEvent::new(events, fd as u64);
+    assert_eq!(epoll_ctl(epfd, EPOLL_CTL_ADD, fd, &event), 0);
+}
+
+/// Wait without blocking and return the fds with events.
+fn ready_fds(epfd: usize, events: &mut [EpollEvent]) -> usize {
+    let n = epoll_wait(epfd, events, 0);
+    assert!(n >= 0);
+    n as usize
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let epfd = epoll_create1(OpenFlags::empty());
+    assert!(epfd >= 0);
+    let epfd = epfd as usize;
+    let mut level = [0usize; 2];
+    let mut edge = [0usize; 2];
+    assert_eq!(pipe(&mut level), 0);
+    assert_eq!(pipe(&mut edge), 0);
+    add(epfd, level[0], EpollEvents::EPOLLIN);
+    add(epfd, edge[0], EpollEvents::EPOLLIN | EpollEvents::EPOLLET);
+    let mut events = [EpollEvent::default(); 4];
+    let mut buf = [0u8; 16];
+    assert_eq!(ready_fds(epfd, &mut events), 0);
+
+    // Level triggered, reported until the data is read.
+    assert_eq!(write(level[1], b"level"), 5);
+    for _ in 0..2 {
+        assert_eq!(ready_fds(epfd, &mut events), 1);
+        assert_eq!(events[0].data, level[0] as u64);
+        assert_eq!(events[0].events(), EpollEvents::EPOLLIN);
+    }
+    assert_eq!(read(level[0], &mut buf), 5);
+    assert_eq!(ready_fds(epfd, &mut events), 0);
+
+    // Edge triggered, reported once for every write.
+    assert_eq!(write(edge[1], b"edge"), 4);
+    assert_eq!(ready_fds(epfd, &mut events), 1);
+    assert_eq!(events[0].data, edge[0] as u64);
+    assert_eq!(ready_fds(epfd, &mut events), 0);
+    assert_eq!(write(edge[1], b"edge"), 4);
+    assert_eq!(ready_fds(epfd, &mut events), 1);
+    assert_eq!(read(edge[0], &mut buf), 8);
+
+    // One shot, disabled after reported until it is modified.
+    let one_shot = EpollEvent::new(
+        EpollEvents::EPOLLIN | EpollEvents::EPOLLONESHOT,
+        level[0] as u64,
+    );
+    assert_eq!(epoll_ctl(epfd, EPOLL_CTL_MOD, level[0], &one_shot), 0);
+    assert_eq!(write(level[1], b"once"), 4);
+    assert_eq!(ready_fds(epfd, &mut events), 1);
+    assert_eq!(ready_fds(epfd, &mut events), 0);
+    assert_eq!(epoll_ctl(epfd, EPOLL_CTL_MOD, level[0], &one_shot), 0);
+    assert_eq!(ready_fds(epfd, &mut events), 1);
+
+    // Only max_events are returned, the others are kept for the next wait.
+    let level_in = EpollEvent::new(EpollEvents::EPOLLIN, level[0] as u64);
+    assert_eq!(epoll_ctl(epfd, EPOLL_CTL_MOD, level[0], &level_in), 0);
+    assert_eq!(write(edge[1], b"edge"), 4);
+    assert_eq!(ready_fds(epfd, &mut events[..1]), 1);
+    let first = events[0].data;
+    assert_eq!(ready_fds(epfd, &mut events), 2);
+    assert!(events[..2].iter().any(|event| event.data != first));
+    assert_eq!(read(level[0], &mut buf), 4);
+    assert_eq!(read(edge[0], &mut buf), 4);
+
+    // Block until a child writes.
+    let pid = fork();
+    if pid == 0 {
+        sleep(30);
+        write(edge[1], b"later");
+        exit(0);
+    }
+    assert_eq!(epoll_wait(epfd, &mut events, -1), 1);
+    assert_eq!(events[0].data, edge[0] as u64);
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid, &mut exit_code, 0), pid);
+    assert_eq!(read(edge[0], &mut buf), 5);
+    assert_eq!(epoll_wait(epfd, &mut events, 20), 0);
+
+    // Closing the write end hangs up the read end.
+    close(edge[1]);
+    assert_eq!(ready_fds(epfd, &mut events), 1);
+    assert_eq!(events[0].events(), EpollEvents::EPOLLHUP);
+    assert_eq!(epoll_ctl(epfd, EPOLL_CTL_DEL, edge[0], &events[0]), 0);
+    assert_eq!(ready_fds(epfd, &mut events), 0);
+
+    // Errors.
+    let event = EpollEvent::new(EpollEvents::EPOLLIN, 0);
+    assert_eq!(epoll_ctl(epfd, EPOLL_CTL_ADD, level[0], &event), -EEXIST);
+    assert_eq!(epoll_ctl(epfd, EPOLL_CTL_DEL, edge[0], &event), -ENOENT);
+    assert_eq!(epoll_ctl(epfd, EPOLL_CTL_ADD, 99, &event), -EBADF);
+    assert_eq!(epoll_ctl(epfd, EPOLL_CTL_ADD, epfd, &event), -EINVAL);
+    assert_eq!(epoll_ctl(level[0], EPOLL_CTL_ADD, edge[0], &event), -EINVAL);
+    assert_eq!(epoll_wait(epfd, &mut events[..0], 0), -EINVAL);
+    let fd = open(
+        "/tmp/epoll_test\0",
+        OpenFlags::CREATE | OpenFlags::WRITE_ONLY,
+    );
+    assert!(fd >= 0);
+    assert_eq!(epoll_ctl(epfd, EPOLL_CTL_ADD, fd as usize, &event), -EPERM);
+    close(fd as usize);
+
+    // A closed fd leaves the interest list, the fd can be added again.
+    close(level[0]);
+    close(level[1]);
+    assert_eq!(pipe(&mut level), 0);
+    add(epfd, level[0], EpollEvents::EPOLLIN);
+    close(level[0]);
+    close(level[1]);
+    close(edge[0]);
+    close(epfd);
+    println!("epoll_test passed!");
+    0
+}
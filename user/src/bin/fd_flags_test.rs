@@ -0,0 +1,100 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, dup2, dup3, exec, exit, fcntl, fork, pipe2, read, waitpid, write, OpenFlags, EAGAIN,
+    EBADF, EINVAL, FD_CLOEXEC, F_DUPFD, F_DUPFD_CLOEXEC, F_GETFD, F_GETFL, F_SETFD, F_SETFL,
+};
+
+/// Kept open across exec.
+const KEPT_FD: usize = 10;
+/// Closed by exec.
+const CLOEXEC_FD: usize = 11;
+
+#[no_mangle]
+pub fn main(argc: usize, argv: &[&str]) -> i32 {
+    // Executed by the child below, check which fds survived exec.
+    if argc == 2 && argv[1] == "exec" {
+        assert_eq!(fcntl(KEPT_FD, F_GETFD, 0), 0);
+        assert_eq!(write(KEPT_FD, b"ok"), 2);
+        assert_eq!(fcntl(CLOEXEC_FD, F_GETFD, 0), -EBADF);
+        return 0;
+    }
+
+    let mut fds = [0usize; 2];
+    assert_eq!(pipe2(&mut fds, OpenFlags::CLOEXEC), 0);
+    assert_eq!(fcntl(fds[0], F_GETFD, 0), FD_CLOEXEC as isize);
+    assert_eq!(fcntl(fds[0], F_SETFD, 0), 0);
+    assert_eq!(fcntl(fds[0], F_GETFD, 0), 0);
+
+    // dup2 places the copy on the given fd without FD_CLOEXEC.
+    assert_eq!(dup2(fds[1], KEPT_FD), KEPT_FD as isize);
+    assert_eq!(fcntl(KEPT_FD, F_GETFD, 0), 0);
+    assert_eq!(
+        dup3(fds[1], CLOEXEC_FD, OpenFlags::CLOEXEC),
+        CLOEXEC_FD as isize
+    );
+    assert_eq!(fcntl(CLOEXEC_FD, F_GETFD, 0), FD_CLOEXEC as isize);
+    assert_eq!(dup2(KEPT_FD, KEPT_FD), KEPT_FD as isize);
+    assert_eq!(dup2(30, 31), -EBADF);
+    assert_eq!(dup3(KEPT_FD, KEPT_FD, OpenFlags::empty()), -EINVAL);
+    // The file on the target fd is replaced.
+    assert_eq!(dup2(fds[0], CLOEXEC_FD), CLOEXEC_FD as isize);
+    assert_eq!(
+        dup3(fds[1], CLOEXEC_FD, OpenFlags::CLOEXEC),
+        CLOEXEC_FD as isize
+    );
+    let mut buf = [0u8; 8];
+    assert_eq!(write(CLOEXEC_FD, b"abc"), 3);
+    assert_eq!(read(fds[0], &mut buf), 3);
+    assert_eq!(&buf[..3], b"abc");
+
+    // F_DUPFD finds the lowest free fd from arg.
+    assert_eq!(fcntl(fds[0], F_DUPFD, 20), 20);
+    assert_eq!(fcntl(fds[0], F_DUPFD_CLOEXEC, 20), 21);
+    assert_eq!(fcntl(21, F_GETFD, 0), FD_CLOEXEC as isize);
+    close(20);
+    close(21);
+
+    // Status flags are shared by all copies of the open file.
+    assert_eq!(
+        fcntl(fds[0], F_GETFL, 0),
+        OpenFlags::READ_ONLY.bits() as isize
+    );
+    assert_eq!(
+        fcntl(KEPT_FD, F_GETFL, 0),
+        OpenFlags::WRITE_ONLY.bits() as isize
+    );
+    let nonblock = OpenFlags::NONBLOCK.bits() as usize;
+    assert_eq!(fcntl(fds[0], F_SETFL, nonblock), 0);
+    assert_eq!(fcntl(fds[0], F_GETFL, 0), nonblock as isize);
+    assert_eq!(read(fds[0], &mut buf), -EAGAIN);
+    assert_eq!(fcntl(fds[0], F_SETFL, 0), 0);
+    assert_eq!(fcntl(99, F_GETFL, 0), -EBADF);
+
+    // Only fds without FD_CLOEXEC are inherited by the new program.
+    let pid = fork();
+    if pid == 0 {
+        let args = [
+            "fd_flags_test\0".as_ptr(),
+            "exec\0".as_ptr(),
+            core::ptr::null(),
+        ];
+        exec("fd_flags_test\0", &args);
+        exit(-4);
+    }
+    close(KEPT_FD);
+    close(CLOEXEC_FD);
+    close(fds[1]);
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid, &mut exit_code, 0), pid);
+    assert_eq!(exit_code, 0);
+    assert_eq!(read(fds[0], &mut buf), 2);
+    assert_eq!(&buf[..2], b"ok");
+    close(fds[0]);
+    println!("fd_flags_test passed!");
+    0
+}
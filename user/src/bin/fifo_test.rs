@@ -0,0 +1,81 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, exit, fork, mkfifo, open, read, sleep, stat, unlink, waitpid, write, OpenFlags, Stat,
+    ENXIO,
+};
+
+const FIFO: &str = "/fifo_test\0";
+
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(mkfifo(FIFO, 0o644), 0);
+    assert_eq!(mkfifo(FIFO, 0o644), -1);
+    let mut st = Stat::default();
+    assert_eq!(stat(FIFO, &mut st), 0);
+    assert!(st.is_fifo());
+
+    // Without readers, a non-blocking writer fails and a non-blocking reader gets end of file.
+    assert_eq!(
+        open(FIFO, OpenFlags::WRITE_ONLY | OpenFlags::NONBLOCK),
+        -ENXIO
+    );
+    let fd = open(FIFO, OpenFlags::READ_ONLY | OpenFlags::NONBLOCK);
+    assert!(fd >= 0);
+    let mut buf = [0u8; 32];
+    assert_eq!(read(fd as usize, &mut buf), 0);
+    close(fd as usize);
+
+    // A process which does not share any fd writes to the FIFO,
+    // both opens wait until the other side is opened.
+    let pid = fork();
+    if pid == 0 {
+        sleep(30);
+        let fd = open(FIFO, OpenFlags::WRITE_ONLY);
+        assert!(fd >= 0);
+        assert_eq!(write(fd as usize, b"hello fifo"), 10);
+        exit(0);
+    }
+    let fd = open(FIFO, OpenFlags::READ_ONLY);
+    assert!(fd >= 0);
+    assert_eq!(read(fd as usize, &mut buf), 10);
+    assert_eq!(&buf[..10], b"hello fifo");
+    // End of file after the writer exits.
+    assert_eq!(read(fd as usize, &mut buf), 0);
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid, &mut exit_code, 0), pid);
+    assert_eq!(exit_code, 0);
+    close(fd as usize);
+
+    // A writer waits for a reader.
+    let pid = fork();
+    if pid == 0 {
+        sleep(30);
+        let fd = open(FIFO, OpenFlags::READ_ONLY);
+        assert!(fd >= 0);
+        let mut buf = [0u8; 8];
+        assert_eq!(read(fd as usize, &mut buf), 5);
+        exit(0);
+    }
+    let fd = open(FIFO, OpenFlags::WRITE_ONLY);
+    assert!(fd >= 0);
+    assert_eq!(write(fd as usize, b"later"), 5);
+    assert_eq!(waitpid(pid, &mut exit_code, 0), pid);
+    assert_eq!(exit_code, 0);
+    close(fd as usize);
+
+    // Opening for both reading and writing never waits.
+    let fd = open(FIFO, OpenFlags::READ_WRITE);
+    assert!(fd >= 0);
+    assert_eq!(write(fd as usize, b"both"), 4);
+    assert_eq!(read(fd as usize, &mut buf), 4);
+    close(fd as usize);
+
+    assert_eq!(unlink(FIFO), 0);
+    println!("fifo_test passed!");
+    0
+}
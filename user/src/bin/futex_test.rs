@@ -0,0 +1,218 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+use user_lib::{
+    exit, fork, futex_cmp_requeue, futex_requeue, futex_wait, futex_wake, get_time, mutex_create,
+    mutex_destroy, mutex_lock, mutex_unlock, shmat, shmctl, shmget, sleep,
+    sync::{Condvar, Mutex, Semaphore},
+    task_info, thread_create, waitpid, waittid, ShmidDs, TaskInfo, TimeSpec, EAGAIN, EINVAL,
+    ETIMEDOUT, IPC_PRIVATE, IPC_RMID,
+};
+
+const THREADS: usize = 4;
+const PER_THREAD: usize = 1000;
+/// The syscall id of futex.
+const SYSCALL_FUTEX: usize = 98;
+
+static WORD: AtomicU32 = AtomicU32::new(0);
+static WORD2: AtomicU32 = AtomicU32::new(0);
+static WAITING: AtomicUsize = AtomicUsize::new(0);
+static DONE: AtomicBool = AtomicBool::new(false);
+
+static MUTEX: Mutex = Mutex::new();
+static mut COUNTER: usize = 0;
+static ITEMS: Semaphore = Semaphore::new(0);
+static SUM: AtomicUsize = AtomicUsize::new(0);
+static READY_LOCK: Mutex = Mutex::new();
+static READY_COND: Condvar = Condvar::new();
+static mut READY: bool = false;
+
+fn spawn(entry: fn() -> !) -> Vec<usize> {
+    WAITING.store(0, Ordering::SeqCst);
+    DONE.store(false, Ordering::SeqCst);
+    let tids = (0..THREADS)
+        .map(|_| thread_create(entry as usize, 0) as usize)
+        .collect();
+    while WAITING.load(Ordering::SeqCst) < THREADS {
+        sleep(1);
+    }
+    // Let the threads block on the futex.
+    sleep(50);
+    tids
+}
+
+fn join(tids: Vec<usize>) {
+    for tid in tids {
+        assert_eq!(waittid(tid), 0);
+    }
+}
+
+fn futex_count() -> u32 {
+    let mut info = TaskInfo::new();
+    assert_eq!(task_info(&mut info), 0);
+    info.syscall_times[SYSCALL_FUTEX]
+}
+
+fn basic_test() {
+    assert_eq!(futex_wait(&WORD, 1, None), -EAGAIN);
+    assert_eq!(futex_wake(&WORD, 1), 0);
+    let start = get_time();
+    let timeout = TimeSpec::from_ms(50);
+    assert_eq!(futex_wait(&WORD, 0, Some(&timeout)), -ETIMEDOUT);
+    assert!(get_time() - start >= 50);
+}
+
+fn wait_until_done() -> ! {
+    WAITING.fetch_add(1, Ordering::SeqCst);
+    while !DONE.load(Ordering::SeqCst) {
+        futex_wait(&WORD, 0, None);
+    }
+    exit(0)
+}
+
+fn wake_test() {
+    let tids = spawn(wait_until_done);
+    // A thread woken up waits again, since it is not done.
+    assert_eq!(futex_wake(&WORD, 1), 1);
+    sleep(50);
+    DONE.store(true, Ordering::SeqCst);
+    assert_eq!(futex_wake(&WORD, usize::MAX), THREADS as isize);
+    join(tids);
+}
+
+fn requeue_test() {
+    let tids = spawn(wait_until_done);
+    assert_eq!(futex_cmp_requeue(&WORD, 1, &WORD2, usize::MAX, 1), -EAGAIN);
+    // One is woken up, and the others wait on WORD2 without being woken up.
+    assert_eq!(
+        futex_requeue(&WORD, 1, &WORD2, usize::MAX),
+        THREADS as isize
+    );
+    sleep(50);
+    DONE.store(true, Ordering::SeqCst);
+    assert_eq!(futex_wake(&WORD, usize::MAX), 1);
+    assert_eq!(futex_wake(&WORD2, usize::MAX), THREADS as isize - 1);
+    join(tids);
+}
+
+/// Processes sharing a segment wait on the same futex.
+fn shared_test() {
+    let id = shmget(IPC_PRIVATE, 4096, 0o600);
+    assert!(id >= 0);
+    let addr = shmat(id as usize, 0, 0);
+    assert!(addr > 0);
+    let word = unsafe { &*(addr as *const AtomicU32) };
+    let pid = fork();
+    if pid == 0 {
+        while word.load(Ordering::SeqCst) == 0 {
+            futex_wait(word, 0, None);
+        }
+        exit(0);
+    }
+    sleep(50);
+    word.store(1, Ordering::SeqCst);
+    assert_eq!(futex_wake(word, 1), 1);
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid, &mut exit_code, 0), pid);
+    assert_eq!(exit_code, 0);
+    assert_eq!(shmctl(id as usize, IPC_RMID, &mut ShmidDs::default()), 0);
+}
+
+fn add() -> ! {
+    for _ in 0..PER_THREAD {
+        MUTEX.lock();
+        unsafe { COUNTER += 1 };
+        MUTEX.unlock();
+    }
+    exit(0)
+}
+
+fn consume() -> ! {
+    for _ in 0..PER_THREAD {
+        ITEMS.down();
+        SUM.fetch_add(1, Ordering::Relaxed);
+    }
+    exit(0)
+}
+
+fn wait_ready() -> ! {
+    READY_LOCK.lock();
+    while unsafe { !READY } {
+        READY_COND.wait(&READY_LOCK);
+    }
+    READY_LOCK.unlock();
+    exit(0)
+}
+
+fn sync_test() {
+    // Uncontended locking does not enter the kernel.
+    let before = futex_count();
+    for _ in 0..PER_THREAD {
+        MUTEX.lock();
+        MUTEX.unlock();
+        ITEMS.up();
+        ITEMS.down();
+    }
+    assert_eq!(futex_count(), before);
+
+    let tids: Vec<usize> = (0..THREADS)
+        .map(|_| thread_create(add as fn() -> ! as usize, 0) as usize)
+        .collect();
+    join(tids);
+    assert_eq!(unsafe { COUNTER }, THREADS * PER_THREAD);
+
+    let tids: Vec<usize> = (0..THREADS)
+        .map(|_| thread_create(consume as fn() -> ! as usize, 0) as usize)
+        .collect();
+    for _ in 0..THREADS * PER_THREAD {
+        ITEMS.up();
+    }
+    join(tids);
+    assert_eq!(SUM.load(Ordering::Relaxed), THREADS * PER_THREAD);
+
+    let tid = thread_create(wait_ready as fn() -> ! as usize, 0) as usize;
+    sleep(20);
+    READY_LOCK.lock();
+    unsafe { READY = true };
+    READY_COND.signal();
+    READY_LOCK.unlock();
+    assert_eq!(waittid(tid), 0);
+}
+
+/// The ids of the mutexes of the lib run out, and creating one more fails instead of panicking.
+/// A destroyed id is reused, and an unknown id fails.
+fn ids_test() {
+    let ids: Vec<isize> = (0..)
+        .map(|_| mutex_create())
+        .take_while(|id| *id >= 0)
+        .collect();
+    assert!(!ids.is_empty());
+    assert_eq!(mutex_create(), -EAGAIN);
+    let last = *ids.last().unwrap() as usize;
+    assert_eq!(mutex_destroy(last), 0);
+    assert_eq!(mutex_destroy(last), -EINVAL);
+    assert_eq!(mutex_lock(last), -EINVAL);
+    assert_eq!(mutex_lock(usize::MAX), -EINVAL);
+    assert_eq!(mutex_create(), last as isize);
+    assert_eq!(mutex_lock(last), 0);
+    assert_eq!(mutex_unlock(last), 0);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    basic_test();
+    wake_test();
+    requeue_test();
+    shared_test();
+    sync_test();
+    ids_test();
+    println!("futex_test passed!");
+    0
+}
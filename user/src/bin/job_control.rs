@@ -0,0 +1,93 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    exit, fork, get_pid, getpgid, getsid, kill, setpgid, setsid, sigaction, sleep, waitpid,
+    wifcontinued, wifstopped, wstopsig, yield_, SignalAction, ENOSYS, EPERM, SIGCONT, SIGKILL,
+    SIGSTOP, SIGUSR1, WCONTINUED, WUNTRACED,
+};
+
+/// Processes in other sessions can not be signalled.
+fn other_session_test() {
+    let child = fork();
+    if child == 0 {
+        assert_eq!(setsid(), get_pid());
+        sleep(50);
+        exit(0);
+    }
+    while getsid(child as usize) != child {
+        yield_();
+    }
+    assert_eq!(kill(child, SIGKILL), -EPERM);
+    assert_eq!(kill(-child, SIGKILL), -EPERM);
+    let mut status = 0;
+    assert_eq!(waitpid(child, &mut status, 0), child);
+    assert_eq!(status, 0);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let pid = get_pid() as usize;
+    let pgid = getpgid(0);
+    let sid = getsid(0);
+    assert!(pgid >= 0 && sid >= 0);
+
+    let child = fork();
+    if child == 0 {
+        // The child inherits the session.
+        assert_eq!(getsid(0), sid);
+        assert_eq!(setpgid(0, 0), 0);
+        assert_eq!(getpgid(0), get_pid());
+        // A process group leader can not create a new session.
+        assert_eq!(setsid(), -1);
+        loop {
+            yield_();
+        }
+    }
+    let child = child as usize;
+    // Put the child in its own group from the parent side too.
+    assert_eq!(setpgid(child, child), 0);
+    assert_eq!(getpgid(child), child as isize);
+    assert_eq!(getpgid(pid), pgid);
+
+    // Stop the group, then continue it.
+    let mut status = 0;
+    assert_eq!(kill(-(child as isize), SIGSTOP), 0);
+    assert_eq!(
+        waitpid(child as isize, &mut status, WUNTRACED),
+        child as isize
+    );
+    assert!(wifstopped(status));
+    assert_eq!(wstopsig(status), SIGSTOP);
+    assert_eq!(kill(-(child as isize), SIGCONT), 0);
+    assert_eq!(
+        waitpid(child as isize, &mut status, WCONTINUED),
+        child as isize
+    );
+    assert!(wifcontinued(status));
+
+    // Signal a group which does not exist.
+    assert_eq!(kill(-(child as isize) - 1000, SIGCONT), -1);
+
+    // SIGKILL kills the child even if it is stopped.
+    assert_eq!(kill(child as isize, SIGSTOP), 0);
+    assert_eq!(
+        waitpid(child as isize, &mut status, WUNTRACED),
+        child as isize
+    );
+    assert!(wifstopped(status));
+    assert_eq!(kill(child as isize, SIGKILL), 0);
+    assert_eq!(waitpid(child as isize, &mut status, 0), child as isize);
+    assert_eq!(status, -9);
+
+    other_session_test();
+    // Signal handlers are not supported.
+    let mut action = SignalAction::default();
+    action.handler = main as usize;
+    assert_eq!(sigaction(SIGUSR1, Some(&action), None), -ENOSYS);
+    println!("job_control passed!");
+    exit(0);
+}
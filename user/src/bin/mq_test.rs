@@ -0,0 +1,232 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, exit, fork, get_time, mq_getattr, mq_open, mq_receive, mq_send, mq_setattr,
+    mq_timedreceive, mq_timedsend, mq_unlink, sleep, waitpid, MqAttr, OpenFlags, TimeSpec, EACCES,
+    EAGAIN, EBADF, EEXIST, EINVAL, EMSGSIZE, ENOENT, ETIMEDOUT, MQ_NONBLOCK, MQ_PRIO_MAX,
+};
+
+const NAME: &str = "/mq_test\0";
+const DEPTH: usize = 4;
+const MSGSIZE: usize = 64;
+const ITEMS: usize = 100;
+
+fn wait_child(pid: isize) {
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid, &mut exit_code, 0), pid);
+    assert_eq!(exit_code, 0);
+}
+
+fn create(flags: OpenFlags) -> usize {
+    let flags = flags | OpenFlags::CREATE | OpenFlags::EXCL;
+    let fd = mq_open(NAME, flags, 0o600, Some(&MqAttr::new(DEPTH, MSGSIZE)));
+    assert!(fd >= 0);
+    fd as usize
+}
+
+fn open(flags: OpenFlags) -> usize {
+    let fd = mq_open(NAME, flags, 0, None);
+    assert!(fd >= 0);
+    fd as usize
+}
+
+fn deadline_after(ms: usize) -> TimeSpec {
+    TimeSpec::from_ms(get_time() as usize + ms)
+}
+
+fn open_test() {
+    let fd = create(OpenFlags::READ_WRITE);
+    let attr = MqAttr::new(DEPTH, MSGSIZE);
+    let flags = || OpenFlags::READ_WRITE | OpenFlags::CREATE | OpenFlags::EXCL;
+    assert_eq!(mq_open(NAME, flags(), 0o600, Some(&attr)), -EEXIST);
+    assert_eq!(
+        mq_open("/mq_none\0", OpenFlags::READ_ONLY, 0, None),
+        -ENOENT
+    );
+    assert_eq!(mq_open("mq_test\0", flags(), 0o600, None), -EINVAL);
+    assert_eq!(mq_open("/mq/test\0", flags(), 0o600, None), -EINVAL);
+    let bad = MqAttr::new(0, MSGSIZE);
+    assert_eq!(mq_open("/mq_bad\0", flags(), 0o600, Some(&bad)), -EINVAL);
+
+    let mut got = MqAttr::default();
+    assert_eq!(mq_getattr(fd, &mut got), 0);
+    assert_eq!((got.maxmsg, got.msgsize, got.curmsgs), (DEPTH, MSGSIZE, 0));
+    assert_eq!(got.flags, 0);
+
+    // The queue is found by its name until it is unlinked, and opened fds still use it.
+    let other = open(OpenFlags::WRITE_ONLY);
+    assert_eq!(mq_send(other, b"named", 0), 0);
+    assert_eq!(mq_unlink(NAME), 0);
+    assert_eq!(mq_unlink(NAME), -ENOENT);
+    assert_eq!(mq_open(NAME, OpenFlags::READ_ONLY, 0, None), -ENOENT);
+    let mut buf = [0u8; MSGSIZE];
+    assert_eq!(mq_receive(fd, &mut buf, None), 5);
+    assert_eq!(&buf[..5], b"named");
+    close(other);
+    close(fd);
+
+    // The mode is checked when an existing queue is opened.
+    let fd = mq_open(NAME, OpenFlags::READ_ONLY | OpenFlags::CREATE, 0o400, None);
+    assert!(fd >= 0);
+    assert_eq!(mq_send(fd as usize, b"x", 0), -EBADF);
+    assert_eq!(mq_open(NAME, OpenFlags::WRITE_ONLY, 0, None), -EACCES);
+    let reader = open(OpenFlags::READ_ONLY);
+    assert_eq!(mq_getattr(reader, &mut got), 0);
+    assert_eq!((got.maxmsg, got.msgsize), (10, 1024));
+    close(reader);
+    close(fd as usize);
+    assert_eq!(mq_unlink(NAME), 0);
+}
+
+fn priority_test() {
+    let fd = create(OpenFlags::READ_WRITE);
+    assert_eq!(mq_send(fd, b"low", 1), 0);
+    assert_eq!(mq_send(fd, b"high first", 5), 0);
+    assert_eq!(mq_send(fd, b"middle", 3), 0);
+    assert_eq!(mq_send(fd, b"high second", 5), 0);
+    let mut got = MqAttr::default();
+    assert_eq!(mq_getattr(fd, &mut got), 0);
+    assert_eq!(got.curmsgs, DEPTH);
+
+    let mut buf = [0u8; MSGSIZE];
+    let mut prio = 0;
+    let expected: [(&[u8], u32); 4] = [
+        (b"high first", 5),
+        (b"high second", 5),
+        (b"middle", 3),
+        (b"low", 1),
+    ];
+    for (msg, msg_prio) in expected {
+        let len = mq_receive(fd, &mut buf, Some(&mut prio));
+        assert_eq!(len, msg.len() as isize);
+        assert_eq!(&buf[..msg.len()], msg);
+        assert_eq!(prio, msg_prio);
+    }
+
+    // Bad messages, buffers and descriptors.
+    assert_eq!(mq_send(fd, &[0u8; MSGSIZE + 1], 0), -EMSGSIZE);
+    assert_eq!(mq_send(fd, b"x", MQ_PRIO_MAX), -EINVAL);
+    assert_eq!(mq_receive(fd, &mut buf[..MSGSIZE - 1], None), -EMSGSIZE);
+    let writer = open(OpenFlags::WRITE_ONLY);
+    let reader = open(OpenFlags::READ_ONLY);
+    assert_eq!(mq_receive(writer, &mut buf, None), -EBADF);
+    assert_eq!(mq_send(reader, b"x", 0), -EBADF);
+    assert_eq!(mq_send(0, b"x", 0), -EBADF);
+    close(writer);
+    close(reader);
+    close(fd);
+    assert_eq!(mq_unlink(NAME), 0);
+}
+
+fn nonblock_test() {
+    let fd = create(OpenFlags::READ_WRITE | OpenFlags::NONBLOCK);
+    let mut buf = [0u8; MSGSIZE];
+    assert_eq!(mq_receive(fd, &mut buf, None), -EAGAIN);
+    for i in 0..DEPTH {
+        assert_eq!(mq_send(fd, &[i as u8], 0), 0);
+    }
+    assert_eq!(mq_send(fd, b"full", 0), -EAGAIN);
+    let mut got = MqAttr::default();
+    assert_eq!(mq_getattr(fd, &mut got), 0);
+    assert_eq!(got.flags, MQ_NONBLOCK);
+
+    // Only the flags of the descriptor are changed.
+    let mut old = MqAttr::default();
+    assert_eq!(mq_setattr(fd, &MqAttr::default(), Some(&mut old)), 0);
+    assert_eq!((old.flags, old.curmsgs), (MQ_NONBLOCK, DEPTH));
+    assert_eq!(mq_getattr(fd, &mut got), 0);
+    assert_eq!((got.flags, got.maxmsg), (0, DEPTH));
+    let mut bad = MqAttr::default();
+    bad.flags = 1;
+    assert_eq!(mq_setattr(fd, &bad, None), -EINVAL);
+    close(fd);
+    assert_eq!(mq_unlink(NAME), 0);
+}
+
+fn timeout_test() {
+    let fd = create(OpenFlags::READ_WRITE);
+    let mut buf = [0u8; MSGSIZE];
+    let start = get_time();
+    assert_eq!(
+        mq_timedreceive(fd, &mut buf, None, &deadline_after(100)),
+        -ETIMEDOUT
+    );
+    assert!(get_time() - start >= 100);
+    // A past deadline does not block, and is not checked when the queue is ready.
+    let past = TimeSpec::from_ms(0);
+    assert_eq!(mq_timedreceive(fd, &mut buf, None, &past), -ETIMEDOUT);
+    for _ in 0..DEPTH {
+        assert_eq!(mq_timedsend(fd, b"x", 0, &past), 0);
+    }
+    let start = get_time();
+    assert_eq!(mq_timedsend(fd, b"x", 0, &deadline_after(100)), -ETIMEDOUT);
+    assert!(get_time() - start >= 100);
+    let bad = TimeSpec {
+        sec: 0,
+        nsec: 1_000_000_000,
+    };
+    assert_eq!(mq_timedsend(fd, b"x", 0, &bad), -EINVAL);
+
+    // A timed receive is woken up by a sender before the deadline.
+    let pid = fork();
+    if pid == 0 {
+        let fd = open(OpenFlags::READ_ONLY);
+        for _ in 0..DEPTH {
+            assert_eq!(mq_receive(fd, &mut buf, None), 1);
+        }
+        let start = get_time();
+        let len = mq_timedreceive(fd, &mut buf, None, &deadline_after(5000));
+        assert_eq!(len, 4);
+        assert_eq!(&buf[..4], b"wake");
+        assert!(get_time() - start < 5000);
+        exit(0);
+    }
+    sleep(100);
+    assert_eq!(mq_timedsend(fd, b"wake", 0, &deadline_after(5000)), 0);
+    wait_child(pid);
+    close(fd);
+    assert_eq!(mq_unlink(NAME), 0);
+}
+
+/// Messages pass through a bounded queue between processes,
+/// the sender blocks while it is full and the receiver blocks while it is empty.
+fn producer_consumer_test() {
+    let fd = create(OpenFlags::READ_ONLY);
+    let producer = fork();
+    if producer == 0 {
+        let fd = open(OpenFlags::WRITE_ONLY);
+        for item in 0..ITEMS {
+            assert_eq!(mq_send(fd, &item.to_ne_bytes(), 0), 0);
+        }
+        exit(0);
+    }
+    // Let the producer fill the queue and block.
+    sleep(50);
+    let mut got = MqAttr::default();
+    assert_eq!(mq_getattr(fd, &mut got), 0);
+    assert_eq!(got.curmsgs, DEPTH);
+    let mut buf = [0u8; MSGSIZE];
+    for item in 0..ITEMS {
+        let len = mq_receive(fd, &mut buf, None);
+        assert_eq!(len, 8);
+        assert_eq!(usize::from_ne_bytes(buf[..8].try_into().unwrap()), item);
+    }
+    wait_child(producer);
+    close(fd);
+    assert_eq!(mq_unlink(NAME), 0);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    open_test();
+    priority_test();
+    nonblock_test();
+    timeout_test();
+    producer_consumer_test();
+    println!("mq_test passed!");
+    0
+}
@@ -0,0 +1,79 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    accept_in, bind_in, close, connect_in, listen, read, socket, write, SockaddrIn, AF_INET,
+    INADDR_ANY, SOCK_STREAM,
+};
+
+/// The host seen from QEMU user-mode networking, where `make net-server` listens.
+const HOST: [u8; 4] = [10, 0, 2, 2];
+const HOST_PORT: u16 = 6200;
+/// The port forwarded from the host by QEMU, with `make run NET_FWD=1`.
+const FORWARD_PORT: u16 = 6201;
+
+/// Send a message to the echo server on the host and check the reply.
+fn client() -> i32 {
+    let fd = socket(AF_INET, SOCK_STREAM) as usize;
+    let ret = connect_in(fd, &SockaddrIn::new(HOST, HOST_PORT));
+    if ret < 0 {
+        println!(
+            "net_client: connect failed: {}, is `make net-server` running?",
+            ret
+        );
+        return -1;
+    }
+    let message = b"hello from rOS";
+    assert_eq!(write(fd, message), message.len() as isize);
+    let mut buf = [0u8; 64];
+    let mut len = 0;
+    while len < message.len() {
+        let ret = read(fd, &mut buf[len..]);
+        assert!(ret > 0);
+        len += ret as usize;
+    }
+    assert_eq!(&buf[..len], message);
+    close(fd);
+    println!("net_client: echo from the host passed!");
+    0
+}
+
+/// Echo one connection from the host to localhost:FORWARD_PORT.
+fn server() -> i32 {
+    let fd = socket(AF_INET, SOCK_STREAM) as usize;
+    assert_eq!(bind_in(fd, &SockaddrIn::new(INADDR_ANY, FORWARD_PORT)), 0);
+    assert_eq!(listen(fd, 1), 0);
+    println!("net_client: listening on port {}", FORWARD_PORT);
+    let mut peer = SockaddrIn::default();
+    let conn = accept_in(fd, Some(&mut peer));
+    assert!(conn >= 0);
+    let conn = conn as usize;
+    println!(
+        "net_client: connection from {:?}:{}",
+        peer.addr,
+        peer.port()
+    );
+    let mut buf = [0u8; 256];
+    loop {
+        let len = read(conn, &mut buf);
+        if len <= 0 {
+            break;
+        }
+        write(conn, &buf[..len as usize]);
+    }
+    close(conn);
+    close(fd);
+    0
+}
+
+#[no_mangle]
+pub fn main(argc: usize, argv: &[&str]) -> i32 {
+    if argc > 1 && argv[1] == "serve" {
+        server()
+    } else {
+        client()
+    }
+}
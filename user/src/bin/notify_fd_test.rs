@@ -0,0 +1,197 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, epoll_create1, epoll_ctl, epoll_wait, eventfd, eventfd_read, eventfd_write, exit, fork,
+    get_pid, get_time, kill, poll, read, signalfd, signalfd_read, sleep, timerfd_create,
+    timerfd_gettime, timerfd_settime, waitpid, EpollEvent, EpollEvents, EventfdFlags, ITimerSpec,
+    OpenFlags, PollEvents, PollFd, SignalFlags, SignalfdFlags, SignalfdSiginfo, TimeSpec,
+    TimerfdFlags, CLOCK_MONOTONIC, EAGAIN, EBADF, EINVAL, EPOLL_CTL_ADD, SIGUSR1, SIGUSR2,
+};
+
+fn wait_child(pid: isize) {
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid, &mut exit_code, 0), pid);
+    assert_eq!(exit_code, 0);
+}
+
+fn eventfd_test() {
+    let fd = eventfd(0, EventfdFlags::EFD_NONBLOCK) as usize;
+    let mut value = 0;
+    assert_eq!(eventfd_read(fd, &mut value), -EAGAIN);
+    assert_eq!(eventfd_write(fd, 3), 8);
+    assert_eq!(eventfd_write(fd, 4), 8);
+    let mut fds = [PollFd::new(fd as i32, PollEvents::POLLIN)];
+    assert_eq!(poll(&mut fds, 0), 1);
+    assert_eq!(eventfd_read(fd, &mut value), 8);
+    assert_eq!(value, 7);
+    assert_eq!(poll(&mut fds, 0), 0);
+    assert_eq!(eventfd_write(fd, u64::MAX), -EINVAL);
+    assert_eq!(read(fd, &mut [0u8; 4]), -EINVAL);
+    close(fd);
+
+    // A semaphore is decreased by 1 for every read.
+    let flags = EventfdFlags::EFD_SEMAPHORE | EventfdFlags::EFD_NONBLOCK;
+    let fd = eventfd(2, flags) as usize;
+    for _ in 0..2 {
+        assert_eq!(eventfd_read(fd, &mut value), 8);
+        assert_eq!(value, 1);
+    }
+    assert_eq!(eventfd_read(fd, &mut value), -EAGAIN);
+    close(fd);
+
+    // A reader blocks until another process writes.
+    let fd = eventfd(0, EventfdFlags::empty()) as usize;
+    let pid = fork();
+    if pid == 0 {
+        sleep(30);
+        eventfd_write(fd, 5);
+        exit(0);
+    }
+    assert_eq!(eventfd_read(fd, &mut value), 8);
+    assert_eq!(value, 5);
+    wait_child(pid);
+    close(fd);
+}
+
+fn timerfd_test() {
+    let fd = timerfd_create(CLOCK_MONOTONIC, TimerfdFlags::empty()) as usize;
+    let mut buf = [0u8; 8];
+
+    // One shot.
+    let start = get_time();
+    let setting = ITimerSpec {
+        interval: TimeSpec::default(),
+        value: TimeSpec::from_ms(30),
+    };
+    assert_eq!(timerfd_settime(fd, 0, &setting, None), 0);
+    assert_eq!(read(fd, &mut buf), 8);
+    assert_eq!(u64::from_ne_bytes(buf), 1);
+    assert!(get_time() - start >= 30);
+    let mut current = ITimerSpec::default();
+    assert_eq!(timerfd_gettime(fd, &mut current), 0);
+    assert_eq!(current.value.sec + current.value.nsec, 0);
+
+    // Periodic, the read blocks until the first expiration,
+    // and more may have been accumulated if this thread is scheduled late.
+    let start = get_time();
+    let setting = ITimerSpec {
+        interval: TimeSpec::from_ms(10),
+        value: TimeSpec::from_ms(10),
+    };
+    assert_eq!(timerfd_settime(fd, 0, &setting, None), 0);
+    assert_eq!(read(fd, &mut buf), 8);
+    assert!(u64::from_ne_bytes(buf) >= 1);
+    assert!(get_time() - start >= 10);
+    assert_eq!(timerfd_gettime(fd, &mut current), 0);
+    assert_eq!(current.interval.nsec, 10_000_000);
+
+    // Disarm, the old setting is returned.
+    let mut old = ITimerSpec::default();
+    assert_eq!(
+        timerfd_settime(fd, 0, &ITimerSpec::default(), Some(&mut old)),
+        0
+    );
+    assert_eq!(old.interval.nsec, 10_000_000);
+    close(fd);
+
+    // Wait for the timer with epoll.
+    let fd = timerfd_create(CLOCK_MONOTONIC, TimerfdFlags::TFD_NONBLOCK) as usize;
+    assert_eq!(read(fd, &mut buf), -EAGAIN);
+    let epfd = epoll_create1(OpenFlags::empty()) as usize;
+    let event = EpollEvent::new(EpollEvents::EPOLLIN, fd as u64);
+    assert_eq!(epoll_ctl(epfd, EPOLL_CTL_ADD, fd, &event), 0);
+    let setting = ITimerSpec {
+        interval: TimeSpec::default(),
+        value: TimeSpec::from_ms(20),
+    };
+    assert_eq!(timerfd_settime(fd, 0, &setting, None), 0);
+    let mut events = [EpollEvent::default(); 2];
+    assert_eq!(epoll_wait(epfd, &mut events, -1), 1);
+    assert_eq!(events[0].data, fd as u64);
+    assert_eq!(read(fd, &mut buf), 8);
+    close(epfd);
+
+    // Errors.
+    assert_eq!(timerfd_create(5, TimerfdFlags::empty()), -EINVAL);
+    assert_eq!(timerfd_gettime(99, &mut current), -EBADF);
+    let efd = eventfd(0, EventfdFlags::empty()) as usize;
+    assert_eq!(timerfd_settime(efd, 0, &setting, None), -EINVAL);
+    let bad = ITimerSpec {
+        interval: TimeSpec::default(),
+        value: TimeSpec {
+            sec: 0,
+            nsec: 1_000_000_000,
+        },
+    };
+    assert_eq!(timerfd_settime(fd, 0, &bad, None), -EINVAL);
+    close(efd);
+    close(fd);
+}
+
+fn signalfd_test() {
+    let mask = SignalFlags::SIGUSR1 | SignalFlags::SIGUSR2;
+    let fd = signalfd(-1, mask, SignalfdFlags::SFD_NONBLOCK);
+    assert!(fd >= 0);
+    let fd = fd as usize;
+    let mut infos = [SignalfdSiginfo::default(); 2];
+    assert_eq!(signalfd_read(fd, &mut infos), -EAGAIN);
+
+    // Pending signals in the mask are read in the order of signal numbers.
+    let pid = get_pid();
+    assert_eq!(kill(pid, SIGUSR2), 0);
+    assert_eq!(kill(pid, SIGUSR1), 0);
+    let mut fds = [PollFd::new(fd as i32, PollEvents::POLLIN)];
+    assert_eq!(poll(&mut fds, 0), 1);
+    let size = core::mem::size_of::<SignalfdSiginfo>() as isize;
+    assert_eq!(size, 128);
+    assert_eq!(signalfd_read(fd, &mut infos), 2 * size);
+    assert_eq!(infos[0].signo, SIGUSR1);
+    assert_eq!(infos[1].signo, SIGUSR2);
+    assert_eq!(signalfd_read(fd, &mut infos), -EAGAIN);
+
+    // Signals out of the mask are not read.
+    assert_eq!(
+        signalfd(fd as isize, SignalFlags::SIGUSR1, SignalfdFlags::empty()),
+        fd as isize
+    );
+    assert_eq!(kill(pid, SIGUSR2), 0);
+    assert_eq!(signalfd_read(fd, &mut infos), -EAGAIN);
+    assert_eq!(
+        signalfd(fd as isize, SignalFlags::SIGUSR2, SignalfdFlags::empty()),
+        fd as isize
+    );
+    assert_eq!(signalfd_read(fd, &mut infos[..1]), size);
+    close(fd);
+
+    // A reader blocks until a signal is sent by another process.
+    let fd = signalfd(-1, SignalFlags::SIGUSR1, SignalfdFlags::empty()) as usize;
+    let child = fork();
+    if child == 0 {
+        sleep(30);
+        kill(pid, SIGUSR1);
+        exit(0);
+    }
+    assert_eq!(signalfd_read(fd, &mut infos[..1]), size);
+    assert_eq!(infos[0].signo, SIGUSR1);
+    wait_child(child);
+    let efd = eventfd(0, EventfdFlags::empty());
+    assert_eq!(
+        signalfd(efd, SignalFlags::SIGUSR1, SignalfdFlags::empty()),
+        -EINVAL
+    );
+    close(efd as usize);
+    close(fd);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    eventfd_test();
+    timerfd_test();
+    signalfd_test();
+    println!("notify_fd_test passed!");
+    0
+}
@@ -8,7 +8,7 @@ extern crate alloc;
 
 use alloc::vec::Vec;
 use user_lib::{exit, get_time, sleep};
-use user_lib::{mutex_blocking_create, mutex_lock, mutex_unlock};
+use user_lib::{kernel_mutex_create, kernel_mutex_lock, kernel_mutex_unlock};
 use user_lib::{thread_create, waittid};
 
 const N: usize = 5;
@@ -47,8 +47,8 @@ fn philosopher_dining_problem(id: *const usize) {
             THINK[id][2 * round + 1] = get_time_u();
         }
         // wait for forks
-        mutex_lock(min);
-        mutex_lock(max);
+        kernel_mutex_lock(min);
+        kernel_mutex_lock(max);
         // eating
         unsafe {
             EAT[id][2 * round] = get_time_u();
@@ -57,8 +57,8 @@ fn philosopher_dining_problem(id: *const usize) {
         unsafe {
             EAT[id][2 * round + 1] = get_time_u();
         }
-        mutex_unlock(max);
-        mutex_unlock(min);
+        kernel_mutex_unlock(max);
+        kernel_mutex_unlock(min);
     }
     exit(0)
 }
@@ -69,7 +69,7 @@ pub fn main() -> i32 {
     let ids: Vec<_> = (0..N).collect();
     let start = get_time_u();
     for i in 0..N {
-        assert_eq!(mutex_blocking_create(), i);
+        assert_eq!(kernel_mutex_create(true), i);
         v.push(thread_create(
             philosopher_dining_problem as usize,
             &ids.as_slice()[i] as *const _ as usize,
@@ -0,0 +1,198 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use user_lib::{
+    enable_deadlock_detect, exit, get_tid, getpriority, kernel_condvar_create, kernel_condvar_wait,
+    kernel_mutex_create_with_flags, kernel_mutex_lock, kernel_mutex_unlock, setpriority, sleep,
+    thread_create, waittid, EDEADLK, EINVAL, EPERM, MUTEX_BLOCKING, MUTEX_ERRORCHECK,
+    MUTEX_PRIO_INHERIT, MUTEX_RECURSIVE, PRIO_THREAD,
+};
+
+const LOW: isize = 10;
+const HIGH: isize = -5;
+const MAX_WAITERS: usize = 4;
+
+static MUTEX: AtomicUsize = AtomicUsize::new(0);
+static LOCKED: AtomicBool = AtomicBool::new(false);
+static ORDER: [AtomicUsize; MAX_WAITERS] = [
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+];
+static ORDER_LEN: AtomicUsize = AtomicUsize::new(0);
+
+fn create(flags: usize) -> usize {
+    let mutex = kernel_mutex_create_with_flags(flags);
+    assert!(mutex >= 0);
+    mutex as usize
+}
+
+fn spawn(entry: fn(usize) -> !, arg: usize) -> usize {
+    let tid = thread_create(entry as usize, arg);
+    assert!(tid > 0);
+    tid as usize
+}
+
+fn flags_test() {
+    assert_eq!(
+        kernel_mutex_create_with_flags(MUTEX_RECURSIVE | MUTEX_ERRORCHECK),
+        -EINVAL
+    );
+    assert_eq!(kernel_mutex_create_with_flags(MUTEX_PRIO_INHERIT), -EINVAL);
+    assert_eq!(kernel_mutex_create_with_flags(1 << 10), -EINVAL);
+}
+
+fn unlock_other(_: usize) -> ! {
+    assert_eq!(kernel_mutex_unlock(MUTEX.load(Ordering::SeqCst)), -EPERM);
+    exit(0)
+}
+
+fn lock_other(_: usize) -> ! {
+    let mutex = MUTEX.load(Ordering::SeqCst);
+    assert_eq!(kernel_mutex_lock(mutex), 0);
+    LOCKED.store(true, Ordering::SeqCst);
+    assert_eq!(kernel_mutex_unlock(mutex), 0);
+    exit(0)
+}
+
+/// The owner locks a recursive mutex again, and others get it after as many unlocks.
+fn recursive_test(base: usize) {
+    let mutex = create(base | MUTEX_RECURSIVE);
+    MUTEX.store(mutex, Ordering::SeqCst);
+    LOCKED.store(false, Ordering::SeqCst);
+    for _ in 0..3 {
+        assert_eq!(kernel_mutex_lock(mutex), 0);
+    }
+    assert_eq!(waittid(spawn(unlock_other, 0)), 0);
+    let tid = spawn(lock_other, 0);
+    for _ in 0..3 {
+        sleep(20);
+        assert!(!LOCKED.load(Ordering::SeqCst));
+        assert_eq!(kernel_mutex_unlock(mutex), 0);
+    }
+    assert_eq!(waittid(tid), 0);
+    assert!(LOCKED.load(Ordering::SeqCst));
+    assert_eq!(kernel_mutex_unlock(mutex), -EPERM);
+}
+
+fn errorcheck_test(base: usize) {
+    let mutex = create(base | MUTEX_ERRORCHECK);
+    MUTEX.store(mutex, Ordering::SeqCst);
+    assert_eq!(kernel_mutex_unlock(mutex), -EPERM);
+    assert_eq!(kernel_mutex_lock(mutex), 0);
+    assert_eq!(kernel_mutex_lock(mutex), -EDEADLK);
+    assert_eq!(waittid(spawn(unlock_other, 0)), 0);
+    assert_eq!(kernel_mutex_unlock(mutex), 0);
+    assert_eq!(kernel_mutex_unlock(mutex), -EPERM);
+}
+
+fn type_test() {
+    for base in [0, MUTEX_BLOCKING, MUTEX_BLOCKING | MUTEX_PRIO_INHERIT] {
+        recursive_test(base);
+        errorcheck_test(base);
+    }
+    // A normal mutex is not checked, except that it must be locked.
+    let mutex = create(MUTEX_BLOCKING);
+    assert_eq!(kernel_mutex_unlock(mutex), -EPERM);
+    let condvar = kernel_condvar_create();
+    assert_eq!(kernel_condvar_wait(condvar, mutex), -EPERM);
+}
+
+/// Locking a recursive mutex again is not a deadlock.
+fn deadlock_detect_test() {
+    assert_eq!(enable_deadlock_detect(true), 0);
+    let mutex = create(MUTEX_BLOCKING | MUTEX_RECURSIVE);
+    MUTEX.store(mutex, Ordering::SeqCst);
+    LOCKED.store(false, Ordering::SeqCst);
+    assert_eq!(kernel_mutex_lock(mutex), 0);
+    assert_eq!(kernel_mutex_lock(mutex), 0);
+    let tid = spawn(lock_other, 0);
+    sleep(20);
+    assert_eq!(kernel_mutex_unlock(mutex), 0);
+    assert_eq!(kernel_mutex_unlock(mutex), 0);
+    assert_eq!(waittid(tid), 0);
+    assert!(LOCKED.load(Ordering::SeqCst));
+    let normal = create(MUTEX_BLOCKING);
+    assert_eq!(kernel_mutex_lock(normal), 0);
+    assert_eq!(kernel_mutex_lock(normal), -EDEADLK);
+    assert_eq!(kernel_mutex_unlock(normal), 0);
+    assert_eq!(enable_deadlock_detect(false), 0);
+}
+
+fn set_nice(nice: isize) {
+    assert_eq!(setpriority(PRIO_THREAD, get_tid() as usize, nice), 0);
+}
+
+fn thread_nice(tid: usize) -> isize {
+    getpriority(PRIO_THREAD, tid).unwrap()
+}
+
+/// Take the mutex at the priority, and record the order it is taken.
+fn lock_at(nice: usize) -> ! {
+    set_nice(nice as isize);
+    let mutex = MUTEX.load(Ordering::SeqCst);
+    assert_eq!(kernel_mutex_lock(mutex), 0);
+    let index = ORDER_LEN.fetch_add(1, Ordering::SeqCst);
+    ORDER[index].store(nice, Ordering::SeqCst);
+    assert_eq!(kernel_mutex_unlock(mutex), 0);
+    exit(0)
+}
+
+/// Threads wait on a mutex held by the main thread at LOW priority, and get it in turn.
+/// # Return
+/// * The priorities of the threads in the order they get the mutex.
+fn wait_in_turn(flags: usize, nices: &[isize]) -> Vec<isize> {
+    let mutex = create(flags);
+    MUTEX.store(mutex, Ordering::SeqCst);
+    ORDER_LEN.store(0, Ordering::SeqCst);
+    set_nice(LOW);
+    assert_eq!(kernel_mutex_lock(mutex), 0);
+    let mut tids = Vec::new();
+    let mut highest = LOW;
+    for &nice in nices {
+        tids.push(spawn(lock_at, nice as usize));
+        // Let it block on the mutex.
+        sleep(50);
+        if flags & MUTEX_PRIO_INHERIT != 0 {
+            highest = highest.min(nice);
+        }
+        assert_eq!(thread_nice(0), highest);
+    }
+    assert_eq!(kernel_mutex_unlock(mutex), 0);
+    assert_eq!(thread_nice(0), LOW);
+    for tid in tids {
+        assert_eq!(waittid(tid), 0);
+    }
+    set_nice(0);
+    (0..ORDER_LEN.load(Ordering::SeqCst))
+        .map(|index| ORDER[index].load(Ordering::SeqCst) as isize)
+        .collect()
+}
+
+/// The owner of a priority inheritance mutex runs at the priority of the highest waiter,
+/// and the highest waiter gets the mutex first.
+fn inherit_test() {
+    let nices = [LOW + 5, 0, HIGH];
+    // A blocking mutex is handed in the order of waiting, without changing priority.
+    assert_eq!(wait_in_turn(MUTEX_BLOCKING, &nices), [LOW + 5, 0, HIGH]);
+    let flags = MUTEX_BLOCKING | MUTEX_PRIO_INHERIT;
+    assert_eq!(wait_in_turn(flags, &nices), [HIGH, 0, LOW + 5]);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    flags_test();
+    type_test();
+    deadlock_detect_test();
+    inherit_test();
+    println!("pi_mutex_test passed!");
+    0
+}
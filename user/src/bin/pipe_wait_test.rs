@@ -0,0 +1,129 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, exit, fork, kill, pipe, pipe2, read, sigaction, sleep, waitpid, write, OpenFlags,
+    SignalAction, EAGAIN, EPIPE, SIGINT, SIGPIPE, SIG_DFL, SIG_IGN,
+};
+
+/// The max number of bytes buffered in a pipe.
+const PIPE_CAPACITY: usize = 16 * 4096;
+/// Bytes sent through the pipe by the writer child, much more than the capacity.
+const TOTAL: usize = 4 * PIPE_CAPACITY + 123;
+
+fn set_sigpipe(handler: usize) {
+    let mut action = SignalAction::default();
+    action.handler = handler;
+    sigaction(SIGPIPE, Some(&action), None);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // A non-blocking pipe never waits.
+    let mut fds = [0usize; 2];
+    assert_eq!(pipe2(&mut fds, OpenFlags::NONBLOCK), 0);
+    let mut buf = [0u8; 4096];
+    assert_eq!(read(fds[0], &mut buf), -EAGAIN);
+    let mut filled = 0;
+    loop {
+        match write(fds[1], &buf) {
+            len if len > 0 => filled += len as usize,
+            err => {
+                assert_eq!(err, -EAGAIN);
+                break;
+            }
+        }
+    }
+    assert_eq!(filled, PIPE_CAPACITY);
+    assert_eq!(write(fds[1], b"x"), -EAGAIN);
+    assert_eq!(read(fds[0], &mut buf), 4096);
+    assert_eq!(write(fds[1], b"x"), 1);
+    // All buffered data can be read after the write end is closed, then EOF.
+    close(fds[1]);
+    let mut drained = 0;
+    loop {
+        match read(fds[0], &mut buf) {
+            0 => break,
+            len => {
+                assert!(len > 0);
+                drained += len as usize;
+            }
+        }
+    }
+    assert_eq!(drained, PIPE_CAPACITY - 4096 + 1);
+    close(fds[0]);
+
+    // Writing to a pipe without readers fails with EPIPE when SIGPIPE is ignored.
+    assert_eq!(pipe(&mut fds), 0);
+    close(fds[0]);
+    set_sigpipe(SIG_IGN);
+    assert_eq!(write(fds[1], b"lost"), -EPIPE);
+    set_sigpipe(SIG_DFL);
+    // Or the writer is killed by SIGPIPE.
+    let pid = fork();
+    if pid == 0 {
+        write(fds[1], b"lost");
+        exit(0);
+    }
+    close(fds[1]);
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid, &mut exit_code, 0), pid);
+    assert_eq!(exit_code, -13);
+
+    // The writer blocks while the pipe is full, and the reader blocks while it is empty.
+    assert_eq!(pipe(&mut fds), 0);
+    let pid = fork();
+    if pid == 0 {
+        close(fds[0]);
+        let data: [u8; 1000] = core::array::from_fn(|i| i as u8);
+        let mut sent = 0;
+        while sent < TOTAL {
+            let len = data.len().min(TOTAL - sent);
+            assert_eq!(write(fds[1], &data[..len]), len as isize);
+            sent += len;
+        }
+        // The reader waits for EOF until the write end is closed.
+        sleep(20);
+        close(fds[1]);
+        exit(0);
+    }
+    close(fds[1]);
+    // Let the writer fill the pipe first.
+    sleep(20);
+    let mut received = 0;
+    loop {
+        let len = read(fds[0], &mut buf);
+        assert!(len >= 0);
+        if len == 0 {
+            break;
+        }
+        for byte in &buf[..len as usize] {
+            assert_eq!(*byte, (received % 1000) as u8);
+            received += 1;
+        }
+    }
+    assert_eq!(received, TOTAL);
+    close(fds[0]);
+    assert_eq!(waitpid(pid, &mut exit_code, 0), pid);
+    assert_eq!(exit_code, 0);
+
+    // A reader waiting on an empty pipe is woken up by a signal, which kills it.
+    assert_eq!(pipe(&mut fds), 0);
+    let pid = fork();
+    if pid == 0 {
+        close(fds[1]);
+        read(fds[0], &mut buf);
+        exit(0);
+    }
+    close(fds[0]);
+    sleep(20);
+    assert_eq!(kill(pid, SIGINT), 0);
+    assert_eq!(waitpid(pid, &mut exit_code, 0), pid);
+    assert_eq!(exit_code, -2);
+    close(fds[1]);
+    println!("pipe_wait_test passed!");
+    0
+}
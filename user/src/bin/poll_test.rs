@@ -0,0 +1,107 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, exit, fork, get_time, pipe, poll, pselect, read, sleep, waitpid, write, FdSet,
+    PollEvents, PollFd, TimeSpec, EBADF,
+};
+
+/// Write to the pipe after a while in a child process.
+fn write_later(fd: usize, data: &[u8]) -> isize {
+    let pid = fork();
+    if pid == 0 {
+        sleep(30);
+        write(fd, data);
+        exit(0);
+    }
+    pid
+}
+
+fn wait_child(pid: isize) {
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid, &mut exit_code, 0), pid);
+    assert_eq!(exit_code, 0);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut first = [0usize; 2];
+    let mut second = [0usize; 2];
+    assert_eq!(pipe(&mut first), 0);
+    assert_eq!(pipe(&mut second), 0);
+    let mut buf = [0u8; 16];
+
+    // Only the pipe written by the child is ready.
+    let pid = write_later(second[1], b"second");
+    let mut fds = [
+        PollFd::new(first[0] as i32, PollEvents::POLLIN),
+        PollFd::new(second[0] as i32, PollEvents::POLLIN),
+        PollFd::new(-1, PollEvents::POLLIN),
+    ];
+    assert_eq!(poll(&mut fds, -1), 1);
+    assert!(fds[0].revents().is_empty());
+    assert_eq!(fds[1].revents(), PollEvents::POLLIN);
+    assert!(fds[2].revents().is_empty());
+    assert_eq!(read(second[0], &mut buf), 6);
+    wait_child(pid);
+
+    // Nothing is ready until the timeout.
+    let start = get_time();
+    assert_eq!(poll(&mut fds[..2], 30), 0);
+    assert!(get_time() - start >= 30);
+    assert_eq!(poll(&mut fds[..2], 0), 0);
+
+    // An empty pipe is writable, a bad fd is reported.
+    let mut fds = [
+        PollFd::new(first[1] as i32, PollEvents::POLLOUT),
+        PollFd::new(99, PollEvents::POLLIN),
+    ];
+    assert_eq!(poll(&mut fds, -1), 2);
+    assert_eq!(fds[0].revents(), PollEvents::POLLOUT);
+    assert_eq!(fds[1].revents(), PollEvents::POLLNVAL);
+
+    // Closing all write ends hangs up the read end.
+    close(first[1]);
+    let mut fds = [PollFd::new(first[0] as i32, PollEvents::POLLIN)];
+    assert_eq!(poll(&mut fds, -1), 1);
+    assert_eq!(fds[0].revents(), PollEvents::POLLHUP);
+    close(first[0]);
+
+    // pselect leaves only the ready fds in the sets.
+    let pid = write_later(second[1], b"again");
+    let mut read_fds = FdSet::new();
+    let mut write_fds = FdSet::new();
+    read_fds.set(second[0]);
+    write_fds.set(second[1]);
+    let nfds = second[0].max(second[1]) + 1;
+    assert_eq!(
+        pselect(nfds, Some(&mut read_fds), Some(&mut write_fds), None, None),
+        1
+    );
+    assert!(!read_fds.is_set(second[0]));
+    assert!(write_fds.is_set(second[1]));
+    let mut read_fds = FdSet::new();
+    read_fds.set(second[0]);
+    assert_eq!(pselect(nfds, Some(&mut read_fds), None, None, None), 1);
+    assert!(read_fds.is_set(second[0]));
+    assert_eq!(read(second[0], &mut buf), 5);
+    wait_child(pid);
+    let mut read_fds = FdSet::new();
+    read_fds.set(second[0]);
+    let timeout = TimeSpec::from_ms(10);
+    assert_eq!(
+        pselect(nfds, Some(&mut read_fds), None, None, Some(&timeout)),
+        0
+    );
+    assert!(!read_fds.is_set(second[0]));
+    let mut read_fds = FdSet::new();
+    read_fds.set(99);
+    assert_eq!(pselect(100, Some(&mut read_fds), None, None, None), -EBADF);
+    close(second[0]);
+    close(second[1]);
+    println!("poll_test passed!");
+    0
+}
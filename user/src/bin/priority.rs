@@ -0,0 +1,90 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    exit, fork, get_pid, get_time, getpriority, nice, setpriority, wait_pid, EPERM, NICE_MAX,
+    NICE_MIN, PRIO_PROCESS,
+};
+
+/// How long the children compete for the cpu.
+const RUN_MS: isize = 1000;
+/// Time for all children to be forked before they start counting.
+const START_MS: isize = 100;
+/// Children of every priority, more than the harts.
+const CHILDREN: usize = 8;
+
+/// Count how many times the loop runs from start to the deadline.
+fn count_until(start: isize, deadline: isize) -> i32 {
+    while get_time() < start {
+        core::hint::spin_loop();
+    }
+    let mut count = 0;
+    while get_time() < deadline {
+        for _ in 0..1000 {
+            core::hint::spin_loop();
+        }
+        count += 1;
+    }
+    count
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // Priority of the current process.
+    assert_eq!(getpriority(PRIO_PROCESS, 0), Some(0));
+    assert_eq!(nice(5), Some(5));
+    assert_eq!(nice(-5), Some(0));
+    assert_eq!(setpriority(PRIO_PROCESS, 0, 100), 0);
+    assert_eq!(getpriority(PRIO_PROCESS, 0), Some(NICE_MAX));
+    assert_eq!(setpriority(PRIO_PROCESS, 0, -100), 0);
+    assert_eq!(getpriority(PRIO_PROCESS, 0), Some(NICE_MIN));
+    assert_eq!(setpriority(PRIO_PROCESS, 0, 0), 0);
+    assert_eq!(setpriority(PRIO_PROCESS, 99999, 0), -1);
+
+    // A child may not set the priority of its parent, the parent may set the child's.
+    let parent = get_pid() as usize;
+    let pid = fork();
+    if pid == 0 {
+        assert_eq!(setpriority(PRIO_PROCESS, parent, 5), -EPERM);
+        exit(0);
+    }
+    assert_eq!(setpriority(PRIO_PROCESS, pid as usize, 5), 0);
+    let mut exit_code = 0;
+    assert_eq!(wait_pid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    assert_eq!(getpriority(PRIO_PROCESS, 0), Some(0));
+
+    // Nice 0 children and nice 10 children run at the same time,
+    // there are more children than harts so they compete for the cpu,
+    // the nice 0 ones should get more cpu time, about 9 times by the weights.
+    // Only the stride scheduler weights them, the fifo scheduler (make SCHED=fifo) fails it.
+    let start = get_time() + START_MS;
+    let deadline = start + RUN_MS;
+    let mut pids = [[0usize; CHILDREN]; 2];
+    for (i, child_nice) in [0isize, 10].iter().enumerate() {
+        for j in 0..CHILDREN {
+            let pid = fork();
+            if pid == 0 {
+                assert_eq!(setpriority(PRIO_PROCESS, 0, *child_nice), 0);
+                exit(count_until(start, deadline));
+            }
+            pids[i][j] = pid as usize;
+        }
+    }
+    let mut counts = [0i32; 2];
+    for i in 0..2 {
+        for pid in pids[i] {
+            let mut count = 0;
+            assert_eq!(wait_pid(pid, &mut count), pid as isize);
+            counts[i] += count;
+        }
+    }
+    println!("nice 0 count: {}, nice 10 count: {}", counts[0], counts[1]);
+    // A wide margin, so it does not fail on a busy machine.
+    assert!(counts[0] > counts[1] * 2);
+    println!("priority passed!");
+    0
+}
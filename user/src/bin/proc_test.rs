@@ -0,0 +1,101 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::{format, string::String};
+use user_lib::{
+    close, fork, get_pid, getdents, kill, open, pipe, read_dir, read_to_string, waitpid,
+    wifstopped, yield_, OpenFlags, SIGKILL, SIGSTOP, WUNTRACED,
+};
+
+/// Get the value of the field in /proc/<pid>/status.
+fn status_field(status: &str, field: &str) -> Option<String> {
+    status
+        .lines()
+        .find(|line| line.starts_with(field) && line[field.len()..].starts_with(':'))
+        .map(|line| String::from(line[field.len() + 1..].trim()))
+}
+
+/// Get the number of kB of the field in /proc/meminfo.
+fn meminfo_kb(meminfo: &str, field: &str) -> usize {
+    status_field(meminfo, field)
+        .unwrap()
+        .trim_end_matches(" kB")
+        .parse()
+        .unwrap()
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let pid = get_pid() as usize;
+    let entries = read_dir("/proc\0").unwrap();
+    assert!(entries.iter().any(|name| name == "meminfo"));
+    assert!(entries.iter().any(|name| name == "uptime"));
+    assert!(entries.iter().any(|name| *name == format!("{}", pid)));
+
+    // The process itself.
+    let status = read_to_string("/proc/self/status\0").unwrap();
+    assert_eq!(status_field(&status, "Name").unwrap(), "proc_test");
+    assert_eq!(status_field(&status, "Pid").unwrap(), format!("{}", pid));
+    assert!(status_field(&status, "State").unwrap().starts_with('R'));
+    assert_eq!(status_field(&status, "Threads").unwrap(), "1");
+    let maps = read_to_string(&format!("/proc/{}/maps\0", pid)).unwrap();
+    assert!(maps.lines().any(|line| line.contains(" r-xu ")));
+
+    // Open files.
+    let mut pipe_fd = [0usize; 2];
+    assert_eq!(pipe(&mut pipe_fd), 0);
+    let fds = read_dir("/proc/self/fd\0").unwrap();
+    for fd in [0, 1, 2, pipe_fd[0], pipe_fd[1]] {
+        assert!(fds.iter().any(|name| *name == format!("{}", fd)));
+    }
+    let kind = read_to_string(&format!("/proc/self/fd/{}\0", pipe_fd[0])).unwrap();
+    assert_eq!(kind.trim(), "pipe:[read]");
+    close(pipe_fd[0]);
+    close(pipe_fd[1]);
+    assert!(
+        open(
+            &format!("/proc/self/fd/{}\0", pipe_fd[0]),
+            OpenFlags::READ_ONLY
+        ) < 0
+    );
+
+    // A stopped child.
+    let child = fork();
+    if child == 0 {
+        loop {
+            yield_();
+        }
+    }
+    let mut exit_code = 0;
+    assert_eq!(kill(child, SIGSTOP), 0);
+    assert_eq!(waitpid(child, &mut exit_code, WUNTRACED), child);
+    assert!(wifstopped(exit_code));
+    let status = read_to_string(&format!("/proc/{}/status\0", child)).unwrap();
+    assert_eq!(status_field(&status, "PPid").unwrap(), format!("{}", pid));
+    assert!(status_field(&status, "State").unwrap().starts_with('T'));
+    assert_eq!(kill(child, SIGKILL), 0);
+    assert_eq!(waitpid(child, &mut exit_code, 0), child);
+    assert!(open(&format!("/proc/{}/status\0", child), OpenFlags::READ_ONLY) < 0);
+
+    // System wide statistics.
+    let meminfo = read_to_string("/proc/meminfo\0").unwrap();
+    let total = meminfo_kb(&meminfo, "MemTotal");
+    let free = meminfo_kb(&meminfo, "MemFree");
+    assert!(total > 0 && free > 0 && free < total);
+    let uptime = read_to_string("/proc/uptime\0").unwrap();
+    let seconds: f64 = uptime.trim().parse().unwrap();
+    assert!(seconds > 0.0);
+
+    // Only directories have entries.
+    let fd = open("/proc/uptime\0", OpenFlags::READ_ONLY);
+    assert!(fd >= 0);
+    let mut buf = [0u8; 64];
+    assert_eq!(getdents(fd as usize, &mut buf), -1);
+    close(fd as usize);
+    println!("proc_test passed!");
+    0
+}
@@ -0,0 +1,62 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::{format, string::String, vec::Vec};
+use user_lib::{read_dir, read_to_string};
+
+/// Get the value of the field in /proc/<pid>/status.
+fn field<'a>(status: &'a str, name: &str) -> &'a str {
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix(name)?.strip_prefix(':'))
+        .map_or("?", |value| value.trim())
+}
+
+/// Show all processes by reading /proc.
+#[no_mangle]
+pub fn main() -> i32 {
+    let Some(entries) = read_dir("/proc\0") else {
+        println!("ps: can not read /proc");
+        return -1;
+    };
+    let mut pids: Vec<usize> = entries
+        .iter()
+        .filter_map(|name| name.parse().ok())
+        .collect();
+    pids.sort();
+    println!(
+        "{:>5} {:>5} {:>5} {:>5} {:>4} {:>8} {:>8}  NAME",
+        "PID", "PPID", "PGID", "STATE", "THR", "RSS(kB)", "TIME(ms)"
+    );
+    for pid in pids {
+        // The process may exit after /proc is read.
+        let Some(status) = read_to_string(&format!("/proc/{}/status\0", pid)) else {
+            continue;
+        };
+        let time = field(&status, "Utime")
+            .trim_end_matches(" ms")
+            .parse::<usize>()
+            .unwrap_or(0)
+            + field(&status, "Stime")
+                .trim_end_matches(" ms")
+                .parse::<usize>()
+                .unwrap_or(0);
+        let state: String = field(&status, "State").chars().take(1).collect();
+        println!(
+            "{:>5} {:>5} {:>5} {:>5} {:>4} {:>8} {:>8}  {}",
+            pid,
+            field(&status, "PPid"),
+            field(&status, "Pgid"),
+            state,
+            field(&status, "Threads"),
+            field(&status, "VmRSS").trim_end_matches(" kB"),
+            time,
+            field(&status, "Name"),
+        );
+    }
+    0
+}
@@ -0,0 +1,268 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+use core::ptr::addr_of_mut;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use user_lib::{
+    exit, rwlock_create, rwlock_read_lock, rwlock_unlock, rwlock_upgrade, rwlock_write_lock, sleep,
+    thread_create, waittid, yield_, EDEADLK, EINVAL, EPERM,
+};
+
+const READERS: usize = 4;
+const LOOKUPS: usize = 200;
+const INVALIDATIONS: usize = 5;
+const KEYS: usize = 8;
+
+static RWLOCK: AtomicUsize = AtomicUsize::new(0);
+/// A cache of value(key), 0 is not cached.
+static mut CACHE: [usize; KEYS] = [0; KEYS];
+/// The threads reading the cache, and whether a thread writes it.
+static READING: AtomicUsize = AtomicUsize::new(0);
+static WRITING: AtomicBool = AtomicBool::new(false);
+static HITS: AtomicUsize = AtomicUsize::new(0);
+static MISSES: AtomicUsize = AtomicUsize::new(0);
+static HELD: AtomicBool = AtomicBool::new(false);
+static ORDER: [AtomicUsize; 2] = [AtomicUsize::new(0), AtomicUsize::new(0)];
+static ORDER_LEN: AtomicUsize = AtomicUsize::new(0);
+
+/// The value is expensive to compute, so it is cached.
+fn value(key: usize) -> usize {
+    key * key + 1
+}
+
+fn spawn(entry: fn(usize) -> !, arg: usize) -> usize {
+    let tid = thread_create(entry as usize, arg);
+    assert!(tid > 0);
+    tid as usize
+}
+
+fn rwlock() -> usize {
+    RWLOCK.load(Ordering::SeqCst)
+}
+
+fn new_rwlock() -> usize {
+    let rwlock = rwlock_create();
+    RWLOCK.store(rwlock, Ordering::SeqCst);
+    rwlock
+}
+
+fn cached(key: usize) -> usize {
+    unsafe { addr_of_mut!(CACHE[key]).read_volatile() }
+}
+
+fn set_cached(key: usize, value: usize) {
+    unsafe { addr_of_mut!(CACHE[key]).write_volatile(value) }
+}
+
+fn start_reading() {
+    assert!(!WRITING.load(Ordering::SeqCst));
+    READING.fetch_add(1, Ordering::SeqCst);
+}
+
+fn start_writing() {
+    assert_eq!(READING.load(Ordering::SeqCst), 0);
+    assert!(!WRITING.swap(true, Ordering::SeqCst));
+}
+
+fn stop_writing() {
+    WRITING.store(false, Ordering::SeqCst);
+}
+
+/// Fill the missing key, with the write lock held.
+fn fill(key: usize) {
+    start_writing();
+    // Another thread may have filled it while this one waited.
+    match cached(key) {
+        0 => {
+            MISSES.fetch_add(1, Ordering::SeqCst);
+            yield_();
+            set_cached(key, value(key));
+        }
+        _ => {
+            HITS.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+    stop_writing();
+}
+
+/// Read the cache, and upgrade to fill it on a miss.
+/// Only one reader can wait to upgrade, the others lock it for writing.
+fn lookup(key: usize) -> usize {
+    let rwlock = rwlock();
+    assert_eq!(rwlock_read_lock(rwlock), 0);
+    start_reading();
+    let value = cached(key);
+    if value != 0 {
+        HITS.fetch_add(1, Ordering::SeqCst);
+        READING.fetch_sub(1, Ordering::SeqCst);
+        assert_eq!(rwlock_unlock(rwlock), 0);
+        return value;
+    }
+    READING.fetch_sub(1, Ordering::SeqCst);
+    match rwlock_upgrade(rwlock) {
+        0 => {}
+        errno => {
+            assert_eq!(errno, -EDEADLK);
+            assert_eq!(rwlock_unlock(rwlock), 0);
+            assert_eq!(rwlock_write_lock(rwlock), 0);
+        }
+    }
+    fill(key);
+    let value = cached(key);
+    assert_eq!(rwlock_unlock(rwlock), 0);
+    value
+}
+
+fn reader(id: usize) -> ! {
+    for i in 0..LOOKUPS {
+        let key = (id + i) % KEYS;
+        assert_eq!(lookup(key), value(key));
+        if i % 16 == 0 {
+            yield_();
+        }
+    }
+    exit(0)
+}
+
+fn invalidator(_: usize) -> ! {
+    let rwlock = rwlock();
+    for _ in 0..INVALIDATIONS {
+        sleep(10);
+        assert_eq!(rwlock_write_lock(rwlock), 0);
+        start_writing();
+        for key in 0..KEYS {
+            set_cached(key, 0);
+            yield_();
+        }
+        stop_writing();
+        assert_eq!(rwlock_unlock(rwlock), 0);
+    }
+    exit(0)
+}
+
+/// Readers share the cache while a writer invalidates it now and then.
+fn cache_test() {
+    new_rwlock();
+    let mut tids: Vec<usize> = (0..READERS).map(|id| spawn(reader, id)).collect();
+    tids.push(spawn(invalidator, 0));
+    for tid in tids {
+        assert_eq!(waittid(tid), 0);
+    }
+    let (hits, misses) = (HITS.load(Ordering::SeqCst), MISSES.load(Ordering::SeqCst));
+    println!("cache hits {}, misses {}", hits, misses);
+    assert_eq!(hits + misses, READERS * LOOKUPS);
+    assert!(hits > misses);
+}
+
+fn errors_test() {
+    let rwlock = new_rwlock();
+    assert_eq!(rwlock_unlock(rwlock), -EPERM);
+    assert_eq!(rwlock_upgrade(rwlock), -EPERM);
+    // Readers share it, the same thread may read it twice while no writer waits.
+    assert_eq!(rwlock_read_lock(rwlock), 0);
+    assert_eq!(rwlock_read_lock(rwlock), 0);
+    assert_eq!(rwlock_unlock(rwlock), 0);
+    assert_eq!(rwlock_unlock(rwlock), 0);
+    assert_eq!(rwlock_write_lock(rwlock), 0);
+    assert_eq!(rwlock_upgrade(rwlock), -EPERM);
+    assert_eq!(rwlock_unlock(rwlock), 0);
+    assert_eq!(rwlock_unlock(rwlock), -EPERM);
+    assert_eq!(rwlock_read_lock(rwlock + 1), -EINVAL);
+    assert_eq!(rwlock_unlock(rwlock + 1), -EINVAL);
+    // Only the holder can unlock it.
+    assert_eq!(rwlock_write_lock(rwlock), 0);
+    assert_eq!(waittid(spawn(unlock_not_held, 0)), 0);
+    assert_eq!(rwlock_unlock(rwlock), 0);
+    assert_eq!(rwlock_read_lock(rwlock), 0);
+    assert_eq!(waittid(spawn(unlock_not_held, 0)), 0);
+    assert_eq!(rwlock_unlock(rwlock), 0);
+}
+
+/// Unlock and upgrade the lock held by the main thread.
+fn unlock_not_held(_: usize) -> ! {
+    let rwlock = rwlock();
+    assert_eq!(rwlock_upgrade(rwlock), -EPERM);
+    assert_eq!(rwlock_unlock(rwlock), -EPERM);
+    exit(0)
+}
+
+const WRITER: usize = 1;
+const READER: usize = 2;
+
+/// Take the lock, and record the order it is taken.
+fn take_in_turn(kind: usize) -> ! {
+    let rwlock = rwlock();
+    match kind {
+        WRITER => assert_eq!(rwlock_write_lock(rwlock), 0),
+        _ => assert_eq!(rwlock_read_lock(rwlock), 0),
+    }
+    let index = ORDER_LEN.fetch_add(1, Ordering::SeqCst);
+    ORDER[index].store(kind, Ordering::SeqCst);
+    sleep(20);
+    assert_eq!(rwlock_unlock(rwlock), 0);
+    exit(0)
+}
+
+/// A reader coming after a waiting writer waits behind it, though only readers hold the lock.
+fn writer_preference_test() {
+    let rwlock = new_rwlock();
+    ORDER_LEN.store(0, Ordering::SeqCst);
+    assert_eq!(rwlock_read_lock(rwlock), 0);
+    let writer = spawn(take_in_turn, WRITER);
+    // Let it wait for the lock.
+    sleep(20);
+    let reader = spawn(take_in_turn, READER);
+    sleep(20);
+    assert_eq!(ORDER_LEN.load(Ordering::SeqCst), 0);
+    assert_eq!(rwlock_unlock(rwlock), 0);
+    assert_eq!(waittid(writer), 0);
+    assert_eq!(waittid(reader), 0);
+    assert_eq!(ORDER_LEN.load(Ordering::SeqCst), 2);
+    assert_eq!(ORDER[0].load(Ordering::SeqCst), WRITER);
+    assert_eq!(ORDER[1].load(Ordering::SeqCst), READER);
+}
+
+/// Read the lock with the main thread, and try to upgrade while the main thread waits to.
+fn upgrade_later(_: usize) -> ! {
+    let rwlock = rwlock();
+    assert_eq!(rwlock_read_lock(rwlock), 0);
+    HELD.store(true, Ordering::SeqCst);
+    sleep(20);
+    assert_eq!(rwlock_upgrade(rwlock), -EDEADLK);
+    assert_eq!(rwlock_unlock(rwlock), 0);
+    exit(0)
+}
+
+/// An upgrade waits for the other readers to unlock,
+/// and a second upgrade fails instead of waiting for the first one.
+fn upgrade_test() {
+    let rwlock = new_rwlock();
+    HELD.store(false, Ordering::SeqCst);
+    assert_eq!(rwlock_read_lock(rwlock), 0);
+    let tid = spawn(upgrade_later, 0);
+    while !HELD.load(Ordering::SeqCst) {
+        sleep(1);
+    }
+    assert_eq!(rwlock_upgrade(rwlock), 0);
+    // The other reader has unlocked it.
+    assert_eq!(waittid(tid), 0);
+    assert_eq!(rwlock_unlock(rwlock), 0);
+    assert_eq!(rwlock_write_lock(rwlock), 0);
+    assert_eq!(rwlock_unlock(rwlock), 0);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    errors_test();
+    writer_preference_test();
+    upgrade_test();
+    cache_test();
+    println!("rwlock_test passed!");
+    0
+}
@@ -0,0 +1,198 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+use user_lib::{
+    exit, fork, get_pid, shmat, shmctl, shmdt, shmget, waitpid, yield_, ShmidDs, EACCES, EEXIST,
+    EINVAL, ENOENT, IPC_CREAT, IPC_EXCL, IPC_PRIVATE, IPC_RMID, IPC_SET, IPC_STAT, SHM_RDONLY,
+    SHM_RND,
+};
+
+const PAGE_SIZE: usize = 4096;
+const KEY: i32 = 0x5348;
+const RING_KEY: i32 = 0x5349;
+const RING_SLOTS: usize = 64;
+const ITEMS: usize = 5000;
+
+/// A single producer single consumer ring in a shared segment, zeroed when it is created.
+#[repr(C)]
+struct Ring {
+    /// The number of items pushed.
+    head: AtomicUsize,
+    /// The number of items popped.
+    tail: AtomicUsize,
+    slots: [AtomicUsize; RING_SLOTS],
+}
+
+fn wait_child(pid: isize) {
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid, &mut exit_code, 0), pid);
+    assert_eq!(exit_code, 0);
+}
+
+fn stat(id: usize) -> ShmidDs {
+    let mut ds = ShmidDs::default();
+    assert_eq!(shmctl(id, IPC_STAT, &mut ds), 0);
+    ds
+}
+
+fn attach_ring() -> &'static Ring {
+    let id = shmget(RING_KEY, core::mem::size_of::<Ring>(), 0o600);
+    assert!(id >= 0);
+    let addr = shmat(id as usize, 0, 0);
+    assert!(addr > 0);
+    unsafe { &*(addr as *const Ring) }
+}
+
+fn fork_test() {
+    let id = shmget(IPC_PRIVATE, 2 * PAGE_SIZE, 0o600);
+    assert!(id >= 0);
+    let id = id as usize;
+    let addr = shmat(id, 0, 0);
+    assert!(addr > 0);
+    let addr = addr as usize;
+    assert_eq!(addr % PAGE_SIZE, 0);
+    let buf = unsafe { core::slice::from_raw_parts_mut(addr as *mut u8, 2 * PAGE_SIZE) };
+    assert!(buf.iter().all(|byte| *byte == 0));
+    buf[..5].copy_from_slice(b"hello");
+    buf[2 * PAGE_SIZE - 1] = 1;
+
+    // The child inherits the attachment, and writes to the same pages.
+    let pid = fork();
+    if pid == 0 {
+        assert_eq!(&buf[..5], b"hello");
+        assert_eq!(buf[2 * PAGE_SIZE - 1], 1);
+        assert_eq!(stat(id).nattch, 2);
+        buf[..5].copy_from_slice(b"world");
+        exit(0);
+    }
+    wait_child(pid);
+    assert_eq!(&buf[..5], b"world");
+    let ds = stat(id);
+    assert_eq!(ds.nattch, 1);
+    assert_eq!(ds.segsz, 2 * PAGE_SIZE);
+    assert_eq!(ds.cpid as isize, get_pid());
+    assert_eq!(ds.perm.key, IPC_PRIVATE);
+
+    // A second attachment maps the same pages.
+    let alias = shmat(id, 0, SHM_RDONLY);
+    assert!(alias > 0);
+    let alias = alias as usize;
+    assert_ne!(alias, addr);
+    assert_eq!(unsafe { *(alias as *const [u8; 5]) }, *b"world");
+    assert_eq!(stat(id).nattch, 2);
+    assert_eq!(shmdt(alias), 0);
+    assert_eq!(shmdt(alias), -EINVAL);
+    assert_eq!(shmdt(addr + PAGE_SIZE), -EINVAL);
+
+    // Attach at a given address, a failed attachment does not change the segment.
+    let ds = stat(id);
+    assert_eq!(shmat(id, alias + 3, 0), -EINVAL);
+    assert_eq!(shmat(id, addr + PAGE_SIZE, 0), -EINVAL);
+    assert_eq!(shmat(id, PAGE_SIZE, 0), -EINVAL);
+    assert_eq!(shmat(id, usize::MAX & !(PAGE_SIZE - 1), 0), -EINVAL);
+    let after = stat(id);
+    assert_eq!(after.nattch, ds.nattch);
+    assert_eq!(after.dtime, ds.dtime);
+    assert_eq!(shmat(id, alias + 3, SHM_RND), alias as isize);
+    assert_eq!(shmdt(alias), 0);
+    assert_eq!(shmat(id + 1000, 0, 0), -EINVAL);
+
+    // A removed segment stays until the last detach.
+    assert_eq!(shmctl(id, IPC_RMID, &mut ShmidDs::default()), 0);
+    assert_eq!(&buf[..5], b"world");
+    assert_eq!(stat(id).nattch, 1);
+    assert_eq!(shmdt(addr), 0);
+    assert_eq!(shmctl(id, IPC_STAT, &mut ShmidDs::default()), -EINVAL);
+}
+
+fn key_test() {
+    let id = shmget(KEY, PAGE_SIZE, IPC_CREAT | 0o600);
+    assert!(id >= 0);
+    assert_eq!(shmget(KEY, PAGE_SIZE, 0), id);
+    assert_eq!(shmget(KEY, 100, IPC_CREAT | 0o600), id);
+    assert_eq!(shmget(KEY, 2 * PAGE_SIZE, 0), -EINVAL);
+    assert_eq!(
+        shmget(KEY, PAGE_SIZE, IPC_CREAT | IPC_EXCL | 0o600),
+        -EEXIST
+    );
+    assert_eq!(shmget(KEY + 1, PAGE_SIZE, 0), -ENOENT);
+    assert_eq!(shmget(IPC_PRIVATE, 0, 0o600), -EINVAL);
+    let id = id as usize;
+    let mut ds = stat(id);
+    assert_eq!(ds.perm.key, KEY);
+    assert_eq!(ds.perm.mode, 0o600);
+
+    // A read only segment can only be attached read only.
+    ds.perm.mode = 0o400;
+    assert_eq!(shmctl(id, IPC_SET, &mut ds), 0);
+    assert_eq!(stat(id).perm.mode, 0o400);
+    assert_eq!(shmat(id, 0, 0), -EACCES);
+    let addr = shmat(id, 0, SHM_RDONLY);
+    assert!(addr > 0);
+    assert_eq!(shmdt(addr as usize), 0);
+    assert_eq!(shmget(KEY, PAGE_SIZE, 0o600), -EACCES);
+
+    // The key is free after the segment is removed.
+    assert_eq!(shmctl(id, IPC_RMID, &mut ds), 0);
+    assert_eq!(shmget(KEY, PAGE_SIZE, 0), -ENOENT);
+}
+
+/// Processes attaching the segment by its key pass items through the ring without copying.
+fn producer_consumer_test() {
+    let id = shmget(
+        RING_KEY,
+        core::mem::size_of::<Ring>(),
+        IPC_CREAT | IPC_EXCL | 0o600,
+    );
+    assert!(id >= 0);
+    let producer = fork();
+    if producer == 0 {
+        let ring = attach_ring();
+        for item in 1..=ITEMS {
+            let head = ring.head.load(Ordering::Relaxed);
+            while head - ring.tail.load(Ordering::Acquire) == RING_SLOTS {
+                yield_();
+            }
+            ring.slots[head % RING_SLOTS].store(item, Ordering::Relaxed);
+            ring.head.store(head + 1, Ordering::Release);
+        }
+        exit(0);
+    }
+    let consumer = fork();
+    if consumer == 0 {
+        let ring = attach_ring();
+        let mut sum = 0;
+        for item in 1..=ITEMS {
+            let tail = ring.tail.load(Ordering::Relaxed);
+            while ring.head.load(Ordering::Acquire) == tail {
+                yield_();
+            }
+            let value = ring.slots[tail % RING_SLOTS].load(Ordering::Relaxed);
+            assert_eq!(value, item);
+            sum += value;
+            ring.tail.store(tail + 1, Ordering::Release);
+        }
+        assert_eq!(sum, ITEMS * (ITEMS + 1) / 2);
+        exit(0);
+    }
+    wait_child(producer);
+    wait_child(consumer);
+    let id = id as usize;
+    let ds = stat(id);
+    assert_eq!(ds.nattch, 0);
+    assert_ne!(ds.lpid as isize, get_pid());
+    assert_eq!(shmctl(id, IPC_RMID, &mut ShmidDs::default()), 0);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    fork_test();
+    key_test();
+    producer_consumer_test();
+    println!("shm_test passed!");
+    0
+}
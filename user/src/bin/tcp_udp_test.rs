@@ -0,0 +1,200 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    accept_in, bind_in, close, connect_in, exit, fork, getpeername_in, getsockname_in, listen,
+    poll, read, recv, recvfrom_in, send, sendto_in, shutdown, socket, socketpair, waitpid, write,
+    PollEvents, PollFd, SockaddrIn, AF_INET, EADDRINUSE, EADDRNOTAVAIL, EAGAIN, ECONNREFUSED,
+    EDESTADDRREQ, EINPROGRESS, EINVAL, EISCONN, EMSGSIZE, ENOTCONN, EOPNOTSUPP, INADDR_ANY,
+    INADDR_LOOPBACK, MSG_DONTWAIT, MSG_TRUNC, SHUT_WR, SOCK_DGRAM, SOCK_NONBLOCK, SOCK_STREAM,
+};
+
+const TCP_PORT: u16 = 7000;
+const UDP_PORT_A: u16 = 7100;
+const UDP_PORT_B: u16 = 7101;
+/// Larger than the socket buffers.
+const BULK_LEN: usize = 32 * 1024;
+
+fn wait_child(pid: isize) {
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid, &mut exit_code, 0), pid);
+    assert_eq!(exit_code, 0);
+}
+
+fn tcp_test() {
+    let server = socket(AF_INET, SOCK_STREAM) as usize;
+    let addr = SockaddrIn::new(INADDR_LOOPBACK, TCP_PORT);
+    assert_eq!(bind_in(server, &addr), 0);
+    let other = socket(AF_INET, SOCK_STREAM) as usize;
+    assert_eq!(bind_in(other, &addr), -EADDRINUSE);
+    assert_eq!(
+        bind_in(other, &SockaddrIn::new([10, 9, 9, 9], 0)),
+        -EADDRNOTAVAIL
+    );
+    close(other);
+    assert_eq!(listen(server, 4), 0);
+
+    let pid = fork();
+    if pid == 0 {
+        let fd = socket(AF_INET, SOCK_STREAM) as usize;
+        assert_eq!(connect_in(fd, &addr), 0);
+        let mut peer = SockaddrIn::default();
+        assert_eq!(getpeername_in(fd, &mut peer), 0);
+        assert_eq!((peer.addr, peer.port()), (INADDR_LOOPBACK, TCP_PORT));
+        assert_eq!(send(fd, b"hello ", 0), 6);
+        assert_eq!(write(fd, b"server"), 6);
+        let mut buf = [0u8; 16];
+        assert_eq!(recv(fd, &mut buf, 0), 5);
+        assert_eq!(&buf[..5], b"reply");
+        // Bulk data is echoed back chunk by chunk.
+        let data = [b'x'; 1024];
+        let mut buf = [0u8; 1024];
+        for _ in 0..BULK_LEN / data.len() {
+            assert_eq!(send(fd, &data, 0), data.len() as isize);
+            let mut len = 0;
+            while len < data.len() {
+                let ret = read(fd, &mut buf[len..]);
+                assert!(ret > 0);
+                len += ret as usize;
+            }
+            assert_eq!(buf, data);
+        }
+        // The server gets end of file, and closes its socket.
+        assert_eq!(shutdown(fd, SHUT_WR), 0);
+        assert_eq!(recv(fd, &mut buf, 0), 0);
+        close(fd);
+        exit(0);
+    }
+    let mut client = SockaddrIn::default();
+    let conn = accept_in(server, Some(&mut client));
+    assert!(conn >= 0);
+    let conn = conn as usize;
+    assert_eq!(client.addr, INADDR_LOOPBACK);
+    assert_ne!(client.port(), TCP_PORT);
+    let mut local = SockaddrIn::default();
+    assert_eq!(getsockname_in(conn, &mut local), 0);
+    assert_eq!((local.addr, local.port()), (INADDR_LOOPBACK, TCP_PORT));
+    // The stream has no message boundaries.
+    let mut buf = [0u8; 1024];
+    let mut len = 0;
+    while len < 12 {
+        let ret = read(conn, &mut buf[len..32]);
+        assert!(ret > 0);
+        len += ret as usize;
+    }
+    assert_eq!(&buf[..12], b"hello server");
+    assert_eq!(send(conn, b"reply", 0), 5);
+    loop {
+        let ret = recv(conn, &mut buf, 0);
+        assert!(ret >= 0);
+        if ret == 0 {
+            break;
+        }
+        assert_eq!(send(conn, &buf[..ret as usize], 0), ret);
+    }
+    close(conn);
+    wait_child(pid);
+
+    // Non-blocking accept and connect.
+    let listener = socket(AF_INET, SOCK_STREAM | SOCK_NONBLOCK) as usize;
+    assert_eq!(listen(listener, 1), 0);
+    let mut addr = SockaddrIn::default();
+    assert_eq!(getsockname_in(listener, &mut addr), 0);
+    assert_ne!(addr.port(), 0);
+    assert_eq!(accept_in(listener, None), -EAGAIN);
+    let fd = socket(AF_INET, SOCK_STREAM | SOCK_NONBLOCK) as usize;
+    let addr = SockaddrIn::new(INADDR_LOOPBACK, addr.port());
+    assert_eq!(connect_in(fd, &addr), -EINPROGRESS);
+    let mut fds = [PollFd::new(fd as i32, PollEvents::POLLOUT)];
+    assert_eq!(poll(&mut fds, 1000), 1);
+    assert!(fds[0].revents().contains(PollEvents::POLLOUT));
+    assert_eq!(connect_in(fd, &addr), -EISCONN);
+    let mut fds = [PollFd::new(listener as i32, PollEvents::POLLIN)];
+    assert_eq!(poll(&mut fds, 1000), 1);
+    let conn = accept_in(listener, None);
+    assert!(conn >= 0);
+    let conn = conn as usize;
+    assert_eq!(recv(conn, &mut buf, MSG_DONTWAIT), -EAGAIN);
+    close(fd);
+    close(conn);
+    close(listener);
+
+    // A closed socket refuses connections, and its port can be bound again.
+    close(server);
+    let fd = socket(AF_INET, SOCK_STREAM) as usize;
+    let addr = SockaddrIn::new(INADDR_LOOPBACK, TCP_PORT);
+    assert_eq!(connect_in(fd, &addr), -ECONNREFUSED);
+    assert_eq!(recv(fd, &mut buf, 0), -ENOTCONN);
+    assert_eq!(send(fd, b"x", 0), -ENOTCONN);
+    close(fd);
+    let fd = socket(AF_INET, SOCK_STREAM) as usize;
+    assert_eq!(bind_in(fd, &addr), 0);
+    close(fd);
+
+    let mut sv = [0i32; 2];
+    assert_eq!(socketpair(AF_INET, SOCK_STREAM, &mut sv), -EOPNOTSUPP);
+}
+
+fn udp_test() {
+    let a = socket(AF_INET, SOCK_DGRAM) as usize;
+    let b = socket(AF_INET, SOCK_DGRAM | SOCK_NONBLOCK) as usize;
+    let addr_a = SockaddrIn::new(INADDR_LOOPBACK, UDP_PORT_A);
+    let addr_b = SockaddrIn::new(INADDR_ANY, UDP_PORT_B);
+    assert_eq!(bind_in(a, &addr_a), 0);
+    assert_eq!(bind_in(b, &addr_b), 0);
+    assert_eq!(bind_in(b, &addr_a), -EINVAL);
+    let to_b = SockaddrIn::new(INADDR_LOOPBACK, UDP_PORT_B);
+
+    // Message boundaries are kept, and the sender is reported.
+    assert_eq!(sendto_in(a, b"first", 0, &to_b), 5);
+    assert_eq!(sendto_in(a, b"second message", 0, &to_b), 14);
+    let mut buf = [0u8; 32];
+    let mut from = SockaddrIn::default();
+    let mut fds = [PollFd::new(b as i32, PollEvents::POLLIN)];
+    assert_eq!(poll(&mut fds, 1000), 1);
+    assert_eq!(recvfrom_in(b, &mut buf, 0, &mut from), 5);
+    assert_eq!(&buf[..5], b"first");
+    assert_eq!((from.addr, from.port()), (INADDR_LOOPBACK, UDP_PORT_A));
+    // The rest of a truncated datagram is dropped.
+    let mut small = [0u8; 6];
+    assert_eq!(recv(b, &mut small, MSG_TRUNC), 14);
+    assert_eq!(&small, b"second");
+    assert_eq!(recv(b, &mut buf, 0), -EAGAIN);
+    assert_eq!(sendto_in(a, &[0u8; 2048], 0, &to_b), -EMSGSIZE);
+
+    // A connected datagram socket sends to its default destination,
+    // and drops datagrams from other addresses.
+    assert_eq!(connect_in(b, &addr_a), 0);
+    assert_eq!(send(b, b"back", 0), 4);
+    assert_eq!(recv(a, &mut buf, 0), 4);
+    assert_eq!(&buf[..4], b"back");
+    let c = socket(AF_INET, SOCK_DGRAM) as usize;
+    assert_eq!(send(c, b"x", 0), -EDESTADDRREQ);
+    assert_eq!(sendto_in(c, b"other", 0, &to_b), 5);
+    assert_eq!(sendto_in(a, b"peer", 0, &to_b), 4);
+    assert_eq!(poll(&mut fds, 1000), 1);
+    assert_eq!(recv(b, &mut buf, 0), 4);
+    assert_eq!(&buf[..4], b"peer");
+    // The sender of an unbound socket is bound to an ephemeral port.
+    let mut local = SockaddrIn::default();
+    assert_eq!(getsockname_in(c, &mut local), 0);
+    assert_ne!(local.port(), 0);
+    close(c);
+
+    close(a);
+    close(b);
+    let a = socket(AF_INET, SOCK_DGRAM) as usize;
+    assert_eq!(bind_in(a, &addr_a), 0);
+    close(a);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    tcp_test();
+    udp_test();
+    println!("tcp_udp_test passed!");
+    0
+}
@@ -0,0 +1,264 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use user_lib::{
+    condvar_broadcast, condvar_create, condvar_timedwait, condvar_wait, exit, get_time,
+    kernel_condvar_broadcast, kernel_condvar_create, kernel_condvar_signal,
+    kernel_condvar_timedwait, kernel_condvar_wait, kernel_mutex_create,
+    kernel_mutex_create_with_flags, kernel_mutex_lock, kernel_mutex_timedlock,
+    kernel_mutex_trylock, kernel_mutex_unlock, kernel_semaphore_create, kernel_semaphore_timeddown,
+    kernel_semaphore_trydown, kernel_semaphore_up, mutex_blocking_create, mutex_lock,
+    mutex_timedlock, mutex_trylock, mutex_unlock, semaphore_create, semaphore_timeddown,
+    semaphore_trydown, semaphore_up, sleep, thread_create, waittid, TimeSpec, EAGAIN, EBUSY,
+    EINVAL, ETIMEDOUT, MUTEX_BLOCKING, MUTEX_PRIO_INHERIT, MUTEX_RECURSIVE,
+};
+
+const THREADS: usize = 4;
+/// How long a thread holds a lock, or waits before it signals.
+const HOLD_MS: usize = 100;
+
+static MUTEX: AtomicUsize = AtomicUsize::new(0);
+static SEMAPHORE: AtomicUsize = AtomicUsize::new(0);
+static CONDVAR: AtomicUsize = AtomicUsize::new(0);
+static HELD: AtomicBool = AtomicBool::new(false);
+static WOKEN: AtomicUsize = AtomicUsize::new(0);
+static READY: AtomicBool = AtomicBool::new(false);
+
+fn deadline_after(ms: usize) -> TimeSpec {
+    TimeSpec::from_ms(get_time() as usize + ms)
+}
+
+fn spawn(entry: fn() -> !) -> usize {
+    let tid = thread_create(entry as usize, 0);
+    assert!(tid > 0);
+    tid as usize
+}
+
+fn join(tids: Vec<usize>) {
+    for tid in tids {
+        assert_eq!(waittid(tid), 0);
+    }
+}
+
+/// Assert that f fails with ETIMEDOUT, after waiting until the deadline.
+fn assert_timeout(ms: usize, f: impl FnOnce(&TimeSpec) -> isize) {
+    let start = get_time();
+    assert_eq!(f(&deadline_after(ms)), -ETIMEDOUT);
+    assert!(get_time() - start >= ms as isize);
+}
+
+fn hold_kernel_mutex() -> ! {
+    let mutex = MUTEX.load(Ordering::SeqCst);
+    assert_eq!(kernel_mutex_lock(mutex), 0);
+    HELD.store(true, Ordering::SeqCst);
+    sleep(HOLD_MS);
+    assert_eq!(kernel_mutex_unlock(mutex), 0);
+    exit(0)
+}
+
+fn kernel_mutex_test(flags: usize) {
+    let mutex = kernel_mutex_create_with_flags(flags);
+    assert!(mutex >= 0);
+    let mutex = mutex as usize;
+    MUTEX.store(mutex, Ordering::SeqCst);
+    HELD.store(false, Ordering::SeqCst);
+    assert_eq!(kernel_mutex_trylock(mutex), 0);
+    // A recursive mutex is locked again by the owner, others are busy.
+    match flags & MUTEX_RECURSIVE {
+        0 => assert_eq!(kernel_mutex_trylock(mutex), -EBUSY),
+        _ => {
+            assert_eq!(kernel_mutex_trylock(mutex), 0);
+            assert_eq!(kernel_mutex_unlock(mutex), 0);
+        }
+    }
+    assert_eq!(kernel_mutex_unlock(mutex), 0);
+
+    let tid = spawn(hold_kernel_mutex);
+    while !HELD.load(Ordering::SeqCst) {
+        sleep(1);
+    }
+    assert_eq!(kernel_mutex_trylock(mutex), -EBUSY);
+    assert_timeout(HOLD_MS / 4, |timeout| {
+        kernel_mutex_timedlock(mutex, timeout)
+    });
+    // The timed out thread is not in the wait queue, the mutex is not handed to it.
+    let start = get_time();
+    assert_eq!(kernel_mutex_timedlock(mutex, &deadline_after(5000)), 0);
+    assert!(get_time() - start < 5000);
+    assert_eq!(kernel_mutex_unlock(mutex), 0);
+    assert_eq!(waittid(tid), 0);
+    assert_eq!(kernel_mutex_trylock(mutex), 0);
+    assert_eq!(kernel_mutex_unlock(mutex), 0);
+
+    let bad = TimeSpec {
+        sec: 0,
+        nsec: 1_000_000_000,
+    };
+    assert_eq!(kernel_mutex_timedlock(mutex, &bad), -EINVAL);
+}
+
+fn up_later() -> ! {
+    sleep(HOLD_MS);
+    kernel_semaphore_up(SEMAPHORE.load(Ordering::SeqCst));
+    exit(0)
+}
+
+fn kernel_semaphore_test() {
+    let semaphore = kernel_semaphore_create(1);
+    SEMAPHORE.store(semaphore, Ordering::SeqCst);
+    assert_eq!(kernel_semaphore_trydown(semaphore), 0);
+    assert_eq!(kernel_semaphore_trydown(semaphore), -EAGAIN);
+    assert_timeout(HOLD_MS / 4, |timeout| {
+        kernel_semaphore_timeddown(semaphore, timeout)
+    });
+    // The unit is not given to the timed out thread.
+    assert_eq!(kernel_semaphore_up(semaphore), 0);
+    assert_eq!(kernel_semaphore_trydown(semaphore), 0);
+
+    let tid = spawn(up_later);
+    let start = get_time();
+    assert_eq!(
+        kernel_semaphore_timeddown(semaphore, &deadline_after(5000)),
+        0
+    );
+    assert!(get_time() - start < 5000);
+    assert_eq!(waittid(tid), 0);
+}
+
+fn wait_kernel_condvar() -> ! {
+    let (mutex, condvar) = (MUTEX.load(Ordering::SeqCst), CONDVAR.load(Ordering::SeqCst));
+    assert_eq!(kernel_mutex_lock(mutex), 0);
+    WOKEN.fetch_add(1, Ordering::SeqCst);
+    while !READY.load(Ordering::SeqCst) {
+        assert_eq!(kernel_condvar_wait(condvar, mutex), 0);
+    }
+    assert_eq!(kernel_mutex_unlock(mutex), 0);
+    exit(0)
+}
+
+/// Start the threads waiting on the condvar until READY.
+fn start_waiters(entry: fn() -> !, n: usize) -> Vec<usize> {
+    READY.store(false, Ordering::SeqCst);
+    WOKEN.store(0, Ordering::SeqCst);
+    let tids = (0..n).map(|_| spawn(entry)).collect();
+    while WOKEN.load(Ordering::SeqCst) < n {
+        sleep(1);
+    }
+    // Let the last one wait.
+    sleep(20);
+    tids
+}
+
+fn kernel_condvar_test() {
+    let mutex = kernel_mutex_create(true);
+    let condvar = kernel_condvar_create();
+    MUTEX.store(mutex, Ordering::SeqCst);
+    CONDVAR.store(condvar, Ordering::SeqCst);
+
+    // The mutex is locked again after a timeout.
+    assert_eq!(kernel_mutex_lock(mutex), 0);
+    assert_timeout(HOLD_MS / 4, |timeout| {
+        kernel_condvar_timedwait(condvar, mutex, timeout)
+    });
+    assert_eq!(kernel_mutex_unlock(mutex), 0);
+
+    // A signal after a timeout is not taken by the timed out thread.
+    let tids = start_waiters(wait_kernel_condvar, 1);
+    assert_eq!(kernel_mutex_lock(mutex), 0);
+    READY.store(true, Ordering::SeqCst);
+    assert_eq!(kernel_condvar_signal(condvar), 0);
+    assert_eq!(kernel_mutex_unlock(mutex), 0);
+    join(tids);
+
+    let tids = start_waiters(wait_kernel_condvar, THREADS);
+    assert_eq!(kernel_mutex_lock(mutex), 0);
+    READY.store(true, Ordering::SeqCst);
+    assert_eq!(kernel_condvar_broadcast(condvar), 0);
+    assert_eq!(kernel_mutex_unlock(mutex), 0);
+    join(tids);
+}
+
+fn hold_mutex() -> ! {
+    let mutex = MUTEX.load(Ordering::SeqCst);
+    mutex_lock(mutex);
+    HELD.store(true, Ordering::SeqCst);
+    sleep(HOLD_MS);
+    mutex_unlock(mutex);
+    exit(0)
+}
+
+fn wait_condvar() -> ! {
+    let (mutex, condvar) = (MUTEX.load(Ordering::SeqCst), CONDVAR.load(Ordering::SeqCst));
+    mutex_lock(mutex);
+    WOKEN.fetch_add(1, Ordering::SeqCst);
+    while !READY.load(Ordering::SeqCst) {
+        condvar_wait(condvar, mutex);
+    }
+    mutex_unlock(mutex);
+    exit(0)
+}
+
+/// The same operations on the futex based primitives.
+fn user_test() {
+    let mutex = mutex_blocking_create() as usize;
+    MUTEX.store(mutex, Ordering::SeqCst);
+    HELD.store(false, Ordering::SeqCst);
+    assert_eq!(mutex_trylock(mutex), 0);
+    assert_eq!(mutex_trylock(mutex), -EBUSY);
+    mutex_unlock(mutex);
+    let tid = spawn(hold_mutex);
+    while !HELD.load(Ordering::SeqCst) {
+        sleep(1);
+    }
+    assert_timeout(HOLD_MS / 4, |timeout| mutex_timedlock(mutex, timeout));
+    assert_eq!(mutex_timedlock(mutex, &deadline_after(5000)), 0);
+    mutex_unlock(mutex);
+    assert_eq!(waittid(tid), 0);
+
+    let semaphore = semaphore_create(0) as usize;
+    assert_eq!(semaphore_trydown(semaphore), -EAGAIN);
+    assert_timeout(HOLD_MS / 4, |timeout| {
+        semaphore_timeddown(semaphore, timeout)
+    });
+    semaphore_up(semaphore);
+    assert_eq!(semaphore_timeddown(semaphore, &deadline_after(5000)), 0);
+
+    let condvar = condvar_create() as usize;
+    CONDVAR.store(condvar, Ordering::SeqCst);
+    mutex_lock(mutex);
+    assert_timeout(HOLD_MS / 4, |timeout| {
+        condvar_timedwait(condvar, mutex, timeout)
+    });
+    assert_eq!(mutex_trylock(mutex), -EBUSY);
+    mutex_unlock(mutex);
+    let tids = start_waiters(wait_condvar, THREADS);
+    mutex_lock(mutex);
+    READY.store(true, Ordering::SeqCst);
+    condvar_broadcast(condvar);
+    mutex_unlock(mutex);
+    join(tids);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    for flags in [
+        0,
+        MUTEX_BLOCKING,
+        MUTEX_BLOCKING | MUTEX_RECURSIVE,
+        MUTEX_BLOCKING | MUTEX_PRIO_INHERIT,
+    ] {
+        kernel_mutex_test(flags);
+    }
+    kernel_semaphore_test();
+    kernel_condvar_test();
+    user_test();
+    println!("timed_sync_test passed!");
+    0
+}
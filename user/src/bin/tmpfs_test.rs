@@ -0,0 +1,75 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, fstat, ftruncate, mkdir, mount, open, read, read_dir, read_to_string, rmdir, stat,
+    truncate, umount, unlink, write, OpenFlags, Stat,
+};
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // /tmp is mounted at boot.
+    let mounts = read_to_string("/proc/mounts\0").unwrap();
+    assert!(mounts.lines().any(|line| line.starts_with("tmpfs /tmp ")));
+
+    // Directories and files.
+    assert_eq!(mkdir("/tmp/tmpfs_dir\0"), 0);
+    assert_eq!(mkdir("/tmp/tmpfs_dir\0"), -1);
+    let fd = open(
+        "/tmp/tmpfs_dir/file\0",
+        OpenFlags::CREATE | OpenFlags::READ_WRITE,
+    );
+    assert!(fd >= 0);
+    let fd = fd as usize;
+    assert_eq!(write(fd, b"hello tmpfs"), 11);
+    assert_eq!(read_dir("/tmp/tmpfs_dir\0").unwrap(), ["file"]);
+    let mut st = Stat::default();
+    assert_eq!(stat("/tmp/tmpfs_dir\0", &mut st), 0);
+    assert!(st.is_dir());
+
+    // Shrink and grow, the grown part reads as zero.
+    assert_eq!(ftruncate(fd, 5), 0);
+    assert_eq!(fstat(fd, &mut st), 0);
+    assert_eq!(st.size, 5);
+    assert_eq!(truncate("/tmp/tmpfs_dir/file\0", 8), 0);
+    close(fd);
+    let fd = open("/tmp/tmpfs_dir/file\0", OpenFlags::READ_ONLY) as usize;
+    let mut buf = [0xffu8; 16];
+    assert_eq!(read(fd, &mut buf), 8);
+    assert_eq!(&buf[..8], b"hello\0\0\0");
+    // A file opened for reading can not be truncated.
+    assert_eq!(ftruncate(fd, 0), -1);
+    close(fd);
+    assert_eq!(truncate("/tmp/tmpfs_dir\0", 0), -1);
+
+    // Only empty directories can be removed.
+    assert_eq!(rmdir("/tmp/tmpfs_dir\0"), -1);
+    assert_eq!(unlink("/tmp/tmpfs_dir/file\0"), 0);
+    assert_eq!(stat("/tmp/tmpfs_dir/file\0", &mut st), -1);
+    assert_eq!(rmdir("/tmp/tmpfs_dir\0"), 0);
+
+    // The size limit, and truncation frees the pages.
+    assert_eq!(mkdir("/tmp/small\0"), 0);
+    assert_eq!(mount("tmpfs\0", "/tmp/small\0", "size=bad\0"), -1);
+    assert_eq!(mount("tmpfs\0", "/tmp/small\0", "size=8k\0"), 0);
+    let fd = open(
+        "/tmp/small/big\0",
+        OpenFlags::CREATE | OpenFlags::WRITE_ONLY,
+    ) as usize;
+    let page = [b'x'; 4096];
+    assert_eq!(write(fd, &page), 4096);
+    assert_eq!(write(fd, &page), 4096);
+    assert_eq!(write(fd, &page), 0);
+    assert_eq!(ftruncate(fd, 4096), 0);
+    assert_eq!(write(fd, &page[..100]), 100);
+    close(fd);
+    assert_eq!(umount("/tmp/small\0"), 0);
+    // The file is gone with the file system.
+    assert_eq!(stat("/tmp/small/big\0", &mut st), -1);
+    assert_eq!(rmdir("/tmp/small\0"), 0);
+    println!("tmpfs_test passed!");
+    0
+}
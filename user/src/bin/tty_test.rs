@@ -0,0 +1,60 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    exit, fork, get_pid, read, setpgid, sigaction, tcgetattr, tcgetpgrp, tcsetattr, tcsetpgrp,
+    waitpid, SignalAction, Termios, ECHO, EIO, ICANON, SIGTTIN, SIG_IGN, VEOF, VERASE, VINTR,
+    VSUSP,
+};
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut termios = Termios::default();
+    assert_eq!(tcgetattr(0, &mut termios), 0);
+    assert!(termios.lflag & ICANON != 0);
+    assert!(termios.lflag & ECHO != 0);
+    assert_eq!(termios.cc[VINTR], 0x03);
+    assert_eq!(termios.cc[VSUSP], 0x1a);
+    assert_eq!(termios.cc[VERASE], 0x7f);
+    assert_eq!(termios.cc[VEOF], 0x04);
+
+    // Switch to raw mode without echo and read the attributes back.
+    let saved = termios;
+    termios.lflag &= !(ICANON | ECHO);
+    assert_eq!(tcsetattr(0, &termios), 0);
+    let mut raw = Termios::default();
+    assert_eq!(tcgetattr(1, &mut raw), 0);
+    assert_eq!(raw.lflag & (ICANON | ECHO), 0);
+    assert_eq!(tcsetattr(0, &saved), 0);
+
+    // Foreground process group, the new foreground must be an existing group.
+    let old_pgrp = tcgetpgrp(0);
+    assert!(old_pgrp >= 0);
+    assert_eq!(setpgid(0, 0), 0);
+    assert_eq!(tcsetpgrp(0, get_pid() as usize), 0);
+    assert_eq!(tcgetpgrp(0), get_pid());
+    assert_eq!(tcsetpgrp(0, old_pgrp as usize), 0);
+
+    // Reading from the background fails instead of stopping when SIGTTIN is ignored.
+    let child = fork();
+    if child == 0 {
+        assert_eq!(setpgid(0, 0), 0);
+        let mut action = SignalAction::default();
+        action.handler = SIG_IGN;
+        assert_eq!(sigaction(SIGTTIN, Some(&action), None), 0);
+        let mut buf = [0u8; 1];
+        assert_eq!(read(0, &mut buf), -EIO);
+        exit(0);
+    }
+    let mut status = -1;
+    assert_eq!(waitpid(child, &mut status, 0), child);
+    assert_eq!(status, 0);
+
+    // Unsupported request.
+    assert_eq!(user_lib::ioctl(0, 0, 0), -1);
+    println!("tty_test passed!");
+    0
+}
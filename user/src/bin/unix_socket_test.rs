@@ -0,0 +1,206 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    accept, bind, close, connect, exit, fork, getpeername, getsockname, listen, open, pipe, read,
+    recv, recv_fds, recvfrom, send, send_fds, sendto, shutdown, socket, socketpair, stat, unlink,
+    waitpid, write, OpenFlags, SockaddrUn, Stat, AF_UNIX, EADDRINUSE, EAGAIN, EBADF, ECONNREFUSED,
+    EINVAL, ENOENT, ENOTCONN, ENOTSOCK, ENXIO, EPIPE, EPROTOTYPE, MSG_NOSIGNAL, MSG_TRUNC, SHUT_WR,
+    SOCK_DGRAM, SOCK_NONBLOCK, SOCK_STREAM,
+};
+
+const STREAM: &str = "/unix_stream.sock\0";
+const DGRAM_A: &str = "/unix_dgram_a.sock\0";
+const DGRAM_B: &str = "/unix_dgram_b.sock\0";
+
+fn wait_child(pid: isize) {
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid, &mut exit_code, 0), pid);
+    assert_eq!(exit_code, 0);
+}
+
+fn stream_test() {
+    let server = socket(AF_UNIX, SOCK_STREAM) as usize;
+    assert_eq!(bind(server, STREAM), 0);
+    let mut st = Stat::default();
+    assert_eq!(stat(STREAM, &mut st), 0);
+    assert!(st.is_socket());
+    assert_eq!(open(STREAM, OpenFlags::READ_ONLY), -ENXIO);
+    let other = socket(AF_UNIX, SOCK_STREAM) as usize;
+    assert_eq!(bind(other, STREAM), -EADDRINUSE);
+    close(other);
+    assert_eq!(listen(server, 4), 0);
+
+    let pid = fork();
+    if pid == 0 {
+        let fd = socket(AF_UNIX, SOCK_STREAM) as usize;
+        assert_eq!(connect(fd, STREAM), 0);
+        let mut addr = SockaddrUn::default();
+        assert_eq!(getpeername(fd, &mut addr), 0);
+        assert_eq!(addr.path(), "/unix_stream.sock");
+        assert_eq!(send(fd, b"hello ", 0), 6);
+        assert_eq!(write(fd, b"server"), 6);
+        let mut buf = [0u8; 16];
+        assert_eq!(recv(fd, &mut buf, 0), 5);
+        assert_eq!(&buf[..5], b"reply");
+        // The server gets end of file, and closes its socket.
+        assert_eq!(shutdown(fd, SHUT_WR), 0);
+        assert_eq!(recv(fd, &mut buf, 0), 0);
+        exit(0);
+    }
+    let conn = accept(server, None);
+    assert!(conn >= 0);
+    let conn = conn as usize;
+    let mut addr = SockaddrUn::default();
+    assert_eq!(getsockname(conn, &mut addr), 0);
+    assert_eq!(addr.path(), "/unix_stream.sock");
+    // The stream has no message boundaries.
+    let mut buf = [0u8; 32];
+    let mut len = 0;
+    while len < 12 {
+        let ret = read(conn, &mut buf[len..]);
+        assert!(ret > 0);
+        len += ret as usize;
+    }
+    assert_eq!(&buf[..12], b"hello server");
+    assert_eq!(send(conn, b"reply", 0), 5);
+    assert_eq!(recv(conn, &mut buf, 0), 0);
+    close(conn);
+    wait_child(pid);
+
+    // No connection is waiting.
+    let listener = socket(AF_UNIX, SOCK_STREAM | SOCK_NONBLOCK) as usize;
+    assert_eq!(listen(listener, 1), -EINVAL);
+    assert_eq!(unlink(STREAM), 0);
+    assert_eq!(bind(listener, STREAM), 0);
+    assert_eq!(listen(listener, 1), 0);
+    assert_eq!(accept(listener, None), -EAGAIN);
+
+    // A closed socket refuses connections while its socket file remains.
+    close(server);
+    close(listener);
+    let fd = socket(AF_UNIX, SOCK_STREAM) as usize;
+    assert_eq!(connect(fd, STREAM), -ECONNREFUSED);
+    assert_eq!(connect(fd, "/\0"), -ECONNREFUSED);
+    assert_eq!(connect(fd, "/no_such.sock\0"), -ENOENT);
+    assert_eq!(recv(fd, &mut buf, 0), -ENOTCONN);
+    assert_eq!(send(fd, b"x", 0), -ENOTCONN);
+    close(fd);
+    assert_eq!(unlink(STREAM), 0);
+
+    // Writing to a closed peer.
+    let mut sv = [0i32; 2];
+    assert_eq!(socketpair(AF_UNIX, SOCK_STREAM | SOCK_NONBLOCK, &mut sv), 0);
+    let (a, b) = (sv[0] as usize, sv[1] as usize);
+    assert_eq!(recv(a, &mut buf, 0), -EAGAIN);
+    close(b);
+    assert_eq!(send(a, b"x", MSG_NOSIGNAL), -EPIPE);
+    assert_eq!(recv(a, &mut buf, 0), 0);
+    close(a);
+}
+
+fn dgram_test() {
+    let a = socket(AF_UNIX, SOCK_DGRAM) as usize;
+    let b = socket(AF_UNIX, SOCK_DGRAM | SOCK_NONBLOCK) as usize;
+    assert_eq!(bind(a, DGRAM_A), 0);
+    assert_eq!(bind(b, DGRAM_B), 0);
+    assert_eq!(bind(b, DGRAM_A), -EINVAL);
+
+    // Message boundaries are kept, and the sender is reported.
+    assert_eq!(sendto(a, b"first", 0, DGRAM_B), 5);
+    assert_eq!(sendto(a, b"second message", 0, DGRAM_B), 14);
+    let mut buf = [0u8; 32];
+    let mut addr = SockaddrUn::default();
+    assert_eq!(recvfrom(b, &mut buf, 0, &mut addr), 5);
+    assert_eq!(&buf[..5], b"first");
+    assert_eq!(addr.path(), "/unix_dgram_a.sock");
+    // The rest of a truncated datagram is dropped.
+    let mut small = [0u8; 6];
+    assert_eq!(recv(b, &mut small, MSG_TRUNC), 14);
+    assert_eq!(&small, b"second");
+    assert_eq!(recv(b, &mut buf, 0), -EAGAIN);
+
+    // A connected datagram socket sends to its default destination.
+    assert_eq!(connect(b, DGRAM_A), 0);
+    assert_eq!(send(b, b"back", 0), 4);
+    assert_eq!(recv(a, &mut buf, 0), 4);
+    assert_eq!(&buf[..4], b"back");
+
+    let c = socket(AF_UNIX, SOCK_DGRAM) as usize;
+    assert_eq!(send(c, b"x", 0), -ENOTCONN);
+    let stream = socket(AF_UNIX, SOCK_STREAM) as usize;
+    assert_eq!(connect(stream, DGRAM_A), -EPROTOTYPE);
+    close(stream);
+    close(c);
+
+    close(b);
+    assert_eq!(sendto(a, b"x", 0, DGRAM_B), -ECONNREFUSED);
+    close(a);
+    assert_eq!(unlink(DGRAM_A), 0);
+    assert_eq!(unlink(DGRAM_B), 0);
+}
+
+fn scm_rights_test() {
+    let mut sv = [0i32; 2];
+    assert_eq!(socketpair(AF_UNIX, SOCK_STREAM, &mut sv), 0);
+    let (a, b) = (sv[0] as usize, sv[1] as usize);
+
+    // A stream stops before the data carrying fds.
+    let mut pipe_fd = [0usize; 2];
+    assert_eq!(pipe(&mut pipe_fd), 0);
+    assert_eq!(send(a, b"ab", 0), 2);
+    assert_eq!(send_fds(a, b"c", &[pipe_fd[0] as i32]), 1);
+    let mut buf = [0u8; 8];
+    let mut fds = [-1i32; 2];
+    assert_eq!(recv_fds(b, &mut buf, &mut fds), (2, 0));
+    assert_eq!(recv_fds(b, &mut buf, &mut fds), (1, 1));
+    assert_eq!(buf[0], b'c');
+    let received = fds[0] as usize;
+    assert_ne!(received, pipe_fd[0]);
+    assert_eq!(write(pipe_fd[1], b"shared"), 6);
+    assert_eq!(read(received, &mut buf), 6);
+    assert_eq!(&buf[..6], b"shared");
+    close(received);
+    close(pipe_fd[0]);
+    close(pipe_fd[1]);
+    assert_eq!(send_fds(a, b"x", &[99]), -EBADF);
+
+    // A pipe created after fork is passed to the child, which does not share the fd table.
+    let pid = fork();
+    if pid == 0 {
+        close(a);
+        let (len, count) = recv_fds(b, &mut buf, &mut fds);
+        assert_eq!((len, count), (1, 1));
+        let fd = fds[0] as usize;
+        let mut data = [0u8; 16];
+        assert_eq!(read(fd, &mut data), 12);
+        assert_eq!(&data[..12], b"from parent!");
+        // End of file after the parent closes the write end.
+        assert_eq!(read(fd, &mut data), 0);
+        exit(0);
+    }
+    close(b);
+    let mut pipe_fd = [0usize; 2];
+    assert_eq!(pipe(&mut pipe_fd), 0);
+    assert_eq!(send_fds(a, b"p", &[pipe_fd[0] as i32]), 1);
+    close(pipe_fd[0]);
+    assert_eq!(write(pipe_fd[1], b"from parent!"), 12);
+    close(pipe_fd[1]);
+    wait_child(pid);
+    close(a);
+
+    assert_eq!(recv(pipe_fd[1], &mut buf, 0), -EBADF);
+    assert_eq!(recv(0, &mut buf, 0), -ENOTSOCK);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    stream_test();
+    dgram_test();
+    scm_rights_test();
+    println!("unix_socket_test passed!");
+    0
+}
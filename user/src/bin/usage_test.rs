@@ -0,0 +1,75 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    exit, fork, get_pid, get_time, getrusage, task_info, times, wait_pid, RUsage, TaskInfo, Tms,
+    RUSAGE_CHILDREN, RUSAGE_SELF,
+};
+
+const SYSCALL_GETPID: usize = 172;
+const GETPID_COUNT: u32 = 100;
+const BUSY_MS: isize = 200;
+
+/// Burn cpu time in user mode.
+fn busy_loop(ms: isize) -> usize {
+    let start = get_time();
+    let mut x = 0usize;
+    while get_time() - start < ms {
+        for i in 0..10000 {
+            x = x.wrapping_mul(31).wrapping_add(i);
+        }
+    }
+    x
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    for _ in 0..GETPID_COUNT {
+        get_pid();
+    }
+    busy_loop(BUSY_MS);
+    let mut info = TaskInfo::new();
+    assert_eq!(task_info(&mut info), 0);
+    assert!(info.syscall_times[SYSCALL_GETPID] >= GETPID_COUNT);
+    assert!(info.time as isize >= BUSY_MS);
+    assert!(info.utime > 0);
+    assert!(info.rss > 0 && info.max_rss >= info.rss);
+    println!(
+        "task_info: getpid={}, time={}ms, utime={}ms, stime={}ms, rss={} pages",
+        info.syscall_times[SYSCALL_GETPID], info.time, info.utime, info.stime, info.rss
+    );
+
+    let mut usage = RUsage::default();
+    assert_eq!(getrusage(RUSAGE_SELF, &mut usage), 0);
+    assert!(usage.utime.as_us() > 0 && usage.maxrss > 0);
+    assert_eq!(getrusage(2, &mut usage), -1);
+
+    // Children are only counted after they are waited.
+    let pid = fork();
+    if pid == 0 {
+        busy_loop(BUSY_MS);
+        exit(0);
+    }
+    let mut children = RUsage::default();
+    assert_eq!(getrusage(RUSAGE_CHILDREN, &mut children), 0);
+    assert_eq!(children.utime.as_us(), 0);
+    let mut exit_code = 0;
+    assert_eq!(wait_pid(pid as usize, &mut exit_code), pid);
+    assert_eq!(getrusage(RUSAGE_CHILDREN, &mut children), 0);
+    assert!(children.utime.as_us() > 0 && children.maxrss > 0);
+    println!(
+        "children: utime={}us, stime={}us, maxrss={}KB",
+        children.utime.as_us(),
+        children.stime.as_us(),
+        children.maxrss
+    );
+
+    let mut tms = Tms::default();
+    assert!(times(&mut tms) > 0);
+    assert!(tms.utime > 0 && tms.cutime > 0);
+    println!("usage_test passed!");
+    0
+}
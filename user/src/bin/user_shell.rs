@@ -5,123 +5,533 @@ use alloc::{
     string::{String, ToString},
     vec::Vec,
 };
-use user_lib::{close, console::get_char, dup, exec, fork, open, wait_pid, OpenFlags};
+use core::iter::Peekable;
+use core::str::Chars;
+use user_lib::{
+    close, dup2, exec, fork, getpgid, kill, open, pipe, read, setpgid, setsid, sigaction,
+    tcsetctty, tcsetpgrp, waitpid, wifstopped, OpenFlags, SignalAction, SignalFlags, SIGCONT,
+    SIGINT, SIGQUIT, SIGTSTP, SIGTTIN, SIGTTOU, SIG_DFL, SIG_IGN, WNOHANG, WUNTRACED,
+};
 
 extern crate alloc;
 #[macro_use]
 extern crate user_lib;
 
-const BS: u8 = 0x08u8; // backspace
-const LF: u8 = 0x0au8; // \n
-const CR: u8 = 0x0du8; // \r
-const DL: u8 = 0x7fu8; // delete
+const STDIN: usize = 0;
+const STDOUT: usize = 1;
+const STDERR: usize = 2;
+const LINE_MAX: usize = 256;
+/// Signals generated by the terminal for job control, the shell itself ignores them.
+const JOB_CONTROL_SIGNALS: [u32; 5] = [SIGINT, SIGQUIT, SIGTSTP, SIGTTIN, SIGTTOU];
 
-#[no_mangle]
-fn main() -> i32 {
-    println!("Welcom to rust shell");
-    print!(">> ");
-    let mut line = String::new();
-    loop {
-        let c = get_char();
-        match c {
-            LF | CR => {
-                println!("");
-                if line.is_empty() {
-                    print!(">> ");
-                    continue;
+#[derive(PartialEq, Eq)]
+enum Token {
+    Word(String),
+    /// |
+    Pipe,
+    /// <
+    Input,
+    /// >
+    Output,
+    /// >>
+    Append,
+    /// 2>
+    ErrorOutput,
+    /// &
+    Background,
+}
+
+/// Split the command line into words and operators.
+/// Operators need no spaces around them, quotes keep spaces and operators in a word.
+fn tokenize(line: &str) -> Result<Vec<Token>, &'static str> {
+    let mut tokens = Vec::new();
+    let mut chars = line.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        let token = match c {
+            ' ' | '\t' => {
+                chars.next();
+                continue;
+            }
+            '|' => Token::Pipe,
+            '&' => Token::Background,
+            '<' => Token::Input,
+            '>' => {
+                chars.next();
+                if chars.peek() == Some(&'>') {
+                    chars.next();
+                    tokens.push(Token::Append);
+                } else {
+                    tokens.push(Token::Output);
+                }
+                continue;
+            }
+            _ => {
+                let (word, quoted) = read_word(&mut chars)?;
+                // "2>" at the start of a word redirects stderr.
+                if word == "2" && !quoted && chars.peek() == Some(&'>') {
+                    chars.next();
+                    tokens.push(Token::ErrorOutput);
+                } else {
+                    tokens.push(Token::Word(word));
                 }
+                continue;
+            }
+        };
+        chars.next();
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
 
-                // Split commands and parameters.
-                let args: Vec<&str> = line.as_str().split(' ').collect();
-                let mut io_redirect_symbol_idx = None;
-                let mut args_with_end: Vec<String> = args
-                    .iter()
-                    .enumerate()
-                    .map(|(idx, &arg)| {
-                        // Processing stream operators
-                        if arg == ">" || arg == "<" {
-                            io_redirect_symbol_idx = Some(idx);
-                        }
-                        let mut arg_string = arg.to_string();
-                        arg_string.push('\0');
-                        arg_string
-                    })
-                    .collect();
-
-                // Get the io redirected file
-                let mut input = String::new();
-                let mut output = String::new();
-                if let Some(idx) = io_redirect_symbol_idx {
-                    let mut drain = args_with_end.drain(idx..=idx + 1);
-                    let symbol = drain.next().unwrap();
-                    let redirect_file = drain.next().unwrap();
-                    if symbol == "<" {
-                        input = redirect_file;
-                    } else {
-                        output = redirect_file;
+/// Read a word until a space or an operator.
+/// # Return
+/// * (the word, whether any part of it is quoted)
+fn read_word(chars: &mut Peekable<Chars>) -> Result<(String, bool), &'static str> {
+    let mut word = String::new();
+    let mut quoted = false;
+    while let Some(&c) = chars.peek() {
+        match c {
+            ' ' | '\t' | '|' | '&' | '<' | '>' => break,
+            '\'' | '"' => {
+                chars.next();
+                loop {
+                    match chars.next() {
+                        Some(q) if q == c => break,
+                        Some(ch) => word.push(ch),
+                        None => return Err("unterminated quote"),
                     }
                 }
+                quoted = true;
+            }
+            _ => {
+                chars.next();
+                word.push(c);
+            }
+        }
+    }
+    Ok((word, quoted))
+}
 
-                let mut args_addr: Vec<*const u8> =
-                    args_with_end.iter().map(|s| s.as_ptr()).collect();
-                args_addr.push(0 as *const u8);
-                let pid = fork();
-                // child process
-                if pid == 0 {
-                    if !input.is_empty() {
-                        let input_fd = open(&input, OpenFlags::READ_ONLY);
-                        if input_fd == -1 {
-                            println!("Error when opening file {}.", input);
-                            return -4;
-                        }
-                        // close stdin
-                        close(0);
-                        // copy input file to fd 0
-                        assert_eq!(dup(input_fd as usize), 0, "Error when input redirect");
-                        close(input_fd as usize);
-                    }
+/// A command of a pipeline with its redirections.
+#[derive(Default)]
+struct Stage {
+    args: Vec<String>,
+    input: Option<String>,
+    /// (file, append)
+    output: Option<(String, bool)>,
+    error: Option<String>,
+}
 
-                    if !output.is_empty() {
-                        let output_fd = open(&output, OpenFlags::WRITE_ONLY | OpenFlags::CREATE);
-                        if output_fd == -1 {
-                            println!("Error when opening file {}.", output);
-                            return -4;
-                        }
-                        // close stdout
-                        close(1);
-                        // copy output file to fd 1
-                        assert_eq!(dup(output_fd as usize), 1, "Error when output redirect");
-                        close(output_fd as usize);
-                    }
+struct Pipeline {
+    stages: Vec<Stage>,
+    background: bool,
+}
+
+/// Parse a command line like "a < in | b 2> err | c >> out &".
+/// # Return
+/// * Ok(None) - the line is empty.
+fn parse(line: &str) -> Result<Option<Pipeline>, &'static str> {
+    let mut tokens = tokenize(line)?;
+    if tokens.is_empty() {
+        return Ok(None);
+    }
+    let background = tokens.last() == Some(&Token::Background);
+    if background {
+        tokens.pop();
+    }
+    let mut stages = Vec::new();
+    let mut stage = Stage::default();
+    let mut tokens = tokens.into_iter();
+    while let Some(token) = tokens.next() {
+        match token {
+            Token::Word(word) => stage.args.push(word),
+            Token::Pipe => {
+                if stage.args.is_empty() {
+                    return Err("missing command before '|'");
+                }
+                stages.push(core::mem::take(&mut stage));
+            }
+            Token::Background => return Err("'&' must be at the end"),
+            redirect => {
+                let Some(Token::Word(file)) = tokens.next() else {
+                    return Err("missing redirect file");
+                };
+                match redirect {
+                    Token::Input => stage.input = Some(file),
+                    Token::Output => stage.output = Some((file, false)),
+                    Token::Append => stage.output = Some((file, true)),
+                    _ => stage.error = Some(file),
+                }
+            }
+        }
+    }
+    if stage.args.is_empty() {
+        return Err("missing command");
+    }
+    stages.push(stage);
+    Ok(Some(Pipeline { stages, background }))
+}
+
+/// Move fd to target.
+fn redirect(fd: usize, target: usize) {
+    assert_eq!(
+        dup2(fd, target),
+        target as isize,
+        "Error when redirecting fd {}",
+        target
+    );
+    close(fd);
+}
+
+/// Open the file on target fd, exit if it fails.
+fn open_on(path: &str, flags: OpenFlags, target: usize) {
+    let mut path_with_end = path.to_string();
+    path_with_end.push('\0');
+    let (bits, append) = (flags.bits(), flags.contains(OpenFlags::APPEND));
+    let mut fd = open(&path_with_end, flags);
+    // CREATE truncates an existing file, so appending creates the file only if it does not exist.
+    if fd == -1 && append {
+        fd = open(
+            &path_with_end,
+            OpenFlags::from_bits_retain(bits) | OpenFlags::CREATE,
+        );
+    }
+    if fd == -1 {
+        println!("Error when opening file {}.", path);
+        user_lib::exit(-4);
+    }
+    redirect(fd as usize, target);
+}
+
+impl Stage {
+    /// Apply the file redirections and execute the command, in the child process.
+    fn exec(&self) -> ! {
+        if let Some(input) = &self.input {
+            open_on(input, OpenFlags::READ_ONLY, STDIN);
+        }
+        if let Some((output, append)) = &self.output {
+            let mode = if *append {
+                OpenFlags::APPEND
+            } else {
+                OpenFlags::CREATE | OpenFlags::TRUNC
+            };
+            open_on(output, OpenFlags::WRITE_ONLY | mode, STDOUT);
+        }
+        if let Some(error) = &self.error {
+            let flags = OpenFlags::WRITE_ONLY | OpenFlags::CREATE | OpenFlags::TRUNC;
+            open_on(error, flags, STDERR);
+        }
+        let args_with_end: Vec<String> = self
+            .args
+            .iter()
+            .map(|arg| {
+                let mut arg_string = arg.clone();
+                arg_string.push('\0');
+                arg_string
+            })
+            .collect();
+        let mut args_addr: Vec<*const u8> = args_with_end.iter().map(|s| s.as_ptr()).collect();
+        args_addr.push(core::ptr::null());
+        exec(args_with_end[0].as_str(), args_addr.as_slice());
+        println!("Error when executing {}.", self.args[0]);
+        user_lib::exit(-4);
+    }
+}
+
+#[derive(PartialEq, Eq)]
+enum JobState {
+    Running,
+    Stopped,
+}
+
+/// A job is a pipeline started by the shell, all of its processes are in one process group,
+/// the pgid is the pid of the first process.
+struct Job {
+    id: usize,
+    pgid: usize,
+    /// Pid and exit code of every stage.
+    stages: Vec<(usize, Option<i32>)>,
+    command: String,
+    state: JobState,
+}
+
+impl Job {
+    fn done(&self) -> bool {
+        self.stages.iter().all(|(_, code)| code.is_some())
+    }
+
+    fn set_exit_code(&mut self, pid: usize, exit_code: i32) {
+        if let Some(stage) = self
+            .stages
+            .iter_mut()
+            .find(|(stage_pid, _)| *stage_pid == pid)
+        {
+            stage.1 = Some(exit_code);
+        }
+    }
 
-                    if exec(args_with_end[0].as_str(), args_addr.as_slice()) == -1 {
-                        println!("Error when executing");
-                        return -4;
+    /// Exit codes of all stages, like "0|1".
+    fn exit_codes(&self) -> String {
+        self.stages
+            .iter()
+            .map(|(_, code)| code.unwrap_or_default().to_string())
+            .collect::<Vec<String>>()
+            .join("|")
+    }
+}
+
+struct Shell {
+    /// The process group of the shell.
+    pgid: usize,
+    jobs: Vec<Job>,
+}
+
+/// Set the action of the job control signals.
+fn set_job_control_signals(handler: usize) {
+    let action = SignalAction {
+        handler,
+        mask: SignalFlags::empty(),
+    };
+    for signum in JOB_CONTROL_SIGNALS {
+        sigaction(signum, Some(&action), None);
+    }
+}
+
+impl Shell {
+    fn new() -> Self {
+        // Become the session leader and take the terminal.
+        setsid();
+        tcsetctty(STDIN);
+        set_job_control_signals(SIG_IGN);
+        Self {
+            pgid: getpgid(0) as usize,
+            jobs: Vec::new(),
+        }
+    }
+
+    /// Add a job and return its index.
+    fn add_job(&mut self, pgid: usize, stages: Vec<(usize, Option<i32>)>, command: &str) -> usize {
+        let id = (1..)
+            .find(|id| self.jobs.iter().all(|job| job.id != *id))
+            .unwrap();
+        self.jobs.push(Job {
+            id,
+            pgid,
+            stages,
+            command: command.to_string(),
+            state: JobState::Running,
+        });
+        self.jobs.len() - 1
+    }
+
+    /// Find a job by "%id" or "id", the latest job if spec is None.
+    fn find_job(&self, spec: Option<&str>) -> Option<usize> {
+        match spec {
+            None => {
+                if self.jobs.is_empty() {
+                    None
+                } else {
+                    Some(self.jobs.len() - 1)
+                }
+            }
+            Some(spec) => {
+                let id: usize = spec.trim_start_matches('%').parse().ok()?;
+                self.jobs.iter().position(|job| job.id == id)
+            }
+        }
+    }
+
+    /// Report background jobs which have finished or stopped.
+    fn reap_jobs(&mut self) {
+        let mut idx = 0;
+        while idx < self.jobs.len() {
+            let job = &mut self.jobs[idx];
+            let mut status = 0;
+            loop {
+                let pid = waitpid(-(job.pgid as isize), &mut status, WNOHANG | WUNTRACED);
+                if pid < 0 {
+                    break;
+                }
+                if wifstopped(status) {
+                    if job.state != JobState::Stopped {
+                        job.state = JobState::Stopped;
+                        println!("[{}]+ Stopped\t{}", job.id, job.command);
                     }
-                    unreachable!();
+                } else {
+                    job.set_exit_code(pid as usize, status);
+                }
+            }
+            if job.done() {
+                println!("[{}] Done({})\t{}", job.id, job.exit_codes(), job.command);
+                self.jobs.remove(idx);
+                continue;
+            }
+            idx += 1;
+        }
+    }
+
+    /// Give the terminal to the job and wait until all of its processes exit or it stops.
+    fn wait_foreground(&mut self, idx: usize) {
+        let pgid = self.jobs[idx].pgid;
+        tcsetpgrp(STDIN, pgid);
+        let job = &mut self.jobs[idx];
+        let mut stopped = false;
+        while !job.done() {
+            let mut status = 0;
+            let pid = waitpid(-(pgid as isize), &mut status, WUNTRACED);
+            if pid < 0 {
+                break;
+            }
+            if wifstopped(status) {
+                stopped = true;
+                break;
+            }
+            job.set_exit_code(pid as usize, status);
+        }
+        tcsetpgrp(STDIN, self.pgid);
+        if stopped {
+            job.state = JobState::Stopped;
+            println!("\n[{}]+ Stopped\t{}", job.id, job.command);
+            return;
+        }
+        for (pid, code) in job.stages.iter() {
+            println!(
+                "Shell: Process {} exit with code {}.",
+                pid,
+                code.unwrap_or_default()
+            );
+        }
+        self.jobs.remove(idx);
+    }
+
+    /// Run the builtin command.
+    /// # Return
+    /// * false - it is not a builtin command.
+    fn builtin(&mut self, args: &[String]) -> bool {
+        match args[0].as_str() {
+            "jobs" => {
+                for job in self.jobs.iter() {
+                    let state = match job.state {
+                        JobState::Running => "Running",
+                        JobState::Stopped => "Stopped",
+                    };
+                    println!("[{}] {}\t{}", job.id, state, job.command);
                 }
-                // parent process
-                let mut exit_code = 0;
-                let exit_pid = wait_pid(pid as usize, &mut exit_code);
-                assert_eq!(pid, exit_pid);
-                println!("Shell: Process {} exit with code {}.", pid, exit_code);
-                print!(">> ");
-                line.clear();
-            }
-            BS | DL => {
-                if line.pop().is_some() {
-                    print!("{}", BS as char);
-                    print!(" ");
-                    print!("{}", BS as char);
+            }
+            "fg" | "bg" => {
+                let Some(idx) = self.find_job(args.get(1).map(String::as_str)) else {
+                    println!("{}: no such job", args[0]);
+                    return true;
+                };
+                let job = &mut self.jobs[idx];
+                job.state = JobState::Running;
+                let pgid = job.pgid;
+                if args[0] == "fg" {
+                    println!("{}", job.command);
+                    tcsetpgrp(STDIN, pgid);
+                    kill(-(pgid as isize), SIGCONT);
+                    self.wait_foreground(idx);
+                } else {
+                    println!("[{}] {} &", job.id, job.command);
+                    kill(-(pgid as isize), SIGCONT);
                 }
             }
-            32..=126 => {
-                print!("{}", c as char);
-                line.push(c as char);
+            _ => return false,
+        }
+        true
+    }
+
+    fn run_line(&mut self, line: &str) {
+        let pipeline = match parse(line) {
+            Ok(Some(pipeline)) => pipeline,
+            Ok(None) => return,
+            Err(err) => {
+                println!("Shell: {}.", err);
+                return;
             }
-            _ => {
-                print!("{}", c as char);
+        };
+        let command = line.trim().trim_end_matches('&').trim_end();
+        if let [stage] = pipeline.stages.as_slice() {
+            if stage.input.is_none()
+                && stage.output.is_none()
+                && stage.error.is_none()
+                && self.builtin(&stage.args)
+            {
+                return;
+            }
+        }
+
+        let mut pgid = 0;
+        let mut stages = Vec::new();
+        // The read end of the pipe from the previous stage.
+        let mut prev_read = None;
+        for (idx, stage) in pipeline.stages.iter().enumerate() {
+            let last = idx + 1 == pipeline.stages.len();
+            let mut pipe_fd = [0usize; 2];
+            if !last && pipe(&mut pipe_fd) != 0 {
+                println!("Error when creating pipe.");
+                break;
+            }
+            let pid = fork();
+            // child process
+            if pid == 0 {
+                // Every job runs in its own process group.
+                setpgid(0, pgid);
+                set_job_control_signals(SIG_DFL);
+                if let Some(read_end) = prev_read {
+                    redirect(read_end, STDIN);
+                }
+                if !last {
+                    close(pipe_fd[0]);
+                    redirect(pipe_fd[1], STDOUT);
+                }
+                stage.exec();
             }
+            // parent process
+            if pgid == 0 {
+                pgid = pid as usize;
+            }
+            // Set the process group here too, the child may not have run yet.
+            setpgid(pid as usize, pgid);
+            if let Some(read_end) = prev_read.take() {
+                close(read_end);
+            }
+            if !last {
+                close(pipe_fd[1]);
+                prev_read = Some(pipe_fd[0]);
+            }
+            stages.push((pid as usize, None));
+        }
+        if stages.is_empty() {
+            return;
+        }
+        let idx = self.add_job(pgid, stages, command);
+        if pipeline.background {
+            println!("[{}] {}", self.jobs[idx].id, pgid);
+        } else {
+            self.wait_foreground(idx);
+        }
+    }
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    println!("Welcom to rust shell");
+    let mut shell = Shell::new();
+    // Line editing and echo are done by the tty, we just read whole lines.
+    let mut buffer = [0u8; LINE_MAX];
+    loop {
+        shell.reap_jobs();
+        print!(">> ");
+        let len = read(STDIN, &mut buffer);
+        if len <= 0 {
+            // ^D on an empty line.
+            println!("");
+            continue;
+        }
+        match core::str::from_utf8(&buffer[..len as usize]) {
+            Ok(line) => shell.run_line(line.trim_end_matches('\n')),
+            Err(_) => println!("Invalid input."),
         }
     }
 }
@@ -23,6 +23,31 @@ static SUCC_TESTS: &[(&str, &str, &str, &str, i32)] = &[
     ("sleep_simple\0", "\0", "\0", "\0", 0),
     ("sleep\0", "\0", "\0", "\0", 0),
     ("yield\0", "\0", "\0", "\0", 0),
+    ("tty_test\0", "\0", "\0", "\0", 0),
+    ("job_control\0", "\0", "\0", "\0", 0),
+    ("priority\0", "\0", "\0", "\0", 0),
+    ("atomic_adder\0", "\0", "\0", "\0", 0),
+    ("usage_test\0", "\0", "\0", "\0", 0),
+    ("proc_test\0", "\0", "\0", "\0", 0),
+    ("vfs_test\0", "\0", "\0", "\0", 0),
+    ("tmpfs_test\0", "\0", "\0", "\0", 0),
+    ("devfs_test\0", "\0", "\0", "\0", 0),
+    ("pipe_wait_test\0", "\0", "\0", "\0", 0),
+    ("fd_flags_test\0", "\0", "\0", "\0", 0),
+    ("poll_test\0", "\0", "\0", "\0", 0),
+    ("epoll_test\0", "\0", "\0", "\0", 0),
+    ("notify_fd_test\0", "\0", "\0", "\0", 0),
+    ("fifo_test\0", "\0", "\0", "\0", 0),
+    ("unix_socket_test\0", "\0", "\0", "\0", 0),
+    ("tcp_udp_test\0", "\0", "\0", "\0", 0),
+    ("shm_test\0", "\0", "\0", "\0", 0),
+    ("mq_test\0", "\0", "\0", "\0", 0),
+    ("futex_test\0", "\0", "\0", "\0", 0),
+    ("deadlock_test\0", "\0", "\0", "\0", 0),
+    ("pi_mutex_test\0", "\0", "\0", "\0", 0),
+    ("timed_sync_test\0", "\0", "\0", "\0", 0),
+    ("rwlock_test\0", "\0", "\0", "\0", 0),
+    ("barrier_test\0", "\0", "\0", "\0", 0),
 ];
 
 static FAIL_TESTS: &[(&str, &str, &str, &str, i32)] = &[("stack_overflow\0", "\0", "\0", "\0", -2)];
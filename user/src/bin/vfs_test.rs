@@ -0,0 +1,79 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, fstat, mkdir, mount, open, read, read_dir, read_to_string, rmdir, stat, umount, unlink,
+    write, OpenFlags, Stat, EEXIST,
+};
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // Directories and files in easy-fs.
+    assert_eq!(mkdir("/vfs_dir\0"), 0);
+    assert_eq!(mkdir("/vfs_dir\0"), -1);
+    let fd = open("/vfs_dir/file\0", OpenFlags::CREATE | OpenFlags::WRITE_ONLY);
+    assert!(fd >= 0);
+    assert_eq!(write(fd as usize, b"hello vfs"), 9);
+    let mut st = Stat::default();
+    assert_eq!(fstat(fd as usize, &mut st), 0);
+    assert!(st.is_file());
+    assert_eq!(st.size, 9);
+    close(fd as usize);
+    // Opening without CREATE or TRUNC keeps the content.
+    let fd = open("vfs_dir/./file\0", OpenFlags::READ_ONLY);
+    let mut buf = [0u8; 16];
+    assert_eq!(read(fd as usize, &mut buf), 9);
+    assert_eq!(&buf[..9], b"hello vfs");
+    close(fd as usize);
+    let flags = OpenFlags::CREATE | OpenFlags::EXCL | OpenFlags::WRITE_ONLY;
+    assert_eq!(open("/vfs_dir/file\0", flags), -EEXIST);
+    // Opening an existing file with CREATE truncates it.
+    let fd = open("/vfs_dir/file\0", OpenFlags::CREATE | OpenFlags::WRITE_ONLY);
+    assert_eq!(fstat(fd as usize, &mut st), 0);
+    assert_eq!(st.size, 0);
+    assert_eq!(write(fd as usize, b"hello vfs"), 9);
+    close(fd as usize);
+    // A directory can not be opened for writing.
+    assert_eq!(open("/vfs_dir\0", OpenFlags::WRITE_ONLY), -1);
+    assert_eq!(stat("/vfs_dir/../vfs_dir\0", &mut st), 0);
+    assert!(st.is_dir());
+    assert_eq!(read_dir("/vfs_dir\0").unwrap(), ["file"]);
+
+    // Only empty directories can be removed, and the type must match.
+    assert_eq!(rmdir("/vfs_dir\0"), -1);
+    assert_eq!(unlink("/vfs_dir\0"), -1);
+    assert_eq!(rmdir("/vfs_dir/file\0"), -1);
+    // A removed file is still readable through an fd opened before.
+    let fd = open("/vfs_dir/file\0", OpenFlags::READ_ONLY);
+    assert_eq!(unlink("/vfs_dir/file\0"), 0);
+    assert_eq!(stat("/vfs_dir/file\0", &mut st), -1);
+    assert!(read_dir("/vfs_dir\0").unwrap().is_empty());
+    assert_eq!(read(fd as usize, &mut buf), 9);
+    assert_eq!(&buf[..9], b"hello vfs");
+    close(fd as usize);
+
+    // Mount procfs on the directory, paths under it cross the mount point.
+    assert_eq!(mount("nofs\0", "/vfs_dir\0", "\0"), -1);
+    assert_eq!(mount("proc\0", "/vfs_dir/missing\0", "\0"), -1);
+    assert_eq!(mount("proc\0", "/vfs_dir\0", "\0"), 0);
+    assert_eq!(mount("proc\0", "/vfs_dir\0", "\0"), -1);
+    assert!(read_to_string("/vfs_dir/uptime\0").is_some());
+    let mounts = read_to_string("/proc/mounts\0").unwrap();
+    assert!(mounts.lines().any(|line| line.starts_with("easy-fs / ")));
+    assert!(mounts
+        .lines()
+        .any(|line| line.starts_with("proc /vfs_dir ")));
+    // A mount point can not be removed, and "/" can not be unmounted.
+    assert_eq!(rmdir("/vfs_dir\0"), -1);
+    assert_eq!(umount("/\0"), -1);
+    assert_eq!(umount("/vfs_dir\0"), 0);
+    assert_eq!(umount("/vfs_dir\0"), -1);
+    assert_eq!(stat("/vfs_dir/uptime\0", &mut st), -1);
+    assert_eq!(rmdir("/vfs_dir\0"), 0);
+    assert_eq!(stat("/vfs_dir\0", &mut st), -1);
+    println!("vfs_test passed!");
+    0
+}
@@ -6,6 +6,7 @@
 #[macro_use]
 pub mod console;
 mod lang_runtimes;
+pub mod sync;
 mod sys_call;
 
 extern crate alloc;
@@ -13,7 +14,9 @@ extern crate alloc;
 extern crate bitflags;
 
 use crate::sys_call::*;
-use alloc::vec::Vec;
+use alloc::{boxed::Box, string::String, vec::Vec};
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use sync::{Condvar, Mutex, Semaphore};
 use buddy_system_allocator::LockedHeap;
 
 const USER_HEAP_SIZE: usize = 0x4000;
@@ -66,15 +69,122 @@ bitflags! {
         const READ_ONLY = 0;
         const WRITE_ONLY = 1 << 0;
         const READ_WRITE = 1 << 1;
+        /// With CREATE, fail if it exists.
+        const EXCL = 1 << 7;
+        /// Create the file if it does not exist, an existing file is not truncated without TRUNC.
         const CREATE = 1 << 9;
         const TRUNC = 1 << 10;
+        const NONBLOCK = 1 << 11;
+        const APPEND = 1 << 12;
+        const CLOEXEC = 1 << 19;
     }
 }
 
+/// Operation not permitted.
+pub const EPERM: isize = 1;
+/// No such file or directory, or no such entry.
+pub const ENOENT: isize = 2;
+/// Interrupted system call.
+pub const EINTR: isize = 4;
+/// Input/output error, such as reading the terminal from the background ignoring SIGTTIN.
+pub const EIO: isize = 5;
+/// No such device or address, such as a named pipe without readers.
+pub const ENXIO: isize = 6;
+/// Bad file descriptor.
+pub const EBADF: isize = 9;
+/// Try again, the operation would block.
+pub const EAGAIN: isize = 11;
+/// Out of memory.
+pub const ENOMEM: isize = 12;
+/// Permission denied by the mode.
+pub const EACCES: isize = 13;
+/// Device or resource busy, such as a locked mutex.
+pub const EBUSY: isize = 16;
+/// The entry already exists.
+pub const EEXIST: isize = 17;
+/// Invalid argument.
+pub const EINVAL: isize = 22;
+/// Too many open files.
+pub const EMFILE: isize = 24;
+/// No space left, such as no free id of a shared memory segment.
+pub const ENOSPC: isize = 28;
+/// Broken pipe.
+pub const EPIPE: isize = 32;
+/// The request of a lock would deadlock.
+pub const EDEADLK: isize = 35;
+/// File name too long.
+pub const ENAMETOOLONG: isize = 36;
+/// Function not implemented.
+pub const ENOSYS: isize = 38;
+/// The fd is not a socket.
+pub const ENOTSOCK: isize = 88;
+/// A datagram socket is not connected and no destination is given.
+pub const EDESTADDRREQ: isize = 89;
+/// Message too long for a datagram socket.
+pub const EMSGSIZE: isize = 90;
+/// Wrong socket type for the operation.
+pub const EPROTOTYPE: isize = 91;
+/// Operation not supported by the socket.
+pub const EOPNOTSUPP: isize = 95;
+/// Address family not supported.
+pub const EAFNOSUPPORT: isize = 97;
+/// Address already in use.
+pub const EADDRINUSE: isize = 98;
+/// The address is not an address of the machine.
+pub const EADDRNOTAVAIL: isize = 99;
+/// The socket is already connected.
+pub const EISCONN: isize = 106;
+/// The socket is not connected.
+pub const ENOTCONN: isize = 107;
+/// A timed wait reached its deadline.
+pub const ETIMEDOUT: isize = 110;
+/// Connection refused, no socket is listening on the address.
+pub const ECONNREFUSED: isize = 111;
+/// A non-blocking connect is still in progress.
+pub const EALREADY: isize = 114;
+/// A non-blocking connect is started.
+pub const EINPROGRESS: isize = 115;
+
 /// Create a copy of the opened file by fd
 pub fn dup(fd: usize) -> isize {
     sys_dup(fd)
 }
+/// Make new_fd a copy of old_fd, the file opened on new_fd is closed first.
+/// Nothing is done if they are the same valid fd.
+pub fn dup2(old_fd: usize, new_fd: usize) -> isize {
+    if old_fd == new_fd {
+        return if fcntl(old_fd, F_GETFD, 0) < 0 {
+            -EBADF
+        } else {
+            new_fd as isize
+        };
+    }
+    sys_dup3(old_fd, new_fd, 0)
+}
+/// dup2 with flags, only OpenFlags::CLOEXEC is supported.
+pub fn dup3(old_fd: usize, new_fd: usize, flags: OpenFlags) -> isize {
+    sys_dup3(old_fd, new_fd, flags.bits())
+}
+
+/// Duplicate the fd to the lowest free fd not less than arg.
+pub const F_DUPFD: usize = 0;
+/// Get the fd flags, FD_CLOEXEC.
+pub const F_GETFD: usize = 1;
+/// Set the fd flags.
+pub const F_SETFD: usize = 2;
+/// Get the access mode and status flags.
+pub const F_GETFL: usize = 3;
+/// Set the status flags, only OpenFlags::NONBLOCK can be changed.
+pub const F_SETFL: usize = 4;
+/// F_DUPFD with FD_CLOEXEC set on the new fd.
+pub const F_DUPFD_CLOEXEC: usize = 1030;
+/// Close the fd when a new program is executed.
+pub const FD_CLOEXEC: usize = 1;
+
+/// Control the fd or the open file by cmd.
+pub fn fcntl(fd: usize, cmd: usize, arg: usize) -> isize {
+    sys_fcntl(fd, cmd, arg)
+}
 /// Open a file with flags
 pub fn open(path: &str, flags: OpenFlags) -> isize {
     sys_open(path, flags.bits())
@@ -88,7 +198,568 @@ pub fn close(fd: usize) -> isize {
 /// * 'pipe_fd' - The address of a usize array with a length of 2.
 ///               The kernel will write the file descriptors of the read and write ends of the pipe into the array.
 pub fn pipe(pipe_fd: &mut [usize]) -> isize {
-    sys_pipe(pipe_fd)
+    sys_pipe(pipe_fd, 0)
+}
+/// Open a pipe with flags, OpenFlags::NONBLOCK and OpenFlags::CLOEXEC are supported.
+pub fn pipe2(pipe_fd: &mut [usize], flags: OpenFlags) -> isize {
+    sys_pipe(pipe_fd, flags.bits())
+}
+
+/// Time in seconds and nanoseconds, the same layout as linux struct timespec.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TimeSpec {
+    pub sec: usize,
+    pub nsec: usize,
+}
+
+impl TimeSpec {
+    pub fn from_ms(ms: usize) -> Self {
+        Self {
+            sec: ms / 1000,
+            nsec: ms % 1000 * 1_000_000,
+        }
+    }
+
+    /// Convert to milliseconds, rounded up.
+    pub fn to_ms(&self) -> usize {
+        self.sec * 1000 + (self.nsec + 999_999) / 1_000_000
+    }
+}
+
+bitflags! {
+    /// Events of poll.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct PollEvents: i16 {
+        /// There is data to read.
+        const POLLIN = 0x1;
+        /// There is urgent data to read.
+        const POLLPRI = 0x2;
+        /// Writing will not block.
+        const POLLOUT = 0x4;
+        /// Error condition, always reported.
+        const POLLERR = 0x8;
+        /// Hang up, always reported.
+        const POLLHUP = 0x10;
+        /// The fd is not open, always reported.
+        const POLLNVAL = 0x20;
+    }
+}
+
+/// An fd and the events to wait for, the same layout as linux struct pollfd.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PollFd {
+    pub fd: i32,
+    pub events: i16,
+    pub revents: i16,
+}
+
+impl PollFd {
+    /// Wait for events of fd, a negative fd is ignored.
+    pub fn new(fd: i32, events: PollEvents) -> Self {
+        Self {
+            fd,
+            events: events.bits(),
+            revents: 0,
+        }
+    }
+    /// The events that happened, filled by poll.
+    pub fn revents(&self) -> PollEvents {
+        PollEvents::from_bits_truncate(self.revents)
+    }
+}
+
+/// Wait until any of the fds is ready.
+/// # Parameter
+/// * 'timeout' - None waits forever
+/// # Return
+/// * The number of fds with events, 0 if the timeout expired.
+/// * -EINTR - interrupted by a signal.
+pub fn ppoll(fds: &mut [PollFd], timeout: Option<&TimeSpec>) -> isize {
+    sys_ppoll(fds, timeout)
+}
+/// ppoll with the timeout in milliseconds, a negative timeout waits forever.
+pub fn poll(fds: &mut [PollFd], timeout_ms: isize) -> isize {
+    if timeout_ms < 0 {
+        sys_ppoll(fds, None)
+    } else {
+        sys_ppoll(fds, Some(&TimeSpec::from_ms(timeout_ms as usize)))
+    }
+}
+
+bitflags! {
+    /// Events and flags of epoll.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct EpollEvents: u32 {
+        const EPOLLIN = 0x1;
+        const EPOLLPRI = 0x2;
+        const EPOLLOUT = 0x4;
+        const EPOLLERR = 0x8;
+        const EPOLLHUP = 0x10;
+        /// Report the fd only once, until it is modified by EPOLL_CTL_MOD.
+        const EPOLLONESHOT = 1 << 30;
+        /// Edge triggered, report the fd once every time it becomes ready.
+        const EPOLLET = 1 << 31;
+    }
+}
+
+/// An event of epoll, the same layout as linux struct epoll_event on riscv64.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct EpollEvent {
+    pub events: u32,
+    /// Returned as it is, usually the fd.
+    pub data: u64,
+}
+
+impl EpollEvent {
+    pub fn new(events: EpollEvents, data: u64) -> Self {
+        Self {
+            events: events.bits(),
+            data,
+        }
+    }
+    pub fn events(&self) -> EpollEvents {
+        EpollEvents::from_bits_truncate(self.events)
+    }
+}
+
+/// Add a fd to the interest list.
+pub const EPOLL_CTL_ADD: usize = 1;
+/// Remove a fd from the interest list.
+pub const EPOLL_CTL_DEL: usize = 2;
+/// Change the events of a fd in the interest list.
+pub const EPOLL_CTL_MOD: usize = 3;
+
+/// Create an epoll instance, only OpenFlags::CLOEXEC is supported.
+pub fn epoll_create1(flags: OpenFlags) -> isize {
+    sys_epoll_create1(flags.bits())
+}
+/// Add, modify or remove a fd in the interest list of the epoll instance.
+pub fn epoll_ctl(epfd: usize, op: usize, fd: usize, event: &EpollEvent) -> isize {
+    sys_epoll_ctl(epfd, op, fd, event)
+}
+/// Wait for events, a negative timeout waits forever.
+/// # Return
+/// * The number of events filled, 0 if the timeout expired.
+pub fn epoll_wait(epfd: usize, events: &mut [EpollEvent], timeout_ms: isize) -> isize {
+    sys_epoll_pwait(epfd, events, timeout_ms)
+}
+
+/// The max number of fds in a FdSet.
+pub const FD_SETSIZE: usize = 1024;
+
+/// A set of fds for pselect, the same layout as linux fd_set.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FdSet {
+    bits: [usize; FD_SETSIZE / usize::BITS as usize],
+}
+
+impl FdSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn set(&mut self, fd: usize) {
+        self.bits[fd / usize::BITS as usize] |= 1 << (fd % usize::BITS as usize);
+    }
+    pub fn clear(&mut self, fd: usize) {
+        self.bits[fd / usize::BITS as usize] &= !(1 << (fd % usize::BITS as usize));
+    }
+    pub fn is_set(&self, fd: usize) -> bool {
+        self.bits[fd / usize::BITS as usize] & (1 << (fd % usize::BITS as usize)) != 0
+    }
+}
+
+/// Wait until any of the fds in the sets is ready, only the ready fds are left in the sets.
+/// # Parameter
+/// * 'nfds' - the highest fd in the sets plus one
+/// * 'timeout' - None waits forever
+/// # Return
+/// * The number of ready fds in all sets, 0 if the timeout expired.
+/// * -EBADF - a fd in the sets is not open.
+/// * -EINTR - interrupted by a signal.
+pub fn pselect(
+    nfds: usize,
+    read_fds: Option<&mut FdSet>,
+    write_fds: Option<&mut FdSet>,
+    except_fds: Option<&mut FdSet>,
+    timeout: Option<&TimeSpec>,
+) -> isize {
+    sys_pselect6(nfds, read_fds, write_fds, except_fds, timeout)
+}
+
+bitflags! {
+    /// Flags of eventfd.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct EventfdFlags: u32 {
+        /// A read decreases the counter by 1 instead of resetting it.
+        const EFD_SEMAPHORE = 1;
+        const EFD_NONBLOCK = 1 << 11;
+        const EFD_CLOEXEC = 1 << 19;
+    }
+}
+
+/// Create an eventfd, a counter which is increased by writes and taken by reads.
+pub fn eventfd(initval: u32, flags: EventfdFlags) -> isize {
+    sys_eventfd2(initval, flags.bits())
+}
+/// Read the counter of the eventfd, wait until it is not 0.
+pub fn eventfd_read(fd: usize, value: &mut u64) -> isize {
+    let mut buf = [0u8; 8];
+    let ret = sys_read(fd, &mut buf);
+    *value = u64::from_ne_bytes(buf);
+    ret
+}
+/// Add the value to the counter of the eventfd.
+pub fn eventfd_write(fd: usize, value: u64) -> isize {
+    sys_write(fd, &value.to_ne_bytes())
+}
+
+bitflags! {
+    /// Flags of timerfd_create.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct TimerfdFlags: u32 {
+        const TFD_NONBLOCK = 1 << 11;
+        const TFD_CLOEXEC = 1 << 19;
+    }
+}
+
+/// Flag of timerfd_settime, the value of the setting is the time since the boot.
+pub const TFD_TIMER_ABSTIME: u32 = 1;
+/// Clocks of timerfd_create, both count from the boot.
+pub const CLOCK_REALTIME: usize = 0;
+pub const CLOCK_MONOTONIC: usize = 1;
+
+/// The setting of a timer, the same layout as linux struct itimerspec.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ITimerSpec {
+    /// The period of the timer, zero for a one shot timer.
+    pub interval: TimeSpec,
+    /// The time until the next expiration, zero disarms the timer.
+    pub value: TimeSpec,
+}
+
+/// Create a disarmed timerfd, a read returns the number of expirations as u64.
+pub fn timerfd_create(clockid: usize, flags: TimerfdFlags) -> isize {
+    sys_timerfd_create(clockid, flags.bits())
+}
+/// Arm or disarm the timerfd, the old setting is filled if old_value is given.
+pub fn timerfd_settime(
+    fd: usize,
+    flags: u32,
+    new_value: &ITimerSpec,
+    old_value: Option<&mut ITimerSpec>,
+) -> isize {
+    sys_timerfd_settime(fd, flags, new_value, old_value)
+}
+/// Get the time until the next expiration and the interval.
+pub fn timerfd_gettime(fd: usize, curr_value: &mut ITimerSpec) -> isize {
+    sys_timerfd_gettime(fd, curr_value)
+}
+
+bitflags! {
+    /// Flags of signalfd.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct SignalfdFlags: u32 {
+        const SFD_NONBLOCK = 1 << 11;
+        const SFD_CLOEXEC = 1 << 19;
+    }
+}
+
+/// A signal read from a signalfd, the same layout as linux struct signalfd_siginfo.
+/// Only signo is filled by the kernel.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SignalfdSiginfo {
+    pub signo: u32,
+    pub errno: i32,
+    pub code: i32,
+    pad: [u32; 29],
+}
+
+/// Create a signalfd if fd is -1, or change the mask of the signalfd.
+/// A read takes the pending signals in the mask, they are not handled by their default actions.
+pub fn signalfd(fd: isize, mask: SignalFlags, flags: SignalfdFlags) -> isize {
+    // Bit (signum - 1) of linux sigset_t is the signal signum.
+    sys_signalfd4(fd, (mask.bits() >> 1) as u64, flags.bits())
+}
+/// Read pending signals from the signalfd, wait until there is one.
+/// # Return
+/// * The number of bytes read, a multiple of the size of SignalfdSiginfo.
+pub fn signalfd_read(fd: usize, infos: &mut [SignalfdSiginfo]) -> isize {
+    let buf = unsafe {
+        core::slice::from_raw_parts_mut(
+            infos.as_mut_ptr() as *mut u8,
+            core::mem::size_of_val(infos),
+        )
+    };
+    sys_read(fd, buf)
+}
+
+/// dirfd of the *at syscalls, paths are relative to the current directory.
+pub const AT_FDCWD: isize = -100;
+/// Remove a directory in unlinkat.
+pub const AT_REMOVEDIR: u32 = 0x200;
+/// Mask of the file type bits in Stat::mode.
+pub const S_IFMT: u32 = 0o170000;
+/// Directory.
+pub const S_IFDIR: u32 = 0o040000;
+/// Regular file.
+pub const S_IFREG: u32 = 0o100000;
+/// Character device.
+pub const S_IFCHR: u32 = 0o020000;
+/// Block device.
+pub const S_IFBLK: u32 = 0o060000;
+/// Named pipe.
+pub const S_IFIFO: u32 = 0o010000;
+/// Socket.
+pub const S_IFSOCK: u32 = 0o140000;
+
+/// File status, the same layout as linux struct stat on riscv64.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Stat {
+    /// Id of the file system.
+    pub dev: u64,
+    /// Inode number, unique in the file system.
+    pub ino: u64,
+    /// File type and permission.
+    pub mode: u32,
+    pub nlink: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub rdev: u64,
+    __pad: u64,
+    /// File size in bytes.
+    pub size: i64,
+    pub blksize: u32,
+    __pad2: i32,
+    pub blocks: u64,
+    pub atime_sec: i64,
+    pub atime_nsec: i64,
+    pub mtime_sec: i64,
+    pub mtime_nsec: i64,
+    pub ctime_sec: i64,
+    pub ctime_nsec: i64,
+    __unused: [u32; 2],
+}
+
+impl Stat {
+    pub fn is_dir(&self) -> bool {
+        self.mode & S_IFMT == S_IFDIR
+    }
+    pub fn is_file(&self) -> bool {
+        self.mode & S_IFMT == S_IFREG
+    }
+    pub fn is_char_device(&self) -> bool {
+        self.mode & S_IFMT == S_IFCHR
+    }
+    pub fn is_block_device(&self) -> bool {
+        self.mode & S_IFMT == S_IFBLK
+    }
+    pub fn is_fifo(&self) -> bool {
+        self.mode & S_IFMT == S_IFIFO
+    }
+    pub fn is_socket(&self) -> bool {
+        self.mode & S_IFMT == S_IFSOCK
+    }
+}
+
+/// Get the status of a file by path, end with '\0'.
+pub fn stat(path: &str, stat: &mut Stat) -> isize {
+    sys_newfstatat(AT_FDCWD, path, stat, 0)
+}
+/// Get the status of an opened file.
+pub fn fstat(fd: usize, stat: &mut Stat) -> isize {
+    sys_fstat(fd, stat)
+}
+/// Create a directory, the path ends with '\0'.
+/// # Return
+/// * -1 - the parent directory does not exist or the name exists.
+pub fn mkdir(path: &str) -> isize {
+    sys_mkdirat(AT_FDCWD, path, 0o755)
+}
+/// Create a named pipe, the path ends with '\0'.
+/// Opening it for reading waits for a writer and opening it for writing waits for a reader,
+/// unless OpenFlags::NONBLOCK is given.
+/// # Return
+/// * -1 - the parent directory does not exist or the name exists.
+pub fn mkfifo(path: &str, mode: u32) -> isize {
+    sys_mknodat(AT_FDCWD, path, S_IFIFO | mode, 0)
+}
+/// Change the size of a file, the path ends with '\0'.
+pub fn truncate(path: &str, length: usize) -> isize {
+    sys_truncate(path, length)
+}
+/// Change the size of a file opened for writing.
+pub fn ftruncate(fd: usize, length: usize) -> isize {
+    sys_ftruncate(fd, length)
+}
+/// Remove a file, the path ends with '\0'.
+pub fn unlink(path: &str) -> isize {
+    sys_unlinkat(AT_FDCWD, path, 0)
+}
+/// Remove an empty directory, the path ends with '\0'.
+pub fn rmdir(path: &str) -> isize {
+    sys_unlinkat(AT_FDCWD, path, AT_REMOVEDIR)
+}
+/// Mount a file system on an existing directory.
+/// # Parameter
+/// * 'fstype' - "easy-fs", "proc", "devfs" or "tmpfs", end with '\0'.
+/// * 'target' - the directory, end with '\0'.
+/// * 'data' - options, such as "size=64k" of tmpfs, end with '\0'.
+pub fn mount(fstype: &str, target: &str, data: &str) -> isize {
+    sys_mount("none\0", target, fstype, 0, data)
+}
+/// Unmount the file system mounted on the target, end with '\0'.
+pub fn umount(target: &str) -> isize {
+    sys_umount2(target, 0)
+}
+
+/// Read entries of an opened directory into buf, in the layout of linux struct dirent64.
+/// # Return
+/// * -1 - bad fd, not a directory, or buf is too small for an entry.
+/// * 0 - no more entries.
+/// * The number of bytes read.
+pub fn getdents(fd: usize, buf: &mut [u8]) -> isize {
+    sys_getdents64(fd, buf)
+}
+/// Get the names of all entries in the directory.
+/// # Parameter
+/// * 'path' - the directory path, end with '\0'.
+/// # Return
+/// * None - the directory can not be opened.
+pub fn read_dir(path: &str) -> Option<Vec<String>> {
+    let fd = open(path, OpenFlags::READ_ONLY);
+    if fd < 0 {
+        return None;
+    }
+    let fd = fd as usize;
+    let mut names = Vec::new();
+    let mut buf = [0u8; 512];
+    loop {
+        let len = getdents(fd, &mut buf);
+        if len <= 0 {
+            close(fd);
+            return if len == 0 { Some(names) } else { None };
+        }
+        let mut pos = 0;
+        while pos < len as usize {
+            // d_ino: u64, d_off: i64, d_reclen: u16, d_type: u8, d_name
+            let reclen = u16::from_ne_bytes([buf[pos + 16], buf[pos + 17]]) as usize;
+            let name = &buf[pos + 19..pos + reclen];
+            let name_len = name.iter().position(|c| *c == 0).unwrap_or(name.len());
+            names.push(String::from(
+                core::str::from_utf8(&name[..name_len]).unwrap(),
+            ));
+            pos += reclen;
+        }
+    }
+}
+/// Read the whole file.
+/// # Parameter
+/// * 'path' - the file path, end with '\0'.
+/// # Return
+/// * None - the file can not be opened.
+pub fn read_to_string(path: &str) -> Option<String> {
+    let fd = open(path, OpenFlags::READ_ONLY);
+    if fd < 0 {
+        return None;
+    }
+    let mut data = Vec::new();
+    let mut buf = [0u8; 256];
+    loop {
+        let len = read(fd as usize, &mut buf);
+        if len <= 0 {
+            break;
+        }
+        data.extend_from_slice(&buf[..len as usize]);
+    }
+    close(fd as usize);
+    String::from_utf8(data).ok()
+}
+
+/// Device specific control of an open file.
+/// # Return
+/// * -1 - bad fd or the request is not supported by the file.
+pub fn ioctl(fd: usize, cmd: usize, arg: usize) -> isize {
+    sys_ioctl(fd, cmd, arg)
+}
+
+pub const TCGETS: usize = 0x5401;
+pub const TCSETS: usize = 0x5402;
+pub const TIOCGPGRP: usize = 0x540F;
+pub const TIOCSPGRP: usize = 0x5410;
+pub const TIOCSCTTY: usize = 0x540E;
+pub const TIOCNOTTY: usize = 0x5422;
+
+pub const NCCS: usize = 19;
+pub const VINTR: usize = 0;
+pub const VQUIT: usize = 1;
+pub const VERASE: usize = 2;
+pub const VKILL: usize = 3;
+pub const VEOF: usize = 4;
+pub const VMIN: usize = 6;
+pub const VSUSP: usize = 10;
+
+/// Translate carriage return to newline on input.
+pub const ICRNL: u32 = 0o400;
+/// Generate signals for INTR, QUIT and SUSP characters.
+pub const ISIG: u32 = 0o1;
+/// Canonical mode, input is made available line by line.
+pub const ICANON: u32 = 0o2;
+/// Echo input characters.
+pub const ECHO: u32 = 0o10;
+/// ERASE character erases the preceding character on screen.
+pub const ECHOE: u32 = 0o20;
+/// KILL character erases the current line on screen.
+pub const ECHOK: u32 = 0o40;
+
+/// Terminal attributes.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Termios {
+    pub iflag: u32,
+    pub oflag: u32,
+    pub cflag: u32,
+    pub lflag: u32,
+    pub line: u8,
+    pub cc: [u8; NCCS],
+}
+
+/// Get the attributes of the terminal.
+pub fn tcgetattr(fd: usize, termios: &mut Termios) -> isize {
+    sys_ioctl(fd, TCGETS, termios as *mut Termios as usize)
+}
+/// Set the attributes of the terminal.
+pub fn tcsetattr(fd: usize, termios: &Termios) -> isize {
+    sys_ioctl(fd, TCSETS, termios as *const Termios as usize)
+}
+/// Get the foreground process group of the terminal.
+/// # Return
+/// * -1 - if fd is not a terminal.
+/// * The foreground process group, 0 means no foreground process.
+pub fn tcgetpgrp(fd: usize) -> isize {
+    let mut pgid: i32 = 0;
+    match sys_ioctl(fd, TIOCGPGRP, &mut pgid as *mut i32 as usize) {
+        0 => pgid as isize,
+        err => err,
+    }
+}
+/// Set the foreground process group of the terminal,
+/// which will receive SIGINT (^C) and SIGTSTP (^Z).
+/// Only a process group in the session of the terminal can be the foreground.
+pub fn tcsetpgrp(fd: usize, pgid: usize) -> isize {
+    let pgid = pgid as i32;
+    sys_ioctl(fd, TIOCSPGRP, &pgid as *const i32 as usize)
+}
+/// Make the terminal the controlling terminal of the current session,
+/// the current process must be the session leader.
+pub fn tcsetctty(fd: usize) -> isize {
+    sys_ioctl(fd, TIOCSCTTY, 0)
 }
 
 /// Read a piece of content from the file into the buffer.
@@ -148,14 +819,7 @@ pub fn exec(path: &str, args: &[*const u8]) -> isize {
 /// * -2, If all child processes have not ended.
 /// * pid, The pid of the child process that ended.
 pub fn wait(exit_code: &mut i32) -> isize {
-    loop {
-        match sys_waitpid(-1, exit_code as *mut i32) {
-            -2 => {
-                yield_();
-            }
-            exit_pid => return exit_pid,
-        }
-    }
+    waitpid(-1, exit_code, 0)
 }
 /// Wait for specific child process that becomes a zombie process, recycle resources and collect return values.
 /// # Parameter
@@ -166,15 +830,112 @@ pub fn wait(exit_code: &mut i32) -> isize {
 /// * -2, If all child processes have not ended.
 /// * pid, The pid of the child process that ended.
 pub fn wait_pid(pid: usize, exit_code: &mut i32) -> isize {
+    waitpid(pid as isize, exit_code, 0)
+}
+
+/// Return immediately if no child has changed state.
+pub const WNOHANG: u32 = 1;
+/// Also report stopped children.
+pub const WUNTRACED: u32 = 2;
+/// Also report children continued by SIGCONT.
+pub const WCONTINUED: u32 = 8;
+
+/// Wait for child processes to change state.
+/// # Parameter
+/// * 'pid' - The pid of the child process, -1 for any child,
+///           0 for any child in the current process group, -pgid for any child in the process group.
+/// * 'status' - exit code of the ended child, or the stop/continue status, see wifstopped/wifcontinued.
+/// * 'options' - WNOHANG, WUNTRACED and WCONTINUED.
+/// # Return
+/// * -1, If there is no such child process.
+/// * -2, If WNOHANG is set and no child has changed state.
+/// * pid, The pid of the child process that changed state.
+pub fn waitpid(pid: isize, status: &mut i32, options: u32) -> isize {
     loop {
-        match sys_waitpid(pid as isize, exit_code as *mut i32) {
-            -2 => {
+        match sys_waitpid(pid, status as *mut i32, options & !WNOHANG) {
+            -2 if options & WNOHANG == 0 => {
                 yield_();
             }
-            exit_pid => return exit_pid,
+            ret => return ret,
         }
     }
 }
+/// Whether the child is stopped, only reported with WUNTRACED.
+pub fn wifstopped(status: i32) -> bool {
+    status & 0xff == 0x7f && (status >> 8) & 0xff != 0
+}
+/// The signal that stopped the child.
+pub fn wstopsig(status: i32) -> u32 {
+    ((status >> 8) & 0xff) as u32
+}
+/// Whether the child is continued, only reported with WCONTINUED.
+pub fn wifcontinued(status: i32) -> bool {
+    status == 0xffff
+}
+/// Set the process group of the current process (pid 0) or a child process.
+/// # Parameter
+/// * 'pgid' - 0 means a new process group whose id is pid.
+pub fn setpgid(pid: usize, pgid: usize) -> isize {
+    sys_setpgid(pid, pgid)
+}
+/// Get the process group of a process, pid 0 means the current process.
+pub fn getpgid(pid: usize) -> isize {
+    sys_getpgid(pid)
+}
+/// Get the session of a process, pid 0 means the current process.
+pub fn getsid(pid: usize) -> isize {
+    sys_getsid(pid)
+}
+/// Create a new session without controlling terminal.
+/// # Return
+/// * -1 - the current process is a process group leader.
+/// * The new session id.
+pub fn setsid() -> isize {
+    sys_setsid()
+}
+/// setpriority/getpriority target: a process.
+pub const PRIO_PROCESS: usize = 0;
+/// setpriority/getpriority target: a process group.
+pub const PRIO_PGRP: usize = 1;
+/// setpriority/getpriority target: a thread of the current process.
+pub const PRIO_THREAD: usize = 3;
+/// The highest priority.
+pub const NICE_MIN: isize = -20;
+/// The lowest priority.
+pub const NICE_MAX: isize = 19;
+
+/// Set the nice value of all threads of the processes, or of a thread,
+/// lower nice means more cpu time.
+/// # Parameter
+/// * 'which' - PRIO_PROCESS, PRIO_PGRP or PRIO_THREAD.
+/// * 'who' - pid or pgid, 0 means the current process or process group, or tid for PRIO_THREAD.
+/// * 'nice' - NICE_MIN..=NICE_MAX, out of range value is clamped.
+/// # Return
+/// * -EPERM - a process is not the current process or one of its descendants.
+pub fn setpriority(which: usize, who: usize, nice: isize) -> isize {
+    sys_setpriority(which, who, nice)
+}
+/// Get the lowest nice value of the processes, or of a thread.
+/// A thread holding a priority inheritance mutex runs at the priority of the threads waiting on it.
+/// # Return
+/// * None - if no process found.
+pub fn getpriority(which: usize, who: usize) -> Option<isize> {
+    match sys_getpriority(which, who) {
+        -1 => None,
+        prio => Some(20 - prio),
+    }
+}
+/// Add inc to the nice value of the current process.
+/// # Return
+/// * The new nice value, or None if error.
+pub fn nice(inc: isize) -> Option<isize> {
+    let nice = getpriority(PRIO_PROCESS, 0)? + inc;
+    if setpriority(PRIO_PROCESS, 0, nice) != 0 {
+        return None;
+    }
+    getpriority(PRIO_PROCESS, 0)
+}
+
 /// Create a child process and run the specified program.
 /// # Parameter
 /// * 'path' - Path to executable file.
@@ -271,12 +1032,21 @@ impl SignalAction {
     }
 }
 
-/// Send a signal to a process.
-pub fn kill(pid: usize, signum: u32) -> isize {
+/// Send a signal to a process, or a process group if pid is negative.
+/// # Return
+/// * -EPERM - the processes are all in other sessions.
+pub fn kill(pid: isize, signum: u32) -> isize {
     sys_kill(pid, signum)
 }
 
-/// Set signal handling function for the current process.
+/// Default signal handling.
+pub const SIG_DFL: usize = 0;
+/// Ignore the signal.
+pub const SIG_IGN: usize = 1;
+
+/// Set signal handling of the current process, only SIG_DFL and SIG_IGN are supported.
+/// # Return
+/// * -ENOSYS - the handler is a function.
 pub fn sigaction(
     signum: u32,
     action: Option<&SignalAction>,
@@ -317,37 +1087,1047 @@ pub fn waittid(tid: usize) -> isize {
         }
     }
 }
-/// Create a block mutex lock
-pub fn mutex_blocking_create() -> usize {
-    sys_mutex_create(true) as usize
+/// The max number of mutexes, semaphores and condvars of a process at a time,
+/// each kind has its own ids. The objects are in the memory of the process,
+/// an id is freed by the destroy function of its kind and reused by the next create.
+/// A forked child inherits copies of them in the state at the fork, not shared with the parent,
+/// so a mutex held by another thread of the parent stays locked in the child.
+/// The create functions return isize instead of usize as the kernel_* ones do,
+/// callers check for a negative errno before using the id.
+const SYNC_MAX: usize = 64;
+
+/// The objects of a kind, an object is used only while its id is allocated.
+struct SyncTable<T> {
+    objects: [T; SYNC_MAX],
+    allocated: [AtomicBool; SYNC_MAX],
+}
+
+impl<T> SyncTable<T> {
+    const fn new(objects: [T; SYNC_MAX]) -> Self {
+        Self {
+            objects,
+            allocated: [const { AtomicBool::new(false) }; SYNC_MAX],
+        }
+    }
+
+    /// Allocate the lowest free id and initialize its object.
+    /// # Return
+    /// * The id.
+    /// * -EAGAIN - all SYNC_MAX ids are allocated.
+    fn create(&self, init: impl FnOnce(&T)) -> isize {
+        let id = self.allocated.iter().position(|allocated| {
+            allocated
+                .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+                .is_ok()
+        });
+        match id {
+            Some(id) => {
+                init(&self.objects[id]);
+                id as isize
+            }
+            None => -EAGAIN,
+        }
+    }
+
+    /// Free the id, its object must not be used any more.
+    /// # Return
+    /// * -EINVAL - the id is not allocated.
+    fn destroy(&self, id: usize) -> isize {
+        match self.allocated.get(id) {
+            Some(allocated) if allocated.swap(false, Ordering::Release) => 0,
+            _ => -EINVAL,
+        }
+    }
+
+    /// Call f with the object of the id.
+    /// # Return
+    /// * The value returned by f.
+    /// * -EINVAL - the id is not allocated.
+    fn with(&self, id: usize, f: impl FnOnce(&T) -> isize) -> isize {
+        match self.allocated.get(id) {
+            Some(allocated) if allocated.load(Ordering::Acquire) => f(&self.objects[id]),
+            _ => -EINVAL,
+        }
+    }
+}
+
+static MUTEXES: SyncTable<Mutex> = SyncTable::new([const { Mutex::new() }; SYNC_MAX]);
+static SEMAPHORES: SyncTable<Semaphore> = SyncTable::new([const { Semaphore::new(0) }; SYNC_MAX]);
+static CONDVARS: SyncTable<Condvar> = SyncTable::new([const { Condvar::new() }; SYNC_MAX]);
+
+/// Create a block mutex lock, it waits on a futex when it is locked by others.
+/// # Return
+/// * The mutex id.
+/// * -EAGAIN - SYNC_MAX mutexes exist.
+pub fn mutex_blocking_create() -> isize {
+    MUTEXES.create(|mutex| mutex.init(false))
+}
+/// Crate a spin mutex lock, it yields when it is locked by others.
+/// # Return
+/// * The mutex id.
+/// * -EAGAIN - SYNC_MAX mutexes exist.
+pub fn mutex_create() -> isize {
+    MUTEXES.create(|mutex| mutex.init(true))
 }
-/// Crate a spin mutex lock
-pub fn mutex_create() -> usize {
-    sys_mutex_create(false) as usize
+/// Free the mutex id, the mutex must not be locked or waited for.
+/// # Return
+/// * -EINVAL - the mutex does not exist.
+pub fn mutex_destroy(mutex_id: usize) -> isize {
+    MUTEXES.destroy(mutex_id)
 }
+/// # Return
+/// * -EINVAL - the mutex does not exist.
 pub fn mutex_lock(mutex_id: usize) -> isize {
-    sys_mutex_lock(mutex_id)
+    MUTEXES.with(mutex_id, |mutex| {
+        mutex.lock();
+        0
+    })
 }
+/// # Return
+/// * -EINVAL - the mutex does not exist.
 pub fn mutex_unlock(mutex_id: usize) -> isize {
-    sys_mutex_unlock(mutex_id)
+    MUTEXES.with(mutex_id, |mutex| {
+        mutex.unlock();
+        0
+    })
+}
+/// Lock the mutex if it is unlocked.
+/// # Return
+/// * -EBUSY - the mutex is locked.
+/// * -EINVAL - the mutex does not exist.
+pub fn mutex_trylock(mutex_id: usize) -> isize {
+    MUTEXES.with(mutex_id, |mutex| match mutex.try_lock() {
+        true => 0,
+        false => -EBUSY,
+    })
+}
+/// Lock the mutex, or give up at abs_timeout, the time since boot.
+/// # Return
+/// * -ETIMEDOUT - the mutex is still locked at abs_timeout.
+/// * -EINVAL - the mutex does not exist.
+pub fn mutex_timedlock(mutex_id: usize, abs_timeout: &TimeSpec) -> isize {
+    MUTEXES.with(mutex_id, |mutex| match mutex.timed_lock(abs_timeout) {
+        true => 0,
+        false => -ETIMEDOUT,
+    })
 }
 /// Create a semaphore
-pub fn semaphore_create(res_count: usize) -> usize {
-    sys_semaphore_create(res_count) as usize
+/// # Return
+/// * The semaphore id.
+/// * -EAGAIN - SYNC_MAX semaphores exist.
+pub fn semaphore_create(res_count: usize) -> isize {
+    SEMAPHORES.create(|semaphore| semaphore.init(res_count as u32))
+}
+/// Free the semaphore id, no thread may wait for it.
+/// # Return
+/// * -EINVAL - the semaphore does not exist.
+pub fn semaphore_destroy(semaphore_id: usize) -> isize {
+    SEMAPHORES.destroy(semaphore_id)
 }
+/// # Return
+/// * -EINVAL - the semaphore does not exist.
 pub fn semaphore_up(semaphore_id: usize) -> isize {
-    sys_semaphore_up(semaphore_id)
+    SEMAPHORES.with(semaphore_id, |semaphore| {
+        semaphore.up();
+        0
+    })
 }
+/// # Return
+/// * -EINVAL - the semaphore does not exist.
 pub fn semaphore_down(semaphore_id: usize) -> isize {
-    sys_semaphore_down(semaphore_id)
+    SEMAPHORES.with(semaphore_id, |semaphore| {
+        semaphore.down();
+        0
+    })
+}
+/// Take a unit of the semaphore if there is one.
+/// # Return
+/// * -EAGAIN - there is no unit.
+/// * -EINVAL - the semaphore does not exist.
+pub fn semaphore_trydown(semaphore_id: usize) -> isize {
+    SEMAPHORES.with(semaphore_id, |semaphore| match semaphore.try_down() {
+        true => 0,
+        false => -EAGAIN,
+    })
+}
+/// Take a unit of the semaphore, or give up at abs_timeout, the time since boot.
+/// # Return
+/// * -ETIMEDOUT - there is still no unit at abs_timeout.
+/// * -EINVAL - the semaphore does not exist.
+pub fn semaphore_timeddown(semaphore_id: usize, abs_timeout: &TimeSpec) -> isize {
+    SEMAPHORES.with(semaphore_id, |semaphore| {
+        match semaphore.timed_down(abs_timeout) {
+            true => 0,
+            false => -ETIMEDOUT,
+        }
+    })
 }
 /// Condvar
-pub fn condvar_create() -> usize {
-    sys_condvar_create() as usize
+/// # Return
+/// * The condvar id.
+/// * -EAGAIN - SYNC_MAX condvars exist.
+pub fn condvar_create() -> isize {
+    CONDVARS.create(|condvar| condvar.init())
 }
+/// Free the condvar id, no thread may wait for it.
+/// # Return
+/// * -EINVAL - the condvar does not exist.
+pub fn condvar_destroy(condvar_id: usize) -> isize {
+    CONDVARS.destroy(condvar_id)
+}
+/// # Return
+/// * -EINVAL - the condvar does not exist.
 pub fn condvar_signal(condvar_id: usize) -> isize {
-    sys_condvar_signal(condvar_id)
+    CONDVARS.with(condvar_id, |condvar| {
+        condvar.signal();
+        0
+    })
 }
+/// # Return
+/// * -EINVAL - the condvar or the mutex does not exist.
 pub fn condvar_wait(condvar_id: usize, mutex_id: usize) -> isize {
+    CONDVARS.with(condvar_id, |condvar| {
+        MUTEXES.with(mutex_id, |mutex| {
+            condvar.wait(mutex);
+            0
+        })
+    })
+}
+/// Wake up all threads waiting on the condvar.
+/// # Return
+/// * -EINVAL - the condvar does not exist.
+pub fn condvar_broadcast(condvar_id: usize) -> isize {
+    CONDVARS.with(condvar_id, |condvar| {
+        condvar.broadcast();
+        0
+    })
+}
+/// condvar_wait which gives up at abs_timeout, the time since boot,
+/// the mutex is locked again even if it times out.
+/// # Return
+/// * -ETIMEDOUT - abs_timeout is reached.
+/// * -EINVAL - the condvar or the mutex does not exist.
+pub fn condvar_timedwait(condvar_id: usize, mutex_id: usize, abs_timeout: &TimeSpec) -> isize {
+    CONDVARS.with(condvar_id, |condvar| {
+        MUTEXES.with(mutex_id, |mutex| {
+            match condvar.timed_wait(mutex, abs_timeout) {
+                true => 0,
+                false => -ETIMEDOUT,
+            }
+        })
+    })
+}
+/// Create a mutex in the kernel, every lock and unlock is a syscall.
+pub fn kernel_mutex_create(blocking: bool) -> usize {
+    sys_mutex_create(blocking as usize) as usize
+}
+/// Flags of kernel_mutex_create_with_flags.
+/// Waiting threads block instead of yielding.
+pub const MUTEX_BLOCKING: usize = 1;
+/// The owner can lock it again, it is unlocked after as many unlocks.
+pub const MUTEX_RECURSIVE: usize = 1 << 1;
+/// Locking it again by the owner fails with EDEADLK, and unlocking it by others fails with EPERM.
+pub const MUTEX_ERRORCHECK: usize = 1 << 2;
+/// The owner runs at the highest priority of the threads waiting on it,
+/// which get the mutex in the order of priority, only for a blocking mutex.
+pub const MUTEX_PRIO_INHERIT: usize = 1 << 3;
+/// Create a mutex in the kernel with the flags.
+/// # Return
+/// * The mutex id.
+/// * -EINVAL - unknown flags, both types, or MUTEX_PRIO_INHERIT without MUTEX_BLOCKING.
+pub fn kernel_mutex_create_with_flags(flags: usize) -> isize {
+    sys_mutex_create(flags)
+}
+/// Lock the kernel mutex.
+/// # Return
+/// * -EDEADLK - deadlock detection is enabled and waiting for the mutex would deadlock,
+///   or an error checking mutex is held by the current thread.
+pub fn kernel_mutex_lock(mutex_id: usize) -> isize {
+    sys_mutex_lock(mutex_id)
+}
+/// Unlock the kernel mutex.
+/// # Return
+/// * -EPERM - the mutex is not locked, or a recursive or error checking mutex is held by others.
+pub fn kernel_mutex_unlock(mutex_id: usize) -> isize {
+    sys_mutex_unlock(mutex_id)
+}
+/// Lock the kernel mutex without waiting.
+/// # Return
+/// * -EBUSY - the mutex is locked, and it is not a recursive mutex held by the current thread.
+pub fn kernel_mutex_trylock(mutex_id: usize) -> isize {
+    sys_mutex_trylock(mutex_id)
+}
+/// Lock the kernel mutex, or give up at abs_timeout, the time since boot.
+/// # Return
+/// * -ETIMEDOUT - the mutex is still locked at abs_timeout.
+/// * -EDEADLK - the same as kernel_mutex_lock.
+pub fn kernel_mutex_timedlock(mutex_id: usize, abs_timeout: &TimeSpec) -> isize {
+    sys_mutex_timedlock(mutex_id, abs_timeout)
+}
+/// Create a semaphore in the kernel.
+pub fn kernel_semaphore_create(res_count: usize) -> usize {
+    sys_semaphore_create(res_count) as usize
+}
+pub fn kernel_semaphore_up(semaphore_id: usize) -> isize {
+    sys_semaphore_up(semaphore_id)
+}
+/// Down the kernel semaphore.
+/// # Return
+/// * -EDEADLK - deadlock detection is enabled and waiting for the semaphore would deadlock.
+pub fn kernel_semaphore_down(semaphore_id: usize) -> isize {
+    sys_semaphore_down(semaphore_id)
+}
+/// Take a unit of the kernel semaphore without waiting.
+/// # Return
+/// * -EAGAIN - there is no unit.
+pub fn kernel_semaphore_trydown(semaphore_id: usize) -> isize {
+    sys_semaphore_trydown(semaphore_id)
+}
+/// Take a unit of the kernel semaphore, or give up at abs_timeout, the time since boot.
+/// # Return
+/// * -ETIMEDOUT - there is still no unit at abs_timeout.
+/// * -EDEADLK - the same as kernel_semaphore_down.
+pub fn kernel_semaphore_timeddown(semaphore_id: usize, abs_timeout: &TimeSpec) -> isize {
+    sys_semaphore_timeddown(semaphore_id, abs_timeout)
+}
+/// Create a condvar in the kernel, it waits with a kernel mutex.
+pub fn kernel_condvar_create() -> usize {
+    sys_condvar_create() as usize
+}
+pub fn kernel_condvar_signal(condvar_id: usize) -> isize {
+    sys_condvar_signal(condvar_id)
+}
+/// Unlock the kernel mutex and wait for a signal, then lock the mutex again.
+/// # Return
+/// * -EPERM - the mutex is not held by the current thread.
+pub fn kernel_condvar_wait(condvar_id: usize, mutex_id: usize) -> isize {
     sys_condvar_wait(condvar_id, mutex_id)
 }
+/// Wake up all threads waiting on the kernel condvar.
+pub fn kernel_condvar_broadcast(condvar_id: usize) -> isize {
+    sys_condvar_broadcast(condvar_id)
+}
+/// kernel_condvar_wait which gives up at abs_timeout, the time since boot,
+/// the mutex is locked again even if it times out.
+/// # Return
+/// * -ETIMEDOUT - abs_timeout is reached.
+/// * -EPERM - the mutex is not held by the current thread.
+pub fn kernel_condvar_timedwait(
+    condvar_id: usize,
+    mutex_id: usize,
+    abs_timeout: &TimeSpec,
+) -> isize {
+    sys_condvar_timedwait(condvar_id, mutex_id, abs_timeout)
+}
+/// A kernel reader-writer lock, writers are preferred over new readers.
+pub fn rwlock_create() -> usize {
+    sys_rwlock_create() as usize
+}
+/// Lock it for reading, wait while a writer holds it or waits for it.
+/// # Return
+/// * -EINVAL - the rwlock does not exist.
+pub fn rwlock_read_lock(rwlock_id: usize) -> isize {
+    sys_rwlock_rdlock(rwlock_id)
+}
+/// Lock it for writing, wait until the readers and the writer unlock it.
+pub fn rwlock_write_lock(rwlock_id: usize) -> isize {
+    sys_rwlock_wrlock(rwlock_id)
+}
+/// Turn the read lock of the current thread into the write lock,
+/// wait until the other readers unlock it.
+/// # Return
+/// * -EPERM - the current thread does not hold it for reading.
+/// * -EDEADLK - another reader is waiting to upgrade.
+pub fn rwlock_upgrade(rwlock_id: usize) -> isize {
+    sys_rwlock_upgrade(rwlock_id)
+}
+/// Unlock the read lock or the write lock of the current thread.
+/// # Return
+/// * -EPERM - the current thread does not hold it.
+pub fn rwlock_unlock(rwlock_id: usize) -> isize {
+    sys_rwlock_unlock(rwlock_id)
+}
+/// A kernel barrier for count threads, reused for every round.
+/// # Return
+/// * -EINVAL - count is 0.
+pub fn barrier_create(count: usize) -> isize {
+    sys_barrier_create(count)
+}
+/// Wait until count threads have arrived.
+/// # Return
+/// * 1 - for the last thread of the round.
+/// * 0 - for the other threads.
+/// * -EINVAL - the barrier does not exist.
+pub fn barrier_wait(barrier_id: usize) -> isize {
+    sys_barrier_wait(barrier_id)
+}
+/// Enable or disable deadlock detection of the kernel mutexes and semaphores of the process,
+/// a lock or down which would deadlock fails with EDEADLK instead of blocking.
+/// A semaphore upped by threads which did not down it may be taken as a deadlock.
+/// The futex based objects of mutex_create, semaphore_create and condvar_create are not seen,
+/// use the kernel_* objects with it.
+pub fn enable_deadlock_detect(enabled: bool) -> isize {
+    sys_enable_deadlock_detect(enabled as usize)
+}
+
+/// Operations of futex.
+pub const FUTEX_WAIT: usize = 0;
+pub const FUTEX_WAKE: usize = 1;
+pub const FUTEX_REQUEUE: usize = 3;
+pub const FUTEX_CMP_REQUEUE: usize = 4;
+
+/// Wait on the futex word if it is still val, until it is woken up.
+/// # Parameter
+/// * 'timeout' - relative timeout, None waits forever
+/// # Return
+/// * 0 - woken up, maybe spuriously, so the word is checked again.
+/// * -EAGAIN - the word is not val.
+/// * -ETIMEDOUT - not woken up before the timeout.
+/// * -EINTR - interrupted by a signal.
+pub fn futex_wait(futex: &AtomicU32, val: u32, timeout: Option<&TimeSpec>) -> isize {
+    let timeout = timeout.map_or(0, |timeout| timeout as *const TimeSpec as usize);
+    sys_futex(futex, FUTEX_WAIT, val, timeout, None, 0)
+}
+/// Wake up at most n threads waiting on the futex word.
+/// # Return
+/// * The number of threads woken up.
+pub fn futex_wake(futex: &AtomicU32, n: usize) -> isize {
+    sys_futex(futex, FUTEX_WAKE, n as u32, 0, None, 0)
+}
+/// Wake up at most n_wake threads waiting on futex, and move at most n_requeue of the others
+/// to wait on futex2.
+/// # Return
+/// * The number of threads woken up and moved.
+pub fn futex_requeue(
+    futex: &AtomicU32,
+    n_wake: usize,
+    futex2: &AtomicU32,
+    n_requeue: usize,
+) -> isize {
+    sys_futex(futex, FUTEX_REQUEUE, n_wake as u32, n_requeue, Some(futex2), 0)
+}
+/// futex_requeue if the word of futex is val.
+/// # Return
+/// * -EAGAIN - the word is not val.
+pub fn futex_cmp_requeue(
+    futex: &AtomicU32,
+    n_wake: usize,
+    futex2: &AtomicU32,
+    n_requeue: usize,
+    val: u32,
+) -> isize {
+    sys_futex(futex, FUTEX_CMP_REQUEUE, n_wake as u32, n_requeue, Some(futex2), val)
+}
+
+/// Syscalls whose id is less than it are counted in TaskInfo.
+pub const MAX_SYSCALL_NUM: usize = 1100;
+/// getrusage: the calling process.
+pub const RUSAGE_SELF: isize = 0;
+/// getrusage: the children which have been waited.
+pub const RUSAGE_CHILDREN: isize = -1;
+/// getrusage: the calling thread.
+pub const RUSAGE_THREAD: isize = 1;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TimeVal {
+    pub sec: usize,
+    pub usec: usize,
+}
+
+impl TimeVal {
+    pub fn as_us(&self) -> usize {
+        self.sec * 1_000_000 + self.usec
+    }
+}
+
+/// Resource usage, the same layout as linux struct rusage.
+/// Only utime, stime and maxrss are filled by the kernel.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RUsage {
+    pub utime: TimeVal,
+    pub stime: TimeVal,
+    /// Max resident set size in KB.
+    pub maxrss: usize,
+    pub ixrss: usize,
+    pub idrss: usize,
+    pub isrss: usize,
+    pub minflt: usize,
+    pub majflt: usize,
+    pub nswap: usize,
+    pub inblock: usize,
+    pub oublock: usize,
+    pub msgsnd: usize,
+    pub msgrcv: usize,
+    pub nsignals: usize,
+    pub nvcsw: usize,
+    pub nivcsw: usize,
+}
+
+/// Process times in clock ticks, the same layout as linux struct tms.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Tms {
+    pub utime: usize,
+    pub stime: usize,
+    pub cutime: usize,
+    pub cstime: usize,
+}
+
+/// Statistics of the current process.
+#[repr(C)]
+pub struct TaskInfo {
+    /// How many times every syscall has been called.
+    pub syscall_times: [u32; MAX_SYSCALL_NUM],
+    /// Milliseconds since the process was created.
+    pub time: usize,
+    /// Cpu time in user mode, in milliseconds.
+    pub utime: usize,
+    /// Cpu time in kernel mode, in milliseconds.
+    pub stime: usize,
+    /// Resident pages.
+    pub rss: usize,
+    /// Max resident pages.
+    pub max_rss: usize,
+}
+
+impl TaskInfo {
+    /// TaskInfo is too large for the user stack, so it is zeroed on heap directly.
+    pub fn new() -> Box<Self> {
+        let layout = core::alloc::Layout::new::<Self>();
+        unsafe { Box::from_raw(alloc::alloc::alloc_zeroed(layout) as *mut Self) }
+    }
+}
+
+/// Get the statistics of the current process.
+pub fn task_info(info: &mut TaskInfo) -> isize {
+    sys_task_info(info)
+}
+/// Get the resource usage.
+/// # Parameter
+/// * 'who' - RUSAGE_SELF, RUSAGE_CHILDREN or RUSAGE_THREAD.
+/// # Return
+/// * -1 - who is invalid.
+pub fn getrusage(who: isize, usage: &mut RUsage) -> isize {
+    sys_getrusage(who, usage)
+}
+/// Get the cpu time of the current process and its waited children.
+/// # Return
+/// * Clock ticks since boot.
+pub fn times(tms: &mut Tms) -> isize {
+    sys_times(tms)
+}
+
+/// Unix domain sockets, addressed by paths in the file system.
+pub const AF_UNIX: usize = 1;
+/// IPv4 sockets.
+pub const AF_INET: usize = 2;
+/// Reliable byte stream.
+pub const SOCK_STREAM: usize = 1;
+/// Messages with boundaries.
+pub const SOCK_DGRAM: usize = 2;
+/// Flags or-ed to the type of socket, socketpair and accept4.
+pub const SOCK_NONBLOCK: usize = 1 << 11;
+pub const SOCK_CLOEXEC: usize = 1 << 19;
+
+/// Flags of send and recv.
+pub const MSG_CTRUNC: u32 = 0x8;
+pub const MSG_TRUNC: u32 = 0x20;
+pub const MSG_DONTWAIT: u32 = 0x40;
+pub const MSG_NOSIGNAL: u32 = 0x4000;
+pub const MSG_CMSG_CLOEXEC: u32 = 0x4000_0000;
+
+/// How of shutdown.
+pub const SHUT_RD: usize = 0;
+pub const SHUT_WR: usize = 1;
+pub const SHUT_RDWR: usize = 2;
+
+/// Level and type of the control message passing fds.
+pub const SOL_SOCKET: i32 = 1;
+pub const SCM_RIGHTS: i32 = 1;
+
+/// Address of a unix domain socket, the same layout as linux struct sockaddr_un.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct SockaddrUn {
+    pub family: u16,
+    pub path: [u8; 108],
+}
+
+impl SockaddrUn {
+    /// The address of the path, end with '\0'.
+    pub fn new(path: &str) -> Self {
+        let mut addr = Self::default();
+        addr.path[..path.len()].copy_from_slice(path.as_bytes());
+        addr
+    }
+    /// The path without '\0', empty for an unbound socket.
+    pub fn path(&self) -> &str {
+        let len = self.path.iter().position(|c| *c == 0).unwrap_or(0);
+        core::str::from_utf8(&self.path[..len]).unwrap()
+    }
+}
+
+impl Default for SockaddrUn {
+    fn default() -> Self {
+        Self {
+            family: AF_UNIX as u16,
+            path: [0; 108],
+        }
+    }
+}
+
+/// Any address of the machine.
+pub const INADDR_ANY: [u8; 4] = [0, 0, 0, 0];
+/// The loopback address.
+pub const INADDR_LOOPBACK: [u8; 4] = [127, 0, 0, 1];
+
+/// Address of an IPv4 socket, the same layout as linux struct sockaddr_in.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct SockaddrIn {
+    pub family: u16,
+    /// The port in network byte order.
+    pub port: u16,
+    pub addr: [u8; 4],
+    pub zero: [u8; 8],
+}
+
+impl SockaddrIn {
+    pub fn new(addr: [u8; 4], port: u16) -> Self {
+        Self {
+            family: AF_INET as u16,
+            port: port.to_be(),
+            addr,
+            zero: [0; 8],
+        }
+    }
+    /// The port in host byte order.
+    pub fn port(&self) -> u16 {
+        u16::from_be(self.port)
+    }
+}
+
+impl Default for SockaddrIn {
+    fn default() -> Self {
+        Self::new(INADDR_ANY, 0)
+    }
+}
+
+/// A buffer of sendmsg and recvmsg, the same layout as linux struct iovec.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct IoVec {
+    pub base: *mut u8,
+    pub len: usize,
+}
+
+/// Message of sendmsg and recvmsg, the same layout as linux struct msghdr.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct MsgHdr {
+    pub name: *mut SockaddrUn,
+    pub namelen: u32,
+    pub iov: *mut IoVec,
+    pub iovlen: usize,
+    /// Control messages, each starts with a CmsgHdr and its data is aligned to 8 bytes.
+    pub control: *mut u8,
+    pub controllen: usize,
+    pub flags: u32,
+}
+
+impl Default for MsgHdr {
+    fn default() -> Self {
+        Self {
+            name: core::ptr::null_mut(),
+            namelen: 0,
+            iov: core::ptr::null_mut(),
+            iovlen: 0,
+            control: core::ptr::null_mut(),
+            controllen: 0,
+            flags: 0,
+        }
+    }
+}
+
+/// Header of a control message, the same layout as linux struct cmsghdr.
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct CmsgHdr {
+    /// The length of the header and the data.
+    pub len: usize,
+    pub level: i32,
+    pub type_: i32,
+}
+
+/// Create a socket.
+/// # Parameter
+/// * 'domain' - AF_UNIX or AF_INET, SOCK_STREAM of AF_INET is TCP and SOCK_DGRAM is UDP
+/// * 'type_' - SOCK_STREAM or SOCK_DGRAM, or-ed with SOCK_NONBLOCK and SOCK_CLOEXEC
+pub fn socket(domain: usize, type_: usize) -> isize {
+    sys_socket(domain, type_, 0)
+}
+/// Create a pair of connected sockets.
+pub fn socketpair(domain: usize, type_: usize, sv: &mut [i32; 2]) -> isize {
+    sys_socketpair(domain, type_, 0, sv)
+}
+/// Bind the socket to the path end with '\0', a socket file is created at the path.
+/// # Return
+/// * -EADDRINUSE - the path exists.
+pub fn bind(fd: usize, path: &str) -> isize {
+    let addr = SockaddrUn::new(path);
+    sys_bind(fd, &addr as *const _ as *const u8, (2 + path.len()) as u32)
+}
+/// Bind the IPv4 socket to the address, port 0 is an ephemeral port.
+/// # Return
+/// * -EADDRINUSE - the port is bound.
+/// * -EADDRNOTAVAIL - the address is not an address of the machine.
+pub fn bind_in(fd: usize, addr: &SockaddrIn) -> isize {
+    let addrlen = core::mem::size_of::<SockaddrIn>() as u32;
+    sys_bind(fd, addr as *const _ as *const u8, addrlen)
+}
+/// Accept connections on a stream socket, a unix domain socket must be bound first.
+pub fn listen(fd: usize, backlog: usize) -> isize {
+    sys_listen(fd, backlog)
+}
+/// Take a connection of the listening socket, wait until a client connects.
+/// # Parameter
+/// * 'addr' - filled with the address of the client.
+/// # Return
+/// * The fd connected to the client.
+pub fn accept(fd: usize, addr: Option<&mut SockaddrUn>) -> isize {
+    accept4(fd, addr, 0)
+}
+/// accept with SOCK_NONBLOCK and SOCK_CLOEXEC of the new fd.
+pub fn accept4(fd: usize, addr: Option<&mut SockaddrUn>, flags: usize) -> isize {
+    let mut addrlen = core::mem::size_of::<SockaddrUn>() as u32;
+    let addr = addr.map_or(core::ptr::null_mut(), |addr| addr as *mut SockaddrUn);
+    sys_accept4(fd, addr as *mut u8, &mut addrlen, flags)
+}
+/// accept of an IPv4 socket.
+pub fn accept_in(fd: usize, addr: Option<&mut SockaddrIn>) -> isize {
+    let mut addrlen = core::mem::size_of::<SockaddrIn>() as u32;
+    let addr = addr.map_or(core::ptr::null_mut(), |addr| addr as *mut SockaddrIn);
+    sys_accept4(fd, addr as *mut u8, &mut addrlen, 0)
+}
+/// Connect to the listening stream socket bound to the path end with '\0',
+/// or set the default destination of a datagram socket.
+/// # Return
+/// * -ECONNREFUSED - no socket is listening on the path.
+pub fn connect(fd: usize, path: &str) -> isize {
+    let addr = SockaddrUn::new(path);
+    sys_connect(fd, &addr as *const _ as *const u8, (2 + path.len()) as u32)
+}
+/// Connect the TCP socket to the address, or set the default destination of a UDP socket.
+/// # Return
+/// * -ECONNREFUSED - no socket is listening on the address.
+/// * -EINPROGRESS - the socket is non-blocking, it is connected when it polls POLLOUT.
+/// * -EALREADY - the connection started by the last connect is in progress.
+pub fn connect_in(fd: usize, addr: &SockaddrIn) -> isize {
+    let addrlen = core::mem::size_of::<SockaddrIn>() as u32;
+    sys_connect(fd, addr as *const _ as *const u8, addrlen)
+}
+/// Get the bound address of the socket.
+pub fn getsockname(fd: usize, addr: &mut SockaddrUn) -> isize {
+    let mut addrlen = core::mem::size_of::<SockaddrUn>() as u32;
+    sys_getsockname(fd, addr as *mut _ as *mut u8, &mut addrlen)
+}
+/// getsockname of an IPv4 socket.
+pub fn getsockname_in(fd: usize, addr: &mut SockaddrIn) -> isize {
+    let mut addrlen = core::mem::size_of::<SockaddrIn>() as u32;
+    sys_getsockname(fd, addr as *mut _ as *mut u8, &mut addrlen)
+}
+/// Get the bound address of the peer.
+pub fn getpeername(fd: usize, addr: &mut SockaddrUn) -> isize {
+    let mut addrlen = core::mem::size_of::<SockaddrUn>() as u32;
+    sys_getpeername(fd, addr as *mut _ as *mut u8, &mut addrlen)
+}
+/// getpeername of an IPv4 socket.
+pub fn getpeername_in(fd: usize, addr: &mut SockaddrIn) -> isize {
+    let mut addrlen = core::mem::size_of::<SockaddrIn>() as u32;
+    sys_getpeername(fd, addr as *mut _ as *mut u8, &mut addrlen)
+}
+/// Send data to the connected peer.
+/// # Parameter
+/// * 'flags' - MSG_DONTWAIT and MSG_NOSIGNAL.
+pub fn send(fd: usize, buf: &[u8], flags: u32) -> isize {
+    sys_sendto(fd, buf, flags, core::ptr::null(), 0)
+}
+/// Send a datagram to the socket bound to the path end with '\0'.
+pub fn sendto(fd: usize, buf: &[u8], flags: u32, path: &str) -> isize {
+    let addr = SockaddrUn::new(path);
+    sys_sendto(fd, buf, flags, &addr as *const _ as *const u8, (2 + path.len()) as u32)
+}
+/// Send a datagram to the IPv4 address.
+/// # Return
+/// * -EMSGSIZE - the datagram does not fit in an ethernet frame.
+pub fn sendto_in(fd: usize, buf: &[u8], flags: u32, addr: &SockaddrIn) -> isize {
+    let addrlen = core::mem::size_of::<SockaddrIn>() as u32;
+    sys_sendto(fd, buf, flags, addr as *const _ as *const u8, addrlen)
+}
+/// Receive data, wait until there is some data.
+/// # Parameter
+/// * 'flags' - MSG_DONTWAIT, and MSG_TRUNC to get the whole length of a truncated datagram.
+/// # Return
+/// * The number of bytes received, 0 at the end of the stream.
+pub fn recv(fd: usize, buf: &mut [u8], flags: u32) -> isize {
+    sys_recvfrom(fd, buf, flags, core::ptr::null_mut(), core::ptr::null_mut())
+}
+/// Receive data and the address of the sender.
+pub fn recvfrom(fd: usize, buf: &mut [u8], flags: u32, addr: &mut SockaddrUn) -> isize {
+    let mut addrlen = core::mem::size_of::<SockaddrUn>() as u32;
+    sys_recvfrom(fd, buf, flags, addr as *mut _ as *mut u8, &mut addrlen)
+}
+/// recvfrom of an IPv4 socket.
+pub fn recvfrom_in(fd: usize, buf: &mut [u8], flags: u32, addr: &mut SockaddrIn) -> isize {
+    let mut addrlen = core::mem::size_of::<SockaddrIn>() as u32;
+    sys_recvfrom(fd, buf, flags, addr as *mut _ as *mut u8, &mut addrlen)
+}
+/// Shut down reading, writing or both of a connected socket.
+/// # Parameter
+/// * 'how' - SHUT_RD, SHUT_WR or SHUT_RDWR.
+pub fn shutdown(fd: usize, how: usize) -> isize {
+    sys_shutdown(fd, how)
+}
+/// Send a message with the fds in msg.control.
+pub fn sendmsg(fd: usize, msg: &MsgHdr, flags: u32) -> isize {
+    sys_sendmsg(fd, msg, flags)
+}
+/// Receive a message, msg.namelen, msg.controllen and msg.flags are updated.
+pub fn recvmsg(fd: usize, msg: &mut MsgHdr, flags: u32) -> isize {
+    sys_recvmsg(fd, msg, flags)
+}
+
+/// The max number of fds of send_fds and recv_fds.
+const MAX_PASSED_FDS: usize = 16;
+/// A control buffer aligned to 8 bytes for an SCM_RIGHTS message of MAX_PASSED_FDS fds.
+type RightsBuffer = [usize; 2 + MAX_PASSED_FDS / 2];
+
+/// Send data with fds as an SCM_RIGHTS message, the receiver gets copies of the fds.
+pub fn send_fds(fd: usize, buf: &[u8], fds: &[i32]) -> isize {
+    assert!(fds.len() <= MAX_PASSED_FDS);
+    let mut control: RightsBuffer = Default::default();
+    let header = CmsgHdr {
+        len: core::mem::size_of::<CmsgHdr>() + fds.len() * 4,
+        level: SOL_SOCKET,
+        type_: SCM_RIGHTS,
+    };
+    unsafe {
+        let ptr = control.as_mut_ptr() as *mut CmsgHdr;
+        ptr.write(header);
+        let data = ptr.add(1) as *mut i32;
+        data.copy_from_nonoverlapping(fds.as_ptr(), fds.len());
+    }
+    let mut iov = IoVec {
+        base: buf.as_ptr() as *mut u8,
+        len: buf.len(),
+    };
+    let msg = MsgHdr {
+        iov: &mut iov,
+        iovlen: 1,
+        control: control.as_mut_ptr() as *mut u8,
+        controllen: header.len,
+        ..Default::default()
+    };
+    sendmsg(fd, &msg, 0)
+}
+/// Receive data and the fds sent with it.
+/// # Return
+/// * (the number of bytes received or -errno, the number of fds written to fds)
+pub fn recv_fds(fd: usize, buf: &mut [u8], fds: &mut [i32]) -> (isize, usize) {
+    let mut control: RightsBuffer = Default::default();
+    let mut iov = IoVec {
+        base: buf.as_mut_ptr(),
+        len: buf.len(),
+    };
+    let mut msg = MsgHdr {
+        iov: &mut iov,
+        iovlen: 1,
+        control: control.as_mut_ptr() as *mut u8,
+        controllen: core::mem::size_of::<CmsgHdr>() + fds.len().min(MAX_PASSED_FDS) * 4,
+        ..Default::default()
+    };
+    let len = recvmsg(fd, &mut msg, 0);
+    if len < 0 || msg.controllen < core::mem::size_of::<CmsgHdr>() {
+        return (len, 0);
+    }
+    let header = unsafe { (control.as_ptr() as *const CmsgHdr).read() };
+    let count = (header.len - core::mem::size_of::<CmsgHdr>()) / 4;
+    let data = unsafe { (control.as_ptr() as *const CmsgHdr).add(1) as *const i32 };
+    for (i, fd) in fds.iter_mut().take(count).enumerate() {
+        *fd = unsafe { data.add(i).read() };
+    }
+    (len, count)
+}
+
+/// The key of shmget which always creates a new segment.
+pub const IPC_PRIVATE: i32 = 0;
+/// Flags of shmget, or-ed with the permission bits of the mode.
+pub const IPC_CREAT: usize = 0o1000;
+pub const IPC_EXCL: usize = 0o2000;
+/// Commands of shmctl.
+pub const IPC_RMID: usize = 0;
+pub const IPC_SET: usize = 1;
+pub const IPC_STAT: usize = 2;
+/// Flags of shmat.
+pub const SHM_RDONLY: usize = 0o10000;
+pub const SHM_RND: usize = 0o20000;
+
+/// Owner and mode of an IPC object, the same layout as linux struct ipc64_perm.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct IpcPerm {
+    pub key: i32,
+    pub uid: u32,
+    pub gid: u32,
+    pub cuid: u32,
+    pub cgid: u32,
+    pub mode: u32,
+    pub seq: u16,
+    pad: u16,
+    unused: [usize; 2],
+}
+
+/// State of a shared memory segment, the same layout as linux struct shmid64_ds.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ShmidDs {
+    pub perm: IpcPerm,
+    /// The size in bytes given to shmget.
+    pub segsz: usize,
+    /// Times of the last shmat, shmdt and change, in seconds since boot.
+    pub atime: isize,
+    pub dtime: isize,
+    pub ctime: isize,
+    /// The creator, and the process of the last shmat or shmdt.
+    pub cpid: i32,
+    pub lpid: i32,
+    /// The number of attachments.
+    pub nattch: usize,
+    unused: [usize; 2],
+}
+
+/// Get the shared memory segment of the key, or create a new segment of size bytes.
+/// # Parameter
+/// * 'flags' - IPC_CREAT, IPC_EXCL, and the permission bits of the mode, such as 0o600
+/// # Return
+/// * The id of the segment.
+/// * -ENOENT - no segment has the key and IPC_CREAT is not given.
+/// * -EEXIST - a segment has the key and IPC_CREAT and IPC_EXCL are given.
+/// * -EINVAL - size is 0 or too large, or larger than the existing segment.
+pub fn shmget(key: i32, size: usize, flags: usize) -> isize {
+    sys_shmget(key, size, flags)
+}
+/// Attach the segment, the attachment is inherited by fork.
+/// # Parameter
+/// * 'addr' - page aligned address, or 0 to attach at a free address
+/// * 'flags' - SHM_RDONLY and SHM_RND
+/// # Return
+/// * The address of the attachment.
+/// * -EINVAL - the address is not aligned or overlaps mapped memory.
+/// * -EACCES - the mode does not allow the access.
+pub fn shmat(shmid: usize, addr: usize, flags: usize) -> isize {
+    sys_shmat(shmid, addr, flags)
+}
+/// Detach the segment attached at the address.
+pub fn shmdt(addr: usize) -> isize {
+    sys_shmdt(addr)
+}
+/// Control the segment.
+/// # Parameter
+/// * 'cmd' - IPC_STAT fills buf, IPC_SET sets the permission bits of the mode from buf,
+///   and IPC_RMID removes the segment, it is destroyed after the last detach
+pub fn shmctl(shmid: usize, cmd: usize, buf: &mut ShmidDs) -> isize {
+    sys_shmctl(shmid, cmd, buf)
+}
+
+/// Priorities of messages are less than MQ_PRIO_MAX.
+pub const MQ_PRIO_MAX: u32 = 32768;
+/// The flag of MqAttr, the same value as OpenFlags::NONBLOCK.
+pub const MQ_NONBLOCK: usize = 1 << 11;
+
+/// Attributes of a message queue, the same layout as linux struct mq_attr.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MqAttr {
+    /// MQ_NONBLOCK of the descriptor.
+    pub flags: usize,
+    /// The max number of messages in the queue.
+    pub maxmsg: usize,
+    /// The max length of a message.
+    pub msgsize: usize,
+    /// The number of messages in the queue.
+    pub curmsgs: usize,
+    reserved: [usize; 4],
+}
+
+impl MqAttr {
+    pub fn new(maxmsg: usize, msgsize: usize) -> Self {
+        Self {
+            maxmsg,
+            msgsize,
+            ..Default::default()
+        }
+    }
+}
+
+/// Open a message queue named globally, such as "/queue\0".
+/// # Parameter
+/// * 'flags' - the access mode, CREATE, EXCL, NONBLOCK and CLOEXEC
+/// * 'mode' - the permission bits of a new queue, such as 0o600
+/// * 'attr' - maxmsg and msgsize of a new queue, None for 10 messages of 1024 bytes
+/// # Return
+/// * The fd of the queue.
+/// * -ENOENT - no queue has the name and CREATE is not given.
+/// * -EEXIST - a queue has the name and CREATE and EXCL are given.
+/// * -EACCES - the mode does not allow the access.
+/// * -EINVAL - bad name or attributes.
+pub fn mq_open(name: &str, flags: OpenFlags, mode: u32, attr: Option<&MqAttr>) -> isize {
+    sys_mq_open(name, flags.bits(), mode, attr)
+}
+/// Remove the name of the queue, opened fds still use the queue.
+pub fn mq_unlink(name: &str) -> isize {
+    sys_mq_unlink(name)
+}
+/// Send a message, block while the queue is full unless the fd is non-blocking.
+/// # Return
+/// * -EMSGSIZE - the message is longer than msgsize.
+/// * -EAGAIN - the queue is full and the fd is non-blocking.
+pub fn mq_send(mqdes: usize, msg: &[u8], prio: u32) -> isize {
+    sys_mq_timedsend(mqdes, msg, prio, None)
+}
+/// Receive the oldest message of the highest priority,
+/// block while the queue is empty unless the fd is non-blocking.
+/// # Return
+/// * The length of the message.
+/// * -EMSGSIZE - buf is shorter than msgsize.
+/// * -EAGAIN - the queue is empty and the fd is non-blocking.
+pub fn mq_receive(mqdes: usize, buf: &mut [u8], prio: Option<&mut u32>) -> isize {
+    sys_mq_timedreceive(mqdes, buf, prio, None)
+}
+/// mq_send which gives up at abs_timeout, the time since boot.
+/// # Return
+/// * -ETIMEDOUT - the queue is still full at abs_timeout.
+pub fn mq_timedsend(mqdes: usize, msg: &[u8], prio: u32, abs_timeout: &TimeSpec) -> isize {
+    sys_mq_timedsend(mqdes, msg, prio, Some(abs_timeout))
+}
+/// mq_receive which gives up at abs_timeout, the time since boot.
+/// # Return
+/// * -ETIMEDOUT - the queue is still empty at abs_timeout.
+pub fn mq_timedreceive(
+    mqdes: usize,
+    buf: &mut [u8],
+    prio: Option<&mut u32>,
+    abs_timeout: &TimeSpec,
+) -> isize {
+    sys_mq_timedreceive(mqdes, buf, prio, Some(abs_timeout))
+}
+/// Get the attributes of the queue and the flags of the fd.
+pub fn mq_getattr(mqdes: usize, attr: &mut MqAttr) -> isize {
+    sys_mq_getsetattr(mqdes, None, Some(attr))
+}
+/// Set MQ_NONBLOCK of the fd, the other attributes are ignored.
+pub fn mq_setattr(mqdes: usize, attr: &MqAttr, old: Option<&mut MqAttr>) -> isize {
+    sys_mq_getsetattr(mqdes, Some(attr), old)
+}
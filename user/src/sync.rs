@@ -0,0 +1,247 @@
+//! Mutex, semaphore and condvar on futexes.
+//! The uncontended paths are atomics in user space, a syscall is only made to wait or wake up.
+//! They can be placed in statics, or in a shared memory segment to be used by processes.
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+use crate::{futex_wait, futex_wake, get_time, yield_, TimeSpec, ETIMEDOUT};
+
+/// States of a mutex.
+const UNLOCKED: u32 = 0;
+const LOCKED: u32 = 1;
+/// Locked, and threads may be waiting on the futex.
+const CONTENDED: u32 = 2;
+
+/// The time left before the deadline in ms since boot, to wait on a futex.
+/// # Return
+/// * None - the deadline is reached
+fn time_left(deadline: usize) -> Option<TimeSpec> {
+    let now = get_time() as usize;
+    (now < deadline).then(|| TimeSpec::from_ms(deadline - now))
+}
+
+/// Wait on the futex if the word is still val, until it is woken up or the deadline is reached.
+/// # Return
+/// * false - the deadline is reached
+fn wait_until(futex: &AtomicU32, val: u32, deadline: Option<usize>) -> bool {
+    let timeout = match deadline {
+        None => None,
+        Some(deadline) => match time_left(deadline) {
+            None => return false,
+            timeout => timeout,
+        },
+    };
+    futex_wait(futex, val, timeout.as_ref()) != -ETIMEDOUT
+}
+
+pub struct Mutex {
+    state: AtomicU32,
+    /// Yield instead of waiting on the futex.
+    spin: AtomicBool,
+}
+
+impl Mutex {
+    pub const fn new() -> Self {
+        Self {
+            state: AtomicU32::new(UNLOCKED),
+            spin: AtomicBool::new(false),
+        }
+    }
+
+    /// A mutex which yields until it is unlocked.
+    pub const fn new_spin() -> Self {
+        Self {
+            state: AtomicU32::new(UNLOCKED),
+            spin: AtomicBool::new(true),
+        }
+    }
+
+    /// Reset an unused mutex in the table of mutex ids.
+    pub(crate) fn init(&self, spin: bool) {
+        self.state.store(UNLOCKED, Ordering::Relaxed);
+        self.spin.store(spin, Ordering::Relaxed);
+    }
+
+    pub fn lock(&self) {
+        self.lock_until(None);
+    }
+
+    /// Lock the mutex if it is unlocked.
+    /// # Return
+    /// * false - the mutex is locked
+    pub fn try_lock(&self) -> bool {
+        self.state
+            .compare_exchange(UNLOCKED, LOCKED, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+    }
+
+    /// Lock the mutex, or give up at abs_timeout, the time since boot.
+    /// # Return
+    /// * false - the mutex is still locked at abs_timeout
+    pub fn timed_lock(&self, abs_timeout: &TimeSpec) -> bool {
+        self.lock_until(Some(abs_timeout.to_ms()))
+    }
+
+    fn lock_until(&self, deadline: Option<usize>) -> bool {
+        if self.try_lock() {
+            return true;
+        }
+        if self.spin.load(Ordering::Relaxed) {
+            while !self.try_lock() {
+                if deadline.is_some_and(|deadline| get_time() as usize >= deadline) {
+                    return false;
+                }
+                yield_();
+            }
+            return true;
+        }
+        // The owner which takes the lock here does not know whether others wait,
+        // so it always wakes one up at unlock.
+        while self.state.swap(CONTENDED, Ordering::Acquire) != UNLOCKED {
+            if !wait_until(&self.state, CONTENDED, deadline) {
+                return false;
+            }
+        }
+        true
+    }
+
+    pub fn unlock(&self) {
+        if self.state.swap(UNLOCKED, Ordering::Release) == CONTENDED {
+            futex_wake(&self.state, 1);
+        }
+    }
+}
+
+impl Default for Mutex {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct Semaphore {
+    count: AtomicU32,
+    /// The number of threads waiting or going to wait on count.
+    waiters: AtomicU32,
+}
+
+impl Semaphore {
+    pub const fn new(count: u32) -> Self {
+        Self {
+            count: AtomicU32::new(count),
+            waiters: AtomicU32::new(0),
+        }
+    }
+
+    pub(crate) fn init(&self, count: u32) {
+        self.count.store(count, Ordering::Relaxed);
+        self.waiters.store(0, Ordering::Relaxed);
+    }
+
+    pub fn up(&self) {
+        self.count.fetch_add(1, Ordering::SeqCst);
+        if self.waiters.load(Ordering::SeqCst) > 0 {
+            futex_wake(&self.count, 1);
+        }
+    }
+
+    pub fn down(&self) {
+        self.down_until(None);
+    }
+
+    /// Take a unit if there is one.
+    /// # Return
+    /// * false - there is no unit
+    pub fn try_down(&self) -> bool {
+        let mut count = self.count.load(Ordering::Relaxed);
+        while count > 0 {
+            match self.count.compare_exchange_weak(
+                count,
+                count - 1,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return true,
+                Err(now) => count = now,
+            }
+        }
+        false
+    }
+
+    /// Take a unit, or give up at abs_timeout, the time since boot.
+    /// # Return
+    /// * false - there is still no unit at abs_timeout
+    pub fn timed_down(&self, abs_timeout: &TimeSpec) -> bool {
+        self.down_until(Some(abs_timeout.to_ms()))
+    }
+
+    fn down_until(&self, deadline: Option<usize>) -> bool {
+        loop {
+            if self.try_down() {
+                return true;
+            }
+            // An up after the increment sees the waiter, and an up before it
+            // makes the futex wait return at once.
+            self.waiters.fetch_add(1, Ordering::SeqCst);
+            let woken = wait_until(&self.count, 0, deadline);
+            self.waiters.fetch_sub(1, Ordering::Relaxed);
+            if !woken {
+                // A unit may come at the deadline.
+                return self.try_down();
+            }
+        }
+    }
+}
+
+pub struct Condvar {
+    /// Changed by every signal, a waiter sleeps only if no signal came after it unlocked.
+    seq: AtomicU32,
+}
+
+impl Condvar {
+    pub const fn new() -> Self {
+        Self {
+            seq: AtomicU32::new(0),
+        }
+    }
+
+    pub(crate) fn init(&self) {
+        self.seq.store(0, Ordering::Relaxed);
+    }
+
+    pub fn signal(&self) {
+        self.seq.fetch_add(1, Ordering::Release);
+        futex_wake(&self.seq, 1);
+    }
+
+    /// Wake up all waiting threads.
+    pub fn broadcast(&self) {
+        self.seq.fetch_add(1, Ordering::Release);
+        futex_wake(&self.seq, usize::MAX);
+    }
+
+    /// Unlock the mutex and wait for a signal, then lock the mutex again.
+    /// It may return without a signal, so the condition is checked in a loop.
+    pub fn wait(&self, mutex: &Mutex) {
+        let seq = self.seq.load(Ordering::Relaxed);
+        mutex.unlock();
+        futex_wait(&self.seq, seq, None);
+        mutex.lock();
+    }
+
+    /// Wait like wait, or give up at abs_timeout, the time since boot.
+    /// The mutex is locked again even if it times out.
+    /// # Return
+    /// * false - abs_timeout is reached
+    pub fn timed_wait(&self, mutex: &Mutex, abs_timeout: &TimeSpec) -> bool {
+        let seq = self.seq.load(Ordering::Relaxed);
+        mutex.unlock();
+        let woken = wait_until(&self.seq, seq, Some(abs_timeout.to_ms()));
+        mutex.lock();
+        woken
+    }
+}
+
+impl Default for Condvar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,17 +1,44 @@
-use core::arch::asm;
+use core::{arch::asm, sync::atomic::AtomicU32};
 
-use crate::SignalAction;
+use crate::{
+    EpollEvent, FdSet, ITimerSpec, MqAttr, MsgHdr, PollFd, RUsage, ShmidDs, SignalAction, Stat,
+    TaskInfo, TimeSpec, Tms,
+};
 
 // system call numbers
 // IO
+const SYS_EVENTFD2: usize = 19;
+const SYS_EPOLL_CREATE1: usize = 20;
+const SYS_EPOLL_CTL: usize = 21;
+const SYS_EPOLL_PWAIT: usize = 22;
+const SYS_DUP3: usize = 23;
 const SYS_DUP: usize = 24;
+const SYS_FCNTL: usize = 25;
+const SYS_IOCTL: usize = 29;
+const SYS_MKNODAT: usize = 33;
+const SYS_MKDIRAT: usize = 34;
+const SYS_UNLINKAT: usize = 35;
+const SYS_UMOUNT2: usize = 39;
+const SYS_MOUNT: usize = 40;
+const SYS_TRUNCATE: usize = 45;
+const SYS_FTRUNCATE: usize = 46;
 const SYS_OPEN: usize = 56;
 const SYS_CLOSE: usize = 57;
 const SYS_PIPE: usize = 59;
+const SYS_GETDENTS64: usize = 61;
 const SYS_READ: usize = 63;
 const SYS_WRITE: usize = 64;
+const SYS_PSELECT6: usize = 72;
+const SYS_PPOLL: usize = 73;
+const SYS_SIGNALFD4: usize = 74;
+const SYS_NEWFSTATAT: usize = 79;
+const SYS_FSTAT: usize = 80;
+const SYS_TIMERFD_CREATE: usize = 85;
+const SYS_TIMERFD_SETTIME: usize = 86;
+const SYS_TIMERFD_GETTIME: usize = 87;
 // Process
 const SYS_EXIT: usize = 93;
+const SYS_FUTEX: usize = 98;
 const SYS_SLEEP: usize = 101;
 const SYS_YIELD: usize = 124;
 // Siganal
@@ -19,14 +46,52 @@ const SYS_KILL: usize = 129;
 const SYS_SIGACTION: usize = 134;
 const SYS_SIGPROCMASK: usize = 135;
 const SYS_SIGRETURN: usize = 139;
+// Priority
+const SYS_SETPRIORITY: usize = 140;
+const SYS_GETPRIORITY: usize = 141;
+// Resource usage
+const SYS_TIMES: usize = 153;
+const SYS_GETRUSAGE: usize = 165;
+const SYS_TASK_INFO: usize = 410;
+// Process group and session
+const SYS_SETPGID: usize = 154;
+const SYS_GETPGID: usize = 155;
+const SYS_GETSID: usize = 156;
+const SYS_SETSID: usize = 157;
 const SYS_GET_TIME: usize = 169;
 // Process
 const SYS_GET_PID: usize = 172;
+// Shared memory
+const SYS_MQ_OPEN: usize = 180;
+const SYS_MQ_UNLINK: usize = 181;
+const SYS_MQ_TIMEDSEND: usize = 182;
+const SYS_MQ_TIMEDRECEIVE: usize = 183;
+const SYS_MQ_GETSETATTR: usize = 185;
+const SYS_SHMGET: usize = 194;
+const SYS_SHMCTL: usize = 195;
+const SYS_SHMAT: usize = 196;
+const SYS_SHMDT: usize = 197;
+// Socket
+const SYS_SOCKET: usize = 198;
+const SYS_SOCKETPAIR: usize = 199;
+const SYS_BIND: usize = 200;
+const SYS_LISTEN: usize = 201;
+const SYS_CONNECT: usize = 203;
+const SYS_GETSOCKNAME: usize = 204;
+const SYS_GETPEERNAME: usize = 205;
+const SYS_SENDTO: usize = 206;
+const SYS_RECVFROM: usize = 207;
+const SYS_SHUTDOWN: usize = 210;
+const SYS_SENDMSG: usize = 211;
+const SYS_RECVMSG: usize = 212;
+const SYS_ACCEPT4: usize = 242;
+// Process
 const SYS_FORK: usize = 220;
 const SYS_EXEC: usize = 221;
 const SYS_WAITPID: usize = 260;
 const SYS_SPAWN: usize = 400;
 // Thread
+const SYS_ENABLE_DEADLOCK_DETECT: usize = 469;
 const SYS_THREAD_CREATE: usize = 1000;
 const SYS_GET_TID: usize = 1001;
 const SYS_WAITTID: usize = 1002;
@@ -34,16 +99,33 @@ const SYS_WAITTID: usize = 1002;
 const SYS_MUTEX_CREATE: usize = 1010;
 const SYS_MUTEX_LOCK: usize = 1011;
 const SYS_MUTEX_UNLOCK: usize = 1012;
+const SYS_MUTEX_TRYLOCK: usize = 1013;
+const SYS_MUTEX_TIMEDLOCK: usize = 1014;
 // Semaphore
 const SYS_SEMAPHORE_CREATE: usize = 1020;
 const SYS_SEMAPHORE_UP: usize = 1021;
 const SYS_SEMAPHORE_DOWN: usize = 1022;
+const SYS_SEMAPHORE_TRYDOWN: usize = 1023;
+const SYS_SEMAPHORE_TIMEDDOWN: usize = 1024;
 // Condvar
 const SYS_CONDVAR_CREATE: usize = 1030;
 const SYS_CONDVAR_SIGNAL: usize = 1031;
 const SYS_CONDVAR_WAIT: usize = 1032;
+const SYS_CONDVAR_BROADCAST: usize = 1033;
+const SYS_CONDVAR_TIMEDWAIT: usize = 1034;
+const SYS_RWLOCK_CREATE: usize = 1040;
+const SYS_RWLOCK_RDLOCK: usize = 1041;
+const SYS_RWLOCK_WRLOCK: usize = 1042;
+const SYS_RWLOCK_UPGRADE: usize = 1043;
+const SYS_RWLOCK_UNLOCK: usize = 1044;
+const SYS_BARRIER_CREATE: usize = 1050;
+const SYS_BARRIER_WAIT: usize = 1051;
 
 fn sys_call(call_id: usize, args: [usize; 3]) -> isize {
+    sys_call6(call_id, [args[0], args[1], args[2], 0, 0, 0])
+}
+
+fn sys_call6(call_id: usize, args: [usize; 6]) -> isize {
     let mut ret: isize;
     unsafe {
         asm! {
@@ -51,6 +133,9 @@ fn sys_call(call_id: usize, args: [usize; 3]) -> isize {
             inlateout("x10") args[0]=>ret,
             in("x11") args[1],
             in("x12") args[2],
+            in("x13") args[3],
+            in("x14") args[4],
+            in("x15") args[5],
             in("x17") call_id,
         }
     }
@@ -61,14 +146,180 @@ fn sys_call(call_id: usize, args: [usize; 3]) -> isize {
 pub fn sys_dup(fd: usize) -> isize {
     sys_call(SYS_DUP, [fd, 0, 0])
 }
+pub fn sys_eventfd2(initval: u32, flags: u32) -> isize {
+    sys_call(SYS_EVENTFD2, [initval as usize, flags as usize, 0])
+}
+pub fn sys_epoll_create1(flags: u32) -> isize {
+    sys_call(SYS_EPOLL_CREATE1, [flags as usize, 0, 0])
+}
+pub fn sys_epoll_ctl(epfd: usize, op: usize, fd: usize, event: &EpollEvent) -> isize {
+    sys_call6(
+        SYS_EPOLL_CTL,
+        [epfd, op, fd, event as *const EpollEvent as usize, 0, 0],
+    )
+}
+pub fn sys_epoll_pwait(epfd: usize, events: &mut [EpollEvent], timeout: isize) -> isize {
+    sys_call6(
+        SYS_EPOLL_PWAIT,
+        [
+            epfd,
+            events.as_mut_ptr() as usize,
+            events.len(),
+            timeout as usize,
+            0,
+            0,
+        ],
+    )
+}
+pub fn sys_dup3(old_fd: usize, new_fd: usize, flags: u32) -> isize {
+    sys_call(SYS_DUP3, [old_fd, new_fd, flags as usize])
+}
+pub fn sys_fcntl(fd: usize, cmd: usize, arg: usize) -> isize {
+    sys_call(SYS_FCNTL, [fd, cmd, arg])
+}
+pub fn sys_ioctl(fd: usize, cmd: usize, arg: usize) -> isize {
+    sys_call(SYS_IOCTL, [fd, cmd, arg])
+}
+pub fn sys_mknodat(dirfd: isize, path: &str, mode: u32, dev: usize) -> isize {
+    sys_call6(
+        SYS_MKNODAT,
+        [dirfd as usize, path.as_ptr() as usize, mode as usize, dev, 0, 0],
+    )
+}
+pub fn sys_mkdirat(dirfd: isize, path: &str, mode: u32) -> isize {
+    sys_call(
+        SYS_MKDIRAT,
+        [dirfd as usize, path.as_ptr() as usize, mode as usize],
+    )
+}
+pub fn sys_unlinkat(dirfd: isize, path: &str, flags: u32) -> isize {
+    sys_call(
+        SYS_UNLINKAT,
+        [dirfd as usize, path.as_ptr() as usize, flags as usize],
+    )
+}
+pub fn sys_umount2(target: &str, flags: u32) -> isize {
+    sys_call(SYS_UMOUNT2, [target.as_ptr() as usize, flags as usize, 0])
+}
+pub fn sys_mount(source: &str, target: &str, fstype: &str, flags: usize, data: &str) -> isize {
+    sys_call6(
+        SYS_MOUNT,
+        [
+            source.as_ptr() as usize,
+            target.as_ptr() as usize,
+            fstype.as_ptr() as usize,
+            flags,
+            data.as_ptr() as usize,
+            0,
+        ],
+    )
+}
+pub fn sys_truncate(path: &str, length: usize) -> isize {
+    sys_call(SYS_TRUNCATE, [path.as_ptr() as usize, length, 0])
+}
+pub fn sys_ftruncate(fd: usize, length: usize) -> isize {
+    sys_call(SYS_FTRUNCATE, [fd, length, 0])
+}
+pub fn sys_newfstatat(dirfd: isize, path: &str, stat: &mut Stat, flags: u32) -> isize {
+    sys_call6(
+        SYS_NEWFSTATAT,
+        [
+            dirfd as usize,
+            path.as_ptr() as usize,
+            stat as *mut Stat as usize,
+            flags as usize,
+            0,
+            0,
+        ],
+    )
+}
+pub fn sys_fstat(fd: usize, stat: &mut Stat) -> isize {
+    sys_call(SYS_FSTAT, [fd, stat as *mut Stat as usize, 0])
+}
 pub fn sys_open(path: &str, flags: u32) -> isize {
     sys_call(SYS_OPEN, [path.as_ptr() as usize, flags as usize, 0])
 }
 pub fn sys_close(fd: usize) -> isize {
     sys_call(SYS_CLOSE, [fd, 0, 0])
 }
-pub fn sys_pipe(pipe: &mut [usize]) -> isize {
-    sys_call(SYS_PIPE, [pipe.as_mut_ptr() as usize, 0, 0])
+pub fn sys_pipe(pipe: &mut [usize], flags: u32) -> isize {
+    sys_call(SYS_PIPE, [pipe.as_mut_ptr() as usize, flags as usize, 0])
+}
+pub fn sys_getdents64(fd: usize, buffer: &mut [u8]) -> isize {
+    sys_call(
+        SYS_GETDENTS64,
+        [fd, buffer.as_mut_ptr() as usize, buffer.len()],
+    )
+}
+pub fn sys_ppoll(fds: &mut [PollFd], timeout: Option<&TimeSpec>) -> isize {
+    sys_call6(
+        SYS_PPOLL,
+        [
+            fds.as_mut_ptr() as usize,
+            fds.len(),
+            timeout.map_or(0, |timeout| timeout as *const TimeSpec as usize),
+            0,
+            0,
+            0,
+        ],
+    )
+}
+pub fn sys_pselect6(
+    nfds: usize,
+    read_fds: Option<&mut FdSet>,
+    write_fds: Option<&mut FdSet>,
+    except_fds: Option<&mut FdSet>,
+    timeout: Option<&TimeSpec>,
+) -> isize {
+    let set_ptr = |set: Option<&mut FdSet>| set.map_or(0, |set| set as *mut FdSet as usize);
+    sys_call6(
+        SYS_PSELECT6,
+        [
+            nfds,
+            set_ptr(read_fds),
+            set_ptr(write_fds),
+            set_ptr(except_fds),
+            timeout.map_or(0, |timeout| timeout as *const TimeSpec as usize),
+            0,
+        ],
+    )
+}
+pub fn sys_signalfd4(fd: isize, mask: u64, flags: u32) -> isize {
+    sys_call6(
+        SYS_SIGNALFD4,
+        [
+            fd as usize,
+            &mask as *const u64 as usize,
+            core::mem::size_of::<u64>(),
+            flags as usize,
+            0,
+            0,
+        ],
+    )
+}
+pub fn sys_timerfd_create(clockid: usize, flags: u32) -> isize {
+    sys_call(SYS_TIMERFD_CREATE, [clockid, flags as usize, 0])
+}
+pub fn sys_timerfd_settime(
+    fd: usize,
+    flags: u32,
+    new_value: &ITimerSpec,
+    old_value: Option<&mut ITimerSpec>,
+) -> isize {
+    sys_call6(
+        SYS_TIMERFD_SETTIME,
+        [
+            fd,
+            flags as usize,
+            new_value as *const ITimerSpec as usize,
+            old_value.map_or(0, |old| old as *mut ITimerSpec as usize),
+            0,
+            0,
+        ],
+    )
+}
+pub fn sys_timerfd_gettime(fd: usize, curr_value: &mut ITimerSpec) -> isize {
+    sys_call(SYS_TIMERFD_GETTIME, [fd, curr_value as *mut ITimerSpec as usize, 0])
 }
 pub fn sys_read(fd: usize, buffer: &mut [u8]) -> isize {
     sys_call(SYS_READ, [fd, buffer.as_mut_ptr() as usize, buffer.len()])
@@ -86,8 +337,8 @@ pub fn sys_sleep(ms: usize) -> isize {
 pub fn sys_yield() -> isize {
     sys_call(SYS_YIELD, [0; 3])
 }
-pub fn sys_kill(pid: usize, signum: u32) -> isize {
-    sys_call(SYS_KILL, [pid, signum as usize, 0])
+pub fn sys_kill(pid: isize, signum: u32) -> isize {
+    sys_call(SYS_KILL, [pid as usize, signum as usize, 0])
 }
 pub fn sys_sigaction(
     signum: u32,
@@ -105,12 +356,182 @@ pub fn sys_sigprocmask(mask: u32) -> isize {
 pub fn sys_sigreturn() -> isize {
     sys_call(SYS_SIGRETURN, [0; 3])
 }
+pub fn sys_setpriority(which: usize, who: usize, nice: isize) -> isize {
+    sys_call(SYS_SETPRIORITY, [which, who, nice as usize])
+}
+pub fn sys_getpriority(which: usize, who: usize) -> isize {
+    sys_call(SYS_GETPRIORITY, [which, who, 0])
+}
+pub fn sys_times(tms: *mut Tms) -> isize {
+    sys_call(SYS_TIMES, [tms as usize, 0, 0])
+}
+pub fn sys_getrusage(who: isize, usage: *mut RUsage) -> isize {
+    sys_call(SYS_GETRUSAGE, [who as usize, usage as usize, 0])
+}
+pub fn sys_task_info(info: *mut TaskInfo) -> isize {
+    sys_call(SYS_TASK_INFO, [info as usize, 0, 0])
+}
 pub fn sys_get_time() -> isize {
     sys_call(SYS_GET_TIME, [0; 3])
 }
 pub fn sys_get_pid() -> isize {
     sys_call(SYS_GET_PID, [0; 3])
 }
+pub fn sys_mq_open(name: &str, flags: u32, mode: u32, attr: Option<&MqAttr>) -> isize {
+    sys_call6(
+        SYS_MQ_OPEN,
+        [
+            name.as_ptr() as usize,
+            flags as usize,
+            mode as usize,
+            attr.map_or(0, |attr| attr as *const MqAttr as usize),
+            0,
+            0,
+        ],
+    )
+}
+pub fn sys_mq_unlink(name: &str) -> isize {
+    sys_call(SYS_MQ_UNLINK, [name.as_ptr() as usize, 0, 0])
+}
+pub fn sys_mq_timedsend(
+    mqdes: usize,
+    msg: &[u8],
+    prio: u32,
+    abs_timeout: Option<&TimeSpec>,
+) -> isize {
+    sys_call6(
+        SYS_MQ_TIMEDSEND,
+        [
+            mqdes,
+            msg.as_ptr() as usize,
+            msg.len(),
+            prio as usize,
+            abs_timeout.map_or(0, |timeout| timeout as *const TimeSpec as usize),
+            0,
+        ],
+    )
+}
+pub fn sys_mq_timedreceive(
+    mqdes: usize,
+    buf: &mut [u8],
+    prio: Option<&mut u32>,
+    abs_timeout: Option<&TimeSpec>,
+) -> isize {
+    sys_call6(
+        SYS_MQ_TIMEDRECEIVE,
+        [
+            mqdes,
+            buf.as_mut_ptr() as usize,
+            buf.len(),
+            prio.map_or(0, |prio| prio as *mut u32 as usize),
+            abs_timeout.map_or(0, |timeout| timeout as *const TimeSpec as usize),
+            0,
+        ],
+    )
+}
+pub fn sys_mq_getsetattr(
+    mqdes: usize,
+    new: Option<&MqAttr>,
+    old: Option<&mut MqAttr>,
+) -> isize {
+    sys_call(
+        SYS_MQ_GETSETATTR,
+        [
+            mqdes,
+            new.map_or(0, |new| new as *const MqAttr as usize),
+            old.map_or(0, |old| old as *mut MqAttr as usize),
+        ],
+    )
+}
+pub fn sys_shmget(key: i32, size: usize, flags: usize) -> isize {
+    sys_call(SYS_SHMGET, [key as usize, size, flags])
+}
+pub fn sys_shmctl(shmid: usize, cmd: usize, buf: &mut ShmidDs) -> isize {
+    sys_call(SYS_SHMCTL, [shmid, cmd, buf as *mut ShmidDs as usize])
+}
+pub fn sys_shmat(shmid: usize, addr: usize, flags: usize) -> isize {
+    sys_call(SYS_SHMAT, [shmid, addr, flags])
+}
+pub fn sys_shmdt(addr: usize) -> isize {
+    sys_call(SYS_SHMDT, [addr, 0, 0])
+}
+pub fn sys_socket(domain: usize, type_: usize, protocol: usize) -> isize {
+    sys_call(SYS_SOCKET, [domain, type_, protocol])
+}
+pub fn sys_socketpair(domain: usize, type_: usize, protocol: usize, sv: &mut [i32; 2]) -> isize {
+    sys_call6(
+        SYS_SOCKETPAIR,
+        [domain, type_, protocol, sv.as_mut_ptr() as usize, 0, 0],
+    )
+}
+pub fn sys_bind(fd: usize, addr: *const u8, addrlen: u32) -> isize {
+    sys_call(SYS_BIND, [fd, addr as usize, addrlen as usize])
+}
+pub fn sys_listen(fd: usize, backlog: usize) -> isize {
+    sys_call(SYS_LISTEN, [fd, backlog, 0])
+}
+pub fn sys_accept4(fd: usize, addr: *mut u8, addrlen: *mut u32, flags: usize) -> isize {
+    sys_call6(
+        SYS_ACCEPT4,
+        [fd, addr as usize, addrlen as usize, flags, 0, 0],
+    )
+}
+pub fn sys_connect(fd: usize, addr: *const u8, addrlen: u32) -> isize {
+    sys_call(SYS_CONNECT, [fd, addr as usize, addrlen as usize])
+}
+pub fn sys_getsockname(fd: usize, addr: *mut u8, addrlen: *mut u32) -> isize {
+    sys_call(SYS_GETSOCKNAME, [fd, addr as usize, addrlen as usize])
+}
+pub fn sys_getpeername(fd: usize, addr: *mut u8, addrlen: *mut u32) -> isize {
+    sys_call(SYS_GETPEERNAME, [fd, addr as usize, addrlen as usize])
+}
+pub fn sys_sendto(
+    fd: usize,
+    buf: &[u8],
+    flags: u32,
+    addr: *const u8,
+    addrlen: u32,
+) -> isize {
+    sys_call6(
+        SYS_SENDTO,
+        [
+            fd,
+            buf.as_ptr() as usize,
+            buf.len(),
+            flags as usize,
+            addr as usize,
+            addrlen as usize,
+        ],
+    )
+}
+pub fn sys_recvfrom(
+    fd: usize,
+    buf: &mut [u8],
+    flags: u32,
+    addr: *mut u8,
+    addrlen: *mut u32,
+) -> isize {
+    sys_call6(
+        SYS_RECVFROM,
+        [
+            fd,
+            buf.as_mut_ptr() as usize,
+            buf.len(),
+            flags as usize,
+            addr as usize,
+            addrlen as usize,
+        ],
+    )
+}
+pub fn sys_shutdown(fd: usize, how: usize) -> isize {
+    sys_call(SYS_SHUTDOWN, [fd, how, 0])
+}
+pub fn sys_sendmsg(fd: usize, msg: *const MsgHdr, flags: u32) -> isize {
+    sys_call(SYS_SENDMSG, [fd, msg as usize, flags as usize])
+}
+pub fn sys_recvmsg(fd: usize, msg: *mut MsgHdr, flags: u32) -> isize {
+    sys_call(SYS_RECVMSG, [fd, msg as usize, flags as usize])
+}
 pub fn sys_fork() -> isize {
     sys_call(SYS_FORK, [0; 3])
 }
@@ -120,8 +541,23 @@ pub fn sys_exec(path: &str, args: &[*const u8]) -> isize {
         [path.as_ptr() as usize, args.as_ptr() as usize, 0],
     )
 }
-pub fn sys_waitpid(pid: isize, exit_code: *mut i32) -> isize {
-    sys_call(SYS_WAITPID, [pid as usize, exit_code as usize, 0])
+pub fn sys_waitpid(pid: isize, exit_code: *mut i32, options: u32) -> isize {
+    sys_call(
+        SYS_WAITPID,
+        [pid as usize, exit_code as usize, options as usize],
+    )
+}
+pub fn sys_setpgid(pid: usize, pgid: usize) -> isize {
+    sys_call(SYS_SETPGID, [pid, pgid, 0])
+}
+pub fn sys_getpgid(pid: usize) -> isize {
+    sys_call(SYS_GETPGID, [pid, 0, 0])
+}
+pub fn sys_getsid(pid: usize) -> isize {
+    sys_call(SYS_GETSID, [pid, 0, 0])
+}
+pub fn sys_setsid() -> isize {
+    sys_call(SYS_SETSID, [0; 3])
 }
 pub fn sys_spawn(path: &str) -> isize {
     sys_call(SYS_SPAWN, [path.as_ptr() as usize, 0, 0])
@@ -135,8 +571,31 @@ pub fn sys_get_tid() -> isize {
 pub fn sys_waittid(tid: usize) -> isize {
     sys_call(SYS_WAITTID, [tid as usize, 0, 0])
 }
-pub fn sys_mutex_create(blocking: bool) -> isize {
-    sys_call(SYS_MUTEX_CREATE, [blocking as usize, 0, 0])
+pub fn sys_futex(
+    futex: &AtomicU32,
+    op: usize,
+    val: u32,
+    timeout: usize,
+    futex2: Option<&AtomicU32>,
+    val3: u32,
+) -> isize {
+    sys_call6(
+        SYS_FUTEX,
+        [
+            futex as *const AtomicU32 as usize,
+            op,
+            val as usize,
+            timeout,
+            futex2.map_or(0, |futex2| futex2 as *const AtomicU32 as usize),
+            val3 as usize,
+        ],
+    )
+}
+pub fn sys_enable_deadlock_detect(enabled: usize) -> isize {
+    sys_call(SYS_ENABLE_DEADLOCK_DETECT, [enabled, 0, 0])
+}
+pub fn sys_mutex_create(flags: usize) -> isize {
+    sys_call(SYS_MUTEX_CREATE, [flags, 0, 0])
 }
 pub fn sys_mutex_lock(mutex_id: usize) -> isize {
     sys_call(SYS_MUTEX_LOCK, [mutex_id, 0, 0])
@@ -144,6 +603,15 @@ pub fn sys_mutex_lock(mutex_id: usize) -> isize {
 pub fn sys_mutex_unlock(mutex_id: usize) -> isize {
     sys_call(SYS_MUTEX_UNLOCK, [mutex_id, 0, 0])
 }
+pub fn sys_mutex_trylock(mutex_id: usize) -> isize {
+    sys_call(SYS_MUTEX_TRYLOCK, [mutex_id, 0, 0])
+}
+pub fn sys_mutex_timedlock(mutex_id: usize, abs_timeout: &TimeSpec) -> isize {
+    sys_call(
+        SYS_MUTEX_TIMEDLOCK,
+        [mutex_id, abs_timeout as *const TimeSpec as usize, 0],
+    )
+}
 pub fn sys_semaphore_create(res_count: usize) -> isize {
     sys_call(SYS_SEMAPHORE_CREATE, [res_count, 0, 0])
 }
@@ -153,6 +621,15 @@ pub fn sys_semaphore_up(semaphore_id: usize) -> isize {
 pub fn sys_semaphore_down(semaphore_id: usize) -> isize {
     sys_call(SYS_SEMAPHORE_DOWN, [semaphore_id, 0, 0])
 }
+pub fn sys_semaphore_trydown(semaphore_id: usize) -> isize {
+    sys_call(SYS_SEMAPHORE_TRYDOWN, [semaphore_id, 0, 0])
+}
+pub fn sys_semaphore_timeddown(semaphore_id: usize, abs_timeout: &TimeSpec) -> isize {
+    sys_call(
+        SYS_SEMAPHORE_TIMEDDOWN,
+        [semaphore_id, abs_timeout as *const TimeSpec as usize, 0],
+    )
+}
 pub fn sys_condvar_create() -> isize {
     sys_call(SYS_CONDVAR_CREATE, [0; 3])
 }
@@ -162,3 +639,33 @@ pub fn sys_condvar_signal(condvar_id: usize) -> isize {
 pub fn sys_condvar_wait(condvar_id: usize, mutex_id: usize) -> isize {
     sys_call(SYS_CONDVAR_WAIT, [condvar_id, mutex_id, 0])
 }
+pub fn sys_condvar_broadcast(condvar_id: usize) -> isize {
+    sys_call(SYS_CONDVAR_BROADCAST, [condvar_id, 0, 0])
+}
+pub fn sys_condvar_timedwait(condvar_id: usize, mutex_id: usize, abs_timeout: &TimeSpec) -> isize {
+    sys_call(
+        SYS_CONDVAR_TIMEDWAIT,
+        [condvar_id, mutex_id, abs_timeout as *const TimeSpec as usize],
+    )
+}
+pub fn sys_rwlock_create() -> isize {
+    sys_call(SYS_RWLOCK_CREATE, [0; 3])
+}
+pub fn sys_rwlock_rdlock(rwlock_id: usize) -> isize {
+    sys_call(SYS_RWLOCK_RDLOCK, [rwlock_id, 0, 0])
+}
+pub fn sys_rwlock_wrlock(rwlock_id: usize) -> isize {
+    sys_call(SYS_RWLOCK_WRLOCK, [rwlock_id, 0, 0])
+}
+pub fn sys_rwlock_upgrade(rwlock_id: usize) -> isize {
+    sys_call(SYS_RWLOCK_UPGRADE, [rwlock_id, 0, 0])
+}
+pub fn sys_rwlock_unlock(rwlock_id: usize) -> isize {
+    sys_call(SYS_RWLOCK_UNLOCK, [rwlock_id, 0, 0])
+}
+pub fn sys_barrier_create(count: usize) -> isize {
+    sys_call(SYS_BARRIER_CREATE, [count, 0, 0])
+}
+pub fn sys_barrier_wait(barrier_id: usize) -> isize {
+    sys_call(SYS_BARRIER_WAIT, [barrier_id, 0, 0])
+}